@@ -3,8 +3,63 @@ import { get } from "svelte/store";
 import axios from "axios";
 import type { AxiosResponse } from "axios";
 
-export function login(email: string, password: string): Promise<AxiosResponse> {
-  return axios.post("http://localhost:8080/api/auth/login", { email, password });
+export function login(email: string, password: string, code?: string): Promise<AxiosResponse> {
+  return axios.post("http://localhost:8080/api/auth/login", { email, password, code });
+}
+
+export function requestPasswordReset(email: string): Promise<AxiosResponse> {
+  return axios.post("http://localhost:8080/api/auth/password_reset", { email });
+}
+
+export function confirmPasswordReset(token: string, newPassword: string): Promise<AxiosResponse> {
+  return axios.post(`http://localhost:8080/api/auth/password_reset/${encodeURIComponent(token)}`, {
+    new_password: newPassword,
+  });
+}
+
+// Returns new tokens, since changing the password logs out every session
+export function changePassword(currentPassword: string, newPassword: string): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post("http://localhost:8080/api/auth/change_password", {
+    current_password: currentPassword,
+    new_password: newPassword,
+  }, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// Who an invite link is for; no login needed
+export function getInvite(token: string): Promise<AxiosResponse> {
+  return axios.get(`http://localhost:8080/api/invites/${encodeURIComponent(token)}`);
+}
+
+export function acceptInvite(token: string, password: string): Promise<AxiosResponse> {
+  return axios.post(`http://localhost:8080/api/invites/${encodeURIComponent(token)}`, {
+    password,
+  });
+}
+
+// The registration mode: closed, invite or open_with_approval; no login
+// needed
+export function getRegistration(): Promise<AxiosResponse> {
+  return axios.get("http://localhost:8080/api/registration");
+}
+
+// Sign up for an account an admin has to approve before it can log in
+export function signUp(email: string, password: string): Promise<AxiosResponse> {
+  return axios.post("http://localhost:8080/api/registration", { email, password });
+}
+
+// Whether the instance is in maintenance mode; no login needed
+export function getStatus(): Promise<AxiosResponse> {
+  return axios.get("http://localhost:8080/api/status");
+}
+
+// Server version and how to reach the admin; no login needed
+export function getInstanceInfo(): Promise<AxiosResponse> {
+  return axios.get("http://localhost:8080/api/status/instance");
 }
 
 export function logout(): Promise<AxiosResponse> {
@@ -15,3 +70,419 @@ export function logout(): Promise<AxiosResponse> {
     }
   });
 }
+
+// The devices the current user is logged in on
+export function getSessions(): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get("http://localhost:8080/api/sessions", {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function deleteSession(sessionId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.delete(`http://localhost:8080/api/sessions/${sessionId}`, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// Logs out every device, this one included
+export function deleteAllSessions(): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.delete("http://localhost:8080/api/sessions", {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function sendNow(userId: number, subscriptionId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post(`http://localhost:8080/api/users/${userId}/subscriptions/${subscriptionId}/send-now`, {}, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// Change a subscription's settings, like its delivery method or max items
+export function updateSubscription(userId: number, subscriptionId: number, changes: object): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.patch(`http://localhost:8080/api/users/${userId}/subscriptions/${subscriptionId}`, changes, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// Renders the subscription's next delivery with unsaved changes (the same
+// fields as a PATCH), so an edit form can show them before saving
+export function previewSubscription(userId: number, subscriptionId: number, changes: object): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post(`http://localhost:8080/api/users/${userId}/subscriptions/${subscriptionId}/preview`, changes, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function getDeliveries(userId: number, subscriptionId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get(`http://localhost:8080/api/users/${userId}/subscriptions/${subscriptionId}/deliveries`, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// The user's id is the `sub` claim of their access token
+export function currentUserId(): number | undefined {
+  const token = get(user).token;
+  if (!token) {
+    return undefined;
+  }
+  return JSON.parse(atob(token.split(".")[1])).sub;
+}
+
+// Set after an admin resets the password; until it's changed, every other
+// request with the token is turned away
+export function mustChangePassword(token = get(user).token): boolean {
+  if (!token) {
+    return false;
+  }
+  return JSON.parse(atob(token.split(".")[1])).must_change_password === true;
+}
+
+// Whether the token's `role` claim, a comma-separated list, includes admin
+export function isAdmin(token = get(user).token): boolean {
+  if (!token) {
+    return false;
+  }
+  return JSON.parse(atob(token.split(".")[1])).role.split(",").includes("admin");
+}
+
+export function getOnboarding(userId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get(`http://localhost:8080/api/users/${userId}/onboarding`, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function dismissOnboarding(userId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post(`http://localhost:8080/api/users/${userId}/onboarding/dismiss`, {}, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function sendTestEmail(userId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post(`http://localhost:8080/api/users/${userId}/send-test`, {}, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function getDiagnostics(userId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get(`http://localhost:8080/api/users/${userId}/diagnostics`, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// 304s resolve rather than reject, so callers can keep what they have
+function getIfChanged(url: string, etag?: string): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get(url, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+      ...(etag ? { "If-None-Match": etag } : {}),
+    },
+    validateStatus: (status) => status === 200 || status === 304,
+  });
+}
+
+// A subscription's frequency as shown in lists: the name, or what the
+// frequencies with values say
+export function frequencyLabel(frequency: any): string {
+  if (frequency.weekly_on) {
+    return `weekly on ${frequency.weekly_on}`;
+  }
+  if (frequency.every_hours) {
+    return `every ${frequency.every_hours} hours`;
+  }
+  return frequency.cron ?? frequency;
+}
+
+export function getSubscriptions(userId: number, etag?: string): Promise<AxiosResponse> {
+  return getIfChanged(`http://localhost:8080/api/users/${userId}/subscriptions`, etag);
+}
+
+// A hash of the subscription list, cheap to poll for changes
+export function getSubscriptionsState(userId: number, etag?: string): Promise<AxiosResponse> {
+  return getIfChanged(`http://localhost:8080/api/users/${userId}/subscriptions/state`, etag);
+}
+
+// How the dashboard sorts the subscriptions: manual, name, last_item or unread
+export function getSubscriptionSort(userId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get(`http://localhost:8080/api/users/${userId}/subscriptions/sort`, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function setSubscriptionSort(userId: number, sort: string): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.put(`http://localhost:8080/api/users/${userId}/subscriptions/sort`, { sort }, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// Saves the order the subscriptions were dragged into, and sorts by it
+export function setSubscriptionOrder(userId: number, ids: number[]): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.put(`http://localhost:8080/api/users/${userId}/subscriptions/order`, { ids }, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// `schedule` is the query's frequency, plus weekday or every_hours
+export function importOpml(userId: number, opml: string, schedule: object): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post(`http://localhost:8080/api/users/${userId}/subscriptions/import`, opml, {
+    params: schedule,
+    headers: {
+      Authorization: `Bearer ${token}`,
+      'Content-Type': 'text/xml',
+    }
+  });
+}
+
+export function getJob(userId: number, jobId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get(`http://localhost:8080/api/users/${userId}/jobs/${jobId}`, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// Replaces the subscription's tags, creating any the user doesn't have
+export function setSubscriptionTags(userId: number, subscriptionId: number, tags: string[]): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.put(`http://localhost:8080/api/users/${userId}/subscriptions/${subscriptionId}/tags`, { tags }, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function getTags(userId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get(`http://localhost:8080/api/users/${userId}/tags`, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// Rename a tag, or send its subscriptions as one combined digest
+export function updateTag(userId: number, tagId: number, changes: object): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.patch(`http://localhost:8080/api/users/${userId}/tags/${tagId}`, changes, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// A page of the newest items across the user's subscriptions
+export function getReaderPage(page: number, unread: boolean, starred: boolean): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get("http://localhost:8080/api/feed_items/reader", {
+    params: { page, unread, starred },
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function getReaderItem(itemId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get(`http://localhost:8080/api/feed_items/${itemId}`, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function markRead(itemId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.put(`http://localhost:8080/api/feed_items/${itemId}/read`, {}, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function markUnread(itemId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.delete(`http://localhost:8080/api/feed_items/${itemId}/read`, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function starItem(itemId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.put(`http://localhost:8080/api/feed_items/${itemId}/star`, {}, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function unstarItem(itemId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.delete(`http://localhost:8080/api/feed_items/${itemId}/star`, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// Admin only, each with its subscription count, last login and delivery
+export function getUsers(): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get("http://localhost:8080/api/users", {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function createUser(email: string, password: string): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post("http://localhost:8080/api/users", { email, password }, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// Admin only; the response's `path` is the registration link, shown once
+export function createInvite(email: string): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post("http://localhost:8080/api/invites", { email }, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// `changes` has the fields to change, like `role` or `is_active`
+export function updateUser(userId: number, changes: object): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.patch(`http://localhost:8080/api/users/${userId}`, changes, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function approveUser(userId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post(`http://localhost:8080/api/admin/users/${userId}/approve`, {}, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function rejectUser(userId: number): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post(`http://localhost:8080/api/admin/users/${userId}/reject`, {}, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function getRegistrationMode(): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get("http://localhost:8080/api/admin/registration", {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+export function setRegistrationMode(registration_mode: string): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.put("http://localhost:8080/api/admin/registration", { registration_mode }, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// `mode` is temporary_password to get the new password back, or email to
+// send it to the user
+export function forcePasswordReset(userId: number, mode: string): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.post(`http://localhost:8080/api/admin/users/${userId}/force-reset`, { mode }, {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// File names of the digest template versions that can be compared
+export function getDigestTemplates(): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get("http://localhost:8080/api/admin/digest-templates", {
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// A subscription's latest items rendered with templates `a` and `b`
+export function getDigestPreview(subscriptionId: number, a: string, b: string): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get("http://localhost:8080/api/admin/digest-preview", {
+    params: { subscription_id: subscriptionId, a, b },
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
+
+// A page of the audit log, filtered by `user_id`, `action`, `after` and `before`
+export function getAuditLog(params: Record<string, string | number>): Promise<AxiosResponse> {
+  const token = get(user).token;
+  return axios.get("http://localhost:8080/api/admin/audit", {
+    params,
+    headers: {
+      Authorization: `Bearer ${token}`,
+    }
+  });
+}
@@ -1,8 +1,26 @@
-mod auth;
+mod admin;
+pub(crate) mod auth;
+mod body_log;
+mod config;
+mod email_links;
+mod etag;
 mod feed_items;
 mod feeds;
+mod invites;
+mod registration;
+mod searches;
+mod sessions;
+mod shares;
+mod status;
 mod subscriptions;
+mod tags;
+mod templates;
+mod tokens;
+mod two_factor;
 mod users;
 
 mod routes;
+pub use self::email_links::page_routes as email_link_routes;
 pub use self::routes::routes;
+pub use self::shares::page_routes as share_page_routes;
+pub use self::status::well_known_routes;
@@ -0,0 +1,9 @@
+mod access;
+mod handlers;
+mod routes;
+mod types;
+
+pub(super) use self::access::{
+    check_request as check_access, check_request_for as check_access_for,
+};
+pub use self::routes::routes;
@@ -0,0 +1,106 @@
+use actix_web::{
+    dev::ServiceRequest,
+    error::{ErrorForbidden, ErrorInternalServerError},
+    web, HttpRequest,
+};
+
+use crate::{
+    claims::Claims,
+    models::{admin_access::AdminAccess, ids::UserId},
+    security::client_ip::real_ip,
+    DbPool,
+};
+
+/// Turn away requests to the admin API from addresses the access rules
+/// don't permit, before the token is even looked at
+pub fn check(req: &ServiceRequest) -> Result<(), actix_web::Error> {
+    check_request(req.request())
+}
+
+/// Turn away an admin-only request from an address the access rules don't
+/// permit. For admin actions outside the admin API, like managing users or
+/// editing feeds, where the same routes serve everyone else too. Fails
+/// closed if the rules can't be loaded.
+pub fn check_request(req: &HttpRequest) -> Result<(), actix_web::Error> {
+    let access = match req
+        .app_data::<web::Data<DbPool>>()
+        .and_then(|pool| pool.get().ok())
+    {
+        Some(mut conn) => AdminAccess::load(&mut conn),
+        None => return Err(ErrorInternalServerError("Error connecting to database")),
+    };
+    let client = real_ip(req);
+    if access.permits(client) {
+        return Ok(());
+    }
+    log::warn!(
+        "Admin request to {} from {:?} blocked by access rules",
+        req.path(),
+        client
+    );
+    Err(ErrorForbidden("Forbidden"))
+}
+
+/// For routes that serve both a user and admins acting on their account:
+/// the admin, and only the admin, has to pass the access rules
+pub fn check_request_for(
+    req: &HttpRequest,
+    claims: &Claims,
+    user_id: UserId,
+) -> Result<(), actix_web::Error> {
+    if user_id == claims.sub {
+        return Ok(());
+    }
+    check_request(req)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::role::Role;
+    use crate::test_helpers::test_helpers::get_test_db_pool;
+    use actix_web::{http::StatusCode, test::TestRequest};
+
+    #[test]
+    fn test_check_request() {
+        let pool = get_test_db_pool();
+        let mut conn = pool.get().unwrap();
+        AdminAccess {
+            allow: vec!["10.0.0.0/8".to_string()],
+            ..Default::default()
+        }
+        .save(&mut conn)
+        .unwrap();
+        drop(conn);
+
+        let request = |peer: &str| {
+            TestRequest::patch()
+                .uri("/api/users/2")
+                .peer_addr(peer.parse().unwrap())
+                .app_data(web::Data::new(pool.clone()))
+                .to_http_request()
+        };
+        assert!(check_request(&request("10.1.2.3:4000")).is_ok());
+        let denied = check_request(&request("203.0.113.9:4000")).unwrap_err();
+        assert_eq!(
+            denied.as_response_error().status_code(),
+            StatusCode::FORBIDDEN
+        );
+
+        // an admin's own account is theirs to manage from anywhere
+        let claims = Claims {
+            sub: UserId(2),
+            role: Role::Admin.into(),
+            exp: 0,
+            email: "admin@example.com".to_string(),
+            sid: None,
+            must_change_password: false,
+        };
+        assert!(check_request_for(&request("203.0.113.9:4000"), &claims, UserId(2)).is_ok());
+        assert!(check_request_for(&request("203.0.113.9:4000"), &claims, UserId(3)).is_err());
+
+        // no database, no way to tell
+        let no_pool = TestRequest::default().to_http_request();
+        assert!(check_request(&no_pool).is_err());
+    }
+}
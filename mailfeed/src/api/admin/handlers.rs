@@ -0,0 +1,1446 @@
+use super::types::{
+    AuditPage, AuditQuery, DigestPreview, DigestPreviewQuery, ForceResetRequest,
+    ForceResetResponse, RenderedDigest, ResetMode, RqChannel, RqJobId, RqWebhookId, SmtpStatus,
+    WebhookCreate, DIGEST_PREVIEW_ITEMS,
+};
+use crate::{
+    api::users::{RqUserId, UsageQuery},
+    claims::Claims,
+    db::DbConnection,
+    models::{
+        admin_access::AdminAccess,
+        admin_contact::AdminContact,
+        audit_log::{AuditAction, AuditEntry, NewAuditEntry},
+        body_logging::BodyLogging,
+        db_stats::DbStats,
+        feed::Feed,
+        feed_item::FeedItem,
+        ids::{UserId, WebhookId},
+        ingest_limits::IngestLimits,
+        maintenance_mode::MaintenanceMode,
+        max_item_age::MaxItemAge,
+        mqtt_settings::MqttSettings,
+        onboarding::Onboarding,
+        password_reset_token::PasswordResetToken,
+        quotas::Quotas,
+        registration::Registration,
+        retention::Retention,
+        retry_policy::{Channel, RetryPolicy},
+        smtp_verification::SmtpVerification,
+        subscription::Subscription,
+        usage::InstanceUsage,
+        user::{User, UserQuery, UserTableError},
+        webhook::{NewWebhook, PartialWebhook, Webhook},
+    },
+    security::{client_ip::real_ip, validation::Validate},
+    tasks::{
+        db_maintenance::types::MaintenanceStatus,
+        email_sender::{
+            digest_templates::DigestTemplate, onboarding, password_reset::send_forced_reset,
+            runner::preview_templates, smtp_verification::current_fingerprint,
+        },
+        feed_monitor::refresh::RefreshJobs,
+        jobs::Jobs,
+        telegram::{BotStatus, TelegramBot},
+        webhooks::{Event, Webhooks},
+    },
+    RqDbPool,
+};
+use actix_web::{
+    delete, get, patch, post, put, web, HttpRequest, HttpResponse, Responder, ResponseError,
+};
+use chrono::Utc;
+
+const TEMP_PASSWORD_LENGTH: usize = 16;
+const WEBHOOK_SECRET_LENGTH: usize = 32;
+
+#[post("/users/{user_id}/force-reset")]
+pub async fn force_password_reset(
+    pool: RqDbPool,
+    path: RqUserId,
+    reset_req: web::Json<ForceResetRequest>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to force password reset by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let user = match User::get(&mut conn, UserQuery::Id(user_id)) {
+        Some(user) => user,
+        None => return HttpResponse::NotFound().body("User not found"),
+    };
+
+    // issued first, so a rate-limited resend doesn't lock the user out of
+    // their account with no way back in
+    let reset_token = match reset_req.mode {
+        ResetMode::TemporaryPassword => None,
+        ResetMode::Email => {
+            match PasswordResetToken::issue(&mut conn, user.id, Utc::now().timestamp()) {
+                Ok(Some(token)) => Some(token),
+                Ok(None) => {
+                    return HttpResponse::TooManyRequests()
+                        .body("A reset link was sent to this user less than a minute ago")
+                }
+                Err(e) => {
+                    log::error!("Error issuing password reset token: {:?}", e);
+                    return HttpResponse::InternalServerError().body("Error resetting password");
+                }
+            }
+        }
+    };
+
+    // in email mode nobody ever sees this password; it only stops the old
+    // one from working until the user follows the link
+    let temp_password = generate_temp_password();
+    match User::force_password_reset(&mut conn, user.id, &temp_password) {
+        Ok(_) => {
+            log::info!(
+                "Forced password reset for user {} by {}",
+                user.id,
+                claims.sub
+            );
+            NewAuditEntry::new(
+                AuditAction::PasswordResetForced,
+                Some(claims.sub),
+                Some(user.id),
+            )
+            .record(&mut conn);
+        }
+        Err(UserTableError::UserNotFound) => {
+            return HttpResponse::NotFound().body("User not found")
+        }
+        Err(_) => return HttpResponse::InternalServerError().body("Error resetting password"),
+    }
+
+    let Some(token) = reset_token else {
+        return HttpResponse::Ok().json(ForceResetResponse {
+            temporary_password: Some(temp_password),
+            message: "Password reset, user must change it at next login".to_string(),
+        });
+    };
+
+    let message = format!(
+        "Password reset, a link to set a new one is being sent to {}",
+        user.login_email
+    );
+    let retry_policy = RetryPolicy::load(&mut conn, Channel::Email);
+    tokio::spawn(async move {
+        if let Err(e) = send_forced_reset(&user, &token, &retry_policy).await {
+            log::error!("Error sending password reset email: {:?}", e);
+        }
+    });
+
+    HttpResponse::Ok().json(ForceResetResponse {
+        temporary_password: None,
+        message,
+    })
+}
+
+/// Let a user who signed up log in, and email them that they can
+#[post("/users/{user_id}/approve")]
+pub async fn approve_user(
+    pool: RqDbPool,
+    webhooks: web::Data<Webhooks>,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to approve a user by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let user = match User::approve(&mut conn, user_id) {
+        Ok(user) => user,
+        Err(UserTableError::UserNotFound) => {
+            return HttpResponse::NotFound().body("No user waiting for approval")
+        }
+        Err(_) => return HttpResponse::InternalServerError().body("Error approving user"),
+    };
+
+    log::info!("User {} approved by {}", user.id, claims.sub);
+    NewAuditEntry::new(AuditAction::UserApproved, Some(claims.sub), Some(user.id))
+        .record(&mut conn);
+    webhooks.emit(Event::UserCreated {
+        user_id: user.id,
+        email: user.login_email.clone(),
+    });
+    if let Err(e) = Onboarding::start(&mut conn, user.id) {
+        log::error!("Error starting onboarding for user {}: {:?}", user.id, e);
+    }
+    // the account is approved either way, so don't fail the request
+    let retry_policy = RetryPolicy::load(&mut conn, Channel::Email);
+    if let Err(e) = onboarding::send_approved(&user, &retry_policy).await {
+        log::error!("Error sending approval email to user {}: {}", user.id, e);
+    }
+    HttpResponse::Ok().json(user)
+}
+
+/// Delete a sign-up that's waiting for approval
+#[post("/users/{user_id}/reject")]
+pub async fn reject_user(pool: RqDbPool, path: RqUserId, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to reject a user by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match User::reject(&mut conn, user_id) {
+        Ok(()) => {
+            log::info!("User {} rejected by {}", user_id, claims.sub);
+            NewAuditEntry::new(AuditAction::UserRejected, Some(claims.sub), Some(user_id))
+                .record(&mut conn);
+            HttpResponse::NoContent().finish()
+        }
+        Err(UserTableError::UserNotFound) => {
+            HttpResponse::NotFound().body("No user waiting for approval")
+        }
+        Err(_) => HttpResponse::InternalServerError().body("Error rejecting user"),
+    }
+}
+
+#[get("/registration")]
+pub async fn get_registration(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get registration mode by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(Registration::load(&mut conn))
+}
+
+/// Sign-ups already waiting for approval stay until an admin decides on
+/// them, whatever the new mode
+#[put("/registration")]
+pub async fn set_registration(
+    pool: RqDbPool,
+    registration: web::Json<Registration>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set registration mode by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match registration.save(&mut conn) {
+        Ok(_) => {
+            log::info!(
+                "Registration mode set to {:?} by {}",
+                registration.registration_mode,
+                claims.sub
+            );
+            audit_setting(&mut conn, &claims, "registration mode");
+            HttpResponse::Ok().json(Registration::load(&mut conn))
+        }
+        Err(e) => {
+            log::error!("Error saving registration mode: {}", e);
+            HttpResponse::InternalServerError().body("Error saving registration mode")
+        }
+    }
+}
+
+#[get("/quotas")]
+pub async fn get_quotas(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get quotas by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(Quotas::load(&mut conn))
+}
+
+/// Replace all quotas, any that are left out become unlimited
+#[put("/quotas")]
+pub async fn set_quotas(
+    pool: RqDbPool,
+    quotas: web::Json<Quotas>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to set quotas by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match quotas.save(&mut conn) {
+        Ok(_) => {
+            log::info!("Quotas set to {:?} by {}", quotas, claims.sub);
+            audit_setting(&mut conn, &claims, "quotas");
+            HttpResponse::Ok().json(quotas.into_inner())
+        }
+        Err(e) => {
+            log::error!("Error saving quotas: {}", e);
+            HttpResponse::InternalServerError().body("Error saving quotas")
+        }
+    }
+}
+
+#[get("/retry-policies/{channel}")]
+pub async fn get_retry_policy(pool: RqDbPool, path: RqChannel, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get retry policy by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(RetryPolicy::load(&mut conn, path.channel))
+}
+
+#[put("/retry-policies/{channel}")]
+pub async fn set_retry_policy(
+    pool: RqDbPool,
+    path: RqChannel,
+    policy: web::Json<RetryPolicy>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to set retry policy by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = policy.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match policy.save(&mut conn, path.channel) {
+        Ok(_) => {
+            log::info!(
+                "{:?} retry policy set to {:?} by {}",
+                path.channel,
+                policy,
+                claims.sub
+            );
+            audit_setting(
+                &mut conn,
+                &claims,
+                &format!("{:?} retry policy", path.channel),
+            );
+            HttpResponse::Ok().json(policy.into_inner())
+        }
+        Err(e) => {
+            log::error!("Error saving retry policy: {}", e);
+            HttpResponse::InternalServerError().body("Error saving retry policy")
+        }
+    }
+}
+
+#[get("/ingest-limits")]
+pub async fn get_ingest_limits(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get ingest limits by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(IngestLimits::load(&mut conn))
+}
+
+#[put("/ingest-limits")]
+pub async fn set_ingest_limits(
+    pool: RqDbPool,
+    limits: web::Json<IngestLimits>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set ingest limits by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = limits.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match limits.save(&mut conn) {
+        Ok(_) => {
+            log::info!("Ingest limits set to {:?} by {}", limits, claims.sub);
+            audit_setting(&mut conn, &claims, "ingest limits");
+            HttpResponse::Ok().json(limits.into_inner())
+        }
+        Err(e) => {
+            log::error!("Error saving ingest limits: {}", e);
+            HttpResponse::InternalServerError().body("Error saving ingest limits")
+        }
+    }
+}
+
+#[get("/max-item-age")]
+pub async fn get_max_item_age(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get max item age by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(MaxItemAge::load(&mut conn))
+}
+
+#[put("/max-item-age")]
+pub async fn set_max_item_age(
+    pool: RqDbPool,
+    max_age: web::Json<MaxItemAge>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to set max item age by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = max_age.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match max_age.save(&mut conn) {
+        Ok(_) => {
+            log::info!("Max item age set to {:?} by {}", max_age, claims.sub);
+            audit_setting(&mut conn, &claims, "max item age");
+            HttpResponse::Ok().json(max_age.into_inner())
+        }
+        Err(e) => {
+            log::error!("Error saving max item age: {}", e);
+            HttpResponse::InternalServerError().body("Error saving max item age")
+        }
+    }
+}
+
+#[get("/mqtt")]
+pub async fn get_mqtt_settings(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get MQTT settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(MqttSettings::load(&mut conn))
+}
+
+#[put("/mqtt")]
+pub async fn set_mqtt_settings(
+    pool: RqDbPool,
+    settings: web::Json<MqttSettings>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set MQTT settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = settings.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match settings.save(&mut conn) {
+        Ok(_) => {
+            log::info!(
+                "MQTT publishing to {}:{} {} by {}",
+                settings.host,
+                settings.port,
+                if settings.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                },
+                claims.sub
+            );
+            audit_setting(&mut conn, &claims, "MQTT settings");
+            HttpResponse::Ok().json(MqttSettings::load(&mut conn))
+        }
+        Err(e) => {
+            log::error!("Error saving MQTT settings: {}", e);
+            HttpResponse::InternalServerError().body("Error saving MQTT settings")
+        }
+    }
+}
+
+#[get("/contact")]
+pub async fn get_admin_contact(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get admin contact by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(AdminContact::load(&mut conn))
+}
+
+/// Shown publicly by /api/status/instance and /.well-known/security.txt
+#[put("/contact")]
+pub async fn set_admin_contact(
+    pool: RqDbPool,
+    contact: web::Json<AdminContact>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set admin contact by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = contact.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match contact.save(&mut conn) {
+        Ok(_) => {
+            log::info!("Admin contact set by {}", claims.sub);
+            audit_setting(&mut conn, &claims, "admin contact");
+            HttpResponse::Ok().json(AdminContact::load(&mut conn))
+        }
+        Err(e) => {
+            log::error!("Error saving admin contact: {}", e);
+            HttpResponse::InternalServerError().body("Error saving admin contact")
+        }
+    }
+}
+
+#[get("/maintenance-mode")]
+pub async fn get_maintenance_mode(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get maintenance mode by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(MaintenanceMode::load(&mut conn))
+}
+
+/// Takes effect on the next request, and for the background senders on
+/// their next check
+#[put("/maintenance-mode")]
+pub async fn set_maintenance_mode(
+    pool: RqDbPool,
+    mode: web::Json<MaintenanceMode>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set maintenance mode by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = mode.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match mode.save(&mut conn) {
+        Ok(_) => {
+            log::info!(
+                "Maintenance mode {} by {}",
+                if mode.enabled { "enabled" } else { "disabled" },
+                claims.sub
+            );
+            audit_setting(&mut conn, &claims, "maintenance mode");
+            HttpResponse::Ok().json(MaintenanceMode::load(&mut conn))
+        }
+        Err(e) => {
+            log::error!("Error saving maintenance mode: {}", e);
+            HttpResponse::InternalServerError().body("Error saving maintenance mode")
+        }
+    }
+}
+
+#[get("/body-logging")]
+pub async fn get_body_logging(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get body logging by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(BodyLogging::load(&mut conn))
+}
+
+/// Takes effect on the next request
+#[put("/body-logging")]
+pub async fn set_body_logging(
+    pool: RqDbPool,
+    logging: web::Json<BodyLogging>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to set body logging by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = logging.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match logging.save(&mut conn) {
+        Ok(_) => {
+            log::info!(
+                "Body logging {} by {}",
+                if logging.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                },
+                claims.sub
+            );
+            audit_setting(&mut conn, &claims, "body logging");
+            HttpResponse::Ok().json(BodyLogging::load(&mut conn))
+        }
+        Err(e) => {
+            log::error!("Error saving body logging: {}", e);
+            HttpResponse::InternalServerError().body("Error saving body logging")
+        }
+    }
+}
+
+fn smtp_status(conn: &mut DbConnection) -> SmtpStatus {
+    let verification = SmtpVerification::load(conn);
+    let fingerprint = current_fingerprint();
+    SmtpStatus {
+        configured: fingerprint.is_some(),
+        held: fingerprint.is_some_and(|fingerprint| verification.holds(&fingerprint)),
+        verification_sent_at: verification.sent_at,
+    }
+}
+
+#[get("/smtp")]
+pub async fn get_smtp_status(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get SMTP status by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(smtp_status(&mut conn))
+}
+
+/// Take the SMTP settings in use as working without the emailed link,
+/// which needs `MF_PUBLIC_URL` to be set
+#[post("/smtp/verify")]
+pub async fn verify_smtp(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to verify SMTP settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let fingerprint = match current_fingerprint() {
+        Some(fingerprint) => fingerprint,
+        None => return HttpResponse::ServiceUnavailable().body("Email sending is not configured"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match SmtpVerification::confirm(&mut conn, &fingerprint) {
+        Ok(()) => {
+            log::info!("SMTP settings verified by {}", claims.sub);
+            audit_setting(&mut conn, &claims, "SMTP verification");
+            HttpResponse::Ok().json(smtp_status(&mut conn))
+        }
+        Err(e) => {
+            log::error!("Error verifying SMTP settings: {:?}", e);
+            HttpResponse::InternalServerError().body("Error verifying settings")
+        }
+    }
+}
+
+/// Checks the Telegram bot token with the Bot API, so a bad one shows up
+/// here rather than only in the logs when a notification fails
+#[get("/telegram/status")]
+pub async fn get_telegram_status(claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get Telegram status by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let status = match TelegramBot::from_env() {
+        Some(bot) => bot.status().await,
+        None => BotStatus::default(),
+    };
+    HttpResponse::Ok().json(status)
+}
+
+#[get("/retention")]
+pub async fn get_retention(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get retention by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(Retention::load(&mut conn))
+}
+
+/// Applied at the next database maintenance run
+#[put("/retention")]
+pub async fn set_retention(
+    pool: RqDbPool,
+    retention: web::Json<Retention>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to set retention by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = retention.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match retention.save(&mut conn) {
+        Ok(_) => {
+            log::info!("Retention set to {:?} by {}", *retention, claims.sub);
+            audit_setting(&mut conn, &claims, "retention");
+            HttpResponse::Ok().json(Retention::load(&mut conn))
+        }
+        Err(e) => {
+            log::error!("Error saving retention: {}", e);
+            HttpResponse::InternalServerError().body("Error saving retention")
+        }
+    }
+}
+
+/// Usage across the instance by month, and the feeds storing the most
+#[get("/usage")]
+pub async fn get_usage(
+    pool: RqDbPool,
+    query: web::Query<UsageQuery>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get instance usage by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = query.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    match InstanceUsage::load(&mut conn, now, query.months()) {
+        Ok(usage) => HttpResponse::Ok().json(usage),
+        Err(e) => {
+            log::error!("Error getting instance usage: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting usage")
+        }
+    }
+}
+
+/// The digest template versions that can be compared
+#[get("/digest-templates")]
+pub async fn get_digest_templates(claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to list digest templates by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    HttpResponse::Ok().json(DigestTemplate::ALL)
+}
+
+/// Render a subscription's latest items through two digest template
+/// versions, so a change to the email can be checked before it's sent.
+/// Nothing is marked as sent.
+#[get("/digest-preview")]
+pub async fn get_digest_preview(
+    pool: RqDbPool,
+    query: web::Query<DigestPreviewQuery>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to preview digest templates by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let Some(sub) = Subscription::get_by_id(&mut conn, query.subscription_id) else {
+        return HttpResponse::NotFound().body("Subscription not found");
+    };
+    let Some(user) = User::get(&mut conn, UserQuery::Id(sub.user_id)) else {
+        return HttpResponse::NotFound().body("User not found");
+    };
+    let Some(feed) = Feed::get_by_id(&mut conn, sub.feed_id) else {
+        return HttpResponse::NotFound().body("Feed not found");
+    };
+    let items = FeedItem::latest(&mut conn, feed.id, DIGEST_PREVIEW_ITEMS);
+    let item_count = items.len();
+
+    match preview_templates(&user, &sub, &feed, items, &[query.a, query.b]) {
+        Ok(rendered) => {
+            let mut rendered = rendered.into_iter();
+            let (Some(a), Some(b)) = (rendered.next(), rendered.next()) else {
+                return HttpResponse::InternalServerError().body("Error rendering digest");
+            };
+            HttpResponse::Ok().json(DigestPreview {
+                subscription_id: sub.id,
+                item_count,
+                a: RenderedDigest {
+                    template: query.a,
+                    html: a,
+                },
+                b: RenderedDigest {
+                    template: query.b,
+                    html: b,
+                },
+            })
+        }
+        Err(e) => {
+            log::error!("Error rendering digest preview: {:?}", e);
+            HttpResponse::InternalServerError().body("Error rendering digest")
+        }
+    }
+}
+
+#[get("/access")]
+pub async fn get_admin_access(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get admin access rules by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(AdminAccess::load(&mut conn))
+}
+
+/// Takes effect on the next request. Rules that would block the admin
+/// saving them are refused, so they can't lock themselves out.
+#[put("/access")]
+pub async fn set_admin_access(
+    req: HttpRequest,
+    pool: RqDbPool,
+    access: web::Json<AdminAccess>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set admin access rules by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = access.validate() {
+        return errors.error_response();
+    }
+    if !access.permits(real_ip(&req)) {
+        return HttpResponse::BadRequest().body("These rules would block your own address");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match access.save(&mut conn) {
+        Ok(_) => {
+            log::info!("Admin access rules changed by {}", claims.sub);
+            audit_setting(&mut conn, &claims, "admin access rules");
+            HttpResponse::Ok().json(AdminAccess::load(&mut conn))
+        }
+        Err(e) => {
+            log::error!("Error saving admin access rules: {}", e);
+            HttpResponse::InternalServerError().body("Error saving admin access rules")
+        }
+    }
+}
+
+/// Note an instance-wide setting an admin changed in the audit log
+fn audit_setting(conn: &mut DbConnection, claims: &Claims, setting: &str) {
+    NewAuditEntry::new(AuditAction::SettingChanged, Some(claims.sub), None)
+        .details(setting)
+        .record(conn);
+}
+
+fn generate_temp_password() -> String {
+    random_alphanumeric(TEMP_PASSWORD_LENGTH)
+}
+
+fn random_alphanumeric(length: usize) -> String {
+    use rand::distributions::Alphanumeric;
+    use rand::{rngs::OsRng, Rng};
+
+    OsRng
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+/// Fetch every feed with an active subscriber now rather than when it's
+/// next due, e.g. after restoring a backup or a long downtime
+#[post("/feeds/refresh-all")]
+pub async fn refresh_all_feeds(
+    pool: RqDbPool,
+    refresh_jobs: web::Data<RefreshJobs>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to refresh all feeds by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let feed_ids = match Feed::active_ids(&mut conn) {
+        Ok(feed_ids) => feed_ids,
+        Err(e) => {
+            log::error!("Error getting active feeds: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error getting feeds");
+        }
+    };
+
+    let job = refresh_jobs.enqueue(&feed_ids, claims.sub, Utc::now().timestamp());
+    log::info!(
+        "Refresh of {} feeds requested by {} (job {})",
+        job.total,
+        claims.sub,
+        job.id
+    );
+    HttpResponse::Ok().json(job)
+}
+
+#[get("/jobs/{job_id}")]
+pub async fn get_job(path: RqJobId, jobs: web::Data<Jobs>, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get job by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let job_id = match path.job_id.parse::<u64>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid job ID"),
+    };
+
+    match jobs.get(job_id) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().body("Job not found"),
+    }
+}
+
+/// The maintenance window and how the last database maintenance went
+#[get("/maintenance")]
+pub async fn get_maintenance(
+    status: web::Data<MaintenanceStatus>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get maintenance status by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    HttpResponse::Ok().json(status.report())
+}
+
+/// Row counts per table, the database size, and the slowest recent queries
+#[get("/db-stats")]
+pub async fn get_db_stats(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get db stats by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match DbStats::load(&mut conn) {
+        Ok(stats) => HttpResponse::Ok().json(stats),
+        Err(e) => {
+            log::error!("Error getting db stats: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting db stats")
+        }
+    }
+}
+
+#[get("/webhooks")]
+pub async fn get_webhooks(pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get webhooks by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Webhook::get_all(&mut conn) {
+        Ok(webhooks) => HttpResponse::Ok().json(webhooks),
+        Err(e) => {
+            log::error!("Error getting webhooks: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting webhooks")
+        }
+    }
+}
+
+/// Add an endpoint for the given event types, with a new signing secret
+#[post("/webhooks")]
+pub async fn create_webhook(
+    pool: RqDbPool,
+    webhook: web::Json<WebhookCreate>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to create webhook by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = webhook.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let secret = random_alphanumeric(WEBHOOK_SECRET_LENGTH);
+    let new_webhook = NewWebhook {
+        url: &webhook.url,
+        secret: &secret,
+        events: &webhook.events,
+        is_active: true,
+        created_at: Utc::now().timestamp(),
+    };
+    match new_webhook.insert(&mut conn) {
+        Ok(created) => {
+            log::info!(
+                "Webhook {} for {} created by {}",
+                created.id,
+                created.events,
+                claims.sub
+            );
+            audit_setting(
+                &mut conn,
+                &claims,
+                &format!("webhook {} created", created.id),
+            );
+            HttpResponse::Ok().json(created)
+        }
+        Err(e) => {
+            log::error!("Error creating webhook: {:?}", e);
+            HttpResponse::InternalServerError().body("Error creating webhook")
+        }
+    }
+}
+
+#[patch("/webhooks/{webhook_id}")]
+pub async fn update_webhook(
+    pool: RqDbPool,
+    path: RqWebhookId,
+    update: web::Json<PartialWebhook>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to update webhook by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let webhook_id = match path.webhook_id.parse::<WebhookId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid webhook ID"),
+    };
+
+    if let Err(errors) = update.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Webhook::update(&mut conn, webhook_id, &update) {
+        Ok(webhook) => {
+            audit_setting(
+                &mut conn,
+                &claims,
+                &format!("webhook {} updated", webhook.id),
+            );
+            HttpResponse::Ok().json(webhook)
+        }
+        Err(diesel::result::Error::NotFound) => HttpResponse::NotFound().body("Webhook not found"),
+        Err(e) => {
+            log::error!("Error updating webhook: {:?}", e);
+            HttpResponse::InternalServerError().body("Error updating webhook")
+        }
+    }
+}
+
+#[delete("/webhooks/{webhook_id}")]
+pub async fn delete_webhook(pool: RqDbPool, path: RqWebhookId, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to delete webhook by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let webhook_id = match path.webhook_id.parse::<WebhookId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid webhook ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Webhook::delete(&mut conn, webhook_id) {
+        Ok(0) => HttpResponse::NotFound().body("Webhook not found"),
+        Ok(_) => {
+            audit_setting(
+                &mut conn,
+                &claims,
+                &format!("webhook {} deleted", webhook_id),
+            );
+            HttpResponse::Ok().body("Webhook deleted")
+        }
+        Err(e) => {
+            log::error!("Error deleting webhook: {:?}", e);
+            HttpResponse::InternalServerError().body("Error deleting webhook")
+        }
+    }
+}
+
+/// Security-relevant events, newest first: logins, password and role
+/// changes, subscription and email settings changes, and what admins did
+#[get("/audit")]
+pub async fn get_audit_log(
+    pool: RqDbPool,
+    query: web::Query<AuditQuery>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get audit log by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = query.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let (page, per_page) = (query.page(), query.per_page());
+    // one extra to tell whether there's another page
+    let mut entries = match AuditEntry::list(
+        &mut conn,
+        &query.filter(),
+        (page - 1) * per_page,
+        per_page + 1,
+    ) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Error getting audit log: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error getting audit log");
+        }
+    };
+    let has_more = entries.len() as i64 > per_page;
+    entries.truncate(per_page as usize);
+
+    HttpResponse::Ok().json(AuditPage {
+        entries,
+        page,
+        has_more,
+    })
+}
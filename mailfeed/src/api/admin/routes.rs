@@ -0,0 +1,63 @@
+use super::{access, handlers};
+use actix_web::{
+    body::MessageBody,
+    dev::{Service, ServiceFactory, ServiceRequest, ServiceResponse},
+    web, Error, Scope,
+};
+use futures_util::future::{ready, Either};
+
+pub fn routes() -> Scope<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    web::scope("/admin")
+        .wrap_fn(|req, srv| match access::check(&req) {
+            Ok(()) => Either::Left(srv.call(req)),
+            Err(e) => Either::Right(ready(Err(e))),
+        })
+        .service(handlers::get_admin_access)
+        .service(handlers::set_admin_access)
+        .service(handlers::force_password_reset)
+        .service(handlers::approve_user)
+        .service(handlers::reject_user)
+        .service(handlers::get_registration)
+        .service(handlers::set_registration)
+        .service(handlers::get_quotas)
+        .service(handlers::set_quotas)
+        .service(handlers::get_retry_policy)
+        .service(handlers::set_retry_policy)
+        .service(handlers::get_ingest_limits)
+        .service(handlers::set_ingest_limits)
+        .service(handlers::get_max_item_age)
+        .service(handlers::set_max_item_age)
+        .service(handlers::get_mqtt_settings)
+        .service(handlers::set_mqtt_settings)
+        .service(handlers::refresh_all_feeds)
+        .service(handlers::get_job)
+        .service(handlers::get_admin_contact)
+        .service(handlers::set_admin_contact)
+        .service(handlers::get_maintenance)
+        .service(handlers::get_maintenance_mode)
+        .service(handlers::set_maintenance_mode)
+        .service(handlers::get_body_logging)
+        .service(handlers::set_body_logging)
+        .service(handlers::get_smtp_status)
+        .service(handlers::verify_smtp)
+        .service(handlers::get_telegram_status)
+        .service(handlers::get_digest_templates)
+        .service(handlers::get_digest_preview)
+        .service(handlers::get_retention)
+        .service(handlers::set_retention)
+        .service(handlers::get_db_stats)
+        .service(handlers::get_audit_log)
+        .service(handlers::get_usage)
+        .service(handlers::get_webhooks)
+        .service(handlers::create_webhook)
+        .service(handlers::update_webhook)
+        .service(handlers::delete_webhook)
+}
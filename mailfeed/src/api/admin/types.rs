@@ -0,0 +1,176 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{
+    audit_log::{AuditAction, AuditEntry, AuditFilter},
+    ids::{SubscriptionId, UserId},
+    retry_policy::Channel,
+    webhook::EventTypes,
+};
+use crate::security::validation::{Validate, ValidationErrors};
+use crate::tasks::email_sender::digest_templates::DigestTemplate;
+
+/// Whether digests are held for new SMTP settings to be verified
+#[derive(Debug, Serialize)]
+pub struct SmtpStatus {
+    /// false if the SMTP settings are missing or invalid
+    pub configured: bool,
+    pub held: bool,
+    /// when the last verification email was sent, zero if none is waiting
+    pub verification_sent_at: i64,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetMode {
+    /// Return the temporary password to the admin
+    #[default]
+    TemporaryPassword,
+    /// Email the user a link to set a new password instead; nobody sees
+    /// the temporary one
+    Email,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForceResetRequest {
+    #[serde(default)]
+    pub mode: ResetMode,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ForceResetResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temporary_password: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelPath {
+    pub channel: Channel,
+}
+
+pub type RqChannel = web::Path<ChannelPath>;
+
+#[derive(Debug, Deserialize)]
+pub struct JobPath {
+    pub job_id: String,
+}
+
+pub type RqJobId = web::Path<JobPath>;
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookCreate {
+    pub url: String,
+    /// comma-separated, e.g. `feed_broken,task_failed`
+    pub events: EventTypes,
+}
+
+impl Validate for WebhookCreate {
+    fn check(&self, errors: &mut ValidationErrors) {
+        errors.url("url", &self.url);
+        if self.events.is_empty() {
+            errors.add("events", "Must include at least one event type");
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookPath {
+    pub webhook_id: String,
+}
+
+pub type RqWebhookId = web::Path<WebhookPath>;
+
+/// How many of the feed's latest items a digest preview renders
+pub const DIGEST_PREVIEW_ITEMS: i64 = 10;
+
+/// The subscription whose digest is rendered, and the two template versions
+/// to render it with, e.g. `a=digest.html&b=digest_next.html`
+#[derive(Debug, Deserialize)]
+pub struct DigestPreviewQuery {
+    pub subscription_id: SubscriptionId,
+    pub a: DigestTemplate,
+    pub b: DigestTemplate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenderedDigest {
+    pub template: DigestTemplate,
+    pub html: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestPreview {
+    pub subscription_id: SubscriptionId,
+    pub item_count: usize,
+    pub a: RenderedDigest,
+    pub b: RenderedDigest,
+}
+
+/// Audit log entries on a page unless asked for otherwise
+pub const DEFAULT_AUDIT_PAGE: i64 = 50;
+
+/// Most audit log entries on one page
+pub const MAX_AUDIT_PAGE: i64 = 200;
+
+/// Filters and pagination for the audit log
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    /// entries done by or to this user
+    pub user_id: Option<UserId>,
+    pub action: Option<AuditAction>,
+    /// only entries from after this unix timestamp
+    pub after: Option<i64>,
+    /// only entries from before this unix timestamp
+    pub before: Option<i64>,
+    /// from 1
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+impl AuditQuery {
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(DEFAULT_AUDIT_PAGE)
+    }
+
+    pub fn filter(&self) -> AuditFilter {
+        AuditFilter {
+            user_id: self.user_id,
+            action: self.action,
+            after: self.after,
+            before: self.before,
+        }
+    }
+}
+
+impl Validate for AuditQuery {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.page() < 1 {
+            errors.add("page", "Must be at least 1");
+        }
+        if !(1..=MAX_AUDIT_PAGE).contains(&self.per_page()) {
+            errors.add(
+                "per_page",
+                format!("Must be between 1 and {}", MAX_AUDIT_PAGE),
+            );
+        }
+        if let (Some(after), Some(before)) = (self.after, self.before) {
+            if after >= before {
+                errors.add("before", "Must be later than after");
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditPage {
+    /// newest first
+    pub entries: Vec<AuditEntry>,
+    pub page: i64,
+    /// whether there's a next page
+    pub has_more: bool,
+}
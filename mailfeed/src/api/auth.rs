@@ -1,5 +1,5 @@
 mod handlers;
-mod jwt;
+pub(crate) mod jwt;
 mod routes;
 mod types;
 
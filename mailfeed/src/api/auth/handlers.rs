@@ -1,13 +1,37 @@
 use super::jwt::{create_access_token, create_refresh_token, verify_and_extract_claims};
-use super::types::{LoginRequest, RefreshRequest, TokenResponse};
-use crate::claims::Claims;
-use crate::models::user::{PartialUser, User, UserQuery};
-use actix_web::{post, web, HttpResponse, Responder};
+use super::types::{
+    ChangePasswordRequest, LoginRequest, PasswordResetConfirm, PasswordResetRequest,
+    RefreshRequest, ResetTokenPath, TokenResponse,
+};
+use crate::claims::PasswordChangeClaims;
+use crate::db::DbConnection;
+use crate::models::audit_log::{AuditAction, NewAuditEntry};
+use crate::models::ids::UserId;
+use crate::models::password_reset_token::PasswordResetToken;
+use crate::models::retry_policy::{Channel, RetryPolicy};
+use crate::models::session::{NewSession, Session};
+use crate::models::two_factor::TwoFactor;
+use crate::models::user::{User, UserQuery, UserTableError};
+use crate::security::client_ip::{describe, real_ip};
+use crate::security::redact;
+use crate::security::secret_box::SecretBox;
+use crate::security::validation::Validate;
+use crate::tasks::email_sender::password_reset::send_reset;
+use actix_web::{http::header, post, web, HttpRequest, HttpResponse, Responder, ResponseError};
+use chrono::Utc;
+use std::net::IpAddr;
+use thiserror::Error;
 
 use crate::RqDbPool;
 
 #[post("/login")]
-pub async fn login(pool: RqDbPool, login_req: web::Json<LoginRequest>) -> impl Responder {
+pub async fn login(
+    req: HttpRequest,
+    pool: RqDbPool,
+    login_req: web::Json<LoginRequest>,
+) -> impl Responder {
+    let ip = real_ip(&req);
+    let client = describe(ip);
     let mut conn = match pool.get() {
         Ok(conn) => conn,
         Err(err) => {
@@ -18,9 +42,18 @@ pub async fn login(pool: RqDbPool, login_req: web::Json<LoginRequest>) -> impl R
 
     let user = match User::get(&mut conn, UserQuery::Email(&login_req.email)) {
         Some(user) => user,
-        None => return HttpResponse::BadRequest().body("Invalid email or password"),
+        None => {
+            NewAuditEntry::new(AuditAction::LoginFailed, None, None)
+                .details(format!("unknown email {}", redact::email(&login_req.email)))
+                .ip(ip)
+                .record(&mut conn);
+            return HttpResponse::BadRequest().body("Invalid email or password");
+        }
     };
 
+    if user.pending_approval {
+        return HttpResponse::BadRequest().body("Account is waiting for admin approval");
+    }
     if !user.is_active {
         return HttpResponse::BadRequest().body("Account is deactivated - contact admin");
     }
@@ -31,40 +64,127 @@ pub async fn login(pool: RqDbPool, login_req: web::Json<LoginRequest>) -> impl R
     };
 
     if !is_password_correct {
+        log::warn!("Wrong password for user {} from {}", user.id, client);
+        login_failed(&mut conn, user.id, ip, "wrong password");
         return HttpResponse::BadRequest().body("Invalid email or password");
     }
 
-    let refresh_token = match create_refresh_token(&user) {
-        Ok(token) => token,
-        Err(_) => return HttpResponse::InternalServerError().body("Error creating refresh token"),
-    };
-
-    let access_token = match create_access_token(&user) {
-        Ok(token) => token,
-        Err(_) => return HttpResponse::InternalServerError().body("Error creating access token"),
+    let two_factor = match TwoFactor::get(&mut conn, user.id) {
+        Ok(two_factor) => two_factor.filter(|two_factor| two_factor.enabled),
+        Err(e) => {
+            log::error!("Error getting two-factor settings: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error checking two-factor code");
+        }
     };
+    if let Some(two_factor) = two_factor {
+        let code = match login_req.code.as_deref() {
+            Some(code) if !code.trim().is_empty() => code,
+            _ => return HttpResponse::Unauthorized().body("Two-factor code required"),
+        };
+        // fail closed: without the key, codes can't be checked
+        let secret_box = match SecretBox::global() {
+            Some(secret_box) => secret_box,
+            None => {
+                log::error!("MF_SECRET_KEY is not set, so user {} can't log in", user.id);
+                return HttpResponse::InternalServerError().body("Error checking two-factor code");
+            }
+        };
+        match two_factor.redeem(&mut conn, secret_box, code, Utc::now().timestamp()) {
+            Ok(true) => {}
+            Ok(false) => {
+                log::warn!("Wrong two-factor code for user {} from {}", user.id, client);
+                login_failed(&mut conn, user.id, ip, "wrong two-factor code");
+                return HttpResponse::Unauthorized().body("Invalid two-factor code");
+            }
+            Err(e) => {
+                log::error!("Error checking two-factor code: {:?}", e);
+                return HttpResponse::InternalServerError().body("Error checking two-factor code");
+            }
+        }
+    }
 
-    let updates = PartialUser {
-        refresh_token: Some(refresh_token.clone()),
-        ..Default::default()
+    let (access_token, refresh_token) = match start_session(&mut conn, &req, &user) {
+        Ok(tokens) => tokens,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
     };
-    // add refresh token to users table
-    if let Err(e) = User::update(&mut conn, user.id, &updates) {
-        log::error!("Error updating user: {:?}", e);
-        return HttpResponse::InternalServerError().body("Error updating user");
-    }
+    // only shown to admins, so not worth failing the login over
+    let _ = User::record_login(&mut conn, user.id, Utc::now().timestamp());
+    log::info!("Login for user {} from {}", user.id, client);
+    NewAuditEntry::new(AuditAction::Login, Some(user.id), Some(user.id))
+        .ip(ip)
+        .record(&mut conn);
 
     let response = TokenResponse {
         access_token: &access_token,
         refresh_token: &refresh_token,
+        must_change_password: user.must_change_password,
     };
 
     HttpResponse::Ok().json(response)
 }
 
+fn login_failed(conn: &mut DbConnection, user_id: UserId, ip: Option<IpAddr>, reason: &str) {
+    NewAuditEntry::new(AuditAction::LoginFailed, None, Some(user_id))
+        .details(reason)
+        .ip(ip)
+        .record(conn);
+}
+
+fn user_agent(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// What failed when logging a user in on a device
+#[derive(Error, Debug)]
+enum SessionError {
+    #[error("Error creating session")]
+    Session,
+    #[error("Error creating refresh token")]
+    RefreshToken,
+    #[error("Error creating access token")]
+    AccessToken,
+}
+
+/// Log the user in on the device making the request, returning its access
+/// and refresh tokens
+fn start_session(
+    conn: &mut DbConnection,
+    req: &HttpRequest,
+    user: &User,
+) -> Result<(String, String), SessionError> {
+    let ip = real_ip(req).map(|ip| ip.to_string());
+    let new_session = NewSession::new(
+        user.id,
+        Utc::now().timestamp(),
+        user_agent(req),
+        ip.as_deref(),
+    );
+    let session = new_session.insert(conn).map_err(|e| {
+        log::error!("Error creating session: {:?}", e);
+        SessionError::Session
+    })?;
+
+    let refresh_token =
+        create_refresh_token(user, &session).map_err(|_| SessionError::RefreshToken)?;
+    let access_token =
+        create_access_token(user, &session).map_err(|_| SessionError::AccessToken)?;
+    Ok((access_token, refresh_token))
+}
+
+/// End the session the request was made with. Other devices stay logged in.
 #[post("/logout")]
-pub async fn logout(pool: RqDbPool, claims: Claims) -> impl Responder {
+pub async fn logout(
+    pool: RqDbPool,
+    PasswordChangeClaims(claims): PasswordChangeClaims,
+) -> impl Responder {
     log::info!("logout: {:?}", &claims.sub);
+    // personal access tokens aren't sessions, and are revoked on their own
+    let Some(session_id) = claims.sid else {
+        return HttpResponse::Ok().body("logout successful");
+    };
+
     let mut conn = match pool.get() {
         Ok(conn) => conn,
         Err(err) => {
@@ -73,16 +193,20 @@ pub async fn logout(pool: RqDbPool, claims: Claims) -> impl Responder {
         }
     };
 
-    if let Err(e) = User::clear_refresh_token(&mut conn, UserQuery::Id(claims.sub)) {
-        log::error!("Error clearing refresh token: {:?}", e);
-        return HttpResponse::InternalServerError().body("Error clearing refresh token");
+    if let Err(e) = Session::delete(&mut conn, claims.sub, session_id) {
+        log::error!("Error ending session: {:?}", e);
+        return HttpResponse::InternalServerError().body("Error ending session");
     }
 
     HttpResponse::Ok().body("logout successful")
 }
 
 #[post("/refresh")]
-pub async fn refresh(pool: RqDbPool, refresh_req: web::Json<RefreshRequest>) -> impl Responder {
+pub async fn refresh(
+    req: HttpRequest,
+    pool: RqDbPool,
+    refresh_req: web::Json<RefreshRequest>,
+) -> impl Responder {
     let mut conn = match pool.get() {
         Ok(conn) => conn,
         Err(err) => {
@@ -91,27 +215,47 @@ pub async fn refresh(pool: RqDbPool, refresh_req: web::Json<RefreshRequest>) ->
         }
     };
 
-    let claims = verify_and_extract_claims(&refresh_req.refresh_token);
-
-    if claims.is_none() {
+    let claims = match verify_and_extract_claims(&refresh_req.refresh_token) {
+        Some(claims) => claims,
+        None => return HttpResponse::Unauthorized().body("Invalid refresh token"),
+    };
+    let Some(session_id) = claims.sid else {
         return HttpResponse::Unauthorized().body("Invalid refresh token");
-    }
-
-    let claims = claims.unwrap();
+    };
 
     let user = match User::get(&mut conn, UserQuery::Id(claims.sub)) {
         Some(user) => user,
         None => return HttpResponse::Unauthorized().body("Invalid refresh token"),
     };
 
+    // sessions are deleted on logout and password changes
+    let now = Utc::now().timestamp();
+    let session = match Session::authenticate(&mut conn, user.id, session_id, now) {
+        Ok(Some(session)) => session,
+        Ok(None) => return HttpResponse::Unauthorized().body("Invalid refresh token"),
+        Err(e) => {
+            log::error!("Error checking session: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error checking session");
+        }
+    };
+
     if !user.is_active {
-        if let Err(e) = User::clear_refresh_token(&mut conn, UserQuery::Id(user.id)) {
-            log::error!("Error clearing refresh token: {:?}", e);
+        if let Err(e) = Session::delete_for_user(&mut conn, user.id, None) {
+            log::error!("Error ending sessions: {:?}", e);
         }
         return HttpResponse::BadRequest().body("Account is deactivated - contact admin");
     }
 
-    let new_access_token = match create_access_token(&user) {
+    let ip = real_ip(&req).map(|ip| ip.to_string());
+    if let Err(e) = Session::touch(&mut conn, session.id, now, user_agent(&req), ip.as_deref()) {
+        log::error!(
+            "Error updating session {}: {:?}",
+            redact::secret(&session.id.to_string()),
+            e
+        );
+    }
+
+    let new_access_token = match create_access_token(&user, &session) {
         Ok(token) => token,
         Err(_) => return HttpResponse::InternalServerError().body("Error creating access token"),
     };
@@ -119,22 +263,171 @@ pub async fn refresh(pool: RqDbPool, refresh_req: web::Json<RefreshRequest>) ->
     let response = TokenResponse {
         access_token: &new_access_token,
         refresh_token: &refresh_req.refresh_token,
+        must_change_password: user.must_change_password,
     };
 
     HttpResponse::Ok().json(response)
 }
 
+/// Email a reset link to the account's login email. The response is the
+/// same whether or not the account exists, and the email is sent in the
+/// background, so this can't be used to find out who has an account.
 #[post("/password_reset")]
-pub async fn password_reset() -> impl Responder {
-    HttpResponse::Ok().body("password_reset")
+pub async fn password_reset(
+    pool: RqDbPool,
+    reset_req: web::Json<PasswordResetRequest>,
+) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let sent = HttpResponse::Ok().body("If the account exists, a reset email has been sent");
+    let user = match User::get(&mut conn, UserQuery::Email(&reset_req.email)) {
+        Some(user) if user.is_active => user,
+        _ => return sent,
+    };
+
+    let token = match PasswordResetToken::issue(&mut conn, user.id, Utc::now().timestamp()) {
+        Ok(Some(token)) => token,
+        Ok(None) => {
+            log::info!("Not resending password reset to user {} so soon", user.id);
+            return sent;
+        }
+        Err(e) => {
+            log::error!("Error creating password reset token: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error resetting password");
+        }
+    };
+
+    log::info!("Password reset requested for user {}", user.id);
+    let retry_policy = RetryPolicy::load(&mut conn, Channel::Email);
+    tokio::spawn(async move {
+        if let Err(e) = send_reset(&user, &token, &retry_policy).await {
+            log::error!("Error sending password reset to user {}: {}", user.id, e);
+        }
+    });
+    sent
 }
 
+/// Set a new password with a token from a reset email. Every session is
+/// logged out, so the user logs in again with the new password.
 #[post("/password_reset/{token}")]
-pub async fn password_reset_confirm() -> impl Responder {
-    HttpResponse::Ok().body("password_reset_confirm")
+pub async fn password_reset_confirm(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: web::Path<ResetTokenPath>,
+    confirm_req: web::Json<PasswordResetConfirm>,
+) -> impl Responder {
+    if let Err(errors) = confirm_req.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let user_id = match PasswordResetToken::redeem(&mut conn, &path.token, Utc::now().timestamp()) {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => return HttpResponse::BadRequest().body("Invalid or expired reset link"),
+        Err(e) => {
+            log::error!("Error checking password reset token: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error resetting password");
+        }
+    };
+
+    match User::get(&mut conn, UserQuery::Id(user_id)) {
+        Some(user) if user.is_active => {}
+        _ => return HttpResponse::BadRequest().body("Invalid or expired reset link"),
+    }
+
+    // this also logs out every session and clears any forced password change
+    match User::change_password(&mut conn, user_id, &confirm_req.new_password) {
+        Ok(_) => {}
+        Err(UserTableError::PasswordTooShort) => {
+            return HttpResponse::BadRequest().body("Password too short")
+        }
+        Err(UserTableError::WeakPassword(reason)) => {
+            return HttpResponse::BadRequest().body(reason)
+        }
+        Err(_) => return HttpResponse::InternalServerError().body("Error changing password"),
+    }
+
+    log::info!("Password reset for user {}", user_id);
+    NewAuditEntry::new(AuditAction::PasswordReset, None, Some(user_id))
+        .ip(real_ip(&req))
+        .record(&mut conn);
+    HttpResponse::Ok().body("Password reset")
 }
 
 #[post("/change_password")]
-pub async fn change_password() -> impl Responder {
-    HttpResponse::Ok().body("change_password")
+pub async fn change_password(
+    req: HttpRequest,
+    pool: RqDbPool,
+    change_req: web::Json<ChangePasswordRequest>,
+    PasswordChangeClaims(claims): PasswordChangeClaims,
+) -> impl Responder {
+    if let Err(errors) = change_req.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let user = match User::get(&mut conn, UserQuery::Id(claims.sub)) {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body("Invalid credentials"),
+    };
+
+    if !user.is_active {
+        return HttpResponse::BadRequest().body("Account is deactivated - contact admin");
+    }
+
+    match User::check_password(&user, &change_req.current_password) {
+        Ok(true) => {}
+        _ => return HttpResponse::BadRequest().body("Current password is incorrect"),
+    }
+
+    // this also logs out every session, this one included...
+    let user = match User::change_password(&mut conn, user.id, &change_req.new_password) {
+        Ok(user) => user,
+        Err(UserTableError::PasswordTooShort) => {
+            return HttpResponse::BadRequest().body("Password too short")
+        }
+        Err(UserTableError::WeakPassword(reason)) => {
+            return HttpResponse::BadRequest().body(reason)
+        }
+        Err(_) => return HttpResponse::InternalServerError().body("Error changing password"),
+    };
+
+    // ...so hand the caller a fresh session to keep them logged in
+    let (access_token, refresh_token) = match start_session(&mut conn, &req, &user) {
+        Ok(tokens) => tokens,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    log::info!("Password changed for user {}", user.id);
+    NewAuditEntry::new(AuditAction::PasswordChanged, Some(user.id), Some(user.id))
+        .ip(real_ip(&req))
+        .record(&mut conn);
+
+    let response = TokenResponse {
+        access_token: &access_token,
+        refresh_token: &refresh_token,
+        must_change_password: user.must_change_password,
+    };
+
+    HttpResponse::Ok().json(response)
 }
@@ -1,25 +1,21 @@
 use super::types::Error;
 use crate::claims::Claims;
 use crate::global::JWT_SECRET;
-use crate::models::user::User;
+use crate::models::{session::Session, user::User};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 
 const BEARER: &str = "Bearer ";
 const JWT_DURATION_SECONDS: i64 = 60 * 15; // 15 minutes
-const REFRESH_DURATION_SECONDS: i64 = 60 * 60 * 24 * 7; // 7 days
-
-fn create_token(user: &User, duration: i64) -> Result<String, Error> {
-    let expiration = Utc::now()
-        .checked_add_signed(Duration::seconds(duration))
-        .expect("valid timestamp")
-        .timestamp();
 
+fn create_token(user: &User, session: &Session, expiration: i64) -> Result<String, Error> {
     let claims = Claims {
         sub: user.id,
         exp: expiration as usize,
         role: user.role.clone(),
         email: user.login_email.clone(),
+        sid: Some(session.id),
+        must_change_password: user.must_change_password,
     };
 
     let secret = match JWT_SECRET.get() {
@@ -31,12 +27,17 @@ fn create_token(user: &User, duration: i64) -> Result<String, Error> {
     encode(&header, &claims, &EncodingKey::from_secret(secret)).map_err(|_| Error::JWTCreationError)
 }
 
-pub fn create_access_token(user: &User) -> Result<String, Error> {
-    create_token(user, JWT_DURATION_SECONDS)
+pub fn create_access_token(user: &User, session: &Session) -> Result<String, Error> {
+    let expiration = Utc::now()
+        .checked_add_signed(Duration::seconds(JWT_DURATION_SECONDS))
+        .expect("valid timestamp")
+        .timestamp();
+    create_token(user, session, expiration)
 }
 
-pub fn create_refresh_token(user: &User) -> Result<String, Error> {
-    create_token(user, REFRESH_DURATION_SECONDS)
+/// Works until the session expires, 7 days after logging in
+pub fn create_refresh_token(user: &User, session: &Session) -> Result<String, Error> {
+    create_token(user, session, session.expires_at)
 }
 
 pub fn verify_and_extract_claims(header_val: &str) -> Option<Claims> {
@@ -70,25 +71,36 @@ mod tests {
     use base64::engine::general_purpose;
 
     use super::*;
+    use crate::models::ids::{SessionId, UserId};
+    use crate::models::session::SESSION_LIFETIME_SECONDS;
+    use crate::test_helpers::test_helpers::test_user;
 
     fn get_test_user() -> User {
         User {
-            id: 1,
             login_email: "testy@mctestface.com".to_string(),
-            send_email: "testy@mctestface.com".to_string(),
-            role: "user".to_string(),
             password: "password".to_string(),
-            created_at: Utc::now().timestamp() as i32,
-            is_active: true,
-            daily_send_time: "".to_string(),
-            refresh_token: None,
+            created_at: Utc::now().timestamp(),
+            ..test_user()
+        }
+    }
+
+    fn get_test_session() -> Session {
+        let now = Utc::now().timestamp();
+        Session {
+            id: SessionId(5),
+            user_id: UserId(1),
+            created_at: now,
+            last_used_at: now,
+            expires_at: now + SESSION_LIFETIME_SECONDS,
+            user_agent: None,
+            ip: None,
         }
     }
 
     fn token_to_claims(token: &str) -> Claims {
         use base64::Engine;
         let token = token.split('.').collect::<Vec<&str>>()[1];
-        let buf = general_purpose::STANDARD_NO_PAD.decode(&token).unwrap();
+        let buf = general_purpose::STANDARD_NO_PAD.decode(token).unwrap();
         let token = String::from_utf8(buf).unwrap();
         serde_json::from_str(&token).unwrap()
     }
@@ -96,31 +108,42 @@ mod tests {
     #[test]
     fn test_access_token() {
         let user = get_test_user();
-        let jwt = create_access_token(&user);
-        assert!(!jwt.is_err());
+        let jwt = create_access_token(&user, &get_test_session());
+        assert!(jwt.is_ok());
         let jwt = jwt.unwrap();
-        assert!(jwt.len() > 0);
+        assert!(!jwt.is_empty());
 
         let jwt = token_to_claims(&jwt);
         assert_eq!(jwt.email, user.login_email);
         assert_eq!(jwt.sub, user.id);
         assert_eq!(jwt.role, user.role);
+        assert_eq!(jwt.sid, Some(SessionId(5)));
         // expires in about 15 minutes
         assert!(jwt.exp > Utc::now().timestamp() as usize + 15 * 60 - 5);
         assert!(jwt.exp < Utc::now().timestamp() as usize + 15 * 60 + 5);
+        assert!(!jwt.must_change_password);
+    }
+
+    #[test]
+    fn test_access_token_must_change_password() {
+        let mut user = get_test_user();
+        user.must_change_password = true;
+        let jwt = create_access_token(&user, &get_test_session()).unwrap();
+        assert!(token_to_claims(&jwt).must_change_password);
     }
 
     #[test]
     fn test_refresh_token() {
         let user = get_test_user();
-        let jwt = create_refresh_token(&user);
-        assert!(!jwt.is_err());
+        let jwt = create_refresh_token(&user, &get_test_session());
+        assert!(jwt.is_ok());
         let jwt = jwt.unwrap();
-        assert!(jwt.len() > 0);
+        assert!(!jwt.is_empty());
 
         let jwt = token_to_claims(&jwt);
         assert_eq!(jwt.email, "testy@mctestface.com");
-        assert_eq!(jwt.sub, 1);
+        assert_eq!(jwt.sub, UserId(1));
+        assert_eq!(jwt.sid, Some(SessionId(5)));
         // expires in about 7 days
         assert!(jwt.exp > Utc::now().timestamp() as usize + 60 * 60 * 24 * 7 - 5);
         assert!(jwt.exp < Utc::now().timestamp() as usize + 60 * 60 * 24 * 7 + 5);
@@ -129,14 +152,14 @@ mod tests {
     #[test]
     fn test_verify_fails_w_bad_signature() {
         let user = get_test_user();
-        let jwt = create_access_token(&user);
-        assert!(!jwt.is_err());
+        let jwt = create_access_token(&user, &get_test_session());
+        assert!(jwt.is_ok());
         let jwt = jwt.unwrap();
-        assert!(jwt.len() > 0);
+        assert!(!jwt.is_empty());
         let parts = jwt.split('.').collect::<Vec<&str>>();
         let sig = parts[2];
         let mut sig = sig.to_string();
-        sig.push_str("a");
+        sig.push('a');
         let jwt = format!("{}.{}.{}", parts[0], parts[1], sig);
         let claims = verify_and_extract_claims(&jwt);
         assert!(claims.is_none());
@@ -146,19 +169,19 @@ mod tests {
     fn test_verify_fails_on_manual_claim_change() {
         use base64::Engine;
         let user = get_test_user();
-        let jwt = create_access_token(&user);
-        assert!(!jwt.is_err());
+        let jwt = create_access_token(&user, &get_test_session());
+        assert!(jwt.is_ok());
         let jwt = jwt.unwrap();
-        assert!(jwt.len() > 0);
+        assert!(!jwt.is_empty());
         let parts = jwt.split('.').collect::<Vec<&str>>();
-        let buf = general_purpose::STANDARD_NO_PAD.decode(&parts[1]).unwrap();
+        let buf = general_purpose::STANDARD_NO_PAD.decode(parts[1]).unwrap();
         let mut claims = String::from_utf8(buf).unwrap();
 
         // change roles from user to admin
         claims = claims.replace("user", "admin");
 
         // back to base64
-        claims = general_purpose::STANDARD_NO_PAD.encode(&claims.as_bytes());
+        claims = general_purpose::STANDARD_NO_PAD.encode(claims.as_bytes());
 
         let jwt = format!("{}.{}.{}", parts[0], claims, parts[2]);
         let claims = verify_and_extract_claims(&jwt);
@@ -169,18 +192,18 @@ mod tests {
     fn test_verify_fails_on_algo_none() {
         use base64::Engine;
         let user = get_test_user();
-        let jwt = create_access_token(&user);
-        assert!(!jwt.is_err());
+        let jwt = create_access_token(&user, &get_test_session());
+        assert!(jwt.is_ok());
         let jwt = jwt.unwrap();
-        assert!(jwt.len() > 0);
+        assert!(!jwt.is_empty());
         let parts = jwt.split('.').collect::<Vec<&str>>();
         let header = parts[0];
 
         // change algo from HS512 to none
-        let buf = general_purpose::STANDARD_NO_PAD.decode(&header).unwrap();
+        let buf = general_purpose::STANDARD_NO_PAD.decode(header).unwrap();
         let mut header = String::from_utf8(buf).unwrap();
         header = header.replace("HS512", "none");
-        let header = general_purpose::STANDARD_NO_PAD.encode(&header.as_bytes());
+        let header = general_purpose::STANDARD_NO_PAD.encode(header.as_bytes());
 
         let jwt = format!("{}.{}.{}", header, parts[1], parts[2]);
 
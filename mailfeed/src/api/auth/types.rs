@@ -1,6 +1,11 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::security::{
+    password_policy::PasswordPolicy,
+    validation::{Validate, ValidationErrors},
+};
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("jwt creation error")]
@@ -13,15 +18,60 @@ pub enum Error {
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
+    /// from the authenticator app, or a recovery code, if the user turned
+    /// on two-factor authentication
+    #[serde(default)]
+    pub code: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TokenResponse<'a> {
     pub access_token: &'a str,
     pub refresh_token: &'a str,
+    /// set after an admin forced a password reset
+    pub must_change_password: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RefreshRequest {
     pub refresh_token: String,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+impl Validate for ChangePasswordRequest {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.new_password == self.current_password {
+            errors.add("new_password", "New password must be different");
+        } else if let Err(e) = PasswordPolicy::global().validate(&self.new_password) {
+            errors.add("new_password", e.to_string());
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetConfirm {
+    pub new_password: String,
+}
+
+impl Validate for PasswordResetConfirm {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Err(e) = PasswordPolicy::global().validate(&self.new_password) {
+            errors.add("new_password", e.to_string());
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetTokenPath {
+    pub token: String,
+}
@@ -0,0 +1,214 @@
+use std::{cell::RefCell, future::Future, pin::Pin, rc::Rc};
+
+use actix_web::{
+    body::{self, BodySize, BoxBody, MessageBody},
+    dev::{Payload, Service, ServiceRequest, ServiceResponse},
+    error::{ErrorInternalServerError, PayloadError},
+    web::{self, Bytes, BytesMut},
+    Error, HttpMessage,
+};
+use futures_util::{Stream, StreamExt};
+
+use crate::{models::body_logging::BodyLogging, security::redact::scrub, DbPool};
+
+/// Larger responses aren't held back to be logged, only their size is
+const MAX_BUFFERED_RESPONSE: u64 = 1024 * 1024;
+
+/// The start of a body, as much as was kept, and how long it was in all
+#[derive(Default)]
+struct Captured {
+    start: BytesMut,
+    total: usize,
+}
+
+/// A request whose bodies are being logged. The request body is copied as
+/// the handler reads it, so nothing is read that the handler wouldn't.
+pub struct Capture {
+    method: String,
+    path: String,
+    max_bytes: usize,
+    request: Rc<RefCell<Captured>>,
+}
+
+/// A body for the log: cut to `max_bytes`, with secrets and email
+/// addresses scrubbed as in any other log line
+pub fn describe(start: &[u8], total: usize) -> String {
+    if total == 0 {
+        return "(empty)".to_string();
+    }
+    let text = match std::str::from_utf8(start) {
+        Ok(text) => text,
+        // only cut off partway through a character
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&start[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return format!("({} bytes, not text)", total),
+    };
+    let text = scrub(text);
+    if start.len() < total {
+        format!("{}... ({} bytes in all)", text, total)
+    } else {
+        text
+    }
+}
+
+/// Start logging the request's bodies if the settings say to
+pub fn start(req: &mut ServiceRequest) -> Option<Capture> {
+    let mut conn = req.app_data::<web::Data<DbPool>>()?.get().ok()?;
+    // most of the time it's off, so that's all that's looked up
+    if !BodyLogging::is_enabled(&mut conn) {
+        return None;
+    }
+    let logging = BodyLogging::load(&mut conn);
+    if !logging.logs(req.path()) {
+        return None;
+    }
+
+    let request = Rc::new(RefCell::new(Captured::default()));
+    let max_bytes = logging.max_bytes;
+    let copy = request.clone();
+    let payload = req.take_payload().map(move |chunk| {
+        if let Ok(chunk) = &chunk {
+            let mut copy = copy.borrow_mut();
+            let room = max_bytes.saturating_sub(copy.start.len());
+            copy.start
+                .extend_from_slice(&chunk[..room.min(chunk.len())]);
+            copy.total += chunk.len();
+        }
+        chunk
+    });
+    let payload: Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>> = Box::pin(payload);
+    req.set_payload(Payload::from(payload));
+
+    Some(Capture {
+        method: req.method().to_string(),
+        path: req.path().to_string(),
+        max_bytes,
+        request,
+    })
+}
+
+/// Middleware for the API scope, logging bodies for the routes the
+/// settings name
+pub fn log_bodies<S>(
+    mut req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = Error>,
+{
+    let capture = start(&mut req);
+    let res = srv.call(req);
+    async move {
+        match capture {
+            Some(capture) => capture.finish(res.await?).await,
+            None => res.await,
+        }
+    }
+}
+
+impl Capture {
+    /// Log the request body and the response's, handing the response on
+    /// as it was
+    pub async fn finish(self, res: ServiceResponse) -> Result<ServiceResponse, Error> {
+        let request = {
+            let request = self.request.borrow();
+            describe(&request.start, request.total)
+        };
+        log::info!("{} {} request body: {}", self.method, self.path, request);
+
+        let status = res.status();
+        let (req, res) = res.into_parts();
+        let (res, body) = res.into_parts();
+        let body = match body.size() {
+            BodySize::None => {
+                log::info!(
+                    "{} {} {} response body: (empty)",
+                    self.method,
+                    self.path,
+                    status
+                );
+                body
+            }
+            BodySize::Sized(size) if size <= MAX_BUFFERED_RESPONSE => {
+                let bytes = body::to_bytes(body)
+                    .await
+                    .map_err(|e| ErrorInternalServerError(e.to_string()))?;
+                let start = &bytes[..self.max_bytes.min(bytes.len())];
+                log::info!(
+                    "{} {} {} response body: {}",
+                    self.method,
+                    self.path,
+                    status,
+                    describe(start, bytes.len())
+                );
+                BoxBody::new(bytes)
+            }
+            size => {
+                log::info!(
+                    "{} {} {} response body not logged ({:?})",
+                    self.method,
+                    self.path,
+                    status,
+                    size
+                );
+                body
+            }
+        };
+        Ok(ServiceResponse::new(req, res.set_body(body)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_pool;
+    use actix_web::{
+        test::{call_and_read_body, init_service, TestRequest},
+        App,
+    };
+
+    #[actix_web::test]
+    async fn test_bodies_pass_through() {
+        let pool = get_test_db_pool();
+        let mut conn = pool.get().unwrap();
+        BodyLogging {
+            enabled: true,
+            routes: vec!["/api/echo".to_string()],
+            max_bytes: 4,
+        }
+        .save(&mut conn)
+        .unwrap();
+        drop(conn);
+
+        let app = init_service(
+            App::new().app_data(web::Data::new(pool)).service(
+                web::scope("/api")
+                    .wrap_fn(log_bodies)
+                    .route("/echo", web::post().to(|body: Bytes| async move { body })),
+            ),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri("/api/echo")
+            .set_payload("longer than four bytes")
+            .to_request();
+        let body = call_and_read_body(&app, req).await;
+        assert_eq!(body, "longer than four bytes");
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe(b"", 0), "(empty)");
+        assert_eq!(
+            describe(br#"{"name":"x","api_key":"abc123"}"#, 31),
+            r#"{"name":"x","api_key":"[redacted]"}"#
+        );
+        // cut partway through the é
+        assert_eq!(
+            describe(&"café".as_bytes()[..4], 5),
+            "caf... (5 bytes in all)"
+        );
+        assert_eq!(describe(&[0xff, 0xfe, 0x00], 3), "(3 bytes, not text)");
+    }
+}
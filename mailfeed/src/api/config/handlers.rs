@@ -0,0 +1,581 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder, ResponseError};
+use chrono::Utc;
+use diesel::QueryResult;
+
+use super::types::{
+    ImportReport, ImportResult, ImportStatus, Preferences, SavedSearchConfig, SubscriptionConfig,
+    TemplateConfig, UserConfig, CONFIG_VERSION,
+};
+use crate::{
+    api::{
+        admin::check_access_for, searches::MAX_SAVED_SEARCHES, templates::MAX_TEMPLATES,
+        users::RqUserId,
+    },
+    claims::Claims,
+    db::DbConnection,
+    models::{
+        bookmark_settings::BookmarkSettings,
+        delivery_window::DeliveryWindow,
+        digest_skips::DigestSkips,
+        feed::{Feed, NewFeed},
+        ids::UserId,
+        keyword_filter::Keywords,
+        quotas::Quotas,
+        saved_search::{PartialSavedSearch, SavedSearch},
+        subscription::{NewSubscription, Subscription},
+        subscription_template::SubscriptionTemplate,
+        trends::TrendSettings,
+        user::{User, UserQuery},
+    },
+    security::validation::{Validate, ValidationErrors},
+    RqDbPool,
+};
+
+/// The user's whole configuration as one JSON document, without secrets.
+/// Admin or the given user.
+#[get("")]
+pub async fn export_config(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if user_id != claims.sub && !claims.role.is_admin() {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, user_id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let user = match User::get(&mut conn, UserQuery::Id(user_id)) {
+        Some(user) => user,
+        None => return HttpResponse::NotFound().body("User not found"),
+    };
+
+    match export(&mut conn, &user, Utc::now().timestamp()) {
+        Ok(config) => HttpResponse::Ok()
+            .insert_header((
+                "Content-Disposition",
+                "attachment; filename=\"mailfeed-config.json\"",
+            ))
+            .json(config),
+        Err(e) => {
+            log::error!("Error exporting config for user {}: {:?}", user_id, e);
+            HttpResponse::InternalServerError().body("Error exporting configuration")
+        }
+    }
+}
+
+/// Apply an exported document on top of the user's configuration. Settings
+/// in it replace the user's; subscriptions to feeds they already have, and
+/// templates and saved searches with names they already use, are skipped.
+#[post("")]
+pub async fn import_config(
+    pool: RqDbPool,
+    path: RqUserId,
+    config: web::Json<UserConfig>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = config.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let report = import(&mut conn, user_id, &config, Utc::now().timestamp());
+    log::info!(
+        "Imported config for user {}: {} subscriptions, {} templates, {} saved searches",
+        user_id,
+        count_added(&report.subscriptions),
+        count_added(&report.templates),
+        count_added(&report.saved_searches)
+    );
+    HttpResponse::Ok().json(report)
+}
+
+fn export(conn: &mut DbConnection, user: &User, now: i64) -> QueryResult<UserConfig> {
+    let mut subscriptions = Vec::new();
+    for sub in Subscription::get_all_for_user(conn, user.id)? {
+        match Feed::get_by_id(conn, sub.feed_id) {
+            Some(feed) => subscriptions.push(SubscriptionConfig::from_subscription(&sub, &feed)),
+            None => log::warn!("Feed {} of subscription {} not found", sub.feed_id, sub.id),
+        }
+    }
+    let templates = SubscriptionTemplate::get_for_user(conn, user.id)?
+        .iter()
+        .map(TemplateConfig::from_template)
+        .collect();
+    let saved_searches = SavedSearch::get_for_user(conn, user.id)?
+        .iter()
+        .map(SavedSearchConfig::from_search)
+        .collect();
+
+    Ok(UserConfig {
+        version: CONFIG_VERSION,
+        exported_at: now,
+        preferences: Some(Preferences::from_user(user)),
+        digest_skips: Some(DigestSkips::load(conn, user.id)),
+        trend_settings: Some(TrendSettings::load(conn, user.id)),
+        bookmark_settings: Some(BookmarkSettings::load(conn, user.id)),
+        subscriptions,
+        templates,
+        saved_searches,
+    })
+}
+
+fn import(conn: &mut DbConnection, user_id: UserId, config: &UserConfig, now: i64) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    if let Some(preferences) = &config.preferences {
+        match User::update(conn, user_id, &preferences.to_update()) {
+            Ok(_) => report.settings.push("preferences"),
+            Err(e) => log::error!("Error importing preferences: {:?}", e),
+        }
+    }
+    if let Some(skips) = &config.digest_skips {
+        match skips.save(conn, user_id) {
+            Ok(()) => report.settings.push("digest_skips"),
+            Err(e) => log::error!("Error importing digest skips: {}", e),
+        }
+    }
+    if let Some(trends) = &config.trend_settings {
+        match trends.save(conn, user_id) {
+            Ok(()) => report.settings.push("trend_settings"),
+            Err(e) => log::error!("Error importing trend settings: {}", e),
+        }
+    }
+    if let Some(bookmarks) = &config.bookmark_settings {
+        // exports don't have the token, so keep the user's own
+        let bookmarks = BookmarkSettings {
+            token: None,
+            ..bookmarks.clone()
+        };
+        match bookmarks.save(conn, user_id) {
+            Ok(()) => report.settings.push("bookmark_settings"),
+            Err(e) => log::error!("Error importing bookmark settings: {}", e),
+        }
+    }
+
+    report.subscriptions = config
+        .subscriptions
+        .iter()
+        .map(|sub| import_subscription(conn, user_id, sub))
+        .collect();
+    report.templates = import_templates(conn, user_id, &config.templates, now);
+    report.saved_searches = import_saved_searches(conn, user_id, &config.saved_searches, now);
+    report
+}
+
+/// Like subscribing through the API, but new feeds aren't fetched until the
+/// feed monitor gets to them
+fn import_subscription(
+    conn: &mut DbConnection,
+    user_id: UserId,
+    sub: &SubscriptionConfig,
+) -> ImportResult {
+    let url = sub.url.as_str();
+    if let Err(errors) = sub.validate() {
+        return failed(url, describe(&errors));
+    }
+
+    let existing_feed = Feed::get_by_url(conn, url);
+    if let Some(feed) = &existing_feed {
+        match Subscription::get_for_user_and_feed(conn, user_id, feed.id) {
+            Ok(None) => {}
+            Ok(Some(_)) => return skipped(url, "Already subscribed"),
+            Err(_) => return failed(url, "Error getting subscriptions"),
+        }
+    }
+
+    let realtime = sub.frequency.is_realtime();
+    if let Err(e) =
+        Quotas::load(conn).check_new_subscription(conn, user_id, realtime, existing_feed.is_none())
+    {
+        return failed(url, e.to_string());
+    }
+
+    let feed = match existing_feed {
+        Some(feed) => feed,
+        None => {
+            let new_feed = NewFeed {
+                url,
+                ..Default::default()
+            };
+            match new_feed.insert(conn) {
+                Some(feed) => feed,
+                None => return failed(url, "Error creating feed"),
+            }
+        }
+    };
+
+    let new_sub = NewSubscription {
+        user_id,
+        feed_id: feed.id,
+        friendly_name: sub.friendly_name.clone(),
+        frequency: sub.frequency.clone(),
+        max_items: sub.max_items,
+        is_active: sub.is_active,
+        description: sub.description.clone(),
+        homepage: sub.homepage.clone(),
+        send_email: sub.send_email.clone(),
+        subject_prefix: sub.subject_prefix.clone(),
+        subject_template: sub.subject_template.clone(),
+        show_stats: sub.show_stats,
+        min_score: sub.min_score.filter(|n| *n > 0),
+        min_comments: sub.min_comments.filter(|n| *n > 0),
+        delivery_window: sub
+            .delivery_window
+            .as_deref()
+            .and_then(DeliveryWindow::parse)
+            .map(|window| window.to_string()),
+        include_keywords: Keywords::trimmed(&sub.include_keywords),
+        exclude_keywords: Keywords::trimmed(&sub.exclude_keywords),
+        delivery_method: sub.delivery_method,
+        max_item_age_days: sub.max_item_age_days.filter(|n| *n > 0),
+        ..Default::default()
+    };
+    match new_sub.insert(conn) {
+        Some(_) => added(url),
+        None => failed(url, "Error creating subscription"),
+    }
+}
+
+fn import_templates(
+    conn: &mut DbConnection,
+    user_id: UserId,
+    templates: &[TemplateConfig],
+    now: i64,
+) -> Vec<ImportResult> {
+    let mut names: Vec<String> = match SubscriptionTemplate::get_for_user(conn, user_id) {
+        Ok(existing) => existing.into_iter().map(|template| template.name).collect(),
+        Err(e) => {
+            log::error!("Error getting subscription templates: {:?}", e);
+            return templates
+                .iter()
+                .map(|template| failed(&template.name, "Error getting templates"))
+                .collect();
+        }
+    };
+
+    let mut results = Vec::new();
+    for template in templates {
+        let name = template.name.as_str();
+        if names.iter().any(|existing| existing == name) {
+            results.push(skipped(name, "A template with this name exists"));
+            continue;
+        }
+        let new_template = template.to_new(user_id, now);
+        if let Err(errors) = new_template.validate() {
+            results.push(failed(name, describe(&errors)));
+            continue;
+        }
+        if names.len() as i64 >= MAX_TEMPLATES {
+            results.push(failed(
+                name,
+                format!(
+                    "At most {} subscription templates are allowed",
+                    MAX_TEMPLATES
+                ),
+            ));
+            continue;
+        }
+        match new_template.insert(conn) {
+            Ok(_) => {
+                names.push(name.to_string());
+                results.push(added(name));
+            }
+            Err(e) => {
+                log::error!("Error importing subscription template: {:?}", e);
+                results.push(failed(name, "Error creating subscription template"));
+            }
+        }
+    }
+    results
+}
+
+fn import_saved_searches(
+    conn: &mut DbConnection,
+    user_id: UserId,
+    searches: &[SavedSearchConfig],
+    now: i64,
+) -> Vec<ImportResult> {
+    let mut names: Vec<String> = match SavedSearch::get_for_user(conn, user_id) {
+        Ok(existing) => existing.into_iter().map(|search| search.name).collect(),
+        Err(e) => {
+            log::error!("Error getting saved searches: {:?}", e);
+            return searches
+                .iter()
+                .map(|search| failed(&search.name, "Error getting saved searches"))
+                .collect();
+        }
+    };
+
+    let mut results = Vec::new();
+    for search in searches {
+        let name = search.name.as_str();
+        if names.iter().any(|existing| existing == name) {
+            results.push(skipped(name, "A saved search with this name exists"));
+            continue;
+        }
+        let new_search = search.to_new(user_id, now);
+        if let Err(errors) = new_search.validate() {
+            results.push(failed(name, describe(&errors)));
+            continue;
+        }
+        if names.len() as i64 >= MAX_SAVED_SEARCHES {
+            results.push(failed(
+                name,
+                format!("At most {} saved searches are allowed", MAX_SAVED_SEARCHES),
+            ));
+            continue;
+        }
+        let inserted = new_search.insert(conn).and_then(|inserted| {
+            if search.is_active {
+                return Ok(inserted);
+            }
+            let pause = PartialSavedSearch {
+                is_active: Some(false),
+                ..Default::default()
+            };
+            SavedSearch::update(conn, user_id, inserted.id, &pause)
+        });
+        match inserted {
+            Ok(_) => {
+                names.push(name.to_string());
+                results.push(added(name));
+            }
+            Err(e) => {
+                log::error!("Error importing saved search: {:?}", e);
+                results.push(failed(name, "Error creating saved search"));
+            }
+        }
+    }
+    results
+}
+
+fn added(name: &str) -> ImportResult {
+    ImportResult::new(name, ImportStatus::Added, None)
+}
+
+fn skipped(name: &str, message: impl Into<String>) -> ImportResult {
+    ImportResult::new(name, ImportStatus::Skipped, Some(message.into()))
+}
+
+fn failed(name: &str, message: impl Into<String>) -> ImportResult {
+    ImportResult::new(name, ImportStatus::Failed, Some(message.into()))
+}
+
+/// One line for the report, e.g. `url: Invalid URL; max_items: ...`
+fn describe(errors: &ValidationErrors) -> String {
+    errors
+        .errors
+        .iter()
+        .map(|error| format!("{}: {}", error.field, error.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn count_added(results: &[ImportResult]) -> usize {
+    results
+        .iter()
+        .filter(|result| result.status == ImportStatus::Added)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{
+            role::Role,
+            saved_search::{NewSavedSearch, NotifyBy},
+            subscription::{DeliveryMethod, Frequency},
+            subscription_template::NewSubscriptionTemplate,
+            user::NewUser,
+        },
+        test_helpers::test_helpers::get_test_db_connection,
+    };
+
+    fn create_user(conn: &mut DbConnection, email: &str) -> User {
+        let new_user = NewUser {
+            email: email.to_string(),
+            password: "correct horse".to_string(),
+        };
+        let claims = Claims {
+            sub: UserId(0),
+            email: email.to_string(),
+            role: Role::Admin.into(),
+            exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
+        };
+        User::create(conn, &new_user, claims).unwrap();
+        User::get(conn, UserQuery::Email(email)).unwrap()
+    }
+
+    fn statuses(results: &[ImportResult]) -> Vec<ImportStatus> {
+        results.iter().map(|result| result.status).collect()
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let mut conn = get_test_db_connection();
+        let alice = create_user(&mut conn, "alice@example.com");
+        let bob = create_user(&mut conn, "bob@example.com");
+
+        let feed = NewFeed {
+            url: "https://example.com/feed",
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        NewSubscription {
+            user_id: alice.id,
+            feed_id: feed.id,
+            friendly_name: "Example".to_string(),
+            exclude_keywords: Keywords(vec!["sponsored".to_string()]),
+            delivery_method: DeliveryMethod::Webhook,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        NewSubscriptionTemplate {
+            user_id: alice.id,
+            name: "Hourly".to_string(),
+            frequency: Frequency::Hourly,
+            max_items: 10,
+            send_email: None,
+            subject_prefix: None,
+            subject_template: None,
+            show_stats: false,
+            min_score: None,
+            min_comments: None,
+            delivery_window: None,
+            created_at: 0,
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let search = NewSavedSearch {
+            user_id: alice.id,
+            name: "Rust".to_string(),
+            query: "rust".to_string(),
+            notify_by: NotifyBy::Email,
+            telegram_chat_id: None,
+            telegram_photos: false,
+            created_at: 0,
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let pause = PartialSavedSearch {
+            is_active: Some(false),
+            ..Default::default()
+        };
+        SavedSearch::update(&mut conn, alice.id, search.id, &pause).unwrap();
+        let skips = DigestSkips {
+            skip_weekends: true,
+            skip_dates: vec![],
+        };
+        skips.save(&mut conn, alice.id).unwrap();
+
+        // through JSON, as it would be downloaded and uploaded
+        let exported = serde_json::to_string(&export(&mut conn, &alice, 1000).unwrap()).unwrap();
+        assert!(!exported.contains("password"));
+        let config: UserConfig = serde_json::from_str(&exported).unwrap();
+        assert!(config.validate().is_ok());
+
+        let report = import(&mut conn, bob.id, &config, 2000);
+        assert_eq!(
+            report.settings,
+            vec![
+                "preferences",
+                "digest_skips",
+                "trend_settings",
+                "bookmark_settings"
+            ]
+        );
+        assert_eq!(statuses(&report.subscriptions), vec![ImportStatus::Added]);
+        assert_eq!(statuses(&report.templates), vec![ImportStatus::Added]);
+        assert_eq!(statuses(&report.saved_searches), vec![ImportStatus::Added]);
+
+        let subs = Subscription::get_all_for_user(&mut conn, bob.id).unwrap();
+        assert_eq!(subs.len(), 1);
+        assert_eq!(subs[0].feed_id, feed.id);
+        assert_eq!(subs[0].exclude_keywords.0, vec!["sponsored".to_string()]);
+        assert_eq!(subs[0].delivery_method, DeliveryMethod::Webhook);
+        assert!(!SavedSearch::get_for_user(&mut conn, bob.id).unwrap()[0].is_active);
+        assert!(DigestSkips::load(&mut conn, bob.id).skip_weekends);
+        // the login email isn't part of the configuration
+        let bob = User::get(&mut conn, UserQuery::Id(bob.id)).unwrap();
+        assert_eq!(bob.login_email, "bob@example.com");
+        assert_eq!(bob.send_email, "alice@example.com");
+
+        // importing again changes nothing
+        let report = import(&mut conn, bob.id, &config, 3000);
+        assert_eq!(statuses(&report.subscriptions), vec![ImportStatus::Skipped]);
+        assert_eq!(statuses(&report.templates), vec![ImportStatus::Skipped]);
+        assert_eq!(
+            statuses(&report.saved_searches),
+            vec![ImportStatus::Skipped]
+        );
+    }
+
+    #[test]
+    fn test_invalid_entries_fail_alone() {
+        let mut conn = get_test_db_connection();
+        let user = create_user(&mut conn, "carol@example.com");
+        let config: UserConfig = serde_json::from_str(
+            r#"{
+                "version": 1,
+                "subscriptions": [
+                    {"url": "not a url", "friendly_name": "", "frequency": "daily", "is_active": true},
+                    {"url": "https://example.com/ok", "friendly_name": "", "frequency": "daily", "is_active": true}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let report = import(&mut conn, user.id, &config, 1000);
+        assert!(report.settings.is_empty());
+        assert_eq!(
+            statuses(&report.subscriptions),
+            vec![ImportStatus::Failed, ImportStatus::Added]
+        );
+        assert!(report.subscriptions[0]
+            .message
+            .as_deref()
+            .unwrap()
+            .starts_with("url: "));
+
+        let config: UserConfig = serde_json::from_str(r#"{"version": 2}"#).unwrap();
+        assert!(config.validate().is_err());
+    }
+}
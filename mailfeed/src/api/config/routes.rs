@@ -0,0 +1,8 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/users/{user_id}/config")
+        .service(handlers::export_config)
+        .service(handlers::import_config)
+}
@@ -0,0 +1,312 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::{
+    bookmark_settings::BookmarkSettings,
+    digest_skips::DigestSkips,
+    feed::Feed,
+    ids::UserId,
+    keyword_filter::Keywords,
+    saved_search::{NewSavedSearch, NotifyBy, SavedSearch},
+    subscription::{DeliveryMethod, Frequency, Subscription},
+    subscription_template::{NewSubscriptionTemplate, SubscriptionTemplate},
+    trends::TrendSettings,
+    user::{PartialUser, User},
+};
+use crate::security::validation::{Validate, ValidationErrors};
+
+/// Version of the exported document, bumped if it changes incompatibly
+pub const CONFIG_VERSION: u32 = 1;
+/// Most subscriptions imported from one document
+pub const MAX_IMPORT_SUBSCRIPTIONS: usize = 1000;
+
+/// Everything a user has set up, for moving it to another instance. Secrets
+/// like passwords, access tokens, two-factor keys, webhooks and share links
+/// aren't included. Sections left out of an imported document are left
+/// alone.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserConfig {
+    pub version: u32,
+    #[serde(default)]
+    pub exported_at: i64,
+    pub preferences: Option<Preferences>,
+    pub digest_skips: Option<DigestSkips>,
+    pub trend_settings: Option<TrendSettings>,
+    /// without the token, which importing keeps
+    pub bookmark_settings: Option<BookmarkSettings>,
+    #[serde(default)]
+    pub subscriptions: Vec<SubscriptionConfig>,
+    #[serde(default)]
+    pub templates: Vec<TemplateConfig>,
+    #[serde(default)]
+    pub saved_searches: Vec<SavedSearchConfig>,
+}
+
+impl Validate for UserConfig {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.version != CONFIG_VERSION {
+            errors.add(
+                "version",
+                format!("Unsupported version, expected {}", CONFIG_VERSION),
+            );
+        }
+        if let Some(preferences) = &self.preferences {
+            preferences.to_update().check(errors);
+        }
+        if let Some(skips) = &self.digest_skips {
+            skips.check(errors);
+        }
+        if let Some(bookmarks) = &self.bookmark_settings {
+            bookmarks.check(errors);
+        }
+        if self.subscriptions.len() > MAX_IMPORT_SUBSCRIPTIONS {
+            errors.add(
+                "subscriptions",
+                format!("At most {} subscriptions", MAX_IMPORT_SUBSCRIPTIONS),
+            );
+        }
+    }
+}
+
+/// The user's delivery settings; their login email and role aren't
+/// included
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Preferences {
+    pub send_email: String,
+    pub daily_send_time: String,
+    pub item_truncate_length: i32,
+    pub from_name: Option<String>,
+    pub subject_template: Option<String>,
+    /// IANA time zone, see User
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+impl Preferences {
+    pub fn from_user(user: &User) -> Self {
+        Preferences {
+            send_email: user.send_email.clone(),
+            daily_send_time: user.daily_send_time.clone(),
+            item_truncate_length: user.item_truncate_length,
+            from_name: user.from_name.clone(),
+            subject_template: user.subject_template.clone(),
+            timezone: user.timezone.clone(),
+        }
+    }
+
+    /// Empty strings clear the user's from name, subject template and time
+    /// zone
+    pub fn to_update(&self) -> PartialUser {
+        PartialUser {
+            send_email: Some(self.send_email.clone()),
+            daily_send_time: Some(self.daily_send_time.clone()),
+            item_truncate_length: Some(self.item_truncate_length),
+            from_name: Some(self.from_name.clone().unwrap_or_default()),
+            subject_template: Some(self.subject_template.clone().unwrap_or_default()),
+            timezone: Some(self.timezone.clone().unwrap_or_default()),
+            ..Default::default()
+        }
+    }
+}
+
+/// A subscription and its feed's URL
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscriptionConfig {
+    pub url: String,
+    pub friendly_name: String,
+    pub frequency: Frequency,
+    #[serde(default)]
+    pub max_items: i32,
+    pub is_active: bool,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub send_email: Option<String>,
+    pub subject_prefix: Option<String>,
+    pub subject_template: Option<String>,
+    #[serde(default)]
+    pub show_stats: bool,
+    pub min_score: Option<i32>,
+    pub min_comments: Option<i32>,
+    pub delivery_window: Option<String>,
+    #[serde(default)]
+    pub include_keywords: Keywords,
+    #[serde(default)]
+    pub exclude_keywords: Keywords,
+    /// webhook subscriptions wait until the user sets up their delivery
+    /// webhook, which isn't exported
+    #[serde(default)]
+    pub delivery_method: DeliveryMethod,
+    #[serde(default)]
+    pub max_item_age_days: Option<i32>,
+}
+
+impl SubscriptionConfig {
+    pub fn from_subscription(sub: &Subscription, feed: &Feed) -> Self {
+        SubscriptionConfig {
+            url: feed.url.clone(),
+            friendly_name: sub.friendly_name.clone(),
+            frequency: sub.frequency.clone(),
+            max_items: sub.max_items,
+            is_active: sub.is_active,
+            description: sub.description.clone(),
+            homepage: sub.homepage.clone(),
+            send_email: sub.send_email.clone(),
+            subject_prefix: sub.subject_prefix.clone(),
+            subject_template: sub.subject_template.clone(),
+            show_stats: sub.show_stats,
+            min_score: sub.min_score,
+            min_comments: sub.min_comments,
+            delivery_window: sub.delivery_window.clone(),
+            include_keywords: sub.include_keywords.clone(),
+            exclude_keywords: sub.exclude_keywords.clone(),
+            delivery_method: sub.delivery_method,
+            max_item_age_days: sub.max_item_age_days,
+        }
+    }
+}
+
+impl Validate for SubscriptionConfig {
+    fn check(&self, errors: &mut ValidationErrors) {
+        errors.url("url", &self.url);
+        errors.frequency("frequency", &self.frequency);
+        if let Some(send_email) = &self.send_email {
+            errors.email("send_email", send_email);
+        }
+        if let Some(template) = &self.subject_template {
+            errors.subject_template("subject_template", template);
+        }
+        errors.non_negative("max_items", Some(self.max_items));
+        errors.non_negative("min_score", self.min_score);
+        errors.non_negative("min_comments", self.min_comments);
+        errors.max_item_age("max_item_age_days", self.max_item_age_days);
+        if let Some(window) = &self.delivery_window {
+            errors.delivery_window("delivery_window", window);
+        }
+        errors.keywords("include_keywords", &self.include_keywords);
+        errors.keywords("exclude_keywords", &self.exclude_keywords);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TemplateConfig {
+    pub name: String,
+    pub frequency: Frequency,
+    #[serde(default)]
+    pub max_items: i32,
+    pub send_email: Option<String>,
+    pub subject_prefix: Option<String>,
+    pub subject_template: Option<String>,
+    #[serde(default)]
+    pub show_stats: bool,
+    pub min_score: Option<i32>,
+    pub min_comments: Option<i32>,
+    pub delivery_window: Option<String>,
+}
+
+impl TemplateConfig {
+    pub fn from_template(template: &SubscriptionTemplate) -> Self {
+        TemplateConfig {
+            name: template.name.clone(),
+            frequency: template.frequency.clone(),
+            max_items: template.max_items,
+            send_email: template.send_email.clone(),
+            subject_prefix: template.subject_prefix.clone(),
+            subject_template: template.subject_template.clone(),
+            show_stats: template.show_stats,
+            min_score: template.min_score,
+            min_comments: template.min_comments,
+            delivery_window: template.delivery_window.clone(),
+        }
+    }
+
+    pub fn to_new(&self, user_id: UserId, now: i64) -> NewSubscriptionTemplate {
+        NewSubscriptionTemplate {
+            user_id,
+            name: self.name.clone(),
+            frequency: self.frequency.clone(),
+            max_items: self.max_items,
+            send_email: self.send_email.clone(),
+            subject_prefix: self.subject_prefix.clone(),
+            subject_template: self.subject_template.clone(),
+            show_stats: self.show_stats,
+            min_score: self.min_score,
+            min_comments: self.min_comments,
+            delivery_window: self.delivery_window.clone(),
+            created_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedSearchConfig {
+    pub name: String,
+    pub query: String,
+    pub notify_by: NotifyBy,
+    pub telegram_chat_id: Option<String>,
+    #[serde(default)]
+    pub telegram_photos: bool,
+    pub is_active: bool,
+}
+
+impl SavedSearchConfig {
+    pub fn from_search(search: &SavedSearch) -> Self {
+        SavedSearchConfig {
+            name: search.name.clone(),
+            query: search.query.clone(),
+            notify_by: search.notify_by,
+            telegram_chat_id: search.telegram_chat_id.clone(),
+            telegram_photos: search.telegram_photos,
+            is_active: search.is_active,
+        }
+    }
+
+    pub fn to_new(&self, user_id: UserId, now: i64) -> NewSavedSearch {
+        NewSavedSearch {
+            user_id,
+            name: self.name.clone(),
+            query: self.query.clone(),
+            notify_by: self.notify_by,
+            telegram_chat_id: self.telegram_chat_id.clone(),
+            telegram_photos: self.telegram_photos,
+            created_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Added,
+    /// already there, e.g. a subscription to the same feed or a template
+    /// with the same name
+    Skipped,
+    Failed,
+}
+
+/// What happened to one subscription, template or saved search
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ImportResult {
+    /// the feed URL, or the template's or search's name
+    pub name: String,
+    pub status: ImportStatus,
+    pub message: Option<String>,
+}
+
+impl ImportResult {
+    pub fn new(name: &str, status: ImportStatus, message: Option<String>) -> Self {
+        ImportResult {
+            name: name.to_string(),
+            status,
+            message,
+        }
+    }
+}
+
+/// Which parts of an imported document were applied
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    /// the settings sections that were replaced
+    pub settings: Vec<&'static str>,
+    pub subscriptions: Vec<ImportResult>,
+    pub templates: Vec<ImportResult>,
+    pub saved_searches: Vec<ImportResult>,
+}
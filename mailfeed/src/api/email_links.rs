@@ -0,0 +1,5 @@
+mod handlers;
+mod routes;
+mod types;
+
+pub use self::routes::page_routes;
@@ -0,0 +1,202 @@
+use actix_web::{get, http::header, post, HttpResponse, Responder};
+use askama::Template;
+
+use super::types::{ItemLinkPath, LinkPage, RqItemLinkPath, RqItemLinkQuery, RqVerifyQuery};
+use crate::{
+    api::shares::web_link,
+    db::DbConnection,
+    models::{
+        feed_item::FeedItem,
+        ids::UserId,
+        read_item::ReadItem,
+        smtp_verification::SmtpVerification,
+        starred_item::StarredItem,
+        subscription::Subscription,
+        user::{User, UserQuery},
+    },
+    security::item_links::{ItemAction, ItemLinks},
+    RqDbPool,
+};
+
+enum LinkError {
+    NotFound,
+    Internal(&'static str),
+}
+
+impl LinkError {
+    fn response(&self) -> HttpResponse {
+        match self {
+            LinkError::NotFound => HttpResponse::NotFound().body("Link not found"),
+            LinkError::Internal(message) => HttpResponse::InternalServerError().body(*message),
+        }
+    }
+}
+
+/// The user and item a link is for, if it's signed and the user can still
+/// see the item. Bad links, and those of deactivated users, are all just
+/// not found.
+fn linked_item(
+    conn: &mut DbConnection,
+    path: &ItemLinkPath,
+    user: &str,
+    sig: &str,
+) -> Result<(UserId, FeedItem), LinkError> {
+    let item_id = path
+        .item_id
+        .parse::<i32>()
+        .map_err(|_| LinkError::NotFound)?;
+    let user_id = user.parse::<UserId>().map_err(|_| LinkError::NotFound)?;
+    let links = ItemLinks::global().ok_or(LinkError::Internal("Links aren't set up"))?;
+    if !links.verify(user_id, item_id, path.action, sig) {
+        log::warn!("Bad signature on email link for item {}", item_id);
+        return Err(LinkError::NotFound);
+    }
+
+    match User::get(conn, UserQuery::Id(user_id)) {
+        Some(user) if user.is_active => {}
+        _ => return Err(LinkError::NotFound),
+    }
+    let item = FeedItem::get_by_id(conn, item_id).ok_or(LinkError::NotFound)?;
+    // the item may have been pruned, or the user unsubscribed since
+    match Subscription::get_for_user_and_feed(conn, user_id, item.feed_id) {
+        Ok(Some(_)) => Ok((user_id, item)),
+        Ok(None) => Err(LinkError::NotFound),
+        Err(_) => Err(LinkError::Internal("Error getting subscriptions")),
+    }
+}
+
+fn render(page: LinkPage) -> HttpResponse {
+    match page.render() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            // keep the signature out of the Referer sent to the item's site
+            .insert_header((header::REFERRER_POLICY, "no-referrer"))
+            .body(body),
+        Err(e) => {
+            log::error!("Error rendering email link page: {:?}", e);
+            HttpResponse::InternalServerError().body("Error rendering page")
+        }
+    }
+}
+
+/// Asks before acting, since some mail providers open every link in an
+/// email to check it, which would otherwise mark everything read
+#[get("/items/{item_id}/{action}")]
+pub async fn item_action_page(
+    pool: RqDbPool,
+    path: RqItemLinkPath,
+    query: RqItemLinkQuery,
+) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let (_, item) = match linked_item(&mut conn, &path, &query.user, &query.sig) {
+        Ok(linked) => linked,
+        Err(e) => return e.response(),
+    };
+    let (heading, button) = match path.action {
+        ItemAction::Read => ("Mark as read?", "Mark as read"),
+        ItemAction::Star => ("Star this item?", "Star"),
+    };
+    render(LinkPage {
+        heading,
+        title: &item.title,
+        link: web_link(&item.link),
+        button: Some(button),
+    })
+}
+
+#[post("/items/{item_id}/{action}")]
+pub async fn apply_item_action(
+    pool: RqDbPool,
+    path: RqItemLinkPath,
+    query: RqItemLinkQuery,
+) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let (user_id, item) = match linked_item(&mut conn, &path, &query.user, &query.sig) {
+        Ok(linked) => linked,
+        Err(e) => return e.response(),
+    };
+    let now = chrono::Utc::now().timestamp();
+    let (done, heading) = match path.action {
+        ItemAction::Read => (
+            ReadItem::mark_read(&mut conn, user_id, item.id, now).map(|_| ()),
+            "Marked as read",
+        ),
+        ItemAction::Star => (
+            StarredItem::star(&mut conn, user_id, item.id, now).map(|_| ()),
+            "Starred",
+        ),
+    };
+    if let Err(e) = done {
+        log::error!("Error applying email link for item {}: {:?}", item.id, e);
+        return HttpResponse::InternalServerError().body("Error updating item");
+    }
+    render(LinkPage {
+        heading,
+        title: &item.title,
+        link: web_link(&item.link),
+        button: None,
+    })
+}
+
+/// The link in the email sent through new SMTP settings. Opening it is
+/// enough, since the email arriving is what shows they work.
+#[get("/smtp/verify")]
+pub async fn verify_smtp(pool: RqDbPool, query: RqVerifyQuery) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match SmtpVerification::verify(&mut conn, &query.token) {
+        Ok(true) => {
+            log::info!("New SMTP settings verified, sending digests again");
+            render(LinkPage {
+                heading: "Email settings verified",
+                title: "Held digests go out at the next check.",
+                link: None,
+                button: None,
+            })
+        }
+        Ok(false) => HttpResponse::NotFound().body("Link not found"),
+        Err(e) => {
+            log::error!("Error verifying SMTP settings: {:?}", e);
+            HttpResponse::InternalServerError().body("Error verifying settings")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_escapes_item_title() {
+        let page = LinkPage {
+            heading: "Star this item?",
+            title: "<script>alert(1)</script>",
+            link: Some("https://a.com/?a=1&b=2"),
+            button: Some("Star"),
+        };
+        let html = page.render().unwrap();
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<form method=\"post\">"));
+    }
+}
@@ -0,0 +1,11 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+/// The pages the links in emails open, outside `/api` and without
+/// authentication, since the signature or token stands in for it
+pub fn page_routes() -> Scope {
+    web::scope("/email")
+        .service(handlers::item_action_page)
+        .service(handlers::apply_item_action)
+        .service(handlers::verify_smtp)
+}
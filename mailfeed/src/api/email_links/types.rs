@@ -0,0 +1,36 @@
+use actix_web::web;
+use askama::Template;
+use serde::Deserialize;
+
+use crate::security::item_links::ItemAction;
+
+#[derive(Debug, Deserialize)]
+pub struct ItemLinkPath {
+    pub item_id: String,
+    pub action: ItemAction,
+}
+pub type RqItemLinkPath = web::Path<ItemLinkPath>;
+
+#[derive(Debug, Deserialize)]
+pub struct ItemLinkQuery {
+    pub user: String,
+    pub sig: String,
+}
+pub type RqItemLinkQuery = web::Query<ItemLinkQuery>;
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyQuery {
+    pub token: String,
+}
+pub type RqVerifyQuery = web::Query<VerifyQuery>;
+
+/// What a link in an email opens: asks before acting, or says it's done
+#[derive(Template)]
+#[template(path = "email_link.html")]
+pub struct LinkPage<'a> {
+    pub heading: &'a str,
+    pub title: &'a str,
+    pub link: Option<&'a str>,
+    /// the button's label, None once it's done
+    pub button: Option<&'a str>,
+}
@@ -0,0 +1,113 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use actix_web::{
+    http::header::{
+        CacheControl, CacheDirective, ContentType, ETag, EntityTag, Header, IfNoneMatch,
+    },
+    HttpRequest, HttpResponse,
+};
+use serde::Serialize;
+
+/// Respond with `body` as JSON, tagged with an ETag of its contents so
+/// polling clients can send If-None-Match and get a 304 when nothing has
+/// changed. Clients may reuse the response for `max_age` seconds without
+/// asking at all. Responses are per-user, so only private caches may keep
+/// them.
+pub(super) fn json_with_etag<T: Serialize>(
+    req: &HttpRequest,
+    body: &T,
+    max_age: u32,
+) -> HttpResponse {
+    let json = match serde_json::to_string(body) {
+        Ok(json) => json,
+        Err(err) => {
+            log::error!("Failed to serialize response: {}", err);
+            return HttpResponse::InternalServerError().body("Error serializing response");
+        }
+    };
+
+    // weak, since the compression middleware may change the bytes sent
+    let etag = EntityTag::new_weak(hash(&json));
+    let cache_control = CacheControl(vec![
+        CacheDirective::Private,
+        CacheDirective::MaxAge(max_age),
+    ]);
+
+    if is_match(req, &etag) {
+        return HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header(cache_control)
+            .finish();
+    }
+    HttpResponse::Ok()
+        .insert_header(ETag(etag))
+        .insert_header(cache_control)
+        .content_type(ContentType::json())
+        .body(json)
+}
+
+/// The default hasher's keys are fixed, so tags stay the same for the same
+/// body between requests and restarts
+fn hash(json: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    json.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn is_match(req: &HttpRequest, etag: &EntityTag) -> bool {
+    match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        http::{
+            header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+            StatusCode,
+        },
+        test::TestRequest,
+    };
+
+    #[test]
+    fn test_json_with_etag() {
+        let req = TestRequest::default().to_http_request();
+        let resp = json_with_etag(&req, &vec![1, 2, 3], 300);
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(CACHE_CONTROL).unwrap(),
+            "private, max-age=300"
+        );
+        let etag = resp.headers().get(ETAG).unwrap().clone();
+        assert!(etag.to_str().unwrap().starts_with("W/\""));
+
+        // same body, same tag
+        let req = TestRequest::default()
+            .insert_header((IF_NONE_MATCH, etag.clone()))
+            .to_http_request();
+        let resp = json_with_etag(&req, &vec![1, 2, 3], 300);
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers().get(ETAG).unwrap(), &etag);
+
+        // changed body
+        let resp = json_with_etag(&req, &vec![1, 2, 3, 4], 300);
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_ne!(resp.headers().get(ETAG).unwrap(), &etag);
+    }
+
+    #[test]
+    fn test_if_none_match_any() {
+        let req = TestRequest::default()
+            .insert_header((IF_NONE_MATCH, "*"))
+            .to_http_request();
+        let resp = json_with_etag(&req, &"anything", 0);
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    }
+}
@@ -1,4 +1,5 @@
 mod handlers;
 mod routes;
+mod types;
 
-pub use self::routes::routes;
+pub use self::routes::{batch_routes, routes};
@@ -1,16 +1,530 @@
-use actix_web::{get, HttpResponse, Responder};
+use std::collections::HashMap;
 
-#[get("/")]
-pub async fn get_items_for_feed() -> impl Responder {
-    // check feed -> subscription -> user -> user.id -> claims.sub
-    // maybe we can add this as a claim? or insert it w/ middleware?
+use super::types::{
+    BatchRequest, BatchResponse, ItemList, ItemsQuery, ListQuery, ReaderItem, ReaderPage,
+    ReaderQuery, RqItemId, StarredFeedItem, SubscriptionItems,
+};
+use crate::{
+    api::{etag::json_with_etag, feeds::RqFeedId},
+    claims::Claims,
+    db::DbConnection,
+    models::{
+        feed::Feed,
+        feed_item::FeedItem,
+        ids::{FeedId, UserId},
+        read_item::{ItemCursor, ReadItem},
+        starred_item::StarredItem,
+        subscription::Subscription,
+    },
+    security::validation::Validate,
+    tasks::types::CHECK_INTERVAL,
+    RqDbPool,
+};
+use actix_web::{
+    delete, get, post, route, web, HttpRequest, HttpResponse, Responder, ResponseError,
+};
+
+/// Items of a feed the current user is subscribed to. Tagged with an ETag
+/// so clients polling for new items get a 304 until the feed has some.
+#[get("")]
+pub async fn get_items_for_feed(
+    req: HttpRequest,
+    pool: RqDbPool,
+    feed_path: RqFeedId,
+    query: web::Query<ItemsQuery>,
+    claims: Claims,
+) -> impl Responder {
+    let feed_id = match feed_path.feed_id.parse::<FeedId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid feed_id"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Subscription::get_for_user_and_feed(&mut conn, claims.sub, feed_id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("Feed not found"),
+        Err(_) => return HttpResponse::InternalServerError().body("Error getting feed"),
+    }
 
-    // check user is active
+    let since = query.since.unwrap_or(0);
+    let state = query.state();
+    let items = if state.unread || state.starred {
+        match ReadItem::feed_items_after(&mut conn, claims.sub, feed_id, since, state) {
+            Ok(items) => items,
+            Err(e) => {
+                log::error!("Error getting feed items: {:?}", e);
+                return HttpResponse::InternalServerError().body("Error getting items");
+            }
+        }
+    } else {
+        FeedItem::items_after(&mut conn, feed_id, since)
+    };
 
-    HttpResponse::Ok().body("get_all_feed_items")
+    // feeds aren't checked any more often than this, so there's no point
+    // in clients asking sooner
+    json_with_etag(&req, &items, CHECK_INTERVAL.as_secs() as u32)
 }
 
 #[get("/")]
 pub async fn get_feed_item() -> impl Responder {
     HttpResponse::Ok().body("get_feed_item")
 }
+
+/// New items for several of the current user's subscriptions in one request,
+/// e.g. for a mobile client to sync
+#[post("/batch")]
+pub async fn get_items_batch(
+    pool: RqDbPool,
+    batch_req: web::Json<BatchRequest>,
+    claims: Claims,
+) -> impl Responder {
+    if let Err(errors) = batch_req.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let subscriptions =
+        match Subscription::get_many_for_user(&mut conn, claims.sub, &batch_req.subscription_ids) {
+            Ok(subs) => subs,
+            Err(_) => {
+                return HttpResponse::InternalServerError().body("Error getting subscriptions")
+            }
+        };
+
+    // only the user's own subscriptions are returned, so anything missing
+    // either doesn't exist or isn't theirs
+    if let Some(missing) = batch_req
+        .subscription_ids
+        .iter()
+        .find(|id| !subscriptions.iter().any(|sub| sub.id == **id))
+    {
+        return HttpResponse::NotFound().body(format!("Subscription {} not found", missing));
+    }
+
+    let state = batch_req.state();
+    let mut batch = Vec::with_capacity(subscriptions.len());
+    for sub in subscriptions {
+        let items = if state.unread || state.starred {
+            match ReadItem::feed_items_after(
+                &mut conn,
+                claims.sub,
+                sub.feed_id,
+                batch_req.since,
+                state,
+            ) {
+                Ok(items) => items,
+                Err(e) => {
+                    log::error!("Error getting feed items: {:?}", e);
+                    return HttpResponse::InternalServerError().body("Error getting items");
+                }
+            }
+        } else {
+            FeedItem::items_after(&mut conn, sub.feed_id, batch_req.since)
+        };
+        batch.push(SubscriptionItems {
+            subscription_id: sub.id,
+            feed_id: sub.feed_id,
+            items,
+        });
+    }
+
+    HttpResponse::Ok().json(BatchResponse {
+        subscriptions: batch,
+    })
+}
+
+/// The current user's starred items, most recently starred first
+#[get("/starred")]
+pub async fn get_starred_items(pool: RqDbPool, claims: Claims) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match StarredItem::get_for_user(&mut conn, claims.sub) {
+        Ok(starred) => HttpResponse::Ok().json(
+            starred
+                .into_iter()
+                .map(StarredFeedItem::from)
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => {
+            log::error!("Error getting starred items: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting starred items")
+        }
+    }
+}
+
+/// Star an item from one of the current user's subscriptions, queueing it
+/// for their bookmark manager. POST does the same, for clients that can't
+/// send PUT.
+#[route("/{item_id}/star", method = "PUT", method = "POST")]
+pub async fn star_item(pool: RqDbPool, path: RqItemId, claims: Claims) -> impl Responder {
+    let item_id = match path.item_id.parse::<i32>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid item_id"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let item = match FeedItem::get_by_id(&mut conn, item_id) {
+        Some(item) => item,
+        None => return HttpResponse::NotFound().body("Item not found"),
+    };
+    // users can only star items from feeds they're subscribed to
+    match Subscription::get_for_user_and_feed(&mut conn, claims.sub, item.feed_id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("Item not found"),
+        Err(_) => return HttpResponse::InternalServerError().body("Error getting subscriptions"),
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    match StarredItem::star(&mut conn, claims.sub, item.id, now) {
+        Ok(star) => HttpResponse::Ok().json(StarredFeedItem::from((star, item))),
+        Err(e) => {
+            log::error!("Error starring item: {:?}", e);
+            HttpResponse::InternalServerError().body("Error starring item")
+        }
+    }
+}
+
+#[delete("/{item_id}/star")]
+pub async fn unstar_item(pool: RqDbPool, path: RqItemId, claims: Claims) -> impl Responder {
+    let item_id = match path.item_id.parse::<i32>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid item_id"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match StarredItem::unstar(&mut conn, claims.sub, item_id) {
+        Ok(0) => HttpResponse::NotFound().body("Item not starred"),
+        Ok(_) => HttpResponse::Ok().body("Item unstarred"),
+        Err(e) => {
+            log::error!("Error unstarring item: {:?}", e);
+            HttpResponse::InternalServerError().body("Error unstarring item")
+        }
+    }
+}
+
+/// What each of the user's subscribed feeds is called in their reader view
+fn subscription_names(
+    conn: &mut DbConnection,
+    uid: UserId,
+) -> Result<HashMap<FeedId, String>, diesel::result::Error> {
+    let mut names = HashMap::new();
+    for sub in Subscription::get_all_for_user(conn, uid)? {
+        let name = match Feed::get_by_id(conn, sub.feed_id) {
+            Some(feed) => sub.display_name(&feed).to_string(),
+            None => sub.friendly_name.clone(),
+        };
+        names.insert(sub.feed_id, name);
+    }
+    Ok(names)
+}
+
+/// A page of the newest items across the current user's subscriptions,
+/// for reading them in the web UI
+#[get("/reader")]
+pub async fn get_reader_page(
+    pool: RqDbPool,
+    query: web::Query<ReaderQuery>,
+    claims: Claims,
+) -> impl Responder {
+    if let Err(errors) = query.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let (page, per_page) = (query.page(), query.per_page());
+    // one extra to tell whether there's another page
+    let items = ReadItem::reader_page(
+        &mut conn,
+        claims.sub,
+        query.state(),
+        (page - 1) * per_page,
+        per_page + 1,
+    );
+    let loaded = items.and_then(|items| {
+        let names = subscription_names(&mut conn, claims.sub)?;
+        let unread_count = ReadItem::unread_count(&mut conn, claims.sub)?;
+        Ok((items, names, unread_count))
+    });
+    let (mut items, names, unread_count) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            log::error!("Error getting reader items: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error getting items");
+        }
+    };
+
+    let has_more = items.len() as i64 > per_page;
+    items.truncate(per_page as usize);
+    let items = items
+        .into_iter()
+        .map(|(item, state)| ReaderItem {
+            subscription_name: names.get(&item.feed_id).cloned().unwrap_or_default(),
+            item,
+            state,
+        })
+        .collect();
+    HttpResponse::Ok().json(ReaderPage {
+        items,
+        page,
+        has_more,
+        unread_count,
+    })
+}
+
+/// The same listing with the cursor for its next page
+fn next_link(req: &HttpRequest, cursor: &str) -> String {
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    for (key, value) in url::form_urlencoded::parse(req.query_string().as_bytes()) {
+        if key != "cursor" {
+            query.append_pair(&key, &value);
+        }
+    }
+    query.append_pair("cursor", cursor);
+    format!("{}?{}", req.path(), query.finish())
+}
+
+/// Items from the current user's subscriptions, newest first, filtered and
+/// a page at a time
+#[get("")]
+pub async fn list_items(
+    req: HttpRequest,
+    pool: RqDbPool,
+    query: web::Query<ListQuery>,
+    claims: Claims,
+) -> impl Responder {
+    if let Err(errors) = query.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let names = match subscription_names(&mut conn, claims.sub) {
+        Ok(names) => names,
+        Err(_) => return HttpResponse::InternalServerError().body("Error getting subscriptions"),
+    };
+    if let Some(feed_id) = query.feed_id {
+        if !names.contains_key(&feed_id) {
+            return HttpResponse::NotFound().body("Feed not found");
+        }
+    }
+
+    let item_query = query.item_query();
+    let limit = query.limit();
+    // one extra to tell whether there's another page
+    let loaded = item_query
+        .page(&mut conn, claims.sub, query.cursor(), limit + 1)
+        .and_then(|items| Ok((items, item_query.count(&mut conn, claims.sub)?)));
+    let (mut items, total) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            log::error!("Error listing items: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error getting items");
+        }
+    };
+
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items
+            .last()
+            .map(|(item, _)| ItemCursor::after(item).to_string())
+    } else {
+        None
+    };
+    let items = items
+        .into_iter()
+        .map(|(item, state)| ReaderItem {
+            subscription_name: names.get(&item.feed_id).cloned().unwrap_or_default(),
+            item,
+            state,
+        })
+        .collect();
+    HttpResponse::Ok().json(ItemList {
+        items,
+        total,
+        next: next_cursor.as_deref().map(|cursor| next_link(&req, cursor)),
+        next_cursor,
+    })
+}
+
+/// An item from one of the current user's subscriptions, for the reader
+/// view's detail page
+#[get("/{item_id}")]
+pub async fn get_reader_item(pool: RqDbPool, path: RqItemId, claims: Claims) -> impl Responder {
+    let item_id = match path.item_id.parse::<i32>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid item_id"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let item = match FeedItem::get_by_id(&mut conn, item_id) {
+        Some(item) => item,
+        None => return HttpResponse::NotFound().body("Item not found"),
+    };
+    let subscription_name = match subscription_names(&mut conn, claims.sub) {
+        Ok(mut names) => match names.remove(&item.feed_id) {
+            Some(name) => name,
+            None => return HttpResponse::NotFound().body("Item not found"),
+        },
+        Err(_) => return HttpResponse::InternalServerError().body("Error getting subscriptions"),
+    };
+
+    match ReadItem::state(&mut conn, claims.sub, item.id) {
+        Ok(state) => HttpResponse::Ok().json(ReaderItem {
+            item,
+            subscription_name,
+            state,
+        }),
+        Err(e) => {
+            log::error!("Error getting read state: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting item")
+        }
+    }
+}
+
+/// POST does the same, for clients that can't send PUT
+#[route("/{item_id}/read", method = "PUT", method = "POST")]
+pub async fn mark_read(pool: RqDbPool, path: RqItemId, claims: Claims) -> impl Responder {
+    let item_id = match path.item_id.parse::<i32>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid item_id"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let item = match FeedItem::get_by_id(&mut conn, item_id) {
+        Some(item) => item,
+        None => return HttpResponse::NotFound().body("Item not found"),
+    };
+    // users can only read items from feeds they're subscribed to
+    match Subscription::get_for_user_and_feed(&mut conn, claims.sub, item.feed_id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("Item not found"),
+        Err(_) => return HttpResponse::InternalServerError().body("Error getting subscriptions"),
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    match ReadItem::mark_read(&mut conn, claims.sub, item.id, now) {
+        Ok(_) => HttpResponse::Ok().body("Item marked read"),
+        Err(e) => {
+            log::error!("Error marking item read: {:?}", e);
+            HttpResponse::InternalServerError().body("Error marking item read")
+        }
+    }
+}
+
+#[delete("/{item_id}/read")]
+pub async fn mark_unread(pool: RqDbPool, path: RqItemId, claims: Claims) -> impl Responder {
+    let item_id = match path.item_id.parse::<i32>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid item_id"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match ReadItem::mark_unread(&mut conn, claims.sub, item_id) {
+        Ok(0) => HttpResponse::NotFound().body("Item not read"),
+        Ok(_) => HttpResponse::Ok().body("Item marked unread"),
+        Err(e) => {
+            log::error!("Error marking item unread: {:?}", e);
+            HttpResponse::InternalServerError().body("Error marking item unread")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_list_query() {
+        let query = web::Query::<ListQuery>::from_query("feed_id=3&search=rust&unread=true")
+            .unwrap()
+            .into_inner();
+        assert_eq!(query.feed_id, Some(FeedId(3)));
+        assert!(query.unread && !query.starred);
+        assert!(query.validate().is_ok());
+
+        let invalid = web::Query::<ListQuery>::from_query("after=20&before=10&cursor=nope")
+            .unwrap()
+            .into_inner();
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_next_link() {
+        let req = TestRequest::get()
+            .uri("/api/feed_items?search=a%20b&cursor=old&limit=5")
+            .to_http_request();
+        assert_eq!(
+            next_link(&req, "new"),
+            "/api/feed_items?search=a+b&limit=5&cursor=new"
+        );
+    }
+}
@@ -6,3 +6,16 @@ pub fn routes() -> Scope {
         .service(handlers::get_items_for_feed)
         .service(handlers::get_feed_item)
 }
+
+pub fn batch_routes() -> Scope {
+    web::scope("/feed_items")
+        .service(handlers::list_items)
+        .service(handlers::get_items_batch)
+        .service(handlers::get_starred_items)
+        .service(handlers::get_reader_page)
+        .service(handlers::get_reader_item)
+        .service(handlers::star_item)
+        .service(handlers::unstar_item)
+        .service(handlers::mark_read)
+        .service(handlers::mark_unread)
+}
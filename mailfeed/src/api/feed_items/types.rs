@@ -0,0 +1,271 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{
+    feed_item::FeedItem,
+    ids::{FeedId, SubscriptionId},
+    read_item::{ItemCursor, ItemQuery, ItemState, StateFilter},
+    starred_item::StarredItem,
+};
+use crate::security::validation::{Validate, ValidationErrors};
+
+/// Most subscriptions that can be fetched in one batch request
+pub const MAX_BATCH_SUBSCRIPTIONS: usize = 100;
+
+/// Items on a page of the reader view unless asked for otherwise
+pub const DEFAULT_READER_PAGE: i64 = 25;
+
+/// Most items on one page of the reader view
+pub const MAX_READER_PAGE: i64 = 100;
+
+/// Items in a listing unless asked for otherwise
+pub const DEFAULT_LIST_LIMIT: i64 = 50;
+
+/// Most items in one page of a listing
+pub const MAX_LIST_LIMIT: i64 = 200;
+
+/// Longest search text in a listing
+pub const MAX_SEARCH_LENGTH: usize = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct ItemsQuery {
+    /// only items published after this unix timestamp are returned
+    pub since: Option<i64>,
+    /// only items the user hasn't read
+    #[serde(default)]
+    pub unread: bool,
+    /// only items the user starred
+    #[serde(default)]
+    pub starred: bool,
+}
+
+impl ItemsQuery {
+    pub fn state(&self) -> StateFilter {
+        StateFilter {
+            unread: self.unread,
+            starred: self.starred,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub subscription_ids: Vec<SubscriptionId>,
+    /// only items published after this unix timestamp are returned
+    pub since: i64,
+    /// only items the user hasn't read
+    #[serde(default)]
+    pub unread: bool,
+    /// only items the user starred
+    #[serde(default)]
+    pub starred: bool,
+}
+
+impl BatchRequest {
+    pub fn state(&self) -> StateFilter {
+        StateFilter {
+            unread: self.unread,
+            starred: self.starred,
+        }
+    }
+}
+
+impl Validate for BatchRequest {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.subscription_ids.is_empty() {
+            errors.add("subscription_ids", "No subscription IDs given");
+        } else if self.subscription_ids.len() > MAX_BATCH_SUBSCRIPTIONS {
+            errors.add(
+                "subscription_ids",
+                format!(
+                    "At most {} subscriptions can be fetched at once",
+                    MAX_BATCH_SUBSCRIPTIONS
+                ),
+            );
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionItems {
+    pub subscription_id: SubscriptionId,
+    pub feed_id: FeedId,
+    pub items: Vec<FeedItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResponse {
+    pub subscriptions: Vec<SubscriptionItems>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ItemPath {
+    pub item_id: String,
+}
+
+pub type RqItemId = web::Path<ItemPath>;
+
+/// A starred item, and whether it's been pushed to the user's bookmark
+/// manager
+#[derive(Debug, Serialize)]
+pub struct StarredFeedItem {
+    #[serde(flatten)]
+    pub item: FeedItem,
+    pub starred_at: i64,
+    pub synced_at: i64,
+    pub bookmark_id: Option<String>,
+    pub sync_error: Option<String>,
+}
+
+impl From<(StarredItem, FeedItem)> for StarredFeedItem {
+    fn from((star, item): (StarredItem, FeedItem)) -> Self {
+        StarredFeedItem {
+            item,
+            starred_at: star.starred_at,
+            synced_at: star.synced_at,
+            bookmark_id: star.bookmark_id,
+            sync_error: star.sync_error,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReaderQuery {
+    /// from 1
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// only items the user hasn't read
+    #[serde(default)]
+    pub unread: bool,
+    /// only items the user starred
+    #[serde(default)]
+    pub starred: bool,
+}
+
+impl ReaderQuery {
+    pub fn state(&self) -> StateFilter {
+        StateFilter {
+            unread: self.unread,
+            starred: self.starred,
+        }
+    }
+
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(DEFAULT_READER_PAGE)
+    }
+}
+
+impl Validate for ReaderQuery {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.page() < 1 {
+            errors.add("page", "Must be at least 1");
+        }
+        if !(1..=MAX_READER_PAGE).contains(&self.per_page()) {
+            errors.add(
+                "per_page",
+                format!("Must be between 1 and {}", MAX_READER_PAGE),
+            );
+        }
+    }
+}
+
+/// An item in the reader view, with which subscription it's from and
+/// whether the user has read or starred it
+#[derive(Debug, Serialize)]
+pub struct ReaderItem {
+    #[serde(flatten)]
+    pub item: FeedItem,
+    pub subscription_name: String,
+    #[serde(flatten)]
+    pub state: ItemState,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReaderPage {
+    pub items: Vec<ReaderItem>,
+    pub page: i64,
+    /// whether there's a next page
+    pub has_more: bool,
+    pub unread_count: i64,
+}
+
+/// Filters and pagination for listing the current user's items
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    pub feed_id: Option<FeedId>,
+    /// only items first seen after this unix timestamp
+    pub after: Option<i64>,
+    /// only items first seen before this unix timestamp
+    pub before: Option<i64>,
+    pub search: Option<String>,
+    pub limit: Option<i64>,
+    /// from the previous page's `next_cursor`
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub unread: bool,
+    #[serde(default)]
+    pub starred: bool,
+}
+
+impl ListQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIST_LIMIT)
+    }
+
+    /// None if there isn't a valid one
+    pub fn cursor(&self) -> Option<ItemCursor> {
+        self.cursor.as_deref()?.parse().ok()
+    }
+
+    pub fn item_query(&self) -> ItemQuery {
+        ItemQuery {
+            feed_id: self.feed_id,
+            after: self.after,
+            before: self.before,
+            search: self.search.clone(),
+            state: StateFilter {
+                unread: self.unread,
+                starred: self.starred,
+            },
+        }
+    }
+}
+
+impl Validate for ListQuery {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if !(1..=MAX_LIST_LIMIT).contains(&self.limit()) {
+            errors.add("limit", format!("Must be between 1 and {}", MAX_LIST_LIMIT));
+        }
+        if let (Some(after), Some(before)) = (self.after, self.before) {
+            if after >= before {
+                errors.add("before", "Must be later than after");
+            }
+        }
+        if let Some(search) = &self.search {
+            if search.chars().count() > MAX_SEARCH_LENGTH {
+                errors.add(
+                    "search",
+                    format!("Must be at most {} characters", MAX_SEARCH_LENGTH),
+                );
+            }
+        }
+        if let Some(Err(e)) = self.cursor.as_deref().map(str::parse::<ItemCursor>) {
+            errors.add("cursor", e.to_string());
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ItemList {
+    pub items: Vec<ReaderItem>,
+    /// how many match in all, on every page
+    pub total: i64,
+    /// None on the last page
+    pub next_cursor: Option<String>,
+    /// this listing's URL for the next page, None on the last
+    pub next: Option<String>,
+}
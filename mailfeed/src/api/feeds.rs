@@ -3,3 +3,4 @@ mod routes;
 mod types;
 
 pub use self::routes::routes;
+pub(super) use self::types::RqFeedId;
@@ -1,15 +1,49 @@
 use crate::{
+    api::admin::check_access,
     claims::Claims,
-    models::{feed::Feed, subscription::Subscription},
+    models::{
+        audit_log::{AuditAction, NewAuditEntry},
+        feed::{Feed, PartialFeed},
+        feed_change::FeedChange,
+        feed_credentials::FeedCredentials,
+        ids::FeedId,
+        subscription::Subscription,
+    },
+    security::{client_ip::real_ip, validation::Validate},
     RqDbPool,
 };
 
-use super::types::RqFeedId;
-use actix_web::{delete, get, patch, post, HttpResponse, Responder};
+use super::types::{FeedListResponse, FeedUpdate, RqFeedId, MAX_CHANGES};
+use actix_web::{
+    delete, get, patch, post, web, HttpRequest, HttpResponse, Responder, ResponseError,
+};
 
+/// Every feed with its subscriber count, latest item and any error
 #[get("")]
-pub async fn get_all_feeds() -> impl Responder {
-    HttpResponse::Ok().body("get_all_feeds")
+pub async fn get_all_feeds(req: HttpRequest, pool: RqDbPool, claims: Claims) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to list feeds by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access(&req) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Feed::summaries(&mut conn) {
+        Ok(summaries) => HttpResponse::Ok().json(FeedListResponse::from(summaries)),
+        Err(e) => {
+            log::error!("Error listing feeds: {:?}", e);
+            HttpResponse::InternalServerError().body("Error listing feeds")
+        }
+    }
 }
 
 #[post("")]
@@ -20,7 +54,7 @@ pub async fn create_feed() -> impl Responder {
 #[get("/{feed_id}")]
 pub async fn get_feed(pool: RqDbPool, feed_path: RqFeedId, claims: Claims) -> impl Responder {
     // parse feed_id from feed_path or else return 400
-    let feed_id = feed_path.feed_id.parse::<i32>();
+    let feed_id = feed_path.feed_id.parse::<FeedId>();
     if feed_id.is_err() {
         return HttpResponse::BadRequest().body("Invalid feed_id");
     }
@@ -62,8 +96,123 @@ pub async fn get_feed(pool: RqDbPool, feed_path: RqFeedId, claims: Claims) -> im
 }
 
 #[patch("/{feed_id}")]
-pub async fn update_feed() -> impl Responder {
-    HttpResponse::Ok().body("update_feed")
+pub async fn update_feed(
+    req: HttpRequest,
+    pool: RqDbPool,
+    feed_path: RqFeedId,
+    updates: web::Json<FeedUpdate>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to update feed by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access(&req) {
+        return e.error_response();
+    }
+
+    if updates.is_empty() {
+        return HttpResponse::BadRequest().body("No fields to update");
+    }
+
+    let feed_id = match feed_path.feed_id.parse::<FeedId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid feed_id"),
+    };
+
+    if let Err(errors) = updates.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let feed = match Feed::get_by_id(&mut conn, feed_id) {
+        Some(feed) => feed,
+        None => return HttpResponse::NotFound().body("Feed not found"),
+    };
+
+    let mut update: PartialFeed = (&*updates).into();
+    if let Some(paused) = updates.paused {
+        if paused != feed.is_paused() {
+            let action = if paused { "paused" } else { "resumed" };
+            log::info!("Polling of feed {} {} by {}", feed.url, action, claims.sub);
+        }
+        // pausing a paused feed keeps when it was paused
+        update.paused_at = Some(match (paused, feed.is_paused()) {
+            (true, true) => feed.paused_at,
+            (true, false) => chrono::Utc::now().timestamp(),
+            (false, _) => 0,
+        });
+    }
+    if let Some(credentials) = &updates.credentials {
+        let sealed = match credentials.as_ref().map(FeedCredentials::seal) {
+            None => None,
+            Some(Some(sealed)) => Some(sealed),
+            Some(None) => {
+                return HttpResponse::ServiceUnavailable()
+                    .body("Private feeds need MF_SECRET_KEY to be set")
+            }
+        };
+        update.credentials = Some(sealed);
+    }
+
+    match Feed::update(&mut conn, feed_id, &update) {
+        Some(feed) => {
+            NewAuditEntry::new(AuditAction::FeedUpdated, Some(claims.sub), None)
+                .details(format!("feed {}: {}", feed_id, updates.describe()))
+                .ip(real_ip(&req))
+                .record(&mut conn);
+            HttpResponse::Ok().json(feed)
+        }
+        None => HttpResponse::InternalServerError().body("Error updating feed"),
+    }
+}
+
+#[get("/{feed_id}/changes")]
+pub async fn get_feed_changes(
+    req: HttpRequest,
+    pool: RqDbPool,
+    feed_path: RqFeedId,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get feed changes by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access(&req) {
+        return e.error_response();
+    }
+
+    let feed_id = match feed_path.feed_id.parse::<FeedId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid feed_id"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if Feed::get_by_id(&mut conn, feed_id).is_none() {
+        return HttpResponse::NotFound().body("Feed not found");
+    }
+
+    match FeedChange::get_for_feed(&mut conn, feed_id, MAX_CHANGES) {
+        Ok(changes) => HttpResponse::Ok().json(changes),
+        Err(e) => {
+            log::error!("Error getting changes for feed {}: {:?}", feed_id, e);
+            HttpResponse::InternalServerError().body("Error getting feed changes")
+        }
+    }
 }
 
 #[delete("/{feed_id}")]
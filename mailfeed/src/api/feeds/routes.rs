@@ -7,5 +7,6 @@ pub fn routes() -> Scope {
         .service(handlers::create_feed)
         .service(handlers::get_feed)
         .service(handlers::update_feed)
+        .service(handlers::get_feed_changes)
         .service(handlers::delete_feed)
 }
@@ -1,5 +1,13 @@
 use actix_web::web;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{
+    feed::{FeedErrorKind, FeedSummary, LinkMode, PartialFeed},
+    feed_credentials::FeedCredentials,
+    subscription_template::nullable,
+};
+use crate::scheduler::cron::CronSchedule;
+use crate::security::validation::{Validate, ValidationErrors};
 
 #[derive(Debug, Deserialize)]
 pub struct FeedPath {
@@ -7,3 +15,159 @@ pub struct FeedPath {
 }
 
 pub type RqFeedId = web::Path<FeedPath>;
+
+/// Most changes returned when listing a feed's change history
+pub const MAX_CHANGES: i64 = 100;
+
+/// Every feed, for the admin feed list
+#[derive(Debug, Serialize)]
+pub struct FeedListResponse {
+    pub feeds: Vec<FeedListEntry>,
+    /// how many of the feeds are failing
+    pub failing: usize,
+    /// how many of the feeds an admin paused polling
+    pub paused: usize,
+}
+
+impl From<Vec<FeedSummary>> for FeedListResponse {
+    fn from(summaries: Vec<FeedSummary>) -> Self {
+        let feeds: Vec<FeedListEntry> = summaries
+            .into_iter()
+            .map(|summary| FeedListEntry {
+                error: FeedErrorSummary::of(&summary),
+                summary,
+            })
+            .collect();
+        FeedListResponse {
+            failing: feeds.iter().filter(|entry| entry.error.is_some()).count(),
+            paused: feeds
+                .iter()
+                .filter(|entry| entry.summary.feed.is_paused())
+                .count(),
+            feeds,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeedListEntry {
+    #[serde(flatten)]
+    pub summary: FeedSummary,
+    /// why the feed is failing, None if its last fetch worked
+    pub error: Option<FeedErrorSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeedErrorSummary {
+    pub kind: FeedErrorKind,
+    pub message: Option<String>,
+    /// when the feed started failing
+    pub since: i64,
+    /// whether the error is likely to go away on its own
+    pub transient: bool,
+    /// fetches that have failed in a row
+    pub failures: i32,
+    /// when the feed was given up on and its subscriptions paused, if it was
+    pub broken_at: Option<i64>,
+}
+
+impl FeedErrorSummary {
+    fn of(summary: &FeedSummary) -> Option<Self> {
+        let feed = &summary.feed;
+        feed.failing_since().map(|since| FeedErrorSummary {
+            kind: feed.error_kind,
+            message: feed.error_message.clone(),
+            since,
+            transient: feed.error_kind.is_transient(),
+            failures: feed.consecutive_failures,
+            broken_at: Some(feed.broken_at).filter(|at| *at > 0),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeedUpdate {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub link_mode: Option<LinkMode>,
+    /// cron expression for when to fetch the feed, or cleared if empty
+    pub fetch_schedule: Option<String>,
+    /// null makes the feed public again
+    #[serde(default, deserialize_with = "nullable")]
+    pub credentials: Option<Option<FeedCredentials>>,
+    /// stop or start polling the feed, leaving its subscriptions as they are
+    pub paused: Option<bool>,
+}
+
+impl FeedUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.description.is_none()
+            && self.homepage.is_none()
+            && self.link_mode.is_none()
+            && self.fetch_schedule.is_none()
+            && self.credentials.is_none()
+            && self.paused.is_none()
+    }
+
+    /// What the update sets, for the audit log. Credentials are only said
+    /// to be set or cleared.
+    pub fn describe(&self) -> String {
+        let fields = [
+            ("title", self.title.is_some()),
+            ("description", self.description.is_some()),
+            ("homepage", self.homepage.is_some()),
+            ("link_mode", self.link_mode.is_some()),
+            ("fetch_schedule", self.fetch_schedule.is_some()),
+        ];
+        let mut changes: Vec<&str> = fields
+            .into_iter()
+            .filter(|(_, set)| *set)
+            .map(|(field, _)| field)
+            .collect();
+        match &self.credentials {
+            Some(Some(_)) => changes.push("credentials set"),
+            Some(None) => changes.push("credentials cleared"),
+            None => {}
+        }
+        match self.paused {
+            Some(true) => changes.push("paused"),
+            Some(false) => changes.push("resumed"),
+            None => {}
+        }
+        changes.join(", ")
+    }
+}
+
+impl Validate for FeedUpdate {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(homepage) = &self.homepage {
+            errors.url("homepage", homepage);
+        }
+        if let Some(Some(credentials)) = &self.credentials {
+            credentials.check(errors);
+        }
+        if let Some(schedule) = self.fetch_schedule.as_deref().filter(|s| !s.is_empty()) {
+            if let Err(e) = CronSchedule::parse(schedule) {
+                errors.add("fetch_schedule", e.to_string());
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a FeedUpdate> for PartialFeed<'a> {
+    fn from(update: &'a FeedUpdate) -> Self {
+        PartialFeed {
+            title: update.title.as_deref(),
+            description: update.description.as_deref(),
+            homepage: update.homepage.as_deref(),
+            link_mode: update.link_mode,
+            fetch_schedule: update
+                .fetch_schedule
+                .as_deref()
+                .map(|schedule| Some(schedule.trim()).filter(|s| !s.is_empty())),
+            ..Default::default()
+        }
+    }
+}
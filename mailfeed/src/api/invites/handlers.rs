@@ -0,0 +1,187 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder, ResponseError};
+use chrono::Utc;
+
+use super::types::{CreatedInvite, InviteInfo, RegisterRequest, RqInviteToken};
+use crate::{
+    api::admin::check_access,
+    claims::Claims,
+    models::{
+        audit_log::{AuditAction, NewAuditEntry},
+        invite::{Invite, NewInvite},
+        onboarding::Onboarding,
+        registration::Registration,
+        user::{NewUser, User, UserTableError},
+    },
+    security::{redact, validation::Validate},
+    tasks::webhooks::{Event, Webhooks},
+    RqDbPool,
+};
+
+/// Invite someone to create an account with their own password. The link
+/// to give them is only returned here.
+#[post("")]
+pub async fn create_invite(
+    req: HttpRequest,
+    pool: RqDbPool,
+    invite: web::Json<NewInvite>,
+    claims: Claims,
+) -> impl Responder {
+    if !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to create an invite by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access(&req) {
+        return e.error_response();
+    }
+
+    let new_invite = NewInvite {
+        created_by: claims.sub,
+        created_at: Utc::now().timestamp(),
+        ..invite.into_inner()
+    };
+    if let Err(errors) = new_invite.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if !Registration::load(&mut conn)
+        .registration_mode
+        .allows_invites()
+    {
+        return HttpResponse::BadRequest().body("Registration is closed");
+    }
+
+    if User::exists(&mut conn, &new_invite.email) {
+        return HttpResponse::BadRequest().body("Email exists");
+    }
+
+    match new_invite.insert(&mut conn) {
+        Ok((invite, token)) => {
+            log::info!(
+                "User {} invited {}",
+                claims.sub,
+                redact::email(&invite.email)
+            );
+            NewAuditEntry::new(AuditAction::InviteCreated, Some(claims.sub), None)
+                .details(redact::email(&invite.email))
+                .record(&mut conn);
+            HttpResponse::Ok().json(CreatedInvite {
+                invite,
+                path: format!("/register?token={}", token),
+            })
+        }
+        Err(e) => {
+            log::error!("Error creating invite: {:?}", e);
+            HttpResponse::InternalServerError().body("Error creating invite")
+        }
+    }
+}
+
+/// Who an invite is for, so the registration page can show it. No login
+/// needed.
+#[get("/{token}")]
+pub async fn get_invite(pool: RqDbPool, path: RqInviteToken) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Invite::find(&mut conn, &path.token, Utc::now().timestamp()) {
+        Ok(Some(invite)) => HttpResponse::Ok().json(InviteInfo {
+            email: &invite.email,
+            expires_at: invite.expires_at,
+        }),
+        Ok(None) => HttpResponse::NotFound().body("Invalid or expired invite"),
+        Err(e) => {
+            log::error!("Error checking invite: {:?}", e);
+            HttpResponse::InternalServerError().body("Error checking invite")
+        }
+    }
+}
+
+/// Create the invited account with the password the invitee chose, which
+/// uses up the invite. No login needed.
+#[post("/{token}")]
+pub async fn accept_invite(
+    pool: RqDbPool,
+    webhooks: web::Data<Webhooks>,
+    path: RqInviteToken,
+    register_req: web::Json<RegisterRequest>,
+) -> impl Responder {
+    if let Err(errors) = register_req.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    // invites sent before registration was closed stop working too
+    if !Registration::load(&mut conn)
+        .registration_mode
+        .allows_invites()
+    {
+        return HttpResponse::BadRequest().body("Registration is closed");
+    }
+
+    let invite = match Invite::find(&mut conn, &path.token, Utc::now().timestamp()) {
+        Ok(Some(invite)) => invite,
+        Ok(None) => return HttpResponse::BadRequest().body("Invalid or expired invite"),
+        Err(e) => {
+            log::error!("Error checking invite: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error creating user");
+        }
+    };
+
+    let new_user = NewUser {
+        email: invite.email.clone(),
+        password: register_req.into_inner().password,
+    };
+    // the email is taken from here on, so the invite can't be used twice
+    // even if it isn't deleted below
+    let user = match User::register(&mut conn, &new_user) {
+        Ok(user) => user,
+        Err(UserTableError::EmailExists) => return HttpResponse::BadRequest().body("Email exists"),
+        Err(UserTableError::PasswordTooShort) => {
+            return HttpResponse::BadRequest().body("Password too short")
+        }
+        Err(UserTableError::WeakPassword(reason)) => {
+            return HttpResponse::BadRequest().body(reason)
+        }
+        Err(_) => return HttpResponse::InternalServerError().body("Error creating user"),
+    };
+
+    if let Err(e) = Invite::delete_for_email(&mut conn, &invite.email) {
+        log::error!("Error removing invites for user {}: {:?}", user.id, e);
+    }
+    log::info!(
+        "User {} registered from an invite by {}",
+        user.id,
+        invite.created_by
+    );
+    NewAuditEntry::new(AuditAction::UserCreated, None, Some(user.id))
+        .details(format!("from an invite by user {}", invite.created_by))
+        .record(&mut conn);
+    webhooks.emit(Event::UserCreated {
+        user_id: user.id,
+        email: user.login_email.clone(),
+    });
+    if let Err(e) = Onboarding::start(&mut conn, user.id) {
+        log::error!("Error starting onboarding for user {}: {:?}", user.id, e);
+    }
+    HttpResponse::Ok().json(user)
+}
@@ -0,0 +1,9 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/invites")
+        .service(handlers::create_invite)
+        .service(handlers::get_invite)
+        .service(handlers::accept_invite)
+}
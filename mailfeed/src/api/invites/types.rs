@@ -0,0 +1,45 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::invite::Invite,
+    security::{
+        password_policy::PasswordPolicy,
+        validation::{Validate, ValidationErrors},
+    },
+};
+
+#[derive(Debug, Deserialize)]
+pub struct InviteTokenPath {
+    pub token: String,
+}
+pub type RqInviteToken = web::Path<InviteTokenPath>;
+
+#[derive(Debug, Serialize)]
+pub struct CreatedInvite {
+    #[serde(flatten)]
+    pub invite: Invite,
+    /// the web UI page the invitee registers on, relative to its root;
+    /// only shown here
+    pub path: String,
+}
+
+/// What the registration page shows about the invite it was opened with
+#[derive(Debug, Serialize)]
+pub struct InviteInfo<'a> {
+    pub email: &'a str,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterRequest {
+    pub password: String,
+}
+
+impl Validate for RegisterRequest {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Err(e) = PasswordPolicy::global().validate(&self.password) {
+            errors.add("password", e.to_string());
+        }
+    }
+}
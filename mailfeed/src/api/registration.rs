@@ -0,0 +1,4 @@
+mod handlers;
+mod routes;
+
+pub use self::routes::routes;
@@ -0,0 +1,71 @@
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError};
+
+use crate::{
+    models::{
+        audit_log::{AuditAction, NewAuditEntry},
+        registration::Registration,
+        user::{NewUser, User, UserTableError},
+    },
+    security::{redact, validation::Validate},
+    RqDbPool,
+};
+
+/// How accounts are created here, so the registration page knows whether
+/// to offer sign-up. No login needed.
+#[get("")]
+pub async fn get_registration(pool: RqDbPool) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(Registration::load(&mut conn))
+}
+
+/// Sign up for an account, which can't log in until an admin approves it.
+/// Only when the registration mode is `open_with_approval`. No login
+/// needed.
+#[post("")]
+pub async fn sign_up(pool: RqDbPool, new_user: web::Json<NewUser>) -> impl Responder {
+    if let Err(errors) = new_user.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if !Registration::load(&mut conn)
+        .registration_mode
+        .allows_sign_up()
+    {
+        return HttpResponse::Forbidden().body("Registration is closed");
+    }
+
+    match User::sign_up(&mut conn, &new_user) {
+        Ok(user) => {
+            log::info!(
+                "User {} signed up as {} and is waiting for approval",
+                user.id,
+                redact::email(&user.login_email)
+            );
+            NewAuditEntry::new(AuditAction::UserCreated, None, Some(user.id))
+                .details("signed up, waiting for approval")
+                .record(&mut conn);
+            HttpResponse::Ok().json(user)
+        }
+        Err(UserTableError::EmailExists) => HttpResponse::BadRequest().body("Email exists"),
+        Err(UserTableError::PasswordTooShort) => {
+            HttpResponse::BadRequest().body("Password too short")
+        }
+        Err(UserTableError::WeakPassword(reason)) => HttpResponse::BadRequest().body(reason),
+        Err(_) => HttpResponse::InternalServerError().body("Error creating user"),
+    }
+}
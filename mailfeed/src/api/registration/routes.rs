@@ -0,0 +1,8 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/registration")
+        .service(handlers::get_registration)
+        .service(handlers::sign_up)
+}
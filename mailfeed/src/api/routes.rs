@@ -1,11 +1,40 @@
-use super::{auth, feed_items, feeds, subscriptions, users};
-use actix_web::{web, Scope};
+use super::{
+    admin, auth, body_log, config, feed_items, feeds, invites, registration, searches, sessions,
+    shares, status, subscriptions, tags, templates, tokens, two_factor, users,
+};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    web, Error, Scope,
+};
 
-pub fn routes() -> Scope {
+pub fn routes() -> Scope<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
     web::scope("/api")
+        .wrap_fn(body_log::log_bodies)
+        .service(shares::routes())
         .service(subscriptions::routes())
+        .service(searches::routes())
+        .service(tags::routes())
+        .service(templates::routes())
+        .service(two_factor::routes())
+        .service(config::routes())
         .service(users::routes())
         .service(auth::routes())
+        .service(invites::routes())
+        .service(registration::routes())
+        .service(tokens::routes())
+        .service(sessions::routes())
         .service(feed_items::routes())
+        .service(feed_items::batch_routes())
         .service(feeds::routes())
+        .service(admin::routes())
+        .service(status::routes())
 }
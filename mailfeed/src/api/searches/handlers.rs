@@ -0,0 +1,177 @@
+use actix_web::{delete, get, patch, post, web, HttpResponse, Responder, ResponseError};
+use chrono::Utc;
+
+use super::types::{RqSearchId, MAX_SAVED_SEARCHES};
+use crate::{
+    api::users::RqUserId,
+    claims::Claims,
+    models::{
+        ids::{SavedSearchId, UserId},
+        saved_search::{NewSavedSearch, PartialSavedSearch, SavedSearch},
+    },
+    security::validation::Validate,
+    RqDbPool,
+};
+
+#[get("")]
+pub async fn get_searches(pool: RqDbPool, path: RqUserId, claims: Claims) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match SavedSearch::get_for_user(&mut conn, user_id) {
+        Ok(searches) => HttpResponse::Ok().json(searches),
+        Err(e) => {
+            log::error!("Error getting saved searches: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting saved searches")
+        }
+    }
+}
+
+#[post("")]
+pub async fn create_search(
+    pool: RqDbPool,
+    path: RqUserId,
+    search: web::Json<NewSavedSearch>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = search.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match SavedSearch::count_for_user(&mut conn, user_id) {
+        Ok(count) if count >= MAX_SAVED_SEARCHES => {
+            return HttpResponse::BadRequest().body(format!(
+                "At most {} saved searches are allowed",
+                MAX_SAVED_SEARCHES
+            ))
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error counting saved searches: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error creating saved search");
+        }
+    }
+
+    let new_search = NewSavedSearch {
+        user_id,
+        created_at: Utc::now().timestamp(),
+        ..search.into_inner()
+    };
+    match new_search.insert(&mut conn) {
+        Ok(search) => HttpResponse::Created().json(search),
+        Err(e) => {
+            log::error!("Error creating saved search: {:?}", e);
+            HttpResponse::InternalServerError().body("Error creating saved search")
+        }
+    }
+}
+
+#[patch("/{search_id}")]
+pub async fn update_search(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    search_path: RqSearchId,
+    update: web::Json<PartialSavedSearch>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    let search_id = match search_path.search_id.parse::<SavedSearchId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid saved search ID"),
+    };
+
+    if let Err(errors) = update.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match SavedSearch::update(&mut conn, user_id, search_id, &update) {
+        Ok(search) => HttpResponse::Ok().json(search),
+        Err(diesel::result::Error::NotFound) => {
+            HttpResponse::NotFound().body("Saved search not found")
+        }
+        Err(e) => {
+            log::error!("Error updating saved search: {:?}", e);
+            HttpResponse::InternalServerError().body("Error updating saved search")
+        }
+    }
+}
+
+#[delete("/{search_id}")]
+pub async fn delete_search(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    search_path: RqSearchId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    let search_id = match search_path.search_id.parse::<SavedSearchId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid saved search ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match SavedSearch::delete(&mut conn, user_id, search_id) {
+        Ok(0) => HttpResponse::NotFound().body("Saved search not found"),
+        Ok(_) => HttpResponse::Ok().body("Saved search deleted"),
+        Err(e) => {
+            log::error!("Error deleting saved search: {:?}", e);
+            HttpResponse::InternalServerError().body("Error deleting saved search")
+        }
+    }
+}
@@ -0,0 +1,10 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/users/{user_id}/searches")
+        .service(handlers::get_searches)
+        .service(handlers::create_search)
+        .service(handlers::update_search)
+        .service(handlers::delete_search)
+}
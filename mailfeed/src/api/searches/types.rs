@@ -0,0 +1,12 @@
+use actix_web::web;
+use serde::Deserialize;
+
+/// Most saved searches a user may have, since each is checked against
+/// every new item from their feeds
+pub const MAX_SAVED_SEARCHES: i64 = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchPath {
+    pub search_id: String,
+}
+pub type RqSearchId = web::Path<SearchPath>;
@@ -0,0 +1,120 @@
+use actix_web::{delete, get, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+
+use super::types::{RqSessionId, SessionInfo};
+use crate::{
+    claims::Claims,
+    models::{
+        audit_log::{AuditAction, NewAuditEntry},
+        ids::SessionId,
+        session::Session,
+    },
+    security::{client_ip::real_ip, redact},
+    RqDbPool,
+};
+
+/// The devices the current user is logged in on
+#[get("")]
+pub async fn get_sessions(pool: RqDbPool, claims: Claims) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Session::get_for_user(&mut conn, claims.sub, Utc::now().timestamp()) {
+        Ok(sessions) => {
+            let sessions: Vec<SessionInfo> = sessions
+                .into_iter()
+                .map(|session| SessionInfo {
+                    current: claims.sid == Some(session.id),
+                    session,
+                })
+                .collect();
+            HttpResponse::Ok().json(sessions)
+        }
+        Err(e) => {
+            log::error!("Error getting sessions: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting sessions")
+        }
+    }
+}
+
+/// Log out everywhere, this device included. Personal access tokens keep
+/// working.
+#[delete("")]
+pub async fn delete_sessions(req: HttpRequest, pool: RqDbPool, claims: Claims) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Session::delete_for_user(&mut conn, claims.sub, None) {
+        Ok(count) => {
+            log::info!("Logged out {} sessions of user {}", count, claims.sub);
+            NewAuditEntry::new(
+                AuditAction::AllSessionsRevoked,
+                Some(claims.sub),
+                Some(claims.sub),
+            )
+            .details(format!("{} sessions", count))
+            .ip(real_ip(&req))
+            .record(&mut conn);
+            HttpResponse::Ok().body("Logged out everywhere")
+        }
+        Err(e) => {
+            log::error!("Error deleting sessions: {:?}", e);
+            HttpResponse::InternalServerError().body("Error logging out")
+        }
+    }
+}
+
+/// Log out one of the current user's devices
+#[delete("/{session_id}")]
+pub async fn delete_session(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqSessionId,
+    claims: Claims,
+) -> impl Responder {
+    let session_id = match path.session_id.parse::<SessionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid session ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Session::delete(&mut conn, claims.sub, session_id) {
+        Ok(0) => HttpResponse::NotFound().body("Session not found"),
+        Ok(_) => {
+            log::info!(
+                "Logged out session {} of user {}",
+                redact::secret(&session_id.to_string()),
+                claims.sub
+            );
+            NewAuditEntry::new(
+                AuditAction::SessionRevoked,
+                Some(claims.sub),
+                Some(claims.sub),
+            )
+            .ip(real_ip(&req))
+            .record(&mut conn);
+            HttpResponse::Ok().body("Session logged out")
+        }
+        Err(e) => {
+            log::error!("Error deleting session: {:?}", e);
+            HttpResponse::InternalServerError().body("Error logging out session")
+        }
+    }
+}
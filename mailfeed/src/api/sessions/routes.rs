@@ -0,0 +1,9 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/sessions")
+        .service(handlers::get_sessions)
+        .service(handlers::delete_sessions)
+        .service(handlers::delete_session)
+}
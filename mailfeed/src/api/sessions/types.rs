@@ -0,0 +1,18 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+
+use crate::models::session::Session;
+
+#[derive(Debug, Deserialize)]
+pub struct SessionPath {
+    pub session_id: String,
+}
+pub type RqSessionId = web::Path<SessionPath>;
+
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    #[serde(flatten)]
+    pub session: Session,
+    /// the session the request was made with
+    pub current: bool,
+}
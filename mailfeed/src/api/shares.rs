@@ -0,0 +1,6 @@
+mod handlers;
+mod routes;
+mod types;
+
+pub(super) use self::handlers::web_link;
+pub use self::routes::{page_routes, routes};
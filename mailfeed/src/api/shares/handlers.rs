@@ -0,0 +1,320 @@
+use actix_web::{delete, get, http::header, post, web, HttpResponse, Responder, ResponseError};
+use askama::Template;
+use chrono::{TimeZone, Utc};
+
+use super::types::{
+    CreatedShareLink, MaintenancePage, RqShareLinkPath, RqShareToken, RqSharesPath, SharePage,
+    SharedItem, MAX_SHARE_LINKS, SUMMARY_LENGTH,
+};
+use crate::{
+    claims::Claims,
+    models::{
+        feed::Feed,
+        feed_item::FeedItem,
+        ids::{ShareLinkId, SubscriptionId, UserId},
+        maintenance_mode::MaintenanceMode,
+        share_link::{NewShareLink, ShareLink},
+        subscription::Subscription,
+        user::{User, UserQuery},
+    },
+    security::validation::Validate,
+    tasks::html_to_text::html_to_text_truncated,
+    RqDbPool,
+};
+
+/// A subscription's share links, without their tokens
+#[get("")]
+pub async fn get_share_links(pool: RqDbPool, path: RqSharesPath, claims: Claims) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_id = match path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) if subscription.user_id == user_id => {}
+        _ => return HttpResponse::NotFound().body("Subscription not found"),
+    }
+
+    match ShareLink::get_for_subscription(&mut conn, sub_id) {
+        Ok(links) => HttpResponse::Ok().json(links),
+        Err(e) => {
+            log::error!("Error getting share links: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting share links")
+        }
+    }
+}
+
+#[post("")]
+pub async fn create_share_link(
+    pool: RqDbPool,
+    path: RqSharesPath,
+    link: web::Json<NewShareLink>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_id = match path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    let new_link = NewShareLink {
+        subscription_id: sub_id,
+        created_at: Utc::now().timestamp(),
+        ..link.into_inner()
+    };
+    if let Err(errors) = new_link.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) if subscription.user_id == user_id => {}
+        _ => return HttpResponse::NotFound().body("Subscription not found"),
+    }
+
+    match ShareLink::count_for_subscription(&mut conn, sub_id) {
+        Ok(count) if count >= MAX_SHARE_LINKS => {
+            return HttpResponse::BadRequest().body(format!(
+                "At most {} share links are allowed per subscription",
+                MAX_SHARE_LINKS
+            ))
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error counting share links: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error creating share link");
+        }
+    }
+
+    match new_link.insert(&mut conn) {
+        Ok((link, token)) => {
+            log::info!("Created share link {} for subscription {}", link.id, sub_id);
+            HttpResponse::Ok().json(CreatedShareLink {
+                link,
+                path: format!("/share/{}", token),
+            })
+        }
+        Err(e) => {
+            log::error!("Error creating share link: {:?}", e);
+            HttpResponse::InternalServerError().body("Error creating share link")
+        }
+    }
+}
+
+/// Revoke a share link, after which its page is gone
+#[delete("/{link_id}")]
+pub async fn delete_share_link(
+    pool: RqDbPool,
+    path: RqShareLinkPath,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_id = match path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    let link_id = match path.link_id.parse::<ShareLinkId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid share link ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) if subscription.user_id == user_id => {}
+        _ => return HttpResponse::NotFound().body("Subscription not found"),
+    }
+
+    match ShareLink::delete(&mut conn, sub_id, link_id) {
+        Ok(0) => HttpResponse::NotFound().body("Share link not found"),
+        Ok(_) => {
+            log::info!("Revoked share link {} of subscription {}", link_id, sub_id);
+            HttpResponse::Ok().body("Share link revoked")
+        }
+        Err(e) => {
+            log::error!("Error deleting share link: {:?}", e);
+            HttpResponse::InternalServerError().body("Error deleting share link")
+        }
+    }
+}
+
+/// The public page for a share link. Revoked links, and those of deleted
+/// or deactivated users, are indistinguishable from ones that never existed.
+#[get("/{token}")]
+pub async fn share_page(pool: RqDbPool, path: RqShareToken) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let maintenance = MaintenanceMode::load(&mut conn);
+    if maintenance.enabled {
+        let page = MaintenancePage {
+            message: maintenance.message(),
+        };
+        return match page.render() {
+            Ok(body) => HttpResponse::ServiceUnavailable()
+                .content_type("text/html; charset=utf-8")
+                .body(body),
+            Err(e) => {
+                log::error!("Error rendering maintenance page: {:?}", e);
+                HttpResponse::ServiceUnavailable().body(maintenance.message().to_string())
+            }
+        };
+    }
+
+    let link = match ShareLink::view(&mut conn, &path.token, Utc::now().timestamp()) {
+        Ok(Some(link)) => link,
+        Ok(None) => return HttpResponse::NotFound().body("Share link not found"),
+        Err(e) => {
+            log::error!("Error getting share link: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error getting share link");
+        }
+    };
+
+    let subscription = match Subscription::get_by_id(&mut conn, link.subscription_id) {
+        Some(subscription) => subscription,
+        None => return HttpResponse::NotFound().body("Share link not found"),
+    };
+    match User::get(&mut conn, UserQuery::Id(subscription.user_id)) {
+        Some(user) if user.is_active => {}
+        _ => return HttpResponse::NotFound().body("Share link not found"),
+    }
+    let feed = match Feed::get_by_id(&mut conn, subscription.feed_id) {
+        Some(feed) => feed,
+        None => return HttpResponse::InternalServerError().body("Error getting feed"),
+    };
+
+    let items = FeedItem::latest(&mut conn, feed.id, link.item_count.into());
+    let page = SharePage {
+        title: subscription.display_name(&feed),
+        description: subscription
+            .display_description(&feed)
+            .map(|description| html_to_text_truncated(description, SUMMARY_LENGTH).0),
+        homepage: web_link(subscription.display_homepage(&feed)),
+        items: items.iter().map(|item| shared_item(item, &feed)).collect(),
+    };
+
+    match page.render() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            // keep the token out of the Referer sent to linked sites
+            .insert_header((header::REFERRER_POLICY, "no-referrer"))
+            .body(body),
+        Err(e) => {
+            log::error!("Error rendering share page: {:?}", e);
+            HttpResponse::InternalServerError().body("Error rendering share page")
+        }
+    }
+}
+
+fn shared_item(item: &FeedItem, feed: &Feed) -> SharedItem {
+    let (link, comments) = item.display_links(feed.link_mode());
+    SharedItem {
+        title: item.title.clone(),
+        link: web_link(link).map(str::to_string),
+        comments: comments.and_then(web_link).map(str::to_string),
+        author: item.author.clone(),
+        date: Utc
+            .timestamp_opt(item.pub_date, 0)
+            .single()
+            .map(|date| date.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_default(),
+        summary: item
+            .description
+            .as_deref()
+            .map(|description| html_to_text_truncated(description, SUMMARY_LENGTH).0),
+    }
+}
+
+/// Feed-supplied URLs end up in links on a page served from this origin, so
+/// anything but http(s) (e.g. `javascript:`) is dropped
+pub(crate) fn web_link(url: &str) -> Option<&str> {
+    let lower = url.to_ascii_lowercase();
+    (lower.starts_with("http://") || lower.starts_with("https://")).then_some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_link() {
+        assert_eq!(web_link("https://a.com/1"), Some("https://a.com/1"));
+        assert_eq!(web_link("HTTP://a.com"), Some("HTTP://a.com"));
+        assert_eq!(web_link("javascript:alert(1)"), None);
+        assert_eq!(web_link("/relative"), None);
+    }
+
+    #[test]
+    fn test_share_page_escapes_feed_content() {
+        let page = SharePage {
+            title: "<b>Feed</b>",
+            description: None,
+            homepage: None,
+            items: vec![SharedItem {
+                title: "<script>alert(1)</script>".to_string(),
+                link: Some("https://a.com/?a=1&b=2".to_string()),
+                comments: None,
+                author: None,
+                date: "2026-10-16 00:00 UTC".to_string(),
+                summary: Some("x < y".to_string()),
+            }],
+        };
+        let html = page.render().unwrap();
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;b&gt;Feed"));
+        assert!(html.contains("x &lt; y"));
+    }
+}
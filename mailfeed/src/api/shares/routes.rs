@@ -0,0 +1,14 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/users/{user_id}/subscriptions/{sub_id}/shares")
+        .service(handlers::get_share_links)
+        .service(handlers::create_share_link)
+        .service(handlers::delete_share_link)
+}
+
+/// The public pages themselves, outside `/api` and without authentication
+pub fn page_routes() -> Scope {
+    web::scope("/share").service(handlers::share_page)
+}
@@ -0,0 +1,64 @@
+use actix_web::web;
+use askama::Template;
+use serde::{Deserialize, Serialize};
+
+use crate::models::share_link::ShareLink;
+
+/// Most share links a subscription may have
+pub const MAX_SHARE_LINKS: i64 = 10;
+/// Characters of each item's description shown on a share page
+pub const SUMMARY_LENGTH: usize = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct SharesPath {
+    pub user_id: String,
+    pub sub_id: String,
+}
+pub type RqSharesPath = web::Path<SharesPath>;
+
+#[derive(Debug, Deserialize)]
+pub struct ShareLinkPath {
+    pub user_id: String,
+    pub sub_id: String,
+    pub link_id: String,
+}
+pub type RqShareLinkPath = web::Path<ShareLinkPath>;
+
+#[derive(Debug, Deserialize)]
+pub struct ShareTokenPath {
+    pub token: String,
+}
+pub type RqShareToken = web::Path<ShareTokenPath>;
+
+#[derive(Debug, Serialize)]
+pub struct CreatedShareLink {
+    #[serde(flatten)]
+    pub link: ShareLink,
+    /// where the page is served, relative to the server root; only shown here
+    pub path: String,
+}
+
+#[derive(Template)]
+#[template(path = "share.html")]
+pub struct SharePage<'a> {
+    pub title: &'a str,
+    pub description: Option<String>,
+    pub homepage: Option<&'a str>,
+    pub items: Vec<SharedItem>,
+}
+
+/// Shown instead of share pages while the instance is in maintenance mode
+#[derive(Template)]
+#[template(path = "maintenance.html")]
+pub struct MaintenancePage<'a> {
+    pub message: &'a str,
+}
+
+pub struct SharedItem {
+    pub title: String,
+    pub link: Option<String>,
+    pub comments: Option<String>,
+    pub author: Option<String>,
+    pub date: String,
+    pub summary: Option<String>,
+}
@@ -0,0 +1,64 @@
+use actix_web::{get, HttpResponse, Responder};
+use chrono::Utc;
+
+use super::types::{InstanceInfo, Status};
+use crate::{
+    models::{admin_contact::AdminContact, maintenance_mode::MaintenanceMode},
+    RqDbPool,
+};
+
+/// Whether the instance is in maintenance mode, so the UI can say so. No
+/// login needed.
+#[get("")]
+pub async fn get_status(pool: RqDbPool) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let mode = MaintenanceMode::load(&mut conn);
+    HttpResponse::Ok().json(Status {
+        maintenance_mode: mode.enabled,
+        message: mode.enabled.then(|| mode.message()),
+    })
+}
+
+/// The server version and admin contact. No login needed.
+#[get("/instance")]
+pub async fn get_instance_info(pool: RqDbPool) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(InstanceInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        contact: AdminContact::load(&mut conn),
+    })
+}
+
+/// Where to report security issues, see RFC 9116. Not found until the admin
+/// has set a contact.
+#[get("/security.txt")]
+pub async fn security_txt(pool: RqDbPool) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match AdminContact::load(&mut conn).security_txt(Utc::now()) {
+        Some(text) => HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(text),
+        None => HttpResponse::NotFound().body("Not found"),
+    }
+}
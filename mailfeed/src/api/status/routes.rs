@@ -0,0 +1,13 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/status")
+        .service(handlers::get_instance_info)
+        .service(handlers::get_status)
+}
+
+/// Served at the site root rather than under /api
+pub fn well_known_routes() -> Scope {
+    web::scope("/.well-known").service(handlers::security_txt)
+}
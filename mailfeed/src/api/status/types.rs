@@ -0,0 +1,19 @@
+use serde::Serialize;
+
+use crate::models::admin_contact::AdminContact;
+
+#[derive(Debug, Serialize)]
+pub struct Status<'a> {
+    pub maintenance_mode: bool,
+    /// what to show users while in maintenance mode
+    pub message: Option<&'a str>,
+}
+
+/// Public facts about the instance, for an about page or for people who
+/// need to report a problem
+#[derive(Debug, Serialize)]
+pub struct InstanceInfo {
+    pub version: &'static str,
+    /// how to reach the admin, empty if they haven't said
+    pub contact: AdminContact,
+}
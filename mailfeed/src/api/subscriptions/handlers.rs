@@ -1,23 +1,215 @@
-use actix_web::{delete, get, patch, post, web, HttpResponse, Responder};
+use std::collections::HashMap;
 
-use super::types::{RqSubId, SubscriptionCreate, SubscriptionResponse};
+use actix_multipart::Multipart;
+use actix_web::{
+    delete, get, http::StatusCode, patch, post, put, web, HttpMessage, HttpRequest, HttpResponse,
+    Responder, ResponseError,
+};
+use chrono::Utc;
+use futures_util::StreamExt;
+
+use super::types::{
+    CloneRequest, FeedError, ImportQuery, PreviewResponse, RqSubId, ScheduleDebug, SendNowResponse,
+    SubscriptionCreate, SubscriptionOrder, SubscriptionResponse, SubscriptionSummary,
+    SubscriptionUpdate, SubscriptionsState, TagsUpdate, MAX_DELIVERIES, MAX_IMPORT_BYTES,
+    MAX_IMPORT_FEEDS, PREVIEW_SAMPLE_ITEMS,
+};
 use crate::{
-    api::users::RqUserId,
+    api::{etag::json_with_etag, users::RqUserId},
     claims::Claims,
+    db::DbConnection,
     models::{
+        audit_log::{AuditAction, NewAuditEntry},
+        delivery::Delivery,
+        delivery_webhook::DeliveryWebhook,
+        delivery_window::DeliveryWindow,
+        discord_webhook::DiscordWebhook,
         feed::{Feed, NewFeed},
-        subscription::{NewSubscription, Subscription},
+        feed_credentials::FeedCredentials,
+        feed_item::FeedItem,
+        ids::{SubscriptionId, UserId},
+        keyword_filter::Keywords,
+        matrix_settings::MatrixSettings,
+        max_item_age::MaxItemAge,
+        onboarding::{Onboarding, OnboardingStep},
+        push_settings::PushSettings,
+        quotas::{QuotaError, Quotas},
+        subscription::{DeliveryMethod, Frequency, NewSubscription, Subscription},
+        subscription_sort::{SortKey, SortSettings, SubscriptionSort},
+        subscription_template::SubscriptionTemplate,
+        tag::Tag,
+        user::{User, UserQuery},
+    },
+    security::validation::Validate,
+    tasks::{
+        dispatch::{
+            decisions::SendDecisions,
+            passing_filters,
+            runner::{send_now as dispatch_now, SendError},
+            Batch, Channels, SendSlots,
+        },
+        email_sender::{
+            channel::unavailable as email_unavailable, runner::preview as preview_email,
+        },
+        feed_monitor::{import, opml},
+        jobs::{JobKind, Jobs},
+        mqtt::Mqtt,
+        webhooks::Webhooks,
     },
     RqDbPool,
 };
 
 #[get("")]
 pub async fn get_all_subscriptions(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let subscriptions = match Subscription::get_all_for_user(&mut conn, user_id) {
+        Ok(subscriptions) => subscriptions,
+        Err(_) => return HttpResponse::InternalServerError().body("Error getting subscriptions"),
+    };
+
+    let mut tags = match Tag::by_subscription(&mut conn, user_id) {
+        Ok(tags) => tags,
+        Err(e) => {
+            log::error!("Error getting subscription tags: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error getting subscriptions");
+        }
+    };
+
+    let mut stats = match Subscription::list_stats(&mut conn, user_id) {
+        Ok(stats) => stats,
+        Err(e) => {
+            log::error!("Error getting subscription stats: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error getting subscriptions");
+        }
+    };
+
+    let mut subscriptions: Vec<SubscriptionSummary> = subscriptions
+        .into_iter()
+        .map(|subscription| {
+            let feed = Feed::get_by_id(&mut conn, subscription.feed_id);
+            SubscriptionSummary {
+                name: feed
+                    .as_ref()
+                    .map(|feed| subscription.display_name(feed))
+                    .unwrap_or(&subscription.friendly_name)
+                    .to_string(),
+                stats: stats.remove(&subscription.id).unwrap_or_default(),
+                feed_error: feed.as_ref().and_then(FeedError::for_feed),
+                feed_warnings: feed.map(|feed| feed.parse_warnings).unwrap_or_default(),
+                tags: tags
+                    .remove(&subscription.id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|tag| tag.name)
+                    .collect(),
+                subscription,
+            }
+        })
+        .collect();
+
+    let sort = SortSettings::load(&mut conn, user_id).sort;
+    subscriptions.sort_by(|a, b| sort.compare(&sort_key(a), &sort_key(b)));
+
+    // revalidate every time, since the user may have just changed one
+    json_with_etag(&req, &subscriptions, 0)
+}
+
+fn sort_key(summary: &SubscriptionSummary) -> SortKey<'_> {
+    SortKey {
+        id: summary.subscription.id,
+        name: &summary.name,
+        position: summary.subscription.position,
+        stats: summary.stats,
+    }
+}
+
+/// How the dashboard sorts the user's subscriptions
+#[get("/sort")]
+pub async fn get_sort(pool: RqDbPool, path: RqUserId, claims: Claims) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(SortSettings::load(&mut conn, user_id))
+}
+
+#[put("/sort")]
+pub async fn set_sort(
+    pool: RqDbPool,
+    path: RqUserId,
+    settings: web::Json<SortSettings>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match settings.save(&mut conn, user_id) {
+        Ok(()) => HttpResponse::Ok().json(settings.into_inner()),
+        Err(e) => {
+            log::error!("Error saving subscription sort: {:?}", e);
+            HttpResponse::InternalServerError().body("Error saving sort")
+        }
+    }
+}
+
+/// Put the subscriptions in the order the user dragged them into, and
+/// switch the dashboard to that order
+#[put("/order")]
+pub async fn set_order(
     pool: RqDbPool,
     path: RqUserId,
+    order: web::Json<SubscriptionOrder>,
     claims: Claims,
 ) -> impl Responder {
-    let user_id = match path.user_id.parse::<i32>() {
+    let user_id = match path.user_id.parse::<UserId>() {
         Ok(id) => id,
         Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
     };
@@ -26,6 +218,10 @@ pub async fn get_all_subscriptions(
         return HttpResponse::Forbidden().body("Forbidden");
     }
 
+    if let Err(errors) = order.validate() {
+        return errors.error_response();
+    }
+
     let mut conn = match pool.get() {
         Ok(conn) => conn,
         Err(err) => {
@@ -38,8 +234,64 @@ pub async fn get_all_subscriptions(
         Ok(subscriptions) => subscriptions,
         Err(_) => return HttpResponse::InternalServerError().body("Error getting subscriptions"),
     };
+    if let Some(id) = order
+        .ids
+        .iter()
+        .find(|id| !subscriptions.iter().any(|sub| sub.id == **id))
+    {
+        return HttpResponse::BadRequest().body(format!("Unknown subscription {}", id));
+    }
+
+    if let Err(e) = Subscription::set_positions(&mut conn, user_id, &order.ids) {
+        log::error!("Error ordering subscriptions: {:?}", e);
+        return HttpResponse::InternalServerError().body("Error ordering subscriptions");
+    }
+    let manual = SortSettings {
+        sort: SubscriptionSort::Manual,
+    };
+    match manual.save(&mut conn, user_id) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            log::error!("Error saving subscription sort: {:?}", e);
+            HttpResponse::InternalServerError().body("Error saving sort")
+        }
+    }
+}
+
+/// A hash of the subscription list, for dashboards polling for changes with
+/// If-None-Match. Cheaper than the list itself, which only needs loading
+/// again when this changes.
+#[get("/state")]
+pub async fn get_subscriptions_state(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
 
-    HttpResponse::Ok().json(subscriptions)
+    match Subscription::state_hash(&mut conn, user_id) {
+        Ok(hash) => json_with_etag(&req, &SubscriptionsState { hash }, 0),
+        Err(e) => {
+            log::error!("Error getting subscription state: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting subscriptions")
+        }
+    }
 }
 
 #[post("")]
@@ -49,7 +301,7 @@ pub async fn create_subscription(
     sub_req: web::Json<SubscriptionCreate>,
     claims: Claims,
 ) -> impl Responder {
-    let user_id = match path.user_id.parse::<i32>() {
+    let user_id = match path.user_id.parse::<UserId>() {
         Ok(id) => id,
         Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
     };
@@ -58,11 +310,52 @@ pub async fn create_subscription(
         return HttpResponse::Forbidden().body("Forbidden");
     }
 
-    // if sub_req.url isn't a valid URL, return 400
-    if url::Url::parse(&sub_req.url).is_err() {
-        return HttpResponse::BadRequest().body("Invalid feed URL");
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let sub_req = match sub_req.template_id {
+        Some(template_id) => match SubscriptionTemplate::get(&mut conn, user_id, template_id) {
+            Ok(Some(template)) => sub_req.into_inner().with_template(&template),
+            Ok(None) => return HttpResponse::NotFound().body("Template not found"),
+            Err(e) => {
+                log::error!("Error getting subscription template: {:?}", e);
+                return HttpResponse::InternalServerError().body("Error getting template");
+            }
+        },
+        None => sub_req.into_inner(),
+    };
+
+    subscribe(&mut conn, user_id, &sub_req)
+}
+
+/// Subscribe to another feed with the same settings as this subscription
+#[post("/{sub_id}/clone")]
+pub async fn clone_subscription(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    sub_path: RqSubId,
+    clone_req: web::Json<CloneRequest>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
     }
 
+    let sub_id = match sub_path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
     let mut conn = match pool.get() {
         Ok(conn) => conn,
         Err(err) => {
@@ -71,16 +364,60 @@ pub async fn create_subscription(
         }
     };
 
+    let subscription = match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) => subscription,
+        None => return HttpResponse::NotFound().body("Subscription not found"),
+    };
+
+    if subscription.user_id != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_req = SubscriptionCreate::cloned_from(&subscription, clone_req.into_inner());
+    subscribe(&mut conn, user_id, &sub_req)
+}
+
+/// Create the subscription, and its feed if no one is subscribed to it yet
+fn subscribe(
+    conn: &mut DbConnection,
+    user_id: UserId,
+    sub_req: &SubscriptionCreate,
+) -> HttpResponse {
+    if let Err(errors) = sub_req.validate() {
+        return errors.error_response();
+    }
+    // checked by validate
+    let frequency = sub_req.frequency.clone().unwrap_or(Frequency::Daily);
+    let delivery_method = sub_req.delivery_method.unwrap_or_default();
+    if let Err(message) = check_delivery_method(conn, user_id, delivery_method, &frequency) {
+        return HttpResponse::BadRequest().body(message);
+    }
+
     // check for an existing feed to this URL
-    let feed = match Feed::get_by_url(&mut conn, &sub_req.url) {
+    let existing_feed = Feed::get_by_url(conn, &sub_req.url);
+    let credentials = match sealed_credentials(existing_feed.as_ref(), sub_req.credentials.as_ref())
+    {
+        Ok(credentials) => credentials,
+        Err((status, message)) => return HttpResponse::build(status).body(message),
+    };
+
+    let realtime = frequency.is_realtime();
+    if let Err(e) =
+        Quotas::load(conn).check_new_subscription(conn, user_id, realtime, existing_feed.is_none())
+    {
+        return quota_exceeded(e);
+    }
+
+    let feed = match existing_feed {
         Some(feed) => feed,
         None => {
             // if no feed exists, create one
             let new_feed = NewFeed {
                 url: &sub_req.url,
+                credentials,
                 ..Default::default()
             };
-            let new_feed = new_feed.insert(&mut conn);
+            let new_feed = new_feed.insert(conn);
             match new_feed {
                 Some(feed) => feed,
                 None => {
@@ -91,7 +428,7 @@ pub async fn create_subscription(
     };
 
     // if the user already has a subscription to this feed, return 400
-    let user_subs = match Subscription::get_all_for_user(&mut conn, user_id) {
+    let user_subs = match Subscription::get_all_for_user(conn, user_id) {
         Ok(subs) => subs,
         Err(_) => return HttpResponse::InternalServerError().body("Error getting subscriptions"),
     };
@@ -102,7 +439,8 @@ pub async fn create_subscription(
     let mut new_sub = NewSubscription {
         user_id,
         feed_id: feed.id,
-        frequency: sub_req.frequency,
+        frequency,
+        delivery_method,
         ..Default::default()
     };
 
@@ -110,40 +448,226 @@ pub async fn create_subscription(
         new_sub.max_items = *max_items;
     }
 
-    if let Some(friendly_name) = &sub_req.friendly_name {
-        new_sub.friendly_name = friendly_name.clone();
-    }
+    new_sub.send_email = sub_req.send_email.clone();
+    new_sub.subject_prefix = sub_req.subject_prefix.clone();
+    new_sub.subject_template = sub_req.subject_template.clone();
+    new_sub.show_stats = sub_req.show_stats.unwrap_or(false);
+    new_sub.min_score = sub_req.min_score.filter(|n| *n > 0);
+    new_sub.min_comments = sub_req.min_comments.filter(|n| *n > 0);
+    new_sub.max_item_age_days = sub_req.max_item_age_days.filter(|n| *n > 0);
+    new_sub.delivery_window = sub_req
+        .delivery_window
+        .as_deref()
+        .and_then(DeliveryWindow::parse)
+        .map(|window| window.to_string());
+    new_sub.include_keywords = sub_req
+        .include_keywords
+        .as_ref()
+        .map(Keywords::trimmed)
+        .unwrap_or_default();
+    new_sub.exclude_keywords = sub_req
+        .exclude_keywords
+        .as_ref()
+        .map(Keywords::trimmed)
+        .unwrap_or_default();
+
+    // default to the feed's title, which may still be empty if the feed
+    // hasn't been fetched yet; see Subscription::display_name
+    new_sub.friendly_name = match &sub_req.friendly_name {
+        Some(friendly_name) => friendly_name.clone(),
+        None => feed.title.clone(),
+    };
 
-    let subscription = match new_sub.insert(&mut conn) {
+    let subscription = match new_sub.insert(conn) {
         Some(subscription) => subscription,
         None => {
             return HttpResponse::InternalServerError().body("Error creating subscription");
         }
     };
+    if let Err(e) = Onboarding::complete(conn, user_id, OnboardingStep::AddFeed) {
+        log::warn!("Error updating onboarding for user {}: {:?}", user_id, e);
+    }
+    NewAuditEntry::new(
+        AuditAction::SubscriptionCreated,
+        Some(user_id),
+        Some(user_id),
+    )
+    .details(format!(
+        "subscription {} to feed {}",
+        subscription.id, feed.id
+    ))
+    .record(conn);
 
-    let res = SubscriptionResponse { subscription, feed };
+    let res = SubscriptionResponse {
+        subscription,
+        feed,
+        last_delivery: None,
+    };
 
     HttpResponse::Ok().json(res)
 }
 
-#[get("/{sub_id}")]
-pub async fn get_subscription() -> impl Responder {
-    HttpResponse::Ok().body("get_subscription")
+/// The credentials to store on a new feed, sealed. Private feeds are shared
+/// only with users who give the same credentials, so subscribing to one
+/// doesn't get around its login.
+fn sealed_credentials(
+    existing_feed: Option<&Feed>,
+    credentials: Option<&FeedCredentials>,
+) -> Result<Option<String>, (StatusCode, &'static str)> {
+    if let Some(feed) = existing_feed {
+        let current = feed.credentials.as_deref().map(FeedCredentials::unseal);
+        return match (current, credentials) {
+            (None, None) => Ok(None),
+            (Some(Some(current)), Some(credentials)) if current == *credentials => Ok(None),
+            (None, Some(_)) => Err((
+                StatusCode::BAD_REQUEST,
+                "This feed is already added without credentials",
+            )),
+            _ => Err((
+                StatusCode::FORBIDDEN,
+                "Wrong credentials for this private feed",
+            )),
+        };
+    }
+    match credentials {
+        None => Ok(None),
+        Some(credentials) => match credentials.seal() {
+            Some(sealed) => Ok(Some(sealed)),
+            None => Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Private feeds need MF_SECRET_KEY to be set",
+            )),
+        },
+    }
 }
 
-#[patch("/{sub_id}")]
-pub async fn update_subscription() -> impl Responder {
-    HttpResponse::Ok().body("update_subscription")
+/// Subscribe to every feed in an OPML file, sent as the body or uploaded as
+/// a form. Feeds are checked and added in the background, so this returns a
+/// job to poll for progress.
+#[post("/import")]
+pub async fn import_subscriptions(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    query: web::Query<ImportQuery>,
+    payload: web::Payload,
+    jobs: web::Data<Jobs>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = query.validate() {
+        return errors.error_response();
+    }
+
+    let opml = match read_opml(&req, payload).await {
+        Ok(opml) => opml,
+        Err(message) => return HttpResponse::BadRequest().body(message),
+    };
+    let feeds = match opml::parse(&opml) {
+        Ok(feeds) => feeds,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    if feeds.len() > MAX_IMPORT_FEEDS {
+        return HttpResponse::BadRequest().body(format!(
+            "At most {} feeds can be imported at once",
+            MAX_IMPORT_FEEDS
+        ));
+    }
+
+    if jobs.running(JobKind::ImportFeeds, Some(user_id)).is_some() {
+        return HttpResponse::BadRequest().body("An import is already running");
+    }
+
+    let frequency = query.frequency();
+    let job = jobs.start(
+        JobKind::ImportFeeds,
+        user_id,
+        feeds.len(),
+        Utc::now().timestamp(),
+    );
+    log::info!(
+        "User {} is importing {} feeds (job {})",
+        user_id,
+        job.total,
+        job.id
+    );
+    if let Ok(mut conn) = pool.get() {
+        NewAuditEntry::new(
+            AuditAction::SubscriptionCreated,
+            Some(user_id),
+            Some(user_id),
+        )
+        .details(format!("importing {} feeds", job.total))
+        .record(&mut conn);
+    }
+    tokio::spawn(import::run(
+        pool.get_ref().clone(),
+        jobs.get_ref().clone(),
+        job.id,
+        user_id,
+        feeds,
+        frequency,
+    ));
+
+    HttpResponse::Ok().json(job)
 }
 
-#[delete("/{sub_id}")]
-pub async fn delete_subscription(
+/// The OPML file from the body, or the first file in a `multipart/form-data`
+/// upload, at most `MAX_IMPORT_BYTES` either way
+async fn read_opml(req: &HttpRequest, payload: web::Payload) -> Result<String, String> {
+    let too_large = format!("OPML file is larger than {} bytes", MAX_IMPORT_BYTES);
+    let bytes = if req.content_type() == "multipart/form-data" {
+        let mut multipart = Multipart::new(req.headers(), payload);
+        let mut file = None;
+        while let Some(field) = multipart.next().await {
+            let mut field = field.map_err(|e| e.to_string())?;
+            // skip any other form fields
+            if field
+                .content_disposition()
+                .and_then(|disposition| disposition.get_filename())
+                .is_none()
+            {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            while let Some(chunk) = field.next().await {
+                let chunk = chunk.map_err(|e| e.to_string())?;
+                if bytes.len() + chunk.len() > MAX_IMPORT_BYTES {
+                    return Err(too_large);
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+            file = Some(bytes);
+            break;
+        }
+        file.ok_or("No file in the upload")?
+    } else {
+        payload
+            .to_bytes_limited(MAX_IMPORT_BYTES)
+            .await
+            .map_err(|_| too_large)?
+            .map_err(|e| e.to_string())?
+            .to_vec()
+    };
+    String::from_utf8(bytes).map_err(|_| "OPML file isn't valid UTF-8".to_string())
+}
+
+#[get("/{sub_id}")]
+pub async fn get_subscription(
     pool: RqDbPool,
     user_path: RqUserId,
     sub_path: RqSubId,
     claims: Claims,
 ) -> impl Responder {
-    let user_id = match user_path.user_id.parse::<i32>() {
+    let user_id = match user_path.user_id.parse::<UserId>() {
         Ok(id) => id,
         Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
     };
@@ -152,7 +676,7 @@ pub async fn delete_subscription(
         return HttpResponse::Forbidden().body("Forbidden");
     }
 
-    let sub_id = match sub_path.sub_id.parse::<i32>() {
+    let sub_id = match sub_path.sub_id.parse::<SubscriptionId>() {
         Ok(id) => id,
         Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
     };
@@ -174,12 +698,787 @@ pub async fn delete_subscription(
         return HttpResponse::Forbidden().body("Forbidden");
     }
 
-    let delete_sub_ok = Subscription::delete(&mut conn, sub_id);
-    let delete_feed_ok = Feed::delete(&mut conn, subscription.feed_id);
+    let feed = match Feed::get_by_id(&mut conn, subscription.feed_id) {
+        Some(feed) => feed,
+        None => return HttpResponse::InternalServerError().body("Error getting feed"),
+    };
 
-    if !delete_sub_ok || !delete_feed_ok {
-        return HttpResponse::InternalServerError().body("Error deleting subscription");
+    let last_delivery = match Delivery::latest_for_subscription(&mut conn, sub_id) {
+        Ok(delivery) => delivery,
+        Err(_) => return HttpResponse::InternalServerError().body("Error getting deliveries"),
+    };
+
+    HttpResponse::Ok().json(SubscriptionResponse {
+        subscription,
+        feed,
+        last_delivery,
+    })
+}
+
+#[get("/{sub_id}/tags")]
+pub async fn get_subscription_tags(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    sub_path: RqSubId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
     }
 
-    HttpResponse::Ok().body("Subscription deleted")
+    let sub_id = match sub_path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) if subscription.user_id == user_id => {}
+        Some(_) => return HttpResponse::Forbidden().body("Forbidden"),
+        None => return HttpResponse::NotFound().body("Subscription not found"),
+    }
+
+    match Tag::get_for_subscription(&mut conn, sub_id) {
+        Ok(tags) => HttpResponse::Ok().json(tags),
+        Err(e) => {
+            log::error!("Error getting subscription tags: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting tags")
+        }
+    }
+}
+
+/// Replace the subscription's tags, creating any the user doesn't have yet
+#[put("/{sub_id}/tags")]
+pub async fn set_subscription_tags(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    sub_path: RqSubId,
+    update: web::Json<TagsUpdate>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_id = match sub_path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    if let Err(errors) = update.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) if subscription.user_id == user_id => {}
+        Some(_) => return HttpResponse::Forbidden().body("Forbidden"),
+        None => return HttpResponse::NotFound().body("Subscription not found"),
+    }
+
+    let now = Utc::now().timestamp();
+    match Tag::set_for_subscription(&mut conn, user_id, sub_id, &update.tags, now) {
+        Ok(tags) => HttpResponse::Ok().json(tags),
+        Err(e) => {
+            log::error!("Error setting subscription tags: {:?}", e);
+            HttpResponse::InternalServerError().body("Error setting tags")
+        }
+    }
+}
+
+/// The subscription's delivery ledger, newest first: when each email was
+/// handed to the SMTP relay and what the relay replied
+#[get("/{sub_id}/deliveries")]
+pub async fn get_deliveries(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    sub_path: RqSubId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_id = match sub_path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let subscription = match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) => subscription,
+        None => return HttpResponse::NotFound().body("Subscription not found"),
+    };
+
+    if subscription.user_id != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    match Delivery::get_for_subscription(&mut conn, sub_id, MAX_DELIVERIES) {
+        Ok(deliveries) => HttpResponse::Ok().json(deliveries),
+        Err(_) => HttpResponse::InternalServerError().body("Error getting deliveries"),
+    }
+}
+
+/// When the subscription will next be sent, and what the dispatcher
+/// decided about it and its items the last time it checked
+#[get("/{sub_id}/schedule-debug")]
+pub async fn get_schedule_debug(
+    pool: RqDbPool,
+    decisions: web::Data<SendDecisions>,
+    user_path: RqUserId,
+    sub_path: RqSubId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_id = match sub_path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let subscription = match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) => subscription,
+        None => return HttpResponse::NotFound().body("Subscription not found"),
+    };
+
+    if subscription.user_id != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let user = match User::get(&mut conn, UserQuery::Id(user_id)) {
+        Some(user) => user,
+        None => return HttpResponse::NotFound().body("User not found"),
+    };
+
+    let slots = SendSlots::from_env();
+    let now = Utc::now().timestamp();
+    HttpResponse::Ok().json(ScheduleDebug {
+        now,
+        next_send_time: slots.next_send_time(&subscription, &user),
+        due: slots.is_due(&subscription, &user, now),
+        last_check: decisions.get(sub_id),
+    })
+}
+
+/// Render the subscription's next delivery as it would be with the changes,
+/// without saving them, so forms can show their effect. Uses the feed's
+/// latest items if nothing new is waiting.
+#[post("/{sub_id}/preview")]
+pub async fn preview_subscription(
+    pool: RqDbPool,
+    channels: web::Data<Channels>,
+    user_path: RqUserId,
+    sub_path: RqSubId,
+    sub_req: web::Json<SubscriptionUpdate>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_id = match sub_path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    if let Err(errors) = sub_req.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let subscription = match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) => subscription,
+        None => return HttpResponse::NotFound().body("Subscription not found"),
+    };
+
+    if subscription.user_id != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let user = match User::get(&mut conn, UserQuery::Id(user_id)) {
+        Some(user) => user,
+        None => return HttpResponse::NotFound().body("User not found"),
+    };
+
+    let feed = match Feed::get_by_id(&mut conn, subscription.feed_id) {
+        Some(feed) => feed,
+        None => return HttpResponse::InternalServerError().body("Error getting feed"),
+    };
+
+    let subscription = subscription.with_changes(sub_req.into_inner().into());
+    let pending = FeedItem::items_after(&mut conn, feed.id, subscription.last_sent_time);
+    let sample = pending.is_empty();
+    let items = if sample {
+        FeedItem::latest(&mut conn, feed.id, PREVIEW_SAMPLE_ITEMS)
+    } else {
+        pending
+    };
+    let now = Utc::now().timestamp();
+    let items = passing_filters(&subscription, &MaxItemAge::load(&mut conn), items, now);
+
+    let mut preview = PreviewResponse {
+        delivery_method: subscription.delivery_method,
+        item_count: items.len(),
+        sample,
+        email: None,
+        messages: Vec::new(),
+    };
+    // stats aren't fetched, so they're left out
+    let batch = Batch {
+        sub: &subscription,
+        feed: &feed,
+        items: &items,
+        stats: &HashMap::new(),
+        now,
+    };
+    if subscription.delivery_method == DeliveryMethod::Email {
+        preview.email = Some(preview_email(&user, &batch));
+    }
+    if let Some(channel) = channels.get(subscription.delivery_method) {
+        preview.messages = channel.preview(&user, &batch);
+    }
+    HttpResponse::Ok().json(preview)
+}
+
+#[patch("/{sub_id}")]
+pub async fn update_subscription(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    sub_path: RqSubId,
+    sub_req: web::Json<SubscriptionUpdate>,
+    claims: Claims,
+) -> impl Responder {
+    if sub_req.is_empty() {
+        return HttpResponse::BadRequest().body("No fields to update");
+    }
+
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_id = match sub_path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    if let Err(errors) = sub_req.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let subscription = match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) => subscription,
+        None => return HttpResponse::NotFound().body("Subscription not found"),
+    };
+
+    if subscription.user_id != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if sub_req.delivery_method.is_some() || sub_req.frequency.is_some() {
+        let method = sub_req
+            .delivery_method
+            .unwrap_or(subscription.delivery_method);
+        let frequency = sub_req
+            .frequency
+            .as_ref()
+            .unwrap_or(&subscription.frequency);
+        if let Err(message) = check_delivery_method(&mut conn, user_id, method, frequency) {
+            return HttpResponse::BadRequest().body(message);
+        }
+    }
+
+    let becomes_realtime = matches!(sub_req.frequency, Some(Frequency::Realtime))
+        && !subscription.frequency.is_realtime();
+    if becomes_realtime {
+        if let Err(e) = Quotas::load(&mut conn).check_new_realtime(&mut conn, user_id) {
+            return quota_exceeded(e);
+        }
+    }
+
+    let update = sub_req.into_inner().into();
+    let subscription = match Subscription::update(&mut conn, sub_id, &update) {
+        Some(subscription) => subscription,
+        None => return HttpResponse::InternalServerError().body("Error updating subscription"),
+    };
+    NewAuditEntry::new(
+        AuditAction::SubscriptionUpdated,
+        Some(user_id),
+        Some(user_id),
+    )
+    .details(format!("subscription {}", sub_id))
+    .record(&mut conn);
+
+    let feed = match Feed::get_by_id(&mut conn, subscription.feed_id) {
+        Some(feed) => feed,
+        None => return HttpResponse::InternalServerError().body("Error getting feed"),
+    };
+
+    let last_delivery = match Delivery::latest_for_subscription(&mut conn, sub_id) {
+        Ok(delivery) => delivery,
+        Err(_) => return HttpResponse::InternalServerError().body("Error getting deliveries"),
+    };
+
+    HttpResponse::Ok().json(SubscriptionResponse {
+        subscription,
+        feed,
+        last_delivery,
+    })
+}
+
+#[delete("/{sub_id}")]
+pub async fn delete_subscription(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    sub_path: RqSubId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_id = match sub_path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let subscription = match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) => subscription,
+        None => return HttpResponse::NotFound().body("Subscription not found"),
+    };
+
+    if subscription.user_id != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let delete_sub_ok = Subscription::delete(&mut conn, sub_id);
+    let delete_feed_ok = Feed::delete(&mut conn, subscription.feed_id);
+
+    if !delete_sub_ok || !delete_feed_ok {
+        return HttpResponse::InternalServerError().body("Error deleting subscription");
+    }
+    NewAuditEntry::new(
+        AuditAction::SubscriptionDeleted,
+        Some(user_id),
+        Some(user_id),
+    )
+    .details(format!("subscription {}", sub_id))
+    .record(&mut conn);
+
+    HttpResponse::Ok().body("Subscription deleted")
+}
+
+#[post("/{sub_id}/send-now")]
+pub async fn send_now(
+    pool: RqDbPool,
+    webhooks: web::Data<Webhooks>,
+    mqtt: web::Data<Mqtt>,
+    channels: web::Data<Channels>,
+    user_path: RqUserId,
+    sub_path: RqSubId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let sub_id = match sub_path.sub_id.parse::<SubscriptionId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid subscription ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let subscription = match Subscription::get_by_id(&mut conn, sub_id) {
+        Some(subscription) => subscription,
+        None => return HttpResponse::NotFound().body("Subscription not found"),
+    };
+
+    if subscription.user_id != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let user = match User::get(&mut conn, UserQuery::Id(user_id)) {
+        Some(user) => user,
+        None => return HttpResponse::NotFound().body("User not found"),
+    };
+
+    let method = subscription.delivery_method;
+    if let Err(reason) = check_delivery_method(&mut conn, user_id, method, &subscription.frequency)
+    {
+        return HttpResponse::BadRequest().body(reason);
+    }
+    if method == DeliveryMethod::Email {
+        if let Some(reason) = email_unavailable(&mut conn) {
+            return HttpResponse::ServiceUnavailable().body(reason);
+        }
+    }
+    match dispatch_now(&mut conn, &channels, &webhooks, &mqtt, &user, &subscription).await {
+        Ok(items_sent) => HttpResponse::Ok().json(SendNowResponse { items_sent }),
+        Err(e @ SendError::NotSetUp(_)) => HttpResponse::BadRequest().body(e.to_string()),
+        Err(e @ SendError::NoChannel(_)) => HttpResponse::ServiceUnavailable().body(e.to_string()),
+        Err(e @ SendError::FeedNotFound) => HttpResponse::NotFound().body(e.to_string()),
+        // the error is kept in the delivery ledger as well
+        Err(SendError::Channel(e)) => {
+            HttpResponse::BadGateway().body(format!("Error sending subscription: {}", e))
+        }
+    }
+}
+
+/// Whether the user has set up where subscriptions delivered by `method`
+/// are sent, and the method suits the subscription's frequency
+fn check_delivery_method(
+    conn: &mut DbConnection,
+    user_id: UserId,
+    method: DeliveryMethod,
+    frequency: &Frequency,
+) -> Result<(), &'static str> {
+    match method {
+        DeliveryMethod::Email => Ok(()),
+        DeliveryMethod::Webhook => DeliveryWebhook::load(conn, user_id)
+            .target()
+            .map(|_| ())
+            .ok_or("Set up a delivery webhook first"),
+        DeliveryMethod::Discord => DiscordWebhook::load(conn, user_id)
+            .url()
+            .map(|_| ())
+            .ok_or("Set up a Discord webhook first"),
+        DeliveryMethod::Matrix => MatrixSettings::load(conn, user_id)
+            .room()
+            .map(|_| ())
+            .ok_or("Set up a Matrix room first"),
+        DeliveryMethod::Push if !frequency.is_realtime() => {
+            Err("Push notifications are only sent for realtime subscriptions")
+        }
+        DeliveryMethod::Push if !PushSettings::load(conn, user_id).is_configured() => {
+            Err("Set up ntfy or Gotify first")
+        }
+        DeliveryMethod::Push => Ok(()),
+    }
+}
+
+fn quota_exceeded(e: QuotaError) -> HttpResponse {
+    match e {
+        QuotaError::Database => {
+            HttpResponse::InternalServerError().body("Error checking usage limits")
+        }
+        e => HttpResponse::Forbidden().body(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::auth::jwt::create_access_token,
+        claims::Claims,
+        models::{role::Role, session::NewSession, user::NewUser},
+        tasks::webhook_sender::WebhookChannel,
+        test_helpers::test_helpers::get_test_db_pool,
+        DbPool,
+    };
+    use actix_web::{
+        test::{call_service, init_service, read_body_json, TestRequest},
+        App, FromRequest,
+    };
+
+    const OPML: &str = r#"<opml><body><outline xmlUrl="https://example.com/feed"/></body></opml>"#;
+
+    async fn read(req: TestRequest) -> Result<String, String> {
+        let (req, mut payload) = req.to_http_parts();
+        let payload = web::Payload::from_request(&req, &mut payload)
+            .await
+            .unwrap();
+        read_opml(&req, payload).await
+    }
+
+    #[actix_web::test]
+    async fn test_read_opml() {
+        let raw = TestRequest::post()
+            .insert_header(("Content-Type", "text/xml"))
+            .set_payload(OPML);
+        assert_eq!(read(raw).await, Ok(OPML.to_string()));
+
+        let form = format!(
+            "--boundary\r\n\
+             Content-Disposition: form-data; name=\"frequency\"\r\n\r\n\
+             daily\r\n\
+             --boundary\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"feeds.opml\"\r\n\
+             Content-Type: text/x-opml\r\n\r\n\
+             {}\r\n\
+             --boundary--\r\n",
+            OPML
+        );
+        let upload = TestRequest::post()
+            .insert_header(("Content-Type", "multipart/form-data; boundary=boundary"))
+            .set_payload(form);
+        assert_eq!(read(upload).await, Ok(OPML.to_string()));
+
+        let too_large = TestRequest::post().set_payload(vec![b'a'; MAX_IMPORT_BYTES + 1]);
+        assert!(read(too_large).await.is_err());
+    }
+
+    #[test]
+    fn test_private_feeds_need_their_credentials() {
+        let mut conn = crate::test_helpers::test_helpers::get_test_db_connection();
+        let feed = NewFeed {
+            url: "https://example.com/private.xml",
+            credentials: Some("sealed".to_string()),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let wrong = FeedCredentials::Bearer {
+            token: "guess".to_string(),
+        };
+        assert_eq!(
+            sealed_credentials(Some(&feed), None).unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            sealed_credentials(Some(&feed), Some(&wrong)).unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+
+        let public = Feed {
+            credentials: None,
+            ..feed
+        };
+        assert_eq!(sealed_credentials(Some(&public), None), Ok(None));
+        assert_eq!(
+            sealed_credentials(Some(&public), Some(&wrong))
+                .unwrap_err()
+                .0,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    /// A user and a logged in access token for them
+    fn log_in(conn: &mut DbConnection, email: &str) -> (User, String) {
+        let admin = Claims {
+            sub: UserId(0),
+            email: email.to_string(),
+            role: Role::Admin.into(),
+            exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
+        };
+        let new_user = NewUser {
+            email: email.to_string(),
+            password: "correct horse".to_string(),
+        };
+        User::create(conn, &new_user, admin).unwrap();
+        let user = User::get(conn, UserQuery::Email(email)).unwrap();
+        let session = NewSession::new(user.id, Utc::now().timestamp(), None, None)
+            .insert(conn)
+            .unwrap();
+        let token = create_access_token(&user, &session).unwrap();
+        (user, token)
+    }
+
+    /// Two users, each with a webhook subscription to a feed with no items
+    fn send_now_setup() -> (DbPool, Vec<(String, Subscription)>) {
+        let pool = get_test_db_pool();
+        let mut conn = pool.get().unwrap();
+        let feed = NewFeed {
+            url: "https://example.com/feed.xml",
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let users = ["ann@example.com", "bob@example.com"].map(|email| {
+            let (user, token) = log_in(&mut conn, email);
+            DeliveryWebhook {
+                url: "https://hooks.example.com/mailfeed".to_string(),
+                secret: Some("s3cr3t".to_string()),
+            }
+            .save(&mut conn, user.id)
+            .unwrap();
+            let sub = NewSubscription {
+                user_id: user.id,
+                feed_id: feed.id,
+                delivery_method: DeliveryMethod::Webhook,
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap();
+            (token, sub)
+        });
+        drop(conn);
+        (pool, users.into())
+    }
+
+    fn send_now_request(token: &str, user_id: UserId, sub_id: SubscriptionId) -> TestRequest {
+        TestRequest::post()
+            .uri(&format!(
+                "/users/{}/subscriptions/{}/send-now",
+                user_id, sub_id
+            ))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+    }
+
+    #[actix_web::test]
+    async fn test_send_now_only_for_own_subscriptions() {
+        let (pool, users) = send_now_setup();
+        let (ann_token, ann_sub) = &users[0];
+        let (_, bob_sub) = &users[1];
+        let (webhooks, _) = Webhooks::new();
+        let (mqtt, _) = Mqtt::new();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .app_data(web::Data::new(webhooks))
+                .app_data(web::Data::new(mqtt))
+                .app_data(web::Data::new(Channels::new(vec![
+                    Box::<WebhookChannel>::default(),
+                ])))
+                .service(super::super::routes()),
+        )
+        .await;
+
+        // as someone else
+        let req = send_now_request(ann_token, bob_sub.user_id, bob_sub.id).to_request();
+        assert_eq!(
+            call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+        // someone else's subscription under their own ID
+        let req = send_now_request(ann_token, ann_sub.user_id, bob_sub.id).to_request();
+        assert_eq!(
+            call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_send_now_without_pending_items() {
+        let (pool, users) = send_now_setup();
+        let (token, sub) = &users[0];
+        let (webhooks, _) = Webhooks::new();
+        let (mqtt, _) = Mqtt::new();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(webhooks))
+                .app_data(web::Data::new(mqtt))
+                .app_data(web::Data::new(Channels::new(vec![
+                    Box::<WebhookChannel>::default(),
+                ])))
+                .service(super::super::routes()),
+        )
+        .await;
+
+        let req = send_now_request(token, sub.user_id, sub.id).to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = read_body_json(res).await;
+        assert_eq!(body["items_sent"], 0);
+        // nothing was sent, so there's no delivery to record
+        let mut conn = pool.get().unwrap();
+        assert_eq!(
+            Delivery::get_for_subscription(&mut conn, sub.id, 10),
+            Ok(vec![])
+        );
+    }
 }
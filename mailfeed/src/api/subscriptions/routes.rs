@@ -4,8 +4,20 @@ use actix_web::{web, Scope};
 pub fn routes() -> Scope {
     web::scope("/users/{user_id}/subscriptions")
         .service(handlers::get_all_subscriptions)
+        .service(handlers::get_subscriptions_state)
+        .service(handlers::get_sort)
+        .service(handlers::set_sort)
+        .service(handlers::set_order)
         .service(handlers::create_subscription)
+        .service(handlers::import_subscriptions)
         .service(handlers::get_subscription)
+        .service(handlers::get_subscription_tags)
+        .service(handlers::set_subscription_tags)
+        .service(handlers::get_deliveries)
+        .service(handlers::get_schedule_debug)
+        .service(handlers::preview_subscription)
         .service(handlers::update_subscription)
         .service(handlers::delete_subscription)
+        .service(handlers::send_now)
+        .service(handlers::clone_subscription)
 }
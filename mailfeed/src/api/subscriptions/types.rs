@@ -1,10 +1,23 @@
+use std::collections::HashSet;
+
 use actix_web::web;
 use serde::{Deserialize, Serialize};
 
 use crate::models::{
-    feed::Feed,
-    subscription::{Frequency, Subscription},
+    delivery::Delivery,
+    delivery_window::DeliveryWindow,
+    feed::{Feed, FeedErrorKind, ParseWarnings},
+    feed_credentials::FeedCredentials,
+    ids::{SubscriptionId, TemplateId},
+    keyword_filter::Keywords,
+    subscription::{
+        DeliveryMethod, Frequency, ListStats, PartialSubscription, Subscription, Weekday,
+    },
+    subscription_template::SubscriptionTemplate,
+    tag,
 };
+use crate::security::validation::{Validate, ValidationErrors};
+use crate::tasks::{dispatch::decisions::SendDecision, email_sender::runner::EmailPreview};
 
 #[derive(Debug, Deserialize)]
 pub struct SubIdPath {
@@ -14,16 +27,404 @@ pub type RqSubId = web::Path<SubIdPath>;
 
 #[derive(Debug, Deserialize)]
 pub struct SubscriptionCreate {
+    /// fills in any settings not given
+    pub template_id: Option<TemplateId>,
     // items from Subscription
-    pub frequency: Frequency,
+    /// required unless there's a template
+    pub frequency: Option<Frequency>,
     pub friendly_name: Option<String>,
     pub max_items: Option<i32>,
+    pub send_email: Option<String>,
+    pub subject_prefix: Option<String>,
+    pub subject_template: Option<String>,
+    pub show_stats: Option<bool>,
+    pub min_score: Option<i32>,
+    pub min_comments: Option<i32>,
+    /// HH:MM-HH:MM in the user's time zone
+    pub delivery_window: Option<String>,
+    /// words, phrases or `/regex/`es, see KeywordFilter
+    pub include_keywords: Option<Keywords>,
+    pub exclude_keywords: Option<Keywords>,
+    /// email if not given
+    pub delivery_method: Option<DeliveryMethod>,
+    /// days, the instance default if not given or zero
+    pub max_item_age_days: Option<i32>,
     // items from Feed
     pub url: String,
+    /// for private feeds
+    pub credentials: Option<FeedCredentials>,
+}
+
+impl SubscriptionCreate {
+    /// The template's settings for any not given in the request
+    pub fn with_template(self, template: &SubscriptionTemplate) -> Self {
+        SubscriptionCreate {
+            frequency: self.frequency.or(Some(template.frequency.clone())),
+            max_items: self.max_items.or(Some(template.max_items)),
+            send_email: self.send_email.or(template.send_email.clone()),
+            subject_prefix: self.subject_prefix.or(template.subject_prefix.clone()),
+            subject_template: self.subject_template.or(template.subject_template.clone()),
+            show_stats: self.show_stats.or(Some(template.show_stats)),
+            min_score: self.min_score.or(template.min_score),
+            min_comments: self.min_comments.or(template.min_comments),
+            delivery_window: self.delivery_window.or(template.delivery_window.clone()),
+            ..self
+        }
+    }
+
+    /// The same settings as an existing subscription, for another feed.
+    /// Overrides of the feed's description and homepage aren't copied,
+    /// since they belong to the original feed.
+    pub fn cloned_from(sub: &Subscription, clone: CloneRequest) -> Self {
+        SubscriptionCreate {
+            template_id: None,
+            frequency: Some(sub.frequency.clone()),
+            friendly_name: clone.friendly_name,
+            max_items: Some(sub.max_items),
+            send_email: sub.send_email.clone(),
+            subject_prefix: sub.subject_prefix.clone(),
+            subject_template: sub.subject_template.clone(),
+            show_stats: Some(sub.show_stats),
+            min_score: sub.min_score,
+            min_comments: sub.min_comments,
+            delivery_window: sub.delivery_window.clone(),
+            include_keywords: Some(sub.include_keywords.clone()),
+            exclude_keywords: Some(sub.exclude_keywords.clone()),
+            delivery_method: Some(sub.delivery_method),
+            max_item_age_days: sub.max_item_age_days,
+            url: clone.url,
+            credentials: None,
+        }
+    }
+}
+
+impl Validate for SubscriptionCreate {
+    fn check(&self, errors: &mut ValidationErrors) {
+        errors.url("url", &self.url);
+        if let Some(credentials) = &self.credentials {
+            credentials.check(errors);
+        }
+        match &self.frequency {
+            Some(frequency) => errors.frequency("frequency", frequency),
+            None => errors.add("frequency", "Required without a template"),
+        }
+        if let Some(send_email) = &self.send_email {
+            errors.email("send_email", send_email);
+        }
+        if let Some(template) = &self.subject_template {
+            errors.subject_template("subject_template", template);
+        }
+        errors.non_negative("max_items", self.max_items);
+        // zero means no threshold
+        errors.non_negative("min_score", self.min_score);
+        errors.non_negative("min_comments", self.min_comments);
+        errors.max_item_age("max_item_age_days", self.max_item_age_days);
+        if let Some(window) = &self.delivery_window {
+            errors.delivery_window("delivery_window", window);
+        }
+        if let Some(keywords) = &self.include_keywords {
+            errors.keywords("include_keywords", keywords);
+        }
+        if let Some(keywords) = &self.exclude_keywords {
+            errors.keywords("exclude_keywords", keywords);
+        }
+    }
+}
+
+/// Subscribe to another feed with an existing subscription's settings
+#[derive(Debug, Deserialize)]
+pub struct CloneRequest {
+    pub url: String,
+    /// the new feed's title if not given
+    pub friendly_name: Option<String>,
+}
+
+/// A subscription as listed on the dashboard, flagged if its feed can't be
+/// fetched or had parse warnings
+#[derive(Debug, Serialize)]
+pub struct SubscriptionSummary {
+    #[serde(flatten)]
+    pub subscription: Subscription,
+    /// the friendly name, or the feed's title if it has none
+    pub name: String,
+    #[serde(flatten)]
+    pub stats: ListStats,
+    pub feed_error: Option<FeedError>,
+    pub feed_warnings: ParseWarnings,
+    /// the names of the subscription's tags
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeedError {
+    /// when the feed started failing
+    pub since: i64,
+    pub kind: FeedErrorKind,
+    pub message: Option<String>,
+    /// fetches that have failed in a row
+    pub failures: i32,
+    /// whether it failed so often it was given up on and the subscription
+    /// paused
+    pub broken: bool,
+}
+
+impl FeedError {
+    /// None if the feed's last check succeeded
+    pub fn for_feed(feed: &Feed) -> Option<FeedError> {
+        feed.failing_since().map(|since| FeedError {
+            since,
+            kind: feed.error_kind,
+            message: feed.error_message.clone(),
+            failures: feed.consecutive_failures,
+            broken: feed.broken_at > 0,
+        })
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct SubscriptionResponse {
     pub subscription: Subscription,
     pub feed: Feed,
+    /// the most recent attempt to send the subscription's email, so users
+    /// can see the relay accepted it
+    pub last_delivery: Option<Delivery>,
+}
+
+/// Most deliveries returned when listing a subscription's ledger
+pub const MAX_DELIVERIES: i64 = 50;
+
+/// Most tags one subscription may have
+pub const MAX_SUBSCRIPTION_TAGS: usize = 20;
+
+/// Most feeds that can be imported from one OPML file
+pub const MAX_IMPORT_FEEDS: usize = 1000;
+
+/// Largest OPML file that can be imported
+pub const MAX_IMPORT_BYTES: usize = 2 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    /// for every imported subscription, daily if not given
+    pub frequency: Option<Frequency>,
+    /// with `weekly`, the day to send on instead of Monday
+    pub weekday: Option<Weekday>,
+    /// instead of `frequency`, send every so many hours
+    pub every_hours: Option<u32>,
+}
+
+impl ImportQuery {
+    /// A query string can't hold the frequencies with values, so they're
+    /// put together from the other fields
+    pub fn frequency(&self) -> Frequency {
+        match (&self.frequency, self.weekday, self.every_hours) {
+            (_, _, Some(hours)) => Frequency::EveryHours(hours),
+            (Some(Frequency::Weekly), Some(day), None) => Frequency::WeeklyOn(day),
+            (Some(frequency), _, None) => frequency.clone(),
+            (None, _, None) => Frequency::Daily,
+        }
+    }
+}
+
+impl Validate for ImportQuery {
+    fn check(&self, errors: &mut ValidationErrors) {
+        errors.frequency("every_hours", &self.frequency());
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionsState {
+    /// changes whenever anything shown in the subscription list does
+    pub hash: String,
+}
+
+/// Replaces a subscription's tags, creating any the user doesn't have
+#[derive(Debug, Deserialize)]
+pub struct TagsUpdate {
+    pub tags: Vec<String>,
+}
+
+impl Validate for TagsUpdate {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.tags.len() > MAX_SUBSCRIPTION_TAGS {
+            errors.add(
+                "tags",
+                format!("At most {} tags are allowed", MAX_SUBSCRIPTION_TAGS),
+            );
+        }
+        for name in &self.tags {
+            tag::check_name(errors, "tags", name);
+        }
+    }
+}
+
+/// The user's subscriptions in the order they dragged them into. Any left
+/// out go after these.
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionOrder {
+    pub ids: Vec<SubscriptionId>,
+}
+
+impl Validate for SubscriptionOrder {
+    fn check(&self, errors: &mut ValidationErrors) {
+        let mut seen = HashSet::new();
+        for id in &self.ids {
+            if !seen.insert(id) {
+                errors.add(
+                    "ids",
+                    format!("Subscription {} is listed more than once", id),
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleDebug {
+    pub now: i64,
+    pub next_send_time: i64,
+    /// whether the next check will look at its items
+    pub due: bool,
+    /// None until the dispatcher has checked it since the last restart
+    pub last_check: Option<SendDecision>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SendNowResponse {
+    pub items_sent: usize,
+}
+
+/// Items previewed when the subscription has nothing new to send
+pub const PREVIEW_SAMPLE_ITEMS: i64 = 5;
+
+/// What the subscription's next delivery would look like with the changes
+#[derive(Debug, Serialize)]
+pub struct PreviewResponse {
+    pub delivery_method: DeliveryMethod,
+    pub item_count: usize,
+    /// true if there was nothing new, so the feed's latest items were used
+    pub sample: bool,
+    /// the digest, for email
+    pub email: Option<EmailPreview>,
+    /// the body of each message, the plain text part for email
+    pub messages: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionUpdate {
+    pub friendly_name: Option<String>,
+    pub frequency: Option<Frequency>,
+    pub max_items: Option<i32>,
+    pub is_active: Option<bool>,
+    /// overrides the feed's description, or clears the override if empty
+    pub description: Option<String>,
+    /// overrides the feed's homepage, or clears the override if empty
+    pub homepage: Option<String>,
+    /// overrides the user's send_email, or clears the override if empty
+    pub send_email: Option<String>,
+    /// prepended to email subjects, or cleared if empty
+    pub subject_prefix: Option<String>,
+    /// overrides the user's subject template, or clears the override if empty
+    pub subject_template: Option<String>,
+    pub show_stats: Option<bool>,
+    /// minimum score for aggregator items, or cleared if zero
+    pub min_score: Option<i32>,
+    /// minimum comment count for aggregator items, or cleared if zero
+    pub min_comments: Option<i32>,
+    /// HH:MM-HH:MM in the user's time zone, or cleared if empty
+    pub delivery_window: Option<String>,
+    /// replaces the include keywords, or clears them if empty
+    pub include_keywords: Option<Keywords>,
+    /// replaces the exclude keywords, or clears them if empty
+    pub exclude_keywords: Option<Keywords>,
+    pub delivery_method: Option<DeliveryMethod>,
+    /// days, or back to the instance default if zero
+    pub max_item_age_days: Option<i32>,
+}
+
+impl SubscriptionUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.friendly_name.is_none()
+            && self.frequency.is_none()
+            && self.max_items.is_none()
+            && self.is_active.is_none()
+            && self.description.is_none()
+            && self.homepage.is_none()
+            && self.send_email.is_none()
+            && self.subject_prefix.is_none()
+            && self.subject_template.is_none()
+            && self.show_stats.is_none()
+            && self.min_score.is_none()
+            && self.min_comments.is_none()
+            && self.delivery_window.is_none()
+            && self.include_keywords.is_none()
+            && self.exclude_keywords.is_none()
+            && self.delivery_method.is_none()
+            && self.max_item_age_days.is_none()
+    }
+}
+
+impl Validate for SubscriptionUpdate {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(frequency) = &self.frequency {
+            errors.frequency("frequency", frequency);
+        }
+        if let Some(homepage) = non_empty(&self.homepage) {
+            errors.url("homepage", homepage);
+        }
+        if let Some(send_email) = non_empty(&self.send_email) {
+            errors.email("send_email", send_email);
+        }
+        if let Some(template) = non_empty(&self.subject_template) {
+            errors.subject_template("subject_template", template);
+        }
+        errors.non_negative("max_items", self.max_items);
+        errors.non_negative("min_score", self.min_score);
+        errors.non_negative("min_comments", self.min_comments);
+        errors.max_item_age("max_item_age_days", self.max_item_age_days);
+        if let Some(window) = non_empty(&self.delivery_window) {
+            errors.delivery_window("delivery_window", window);
+        }
+        if let Some(keywords) = &self.include_keywords {
+            errors.keywords("include_keywords", keywords);
+        }
+        if let Some(keywords) = &self.exclude_keywords {
+            errors.keywords("exclude_keywords", keywords);
+        }
+    }
+}
+
+/// Empty strings in an update clear an override rather than setting it, so
+/// there's nothing to check
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|v| !v.is_empty())
+}
+
+impl From<SubscriptionUpdate> for PartialSubscription {
+    fn from(update: SubscriptionUpdate) -> Self {
+        let non_empty = |s: String| if s.is_empty() { None } else { Some(s) };
+        let non_zero = |n: i32| if n == 0 { None } else { Some(n) };
+        PartialSubscription {
+            friendly_name: update.friendly_name,
+            frequency: update.frequency,
+            max_items: update.max_items,
+            is_active: update.is_active,
+            description: update.description.map(non_empty),
+            homepage: update.homepage.map(non_empty),
+            send_email: update.send_email.map(non_empty),
+            subject_prefix: update.subject_prefix.map(non_empty),
+            subject_template: update.subject_template.map(non_empty),
+            show_stats: update.show_stats,
+            min_score: update.min_score.map(non_zero),
+            min_comments: update.min_comments.map(non_zero),
+            // stored as parsed, so it reads the same everywhere
+            delivery_window: update
+                .delivery_window
+                .map(|window| DeliveryWindow::parse(&window).map(|window| window.to_string())),
+            include_keywords: update.include_keywords.map(|keywords| keywords.trimmed()),
+            exclude_keywords: update.exclude_keywords.map(|keywords| keywords.trimmed()),
+            delivery_method: update.delivery_method,
+            max_item_age_days: update.max_item_age_days.map(non_zero),
+            ..Default::default()
+        }
+    }
 }
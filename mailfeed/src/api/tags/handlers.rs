@@ -0,0 +1,140 @@
+use actix_web::{delete, get, patch, web, HttpResponse, Responder, ResponseError};
+
+use super::types::{RqTagId, TagSummary};
+use crate::{
+    api::users::RqUserId,
+    claims::Claims,
+    models::{
+        ids::{TagId, UserId},
+        tag::{PartialTag, Tag},
+    },
+    security::validation::Validate,
+    RqDbPool,
+};
+
+#[get("")]
+pub async fn get_tags(pool: RqDbPool, path: RqUserId, claims: Claims) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let tags = Tag::get_for_user(&mut conn, user_id)
+        .and_then(|tags| Ok((tags, Tag::by_subscription(&mut conn, user_id)?)));
+    match tags {
+        Ok((tags, by_subscription)) => {
+            let tags: Vec<TagSummary> = tags
+                .into_iter()
+                .map(|tag| TagSummary {
+                    subscription_count: by_subscription
+                        .values()
+                        .filter(|sub_tags| sub_tags.iter().any(|sub_tag| sub_tag.id == tag.id))
+                        .count(),
+                    tag,
+                })
+                .collect();
+            HttpResponse::Ok().json(tags)
+        }
+        Err(e) => {
+            log::error!("Error getting tags: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting tags")
+        }
+    }
+}
+
+/// Rename a tag, or choose whether its subscriptions are sent as one
+/// combined digest
+#[patch("/{tag_id}")]
+pub async fn update_tag(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    tag_path: RqTagId,
+    update: web::Json<PartialTag>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    let tag_id = match tag_path.tag_id.parse::<TagId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid tag ID"),
+    };
+
+    if let Err(errors) = update.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Tag::update(&mut conn, user_id, tag_id, &update) {
+        Ok(tag) => HttpResponse::Ok().json(tag),
+        Err(diesel::result::Error::NotFound) => HttpResponse::NotFound().body("Tag not found"),
+        Err(diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            _,
+        )) => HttpResponse::Conflict().body("A tag with that name already exists"),
+        Err(e) => {
+            log::error!("Error updating tag: {:?}", e);
+            HttpResponse::InternalServerError().body("Error updating tag")
+        }
+    }
+}
+
+/// Delete a tag, untagging its subscriptions
+#[delete("/{tag_id}")]
+pub async fn delete_tag(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    tag_path: RqTagId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    let tag_id = match tag_path.tag_id.parse::<TagId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid tag ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Tag::delete(&mut conn, user_id, tag_id) {
+        Ok(0) => HttpResponse::NotFound().body("Tag not found"),
+        Ok(_) => HttpResponse::Ok().body("Tag deleted"),
+        Err(e) => {
+            log::error!("Error deleting tag: {:?}", e);
+            HttpResponse::InternalServerError().body("Error deleting tag")
+        }
+    }
+}
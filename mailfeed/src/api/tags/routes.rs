@@ -0,0 +1,9 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/users/{user_id}/tags")
+        .service(handlers::get_tags)
+        .service(handlers::update_tag)
+        .service(handlers::delete_tag)
+}
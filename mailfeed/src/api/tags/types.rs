@@ -0,0 +1,18 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+
+use crate::models::tag::Tag;
+
+#[derive(Debug, Deserialize)]
+pub struct TagPath {
+    pub tag_id: String,
+}
+pub type RqTagId = web::Path<TagPath>;
+
+/// A tag as listed in the dashboard
+#[derive(Debug, Serialize)]
+pub struct TagSummary {
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub subscription_count: usize,
+}
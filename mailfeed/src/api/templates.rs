@@ -0,0 +1,6 @@
+mod handlers;
+mod routes;
+mod types;
+
+pub use self::routes::routes;
+pub(super) use self::types::MAX_TEMPLATES;
@@ -0,0 +1,178 @@
+use actix_web::{delete, get, patch, post, web, HttpResponse, Responder, ResponseError};
+use chrono::Utc;
+
+use super::types::{RqTemplateId, MAX_TEMPLATES};
+use crate::{
+    api::users::RqUserId,
+    claims::Claims,
+    models::{
+        ids::{TemplateId, UserId},
+        subscription_template::{
+            NewSubscriptionTemplate, PartialSubscriptionTemplate, SubscriptionTemplate,
+        },
+    },
+    security::validation::Validate,
+    RqDbPool,
+};
+
+#[get("")]
+pub async fn get_templates(pool: RqDbPool, path: RqUserId, claims: Claims) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match SubscriptionTemplate::get_for_user(&mut conn, user_id) {
+        Ok(templates) => HttpResponse::Ok().json(templates),
+        Err(e) => {
+            log::error!("Error getting subscription templates: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting subscription templates")
+        }
+    }
+}
+
+#[post("")]
+pub async fn create_template(
+    pool: RqDbPool,
+    path: RqUserId,
+    template: web::Json<NewSubscriptionTemplate>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    if let Err(errors) = template.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match SubscriptionTemplate::count_for_user(&mut conn, user_id) {
+        Ok(count) if count >= MAX_TEMPLATES => {
+            return HttpResponse::BadRequest().body(format!(
+                "At most {} subscription templates are allowed",
+                MAX_TEMPLATES
+            ))
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error counting subscription templates: {:?}", e);
+            return HttpResponse::InternalServerError()
+                .body("Error creating subscription template");
+        }
+    }
+
+    let new_template = NewSubscriptionTemplate {
+        user_id,
+        created_at: Utc::now().timestamp(),
+        ..template.into_inner()
+    };
+    match new_template.insert(&mut conn) {
+        Ok(template) => HttpResponse::Created().json(template),
+        Err(e) => {
+            log::error!("Error creating subscription template: {:?}", e);
+            HttpResponse::InternalServerError().body("Error creating subscription template")
+        }
+    }
+}
+
+#[patch("/{template_id}")]
+pub async fn update_template(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    template_path: RqTemplateId,
+    update: web::Json<PartialSubscriptionTemplate>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    let template_id = match template_path.template_id.parse::<TemplateId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid template ID"),
+    };
+
+    if let Err(errors) = update.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match SubscriptionTemplate::update(&mut conn, user_id, template_id, &update) {
+        Ok(template) => HttpResponse::Ok().json(template),
+        Err(diesel::result::Error::NotFound) => HttpResponse::NotFound().body("Template not found"),
+        Err(e) => {
+            log::error!("Error updating subscription template: {:?}", e);
+            HttpResponse::InternalServerError().body("Error updating subscription template")
+        }
+    }
+}
+
+#[delete("/{template_id}")]
+pub async fn delete_template(
+    pool: RqDbPool,
+    user_path: RqUserId,
+    template_path: RqTemplateId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match user_path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    let template_id = match template_path.template_id.parse::<TemplateId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid template ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match SubscriptionTemplate::delete(&mut conn, user_id, template_id) {
+        Ok(0) => HttpResponse::NotFound().body("Template not found"),
+        Ok(_) => HttpResponse::Ok().body("Template deleted"),
+        Err(e) => {
+            log::error!("Error deleting subscription template: {:?}", e);
+            HttpResponse::InternalServerError().body("Error deleting subscription template")
+        }
+    }
+}
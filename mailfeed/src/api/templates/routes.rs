@@ -0,0 +1,10 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/users/{user_id}/subscription-templates")
+        .service(handlers::get_templates)
+        .service(handlers::create_template)
+        .service(handlers::update_template)
+        .service(handlers::delete_template)
+}
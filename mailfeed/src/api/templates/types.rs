@@ -0,0 +1,11 @@
+use actix_web::web;
+use serde::Deserialize;
+
+/// Most subscription templates a user may have
+pub const MAX_TEMPLATES: i64 = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct TemplatePath {
+    pub template_id: String,
+}
+pub type RqTemplateId = web::Path<TemplatePath>;
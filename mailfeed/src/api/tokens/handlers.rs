@@ -0,0 +1,134 @@
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder, ResponseError};
+use chrono::Utc;
+
+use super::types::{CreatedToken, RqTokenId, MAX_ACCESS_TOKENS};
+use crate::{
+    claims::Claims,
+    models::{
+        audit_log::{AuditAction, NewAuditEntry},
+        ids::AccessTokenId,
+        personal_access_token::{NewPersonalAccessToken, PersonalAccessToken},
+    },
+    security::{client_ip::real_ip, validation::Validate},
+    RqDbPool,
+};
+
+/// The current user's access tokens, without their secrets
+#[get("")]
+pub async fn get_tokens(pool: RqDbPool, claims: Claims) -> impl Responder {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match PersonalAccessToken::get_for_user(&mut conn, claims.sub) {
+        Ok(tokens) => HttpResponse::Ok().json(tokens),
+        Err(e) => {
+            log::error!("Error getting access tokens: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting access tokens")
+        }
+    }
+}
+
+#[post("")]
+pub async fn create_token(
+    req: HttpRequest,
+    pool: RqDbPool,
+    token: web::Json<NewPersonalAccessToken>,
+    claims: Claims,
+) -> impl Responder {
+    let new_token = NewPersonalAccessToken {
+        user_id: claims.sub,
+        created_at: Utc::now().timestamp(),
+        ..token.into_inner()
+    };
+    if let Err(errors) = new_token.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match PersonalAccessToken::count_for_user(&mut conn, claims.sub) {
+        Ok(count) if count >= MAX_ACCESS_TOKENS => {
+            return HttpResponse::BadRequest().body(format!(
+                "At most {} access tokens are allowed",
+                MAX_ACCESS_TOKENS
+            ))
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error counting access tokens: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error creating access token");
+        }
+    }
+
+    match new_token.insert(&mut conn) {
+        Ok((token, secret)) => {
+            log::info!("Created access token {} for user {}", token.id, claims.sub);
+            NewAuditEntry::new(
+                AuditAction::AccessTokenCreated,
+                Some(claims.sub),
+                Some(claims.sub),
+            )
+            .details(format!("token {} ({})", token.id, token.name))
+            .ip(real_ip(&req))
+            .record(&mut conn);
+            HttpResponse::Ok().json(CreatedToken { token, secret })
+        }
+        Err(e) => {
+            log::error!("Error creating access token: {:?}", e);
+            HttpResponse::InternalServerError().body("Error creating access token")
+        }
+    }
+}
+
+/// Revoke one of the current user's tokens
+#[delete("/{token_id}")]
+pub async fn delete_token(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqTokenId,
+    claims: Claims,
+) -> impl Responder {
+    let token_id = match path.token_id.parse::<AccessTokenId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid token ID"),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match PersonalAccessToken::delete(&mut conn, claims.sub, token_id) {
+        Ok(0) => HttpResponse::NotFound().body("Access token not found"),
+        Ok(_) => {
+            log::info!("Revoked access token {} of user {}", token_id, claims.sub);
+            NewAuditEntry::new(
+                AuditAction::AccessTokenRevoked,
+                Some(claims.sub),
+                Some(claims.sub),
+            )
+            .details(format!("token {}", token_id))
+            .ip(real_ip(&req))
+            .record(&mut conn);
+            HttpResponse::Ok().body("Access token revoked")
+        }
+        Err(e) => {
+            log::error!("Error deleting access token: {:?}", e);
+            HttpResponse::InternalServerError().body("Error deleting access token")
+        }
+    }
+}
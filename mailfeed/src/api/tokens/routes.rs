@@ -0,0 +1,9 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/tokens")
+        .service(handlers::get_tokens)
+        .service(handlers::create_token)
+        .service(handlers::delete_token)
+}
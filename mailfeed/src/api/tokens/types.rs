@@ -0,0 +1,21 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+
+use crate::models::personal_access_token::PersonalAccessToken;
+
+/// Most access tokens a user may have
+pub const MAX_ACCESS_TOKENS: i64 = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct TokenPath {
+    pub token_id: String,
+}
+pub type RqTokenId = web::Path<TokenPath>;
+
+#[derive(Debug, Serialize)]
+pub struct CreatedToken {
+    #[serde(flatten)]
+    pub token: PersonalAccessToken,
+    /// the secret to send as `Authorization: Bearer`, only shown here
+    pub secret: String,
+}
@@ -0,0 +1,308 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+
+use super::types::{CodeRequest, DisableRequest, Enrollment, RecoveryCodes, TwoFactorStatus};
+use crate::{
+    api::admin::check_access_for,
+    api::users::RqUserId,
+    claims::Claims,
+    models::{
+        audit_log::{AuditAction, NewAuditEntry},
+        ids::UserId,
+        two_factor::TwoFactor,
+        user::{User, UserQuery},
+    },
+    security::{client_ip::real_ip, secret_box::SecretBox, totp},
+    RqDbPool,
+};
+
+/// Whether two-factor logins are on for the user. Admin or the given user.
+#[get("")]
+pub async fn get_status(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if user_id != claims.sub && !claims.role.is_admin() {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, user_id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let enabled = match TwoFactor::get(&mut conn, user_id) {
+        Ok(two_factor) => two_factor.is_some_and(|two_factor| two_factor.enabled),
+        Err(e) => {
+            log::error!("Error getting two-factor settings: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error getting two-factor settings");
+        }
+    };
+    match TwoFactor::recovery_codes_left(&mut conn, user_id) {
+        Ok(recovery_codes_left) => HttpResponse::Ok().json(TwoFactorStatus {
+            enabled,
+            recovery_codes_left,
+        }),
+        Err(e) => {
+            log::error!("Error counting recovery codes: {:?}", e);
+            HttpResponse::InternalServerError().body("Error getting two-factor settings")
+        }
+    }
+}
+
+/// Start enrolling with a new secret. Logins don't need codes until it's
+/// confirmed.
+#[post("/enroll")]
+pub async fn enroll(pool: RqDbPool, path: RqUserId, claims: Claims) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let secret_box = match SecretBox::global() {
+        Some(secret_box) => secret_box,
+        None => {
+            return HttpResponse::ServiceUnavailable()
+                .body("Two-factor authentication needs MF_SECRET_KEY to be set")
+        }
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match TwoFactor::get(&mut conn, user_id) {
+        Ok(Some(two_factor)) if two_factor.enabled => {
+            return HttpResponse::Conflict().body("Two-factor authentication is already enabled")
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error getting two-factor settings: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error enrolling");
+        }
+    }
+
+    let secret = totp::generate_secret();
+    let now = Utc::now().timestamp();
+    match TwoFactor::enroll(&mut conn, user_id, secret_box.encrypt(&secret), now) {
+        Ok(_) => HttpResponse::Ok().json(Enrollment {
+            secret: totp::base32(&secret),
+            provisioning_uri: totp::provisioning_uri(&secret, &claims.email),
+        }),
+        Err(e) => {
+            log::error!("Error enrolling in two-factor authentication: {:?}", e);
+            HttpResponse::InternalServerError().body("Error enrolling")
+        }
+    }
+}
+
+/// Turn on two-factor logins with a code from the authenticator app,
+/// returning recovery codes
+#[post("/confirm")]
+pub async fn confirm(
+    pool: RqDbPool,
+    path: RqUserId,
+    body: web::Json<CodeRequest>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let secret_box = match SecretBox::global() {
+        Some(secret_box) => secret_box,
+        None => {
+            return HttpResponse::ServiceUnavailable()
+                .body("Two-factor authentication needs MF_SECRET_KEY to be set")
+        }
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let two_factor = match TwoFactor::get(&mut conn, user_id) {
+        Ok(Some(two_factor)) if !two_factor.enabled => two_factor,
+        Ok(Some(_)) => {
+            return HttpResponse::Conflict().body("Two-factor authentication is already enabled")
+        }
+        Ok(None) => return HttpResponse::BadRequest().body("Not enrolling"),
+        Err(e) => {
+            log::error!("Error getting two-factor settings: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error confirming two-factor code");
+        }
+    };
+
+    let step = match two_factor.verify_code(secret_box, &body.code, Utc::now().timestamp()) {
+        Some(step) => step,
+        None => return HttpResponse::BadRequest().body("Invalid two-factor code"),
+    };
+
+    match two_factor.enable(&mut conn, step) {
+        Ok(recovery_codes) => {
+            log::info!("Enabled two-factor authentication for user {}", user_id);
+            HttpResponse::Ok().json(RecoveryCodes { recovery_codes })
+        }
+        Err(e) => {
+            log::error!("Error enabling two-factor authentication: {:?}", e);
+            HttpResponse::InternalServerError().body("Error confirming two-factor code")
+        }
+    }
+}
+
+/// Replace the recovery codes, given a current code
+#[post("/recovery-codes")]
+pub async fn regenerate_recovery_codes(
+    pool: RqDbPool,
+    path: RqUserId,
+    body: web::Json<CodeRequest>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if claims.sub != user_id {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let secret_box = match SecretBox::global() {
+        Some(secret_box) => secret_box,
+        None => {
+            return HttpResponse::ServiceUnavailable()
+                .body("Two-factor authentication needs MF_SECRET_KEY to be set")
+        }
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let two_factor = match TwoFactor::get(&mut conn, user_id) {
+        Ok(Some(two_factor)) if two_factor.enabled => two_factor,
+        Ok(_) => {
+            return HttpResponse::BadRequest().body("Two-factor authentication is not enabled")
+        }
+        Err(e) => {
+            log::error!("Error getting two-factor settings: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error generating recovery codes");
+        }
+    };
+
+    match two_factor.redeem(&mut conn, secret_box, &body.code, Utc::now().timestamp()) {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::BadRequest().body("Invalid two-factor code"),
+        Err(e) => {
+            log::error!("Error checking two-factor code: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error generating recovery codes");
+        }
+    }
+
+    match TwoFactor::regenerate_recovery_codes(&mut conn, user_id) {
+        Ok(recovery_codes) => HttpResponse::Ok().json(RecoveryCodes { recovery_codes }),
+        Err(e) => {
+            log::error!("Error generating recovery codes: {:?}", e);
+            HttpResponse::InternalServerError().body("Error generating recovery codes")
+        }
+    }
+}
+
+/// Turn off two-factor logins. Users confirm with their password; admins
+/// can turn it off for users who lost their device and recovery codes.
+#[post("/disable")]
+pub async fn disable(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    body: web::Json<DisableRequest>,
+    claims: Claims,
+) -> impl Responder {
+    let user_id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if user_id != claims.sub && !claims.role.is_admin() {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, user_id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if user_id == claims.sub {
+        let user = match User::get(&mut conn, UserQuery::Id(user_id)) {
+            Some(user) => user,
+            None => return HttpResponse::NotFound().body("User not found"),
+        };
+        let password = body.password.as_deref().unwrap_or_default();
+        if !User::check_password(&user, password).unwrap_or(false) {
+            return HttpResponse::BadRequest().body("Invalid password");
+        }
+    }
+
+    match TwoFactor::disable(&mut conn, user_id) {
+        Ok(0) => HttpResponse::NotFound().body("Two-factor authentication is not enabled"),
+        Ok(_) => {
+            log::info!(
+                "Disabled two-factor authentication for user {} (by user {})",
+                user_id,
+                claims.sub
+            );
+            NewAuditEntry::new(
+                AuditAction::TwoFactorDisabled,
+                Some(claims.sub),
+                Some(user_id),
+            )
+            .ip(real_ip(&req))
+            .record(&mut conn);
+            HttpResponse::Ok().body("Two-factor authentication disabled")
+        }
+        Err(e) => {
+            log::error!("Error disabling two-factor authentication: {:?}", e);
+            HttpResponse::InternalServerError().body("Error disabling two-factor authentication")
+        }
+    }
+}
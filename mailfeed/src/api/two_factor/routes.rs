@@ -0,0 +1,11 @@
+use super::handlers;
+use actix_web::{web, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/users/{user_id}/2fa")
+        .service(handlers::get_status)
+        .service(handlers::enroll)
+        .service(handlers::confirm)
+        .service(handlers::regenerate_recovery_codes)
+        .service(handlers::disable)
+}
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub recovery_codes_left: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Enrollment {
+    /// base32, for typing into an authenticator app
+    pub secret: String,
+    /// `otpauth://` URI to show as a QR code
+    pub provisioning_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CodeRequest {
+    /// from the authenticator app, or a recovery code where accepted
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    /// each works once in place of a code, and is only shown here
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisableRequest {
+    /// needed unless an admin is disabling it for someone else
+    #[serde(default)]
+    pub password: Option<String>,
+}
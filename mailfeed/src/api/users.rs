@@ -3,4 +3,4 @@ mod routes;
 mod types;
 
 pub use self::routes::routes;
-pub(super) use self::types::RqUserId;
+pub(super) use self::types::{RqUserId, UsageQuery};
@@ -1,12 +1,41 @@
-use super::types::{RqPartUser, RqUserId};
-use crate::models::user::{NewUser, User, UserQuery, UserTableError};
+use super::types::{DiscordStatus, RqPartUser, RqUserId, RqUserJobId, UsageQuery, UserListEntry};
+use crate::api::{
+    admin::{check_access, check_access_for},
+    etag::json_with_etag,
+};
+use crate::db::DbConnection;
+use crate::models::{
+    audit_log::{AuditAction, NewAuditEntry},
+    bookmark_settings::BookmarkSettings,
+    delivery_webhook::DeliveryWebhook,
+    digest_skips::DigestSkips,
+    discord_webhook::DiscordWebhook,
+    ids::UserId,
+    matrix_settings::MatrixSettings,
+    onboarding::{Onboarding, OnboardingStep},
+    push_settings::PushSettings,
+    retry_policy::{Channel, RetryPolicy},
+    starred_item::StarredItem,
+    trends::{TrendSettings, Trends},
+    usage::Usage,
+    user::{NewUser, User, UserQuery, UserTableError},
+};
+use crate::security::{redact, validation::Validate};
+use crate::tasks::email_sender::{
+    account_changes::{account_changes, notice_channel, send_account_changed},
+    diagnostics, onboarding,
+};
+use crate::tasks::jobs::Jobs;
+use crate::tasks::webhooks::{Event, Webhooks};
 use crate::RqDbPool;
-use actix_web::{delete, get, patch, post, web, HttpResponse, Responder};
+use actix_web::{
+    delete, get, patch, post, put, web, HttpRequest, HttpResponse, Responder, ResponseError,
+};
 
 use crate::claims::Claims;
 
 #[get("")]
-pub async fn get_all_users(pool: RqDbPool, claims: Claims) -> impl Responder {
+pub async fn get_all_users(req: HttpRequest, pool: RqDbPool, claims: Claims) -> impl Responder {
     let mut conn = match pool.get() {
         Ok(conn) => conn,
         Err(err) => {
@@ -15,25 +44,42 @@ pub async fn get_all_users(pool: RqDbPool, claims: Claims) -> impl Responder {
         }
     };
 
-    if &claims.role != "admin" {
+    if !claims.role.is_admin() {
         log::warn!("Unauthorized attempt to get all users by {}", claims.sub);
         return HttpResponse::Forbidden().body("Forbidden");
     }
+    if let Err(e) = check_access(&req) {
+        return e.error_response();
+    }
 
-    let users_result = User::get_all(&mut conn);
-
-    match users_result {
-        Ok(users) => HttpResponse::Ok().json(users),
+    match User::summaries(&mut conn) {
+        Ok(summaries) => {
+            let users: Vec<UserListEntry> =
+                summaries.into_iter().map(UserListEntry::from).collect();
+            json_with_etag(&req, &users, 0)
+        }
         Err(_) => HttpResponse::InternalServerError().body("Error getting users"),
     }
 }
 
 #[post("")]
 pub async fn create_user(
+    req: HttpRequest,
     pool: RqDbPool,
+    webhooks: web::Data<Webhooks>,
     new_user: web::Json<NewUser>,
     claims: Claims,
 ) -> impl Responder {
+    // only admins may, which User::create checks
+    if claims.role.is_admin() {
+        if let Err(e) = check_access(&req) {
+            return e.error_response();
+        }
+    }
+    if let Err(errors) = new_user.validate() {
+        return errors.error_response();
+    }
+
     let mut conn = match pool.get() {
         Ok(conn) => conn,
         Err(err) => {
@@ -41,25 +87,46 @@ pub async fn create_user(
             return HttpResponse::InternalServerError().body("Error connecting to database");
         }
     };
+    let actor = claims.sub;
     let db_result = User::create(&mut conn, &new_user, claims);
 
     match db_result {
         Ok(_) => {
-            log::info!("created new user: {:?}", new_user.email);
+            log::info!("created new user: {}", redact::email(&new_user.email));
             let user = User::get(&mut conn, UserQuery::Email(&new_user.email)).unwrap();
+            NewAuditEntry::new(AuditAction::UserCreated, Some(actor), Some(user.id))
+                .record(&mut conn);
+            webhooks.emit(Event::UserCreated {
+                user_id: user.id,
+                email: user.login_email.clone(),
+            });
+            if let Err(e) = Onboarding::start(&mut conn, user.id) {
+                log::error!("Error starting onboarding for user {}: {:?}", user.id, e);
+            }
+            // the account is usable without it, so don't fail the request
+            let retry_policy = RetryPolicy::load(&mut conn, Channel::Email);
+            if let Err(e) = onboarding::send_welcome(&user, &retry_policy).await {
+                log::error!("Error sending welcome email to user {}: {}", user.id, e);
+            }
             HttpResponse::Ok().json(user)
         }
         Err(UserTableError::EmailExists) => HttpResponse::BadRequest().body("Email exists"),
         Err(UserTableError::PasswordTooShort) => {
             HttpResponse::BadRequest().body("Password too short")
         }
+        Err(UserTableError::WeakPassword(reason)) => HttpResponse::BadRequest().body(reason),
         Err(_) => HttpResponse::InternalServerError().body("Error creating user"),
     }
 }
 
 #[get("/{user_id}")]
-pub async fn get_user(pool: RqDbPool, user_path: RqUserId, claims: Claims) -> impl Responder {
-    let id = user_path.user_id.parse::<i32>();
+pub async fn get_user(
+    req: HttpRequest,
+    pool: RqDbPool,
+    user_path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = user_path.user_id.parse::<UserId>();
 
     if id.is_err() {
         return HttpResponse::BadRequest().body("Invalid user ID");
@@ -78,16 +145,20 @@ pub async fn get_user(pool: RqDbPool, user_path: RqUserId, claims: Claims) -> im
         None => return HttpResponse::InternalServerError().body("Error getting user"),
     };
 
-    if &claims.role != "admin" && claims.sub != user.id {
+    if !claims.role.is_admin() && claims.sub != user.id {
         log::warn!("Unauthorized attempt to get user by {}", claims.sub);
         return HttpResponse::Forbidden().body("Forbidden");
     }
+    if let Err(e) = check_access_for(&req, &claims, user.id) {
+        return e.error_response();
+    }
 
     HttpResponse::Ok().json(user)
 }
 
 #[patch("/{user_id}")]
 pub async fn update_user(
+    req: HttpRequest,
     pool: RqDbPool,
     path: RqUserId,
     updates: RqPartUser,
@@ -97,25 +168,34 @@ pub async fn update_user(
     if updates.is_empty() {
         return HttpResponse::BadRequest().body("No fields to update");
     }
-    let id = match path.user_id.parse::<i32>() {
+    let id = match path.user_id.parse::<UserId>() {
         Ok(id) => id,
         Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
     };
 
-    if id != claims.sub && &claims.role != "admin" {
+    if id != claims.sub && !claims.role.is_admin() {
         log::warn!("Unauthorized attempt to update user by {}", claims.sub);
         return HttpResponse::Forbidden().body("Forbidden");
     }
 
     // if role is being changed, it should only be changed by an admin
-    if updates.role.is_some() && &claims.role != "admin" {
+    if updates.role.is_some() && !claims.role.is_admin() {
         log::warn!("Unauthorized attempt to change role by {}", claims.sub);
         return HttpResponse::Forbidden().body("Forbidden");
     }
-    if updates.is_active.is_some() && &claims.role != "admin" {
+    if updates.is_active.is_some() && !claims.role.is_admin() {
         log::warn!("Unauthorized attempt to change is_active by {}", claims.sub);
         return HttpResponse::Forbidden().body("Forbidden");
     }
+    // past the checks above, any of these is an admin at work
+    if id != claims.sub || updates.role.is_some() || updates.is_active.is_some() {
+        if let Err(e) = check_access(&req) {
+            return e.error_response();
+        }
+    }
+    if let Err(errors) = updates.validate() {
+        return errors.error_response();
+    }
 
     let mut conn = match pool.get() {
         Ok(conn) => conn,
@@ -125,21 +205,100 @@ pub async fn update_user(
         }
     };
 
+    // kept to tell what changed
+    let before = match User::get(&mut conn, UserQuery::Id(id)) {
+        Some(user) => user,
+        None => return HttpResponse::NotFound().body("User not found"),
+    };
+
     let updated_user = match User::update(&mut conn, id, &updates) {
         Ok(user) => user,
         Err(UserTableError::EmailExists) => return HttpResponse::BadRequest().body("Email exists"),
         Err(_) => return HttpResponse::InternalServerError().body("Error updating user"),
     };
 
+    audit_account_changes(&mut conn, claims.sub, &before, &updated_user);
+    let changes = account_changes(&before, &updated_user);
+    if !changes.is_empty() {
+        log::info!(
+            "User {} changed the account of user {}: {}",
+            claims.sub,
+            id,
+            changes.join("; ")
+        );
+        if id != claims.sub {
+            let push = PushSettings::load(&mut conn, id);
+            let retry_policy = RetryPolicy::load(&mut conn, notice_channel(&push));
+            let user = updated_user.clone();
+            tokio::spawn(async move {
+                if let Err(e) = send_account_changed(&user, &push, &changes, &retry_policy).await {
+                    log::error!("Error telling user {} about account changes: {}", id, e);
+                }
+            });
+        }
+    }
+
+    if updates.send_email.is_some() || updates.daily_send_time.is_some() {
+        if let Err(e) = Onboarding::complete(&mut conn, id, OnboardingStep::SetDelivery) {
+            log::warn!("Error updating onboarding for user {}: {:?}", id, e);
+        }
+    }
+
     HttpResponse::Ok().json(updated_user)
 }
 
+/// Note role, activation and email settings changes in the audit log
+fn audit_account_changes(conn: &mut DbConnection, actor: UserId, before: &User, after: &User) {
+    let audit = |action| NewAuditEntry::new(action, Some(actor), Some(after.id));
+    if before.role != after.role {
+        audit(AuditAction::RoleChanged)
+            .details(format!("{} -> {}", before.role, after.role))
+            .record(conn);
+    }
+    if before.is_active != after.is_active {
+        let action = if after.is_active {
+            AuditAction::UserReactivated
+        } else {
+            AuditAction::UserDeactivated
+        };
+        audit(action).record(conn);
+    }
+    let email_settings: Vec<&str> = [
+        ("login_email", before.login_email != after.login_email),
+        ("send_email", before.send_email != after.send_email),
+        ("from_name", before.from_name != after.from_name),
+        (
+            "subject_template",
+            before.subject_template != after.subject_template,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(field, changed)| changed.then_some(field))
+    .collect();
+    if !email_settings.is_empty() {
+        audit(AuditAction::EmailSettingsChanged)
+            .details(email_settings.join(", "))
+            .record(conn);
+    }
+}
+
 #[delete("/{user_id}")]
-pub async fn delete_user(pool: RqDbPool, user_path: RqUserId, claims: Claims) -> impl Responder {
-    let id = match user_path.user_id.parse::<i32>() {
+pub async fn delete_user(
+    req: HttpRequest,
+    pool: RqDbPool,
+    user_path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match user_path.user_id.parse::<UserId>() {
         Ok(id) => id,
         Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
     };
+    // only admins may delete someone else, which User::delete checks
+    if id != claims.sub && claims.role.is_admin() {
+        if let Err(e) = check_access(&req) {
+            return e.error_response();
+        }
+    }
 
     let mut conn = match pool.get() {
         Ok(conn) => conn,
@@ -149,11 +308,13 @@ pub async fn delete_user(pool: RqDbPool, user_path: RqUserId, claims: Claims) ->
         }
     };
 
+    let actor = claims.sub;
     let delete_result = User::delete(&mut conn, id, claims);
 
     match delete_result {
         Ok(_) => {
             log::info!("Deleted user with ID {}", id);
+            NewAuditEntry::new(AuditAction::UserDeleted, Some(actor), Some(id)).record(&mut conn);
             HttpResponse::Ok().body("User deleted")
         }
         Err(err) => {
@@ -165,3 +326,791 @@ pub async fn delete_user(pool: RqDbPool, user_path: RqUserId, claims: Claims) ->
         }
     }
 }
+
+#[get("/{user_id}/onboarding")]
+pub async fn get_onboarding(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get onboarding by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    // null for users who were never given a checklist
+    match Onboarding::get(&mut conn, id) {
+        Ok(onboarding) => HttpResponse::Ok().json(onboarding),
+        Err(e) => {
+            log::error!("Error getting onboarding for user {}: {:?}", id, e);
+            HttpResponse::InternalServerError().body("Error getting onboarding")
+        }
+    }
+}
+
+#[post("/{user_id}/onboarding/dismiss")]
+pub async fn dismiss_onboarding(pool: RqDbPool, path: RqUserId, claims: Claims) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if id != claims.sub {
+        log::warn!(
+            "Unauthorized attempt to dismiss onboarding by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Onboarding::dismiss(&mut conn, id) {
+        Ok(0) => HttpResponse::NotFound().body("No onboarding checklist"),
+        Ok(_) => HttpResponse::Ok().body("Onboarding dismissed"),
+        Err(e) => {
+            log::error!("Error dismissing onboarding for user {}: {:?}", id, e);
+            HttpResponse::InternalServerError().body("Error dismissing onboarding")
+        }
+    }
+}
+
+#[post("/{user_id}/send-test")]
+pub async fn send_test_email(pool: RqDbPool, path: RqUserId, claims: Claims) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if id != claims.sub {
+        log::warn!("Unauthorized attempt to send test email by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+    let user = match User::get(&mut conn, UserQuery::Id(id)) {
+        Some(user) => user,
+        None => return HttpResponse::NotFound().body("User not found"),
+    };
+
+    let retry_policy = RetryPolicy::load(&mut conn, Channel::Email);
+    if let Err(e) = onboarding::send_test(&user, &retry_policy).await {
+        log::error!("Error sending test email to user {}: {}", id, e);
+        return HttpResponse::InternalServerError().body("Error sending test email");
+    }
+    if let Err(e) = Onboarding::complete(&mut conn, id, OnboardingStep::SendTest) {
+        log::warn!("Error updating onboarding for user {}: {:?}", id, e);
+    }
+
+    HttpResponse::Ok().body("Test email sent")
+}
+
+#[get("/{user_id}/diagnostics")]
+pub async fn get_diagnostics(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get diagnostics by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+    let user = match User::get(&mut conn, UserQuery::Id(id)) {
+        Some(user) => user,
+        None => return HttpResponse::NotFound().body("User not found"),
+    };
+
+    HttpResponse::Ok().json(diagnostics::diagnose(&mut conn, &user))
+}
+
+/// Progress of a background job the user started, like an OPML import
+#[get("/{user_id}/jobs/{job_id}")]
+pub async fn get_job(
+    req: HttpRequest,
+    path: RqUserJobId,
+    jobs: web::Data<Jobs>,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get job by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+    let job_id = match path.job_id.parse::<u64>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid job ID"),
+    };
+
+    match jobs.get(job_id).filter(|job| job.user_id == id) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().body("Job not found"),
+    }
+}
+
+/// Where the user's starred items are pushed
+#[get("/{user_id}/bookmarks")]
+pub async fn get_bookmark_settings(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get bookmark settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(BookmarkSettings::load(&mut conn, id))
+}
+
+/// Saving also retries any starred items that failed to push, since the
+/// settings were likely why
+#[put("/{user_id}/bookmarks")]
+pub async fn set_bookmark_settings(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    settings: web::Json<BookmarkSettings>,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set bookmark settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    if let Err(errors) = settings.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if let Err(e) = settings.save(&mut conn, id) {
+        log::error!("Error saving bookmark settings: {}", e);
+        return HttpResponse::InternalServerError().body("Error saving bookmark settings");
+    }
+    if let Err(e) = StarredItem::retry_failed(&mut conn, id) {
+        log::warn!("Error retrying starred items for user {}: {:?}", id, e);
+    }
+    HttpResponse::Ok().json(BookmarkSettings::load(&mut conn, id))
+}
+
+/// Where the user's subscriptions delivered by webhook are sent. The secret
+/// isn't returned.
+#[get("/{user_id}/delivery-webhook")]
+pub async fn get_delivery_webhook(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get delivery webhook by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(DeliveryWebhook::load(&mut conn, id))
+}
+
+#[put("/{user_id}/delivery-webhook")]
+pub async fn set_delivery_webhook(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    webhook: web::Json<DeliveryWebhook>,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set delivery webhook by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    if let Err(errors) = webhook.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if let Err(e) = webhook.save(&mut conn, id) {
+        log::error!("Error saving delivery webhook: {}", e);
+        return HttpResponse::InternalServerError().body("Error saving delivery webhook");
+    }
+    HttpResponse::Ok().json(DeliveryWebhook::load(&mut conn, id))
+}
+
+/// Whether the user has set up a Discord webhook for subscriptions
+/// delivered by Discord
+#[get("/{user_id}/discord")]
+pub async fn get_discord_webhook(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get Discord webhook by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(DiscordStatus {
+        configured: DiscordWebhook::load(&mut conn, id).url().is_some(),
+    })
+}
+
+#[put("/{user_id}/discord")]
+pub async fn set_discord_webhook(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    webhook: web::Json<DiscordWebhook>,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set Discord webhook by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    if let Err(errors) = webhook.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if let Err(e) = webhook.save(&mut conn, id) {
+        log::error!("Error saving Discord webhook: {}", e);
+        return HttpResponse::InternalServerError().body("Error saving Discord webhook");
+    }
+    HttpResponse::Ok().json(DiscordStatus {
+        configured: webhook.url().is_some(),
+    })
+}
+
+/// The Matrix room subscriptions delivered by Matrix are posted to, without
+/// the access token
+#[get("/{user_id}/matrix")]
+pub async fn get_matrix_settings(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get Matrix settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(MatrixSettings::load(&mut conn, id))
+}
+
+#[put("/{user_id}/matrix")]
+pub async fn set_matrix_settings(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    settings: web::Json<MatrixSettings>,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set Matrix settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    if let Err(errors) = settings.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if let Err(e) = settings.save(&mut conn, id) {
+        log::error!("Error saving Matrix settings: {}", e);
+        return HttpResponse::InternalServerError().body("Error saving Matrix settings");
+    }
+    HttpResponse::Ok().json(MatrixSettings::load(&mut conn, id))
+}
+
+/// The ntfy topic or Gotify server subscriptions delivered by push are
+/// sent to, without the token
+#[get("/{user_id}/push")]
+pub async fn get_push_settings(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get push settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(PushSettings::load(&mut conn, id))
+}
+
+#[put("/{user_id}/push")]
+pub async fn set_push_settings(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    settings: web::Json<PushSettings>,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set push settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    if let Err(errors) = settings.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if let Err(e) = settings.save(&mut conn, id) {
+        log::error!("Error saving push settings: {}", e);
+        return HttpResponse::InternalServerError().body("Error saving push settings");
+    }
+    HttpResponse::Ok().json(PushSettings::load(&mut conn, id))
+}
+
+/// The most frequent keywords and sites across the past week's items from
+/// the user's feeds
+#[get("/{user_id}/trends")]
+pub async fn get_trends(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get trends by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    match Trends::for_user(&mut conn, id, chrono::Utc::now().timestamp()) {
+        Ok(trends) => HttpResponse::Ok().json(trends),
+        Err(e) => {
+            log::error!("Error getting trends for user {}: {:?}", id, e);
+            HttpResponse::InternalServerError().body("Error getting trends")
+        }
+    }
+}
+
+/// Items, digests and bytes the user's subscriptions generated, month by
+/// month
+#[get("/{user_id}/usage")]
+pub async fn get_usage(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    query: web::Query<UsageQuery>,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get usage by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    if let Err(errors) = query.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    match Usage::for_user(&mut conn, id, now, query.months()) {
+        Ok(usage) => HttpResponse::Ok().json(usage),
+        Err(e) => {
+            log::error!("Error getting usage for user {}: {:?}", id, e);
+            HttpResponse::InternalServerError().body("Error getting usage")
+        }
+    }
+}
+
+#[get("/{user_id}/trends/settings")]
+pub async fn get_trend_settings(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to get trend settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(TrendSettings::load(&mut conn, id))
+}
+
+#[put("/{user_id}/trends/settings")]
+pub async fn set_trend_settings(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    settings: web::Json<TrendSettings>,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!(
+            "Unauthorized attempt to set trend settings by {}",
+            claims.sub
+        );
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if let Err(e) = settings.save(&mut conn, id) {
+        log::error!("Error saving trend settings: {}", e);
+        return HttpResponse::InternalServerError().body("Error saving trend settings");
+    }
+    HttpResponse::Ok().json(TrendSettings::load(&mut conn, id))
+}
+
+/// Days the user doesn't get digests
+#[get("/{user_id}/digest-skips")]
+pub async fn get_digest_skips(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to get digest skips by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    HttpResponse::Ok().json(DigestSkips::load(&mut conn, id))
+}
+
+#[put("/{user_id}/digest-skips")]
+pub async fn set_digest_skips(
+    req: HttpRequest,
+    pool: RqDbPool,
+    path: RqUserId,
+    skips: web::Json<DigestSkips>,
+    claims: Claims,
+) -> impl Responder {
+    let id = match path.user_id.parse::<UserId>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid user ID"),
+    };
+    if id != claims.sub && !claims.role.is_admin() {
+        log::warn!("Unauthorized attempt to set digest skips by {}", claims.sub);
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+    if let Err(e) = check_access_for(&req, &claims, id) {
+        return e.error_response();
+    }
+
+    if let Err(errors) = skips.validate() {
+        return errors.error_response();
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::error!("Failed to get db connection from pool: {}", err);
+            return HttpResponse::InternalServerError().body("Error connecting to database");
+        }
+    };
+
+    if let Err(e) = skips.save(&mut conn, id) {
+        log::error!("Error saving digest skips: {}", e);
+        return HttpResponse::InternalServerError().body("Error saving digest skips");
+    }
+    HttpResponse::Ok().json(DigestSkips::load(&mut conn, id))
+}
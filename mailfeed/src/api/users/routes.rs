@@ -8,4 +8,25 @@ pub fn routes() -> Scope {
         .service(handlers::get_user)
         .service(handlers::update_user)
         .service(handlers::delete_user)
+        .service(handlers::get_onboarding)
+        .service(handlers::dismiss_onboarding)
+        .service(handlers::send_test_email)
+        .service(handlers::get_diagnostics)
+        .service(handlers::get_job)
+        .service(handlers::get_bookmark_settings)
+        .service(handlers::set_bookmark_settings)
+        .service(handlers::get_delivery_webhook)
+        .service(handlers::set_delivery_webhook)
+        .service(handlers::get_discord_webhook)
+        .service(handlers::set_discord_webhook)
+        .service(handlers::get_matrix_settings)
+        .service(handlers::set_matrix_settings)
+        .service(handlers::get_push_settings)
+        .service(handlers::set_push_settings)
+        .service(handlers::get_trends)
+        .service(handlers::get_usage)
+        .service(handlers::get_trend_settings)
+        .service(handlers::set_trend_settings)
+        .service(handlers::get_digest_skips)
+        .service(handlers::set_digest_skips)
 }
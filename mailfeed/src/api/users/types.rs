@@ -1,7 +1,14 @@
 use actix_web::web;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::models::user::PartialUser;
+use crate::{
+    models::{
+        retry_policy::Channel,
+        usage::{DEFAULT_USAGE_MONTHS, MAX_USAGE_MONTHS},
+        user::{PartialUser, UserSummary},
+    },
+    security::validation::{Validate, ValidationErrors},
+};
 
 #[derive(Debug, Deserialize)]
 pub struct UserPath {
@@ -9,4 +16,60 @@ pub struct UserPath {
 }
 
 pub type RqUserId = web::Path<UserPath>;
+
+#[derive(Debug, Deserialize)]
+pub struct UserJobPath {
+    pub user_id: String,
+    pub job_id: String,
+}
+
+pub type RqUserJobId = web::Path<UserJobPath>;
 pub type RqPartUser = web::Json<PartialUser>;
+
+/// The Discord webhook's URL holds its token, so only whether it's set is
+/// returned
+#[derive(Debug, Serialize)]
+pub struct DiscordStatus {
+    pub configured: bool,
+}
+
+/// A user in the admin user list
+#[derive(Debug, Serialize)]
+pub struct UserListEntry {
+    #[serde(flatten)]
+    pub summary: UserSummary,
+    /// channels other than email the user can deliver to
+    pub channels: Vec<Channel>,
+}
+
+impl From<UserSummary> for UserListEntry {
+    fn from(summary: UserSummary) -> Self {
+        UserListEntry {
+            channels: summary.channels(),
+            summary,
+        }
+    }
+}
+
+/// How many months of usage to report, counting this one
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub months: Option<u32>,
+}
+
+impl UsageQuery {
+    pub fn months(&self) -> u32 {
+        self.months.unwrap_or(DEFAULT_USAGE_MONTHS)
+    }
+}
+
+impl Validate for UsageQuery {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if !(1..=MAX_USAGE_MONTHS).contains(&self.months()) {
+            errors.add(
+                "months",
+                format!("Must be between 1 and {}", MAX_USAGE_MONTHS),
+            );
+        }
+    }
+}
@@ -1,7 +1,21 @@
 use std::future::{ready, Ready};
 
-use crate::{global::JWT_SECRET, types::ErrorMessage};
-use actix_web::{error::ResponseError, http::StatusCode, FromRequest, HttpRequest, HttpResponse};
+use crate::{
+    global::JWT_SECRET,
+    models::{
+        ids::{SessionId, UserId},
+        maintenance_mode::MaintenanceMode,
+        personal_access_token::PersonalAccessToken,
+        role::Roles,
+        session::Session,
+        user::{User, UserQuery},
+    },
+    types::ErrorMessage,
+    DbPool,
+};
+use actix_web::{
+    error::ResponseError, http::StatusCode, web, FromRequest, HttpRequest, HttpResponse,
+};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 use derive_more::Display;
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
@@ -13,15 +27,31 @@ enum ClientError {
     Decode(jsonwebtoken::errors::Error),
     #[display(fmt = "not_found")]
     NotFound(String),
+    #[display(fmt = "maintenance")]
+    Maintenance(String),
+    #[display(fmt = "password_change_required")]
+    PasswordChangeRequired,
 }
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Claims {
-    pub sub: i32,
-    pub role: String,
+    pub sub: UserId,
+    pub role: Roles,
     pub exp: usize,
     pub email: String,
+    /// the login session the token was issued for, which it only works
+    /// while; None for personal access tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<SessionId>,
+    /// set when an admin has reset the password; until it's changed the
+    /// token only works for changing it or logging out
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub must_change_password: bool,
 }
 
+/// Claims for the few routes that still work while the user must change
+/// their password, i.e. changing it and logging out
+pub struct PasswordChangeClaims(pub Claims);
+
 impl ResponseError for ClientError {
     fn error_response(&self) -> HttpResponse {
         match self {
@@ -38,11 +68,25 @@ impl ResponseError for ClientError {
                 error_description: Some(msg.to_string()),
                 message: "Bad credentials".to_string(),
             }),
+            Self::Maintenance(msg) => HttpResponse::ServiceUnavailable().json(ErrorMessage {
+                error: Some("maintenance".to_string()),
+                error_description: None,
+                message: msg.to_string(),
+            }),
+            Self::PasswordChangeRequired => HttpResponse::Forbidden().json(ErrorMessage {
+                error: Some("password_change_required".to_string()),
+                error_description: None,
+                message: "You must change your password before continuing".to_string(),
+            }),
         }
     }
 
     fn status_code(&self) -> StatusCode {
-        StatusCode::UNAUTHORIZED
+        match self {
+            Self::Maintenance(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::PasswordChangeRequired => StatusCode::FORBIDDEN,
+            _ => StatusCode::UNAUTHORIZED,
+        }
     }
 }
 
@@ -51,30 +95,116 @@ impl FromRequest for Claims {
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
-        let bearer_auth = match BearerAuth::extract(req).into_inner() {
-            Ok(auth) => auth,
-            Err(err) => return ready(Err(err.into())),
-        };
+        let claims = extract(req).and_then(|claims| {
+            if claims.must_change_password {
+                return Err(ClientError::PasswordChangeRequired.into());
+            }
+            Ok(claims)
+        });
+        ready(claims)
+    }
+}
 
-        let token = bearer_auth.token();
+impl FromRequest for PasswordChangeClaims {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
 
-        let mut validation = Validation::new(Algorithm::HS512);
-        validation.set_audience(&["mailfeed"]);
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        ready(extract(req).map(PasswordChangeClaims))
+    }
+}
 
-        let jwt_secret = JWT_SECRET
-            .get()
-            .ok_or_else(|| ClientError::NotFound("JWT_SECRET not found".to_string()));
+fn extract(req: &HttpRequest) -> Result<Claims, actix_web::Error> {
+    let bearer_auth = BearerAuth::extract(req).into_inner()?;
 
-        let key = match jwt_secret {
-            Ok(secret) => DecodingKey::from_secret(secret.as_bytes()),
-            Err(err) => return ready(Err(err.into())),
-        };
+    let token = bearer_auth.token();
+    if PersonalAccessToken::is_personal_access_token(token) {
+        let claims =
+            access_token_claims(req, token).and_then(|claims| check_maintenance_mode(req, claims));
+        return claims.map_err(Into::into);
+    }
 
-        let token = match decode::<Claims>(token, &key, &validation).map_err(ClientError::Decode) {
-            Ok(token) => token,
-            Err(err) => return ready(Err(err.into())),
-        };
+    let mut validation = Validation::new(Algorithm::HS512);
+    validation.set_audience(&["mailfeed"]);
 
-        ready(Ok(token.claims))
+    let secret = JWT_SECRET
+        .get()
+        .ok_or_else(|| ClientError::NotFound("JWT_SECRET not found".to_string()))?;
+    let key = DecodingKey::from_secret(secret.as_bytes());
+
+    let token = decode::<Claims>(token, &key, &validation).map_err(ClientError::Decode)?;
+
+    let claims =
+        check_session(req, token.claims).and_then(|claims| check_maintenance_mode(req, claims));
+    claims.map_err(Into::into)
+}
+
+/// Tokens from logging in only work while their session does, so logging a
+/// device out takes effect right away rather than when its token expires
+fn check_session(req: &HttpRequest, claims: Claims) -> Result<Claims, ClientError> {
+    let ended = || ClientError::NotFound("Session has ended".to_string());
+    let session_id = claims.sid.ok_or_else(ended)?;
+    let mut conn = req
+        .app_data::<web::Data<DbPool>>()
+        .and_then(|pool| pool.get().ok())
+        .ok_or_else(|| ClientError::NotFound("Database unavailable".to_string()))?;
+    let now = chrono::Utc::now().timestamp();
+    match Session::authenticate(&mut conn, claims.sub, session_id, now) {
+        Ok(Some(_)) => Ok(claims),
+        Ok(None) => Err(ended()),
+        Err(e) => {
+            log::error!("Error checking session: {:?}", e);
+            Err(ended())
+        }
+    }
+}
+
+/// Turn away everyone but admins while the instance is in maintenance mode
+fn check_maintenance_mode(req: &HttpRequest, claims: Claims) -> Result<Claims, ClientError> {
+    if claims.role.is_admin() {
+        return Ok(claims);
+    }
+    let mode = match req
+        .app_data::<web::Data<DbPool>>()
+        .and_then(|pool| pool.get().ok())
+    {
+        Some(mut conn) => MaintenanceMode::load(&mut conn),
+        None => return Ok(claims),
+    };
+    if mode.enabled {
+        return Err(ClientError::Maintenance(mode.message().to_string()));
     }
+    Ok(claims)
+}
+
+/// Claims for the user a personal access token belongs to, as if they'd
+/// logged in. The user is looked up on every request, so deactivating them
+/// or changing their role takes effect right away.
+fn access_token_claims(req: &HttpRequest, token: &str) -> Result<Claims, ClientError> {
+    let invalid = || ClientError::NotFound("Invalid or expired access token".to_string());
+    let mut conn = req
+        .app_data::<web::Data<DbPool>>()
+        .and_then(|pool| pool.get().ok())
+        .ok_or_else(|| ClientError::NotFound("Database unavailable".to_string()))?;
+    let now = chrono::Utc::now().timestamp();
+    let token = match PersonalAccessToken::authenticate(&mut conn, token, now) {
+        Ok(Some(token)) => token,
+        Ok(None) => return Err(invalid()),
+        Err(e) => {
+            log::error!("Error checking access token: {:?}", e);
+            return Err(invalid());
+        }
+    };
+    let user = match User::get(&mut conn, UserQuery::Id(token.user_id)) {
+        Some(user) if user.is_active => user,
+        _ => return Err(invalid()),
+    };
+    Ok(Claims {
+        sub: user.id,
+        role: user.role,
+        exp: token.expires_at.unwrap_or(i64::MAX) as usize,
+        email: user.login_email,
+        sid: None,
+        must_change_password: user.must_change_password,
+    })
 }
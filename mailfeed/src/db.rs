@@ -0,0 +1,217 @@
+use diesel::{
+    prelude::*,
+    r2d2::{self, R2D2Connection},
+};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+pub const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("src/migrations");
+pub const POSTGRES_MIGRATIONS: EmbeddedMigrations = embed_migrations!("src/migrations_postgres");
+
+/// A connection to whichever database `MF_DATABASE_URL` points at. Queries
+/// built with diesel's DSL run on either; raw SQL has to work on both, or
+/// check which this is.
+#[derive(diesel::MultiConnection)]
+pub enum DbConnection {
+    Sqlite(SqliteConnection),
+    Postgres(PgConnection),
+}
+
+/// Run `$body` with `$conn` as the backend's own connection, for queries
+/// only the backends themselves support, like `ON CONFLICT` or inserting
+/// several rows at once
+macro_rules! with_backend {
+    ($conn:ident, $body:expr) => {
+        match $conn {
+            $crate::db::DbConnection::Sqlite($conn) => $body,
+            $crate::db::DbConnection::Postgres($conn) => $body,
+        }
+    };
+}
+pub(crate) use with_backend;
+
+/// Store a type as text, the string to store given by `$to_text` with
+/// `$value` bound to it. Both backends need their own `ToSql`, which the
+/// shared backend hands values to.
+macro_rules! to_sql_as_text {
+    ($type:ty, |$value:ident| $to_text:expr) => {
+        impl diesel::serialize::ToSql<diesel::sql_types::Text, diesel::sqlite::Sqlite> for $type {
+            fn to_sql<'b>(
+                &'b self,
+                out: &mut diesel::serialize::Output<'b, '_, diesel::sqlite::Sqlite>,
+            ) -> diesel::serialize::Result {
+                let $value = self;
+                out.set_value($to_text);
+                Ok(diesel::serialize::IsNull::No)
+            }
+        }
+
+        impl diesel::serialize::ToSql<diesel::sql_types::Text, diesel::pg::Pg> for $type {
+            fn to_sql<'b>(
+                &'b self,
+                out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
+            ) -> diesel::serialize::Result {
+                use std::io::Write;
+                let $value = self;
+                let text = $to_text;
+                out.write_all(AsRef::<str>::as_ref(&text).as_bytes())?;
+                Ok(diesel::serialize::IsNull::No)
+            }
+        }
+
+        impl diesel::serialize::ToSql<diesel::sql_types::Text, $crate::db::MultiBackend> for $type {
+            fn to_sql<'b>(
+                &'b self,
+                out: &mut diesel::serialize::Output<'b, '_, $crate::db::MultiBackend>,
+            ) -> diesel::serialize::Result {
+                out.set_value((diesel::sql_types::Text, self));
+                Ok(diesel::serialize::IsNull::No)
+            }
+        }
+    };
+}
+pub(crate) use to_sql_as_text;
+
+/// `MF_DATABASE_URL`: a path to a SQLite database, optionally written as a
+/// `sqlite://` URL, or a `postgres://` URL
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatabaseUrl {
+    Sqlite(String),
+    Postgres(String),
+}
+
+impl DatabaseUrl {
+    pub fn parse(url: &str) -> Result<DatabaseUrl, String> {
+        let (scheme, rest) = match url.split_once("://") {
+            Some((scheme, rest)) => (scheme.to_ascii_lowercase(), rest),
+            None => return Ok(DatabaseUrl::Sqlite(url.to_string())),
+        };
+        match scheme.as_str() {
+            "sqlite" => Ok(DatabaseUrl::Sqlite(rest.to_string())),
+            "postgres" | "postgresql" => Ok(DatabaseUrl::Postgres(url.to_string())),
+            _ => Err(format!("Unknown database scheme '{}'", scheme)),
+        }
+    }
+
+    /// Where the database is, without a Postgres password
+    pub fn describe(&self) -> String {
+        match self {
+            DatabaseUrl::Sqlite(path) => path.clone(),
+            DatabaseUrl::Postgres(url) => match url::Url::parse(url) {
+                Ok(mut url) => {
+                    if url.password().is_some() {
+                        let _ = url.set_password(Some("***"));
+                    }
+                    url.to_string()
+                }
+                Err(_) => "postgres".to_string(),
+            },
+        }
+    }
+}
+
+/// Opens connections for the pool with the backend the URL's scheme names,
+/// rather than trying each in turn, so a Postgres server that's down isn't
+/// taken for a SQLite file
+#[derive(Debug)]
+pub struct ConnectionManager {
+    url: DatabaseUrl,
+    /// each connection runs in a transaction that's never committed
+    test_transaction: bool,
+}
+
+impl ConnectionManager {
+    pub fn new(url: DatabaseUrl) -> ConnectionManager {
+        ConnectionManager {
+            url,
+            test_transaction: false,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn for_tests(url: DatabaseUrl) -> ConnectionManager {
+        ConnectionManager {
+            url,
+            test_transaction: true,
+        }
+    }
+
+    pub fn establish(&self) -> ConnectionResult<DbConnection> {
+        let mut conn = match &self.url {
+            DatabaseUrl::Sqlite(path) => {
+                SqliteConnection::establish(path).map(DbConnection::Sqlite)?
+            }
+            DatabaseUrl::Postgres(url) => {
+                PgConnection::establish(url).map(DbConnection::Postgres)?
+            }
+        };
+        if self.test_transaction {
+            conn.begin_test_transaction()
+                .map_err(diesel::ConnectionError::CouldntSetupConfiguration)?;
+        }
+        Ok(conn)
+    }
+}
+
+impl r2d2::ManageConnection for ConnectionManager {
+    type Connection = DbConnection;
+    type Error = r2d2::Error;
+
+    fn connect(&self) -> Result<DbConnection, r2d2::Error> {
+        self.establish().map_err(r2d2::Error::ConnectionError)
+    }
+
+    fn is_valid(&self, conn: &mut DbConnection) -> Result<(), r2d2::Error> {
+        conn.ping().map_err(r2d2::Error::QueryError)
+    }
+
+    fn has_broken(&self, conn: &mut DbConnection) -> bool {
+        conn.is_broken()
+    }
+}
+
+/// Bring the database's schema up to date, with the backend's migrations
+pub fn run_migrations(conn: &mut DbConnection) -> diesel::migration::Result<()> {
+    match conn {
+        DbConnection::Sqlite(conn) => conn.run_pending_migrations(SQLITE_MIGRATIONS)?,
+        DbConnection::Postgres(conn) => conn.run_pending_migrations(POSTGRES_MIGRATIONS)?,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_database_url() {
+        let sqlite = |path: &str| Ok(DatabaseUrl::Sqlite(path.to_string()));
+        assert_eq!(
+            DatabaseUrl::parse("/data/mailfeed.db"),
+            sqlite("/data/mailfeed.db")
+        );
+        assert_eq!(
+            DatabaseUrl::parse("sqlite:///data/mailfeed.db"),
+            sqlite("/data/mailfeed.db")
+        );
+        assert_eq!(
+            DatabaseUrl::parse("SQLITE://mailfeed.db"),
+            sqlite("mailfeed.db")
+        );
+        let url = "postgres://mailfeed:secret@db/mailfeed";
+        assert_eq!(
+            DatabaseUrl::parse(url),
+            Ok(DatabaseUrl::Postgres(url.to_string()))
+        );
+        assert!(matches!(
+            DatabaseUrl::parse("postgresql://db/mailfeed"),
+            Ok(DatabaseUrl::Postgres(_))
+        ));
+        assert!(DatabaseUrl::parse("mysql://db/mailfeed").is_err());
+    }
+
+    #[test]
+    fn test_describe_hides_password() {
+        let url = DatabaseUrl::parse("postgres://mailfeed:secret@db/mailfeed").unwrap();
+        assert_eq!(url.describe(), "postgres://mailfeed:***@db/mailfeed");
+    }
+}
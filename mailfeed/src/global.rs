@@ -1,16 +1,16 @@
-use diesel::SqliteConnection;
 use once_cell::sync::OnceCell;
 
+use crate::db::DbConnection;
 use crate::models::settings::{NewSetting, Setting};
 
 pub static JWT_SECRET: OnceCell<String> = OnceCell::new();
 
-pub fn init_jwt_secret(conn: &mut SqliteConnection) {
+pub fn init_jwt_secret(conn: &mut DbConnection) {
     let secret = get_jwt_secret(conn).unwrap();
     JWT_SECRET.set(secret).expect("Failed to set JWT secret");
 }
 
-fn get_jwt_secret(conn: &mut SqliteConnection) -> Option<String> {
+fn get_jwt_secret(conn: &mut DbConnection) -> Option<String> {
     use crate::schema::settings::dsl::*;
     use diesel::prelude::*;
 
@@ -57,7 +57,7 @@ mod tests {
         let mut conn = get_test_db_connection();
         let secret = get_jwt_secret(&mut conn);
         assert_ne!(secret, None);
-        assert!(secret.unwrap().len() > 0);
+        assert!(!secret.unwrap().is_empty());
     }
 
     #[test]
@@ -75,7 +75,7 @@ mod tests {
         assert_ne!(secret, None);
 
         let res = Setting::get(&mut conn, "jwt_secret", None);
-        assert!(!res.is_err());
+        assert!(res.is_ok());
         let setting = res.unwrap();
         assert_eq!(setting.user_id, None);
     }
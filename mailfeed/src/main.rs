@@ -2,31 +2,42 @@ extern crate diesel;
 
 mod api;
 mod claims;
+mod db;
 mod global;
 mod models;
+mod scheduler;
 mod schema;
+mod security;
 mod tasks;
 mod test_helpers;
 mod types;
 
 use crate::claims::Claims;
+use crate::db::{DatabaseUrl, DbConnection};
 use crate::global::init_jwt_secret;
+use crate::models::ids::UserId;
+use crate::models::instance_archive::InstanceArchive;
+use crate::models::role::Role;
 use crate::models::user::{NewUser, PartialUser, User};
+use crate::security::client_ip::{describe, real_ip};
+use crate::tasks::{
+    db_maintenance::types::{MaintenanceStatus, MaintenanceWindow},
+    dispatch::decisions::SendDecisions,
+    feed_monitor::refresh::RefreshJobs,
+    jobs::Jobs,
+    mqtt::Mqtt,
+    webhooks::Webhooks,
+};
 use actix_cors::Cors;
 use actix_files::Files;
-use actix_web::{middleware, web, App, HttpServer};
+use actix_web::{http::header, middleware, web, App, HttpServer};
 use chrono::Utc;
-use clap::Parser;
-use diesel::{
-    prelude::*,
-    r2d2::{self},
-};
-use diesel_migrations::MigrationHarness;
-use diesel_migrations::{embed_migrations, EmbeddedMigrations};
+use clap::{Parser, Subcommand};
+use diesel::r2d2;
 use dotenvy::dotenv;
 use std::env;
-
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("src/migrations");
+use std::fs;
+use std::path::{Path, PathBuf};
 
 /// CLI options
 #[derive(Parser, Debug)]
@@ -35,19 +46,36 @@ struct Args {
     /// Create a new user
     #[clap(long)]
     create_admin: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write users (with password hashes), feeds, subscriptions and settings
+    /// to a JSON archive, to move the instance to another host
+    ExportInstance {
+        /// file to write the archive to
+        path: PathBuf,
+    },
+    /// Load an archive from export-instance into this instance, which must
+    /// not have any users, feeds or subscriptions yet
+    ImportInstance {
+        /// archive to read
+        path: PathBuf,
+    },
 }
 
 fn main() -> std::io::Result<()> {
     dotenv().ok();
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    security::redact::init_logger();
 
     let config = load_config();
 
-    let db_pool = initialize_db_pool(config.db_path);
+    let db_pool = initialize_db_pool(config.db_url);
     log::info!("Running database migrations");
     let mut conn = db_pool.get().expect("Failed to get database connection");
-    conn.run_pending_migrations(MIGRATIONS)
-        .expect("Failed to run migrations");
+    db::run_migrations(&mut conn).expect("Failed to run migrations");
     init_jwt_secret(&mut conn);
 
     let args = Args::parse();
@@ -55,11 +83,22 @@ fn main() -> std::io::Result<()> {
         cli_create_user(&mut conn);
         return Ok(());
     }
+    match &args.command {
+        Some(Command::ExportInstance { path }) => {
+            cli_export_instance(&mut conn, path);
+            return Ok(());
+        }
+        Some(Command::ImportInstance { path }) => {
+            cli_import_instance(&mut conn, path);
+            return Ok(());
+        }
+        None => {}
+    }
 
     run_server(config.public_path, db_pool, config.port)
 }
 
-fn cli_create_user(db: &mut SqliteConnection) {
+fn cli_create_user(db: &mut DbConnection) {
     println!("\nEnter user login email:");
     let mut email = String::new();
     std::io::stdin()
@@ -86,10 +125,12 @@ fn cli_create_user(db: &mut SqliteConnection) {
     };
 
     let claims = Claims {
-        sub: 0,
+        sub: UserId(0),
         email: "system@mailfeed".to_string(),
         exp: (Utc::now().timestamp() + 10) as usize,
-        role: "admin".to_string(),
+        sid: None,
+        must_change_password: false,
+        role: Role::Admin.into(),
     };
 
     let user = match User::create(db, &new_user, claims) {
@@ -101,7 +142,7 @@ fn cli_create_user(db: &mut SqliteConnection) {
     };
 
     let updates = PartialUser {
-        role: Some("admin".to_string()),
+        role: Some(Role::Admin.into()),
         ..Default::default()
     };
 
@@ -117,9 +158,64 @@ fn cli_create_user(db: &mut SqliteConnection) {
     }
 }
 
+fn cli_export_instance(db: &mut DbConnection, path: &Path) {
+    let archive = match InstanceArchive::export(db, Utc::now().timestamp()) {
+        Ok(archive) => archive,
+        Err(e) => {
+            println!("Failed to export instance: {:?}", e);
+            return;
+        }
+    };
+    let json = match serde_json::to_string_pretty(&archive) {
+        Ok(json) => json,
+        Err(e) => {
+            println!("Failed to serialize archive: {}", e);
+            return;
+        }
+    };
+    match fs::write(path, json) {
+        Ok(()) => println!(
+            "Exported {} users, {} feeds, {} subscriptions and {} settings to {}",
+            archive.users.len(),
+            archive.feeds.len(),
+            archive.subscriptions.len(),
+            archive.settings.len(),
+            path.display()
+        ),
+        Err(e) => println!("Failed to write {}: {}", path.display(), e),
+    }
+}
+
+fn cli_import_instance(db: &mut DbConnection, path: &Path) {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) => {
+            println!("Failed to read {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let archive: InstanceArchive = match serde_json::from_str(&json) {
+        Ok(archive) => archive,
+        Err(e) => {
+            println!("Invalid archive: {}", e);
+            return;
+        }
+    };
+    match archive.import(db) {
+        Ok(()) => println!(
+            "Imported {} users, {} feeds, {} subscriptions and {} settings",
+            archive.users.len(),
+            archive.feeds.len(),
+            archive.subscriptions.len(),
+            archive.settings.len()
+        ),
+        Err(e) => println!("Failed to import instance: {}", e),
+    }
+}
+
 struct AppConfig {
     public_path: String,
-    db_path: String,
+    db_url: DatabaseUrl,
     port: u16,
 }
 
@@ -137,17 +233,18 @@ fn load_config() -> AppConfig {
             res
         }
     };
-    let db_path = match env::var("MF_DATABASE_URL") {
-        Ok(path) => {
-            log::info!("Using database path from MF_DATABASE_URL: {}", path);
-            path
+    let db_url = match env::var("MF_DATABASE_URL") {
+        Ok(url) => {
+            let url = DatabaseUrl::parse(&url).expect("Invalid MF_DATABASE_URL");
+            log::info!("Using database from MF_DATABASE_URL: {}", url.describe());
+            url
         }
         Err(_) => {
             let mut path = env::current_dir().expect("Failed to get current directory");
             path.push("mailfeed.db");
             let res = path.to_str().unwrap().to_string();
             log::info!("Using default database path: {}", res);
-            res
+            DatabaseUrl::Sqlite(res)
         }
     };
     let port = match env::var("MF_PORT") {
@@ -163,7 +260,7 @@ fn load_config() -> AppConfig {
 
     AppConfig {
         public_path,
-        db_path,
+        db_url,
         port,
     }
 }
@@ -173,24 +270,83 @@ async fn run_server(public_path: String, db_pool: DbPool, port: u16) -> std::io:
     log::info!("Serving static files from {}", public_path);
     log::info!("Starting server at http://127.0.0.1:{}", port);
 
-    tokio::spawn(tasks::feed_monitor::runner::start(db_pool.clone()));
+    let (webhooks, webhook_events) = Webhooks::new();
+    tokio::spawn(tasks::webhooks::runner::start(
+        db_pool.clone(),
+        webhook_events,
+    ));
+
+    let (mqtt, mqtt_events) = Mqtt::new();
+    tokio::spawn(tasks::mqtt::runner::start(db_pool.clone(), mqtt_events));
+
+    let jobs = Jobs::default();
+    let refresh_jobs = RefreshJobs::new(jobs.clone());
+    let decisions = SendDecisions::default();
     tokio::spawn(tasks::email_sender::runner::start(db_pool.clone()));
+    tokio::spawn(tasks::session_cleanup::runner::start(db_pool.clone()));
+    tokio::spawn(tasks::bookmark_sync::runner::start(db_pool.clone()));
+    let channels = tasks::dispatch::Channels::new(vec![
+        Box::new(tasks::email_sender::channel::EmailChannel::from_env()),
+        Box::<tasks::webhook_sender::WebhookChannel>::default(),
+        Box::<tasks::discord::DiscordChannel>::default(),
+        Box::<tasks::matrix_sender::MatrixChannel>::default(),
+        Box::<tasks::push_sender::PushChannel>::default(),
+    ]);
+    tokio::spawn(tasks::feed_monitor::runner::start(
+        db_pool.clone(),
+        refresh_jobs.clone(),
+        webhooks.clone(),
+        mqtt.clone(),
+        channels.clone(),
+    ));
+    tokio::spawn(tasks::dispatch::runner::start(
+        db_pool.clone(),
+        channels.clone(),
+        decisions.clone(),
+        webhooks.clone(),
+        mqtt.clone(),
+    ));
+    let maintenance = MaintenanceStatus::new(MaintenanceWindow::from_env());
+    tokio::spawn(tasks::db_maintenance::runner::start(
+        db_pool.clone(),
+        maintenance.clone(),
+        webhooks.clone(),
+    ));
 
     HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin()
             .allow_any_method()
             .allow_any_header()
+            // so the UI can send list ETags back in If-None-Match
+            .expose_headers([header::ETAG])
             .max_age(3600);
         App::new()
-            .wrap(middleware::Logger::default())
+            // as Logger::default(), but with the client's address per
+            // MF_TRUSTED_PROXIES rather than whatever headers it sent
+            .wrap(
+                middleware::Logger::new(
+                    "%{client}xi \"%r\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T",
+                )
+                .custom_request_replace("client", |req| describe(real_ip(req.request()))),
+            )
             .wrap(middleware::Compress::default())
             .wrap(middleware::NormalizePath::new(
                 middleware::TrailingSlash::Trim,
             ))
             .wrap(cors)
             .app_data(web::Data::new(db_pool.clone()))
+            .app_data(web::Data::new(jobs.clone()))
+            .app_data(web::Data::new(refresh_jobs.clone()))
+            .app_data(web::Data::new(maintenance.clone()))
+            .app_data(web::Data::new(decisions.clone()))
+            .app_data(web::Data::new(webhooks.clone()))
+            .app_data(web::Data::new(mqtt.clone()))
+            .app_data(web::Data::new(channels.clone()))
             .service(api::routes())
+            .service(api::share_page_routes())
+            .service(api::email_link_routes())
+            .service(api::well_known_routes())
             .service(Files::new("/", &public_path).index_file("index.html"))
     })
     .workers(1)
@@ -199,24 +355,25 @@ async fn run_server(public_path: String, db_pool: DbPool, port: u16) -> std::io:
     .await
 }
 
-type DbPool = r2d2::Pool<r2d2::ConnectionManager<SqliteConnection>>;
+type DbPool = r2d2::Pool<db::ConnectionManager>;
 type RqDbPool = web::Data<DbPool>;
-fn initialize_db_pool(db_path: String) -> DbPool {
+fn initialize_db_pool(db_url: DatabaseUrl) -> DbPool {
     dotenv().ok();
 
-    let manager = r2d2::ConnectionManager::<SqliteConnection>::new(db_path);
+    let manager = db::ConnectionManager::new(db_url);
     r2d2::Pool::builder()
         .build(manager)
-        .expect("Database URL should be a valid path to SQLite DB file")
+        .expect("Database URL should be a SQLite path or a reachable Postgres server")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use diesel::RunQueryDsl;
 
     #[test]
     fn test_initialize_db_pool() {
-        let pool = initialize_db_pool(":memory:".to_string());
+        let pool = initialize_db_pool(DatabaseUrl::Sqlite(":memory:".to_string()));
         let mut conn = pool.get().unwrap();
         let result = diesel::sql_query("SELECT 1").execute(&mut conn);
         assert_eq!(result, Ok(0));
@@ -0,0 +1 @@
+ALTER TABLE users DROP COLUMN item_truncate_length;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN item_truncate_length INTEGER NOT NULL DEFAULT 200;
\ No newline at end of file
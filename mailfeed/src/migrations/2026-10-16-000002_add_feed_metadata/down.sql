@@ -0,0 +1,4 @@
+ALTER TABLE subscriptions DROP COLUMN homepage;
+ALTER TABLE subscriptions DROP COLUMN description;
+ALTER TABLE feeds DROP COLUMN homepage;
+ALTER TABLE feeds DROP COLUMN description;
@@ -0,0 +1,4 @@
+ALTER TABLE feeds ADD COLUMN description TEXT;
+ALTER TABLE feeds ADD COLUMN homepage TEXT;
+ALTER TABLE subscriptions ADD COLUMN description TEXT;
+ALTER TABLE subscriptions ADD COLUMN homepage TEXT;
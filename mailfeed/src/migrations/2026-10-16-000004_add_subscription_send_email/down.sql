@@ -0,0 +1 @@
+ALTER TABLE subscriptions DROP COLUMN send_email;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE subscriptions ADD COLUMN send_email TEXT;
\ No newline at end of file
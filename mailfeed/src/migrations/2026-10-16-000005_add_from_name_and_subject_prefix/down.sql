@@ -0,0 +1,2 @@
+ALTER TABLE subscriptions DROP COLUMN subject_prefix;
+ALTER TABLE users DROP COLUMN from_name;
@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN from_name TEXT;
+ALTER TABLE subscriptions ADD COLUMN subject_prefix TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE subscriptions DROP COLUMN subject_template;
+ALTER TABLE users DROP COLUMN subject_template;
@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN subject_template TEXT;
+ALTER TABLE subscriptions ADD COLUMN subject_template TEXT;
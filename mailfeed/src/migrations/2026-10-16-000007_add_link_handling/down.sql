@@ -0,0 +1,2 @@
+ALTER TABLE feed_items DROP COLUMN comments_link;
+ALTER TABLE feeds DROP COLUMN link_mode;
@@ -0,0 +1,2 @@
+ALTER TABLE feeds ADD COLUMN link_mode INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE feed_items ADD COLUMN comments_link TEXT;
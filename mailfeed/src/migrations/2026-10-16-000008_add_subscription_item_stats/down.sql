@@ -0,0 +1,3 @@
+ALTER TABLE subscriptions DROP COLUMN min_comments;
+ALTER TABLE subscriptions DROP COLUMN min_score;
+ALTER TABLE subscriptions DROP COLUMN show_stats;
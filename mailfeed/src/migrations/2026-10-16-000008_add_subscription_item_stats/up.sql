@@ -0,0 +1,3 @@
+ALTER TABLE subscriptions ADD COLUMN show_stats BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE subscriptions ADD COLUMN min_score INTEGER;
+ALTER TABLE subscriptions ADD COLUMN min_comments INTEGER;
@@ -0,0 +1 @@
+ALTER TABLE feeds DROP COLUMN body_hash;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE feeds ADD COLUMN body_hash TEXT;
\ No newline at end of file
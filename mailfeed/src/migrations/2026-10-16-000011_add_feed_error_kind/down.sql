@@ -0,0 +1 @@
+ALTER TABLE feeds DROP COLUMN error_kind;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE feeds ADD COLUMN error_kind INTEGER NOT NULL DEFAULT 0;
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE deliveries;
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    subscription_id INTEGER NOT NULL,
+    sent_at INTEGER NOT NULL,
+    recipient TEXT NOT NULL,
+    item_count INTEGER NOT NULL,
+    accepted BOOLEAN NOT NULL,
+    relay_response TEXT NOT NULL,
+    FOREIGN KEY(subscription_id) REFERENCES subscriptions(id)
+);
+CREATE INDEX deliveries_subscription_id ON deliveries(subscription_id);
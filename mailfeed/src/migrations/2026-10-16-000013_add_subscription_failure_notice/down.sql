@@ -0,0 +1 @@
+ALTER TABLE subscriptions DROP COLUMN feed_failure_notified_at;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE subscriptions ADD COLUMN feed_failure_notified_at INTEGER NOT NULL DEFAULT 0;
\ No newline at end of file
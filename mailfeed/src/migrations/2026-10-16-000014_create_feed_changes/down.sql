@@ -0,0 +1 @@
+DROP TABLE feed_changes;
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE feed_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    feed_id INTEGER NOT NULL,
+    changed_at INTEGER NOT NULL,
+    kind INTEGER NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    significant BOOLEAN NOT NULL,
+    FOREIGN KEY(feed_id) REFERENCES feeds(id)
+);
+CREATE INDEX feed_changes_feed_id ON feed_changes(feed_id);
@@ -0,0 +1,9 @@
+-- users created before this have no row, and so no checklist
+CREATE TABLE onboarding (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    added_feed BOOLEAN NOT NULL DEFAULT 0,
+    set_delivery BOOLEAN NOT NULL DEFAULT 0,
+    sent_test BOOLEAN NOT NULL DEFAULT 0,
+    dismissed BOOLEAN NOT NULL DEFAULT 0,
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
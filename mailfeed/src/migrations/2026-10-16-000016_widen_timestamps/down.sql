@@ -0,0 +1,120 @@
+CREATE TABLE users_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    login_email TEXT NOT NULL,
+    send_email TEXT NOT NULL,
+    password TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT 1,
+    daily_send_time TEXT NOT NULL,
+    role TEXT NOT NULL DEFAULT 'user',
+    refresh_token TEXT,
+    item_truncate_length INTEGER NOT NULL DEFAULT 200,
+    must_change_password BOOLEAN NOT NULL DEFAULT 0,
+    from_name TEXT,
+    subject_template TEXT
+);
+INSERT INTO users_new SELECT * FROM users;
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
+
+CREATE TABLE feeds_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    url TEXT NOT NULL,
+    feed_type INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    last_checked INTEGER NOT NULL DEFAULT 0,
+    last_updated INTEGER NOT NULL,
+    error_time INTEGER NOT NULL DEFAULT 0,
+    error_message TEXT,
+    description TEXT,
+    homepage TEXT,
+    link_mode INTEGER NOT NULL DEFAULT 0,
+    poll_interval INTEGER NOT NULL DEFAULT 0,
+    body_hash TEXT,
+    error_kind INTEGER NOT NULL DEFAULT 0
+);
+INSERT INTO feeds_new SELECT * FROM feeds;
+DROP TABLE feeds;
+ALTER TABLE feeds_new RENAME TO feeds;
+
+CREATE TABLE feed_items_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    feed_id INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    link TEXT NOT NULL,
+    pub_date INTEGER NOT NULL,
+    description TEXT,
+    author TEXT,
+    comments_link TEXT,
+    FOREIGN KEY(feed_id) REFERENCES feeds(id)
+);
+INSERT INTO feed_items_new SELECT * FROM feed_items;
+DROP TABLE feed_items;
+ALTER TABLE feed_items_new RENAME TO feed_items;
+
+CREATE TABLE subscriptions_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    friendly_name TEXT NOT NULL,
+    frequency INTEGER NOT NULL,
+    last_sent_time INTEGER NOT NULL DEFAULT 0,
+    max_items INTEGER NOT NULL DEFAULT 0,
+    is_active BOOLEAN NOT NULL DEFAULT 1,
+    feed_id INTEGER NOT NULL,
+    description TEXT,
+    homepage TEXT,
+    send_email TEXT,
+    subject_prefix TEXT,
+    subject_template TEXT,
+    show_stats BOOLEAN NOT NULL DEFAULT 0,
+    min_score INTEGER,
+    min_comments INTEGER,
+    feed_failure_notified_at INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY(feed_id) REFERENCES feeds(id),
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+INSERT INTO subscriptions_new SELECT * FROM subscriptions;
+DROP TABLE subscriptions;
+ALTER TABLE subscriptions_new RENAME TO subscriptions;
+
+CREATE TABLE settings_new (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+INSERT INTO settings_new SELECT * FROM settings;
+DROP TABLE settings;
+ALTER TABLE settings_new RENAME TO settings;
+
+CREATE TABLE deliveries_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    subscription_id INTEGER NOT NULL,
+    sent_at INTEGER NOT NULL,
+    recipient TEXT NOT NULL,
+    item_count INTEGER NOT NULL,
+    accepted BOOLEAN NOT NULL,
+    relay_response TEXT NOT NULL,
+    FOREIGN KEY(subscription_id) REFERENCES subscriptions(id)
+);
+INSERT INTO deliveries_new SELECT * FROM deliveries;
+DROP TABLE deliveries;
+ALTER TABLE deliveries_new RENAME TO deliveries;
+CREATE INDEX deliveries_subscription_id ON deliveries(subscription_id);
+
+CREATE TABLE feed_changes_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    feed_id INTEGER NOT NULL,
+    changed_at INTEGER NOT NULL,
+    kind INTEGER NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    significant BOOLEAN NOT NULL,
+    FOREIGN KEY(feed_id) REFERENCES feeds(id)
+);
+INSERT INTO feed_changes_new SELECT * FROM feed_changes;
+DROP TABLE feed_changes;
+ALTER TABLE feed_changes_new RENAME TO feed_changes;
+CREATE INDEX feed_changes_feed_id ON feed_changes(feed_id);
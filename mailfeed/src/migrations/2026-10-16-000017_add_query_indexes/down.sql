@@ -0,0 +1,3 @@
+DROP INDEX subscriptions_feed_id;
+DROP INDEX subscriptions_user_id_feed_id;
+DROP INDEX feed_items_feed_id_pub_date;
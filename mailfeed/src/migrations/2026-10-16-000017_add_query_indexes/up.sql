@@ -0,0 +1,4 @@
+-- items are selected for delivery and deduplicated by feed and publish date
+CREATE INDEX feed_items_feed_id_pub_date ON feed_items(feed_id, pub_date);
+CREATE INDEX subscriptions_user_id_feed_id ON subscriptions(user_id, feed_id);
+CREATE INDEX subscriptions_feed_id ON subscriptions(feed_id);
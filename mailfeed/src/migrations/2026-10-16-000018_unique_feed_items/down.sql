@@ -0,0 +1 @@
+DROP INDEX feed_items_feed_id_link_pub_date;
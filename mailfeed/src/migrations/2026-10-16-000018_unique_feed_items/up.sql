@@ -0,0 +1,5 @@
+-- keep the first copy of any item that was stored more than once
+DELETE FROM feed_items WHERE id NOT IN (
+    SELECT MIN(id) FROM feed_items GROUP BY feed_id, link, pub_date
+);
+CREATE UNIQUE INDEX feed_items_feed_id_link_pub_date ON feed_items(feed_id, link, pub_date);
@@ -0,0 +1,2 @@
+ALTER TABLE feeds DROP COLUMN skipped_items;
+ALTER TABLE feeds DROP COLUMN new_items;
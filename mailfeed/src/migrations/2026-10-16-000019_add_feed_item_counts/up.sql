@@ -0,0 +1,3 @@
+-- items the last parsed fetch added, and those over the per-fetch cap it skipped
+ALTER TABLE feeds ADD COLUMN new_items INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE feeds ADD COLUMN skipped_items INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+CREATE TABLE webhooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    url TEXT NOT NULL,
+    -- key for the HMAC signature on each request
+    secret TEXT NOT NULL,
+    -- comma-separated event types the endpoint receives
+    events TEXT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT 1,
+    created_at BIGINT NOT NULL,
+    last_delivery_at BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT
+);
@@ -0,0 +1 @@
+DROP TABLE starred_items;
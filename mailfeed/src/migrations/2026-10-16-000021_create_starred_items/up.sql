@@ -0,0 +1,16 @@
+CREATE TABLE starred_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    feed_item_id INTEGER NOT NULL,
+    starred_at BIGINT NOT NULL,
+    -- set once pushed to the user's bookmark manager, or found already there
+    synced_at BIGINT NOT NULL DEFAULT 0,
+    -- id of the bookmark in the user's bookmark manager
+    bookmark_id TEXT,
+    -- why the last push failed, items with an error aren't pushed again
+    sync_error TEXT,
+    FOREIGN KEY(user_id) REFERENCES users(id),
+    FOREIGN KEY(feed_item_id) REFERENCES feed_items(id),
+    UNIQUE(user_id, feed_item_id)
+);
+CREATE INDEX starred_items_synced_at ON starred_items(synced_at);
@@ -0,0 +1,16 @@
+CREATE TABLE saved_searches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    -- see models::search_query for the syntax
+    query TEXT NOT NULL,
+    -- 0 email, 1 telegram
+    notify_by INTEGER NOT NULL DEFAULT 0,
+    telegram_chat_id TEXT,
+    is_active BOOLEAN NOT NULL DEFAULT 1,
+    created_at BIGINT NOT NULL,
+    last_matched_at BIGINT NOT NULL DEFAULT 0,
+    match_count INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+CREATE INDEX saved_searches_user_id ON saved_searches(user_id);
@@ -0,0 +1,3 @@
+-- validators from the last parsed fetch, sent back so unchanged feeds get a 304
+ALTER TABLE feeds ADD COLUMN etag TEXT;
+ALTER TABLE feeds ADD COLUMN last_modified TEXT;
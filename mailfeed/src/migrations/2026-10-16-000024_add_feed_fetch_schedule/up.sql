@@ -0,0 +1,2 @@
+-- optional cron expression replacing interval-based polling
+ALTER TABLE feeds ADD COLUMN fetch_schedule TEXT;
@@ -0,0 +1,2 @@
+-- optional HH:MM-HH:MM window, in the user's time zone, when emails may be sent
+ALTER TABLE subscriptions ADD COLUMN delivery_window TEXT;
@@ -0,0 +1,11 @@
+CREATE TABLE password_reset_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    -- SHA-256 of the token emailed to the user, so a copy of the database
+    -- can't be used to reset passwords
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX password_reset_tokens_user_id ON password_reset_tokens(user_id);
@@ -0,0 +1,14 @@
+CREATE TABLE personal_access_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    -- SHA-256 of the token, which is only shown when it's created
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL,
+    -- zero if never used
+    last_used_at BIGINT NOT NULL DEFAULT 0,
+    -- NULL if it never expires
+    expires_at BIGINT,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX personal_access_tokens_user_id ON personal_access_tokens(user_id);
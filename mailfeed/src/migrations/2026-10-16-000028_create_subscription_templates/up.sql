@@ -0,0 +1,18 @@
+-- saved defaults for new subscriptions, see models::subscription_template
+CREATE TABLE subscription_templates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    frequency INTEGER NOT NULL,
+    max_items INTEGER NOT NULL DEFAULT 0,
+    send_email TEXT,
+    subject_prefix TEXT,
+    subject_template TEXT,
+    show_stats BOOLEAN NOT NULL DEFAULT 0,
+    min_score INTEGER,
+    min_comments INTEGER,
+    delivery_window TEXT,
+    created_at BIGINT NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX subscription_templates_user_id ON subscription_templates(user_id);
@@ -0,0 +1,13 @@
+CREATE TABLE share_links (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    subscription_id INTEGER NOT NULL,
+    -- SHA-256 of the token in the public URL, which is only shown when it's created
+    token_hash TEXT NOT NULL UNIQUE,
+    -- how many of the newest items the page shows
+    item_count INTEGER NOT NULL,
+    created_at BIGINT NOT NULL,
+    -- zero if never viewed
+    last_viewed_at BIGINT NOT NULL DEFAULT 0,
+    FOREIGN KEY(subscription_id) REFERENCES subscriptions(id) ON DELETE CASCADE
+);
+CREATE INDEX share_links_subscription_id ON share_links(subscription_id);
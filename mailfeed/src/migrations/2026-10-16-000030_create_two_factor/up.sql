@@ -0,0 +1,20 @@
+CREATE TABLE two_factor (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    -- the TOTP secret, encrypted with MF_SECRET_KEY
+    secret TEXT NOT NULL,
+    -- false until a code from the authenticator app confirms enrollment
+    enabled BOOLEAN NOT NULL DEFAULT 0,
+    -- time step of the last code accepted, so codes can't be replayed
+    last_used_step BIGINT NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE recovery_codes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    -- SHA-256 of the code, which is only shown when it's generated
+    code_hash TEXT NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX recovery_codes_user_id ON recovery_codes(user_id);
@@ -0,0 +1,2 @@
+ALTER TABLE subscriptions DROP COLUMN exclude_keywords;
+ALTER TABLE subscriptions DROP COLUMN include_keywords;
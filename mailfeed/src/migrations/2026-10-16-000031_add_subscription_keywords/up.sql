@@ -0,0 +1,3 @@
+-- one keyword per line, empty if none
+ALTER TABLE subscriptions ADD COLUMN include_keywords TEXT NOT NULL DEFAULT '';
+ALTER TABLE subscriptions ADD COLUMN exclude_keywords TEXT NOT NULL DEFAULT '';
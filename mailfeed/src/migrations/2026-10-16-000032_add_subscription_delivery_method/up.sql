@@ -0,0 +1,2 @@
+-- 0 = email, 1 = the user's delivery webhook
+ALTER TABLE subscriptions ADD COLUMN delivery_method INTEGER NOT NULL DEFAULT 0;
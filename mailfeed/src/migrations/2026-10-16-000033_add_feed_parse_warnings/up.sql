@@ -0,0 +1 @@
+ALTER TABLE feeds ADD COLUMN parse_warnings TEXT NOT NULL DEFAULT '[]';
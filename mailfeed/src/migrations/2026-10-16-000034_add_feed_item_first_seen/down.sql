@@ -0,0 +1,2 @@
+DROP INDEX feed_items_feed_id_first_seen;
+ALTER TABLE feed_items DROP COLUMN first_seen;
@@ -0,0 +1,4 @@
+ALTER TABLE feed_items ADD COLUMN first_seen BIGINT NOT NULL DEFAULT 0;
+-- items stored before this was tracked were picked for delivery by publish date
+UPDATE feed_items SET first_seen = pub_date;
+CREATE INDEX feed_items_feed_id_first_seen ON feed_items(feed_id, first_seen);
@@ -0,0 +1 @@
+ALTER TABLE subscriptions DROP COLUMN max_item_age_days;
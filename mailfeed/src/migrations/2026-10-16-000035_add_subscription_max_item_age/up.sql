@@ -0,0 +1,2 @@
+-- NULL uses the instance default
+ALTER TABLE subscriptions ADD COLUMN max_item_age_days INTEGER;
@@ -0,0 +1,2 @@
+-- sealed FeedCredentials for private feeds, NULL for public ones
+ALTER TABLE feeds ADD COLUMN credentials TEXT;
@@ -0,0 +1,2 @@
+-- IANA name like Europe/Berlin, NULL or empty to use daily_send_time's offset
+ALTER TABLE users ADD COLUMN timezone TEXT;
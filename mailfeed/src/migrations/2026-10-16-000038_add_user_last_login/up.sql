@@ -0,0 +1,2 @@
+-- when the user last logged in with their password, NULL if never
+ALTER TABLE users ADD COLUMN last_login_at BIGINT;
@@ -0,0 +1,18 @@
+-- weekly and cron frequencies become daily
+ALTER TABLE subscriptions ADD COLUMN frequency_code INTEGER NOT NULL DEFAULT 2;
+UPDATE subscriptions SET frequency_code = CASE frequency
+    WHEN 'realtime' THEN 0
+    WHEN 'hourly' THEN 1
+    ELSE 2
+END;
+ALTER TABLE subscriptions DROP COLUMN frequency;
+ALTER TABLE subscriptions RENAME COLUMN frequency_code TO frequency;
+
+ALTER TABLE subscription_templates ADD COLUMN frequency_code INTEGER NOT NULL DEFAULT 2;
+UPDATE subscription_templates SET frequency_code = CASE frequency
+    WHEN 'realtime' THEN 0
+    WHEN 'hourly' THEN 1
+    ELSE 2
+END;
+ALTER TABLE subscription_templates DROP COLUMN frequency;
+ALTER TABLE subscription_templates RENAME COLUMN frequency_code TO frequency;
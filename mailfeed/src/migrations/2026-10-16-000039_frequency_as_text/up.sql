@@ -0,0 +1,19 @@
+-- Frequencies are stored by name, or as a cron expression, instead of as
+-- 0 (realtime), 1 (hourly) and 2 (daily)
+ALTER TABLE subscriptions ADD COLUMN frequency_text TEXT NOT NULL DEFAULT 'daily';
+UPDATE subscriptions SET frequency_text = CASE frequency
+    WHEN 0 THEN 'realtime'
+    WHEN 1 THEN 'hourly'
+    ELSE 'daily'
+END;
+ALTER TABLE subscriptions DROP COLUMN frequency;
+ALTER TABLE subscriptions RENAME COLUMN frequency_text TO frequency;
+
+ALTER TABLE subscription_templates ADD COLUMN frequency_text TEXT NOT NULL DEFAULT 'daily';
+UPDATE subscription_templates SET frequency_text = CASE frequency
+    WHEN 0 THEN 'realtime'
+    WHEN 1 THEN 'hourly'
+    ELSE 'daily'
+END;
+ALTER TABLE subscription_templates DROP COLUMN frequency;
+ALTER TABLE subscription_templates RENAME COLUMN frequency_text TO frequency;
@@ -0,0 +1,19 @@
+CREATE TABLE tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    -- send the tag's subscriptions as one email instead of one each
+    combined_digest BOOLEAN NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES users(id),
+    UNIQUE(user_id, name)
+);
+
+CREATE TABLE subscription_tags (
+    subscription_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+    PRIMARY KEY(subscription_id, tag_id),
+    FOREIGN KEY(subscription_id) REFERENCES subscriptions(id),
+    FOREIGN KEY(tag_id) REFERENCES tags(id)
+);
+CREATE INDEX subscription_tags_tag_id ON subscription_tags(tag_id);
@@ -0,0 +1,9 @@
+CREATE TABLE read_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    feed_item_id INTEGER NOT NULL,
+    read_at BIGINT NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES users(id),
+    FOREIGN KEY(feed_item_id) REFERENCES feed_items(id),
+    UNIQUE(user_id, feed_item_id)
+);
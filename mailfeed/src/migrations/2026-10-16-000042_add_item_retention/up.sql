@@ -0,0 +1,3 @@
+-- The newest publish date of the feed's pruned items, so fetching the feed
+-- again doesn't add them back as new
+ALTER TABLE feeds ADD COLUMN pruned_through BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+ALTER TABLE feeds DROP COLUMN broken_at;
+ALTER TABLE feeds DROP COLUMN consecutive_failures;
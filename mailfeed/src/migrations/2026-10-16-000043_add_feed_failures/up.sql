@@ -0,0 +1,4 @@
+-- Fetches that have failed in a row, and when the feed was given up on
+-- as broken after too many of them (0 if it wasn't)
+ALTER TABLE feeds ADD COLUMN consecutive_failures INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE feeds ADD COLUMN broken_at BIGINT NOT NULL DEFAULT 0;
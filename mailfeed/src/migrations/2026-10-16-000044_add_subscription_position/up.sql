@@ -0,0 +1,2 @@
+-- Where the user put the subscription in their list, 0 until they order it
+ALTER TABLE subscriptions ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- When an admin paused polling the feed, 0 if it's polled as usual
+ALTER TABLE feeds ADD COLUMN paused_at BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,13 @@
+CREATE TABLE invites (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    -- the login email the invitee's account is created with
+    email TEXT NOT NULL,
+    -- SHA-256 of the token in the invite link, so a copy of the database
+    -- can't be used to register
+    token_hash TEXT NOT NULL UNIQUE,
+    created_by INTEGER NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    FOREIGN KEY(created_by) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX invites_email ON invites(email);
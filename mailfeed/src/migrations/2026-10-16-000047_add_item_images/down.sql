@@ -0,0 +1,2 @@
+ALTER TABLE saved_searches DROP COLUMN telegram_photos;
+ALTER TABLE feed_items DROP COLUMN image_url;
@@ -0,0 +1,4 @@
+-- The item's lead image from an enclosure or Media RSS, if it had one
+ALTER TABLE feed_items ADD COLUMN image_url TEXT;
+-- Send matches with a lead image as Telegram photos rather than text
+ALTER TABLE saved_searches ADD COLUMN telegram_photos BOOLEAN NOT NULL DEFAULT 0;
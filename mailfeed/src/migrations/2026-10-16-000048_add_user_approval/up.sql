@@ -0,0 +1,3 @@
+-- Users who signed up on their own and are waiting for an admin to approve
+-- them. They're inactive until then.
+ALTER TABLE users ADD COLUMN pending_approval BOOLEAN NOT NULL DEFAULT 0;
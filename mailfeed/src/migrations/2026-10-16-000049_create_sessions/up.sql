@@ -0,0 +1,16 @@
+-- Each device a user is logged in on. Tokens carry their session's id and
+-- stop working once it's deleted.
+CREATE TABLE sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    created_at BIGINT NOT NULL,
+    last_used_at BIGINT NOT NULL,
+    -- when the refresh token expires
+    expires_at BIGINT NOT NULL,
+    user_agent TEXT,
+    ip TEXT,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX sessions_user_id ON sessions(user_id);
+-- Replaced by sessions, so everyone logs in again once
+ALTER TABLE users DROP COLUMN refresh_token;
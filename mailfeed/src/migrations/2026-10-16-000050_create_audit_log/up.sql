@@ -0,0 +1,17 @@
+-- Security-relevant events, for admins to look back over. Users aren't
+-- foreign keys, so entries are kept after the accounts they're about are
+-- deleted.
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    created_at BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    -- who did it, if anyone was logged in
+    actor_id INTEGER,
+    -- whose account it was done to
+    user_id INTEGER,
+    details TEXT,
+    ip TEXT
+);
+CREATE INDEX audit_log_created_at ON audit_log(created_at);
+CREATE INDEX audit_log_user_id ON audit_log(user_id);
+CREATE INDEX audit_log_actor_id ON audit_log(actor_id);
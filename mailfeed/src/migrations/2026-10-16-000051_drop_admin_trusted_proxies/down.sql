@@ -0,0 +1 @@
+-- The deleted ranges can't be brought back; set MF_TRUSTED_PROXIES instead
@@ -0,0 +1,3 @@
+-- Trusted proxies come from MF_TRUSTED_PROXIES alone now, so the admin
+-- access rules' own list would otherwise linger unused
+DELETE FROM settings WHERE key = 'admin_access.trusted_proxies' AND user_id IS NULL;
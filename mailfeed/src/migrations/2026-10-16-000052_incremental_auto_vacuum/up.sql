@@ -0,0 +1,6 @@
+-- Without this, PRAGMA incremental_vacuum in the nightly maintenance does
+-- nothing. Changing auto_vacuum on a database that already has tables only
+-- takes effect once it's rebuilt, which VACUUM does; it can't run in a
+-- transaction, hence metadata.toml.
+PRAGMA auto_vacuum = INCREMENTAL;
+VACUUM;
@@ -0,0 +1,23 @@
+DROP TABLE audit_log;
+DROP TABLE sessions;
+DROP TABLE invites;
+DROP TABLE read_items;
+DROP TABLE subscription_tags;
+DROP TABLE tags;
+DROP TABLE recovery_codes;
+DROP TABLE two_factor;
+DROP TABLE share_links;
+DROP TABLE subscription_templates;
+DROP TABLE personal_access_tokens;
+DROP TABLE password_reset_tokens;
+DROP TABLE saved_searches;
+DROP TABLE starred_items;
+DROP TABLE webhooks;
+DROP TABLE feed_changes;
+DROP TABLE deliveries;
+DROP TABLE settings;
+DROP TABLE subscriptions;
+DROP TABLE feed_items;
+DROP TABLE feeds;
+DROP TABLE users;
+DROP TABLE onboarding;
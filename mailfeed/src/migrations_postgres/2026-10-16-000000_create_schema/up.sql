@@ -0,0 +1,317 @@
+-- The SQLite migrations' schema as it stands, for a new Postgres database.
+-- Like on SQLite, where foreign keys are never switched on, the models
+-- clean up related rows themselves, so there are no FOREIGN KEY constraints.
+
+CREATE TABLE onboarding (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    added_feed BOOLEAN NOT NULL DEFAULT FALSE,
+    set_delivery BOOLEAN NOT NULL DEFAULT FALSE,
+    sent_test BOOLEAN NOT NULL DEFAULT FALSE,
+    dismissed BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE users (
+    id SERIAL PRIMARY KEY,
+    login_email TEXT NOT NULL,
+    send_email TEXT NOT NULL,
+    password TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    daily_send_time TEXT NOT NULL,
+    role TEXT NOT NULL DEFAULT 'user',
+    item_truncate_length INTEGER NOT NULL DEFAULT 200,
+    must_change_password BOOLEAN NOT NULL DEFAULT FALSE,
+    from_name TEXT,
+    subject_template TEXT,
+    timezone TEXT,
+    last_login_at BIGINT,
+    pending_approval BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE feeds (
+    id SERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    feed_type INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    last_checked BIGINT NOT NULL DEFAULT 0,
+    last_updated BIGINT NOT NULL,
+    error_time BIGINT NOT NULL DEFAULT 0,
+    error_message TEXT,
+    description TEXT,
+    homepage TEXT,
+    link_mode INTEGER NOT NULL DEFAULT 0,
+    poll_interval INTEGER NOT NULL DEFAULT 0,
+    body_hash TEXT,
+    error_kind INTEGER NOT NULL DEFAULT 0,
+    new_items INTEGER NOT NULL DEFAULT 0,
+    skipped_items INTEGER NOT NULL DEFAULT 0,
+    etag TEXT,
+    last_modified TEXT,
+    fetch_schedule TEXT,
+    parse_warnings TEXT NOT NULL DEFAULT '[]',
+    credentials TEXT,
+    pruned_through BIGINT NOT NULL DEFAULT 0,
+    consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    broken_at BIGINT NOT NULL DEFAULT 0,
+    paused_at BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE feed_items (
+    id SERIAL PRIMARY KEY,
+    feed_id INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    link TEXT NOT NULL,
+    pub_date BIGINT NOT NULL,
+    description TEXT,
+    author TEXT,
+    comments_link TEXT,
+    first_seen BIGINT NOT NULL DEFAULT 0,
+    image_url TEXT
+);
+
+CREATE TABLE subscriptions (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    friendly_name TEXT NOT NULL,
+    last_sent_time BIGINT NOT NULL DEFAULT 0,
+    max_items INTEGER NOT NULL DEFAULT 0,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    feed_id INTEGER NOT NULL,
+    description TEXT,
+    homepage TEXT,
+    send_email TEXT,
+    subject_prefix TEXT,
+    subject_template TEXT,
+    show_stats BOOLEAN NOT NULL DEFAULT FALSE,
+    min_score INTEGER,
+    min_comments INTEGER,
+    feed_failure_notified_at BIGINT NOT NULL DEFAULT 0,
+    delivery_window TEXT,
+    include_keywords TEXT NOT NULL DEFAULT '',
+    exclude_keywords TEXT NOT NULL DEFAULT '',
+    delivery_method INTEGER NOT NULL DEFAULT 0,
+    max_item_age_days INTEGER,
+    frequency TEXT NOT NULL DEFAULT 'daily',
+    position INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE settings (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+
+CREATE TABLE deliveries (
+    id SERIAL PRIMARY KEY,
+    subscription_id INTEGER NOT NULL,
+    sent_at BIGINT NOT NULL,
+    recipient TEXT NOT NULL,
+    item_count INTEGER NOT NULL,
+    accepted BOOLEAN NOT NULL,
+    relay_response TEXT NOT NULL
+);
+
+CREATE TABLE feed_changes (
+    id SERIAL PRIMARY KEY,
+    feed_id INTEGER NOT NULL,
+    changed_at BIGINT NOT NULL,
+    kind INTEGER NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    significant BOOLEAN NOT NULL
+);
+
+CREATE TABLE webhooks (
+    id SERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    -- key for the HMAC signature on each request
+    secret TEXT NOT NULL,
+    -- comma-separated event types the endpoint receives
+    events TEXT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at BIGINT NOT NULL,
+    last_delivery_at BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT
+);
+
+CREATE TABLE starred_items (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    feed_item_id INTEGER NOT NULL,
+    starred_at BIGINT NOT NULL,
+    -- set once pushed to the user's bookmark manager, or found already there
+    synced_at BIGINT NOT NULL DEFAULT 0,
+    -- id of the bookmark in the user's bookmark manager
+    bookmark_id TEXT,
+    -- why the last push failed, items with an error aren't pushed again
+    sync_error TEXT,
+    UNIQUE (user_id, feed_item_id)
+);
+
+CREATE TABLE saved_searches (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    -- see models::search_query for the syntax
+    query TEXT NOT NULL,
+    -- 0 email, 1 telegram
+    notify_by INTEGER NOT NULL DEFAULT 0,
+    telegram_chat_id TEXT,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at BIGINT NOT NULL,
+    last_matched_at BIGINT NOT NULL DEFAULT 0,
+    match_count INTEGER NOT NULL DEFAULT 0,
+    telegram_photos BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE password_reset_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    -- SHA-256 of the token emailed to the user, so a copy of the database
+    -- can't be used to reset passwords
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+
+CREATE TABLE personal_access_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    -- SHA-256 of the token, which is only shown when it's created
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL,
+    -- zero if never used
+    last_used_at BIGINT NOT NULL DEFAULT 0,
+    -- NULL if it never expires
+    expires_at BIGINT
+);
+
+CREATE TABLE subscription_templates (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    max_items INTEGER NOT NULL DEFAULT 0,
+    send_email TEXT,
+    subject_prefix TEXT,
+    subject_template TEXT,
+    show_stats BOOLEAN NOT NULL DEFAULT FALSE,
+    min_score INTEGER,
+    min_comments INTEGER,
+    delivery_window TEXT,
+    created_at BIGINT NOT NULL,
+    frequency TEXT NOT NULL DEFAULT 'daily'
+);
+
+CREATE TABLE share_links (
+    id SERIAL PRIMARY KEY,
+    subscription_id INTEGER NOT NULL,
+    -- SHA-256 of the token in the public URL, which is only shown when it's created
+    token_hash TEXT NOT NULL UNIQUE,
+    -- how many of the newest items the page shows
+    item_count INTEGER NOT NULL,
+    created_at BIGINT NOT NULL,
+    -- zero if never viewed
+    last_viewed_at BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE two_factor (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    -- the TOTP secret, encrypted with MF_SECRET_KEY
+    secret TEXT NOT NULL,
+    -- false until a code from the authenticator app confirms enrollment
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- time step of the last code accepted, so codes can't be replayed
+    last_used_step BIGINT NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE recovery_codes (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    -- SHA-256 of the code, which is only shown when it's generated
+    code_hash TEXT NOT NULL
+);
+
+CREATE TABLE tags (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    -- send the tag's subscriptions as one email instead of one each
+    combined_digest BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at BIGINT NOT NULL,
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE subscription_tags (
+    subscription_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+    PRIMARY KEY (subscription_id, tag_id)
+);
+
+CREATE TABLE read_items (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    feed_item_id INTEGER NOT NULL,
+    read_at BIGINT NOT NULL,
+    UNIQUE (user_id, feed_item_id)
+);
+
+CREATE TABLE invites (
+    id SERIAL PRIMARY KEY,
+    -- the login email the invitee's account is created with
+    email TEXT NOT NULL,
+    -- SHA-256 of the token in the invite link, so a copy of the database
+    -- can't be used to register
+    token_hash TEXT NOT NULL UNIQUE,
+    created_by INTEGER NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+
+CREATE TABLE sessions (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    created_at BIGINT NOT NULL,
+    last_used_at BIGINT NOT NULL,
+    -- when the refresh token expires
+    expires_at BIGINT NOT NULL,
+    user_agent TEXT,
+    ip TEXT
+);
+
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    created_at BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    -- who did it, if anyone was logged in
+    actor_id INTEGER,
+    -- whose account it was done to
+    user_id INTEGER,
+    details TEXT,
+    ip TEXT
+);
+
+CREATE INDEX deliveries_subscription_id ON deliveries(subscription_id);
+CREATE INDEX feed_changes_feed_id ON feed_changes(feed_id);
+CREATE INDEX feed_items_feed_id_pub_date ON feed_items(feed_id, pub_date);
+CREATE INDEX subscriptions_user_id_feed_id ON subscriptions(user_id, feed_id);
+CREATE INDEX subscriptions_feed_id ON subscriptions(feed_id);
+CREATE UNIQUE INDEX feed_items_feed_id_link_pub_date ON feed_items(feed_id, link, pub_date);
+CREATE INDEX starred_items_synced_at ON starred_items(synced_at);
+CREATE INDEX saved_searches_user_id ON saved_searches(user_id);
+CREATE INDEX password_reset_tokens_user_id ON password_reset_tokens(user_id);
+CREATE INDEX personal_access_tokens_user_id ON personal_access_tokens(user_id);
+CREATE INDEX subscription_templates_user_id ON subscription_templates(user_id);
+CREATE INDEX share_links_subscription_id ON share_links(subscription_id);
+CREATE INDEX recovery_codes_user_id ON recovery_codes(user_id);
+CREATE INDEX feed_items_feed_id_first_seen ON feed_items(feed_id, first_seen);
+CREATE INDEX subscription_tags_tag_id ON subscription_tags(tag_id);
+CREATE INDEX invites_email ON invites(email);
+CREATE INDEX sessions_user_id ON sessions(user_id);
+CREATE INDEX audit_log_created_at ON audit_log(created_at);
+CREATE INDEX audit_log_user_id ON audit_log(user_id);
+CREATE INDEX audit_log_actor_id ON audit_log(actor_id);
@@ -1,5 +1,51 @@
+pub mod admin_access;
+pub mod admin_contact;
+pub mod audit_log;
+pub mod body_logging;
+pub mod bookmark_settings;
+pub mod db_stats;
+pub mod delivery;
+pub mod delivery_webhook;
+pub mod delivery_window;
+pub mod digest_skips;
+pub mod discord_webhook;
 pub mod feed;
+pub mod feed_change;
+pub mod feed_credentials;
 pub mod feed_item;
+pub mod ids;
+pub mod ingest_limits;
+pub mod instance_archive;
+pub mod invite;
+pub mod keyword_filter;
+pub mod maintenance_mode;
+pub mod matrix_settings;
+pub mod max_item_age;
+pub mod mqtt_settings;
+pub mod onboarding;
+pub mod password_reset_token;
+pub mod personal_access_token;
+pub mod push_settings;
+pub mod query_timing;
+pub mod quotas;
+pub mod read_item;
+pub mod registration;
+pub mod retention;
+pub mod retry_policy;
+pub mod role;
+pub mod saved_search;
+pub mod search_query;
+pub mod session;
 pub mod settings;
+pub mod share_link;
+pub mod smtp_verification;
+pub mod starred_item;
 pub mod subscription;
+pub mod subscription_sort;
+pub mod subscription_template;
+pub mod tag;
+pub mod trends;
+pub mod two_factor;
+pub mod usage;
 pub mod user;
+pub mod webhook;
@@ -0,0 +1,164 @@
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use super::settings::{self, NewSetting, Setting};
+use crate::db::DbConnection;
+use crate::security::{
+    ip_network::IpNetwork,
+    validation::{Validate, ValidationErrors},
+};
+
+const ALLOW: &str = "admin_access.allow";
+const DENY: &str = "admin_access.deny";
+
+/// Most ranges in each list
+pub const MAX_NETWORKS: usize = 50;
+
+/// Which addresses may reach the admin API, stored as system settings.
+/// Ranges are in CIDR notation. With no ranges at all, anyone may. The
+/// address is the one `client_ip::real_ip` gives, so behind a proxy it
+/// depends on MF_TRUSTED_PROXIES like everywhere else.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AdminAccess {
+    /// if any, only these may
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// never these, even if allowed
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+fn networks(ranges: &[String]) -> Vec<IpNetwork> {
+    ranges
+        .iter()
+        .filter_map(|range| range.parse().ok())
+        .collect()
+}
+
+impl AdminAccess {
+    /// The current rules, none if they were never set
+    pub fn load(conn: &mut DbConnection) -> AdminAccess {
+        let mut get = |key| {
+            Setting::get(conn, key, None)
+                .ok()
+                .map(|setting| {
+                    setting
+                        .value
+                        .split(',')
+                        .filter(|range| !range.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        AdminAccess {
+            allow: get(ALLOW),
+            deny: get(DENY),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        for (key, ranges) in [(ALLOW, &self.allow), (DENY, &self.deny)] {
+            let ranges: Vec<&str> = ranges.iter().map(|range| range.trim()).collect();
+            let setting = NewSetting {
+                user_id: None,
+                key: key.to_string(),
+                value: ranges.join(","),
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the address may reach the admin API. When there are rules,
+    /// an unknown address may not.
+    pub fn permits(&self, addr: Option<IpAddr>) -> bool {
+        let (allow, deny) = (networks(&self.allow), networks(&self.deny));
+        if allow.is_empty() && deny.is_empty() {
+            return true;
+        }
+        let addr = match addr {
+            Some(addr) => addr,
+            None => return false,
+        };
+        if deny.iter().any(|range| range.contains(addr)) {
+            return false;
+        }
+        allow.is_empty() || allow.iter().any(|range| range.contains(addr))
+    }
+}
+
+fn check_ranges(errors: &mut ValidationErrors, field: &'static str, ranges: &[String]) {
+    if ranges.len() > MAX_NETWORKS {
+        errors.add(field, format!("At most {} ranges", MAX_NETWORKS));
+    }
+    for range in ranges {
+        if let Err(e) = range.parse::<IpNetwork>() {
+            errors.add(field, e.to_string());
+        }
+    }
+}
+
+impl Validate for AdminAccess {
+    fn check(&self, errors: &mut ValidationErrors) {
+        check_ranges(errors, "allow", &self.allow);
+        check_ranges(errors, "deny", &self.deny);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    fn ranges(ranges: &[&str]) -> Vec<String> {
+        ranges.iter().map(|range| range.to_string()).collect()
+    }
+
+    #[test]
+    fn test_permits() {
+        assert!(AdminAccess::default().permits(None));
+
+        let access = AdminAccess {
+            allow: ranges(&["10.0.0.0/8"]),
+            deny: ranges(&["10.0.0.66"]),
+        };
+        assert!(access.permits(ip("10.1.2.3")));
+        assert!(!access.permits(ip("10.0.0.66")));
+        assert!(!access.permits(ip("192.0.2.1")));
+        assert!(!access.permits(None));
+
+        let deny_only = AdminAccess {
+            deny: ranges(&["192.0.2.0/24"]),
+            ..Default::default()
+        };
+        assert!(deny_only.permits(ip("198.51.100.1")));
+        assert!(!deny_only.permits(ip("192.0.2.9")));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(AdminAccess::load(&mut conn), AdminAccess::default());
+
+        let access = AdminAccess {
+            allow: ranges(&[" 10.0.0.0/8", "2001:db8::/32"]),
+            deny: Vec::new(),
+        };
+        assert!(access.validate().is_ok());
+        access.save(&mut conn).unwrap();
+        let loaded = AdminAccess::load(&mut conn);
+        assert_eq!(loaded.allow, ranges(&["10.0.0.0/8", "2001:db8::/32"]));
+
+        let invalid = AdminAccess {
+            deny: ranges(&["10.0.0.0/40"]),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::settings::{self, NewSetting, Setting};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+const EMAIL: &str = "instance.contact_email";
+const URL: &str = "instance.contact_url";
+const POLICY_URL: &str = "instance.security_policy_url";
+
+/// How long a served security.txt says it's good for. It's generated on each
+/// request, so this only has to outlast caches.
+const SECURITY_TXT_LIFETIME_DAYS: i64 = 365;
+
+/// How to reach whoever runs the instance, stored as system settings and
+/// shown publicly so people can report problems, security issues included.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AdminContact {
+    #[serde(default)]
+    pub email: String,
+    /// a contact form or issue tracker, used alongside or instead of email
+    #[serde(default)]
+    pub url: String,
+    /// a page describing how security reports are handled
+    #[serde(default)]
+    pub security_policy_url: String,
+}
+
+impl AdminContact {
+    /// The contact details, empty if never set
+    pub fn load(conn: &mut DbConnection) -> AdminContact {
+        let mut get = |key| {
+            Setting::get(conn, key, None)
+                .ok()
+                .map(|setting| setting.value)
+                .unwrap_or_default()
+        };
+        AdminContact {
+            email: get(EMAIL),
+            url: get(URL),
+            security_policy_url: get(POLICY_URL),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        for (key, value) in [
+            (EMAIL, &self.email),
+            (URL, &self.url),
+            (POLICY_URL, &self.security_policy_url),
+        ] {
+            let setting = NewSetting {
+                user_id: None,
+                key: key.to_string(),
+                value: value.trim().to_string(),
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// Whether there's any way to reach the admin
+    pub fn is_set(&self) -> bool {
+        !self.email.is_empty() || !self.url.is_empty()
+    }
+
+    /// The contents of /.well-known/security.txt as described in RFC 9116,
+    /// or None if there's no contact to put in it
+    pub fn security_txt(&self, now: DateTime<Utc>) -> Option<String> {
+        if !self.is_set() {
+            return None;
+        }
+        let mut lines = Vec::new();
+        if !self.email.is_empty() {
+            lines.push(format!("Contact: mailto:{}", self.email));
+        }
+        if !self.url.is_empty() {
+            lines.push(format!("Contact: {}", self.url));
+        }
+        let expires = now + Duration::days(SECURITY_TXT_LIFETIME_DAYS);
+        lines.push(format!(
+            "Expires: {}",
+            expires.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ));
+        if !self.security_policy_url.is_empty() {
+            lines.push(format!("Policy: {}", self.security_policy_url));
+        }
+        lines.push("Preferred-Languages: en".to_string());
+        Some(lines.join("\n") + "\n")
+    }
+}
+
+impl Validate for AdminContact {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if !self.email.trim().is_empty() {
+            errors.email("email", self.email.trim());
+        }
+        if !self.url.trim().is_empty() {
+            errors.url("url", self.url.trim());
+        }
+        if !self.security_policy_url.trim().is_empty() {
+            errors.url("security_policy_url", self.security_policy_url.trim());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        let loaded = AdminContact::load(&mut conn);
+        assert_eq!(loaded, AdminContact::default());
+        assert!(!loaded.is_set());
+
+        let contact = AdminContact {
+            email: " admin@example.com ".to_string(),
+            url: String::new(),
+            security_policy_url: "https://example.com/security".to_string(),
+        };
+        contact.save(&mut conn).unwrap();
+        let loaded = AdminContact::load(&mut conn);
+        assert!(loaded.is_set());
+        assert_eq!(loaded.email, "admin@example.com");
+    }
+
+    #[test]
+    fn test_security_txt() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+        assert_eq!(AdminContact::default().security_txt(now), None);
+
+        let contact = AdminContact {
+            email: "admin@example.com".to_string(),
+            url: "https://example.com/report".to_string(),
+            security_policy_url: String::new(),
+        };
+        assert_eq!(
+            contact.security_txt(now).unwrap(),
+            "Contact: mailto:admin@example.com\n\
+             Contact: https://example.com/report\n\
+             Expires: 2027-10-16T12:00:00Z\n\
+             Preferred-Languages: en\n"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(AdminContact::default().validate().is_ok());
+        let contact = AdminContact {
+            email: "not an address".to_string(),
+            url: "example.com".to_string(),
+            security_policy_url: String::new(),
+        };
+        let errors = contact.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["email", "url"]);
+    }
+}
@@ -0,0 +1,350 @@
+use std::{fmt, net::IpAddr, str::FromStr};
+
+use chrono::Utc;
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    prelude::*,
+    sql_types::Text,
+    AsExpression, FromSqlRow,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::ids::{AuditEntryId, UserId};
+use crate::db::{to_sql_as_text, DbConnection, MultiBackend};
+use crate::schema::*;
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Unknown audit action '{0}'")]
+pub struct UnknownAuditAction(String);
+
+/// What happened. Stored and serialized in snake_case, like `login_failed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Login,
+    /// a wrong password or two-factor code, or an unknown email
+    LoginFailed,
+    PasswordChanged,
+    /// set from a reset email
+    PasswordReset,
+    /// an admin made the user pick a new password
+    PasswordResetForced,
+    RoleChanged,
+    UserDeactivated,
+    UserReactivated,
+    /// where and how the user's emails are sent
+    EmailSettingsChanged,
+    SubscriptionCreated,
+    SubscriptionUpdated,
+    SubscriptionDeleted,
+    UserCreated,
+    UserDeleted,
+    UserApproved,
+    UserRejected,
+    InviteCreated,
+    /// an instance-wide setting an admin changed, named in the details
+    SettingChanged,
+    TwoFactorDisabled,
+    /// an admin edited a feed, with what changed in the details
+    FeedUpdated,
+    AccessTokenCreated,
+    AccessTokenRevoked,
+    /// logged out of one device
+    SessionRevoked,
+    /// logged out everywhere
+    AllSessionsRevoked,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "login",
+            AuditAction::LoginFailed => "login_failed",
+            AuditAction::PasswordChanged => "password_changed",
+            AuditAction::PasswordReset => "password_reset",
+            AuditAction::PasswordResetForced => "password_reset_forced",
+            AuditAction::RoleChanged => "role_changed",
+            AuditAction::UserDeactivated => "user_deactivated",
+            AuditAction::UserReactivated => "user_reactivated",
+            AuditAction::EmailSettingsChanged => "email_settings_changed",
+            AuditAction::SubscriptionCreated => "subscription_created",
+            AuditAction::SubscriptionUpdated => "subscription_updated",
+            AuditAction::SubscriptionDeleted => "subscription_deleted",
+            AuditAction::UserCreated => "user_created",
+            AuditAction::UserDeleted => "user_deleted",
+            AuditAction::UserApproved => "user_approved",
+            AuditAction::UserRejected => "user_rejected",
+            AuditAction::InviteCreated => "invite_created",
+            AuditAction::SettingChanged => "setting_changed",
+            AuditAction::TwoFactorDisabled => "two_factor_disabled",
+            AuditAction::FeedUpdated => "feed_updated",
+            AuditAction::AccessTokenCreated => "access_token_created",
+            AuditAction::AccessTokenRevoked => "access_token_revoked",
+            AuditAction::SessionRevoked => "session_revoked",
+            AuditAction::AllSessionsRevoked => "all_sessions_revoked",
+        }
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = UnknownAuditAction;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "login" => Ok(AuditAction::Login),
+            "login_failed" => Ok(AuditAction::LoginFailed),
+            "password_changed" => Ok(AuditAction::PasswordChanged),
+            "password_reset" => Ok(AuditAction::PasswordReset),
+            "password_reset_forced" => Ok(AuditAction::PasswordResetForced),
+            "role_changed" => Ok(AuditAction::RoleChanged),
+            "user_deactivated" => Ok(AuditAction::UserDeactivated),
+            "user_reactivated" => Ok(AuditAction::UserReactivated),
+            "email_settings_changed" => Ok(AuditAction::EmailSettingsChanged),
+            "subscription_created" => Ok(AuditAction::SubscriptionCreated),
+            "subscription_updated" => Ok(AuditAction::SubscriptionUpdated),
+            "subscription_deleted" => Ok(AuditAction::SubscriptionDeleted),
+            "user_created" => Ok(AuditAction::UserCreated),
+            "user_deleted" => Ok(AuditAction::UserDeleted),
+            "user_approved" => Ok(AuditAction::UserApproved),
+            "user_rejected" => Ok(AuditAction::UserRejected),
+            "invite_created" => Ok(AuditAction::InviteCreated),
+            "setting_changed" => Ok(AuditAction::SettingChanged),
+            "two_factor_disabled" => Ok(AuditAction::TwoFactorDisabled),
+            "feed_updated" => Ok(AuditAction::FeedUpdated),
+            "access_token_created" => Ok(AuditAction::AccessTokenCreated),
+            "access_token_revoked" => Ok(AuditAction::AccessTokenRevoked),
+            "session_revoked" => Ok(AuditAction::SessionRevoked),
+            "all_sessions_revoked" => Ok(AuditAction::AllSessionsRevoked),
+            other => Err(UnknownAuditAction(other.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<DB> FromSql<Text, DB> for AuditAction
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+        Ok(String::from_sql(bytes)?.parse()?)
+    }
+}
+
+to_sql_as_text!(AuditAction, |action| action.as_str());
+
+/// A security-relevant event, kept so admins can see who did what and when
+#[derive(Debug, Clone, Serialize, Queryable, Identifiable, PartialEq)]
+#[diesel(table_name = audit_log)]
+pub struct AuditEntry {
+    pub id: AuditEntryId,
+    pub created_at: i64,
+    pub action: AuditAction,
+    /// who did it, None if no one was logged in, like for a failed login
+    pub actor_id: Option<UserId>,
+    /// whose account it was done to
+    pub user_id: Option<UserId>,
+    pub details: Option<String>,
+    /// where the request came from
+    pub ip: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditEntry {
+    pub created_at: i64,
+    pub action: AuditAction,
+    pub actor_id: Option<UserId>,
+    pub user_id: Option<UserId>,
+    pub details: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl NewAuditEntry {
+    /// An entry for now, without details or an address
+    pub fn new(action: AuditAction, actor_id: Option<UserId>, user_id: Option<UserId>) -> Self {
+        NewAuditEntry {
+            created_at: Utc::now().timestamp(),
+            action,
+            actor_id,
+            user_id,
+            details: None,
+            ip: None,
+        }
+    }
+
+    pub fn details(self, details: impl Into<String>) -> Self {
+        NewAuditEntry {
+            details: Some(details.into()),
+            ..self
+        }
+    }
+
+    pub fn ip(self, ip: Option<IpAddr>) -> Self {
+        NewAuditEntry {
+            ip: ip.map(|ip| ip.to_string()),
+            ..self
+        }
+    }
+
+    pub fn insert(&self, conn: &mut DbConnection) -> QueryResult<AuditEntry> {
+        diesel::insert_into(audit_log::table)
+            .values(self)
+            .get_result(conn)
+    }
+
+    /// Store the entry. What it's about has already happened by now, so
+    /// failing to is only logged.
+    pub fn record(&self, conn: &mut DbConnection) {
+        if let Err(e) = self.insert(conn) {
+            log::error!("Error recording {} in the audit log: {:?}", self.action, e);
+        }
+    }
+}
+
+/// Which entries to list. Unset fields match everything.
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    /// entries done by or to the user
+    pub user_id: Option<UserId>,
+    pub action: Option<AuditAction>,
+    /// only entries from after this unix timestamp
+    pub after: Option<i64>,
+    /// only entries from before this unix timestamp
+    pub before: Option<i64>,
+}
+
+impl AuditFilter {
+    fn matching(&self) -> audit_log::BoxedQuery<'static, MultiBackend> {
+        let mut query = audit_log::table.into_boxed();
+        if let Some(uid) = self.user_id {
+            query = query.filter(audit_log::user_id.eq(uid).or(audit_log::actor_id.eq(uid)));
+        }
+        if let Some(action) = self.action {
+            query = query.filter(audit_log::action.eq(action));
+        }
+        if let Some(after) = self.after {
+            query = query.filter(audit_log::created_at.gt(after));
+        }
+        if let Some(before) = self.before {
+            query = query.filter(audit_log::created_at.lt(before));
+        }
+        query
+    }
+}
+
+impl AuditEntry {
+    /// Matching entries, newest first
+    pub fn list(
+        conn: &mut DbConnection,
+        filter: &AuditFilter,
+        offset: i64,
+        limit: i64,
+    ) -> QueryResult<Vec<AuditEntry>> {
+        filter
+            .matching()
+            .order((audit_log::created_at.desc(), audit_log::id.desc()))
+            .offset(offset)
+            .limit(limit)
+            .load(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn record(conn: &mut DbConnection, entry: NewAuditEntry, at: i64) {
+        NewAuditEntry {
+            created_at: at,
+            ..entry
+        }
+        .insert(conn)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_parse_action() {
+        assert_eq!(
+            "login_failed".parse::<AuditAction>(),
+            Ok(AuditAction::LoginFailed)
+        );
+        assert_eq!(AuditAction::SettingChanged.as_str(), "setting_changed");
+        assert_eq!(
+            serde_json::to_string(&AuditAction::UserDeactivated).unwrap(),
+            "\"user_deactivated\""
+        );
+        assert_eq!(
+            "all_sessions_revoked".parse::<AuditAction>(),
+            Ok(AuditAction::AllSessionsRevoked)
+        );
+        assert!("logged_in".parse::<AuditAction>().is_err());
+    }
+
+    #[test]
+    fn test_list() {
+        let mut conn = get_test_db_connection();
+        let login = NewAuditEntry::new(AuditAction::Login, Some(UserId(1)), Some(UserId(1)))
+            .ip(Some([127, 0, 0, 1].into()));
+        record(&mut conn, login, 1000);
+        let failed = NewAuditEntry::new(AuditAction::LoginFailed, None, Some(UserId(2)))
+            .details("wrong password");
+        record(&mut conn, failed, 2000);
+        let role = NewAuditEntry::new(AuditAction::RoleChanged, Some(UserId(1)), Some(UserId(2)))
+            .details("user -> admin");
+        record(&mut conn, role, 3000);
+
+        let all = AuditEntry::list(&mut conn, &AuditFilter::default(), 0, 10).unwrap();
+        let actions: Vec<AuditAction> = all.iter().map(|entry| entry.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::RoleChanged,
+                AuditAction::LoginFailed,
+                AuditAction::Login
+            ]
+        );
+        assert_eq!(all[2].ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(all[1].details.as_deref(), Some("wrong password"));
+
+        // by or to the user
+        let filter = AuditFilter {
+            user_id: Some(UserId(1)),
+            ..Default::default()
+        };
+        assert_eq!(
+            AuditEntry::list(&mut conn, &filter, 0, 10).unwrap().len(),
+            2
+        );
+
+        let filter = AuditFilter {
+            action: Some(AuditAction::LoginFailed),
+            ..Default::default()
+        };
+        assert_eq!(
+            AuditEntry::list(&mut conn, &filter, 0, 10).unwrap().len(),
+            1
+        );
+
+        let filter = AuditFilter {
+            after: Some(1000),
+            before: Some(3000),
+            ..Default::default()
+        };
+        let page = AuditEntry::list(&mut conn, &filter, 0, 10).unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].action, AuditAction::LoginFailed);
+
+        let page = AuditEntry::list(&mut conn, &AuditFilter::default(), 1, 1).unwrap();
+        assert_eq!(page[0].action, AuditAction::LoginFailed);
+    }
+}
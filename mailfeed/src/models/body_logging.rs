@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+
+use super::settings::{self, NewSetting, Setting};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+const ENABLED: &str = "body_logging.enabled";
+const ROUTES: &str = "body_logging.routes";
+const MAX_BYTES: &str = "body_logging.max_bytes";
+
+/// Logged of each body unless set otherwise
+pub const DEFAULT_MAX_BYTES: usize = 2048;
+/// Most of each body that can be logged
+pub const MAX_MAX_BYTES: usize = 65536;
+/// Most routes that can be logged at once
+pub const MAX_ROUTES: usize = 20;
+
+/// Logins, password resets, invites, sign-ups, access tokens and two-factor
+/// setup are never logged, whatever the routes say
+const NEVER_LOGGED: &[&str] = &[
+    "/api/auth",
+    "/api/invites",
+    "/api/registration",
+    "/api/tokens",
+    "/api/users/*/2fa",
+];
+
+fn default_max_bytes() -> usize {
+    DEFAULT_MAX_BYTES
+}
+
+/// Whether `path` is `route` or under it. A `*` in the route stands for
+/// any one segment, like a user ID.
+fn under(route: &str, path: &str) -> bool {
+    let mut path = path.trim_end_matches('/').split('/');
+    route
+        .trim_end_matches('/')
+        .split('/')
+        .all(|segment| match path.next() {
+            Some(part) => segment == "*" || segment == part,
+            None => false,
+        })
+}
+
+/// Whether request and response bodies are logged for some API routes,
+/// stored as system settings. Meant for working out what a third-party
+/// client is sending without putting a proxy in front, and for turning off
+/// again afterwards.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BodyLogging {
+    pub enabled: bool,
+    /// path prefixes like `/api/feed_items` or `/api/users/*/subscriptions`
+    #[serde(default)]
+    pub routes: Vec<String>,
+    /// longer bodies are cut short
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: usize,
+}
+
+impl Default for BodyLogging {
+    fn default() -> Self {
+        BodyLogging {
+            enabled: false,
+            routes: Vec::new(),
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+impl BodyLogging {
+    /// The current setting, off if it was never set
+    pub fn load(conn: &mut DbConnection) -> BodyLogging {
+        let mut get = |key| {
+            Setting::get(conn, key, None)
+                .ok()
+                .map(|setting| setting.value)
+        };
+        BodyLogging {
+            enabled: get(ENABLED).is_some_and(|value| value == "true"),
+            routes: get(ROUTES)
+                .map(|routes| {
+                    routes
+                        .split(',')
+                        .filter(|route| !route.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            max_bytes: get(MAX_BYTES)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_MAX_BYTES),
+        }
+    }
+
+    /// Only looks at whether it's on, since that's checked on every request
+    pub fn is_enabled(conn: &mut DbConnection) -> bool {
+        Setting::get(conn, ENABLED, None).is_ok_and(|setting| setting.value == "true")
+    }
+
+    pub fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        let routes: Vec<&str> = self.routes.iter().map(|route| route.trim()).collect();
+        for (key, value) in [
+            (ENABLED, self.enabled.to_string()),
+            (ROUTES, routes.join(",")),
+            (MAX_BYTES, self.max_bytes.to_string()),
+        ] {
+            let setting = NewSetting {
+                user_id: None,
+                key: key.to_string(),
+                value,
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_sensitive(path: &str) -> bool {
+        NEVER_LOGGED.iter().any(|route| under(route, path))
+    }
+
+    /// Whether a request to `path` has its bodies logged
+    pub fn logs(&self, path: &str) -> bool {
+        self.enabled
+            && !BodyLogging::is_sensitive(path)
+            && self.routes.iter().any(|route| under(route.trim(), path))
+    }
+}
+
+impl Validate for BodyLogging {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.routes.len() > MAX_ROUTES {
+            errors.add("routes", format!("At most {} routes", MAX_ROUTES));
+        }
+        for route in self.routes.iter().map(|route| route.trim()) {
+            if !route.starts_with("/api/") || route.contains(',') {
+                errors.add("routes", format!("'{}' isn't an API route", route));
+            } else if BodyLogging::is_sensitive(route) {
+                errors.add("routes", format!("'{}' is never logged", route));
+            }
+        }
+        if !(1..=MAX_MAX_BYTES).contains(&self.max_bytes) {
+            errors.add(
+                "max_bytes",
+                format!("Must be between 1 and {}", MAX_MAX_BYTES),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_logs() {
+        let logging = BodyLogging {
+            enabled: true,
+            routes: vec![
+                "/api/feed_items".to_string(),
+                "/api/users/*/subscriptions/".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert!(logging.logs("/api/feed_items"));
+        assert!(logging.logs("/api/feed_items/batch"));
+        assert!(logging.logs("/api/users/3/subscriptions/7"));
+        assert!(!logging.logs("/api/feed_items_other"));
+        assert!(!logging.logs("/api/users/3"));
+        assert!(!BodyLogging::default().logs("/api/feed_items"));
+
+        // even when asked for
+        let everything = BodyLogging {
+            enabled: true,
+            routes: vec!["/api/".to_string()],
+            ..Default::default()
+        };
+        assert!(everything.logs("/api/feeds"));
+        assert!(!everything.logs("/api/auth/login"));
+        assert!(!everything.logs("/api/users/3/2fa/enroll"));
+        assert!(everything.validate().is_ok());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(BodyLogging::load(&mut conn), BodyLogging::default());
+        assert!(!BodyLogging::is_enabled(&mut conn));
+
+        let logging = BodyLogging {
+            enabled: true,
+            routes: vec![" /api/feed_items".to_string()],
+            max_bytes: 100,
+        };
+        logging.save(&mut conn).unwrap();
+        assert!(BodyLogging::is_enabled(&mut conn));
+        let loaded = BodyLogging::load(&mut conn);
+        assert_eq!(loaded.routes, vec!["/api/feed_items"]);
+        assert_eq!(loaded.max_bytes, 100);
+
+        let invalid = BodyLogging {
+            routes: vec!["/api/auth/login".to_string(), "feeds".to_string()],
+            max_bytes: 0,
+            ..Default::default()
+        };
+        let errors = invalid.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["routes", "routes", "max_bytes"]);
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ids::UserId,
+    settings::{self, NewSetting, Setting},
+};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+const SERVICE: &str = "bookmarks.service";
+const URL: &str = "bookmarks.url";
+const USERNAME: &str = "bookmarks.username";
+const TOKEN: &str = "bookmarks.token";
+
+/// A self-hosted bookmark manager starred items can be pushed to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BookmarkService {
+    Linkding,
+    Shiori,
+}
+
+impl BookmarkService {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BookmarkService::Linkding => "linkding",
+            BookmarkService::Shiori => "shiori",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "linkding" => Some(BookmarkService::Linkding),
+            "shiori" => Some(BookmarkService::Shiori),
+            _ => None,
+        }
+    }
+}
+
+/// Where a user's starred items are pushed, stored as the user's settings.
+/// Nothing is pushed until a service is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BookmarkSettings {
+    pub service: Option<BookmarkService>,
+    /// the bookmark manager's base URL, e.g. `https://links.example.com`
+    pub url: String,
+    /// the account to log in as, for Shiori
+    #[serde(default)]
+    pub username: String,
+    /// the API token for Linkding, or the account's password for Shiori.
+    /// Never sent back by the API. When saving, `None` keeps the current
+    /// one.
+    #[serde(skip_serializing, default)]
+    pub token: Option<String>,
+}
+
+impl BookmarkSettings {
+    /// The user's settings, with defaults for anything not set
+    pub fn load(conn: &mut DbConnection, user_id: UserId) -> BookmarkSettings {
+        let mut get = |key| {
+            Setting::get(conn, key, Some(user_id))
+                .ok()
+                .map(|setting| setting.value)
+        };
+        BookmarkSettings {
+            service: get(SERVICE).and_then(|value| BookmarkService::from_str(&value)),
+            url: get(URL).unwrap_or_default(),
+            username: get(USERNAME).unwrap_or_default(),
+            token: get(TOKEN).filter(|token| !token.is_empty()),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection, user_id: UserId) -> Result<(), settings::Error> {
+        let mut values = vec![
+            (SERVICE, self.service.map_or("", |service| service.as_str())),
+            (URL, self.url.trim_end_matches('/')),
+            (USERNAME, self.username.as_str()),
+        ];
+        if let Some(token) = &self.token {
+            values.push((TOKEN, token.as_str()));
+        }
+        for (key, value) in values {
+            let setting = NewSetting {
+                user_id: Some(user_id),
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for BookmarkSettings {
+    fn check(&self, errors: &mut ValidationErrors) {
+        let service = match self.service {
+            Some(service) => service,
+            None => return,
+        };
+        errors.url("url", &self.url);
+        if service == BookmarkService::Shiori && self.username.is_empty() {
+            errors.add("username", "Required for Shiori");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(
+            BookmarkSettings::load(&mut conn, UserId(1)),
+            BookmarkSettings::default()
+        );
+
+        let settings = BookmarkSettings {
+            service: Some(BookmarkService::Linkding),
+            url: "https://links.example.com/".to_string(),
+            username: String::new(),
+            token: Some("abc123".to_string()),
+        };
+        settings.save(&mut conn, UserId(1)).unwrap();
+        let loaded = BookmarkSettings::load(&mut conn, UserId(1));
+        assert_eq!(loaded.url, "https://links.example.com");
+        assert_eq!(loaded.token.as_deref(), Some("abc123"));
+        assert_eq!(
+            BookmarkSettings::load(&mut conn, UserId(2)),
+            BookmarkSettings::default()
+        );
+
+        // leaving the token out keeps it
+        BookmarkSettings {
+            service: None,
+            token: None,
+            ..settings
+        }
+        .save(&mut conn, UserId(1))
+        .unwrap();
+        let loaded = BookmarkSettings::load(&mut conn, UserId(1));
+        assert_eq!(loaded.service, None);
+        assert_eq!(loaded.token.as_deref(), Some("abc123"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(BookmarkSettings::default().validate().is_ok());
+        let settings = BookmarkSettings {
+            service: Some(BookmarkService::Shiori),
+            url: "not a url".to_string(),
+            ..Default::default()
+        };
+        let errors = settings.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["url", "username"]);
+    }
+}
@@ -0,0 +1,89 @@
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Text},
+};
+use serde::Serialize;
+
+use super::query_timing::{self, QueryTiming};
+use crate::db::DbConnection;
+
+#[derive(QueryableByName)]
+struct TableName {
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TableSize {
+    pub name: String,
+    pub rows: i64,
+}
+
+/// How big the database is, and which queries have been slow lately
+#[derive(Debug, Serialize)]
+pub struct DbStats {
+    pub size_bytes: i64,
+    pub tables: Vec<TableSize>,
+    pub slowest_queries: Vec<QueryTiming>,
+}
+
+impl DbStats {
+    pub fn load(conn: &mut DbConnection) -> QueryResult<DbStats> {
+        let (size, names) = match conn {
+            DbConnection::Sqlite(_) => (
+                "SELECT page_count * page_size AS count FROM pragma_page_count(), pragma_page_size()",
+                "SELECT name FROM sqlite_master WHERE type = 'table' \
+                 AND name NOT LIKE 'sqlite_%' AND name NOT LIKE '__diesel%' ORDER BY name",
+            ),
+            DbConnection::Postgres(_) => (
+                "SELECT pg_database_size(current_database()) AS count",
+                "SELECT tablename AS name FROM pg_tables WHERE schemaname = current_schema() \
+                 AND tablename NOT LIKE '__diesel%' ORDER BY tablename",
+            ),
+        };
+        let size_bytes = diesel::sql_query(size).get_result::<Count>(conn)?.count;
+
+        let names = diesel::sql_query(names).load::<TableName>(conn)?;
+        let mut tables = Vec::with_capacity(names.len());
+        for TableName { name } in names {
+            // names come from the database's catalog, not the request
+            let rows = diesel::sql_query(format!("SELECT COUNT(*) AS count FROM \"{}\"", name))
+                .get_result::<Count>(conn)?
+                .count;
+            tables.push(TableSize { name, rows });
+        }
+
+        Ok(DbStats {
+            size_bytes,
+            tables,
+            slowest_queries: query_timing::slowest(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_load() {
+        let mut conn = get_test_db_connection();
+        let stats = DbStats::load(&mut conn).unwrap();
+        assert!(stats.size_bytes > 0);
+        assert!(stats.tables.contains(&TableSize {
+            name: "feed_items".to_string(),
+            rows: 0
+        }));
+        assert!(!stats
+            .tables
+            .iter()
+            .any(|table| table.name.starts_with("__diesel")));
+    }
+}
@@ -0,0 +1,178 @@
+use super::ids::SubscriptionId;
+use super::subscription::Subscription;
+use crate::db::DbConnection;
+use crate::schema::*;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// One attempt to hand a subscription's email to the SMTP relay, and what
+/// the relay said about it
+#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations, PartialEq)]
+#[diesel(belongs_to(Subscription))]
+#[diesel(table_name = deliveries)]
+pub struct Delivery {
+    pub id: i32,
+    pub subscription_id: SubscriptionId,
+    pub sent_at: i64,
+    pub recipient: String,
+    pub item_count: i32,
+    /// whether the relay accepted the message for delivery
+    pub accepted: bool,
+    /// the relay's reply, e.g. "250 2.0.0 Ok: queued as 4F1A2B3C", or the
+    /// error if it was rejected or couldn't be reached
+    pub relay_response: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = deliveries)]
+pub struct NewDelivery<'a> {
+    pub subscription_id: SubscriptionId,
+    pub sent_at: i64,
+    pub recipient: &'a str,
+    pub item_count: i32,
+    pub accepted: bool,
+    pub relay_response: &'a str,
+}
+
+impl<'a> NewDelivery<'a> {
+    pub fn insert(&self, conn: &mut DbConnection) -> Option<Delivery> {
+        use crate::schema::deliveries::dsl::*;
+        match diesel::insert_into(deliveries)
+            .values(self)
+            .get_result(conn)
+        {
+            Ok(delivery) => Some(delivery),
+            Err(e) => {
+                log::warn!("Error recording delivery: {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+impl Delivery {
+    /// The subscription's most recent deliveries, newest first
+    pub fn get_for_subscription(
+        conn: &mut DbConnection,
+        sub_id: SubscriptionId,
+        limit: i64,
+    ) -> Result<Vec<Delivery>, diesel::result::Error> {
+        use crate::schema::deliveries::dsl::{deliveries, id, subscription_id};
+        deliveries
+            .filter(subscription_id.eq(sub_id))
+            .order(id.desc())
+            .limit(limit)
+            .load::<Delivery>(conn)
+    }
+
+    pub fn latest_for_subscription(
+        conn: &mut DbConnection,
+        sub_id: SubscriptionId,
+    ) -> Result<Option<Delivery>, diesel::result::Error> {
+        Delivery::get_for_subscription(conn, sub_id, 1).map(|mut found| found.pop())
+    }
+
+    /// The most recent email the relay accepted for any of the subscriptions
+    pub fn last_accepted(
+        conn: &mut DbConnection,
+        sub_ids: &[SubscriptionId],
+    ) -> Result<Option<Delivery>, diesel::result::Error> {
+        use crate::schema::deliveries::dsl::{accepted, deliveries, id, subscription_id};
+        deliveries
+            .filter(subscription_id.eq_any(sub_ids))
+            .filter(accepted.eq(true))
+            .order(id.desc())
+            .first::<Delivery>(conn)
+            .optional()
+    }
+
+    pub fn delete_for_subscription(
+        conn: &mut DbConnection,
+        sub_id: SubscriptionId,
+    ) -> Result<usize, diesel::result::Error> {
+        use crate::schema::deliveries::dsl::{deliveries, subscription_id};
+        diesel::delete(deliveries.filter(subscription_id.eq(sub_id))).execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn record(conn: &mut DbConnection, sub_id: SubscriptionId, sent_at: i64, response: &str) {
+        NewDelivery {
+            subscription_id: sub_id,
+            sent_at,
+            recipient: "test@example.com",
+            item_count: 2,
+            accepted: response.starts_with('2'),
+            relay_response: response,
+        }
+        .insert(conn)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_latest_for_subscription() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(
+            Delivery::latest_for_subscription(&mut conn, SubscriptionId(1)),
+            Ok(None)
+        );
+
+        record(
+            &mut conn,
+            SubscriptionId(1),
+            100,
+            "250 2.0.0 Ok: queued as ABC",
+        );
+        record(
+            &mut conn,
+            SubscriptionId(2),
+            200,
+            "250 2.0.0 Ok: queued as DEF",
+        );
+        record(&mut conn, SubscriptionId(1), 300, "550 5.1.1 No such user");
+
+        let latest = Delivery::latest_for_subscription(&mut conn, SubscriptionId(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest.sent_at, 300);
+        assert_eq!(latest.relay_response, "550 5.1.1 No such user");
+        assert!(!latest.accepted);
+
+        let last_accepted =
+            Delivery::last_accepted(&mut conn, &[SubscriptionId(1), SubscriptionId(3)])
+                .unwrap()
+                .unwrap();
+        assert_eq!(last_accepted.sent_at, 100);
+
+        let all = Delivery::get_for_subscription(&mut conn, SubscriptionId(1), 10).unwrap();
+        assert_eq!(
+            all.iter().map(|d| d.sent_at).collect::<Vec<_>>(),
+            vec![300, 100]
+        );
+    }
+
+    #[test]
+    fn test_delete_for_subscription() {
+        let mut conn = get_test_db_connection();
+        record(&mut conn, SubscriptionId(1), 100, "250 Ok");
+        record(&mut conn, SubscriptionId(2), 200, "250 Ok");
+
+        assert_eq!(
+            Delivery::delete_for_subscription(&mut conn, SubscriptionId(1)),
+            Ok(1)
+        );
+        assert_eq!(
+            Delivery::latest_for_subscription(&mut conn, SubscriptionId(1)),
+            Ok(None)
+        );
+        assert!(
+            Delivery::latest_for_subscription(&mut conn, SubscriptionId(2))
+                .unwrap()
+                .is_some()
+        );
+    }
+}
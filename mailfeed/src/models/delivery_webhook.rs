@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ids::UserId,
+    settings::{self, NewSetting, Setting},
+};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+pub(super) const URL: &str = "delivery_webhook.url";
+const SECRET: &str = "delivery_webhook.secret";
+const MIN_SECRET_LENGTH: usize = 16;
+
+/// Where the user's subscriptions delivered by webhook are POSTed, stored
+/// as the user's settings. Nothing is sent until both are set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DeliveryWebhook {
+    pub url: String,
+    /// signs each request, see `tasks::webhook_sender`. Never sent back by
+    /// the API. When saving, `None` keeps the current one.
+    #[serde(skip_serializing, default)]
+    pub secret: Option<String>,
+}
+
+impl DeliveryWebhook {
+    /// The user's settings, with defaults for anything not set
+    pub fn load(conn: &mut DbConnection, user_id: UserId) -> DeliveryWebhook {
+        let mut get = |key| {
+            Setting::get(conn, key, Some(user_id))
+                .ok()
+                .map(|setting| setting.value)
+        };
+        DeliveryWebhook {
+            url: get(URL).unwrap_or_default(),
+            secret: get(SECRET).filter(|secret| !secret.is_empty()),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection, user_id: UserId) -> Result<(), settings::Error> {
+        let mut values = vec![(URL, self.url.trim())];
+        if let Some(secret) = &self.secret {
+            values.push((SECRET, secret.as_str()));
+        }
+        for (key, value) in values {
+            let setting = NewSetting {
+                user_id: Some(user_id),
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// The URL and secret to send with, if both are set
+    pub fn target(&self) -> Option<(&str, &str)> {
+        match (self.url.as_str(), self.secret.as_deref()) {
+            ("", _) | (_, None) => None,
+            (url, Some(secret)) => Some((url, secret)),
+        }
+    }
+}
+
+impl Validate for DeliveryWebhook {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if !self.url.trim().is_empty() {
+            errors.url("url", self.url.trim());
+        }
+        if matches!(&self.secret, Some(secret) if secret.len() < MIN_SECRET_LENGTH) {
+            errors.add(
+                "secret",
+                format!("Must be at least {} characters", MIN_SECRET_LENGTH),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(DeliveryWebhook::load(&mut conn, UserId(1)).target(), None);
+
+        let webhook = DeliveryWebhook {
+            url: " https://hooks.example.com/mailfeed ".to_string(),
+            secret: Some("0123456789abcdef".to_string()),
+        };
+        assert!(webhook.validate().is_ok());
+        webhook.save(&mut conn, UserId(1)).unwrap();
+        let loaded = DeliveryWebhook::load(&mut conn, UserId(1));
+        assert_eq!(
+            loaded.target(),
+            Some(("https://hooks.example.com/mailfeed", "0123456789abcdef"))
+        );
+
+        // leaving the secret out keeps it
+        DeliveryWebhook {
+            url: "https://other.example.com".to_string(),
+            secret: None,
+        }
+        .save(&mut conn, UserId(1))
+        .unwrap();
+        assert_eq!(
+            DeliveryWebhook::load(&mut conn, UserId(1)).target(),
+            Some(("https://other.example.com", "0123456789abcdef"))
+        );
+
+        let short = DeliveryWebhook {
+            url: "https://hooks.example.com".to_string(),
+            secret: Some("short".to_string()),
+        };
+        assert!(short.validate().is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+
+use chrono::NaiveTime;
+
+const TIME_FORMAT: &str = "%H:%M";
+
+/// Time of day when a subscription's emails may be sent, like
+/// `07:00-09:00`, in the user's time zone. The window wraps past midnight
+/// if it ends before it starts. Due items outside it wait for it to open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeliveryWindow {
+    pub start: NaiveTime,
+    /// the first minute after the window
+    pub end: NaiveTime,
+}
+
+impl DeliveryWindow {
+    /// None unless it's two `HH:MM` times, not the same, joined by `-`
+    pub fn parse(value: &str) -> Option<DeliveryWindow> {
+        let (start, end) = value.split_once('-')?;
+        let start = NaiveTime::parse_from_str(start.trim(), TIME_FORMAT).ok()?;
+        let end = NaiveTime::parse_from_str(end.trim(), TIME_FORMAT).ok()?;
+        (start != end).then_some(DeliveryWindow { start, end })
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl fmt::Display for DeliveryWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format(TIME_FORMAT),
+            self.end.format(TIME_FORMAT)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let window = DeliveryWindow::parse("07:00-09:30").unwrap();
+        assert_eq!((window.start, window.end), (time(7, 0), time(9, 30)));
+        assert_eq!(window.to_string(), "07:00-09:30");
+        assert_eq!(
+            DeliveryWindow::parse(" 7:00 - 9:30 ").map(|w| w.to_string()),
+            Some("07:00-09:30".to_string())
+        );
+
+        assert_eq!(DeliveryWindow::parse("07:00"), None);
+        assert_eq!(DeliveryWindow::parse("07:00-25:00"), None);
+        assert_eq!(DeliveryWindow::parse("07:00-07:00"), None);
+    }
+
+    #[test]
+    fn test_contains() {
+        let morning = DeliveryWindow::parse("07:00-09:00").unwrap();
+        assert!(!morning.contains(time(6, 59)));
+        assert!(morning.contains(time(7, 0)));
+        assert!(morning.contains(time(8, 59)));
+        assert!(!morning.contains(time(9, 0)));
+
+        let overnight = DeliveryWindow::parse("22:00-02:00").unwrap();
+        assert!(overnight.contains(time(23, 0)));
+        assert!(overnight.contains(time(1, 0)));
+        assert!(!overnight.contains(time(12, 0)));
+    }
+}
@@ -0,0 +1,186 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ids::UserId,
+    settings::{self, NewSetting, Setting},
+};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+const SKIP_WEEKENDS: &str = "digests.skip_weekends";
+const SKIP_DATES: &str = "digests.skip_dates";
+
+/// Most dates a user may skip, which is plenty for a couple of years of
+/// holidays
+pub const MAX_SKIP_DATES: usize = 100;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Why a day's digests aren't sent
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    Weekend,
+    /// one of the user's skip dates
+    SkipDate,
+}
+
+/// Days the user doesn't want digests, stored as the user's settings.
+/// Nothing is sent on those days, so the items roll into the next digest.
+/// Days are in the user's time zone, from their daily send time.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DigestSkips {
+    pub skip_weekends: bool,
+    /// as YYYY-MM-DD
+    #[serde(default)]
+    pub skip_dates: Vec<String>,
+}
+
+impl DigestSkips {
+    pub fn load(conn: &mut DbConnection, user_id: UserId) -> DigestSkips {
+        let mut get = |key| {
+            Setting::get(conn, key, Some(user_id))
+                .ok()
+                .map(|setting| setting.value)
+        };
+        DigestSkips {
+            skip_weekends: get(SKIP_WEEKENDS).is_some_and(|value| value == "true"),
+            skip_dates: get(SKIP_DATES)
+                .map(|value| {
+                    value
+                        .split(',')
+                        .filter(|date| !date.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Dates are saved sorted, without duplicates
+    pub fn save(&self, conn: &mut DbConnection, user_id: UserId) -> Result<(), settings::Error> {
+        let mut dates: Vec<&str> = self.skip_dates.iter().map(|date| date.trim()).collect();
+        dates.sort_unstable();
+        dates.dedup();
+        for (key, value) in [
+            (SKIP_WEEKENDS, self.skip_weekends.to_string()),
+            (SKIP_DATES, dates.join(",")),
+        ] {
+            let setting = NewSetting {
+                user_id: Some(user_id),
+                key: key.to_string(),
+                value,
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// Why digests aren't sent on the date, if they aren't
+    pub fn skip_reason(&self, date: NaiveDate) -> Option<SkipReason> {
+        let date_str = date.format(DATE_FORMAT).to_string();
+        if self.skip_dates.contains(&date_str) {
+            Some(SkipReason::SkipDate)
+        } else if self.skip_weekends && matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            Some(SkipReason::Weekend)
+        } else {
+            None
+        }
+    }
+}
+
+impl Validate for DigestSkips {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.skip_dates.len() > MAX_SKIP_DATES {
+            errors.add(
+                "skip_dates",
+                format!("At most {} dates can be skipped", MAX_SKIP_DATES),
+            );
+        }
+        if let Some(date) = self
+            .skip_dates
+            .iter()
+            .find(|date| NaiveDate::parse_from_str(date.trim(), DATE_FORMAT).is_err())
+        {
+            errors.add(
+                "skip_dates",
+                format!("'{}' isn't a date like 2026-12-25", date),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_skip_reason() {
+        let skips = DigestSkips {
+            skip_weekends: true,
+            skip_dates: vec!["2026-12-25".to_string()],
+        };
+        // a Friday, Saturday and Sunday
+        assert_eq!(skips.skip_reason(date(2026, 10, 16)), None);
+        assert_eq!(
+            skips.skip_reason(date(2026, 10, 17)),
+            Some(SkipReason::Weekend)
+        );
+        assert_eq!(
+            skips.skip_reason(date(2026, 10, 18)),
+            Some(SkipReason::Weekend)
+        );
+        assert_eq!(
+            skips.skip_reason(date(2026, 12, 25)),
+            Some(SkipReason::SkipDate)
+        );
+        assert_eq!(DigestSkips::default().skip_reason(date(2026, 10, 17)), None);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(
+            DigestSkips::load(&mut conn, UserId(1)),
+            DigestSkips::default()
+        );
+        DigestSkips {
+            skip_weekends: true,
+            skip_dates: vec![
+                "2027-01-01".to_string(),
+                "2026-12-25".to_string(),
+                "2027-01-01".to_string(),
+            ],
+        }
+        .save(&mut conn, UserId(1))
+        .unwrap();
+        assert_eq!(
+            DigestSkips::load(&mut conn, UserId(1)),
+            DigestSkips {
+                skip_weekends: true,
+                skip_dates: vec!["2026-12-25".to_string(), "2027-01-01".to_string()],
+            }
+        );
+        assert_eq!(
+            DigestSkips::load(&mut conn, UserId(2)),
+            DigestSkips::default()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let skips = DigestSkips {
+            skip_weekends: false,
+            skip_dates: vec!["2026-12-25".to_string(), "Dec 26".to_string()],
+        };
+        let errors = skips.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["skip_dates"]);
+    }
+}
@@ -0,0 +1,92 @@
+use serde::Deserialize;
+
+use super::{
+    ids::UserId,
+    settings::{self, NewSetting, Setting},
+};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+pub(super) const URL: &str = "discord.webhook_url";
+const URL_PREFIXES: [&str; 2] = [
+    "https://discord.com/api/webhooks/",
+    "https://discordapp.com/api/webhooks/",
+];
+
+/// The Discord webhook the user's subscriptions delivered by Discord are
+/// posted to, stored as the user's setting. The URL holds the webhook's
+/// token, so it's never sent back by the API.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct DiscordWebhook {
+    /// empty to remove it
+    pub url: String,
+}
+
+impl DiscordWebhook {
+    pub fn load(conn: &mut DbConnection, user_id: UserId) -> DiscordWebhook {
+        DiscordWebhook {
+            url: Setting::get(conn, URL, Some(user_id))
+                .map(|setting| setting.value)
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection, user_id: UserId) -> Result<(), settings::Error> {
+        let setting = NewSetting {
+            user_id: Some(user_id),
+            key: URL.to_string(),
+            value: self.url.trim().to_string(),
+        };
+        Setting::set(conn, &setting)?;
+        Ok(())
+    }
+
+    /// The URL to post to, if it's set
+    pub fn url(&self) -> Option<&str> {
+        Some(self.url.trim()).filter(|url| !url.is_empty())
+    }
+}
+
+impl Validate for DiscordWebhook {
+    fn check(&self, errors: &mut ValidationErrors) {
+        let url = self.url.trim();
+        if !url.is_empty() && !URL_PREFIXES.iter().any(|prefix| url.starts_with(prefix)) {
+            errors.add(
+                "url",
+                "Must be a Discord webhook URL, like https://discord.com/api/webhooks/...",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(DiscordWebhook::load(&mut conn, UserId(1)).url(), None);
+
+        let webhook = DiscordWebhook {
+            url: " https://discord.com/api/webhooks/123/abc ".to_string(),
+        };
+        assert!(webhook.validate().is_ok());
+        webhook.save(&mut conn, UserId(1)).unwrap();
+        assert_eq!(
+            DiscordWebhook::load(&mut conn, UserId(1)).url(),
+            Some("https://discord.com/api/webhooks/123/abc")
+        );
+
+        DiscordWebhook::default()
+            .save(&mut conn, UserId(1))
+            .unwrap();
+        assert_eq!(DiscordWebhook::load(&mut conn, UserId(1)).url(), None);
+
+        let other = DiscordWebhook {
+            url: "https://example.com/api/webhooks/123/abc".to_string(),
+        };
+        assert!(other.validate().is_err());
+    }
+}
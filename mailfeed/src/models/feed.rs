@@ -1,29 +1,73 @@
+use super::feed_change::FeedChange;
+use super::ids::FeedId;
+use crate::db::{to_sql_as_text, DbConnection};
+use crate::scheduler::cron::CronSchedule;
 use crate::schema::*;
 use diesel::{
     backend::Backend,
     deserialize::{self, FromSql},
     prelude::*,
     serialize::{self, Output, ToSql},
-    sql_types::Integer,
+    sql_types::{BigInt, Integer, Nullable, Text},
     AsExpression, FromSqlRow,
 };
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, PartialEq)]
+#[derive(
+    Debug, Serialize, Deserialize, Queryable, QueryableByName, Insertable, Identifiable, PartialEq,
+)]
 #[diesel(table_name = feeds)]
 pub struct Feed {
-    pub id: i32,
+    pub id: FeedId,
     pub url: String,
     pub feed_type: FeedType,
     pub title: String,
-    // TODO: update vv or remove
-    pub last_checked: i32, // zero if never checked
+    pub last_checked: i64, // zero if never checked
     // TODO: is vv actually used
-    pub last_updated: i32,
-    // TODO: update vv
-    pub error_time: i32, // zero if no error
-    // TODO: update vv
+    pub last_updated: i64,
+    /// when the feed started failing, zero if its last check succeeded
+    pub error_time: i64,
     pub error_message: Option<String>,
+    pub description: Option<String>,
+    /// the site the feed belongs to, as opposed to the feed's own URL
+    pub homepage: Option<String>,
+    pub link_mode: LinkMode,
+    /// seconds to wait after last_checked before fetching again
+    pub poll_interval: i32,
+    /// hash of the last body that was parsed, to skip unchanged fetches
+    pub body_hash: Option<String>,
+    pub error_kind: FeedErrorKind,
+    /// items added by the last parsed fetch
+    pub new_items: i32,
+    /// items the last parsed fetch skipped for being over the per-fetch cap
+    pub skipped_items: i32,
+    /// the ETag header from the last parsed fetch, sent as If-None-Match
+    pub etag: Option<String>,
+    /// the Last-Modified header from the last parsed fetch, sent as
+    /// If-Modified-Since
+    pub last_modified: Option<String>,
+    /// cron expression for when to fetch, instead of the poll interval
+    pub fetch_schedule: Option<String>,
+    /// what was odd about the last parsed fetch
+    #[serde(default)]
+    pub parse_warnings: ParseWarnings,
+    /// sealed FeedCredentials for private feeds, never sent by the API
+    #[serde(skip_serializing, default)]
+    pub credentials: Option<String>,
+    /// the newest publish date of the items pruned so far. Entries
+    /// published no later aren't added again. Zero if none were.
+    #[serde(default)]
+    pub pruned_through: i64,
+    /// fetches that failed in a row since the last one that worked
+    #[serde(default)]
+    pub consecutive_failures: i32,
+    /// when too many failures in a row marked the feed broken and paused
+    /// its subscriptions, zero if it isn't
+    #[serde(default)]
+    pub broken_at: i64,
+    /// when an admin paused polling the feed, zero if it's polled
+    #[serde(default)]
+    pub paused_at: i64,
 }
 
 #[repr(i32)]
@@ -68,6 +112,190 @@ where
     }
 }
 
+/// Which of an item's links to use in emails. Aggregators like Hacker News
+/// have both the article and a discussion page for each item.
+#[repr(i32)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, AsExpression, FromSqlRow)]
+#[diesel(sql_type=Integer)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkMode {
+    /// `Both` for known aggregators, otherwise `Link`
+    Auto,
+    Link,
+    Comments,
+    Both,
+}
+
+impl<DB> FromSql<Integer, DB> for LinkMode
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(LinkMode::Auto),
+            1 => Ok(LinkMode::Link),
+            2 => Ok(LinkMode::Comments),
+            3 => Ok(LinkMode::Both),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl<DB> ToSql<Integer, DB> for LinkMode
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            LinkMode::Auto => 0.to_sql(out),
+            LinkMode::Link => 1.to_sql(out),
+            LinkMode::Comments => 2.to_sql(out),
+            LinkMode::Both => 3.to_sql(out),
+        }
+    }
+}
+
+/// What went wrong the last time a feed was fetched
+#[repr(i32)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, AsExpression, FromSqlRow)]
+#[diesel(sql_type=Integer)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedErrorKind {
+    None,
+    /// the host name couldn't be resolved
+    Dns,
+    Timeout,
+    /// the connection was refused, reset, or failed TLS
+    Connection,
+    /// the server responded with a non-success status
+    Http,
+    /// the response wasn't a feed we could parse
+    Parse,
+}
+
+impl FeedErrorKind {
+    /// Whether the error is likely to go away on its own soon
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            FeedErrorKind::Dns | FeedErrorKind::Timeout | FeedErrorKind::Connection
+        )
+    }
+}
+
+impl<DB> FromSql<Integer, DB> for FeedErrorKind
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(FeedErrorKind::None),
+            1 => Ok(FeedErrorKind::Dns),
+            2 => Ok(FeedErrorKind::Timeout),
+            3 => Ok(FeedErrorKind::Connection),
+            4 => Ok(FeedErrorKind::Http),
+            5 => Ok(FeedErrorKind::Parse),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl<DB> ToSql<Integer, DB> for FeedErrorKind
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            FeedErrorKind::None => 0.to_sql(out),
+            FeedErrorKind::Dns => 1.to_sql(out),
+            FeedErrorKind::Timeout => 2.to_sql(out),
+            FeedErrorKind::Connection => 3.to_sql(out),
+            FeedErrorKind::Http => 4.to_sql(out),
+            FeedErrorKind::Parse => 5.to_sql(out),
+        }
+    }
+}
+
+/// Something odd about a feed that still parsed, which may explain why its
+/// items look wrong
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseWarningKind {
+    /// items without a publish date, which are dated when first seen
+    MissingDates,
+    /// items with the same ID (GUID) as an earlier one in the feed
+    DuplicateIds,
+    /// items without a link, which are skipped
+    MissingLinks,
+    /// bytes that weren't valid in the feed's encoding
+    InvalidEncoding,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ParseWarning {
+    pub kind: ParseWarningKind,
+    /// items affected, zero if it's about the whole feed
+    pub items: usize,
+    /// to show users
+    pub message: String,
+}
+
+impl ParseWarning {
+    pub fn new(kind: ParseWarningKind, items: usize) -> Self {
+        let message = match kind {
+            ParseWarningKind::MissingDates => format!(
+                "{} items have no publish date, so they're dated when they were first seen",
+                items
+            ),
+            ParseWarningKind::DuplicateIds => format!(
+                "{} items have the same ID as another item, so some may be missing or repeated",
+                items
+            ),
+            ParseWarningKind::MissingLinks => {
+                format!("{} items have no link, so they were skipped", items)
+            }
+            ParseWarningKind::InvalidEncoding => {
+                "The feed has text that isn't valid in its encoding, shown as \u{FFFD}".to_string()
+            }
+        };
+        ParseWarning {
+            kind,
+            items,
+            message,
+        }
+    }
+}
+
+/// A feed's parse warnings, stored as JSON
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+#[serde(transparent)]
+pub struct ParseWarnings(pub Vec<ParseWarning>);
+
+impl<DB> FromSql<Text, DB> for ParseWarnings
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+        Ok(serde_json::from_str(&String::from_sql(bytes)?)?)
+    }
+}
+
+to_sql_as_text!(ParseWarnings, |warnings| serde_json::to_string(warnings)?);
+
+/// Hosts whose feeds link to both an article and a discussion page
+const AGGREGATOR_HOSTS: &[&str] = &[
+    "news.ycombinator.com",
+    "hnrss.org",
+    "lobste.rs",
+    "reddit.com",
+];
+
 #[derive(Debug, Serialize, Deserialize, Insertable)]
 #[diesel(table_name = feeds)]
 pub struct NewFeed<'a> {
@@ -75,11 +303,29 @@ pub struct NewFeed<'a> {
     pub feed_type: FeedType,
     pub title: String,
     /// zero if never checked
-    pub last_checked: i32,
-    pub last_updated: i32,
+    pub last_checked: i64,
+    pub last_updated: i64,
     /// zero if no error
-    pub error_time: i32,
+    pub error_time: i64,
     pub error_message: Option<String>,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub link_mode: LinkMode,
+    pub poll_interval: i32,
+    pub body_hash: Option<String>,
+    pub error_kind: FeedErrorKind,
+    pub new_items: i32,
+    pub skipped_items: i32,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub fetch_schedule: Option<String>,
+    pub parse_warnings: ParseWarnings,
+    /// sealed FeedCredentials
+    pub credentials: Option<String>,
+    pub pruned_through: i64,
+    pub consecutive_failures: i32,
+    pub broken_at: i64,
+    pub paused_at: i64,
 }
 
 impl<'a> Default for NewFeed<'a> {
@@ -92,6 +338,23 @@ impl<'a> Default for NewFeed<'a> {
             last_updated: 0,
             error_time: 0,
             error_message: None,
+            description: None,
+            homepage: None,
+            link_mode: LinkMode::Auto,
+            poll_interval: 0,
+            body_hash: None,
+            error_kind: FeedErrorKind::None,
+            new_items: 0,
+            skipped_items: 0,
+            etag: None,
+            last_modified: None,
+            fetch_schedule: None,
+            parse_warnings: ParseWarnings::default(),
+            credentials: None,
+            pruned_through: 0,
+            consecutive_failures: 0,
+            broken_at: 0,
+            paused_at: 0,
         }
     }
 }
@@ -102,14 +365,30 @@ pub struct PartialFeed<'a> {
     pub url: Option<String>,
     pub feed_type: Option<FeedType>,
     pub title: Option<&'a str>,
-    pub last_checked: Option<i32>,
-    pub last_updated: Option<i32>,
-    pub error_time: Option<i32>,
-    pub error_message: Option<String>,
+    pub last_checked: Option<i64>,
+    pub last_updated: Option<i64>,
+    pub error_time: Option<i64>,
+    pub error_message: Option<Option<String>>,
+    pub description: Option<&'a str>,
+    pub homepage: Option<&'a str>,
+    pub link_mode: Option<LinkMode>,
+    pub poll_interval: Option<i32>,
+    pub body_hash: Option<&'a str>,
+    pub error_kind: Option<FeedErrorKind>,
+    pub new_items: Option<i32>,
+    pub skipped_items: Option<i32>,
+    pub etag: Option<Option<&'a str>>,
+    pub last_modified: Option<Option<&'a str>>,
+    pub fetch_schedule: Option<Option<&'a str>>,
+    pub parse_warnings: Option<ParseWarnings>,
+    pub credentials: Option<Option<String>>,
+    pub consecutive_failures: Option<i32>,
+    pub broken_at: Option<i64>,
+    pub paused_at: Option<i64>,
 }
 
 impl<'a> NewFeed<'a> {
-    pub fn insert(&self, conn: &mut SqliteConnection) -> Option<Feed> {
+    pub fn insert(&self, conn: &mut DbConnection) -> Option<Feed> {
         use crate::schema::feeds::dsl::*;
         match diesel::insert_into(feeds).values(self).get_result(conn) {
             Ok(feed) => Some(feed),
@@ -121,8 +400,88 @@ impl<'a> NewFeed<'a> {
     }
 }
 
+/// A feed with what the feed list shows alongside it
+#[derive(Debug, Serialize, QueryableByName)]
+pub struct FeedSummary {
+    #[diesel(embed)]
+    #[serde(flatten)]
+    pub feed: Feed,
+    /// users with an active subscription to the feed
+    #[diesel(sql_type = BigInt)]
+    pub subscriber_count: i64,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub latest_item_title: Option<String>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    pub latest_item_date: Option<i64>,
+}
+
 impl Feed {
-    pub fn get_by_id(conn: &mut SqliteConnection, id: i32) -> Option<Feed> {
+    /// When the current run of fetch errors started, if the feed is failing
+    pub fn failing_since(&self) -> Option<i64> {
+        match self.error_time {
+            0 => None,
+            since => Some(since),
+        }
+    }
+
+    /// Whether the feed's poll interval has passed since it was last
+    /// checked, or with a fetch schedule, whether a scheduled time has
+    /// passed since. Paused feeds never are.
+    pub fn is_due(&self, now: i64) -> bool {
+        if self.is_paused() {
+            return false;
+        }
+        match self.fetch_schedule() {
+            Some(schedule) => schedule
+                .next_after(self.last_checked)
+                .is_some_and(|next| now >= next),
+            None => now >= self.last_checked + i64::from(self.poll_interval),
+        }
+    }
+
+    /// Whether an admin paused polling, e.g. while the site is down for
+    /// maintenance. Subscriptions are left as they are.
+    pub fn is_paused(&self) -> bool {
+        self.paused_at > 0
+    }
+
+    /// The feed's fetch schedule, if it has a valid one
+    pub fn fetch_schedule(&self) -> Option<CronSchedule> {
+        let expression = self.fetch_schedule.as_deref()?;
+        match CronSchedule::parse(expression) {
+            Ok(schedule) => Some(schedule),
+            Err(e) => {
+                log::warn!(
+                    "Ignoring fetch schedule '{}' of feed {}: {}",
+                    expression,
+                    self.id,
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// The feed's link mode, with `Auto` resolved from the feed's URL
+    pub fn link_mode(&self) -> LinkMode {
+        if self.link_mode != LinkMode::Auto {
+            return self.link_mode;
+        }
+        let host = url::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let is_aggregator = AGGREGATOR_HOSTS
+            .iter()
+            .any(|agg| host == *agg || host.ends_with(&format!(".{}", agg)));
+        if is_aggregator {
+            LinkMode::Both
+        } else {
+            LinkMode::Link
+        }
+    }
+
+    pub fn get_by_id(conn: &mut DbConnection, id: FeedId) -> Option<Feed> {
         use crate::schema::feeds::dsl::feeds;
         match feeds.find(id).first::<Feed>(conn) {
             Ok(feed) => Some(feed),
@@ -133,7 +492,7 @@ impl Feed {
         }
     }
 
-    pub fn get_by_url(conn: &mut SqliteConnection, url: &str) -> Option<Feed> {
+    pub fn get_by_url(conn: &mut DbConnection, url: &str) -> Option<Feed> {
         use crate::schema::feeds::dsl::{feeds, url as url_col};
         match feeds.filter(url_col.eq(url)).first::<Feed>(conn) {
             Ok(feed) => Some(feed),
@@ -144,7 +503,26 @@ impl Feed {
         }
     }
 
-    pub fn get_all(conn: &mut SqliteConnection) -> Option<Vec<Feed>> {
+    pub fn count(conn: &mut DbConnection) -> Result<i64, diesel::result::Error> {
+        use crate::schema::feeds::dsl::feeds;
+        feeds.count().get_result(conn)
+    }
+
+    /// Feeds with at least one active subscription, other than paused ones
+    pub fn active_ids(conn: &mut DbConnection) -> Result<Vec<FeedId>, diesel::result::Error> {
+        use crate::schema::feeds::dsl::paused_at;
+        use crate::schema::subscriptions::dsl::{feed_id, is_active, subscriptions};
+        subscriptions
+            .inner_join(crate::schema::feeds::table)
+            .filter(is_active.eq(true))
+            .filter(paused_at.eq(0))
+            .select(feed_id)
+            .distinct()
+            .order(feed_id)
+            .load::<FeedId>(conn)
+    }
+
+    pub fn get_all(conn: &mut DbConnection) -> Option<Vec<Feed>> {
         use crate::schema::feeds::dsl::feeds;
         match feeds.load::<Feed>(conn) {
             Ok(found) => match found.len() {
@@ -158,7 +536,33 @@ impl Feed {
         }
     }
 
-    pub fn update(conn: &mut SqliteConnection, feed_id: i32, update: &PartialFeed) -> Option<Feed> {
+    /// Every feed with its subscriber count and most recently published
+    /// item, in one query, ordered by title
+    pub fn summaries(conn: &mut DbConnection) -> Result<Vec<FeedSummary>, diesel::result::Error> {
+        diesel::sql_query(
+            "SELECT feeds.*,
+                COALESCE(subscribers.count, 0) AS subscriber_count,
+                latest.title AS latest_item_title,
+                latest.pub_date AS latest_item_date
+            FROM feeds
+            LEFT JOIN (
+                SELECT feed_id, COUNT(DISTINCT user_id) AS count
+                FROM subscriptions
+                WHERE is_active
+                GROUP BY feed_id
+            ) AS subscribers ON subscribers.feed_id = feeds.id
+            LEFT JOIN feed_items AS latest ON latest.id = (
+                SELECT id FROM feed_items
+                WHERE feed_id = feeds.id
+                ORDER BY pub_date DESC, id DESC
+                LIMIT 1
+            )
+            ORDER BY lower(feeds.title), feeds.id",
+        )
+        .load::<FeedSummary>(conn)
+    }
+
+    pub fn update(conn: &mut DbConnection, feed_id: FeedId, update: &PartialFeed) -> Option<Feed> {
         use crate::schema::feeds::dsl::{feeds, id};
         match diesel::update(feeds.filter(id.eq(feed_id)))
             .set(update)
@@ -172,8 +576,12 @@ impl Feed {
         }
     }
 
-    pub fn delete(conn: &mut SqliteConnection, feed_id: i32) -> bool {
+    pub fn delete(conn: &mut DbConnection, feed_id: FeedId) -> bool {
         use crate::schema::feeds::dsl::{feeds, id};
+        if let Err(e) = FeedChange::delete_for_feed(conn, feed_id) {
+            log::warn!("Error deleting feed's change history: {:?}", e);
+            return false;
+        }
         match diesel::delete(feeds.filter(id.eq(feed_id))).execute(conn) {
             Ok(_) => true,
             Err(e) => {
@@ -183,3 +591,167 @@ impl Feed {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::feed_item::NewFeedItem;
+    use crate::models::ids::UserId;
+    use crate::models::subscription::{NewSubscription, PartialSubscription, Subscription};
+    use crate::test_helpers::test_helpers::{get_test_db_connection, test_feed};
+
+    #[test]
+    fn test_link_mode_auto_detects_aggregators() {
+        let mut feed = Feed {
+            url: "https://news.ycombinator.com/rss".to_string(),
+            title: String::new(),
+            ..test_feed()
+        };
+        assert_eq!(feed.link_mode(), LinkMode::Both);
+
+        feed.url = "https://old.reddit.com/r/rust/.rss".to_string();
+        assert_eq!(feed.link_mode(), LinkMode::Both);
+
+        feed.url = "https://example.com/feed.xml".to_string();
+        assert_eq!(feed.link_mode(), LinkMode::Link);
+
+        feed.link_mode = LinkMode::Comments;
+        assert_eq!(feed.link_mode(), LinkMode::Comments);
+    }
+
+    #[test]
+    fn test_is_due_with_fetch_schedule() {
+        let mut conn = get_test_db_connection();
+        let feed = NewFeed {
+            url: "https://example.com/feed.xml",
+            poll_interval: 300,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        // 2026-10-16 08:00 UTC, a Friday
+        let friday = 1_792_137_600;
+        let update = PartialFeed {
+            last_checked: Some(friday),
+            ..Default::default()
+        };
+        let feed = Feed::update(&mut conn, feed.id, &update).unwrap();
+        assert!(feed.is_due(friday + 300));
+
+        let update = PartialFeed {
+            fetch_schedule: Some(Some("0 9 * * mon-fri")),
+            ..Default::default()
+        };
+        let feed = Feed::update(&mut conn, feed.id, &update).unwrap();
+        assert!(!feed.is_due(friday + 300));
+        assert!(feed.is_due(friday + 60 * 60));
+
+        let update = PartialFeed {
+            paused_at: Some(friday),
+            ..Default::default()
+        };
+        let feed = Feed::update(&mut conn, feed.id, &update).unwrap();
+        assert!(!feed.is_due(friday + 60 * 60));
+    }
+
+    #[test]
+    fn test_active_ids() {
+        let mut conn = get_test_db_connection();
+        let mut insert_feed = |url| {
+            NewFeed {
+                url,
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap()
+            .id
+        };
+        let active = insert_feed("https://example.com/active.xml");
+        let paused = insert_feed("https://example.com/paused.xml");
+        let not_polled = insert_feed("https://example.com/not-polled.xml");
+        insert_feed("https://example.com/unsubscribed.xml");
+
+        for (user_id, feed_id, is_active) in [
+            (UserId(1), active, true),
+            (UserId(2), active, true),
+            (UserId(1), paused, false),
+            (UserId(2), not_polled, true),
+        ] {
+            let sub = NewSubscription {
+                user_id,
+                feed_id,
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap();
+            if !is_active {
+                let update = PartialSubscription {
+                    is_active: Some(false),
+                    ..Default::default()
+                };
+                Subscription::update(&mut conn, sub.id, &update).unwrap();
+            }
+        }
+
+        let update = PartialFeed {
+            paused_at: Some(100),
+            ..Default::default()
+        };
+        Feed::update(&mut conn, not_polled, &update).unwrap();
+
+        assert_eq!(Feed::active_ids(&mut conn), Ok(vec![active]));
+    }
+
+    #[test]
+    fn test_summaries() {
+        let mut conn = get_test_db_connection();
+        let mut insert_feed = |url, title: &str| {
+            NewFeed {
+                url,
+                title: title.to_string(),
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap()
+            .id
+        };
+        let busy = insert_feed("https://example.com/busy.xml", "Busy");
+        let quiet = insert_feed("https://example.com/quiet.xml", "Quiet");
+
+        for (user_id, is_active) in [(UserId(1), true), (UserId(2), true), (UserId(3), false)] {
+            let sub = NewSubscription {
+                user_id,
+                feed_id: busy,
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap();
+            let update = PartialSubscription {
+                is_active: Some(is_active),
+                ..Default::default()
+            };
+            Subscription::update(&mut conn, sub.id, &update).unwrap();
+        }
+        for (title, pub_date) in [("Newest", 300), ("Oldest", 100), ("Middle", 200)] {
+            NewFeedItem {
+                feed_id: busy,
+                title,
+                link: "https://example.com/item",
+                pub_date,
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap();
+        }
+
+        let summaries = Feed::summaries(&mut conn).unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].feed.id, busy);
+        assert_eq!(summaries[0].subscriber_count, 2);
+        assert_eq!(summaries[0].latest_item_title.as_deref(), Some("Newest"));
+        assert_eq!(summaries[0].latest_item_date, Some(300));
+        assert_eq!(summaries[1].feed.id, quiet);
+        assert_eq!(summaries[1].subscriber_count, 0);
+        assert_eq!(summaries[1].latest_item_title, None);
+    }
+}
@@ -0,0 +1,190 @@
+use super::feed::Feed;
+use super::ids::FeedId;
+use crate::db::DbConnection;
+use crate::schema::*;
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    prelude::*,
+    serialize::{self, Output, ToSql},
+    sql_types::Integer,
+    AsExpression, FromSqlRow,
+};
+use serde::{Deserialize, Serialize};
+
+/// A change in what a feed says about itself, or where it's served from.
+/// The first value seen for each kind is also recorded, with no old value.
+#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations, PartialEq)]
+#[diesel(belongs_to(Feed))]
+#[diesel(table_name = feed_changes)]
+pub struct FeedChange {
+    pub id: i32,
+    pub feed_id: FeedId,
+    pub changed_at: i64,
+    pub kind: FeedChangeKind,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    /// whether admins were alerted, e.g. because the feed moved to
+    /// another host
+    pub significant: bool,
+}
+
+#[repr(i32)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, AsExpression, FromSqlRow)]
+#[diesel(sql_type=Integer)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedChangeKind {
+    /// the title in the feed itself, not any title an admin has set
+    Title,
+    /// the feed's `rel="self"` link
+    SelfLink,
+    /// where fetching the feed's URL ends up after redirects, None if it
+    /// isn't redirected
+    Redirect,
+}
+
+impl<DB> FromSql<Integer, DB> for FeedChangeKind
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(FeedChangeKind::Title),
+            1 => Ok(FeedChangeKind::SelfLink),
+            2 => Ok(FeedChangeKind::Redirect),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl<DB> ToSql<Integer, DB> for FeedChangeKind
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            FeedChangeKind::Title => 0.to_sql(out),
+            FeedChangeKind::SelfLink => 1.to_sql(out),
+            FeedChangeKind::Redirect => 2.to_sql(out),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = feed_changes)]
+pub struct NewFeedChange<'a> {
+    pub feed_id: FeedId,
+    pub changed_at: i64,
+    pub kind: FeedChangeKind,
+    pub old_value: Option<&'a str>,
+    pub new_value: Option<&'a str>,
+    pub significant: bool,
+}
+
+impl<'a> NewFeedChange<'a> {
+    pub fn insert(&self, conn: &mut DbConnection) -> Option<FeedChange> {
+        use crate::schema::feed_changes::dsl::*;
+        match diesel::insert_into(feed_changes)
+            .values(self)
+            .get_result(conn)
+        {
+            Ok(change) => Some(change),
+            Err(e) => {
+                log::warn!("Error recording feed change: {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+impl FeedChange {
+    /// The feed's most recent changes, newest first
+    pub fn get_for_feed(
+        conn: &mut DbConnection,
+        feed_id: FeedId,
+        limit: i64,
+    ) -> Result<Vec<FeedChange>, diesel::result::Error> {
+        use crate::schema::feed_changes::dsl::{feed_changes, feed_id as fid, id};
+        feed_changes
+            .filter(fid.eq(feed_id))
+            .order(id.desc())
+            .limit(limit)
+            .load::<FeedChange>(conn)
+    }
+
+    /// The last recorded change of this kind, which holds its current value
+    pub fn latest(
+        conn: &mut DbConnection,
+        feed_id: FeedId,
+        kind: FeedChangeKind,
+    ) -> Result<Option<FeedChange>, diesel::result::Error> {
+        use crate::schema::feed_changes::dsl::{feed_changes, feed_id as fid, id, kind as k};
+        feed_changes
+            .filter(fid.eq(feed_id))
+            .filter(k.eq(kind))
+            .order(id.desc())
+            .first::<FeedChange>(conn)
+            .optional()
+    }
+
+    pub fn delete_for_feed(
+        conn: &mut DbConnection,
+        feed_id: FeedId,
+    ) -> Result<usize, diesel::result::Error> {
+        use crate::schema::feed_changes::dsl::{feed_changes, feed_id as fid};
+        diesel::delete(feed_changes.filter(fid.eq(feed_id))).execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn record(conn: &mut DbConnection, kind: FeedChangeKind, new_value: &str) {
+        NewFeedChange {
+            feed_id: FeedId(1),
+            changed_at: 0,
+            kind,
+            old_value: None,
+            new_value: Some(new_value),
+            significant: false,
+        }
+        .insert(conn)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_latest() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(
+            FeedChange::latest(&mut conn, FeedId(1), FeedChangeKind::Title),
+            Ok(None)
+        );
+
+        record(&mut conn, FeedChangeKind::Title, "First");
+        record(
+            &mut conn,
+            FeedChangeKind::SelfLink,
+            "https://example.com/feed",
+        );
+        record(&mut conn, FeedChangeKind::Title, "Second");
+
+        let latest = FeedChange::latest(&mut conn, FeedId(1), FeedChangeKind::Title)
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest.new_value.as_deref(), Some("Second"));
+        assert_eq!(
+            FeedChange::get_for_feed(&mut conn, FeedId(1), 10)
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(
+            FeedChange::latest(&mut conn, FeedId(2), FeedChangeKind::Title),
+            Ok(None)
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use crate::security::{
+    secret_box::SecretBox,
+    validation::{Validate, ValidationErrors},
+};
+
+/// How to log in to a private feed, e.g. a Patreon, GitHub or company feed.
+/// Stored on the feed sealed with the instance's SecretBox, and never sent
+/// back by the API.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeedCredentials {
+    /// HTTP Basic auth
+    Basic { username: String, password: String },
+    /// sent as `Authorization: Bearer <token>`
+    Bearer { token: String },
+}
+
+impl FeedCredentials {
+    /// Sealed for storing, None if `MF_SECRET_KEY` isn't set
+    pub fn seal(&self) -> Option<String> {
+        SecretBox::global().map(|secret_box| self.seal_with(secret_box))
+    }
+
+    /// The credentials a feed was stored with, None if they were sealed with
+    /// another key
+    pub fn unseal(sealed: &str) -> Option<FeedCredentials> {
+        SecretBox::global().and_then(|secret_box| Self::unseal_with(secret_box, sealed))
+    }
+
+    fn seal_with(&self, secret_box: &SecretBox) -> String {
+        let json = serde_json::to_vec(self).expect("Credentials serialize to JSON");
+        secret_box.encrypt(&json)
+    }
+
+    fn unseal_with(secret_box: &SecretBox, sealed: &str) -> Option<FeedCredentials> {
+        let json = secret_box.decrypt(sealed)?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+/// Leaves out the password and token, so credentials can't leak into logs
+impl std::fmt::Debug for FeedCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeedCredentials::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &"[redacted]")
+                .finish(),
+            FeedCredentials::Bearer { .. } => f
+                .debug_struct("Bearer")
+                .field("token", &"[redacted]")
+                .finish(),
+        }
+    }
+}
+
+impl Validate for FeedCredentials {
+    fn check(&self, errors: &mut ValidationErrors) {
+        match self {
+            FeedCredentials::Basic { username, .. } => {
+                if username.is_empty() || username.contains(':') {
+                    errors.add("credentials.username", "Must not be empty or contain ':'");
+                }
+            }
+            FeedCredentials::Bearer { token } => {
+                if token.trim().is_empty() {
+                    errors.add("credentials.token", "Must not be empty");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip() {
+        let secret_box = SecretBox::new("test key");
+        let credentials = FeedCredentials::Basic {
+            username: "reader".to_string(),
+            password: "hunter2".to_string(),
+        };
+        let sealed = credentials.seal_with(&secret_box);
+        assert!(!sealed.contains("hunter2"));
+        assert_eq!(
+            FeedCredentials::unseal_with(&secret_box, &sealed),
+            Some(credentials)
+        );
+        let other_box = SecretBox::new("other key");
+        assert_eq!(FeedCredentials::unseal_with(&other_box, &sealed), None);
+    }
+
+    #[test]
+    fn test_debug_leaves_out_secrets() {
+        let bearer = FeedCredentials::Bearer {
+            token: "ghp_abc123".to_string(),
+        };
+        assert!(!format!("{:?}", bearer).contains("ghp_abc123"));
+        let basic = FeedCredentials::Basic {
+            username: "reader".to_string(),
+            password: "hunter2".to_string(),
+        };
+        let debug = format!("{:?}", basic);
+        assert!(debug.contains("reader"));
+        assert!(!debug.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let basic: FeedCredentials =
+            serde_json::from_str(r#"{"type": "basic", "username": "", "password": "x"}"#).unwrap();
+        assert!(basic.validate().is_err());
+        let bearer = FeedCredentials::Bearer {
+            token: "abc".to_string(),
+        };
+        assert!(bearer.validate().is_ok());
+    }
+}
@@ -1,34 +1,56 @@
-use super::feed::Feed;
+use std::collections::HashMap;
+
+use super::feed::{Feed, LinkMode};
+use super::ids::FeedId;
+use super::query_timing::timed;
+use crate::db::{with_backend, DbConnection};
 use crate::schema::*;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations, PartialEq)]
+/// Rows per INSERT, well under SQLite's limit on bound parameters
+const INSERT_BATCH_SIZE: usize = 100;
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations, PartialEq,
+)]
 #[diesel(belongs_to(Feed))]
 #[diesel(table_name = feed_items)]
 pub struct FeedItem {
     pub id: i32,
-    pub feed_id: i32,
+    pub feed_id: FeedId,
     pub title: String,
     pub link: String,
-    pub pub_date: i32,
+    /// from the feed, or when it was first seen if the feed didn't say
+    pub pub_date: i64,
     pub description: Option<String>,
     pub author: Option<String>,
+    /// discussion page for the item, e.g. on an aggregator
+    pub comments_link: Option<String>,
+    /// when the item was first fetched, which decides when it's sent
+    pub first_seen: i64,
+    /// lead image from an enclosure or Media RSS, see `tasks::item_media`
+    pub image_url: Option<String>,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize, Insertable)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Insertable)]
 #[diesel(table_name = feed_items)]
+// insert NULL rather than DEFAULT, which SQLite needs for multi-row inserts
+#[diesel(treat_none_as_default_value = false)]
 pub struct NewFeedItem<'a> {
-    pub feed_id: i32,
+    pub feed_id: FeedId,
     pub title: &'a str, // TODO: make optional
     pub link: &'a str,  // TODO: add link_title
-    pub pub_date: i32,
+    pub pub_date: i64,
     pub description: Option<&'a str>, // TODO: rename to summary
     pub author: Option<&'a str>,
+    pub comments_link: Option<&'a str>,
+    pub first_seen: i64,
+    pub image_url: Option<&'a str>,
 }
 
 impl<'a> NewFeedItem<'a> {
-    pub fn insert(&self, conn: &mut SqliteConnection) -> Option<FeedItem> {
+    pub fn insert(&self, conn: &mut DbConnection) -> Option<FeedItem> {
         use crate::schema::feed_items::dsl::*;
         match diesel::insert_into(feed_items)
             .values(self)
@@ -42,30 +64,67 @@ impl<'a> NewFeedItem<'a> {
         }
     }
 
-    pub fn insert_if_not_present(
-        &self,
-        conn: &mut SqliteConnection,
-    ) -> Result<Option<FeedItem>, diesel::result::Error> {
+    /// Insert the items in one transaction, several rows per statement,
+    /// skipping any already stored. Returns the items that were added.
+    pub fn insert_all(
+        conn: &mut DbConnection,
+        items: &[NewFeedItem],
+    ) -> Result<Vec<FeedItem>, diesel::result::Error> {
         use crate::schema::feed_items::dsl::*;
 
-        if FeedItem::has(conn, self) {
-            return Ok(None);
+        timed("feed_items_insert_all", || {
+            conn.transaction(|conn| {
+                let mut added = Vec::new();
+                for batch in items.chunks(INSERT_BATCH_SIZE) {
+                    let insert = diesel::insert_into(feed_items)
+                        .values(batch)
+                        .on_conflict_do_nothing();
+                    added.extend(with_backend!(conn, insert.get_results::<FeedItem>(conn))?);
+                }
+                Ok(added)
+            })
+        })
+    }
+}
+
+impl FeedItem {
+    /// The link to show for this item, and a comments link to show
+    /// alongside it if the mode asks for both
+    pub fn display_links(&self, mode: LinkMode) -> (&str, Option<&str>) {
+        let comments = self
+            .comments_link
+            .as_deref()
+            .filter(|comments| *comments != self.link);
+        match mode {
+            LinkMode::Comments => (comments.unwrap_or(&self.link), None),
+            LinkMode::Both => (&self.link, comments),
+            LinkMode::Auto | LinkMode::Link => (&self.link, None),
         }
-        match diesel::insert_into(feed_items)
-            .values(self)
-            .get_result(conn)
-        {
-            Ok(item) => Ok(Some(item)),
-            Err(e) => {
-                log::warn!("Error inserting feed item: {:?}", e);
-                Err(e)
+    }
+
+    /// The publish dates the feed's items with these links were stored
+    /// with, by link. If a link was stored more than once, the first is used.
+    pub fn pub_dates_by_link(
+        conn: &mut DbConnection,
+        feed_id: FeedId,
+        links: &[&str],
+    ) -> QueryResult<HashMap<String, i64>> {
+        let mut dates = HashMap::new();
+        for batch in links.chunks(INSERT_BATCH_SIZE) {
+            let stored: Vec<(String, i64)> = feed_items::table
+                .filter(feed_items::feed_id.eq(feed_id))
+                .filter(feed_items::link.eq_any(batch))
+                .order(feed_items::id)
+                .select((feed_items::link, feed_items::pub_date))
+                .load(conn)?;
+            for (link, pub_date) in stored {
+                dates.entry(link).or_insert(pub_date);
             }
         }
+        Ok(dates)
     }
-}
 
-impl FeedItem {
-    pub fn get_by_id(conn: &mut SqliteConnection, id: i32) -> Option<FeedItem> {
+    pub fn get_by_id(conn: &mut DbConnection, id: i32) -> Option<FeedItem> {
         use crate::schema::feed_items::dsl::feed_items;
         match feed_items.find(id).first::<FeedItem>(conn) {
             Ok(item) => Some(item),
@@ -76,7 +135,7 @@ impl FeedItem {
         }
     }
 
-    pub fn get_all(conn: &mut SqliteConnection) -> Option<Vec<FeedItem>> {
+    pub fn get_all(conn: &mut DbConnection) -> Option<Vec<FeedItem>> {
         use crate::schema::feed_items::dsl::feed_items;
         match feed_items.load::<FeedItem>(conn) {
             Ok(items) => match items.len() {
@@ -90,9 +149,11 @@ impl FeedItem {
         }
     }
 
-    pub fn get_by_feed(conn: &mut SqliteConnection, feed_id: i32) -> Option<Vec<FeedItem>> {
+    pub fn get_by_feed(conn: &mut DbConnection, feed_id: FeedId) -> Option<Vec<FeedItem>> {
         use crate::schema::feed_items::dsl::{feed_id as fid, feed_items};
-        match feed_items.filter(fid.eq(feed_id)).load::<FeedItem>(conn) {
+        match timed("feed_items_by_feed", || {
+            feed_items.filter(fid.eq(feed_id)).load::<FeedItem>(conn)
+        }) {
             Ok(items) => match items.len() {
                 0 => None,
                 _ => Some(items),
@@ -104,17 +165,17 @@ impl FeedItem {
         }
     }
 
-    pub fn items_after(
-        conn: &mut SqliteConnection,
-        feed_id: i32,
-        time_after: i32,
-    ) -> Vec<FeedItem> {
-        use crate::schema::feed_items::dsl::{feed_id as fid, feed_items, pub_date};
-        match feed_items
-            .filter(fid.eq(feed_id))
-            .filter(pub_date.gt(time_after))
-            .load::<FeedItem>(conn)
-        {
+    /// The feed's items first seen after the given time. Going by when they
+    /// were seen rather than published means items without a date, or
+    /// published before they showed up in the feed, are still sent.
+    pub fn items_after(conn: &mut DbConnection, feed_id: FeedId, time_after: i64) -> Vec<FeedItem> {
+        use crate::schema::feed_items::dsl::{feed_id as fid, feed_items, first_seen};
+        match timed("feed_items_after", || {
+            feed_items
+                .filter(fid.eq(feed_id))
+                .filter(first_seen.gt(time_after))
+                .load::<FeedItem>(conn)
+        }) {
             Ok(items) => items,
             Err(e) => {
                 log::warn!("Error getting feed items: {:?}", e);
@@ -123,14 +184,22 @@ impl FeedItem {
         }
     }
 
-    pub fn has(conn: &mut SqliteConnection, item: &NewFeedItem) -> bool {
-        use crate::schema::feed_items::dsl::{feed_id, feed_items, link, pub_date};
-        feed_items
-            .filter(feed_id.eq(item.feed_id))
-            .filter(link.eq(item.link))
-            .filter(pub_date.eq(item.pub_date))
-            .first::<FeedItem>(conn)
-            .is_ok()
+    /// The feed's newest items, newest first
+    pub fn latest(conn: &mut DbConnection, feed_id: FeedId, limit: i64) -> Vec<FeedItem> {
+        use crate::schema::feed_items::dsl::{feed_id as fid, feed_items, id, pub_date};
+        match timed("feed_items_latest", || {
+            feed_items
+                .filter(fid.eq(feed_id))
+                .order((pub_date.desc(), id.desc()))
+                .limit(limit)
+                .load::<FeedItem>(conn)
+        }) {
+            Ok(items) => items,
+            Err(e) => {
+                log::warn!("Error getting feed items: {:?}", e);
+                Vec::new()
+            }
+        }
     }
 }
 
@@ -139,7 +208,7 @@ mod tests {
     use super::*;
     use crate::test_helpers::test_helpers::get_test_db_connection;
 
-    fn insert_items(conn: &mut SqliteConnection, num_items: i32, feed_id: i32) -> Vec<FeedItem> {
+    fn insert_items(conn: &mut DbConnection, num_items: i32, feed_id: FeedId) -> Vec<FeedItem> {
         let mut inserted = Vec::new();
         for i in 0..num_items {
             let item = NewFeedItem {
@@ -160,14 +229,43 @@ mod tests {
     #[test]
     fn test_insert_feed_item() {
         let mut conn = get_test_db_connection();
-        let binding = insert_items(&mut conn, 1, 1);
+        let binding = insert_items(&mut conn, 1, FeedId(1));
         let item = binding.first().unwrap();
-        assert_eq!(item.feed_id, 1);
+        assert_eq!(item.feed_id, FeedId(1));
         assert_eq!(item.title, "test_title_0");
         assert_eq!(item.link, "http://test.com/0");
         assert_eq!(item.pub_date, 0);
         assert_eq!(item.description, None);
         assert_eq!(item.author, None);
+        assert_eq!(item.comments_link, None);
+    }
+
+    #[test]
+    fn test_display_links() {
+        let mut conn = get_test_db_connection();
+        let item = NewFeedItem {
+            feed_id: FeedId(1),
+            title: "test_title",
+            link: "http://test.com/article",
+            comments_link: Some("http://test.com/comments"),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let article = "http://test.com/article";
+        let comments = "http://test.com/comments";
+
+        assert_eq!(item.display_links(LinkMode::Link), (article, None));
+        assert_eq!(item.display_links(LinkMode::Comments), (comments, None));
+        assert_eq!(
+            item.display_links(LinkMode::Both),
+            (article, Some(comments))
+        );
+
+        let item = insert_items(&mut conn, 1, FeedId(1)).pop().unwrap();
+        let link = "http://test.com/0";
+        assert_eq!(item.display_links(LinkMode::Comments), (link, None));
+        assert_eq!(item.display_links(LinkMode::Both), (link, None));
     }
 
     #[test]
@@ -176,7 +274,7 @@ mod tests {
         let item = FeedItem::get_by_id(&mut conn, 1);
         assert_eq!(item, None);
 
-        insert_items(&mut conn, 3, 1);
+        insert_items(&mut conn, 3, FeedId(1));
         let item = FeedItem::get_by_id(&mut conn, -1);
         assert_eq!(item, None);
 
@@ -190,8 +288,8 @@ mod tests {
         let items = FeedItem::get_all(&mut conn);
         assert_eq!(items, None);
 
-        insert_items(&mut conn, 3, 1);
-        insert_items(&mut conn, 3, 2);
+        insert_items(&mut conn, 3, FeedId(1));
+        insert_items(&mut conn, 3, FeedId(2));
         let items = FeedItem::get_all(&mut conn);
         assert_eq!(items.unwrap().len(), 6);
     }
@@ -199,12 +297,116 @@ mod tests {
     #[test]
     fn test_get_by_feed() {
         let mut conn = get_test_db_connection();
-        let items = FeedItem::get_by_feed(&mut conn, 1);
+        let items = FeedItem::get_by_feed(&mut conn, FeedId(1));
         assert_eq!(items, None);
 
-        insert_items(&mut conn, 3, 1);
-        insert_items(&mut conn, 3, 2);
-        let items = FeedItem::get_by_feed(&mut conn, 1);
+        insert_items(&mut conn, 3, FeedId(1));
+        insert_items(&mut conn, 3, FeedId(2));
+        let items = FeedItem::get_by_feed(&mut conn, FeedId(1));
         assert_eq!(items.unwrap().len(), 3);
     }
+
+    #[test]
+    fn test_latest() {
+        let mut conn = get_test_db_connection();
+        let inserted = insert_items(&mut conn, 3, FeedId(1));
+        insert_items(&mut conn, 3, FeedId(2));
+        let items = FeedItem::latest(&mut conn, FeedId(1), 2);
+        let ids: Vec<_> = items.iter().map(|item| item.id).collect();
+        assert_eq!(ids, vec![inserted[2].id, inserted[1].id]);
+    }
+
+    #[test]
+    fn test_items_after_2038() {
+        let mut conn = get_test_db_connection();
+        // 2040-01-01, past the end of 32-bit unix time
+        let pub_date = 2_208_988_800;
+        NewFeedItem {
+            feed_id: FeedId(1),
+            title: "test_title",
+            link: "http://test.com/future",
+            pub_date,
+            first_seen: pub_date,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        insert_items(&mut conn, 1, FeedId(1));
+
+        let items = FeedItem::items_after(&mut conn, FeedId(1), i32::MAX as i64);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].pub_date, pub_date);
+    }
+
+    #[test]
+    fn test_items_after_uses_index() {
+        #[derive(QueryableByName)]
+        struct Plan {
+            #[diesel(sql_type = diesel::sql_types::Text)]
+            detail: String,
+        }
+
+        let mut conn = get_test_db_connection();
+        // Postgres's planner skips indexes on tables this small
+        if let DbConnection::Postgres(_) = conn {
+            return;
+        }
+        let plan = diesel::sql_query(
+            "EXPLAIN QUERY PLAN SELECT * FROM feed_items WHERE feed_id = 1 AND first_seen > 0",
+        )
+        .load::<Plan>(&mut conn)
+        .unwrap();
+        assert!(plan
+            .iter()
+            .any(|row| row.detail.contains("feed_items_feed_id_first_seen")));
+    }
+
+    #[test]
+    fn test_items_after_goes_by_first_seen() {
+        let mut conn = get_test_db_connection();
+        // published long ago but only just showed up in the feed
+        NewFeedItem {
+            feed_id: FeedId(1),
+            title: "test_title",
+            link: "http://test.com/backdated",
+            pub_date: 1_000,
+            first_seen: 2_000,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+
+        assert_eq!(FeedItem::items_after(&mut conn, FeedId(1), 1_500).len(), 1);
+        assert!(FeedItem::items_after(&mut conn, FeedId(1), 2_000).is_empty());
+    }
+
+    #[test]
+    fn test_insert_all_skips_stored_items() {
+        let mut conn = get_test_db_connection();
+        let links: Vec<String> = (0..250).map(|i| format!("http://test.com/{}", i)).collect();
+        let items: Vec<NewFeedItem> = links
+            .iter()
+            .map(|link| NewFeedItem {
+                feed_id: FeedId(1),
+                title: "title",
+                link,
+                ..Default::default()
+            })
+            .collect();
+
+        assert_eq!(
+            NewFeedItem::insert_all(&mut conn, &items[..200]).map(|added| added.len()),
+            Ok(200)
+        );
+        // only the new items are added, including duplicates within the batch
+        let mut next = items[150..].to_vec();
+        next.push(items[249].clone());
+        let added = NewFeedItem::insert_all(&mut conn, &next).unwrap();
+        assert_eq!(added.len(), 50);
+        assert_eq!(added[49].link, "http://test.com/249");
+        assert_eq!(
+            FeedItem::get_by_feed(&mut conn, FeedId(1)).unwrap().len(),
+            250
+        );
+    }
 }
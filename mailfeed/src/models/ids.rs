@@ -0,0 +1,97 @@
+use std::{fmt, num::ParseIntError, str::FromStr};
+
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::Integer,
+    AsExpression, FromSqlRow,
+};
+use serde::{Deserialize, Serialize};
+
+/// Declares an id newtype over a table's INTEGER primary key, so ids for
+/// different tables can't be mixed up. They're stored and serialized as
+/// plain integers.
+macro_rules! id_type {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(
+            Debug,
+            Default,
+            Clone,
+            Copy,
+            PartialEq,
+            Eq,
+            Hash,
+            PartialOrd,
+            Ord,
+            Serialize,
+            Deserialize,
+            AsExpression,
+            FromSqlRow,
+        )]
+        #[diesel(sql_type = Integer)]
+        #[serde(transparent)]
+        pub struct $name(pub i32);
+
+        impl<DB> FromSql<Integer, DB> for $name
+        where
+            DB: Backend,
+            i32: FromSql<Integer, DB>,
+        {
+            fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+                i32::from_sql(bytes).map($name)
+            }
+        }
+
+        impl<DB> ToSql<Integer, DB> for $name
+        where
+            DB: Backend,
+            i32: ToSql<Integer, DB>,
+        {
+            fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+                self.0.to_sql(out)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map($name)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+
+id_type!(UserId);
+id_type!(FeedId);
+id_type!(SubscriptionId);
+id_type!(WebhookId);
+id_type!(SavedSearchId);
+id_type!(AccessTokenId);
+id_type!(TemplateId);
+id_type!(ShareLinkId);
+id_type!(TagId);
+id_type!(SessionId);
+id_type!(AuditEntryId);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_are_plain_integers() {
+        assert_eq!("42".parse::<UserId>(), Ok(UserId(42)));
+        assert!("abc".parse::<FeedId>().is_err());
+        assert_eq!(serde_json::to_string(&SubscriptionId(7)).unwrap(), "7");
+        assert_eq!(serde_json::from_str::<UserId>("3").unwrap(), UserId(3));
+        assert_eq!(FeedId(9).to_string(), "9");
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use super::settings::{self, NewSetting, Setting};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+const MAX_ITEMS_PER_FETCH: &str = "ingest.max_items_per_fetch";
+const NEW_ITEMS_ALARM: &str = "ingest.new_items_alarm";
+
+/// Most items per fetch an admin may allow
+const MAX_ITEMS_PER_FETCH_LIMIT: u32 = 10_000;
+
+/// How many items are taken from each fetch of a feed, stored as system
+/// settings, so a misbehaving feed can't stall the monitor
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IngestLimits {
+    /// the newest items are kept, the rest are skipped
+    pub max_items_per_fetch: u32,
+    /// log a warning when a fetch adds more new items than this, 0 for never
+    pub new_items_alarm: u32,
+}
+
+impl Default for IngestLimits {
+    fn default() -> Self {
+        IngestLimits {
+            max_items_per_fetch: 500,
+            new_items_alarm: 200,
+        }
+    }
+}
+
+impl IngestLimits {
+    /// The limits, with defaults for anything not set
+    pub fn load(conn: &mut DbConnection) -> IngestLimits {
+        let default = IngestLimits::default();
+        let mut get = |key| {
+            Setting::get(conn, key, None)
+                .ok()
+                .and_then(|setting| setting.value.parse::<u32>().ok())
+        };
+        IngestLimits {
+            max_items_per_fetch: get(MAX_ITEMS_PER_FETCH).unwrap_or(default.max_items_per_fetch),
+            new_items_alarm: get(NEW_ITEMS_ALARM).unwrap_or(default.new_items_alarm),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        for (key, value) in [
+            (MAX_ITEMS_PER_FETCH, self.max_items_per_fetch),
+            (NEW_ITEMS_ALARM, self.new_items_alarm),
+        ] {
+            let setting = NewSetting {
+                user_id: None,
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// Whether this many new items from one fetch is suspicious
+    pub fn is_alarming(&self, new_items: usize) -> bool {
+        self.new_items_alarm > 0 && new_items > self.new_items_alarm as usize
+    }
+}
+
+impl Validate for IngestLimits {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.max_items_per_fetch == 0 || self.max_items_per_fetch > MAX_ITEMS_PER_FETCH_LIMIT {
+            errors.add(
+                "max_items_per_fetch",
+                format!("Must be between 1 and {}", MAX_ITEMS_PER_FETCH_LIMIT),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(IngestLimits::load(&mut conn), IngestLimits::default());
+
+        let limits = IngestLimits {
+            max_items_per_fetch: 50,
+            new_items_alarm: 0,
+        };
+        limits.save(&mut conn).unwrap();
+        assert_eq!(IngestLimits::load(&mut conn), limits);
+        assert!(!limits.is_alarming(1000));
+    }
+
+    #[test]
+    fn test_validate() {
+        let limits = IngestLimits {
+            max_items_per_fetch: 0,
+            ..Default::default()
+        };
+        assert!(limits.validate().is_err());
+        assert!(IngestLimits::default().validate().is_ok());
+        assert!(IngestLimits::default().is_alarming(201));
+        assert!(!IngestLimits::default().is_alarming(200));
+    }
+}
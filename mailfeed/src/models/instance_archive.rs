@@ -0,0 +1,448 @@
+use diesel::{connection::SimpleConnection, prelude::*};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    feed::Feed,
+    ids::{AccessTokenId, SubscriptionId, TagId, UserId},
+    role::Roles,
+    saved_search::SavedSearch,
+    settings::{Error as SettingError, NewSetting, Setting},
+    subscription::Subscription,
+    subscription_template::SubscriptionTemplate,
+    tag::Tag,
+    user::User,
+};
+use crate::db::{with_backend, DbConnection};
+use crate::schema::*;
+
+/// Version of the archive format, bumped if it changes incompatibly
+pub const ARCHIVE_VERSION: u32 = 1;
+/// Each instance keeps its own, so it isn't archived
+const JWT_SECRET_KEY: &str = "jwt_secret";
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Unsupported archive version {0}, expected {}", ARCHIVE_VERSION)]
+    Version(u32),
+    #[error("This instance already has users, feeds or subscriptions")]
+    NotEmpty,
+    #[error("Error saving setting: {0}")]
+    Setting(#[from] SettingError),
+    #[error("Database error: {0}")]
+    Database(#[from] diesel::result::Error),
+}
+
+/// Everything needed to move an instance to another host: users with their
+/// password hashes, two-factor secrets and access tokens, feeds,
+/// subscriptions with their tags, templates, saved searches and settings,
+/// keeping their IDs. Two-factor secrets stay encrypted, so the new host
+/// needs the same `MF_SECRET_KEY`. Feed items, with users' read and starred
+/// marks and share links, delivery history, sessions, webhooks, invites and
+/// private feeds' credentials aren't included; feeds are fetched again after
+/// importing.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstanceArchive {
+    pub version: u32,
+    pub exported_at: i64,
+    pub users: Vec<ArchivedUser>,
+    pub feeds: Vec<Feed>,
+    pub subscriptions: Vec<Subscription>,
+    pub settings: Vec<NewSetting>,
+    // missing from archives made before they were added
+    #[serde(default)]
+    pub two_factor: Vec<ArchivedTwoFactor>,
+    #[serde(default)]
+    pub recovery_codes: Vec<ArchivedRecoveryCode>,
+    #[serde(default)]
+    pub access_tokens: Vec<ArchivedAccessToken>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub subscription_tags: Vec<ArchivedSubscriptionTag>,
+    #[serde(default)]
+    pub templates: Vec<SubscriptionTemplate>,
+    #[serde(default)]
+    pub saved_searches: Vec<SavedSearch>,
+}
+
+/// A user as stored, including their password hash but not their session
+#[derive(Debug, Serialize, Deserialize, Insertable, PartialEq)]
+#[diesel(table_name = users)]
+pub struct ArchivedUser {
+    pub id: UserId,
+    pub login_email: String,
+    pub send_email: String,
+    /// the argon2 hash
+    pub password: String,
+    pub created_at: i64,
+    pub is_active: bool,
+    pub daily_send_time: String,
+    pub role: Roles,
+    pub item_truncate_length: i32,
+    pub must_change_password: bool,
+    pub from_name: Option<String>,
+    pub subject_template: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub last_login_at: Option<i64>,
+    #[serde(default)]
+    pub pending_approval: bool,
+}
+
+/// A user's two-factor secret, still encrypted with the instance's
+/// `MF_SECRET_KEY`
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, PartialEq)]
+#[diesel(table_name = two_factor)]
+pub struct ArchivedTwoFactor {
+    pub user_id: UserId,
+    pub secret: String,
+    pub enabled: bool,
+    pub last_used_step: i64,
+    pub created_at: i64,
+}
+
+/// An unused two-factor recovery code, hashed
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, PartialEq)]
+#[diesel(table_name = recovery_codes)]
+pub struct ArchivedRecoveryCode {
+    pub id: i32,
+    pub user_id: UserId,
+    pub code_hash: String,
+}
+
+/// A personal access token, hashed, so existing scripts keep working
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, PartialEq)]
+#[diesel(table_name = personal_access_tokens)]
+pub struct ArchivedAccessToken {
+    pub id: AccessTokenId,
+    pub user_id: UserId,
+    pub name: String,
+    pub token_hash: String,
+    pub created_at: i64,
+    pub last_used_at: i64,
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, PartialEq)]
+#[diesel(table_name = subscription_tags)]
+pub struct ArchivedSubscriptionTag {
+    pub subscription_id: SubscriptionId,
+    pub tag_id: TagId,
+}
+
+impl From<User> for ArchivedUser {
+    fn from(user: User) -> Self {
+        ArchivedUser {
+            id: user.id,
+            login_email: user.login_email,
+            send_email: user.send_email,
+            password: user.password,
+            created_at: user.created_at,
+            is_active: user.is_active,
+            daily_send_time: user.daily_send_time,
+            role: user.role,
+            item_truncate_length: user.item_truncate_length,
+            must_change_password: user.must_change_password,
+            from_name: user.from_name,
+            subject_template: user.subject_template,
+            timezone: user.timezone,
+            last_login_at: user.last_login_at,
+            pending_approval: user.pending_approval,
+        }
+    }
+}
+
+impl InstanceArchive {
+    pub fn export(conn: &mut DbConnection, now: i64) -> QueryResult<InstanceArchive> {
+        let users = users::table
+            .order(users::id)
+            .load::<User>(conn)?
+            .into_iter()
+            .map(ArchivedUser::from)
+            .collect();
+        let feeds = feeds::table.order(feeds::id).load::<Feed>(conn)?;
+        let subscriptions = subscriptions::table
+            .order(subscriptions::id)
+            .load::<Subscription>(conn)?;
+        let settings = settings::table
+            .filter(settings::key.ne(JWT_SECRET_KEY))
+            .order(settings::id)
+            .load::<Setting>(conn)?
+            .into_iter()
+            .map(|setting| NewSetting {
+                user_id: setting.user_id,
+                key: setting.key,
+                value: setting.value,
+            })
+            .collect();
+
+        Ok(InstanceArchive {
+            version: ARCHIVE_VERSION,
+            exported_at: now,
+            users,
+            feeds,
+            subscriptions,
+            settings,
+            two_factor: two_factor::table.order(two_factor::user_id).load(conn)?,
+            recovery_codes: recovery_codes::table.order(recovery_codes::id).load(conn)?,
+            access_tokens: personal_access_tokens::table
+                .order(personal_access_tokens::id)
+                .load(conn)?,
+            tags: tags::table.order(tags::id).load(conn)?,
+            subscription_tags: subscription_tags::table
+                .order((
+                    subscription_tags::subscription_id,
+                    subscription_tags::tag_id,
+                ))
+                .load(conn)?,
+            templates: subscription_templates::table
+                .order(subscription_templates::id)
+                .load(conn)?,
+            saved_searches: saved_searches::table.order(saved_searches::id).load(conn)?,
+        })
+    }
+
+    /// Load the archive into a new instance, all or nothing. Settings
+    /// replace the instance's own, but users, feeds and subscriptions keep
+    /// their IDs so there mustn't be any yet.
+    pub fn import(&self, conn: &mut DbConnection) -> Result<(), ArchiveError> {
+        if self.version != ARCHIVE_VERSION {
+            return Err(ArchiveError::Version(self.version));
+        }
+
+        conn.transaction(|conn| {
+            let existing: i64 = users::table.count().get_result::<i64>(conn)?
+                + feeds::table.count().get_result::<i64>(conn)?
+                + subscriptions::table.count().get_result::<i64>(conn)?;
+            if existing > 0 {
+                return Err(ArchiveError::NotEmpty);
+            }
+
+            let insert = diesel::insert_into(users::table).values(&self.users);
+            with_backend!(conn, insert.execute(conn))?;
+            for feed in &self.feeds {
+                // forget the last fetch so the new instance fetches the
+                // whole feed, since its items weren't archived
+                diesel::insert_into(feeds::table)
+                    .values(feed)
+                    .execute(conn)?;
+                diesel::update(feeds::table.find(feed.id))
+                    .set((
+                        feeds::last_checked.eq(0),
+                        feeds::body_hash.eq(None::<String>),
+                        feeds::etag.eq(None::<String>),
+                        feeds::last_modified.eq(None::<String>),
+                    ))
+                    .execute(conn)?;
+            }
+            let insert = diesel::insert_into(subscriptions::table).values(&self.subscriptions);
+            with_backend!(conn, insert.execute(conn))?;
+            let insert = diesel::insert_into(two_factor::table).values(&self.two_factor);
+            with_backend!(conn, insert.execute(conn))?;
+            let insert = diesel::insert_into(recovery_codes::table).values(&self.recovery_codes);
+            with_backend!(conn, insert.execute(conn))?;
+            let insert =
+                diesel::insert_into(personal_access_tokens::table).values(&self.access_tokens);
+            with_backend!(conn, insert.execute(conn))?;
+            let insert = diesel::insert_into(tags::table).values(&self.tags);
+            with_backend!(conn, insert.execute(conn))?;
+            let insert =
+                diesel::insert_into(subscription_tags::table).values(&self.subscription_tags);
+            with_backend!(conn, insert.execute(conn))?;
+            let insert = diesel::insert_into(subscription_templates::table).values(&self.templates);
+            with_backend!(conn, insert.execute(conn))?;
+            let insert = diesel::insert_into(saved_searches::table).values(&self.saved_searches);
+            with_backend!(conn, insert.execute(conn))?;
+            for setting in self
+                .settings
+                .iter()
+                .filter(|setting| setting.key != JWT_SECRET_KEY)
+            {
+                Setting::set(conn, setting)?;
+            }
+            if let DbConnection::Postgres(_) = conn {
+                // the rows kept their ids, so the sequences have to start
+                // past them
+                for table in [
+                    "users",
+                    "feeds",
+                    "subscriptions",
+                    "recovery_codes",
+                    "personal_access_tokens",
+                    "tags",
+                    "subscription_templates",
+                    "saved_searches",
+                    "settings",
+                ] {
+                    conn.batch_execute(&format!(
+                        "SELECT setval(pg_get_serial_sequence('{0}', 'id'), \
+                         COALESCE(MAX(id), 0) + 1, false) FROM {0}",
+                        table
+                    ))?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::{
+        claims::Claims,
+        models::{
+            feed::NewFeed,
+            personal_access_token::{NewPersonalAccessToken, PersonalAccessToken},
+            role::Role,
+            subscription::NewSubscription,
+            two_factor::TwoFactor,
+            user::NewUser,
+        },
+        security::{secret_box::SecretBox, totp},
+        test_helpers::test_helpers::get_test_db_connection,
+    };
+
+    fn admin_claims() -> Claims {
+        Claims {
+            sub: UserId(0),
+            email: "system@mailfeed".to_string(),
+            role: Role::Admin.into(),
+            exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
+        }
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let mut conn = get_test_db_connection();
+        let claims = admin_claims();
+        let new_user = NewUser {
+            email: "test@example.com".to_string(),
+            password: "correct horse".to_string(),
+        };
+        let user = User::create(&mut conn, &new_user, claims).unwrap();
+        let feed = NewFeed {
+            url: "https://example.com/feed",
+            title: "Example".to_string(),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        diesel::update(feeds::table.find(feed.id))
+            .set((feeds::last_checked.eq(1000), feeds::etag.eq("\"abc\"")))
+            .execute(&mut conn)
+            .unwrap();
+        NewSubscription {
+            user_id: user.id,
+            feed_id: feed.id,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        for (user_id, key) in [(None, JWT_SECRET_KEY), (Some(user.id), "trends.in_digest")] {
+            let setting = NewSetting {
+                user_id,
+                key: key.to_string(),
+                value: "value".to_string(),
+            };
+            Setting::set(&mut conn, &setting).unwrap();
+        }
+
+        let archive = InstanceArchive::export(&mut conn, 2000).unwrap();
+        assert_eq!(archive.users.len(), 1);
+        assert_eq!(archive.users[0].password, user.password);
+        assert_eq!(archive.settings.len(), 1);
+        // through JSON, as it's written to the file
+        let archive: InstanceArchive =
+            serde_json::from_str(&serde_json::to_string(&archive).unwrap()).unwrap();
+
+        let mut new_conn = get_test_db_connection();
+        archive.import(&mut new_conn).unwrap();
+        let imported = InstanceArchive::export(&mut new_conn, 2000).unwrap();
+        assert_eq!(imported.users, archive.users);
+        assert_eq!(imported.subscriptions.len(), 1);
+        assert_eq!(imported.subscriptions[0].user_id, user.id);
+        assert_eq!(imported.feeds[0].title, "Example");
+        assert_eq!(imported.feeds[0].last_checked, 0);
+        assert_eq!(imported.feeds[0].etag, None);
+        assert_eq!(
+            Setting::get(&mut new_conn, "trends.in_digest", Some(user.id))
+                .unwrap()
+                .value,
+            "value"
+        );
+        assert!(Setting::get(&mut new_conn, JWT_SECRET_KEY, None).is_err());
+
+        assert!(matches!(
+            archive.import(&mut new_conn),
+            Err(ArchiveError::NotEmpty)
+        ));
+    }
+
+    #[test]
+    fn test_two_factor_tags_and_tokens_round_trip() {
+        let mut conn = get_test_db_connection();
+        let new_user = NewUser {
+            email: "test@example.com".to_string(),
+            password: "correct horse".to_string(),
+        };
+        let user = User::create(&mut conn, &new_user, admin_claims()).unwrap();
+        let feed = NewFeed {
+            url: "https://example.com/feed",
+            title: "Example".to_string(),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let sub = NewSubscription {
+            user_id: user.id,
+            feed_id: feed.id,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        Tag::set_for_subscription(&mut conn, user.id, sub.id, &["news".to_string()], 1000).unwrap();
+
+        let secret_box = SecretBox::new("test key");
+        let secret = totp::generate_secret();
+        let codes = TwoFactor::enroll(&mut conn, user.id, secret_box.encrypt(&secret), 1000)
+            .unwrap()
+            .enable(&mut conn, 1)
+            .unwrap();
+        let (_, token) = NewPersonalAccessToken {
+            user_id: user.id,
+            name: "backup script".to_string(),
+            token_hash: String::new(),
+            created_at: 1000,
+            expires_at: None,
+        }
+        .insert(&mut conn)
+        .unwrap();
+
+        let archive = InstanceArchive::export(&mut conn, 2000).unwrap();
+        let archive: InstanceArchive =
+            serde_json::from_str(&serde_json::to_string(&archive).unwrap()).unwrap();
+        let mut new_conn = get_test_db_connection();
+        archive.import(&mut new_conn).unwrap();
+
+        // the same MF_SECRET_KEY still decrypts the secret
+        let two_factor = TwoFactor::get(&mut new_conn, user.id).unwrap().unwrap();
+        assert!(two_factor.enabled);
+        assert_eq!(secret_box.decrypt(&two_factor.secret), Some(secret));
+        assert_eq!(
+            two_factor.redeem(&mut new_conn, &secret_box, &codes[0], 3000),
+            Ok(true)
+        );
+        let authenticated = PersonalAccessToken::authenticate(&mut new_conn, &token, 3000);
+        assert_eq!(authenticated.unwrap().unwrap().user_id, user.id);
+        let tags = Tag::get_for_subscription(&mut new_conn, sub.id).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].name, "news");
+    }
+}
@@ -0,0 +1,139 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::ids::UserId;
+use crate::db::DbConnection;
+use crate::schema::*;
+use crate::security::{
+    tokens::{hash_token, random_token},
+    validation::{Validate, ValidationErrors},
+};
+
+/// How long an invite link works
+pub const INVITE_LIFETIME_SECONDS: i64 = 7 * 24 * 60 * 60;
+const TOKEN_LENGTH: usize = 48;
+
+/// A single-use link an admin hands to someone, so they can create their
+/// account with a password of their own. Only the token's hash is stored.
+#[derive(Debug, Serialize, Queryable, Identifiable, PartialEq)]
+#[diesel(table_name = invites)]
+pub struct Invite {
+    pub id: i32,
+    /// the login email the account is created with
+    pub email: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    /// the admin who sent the invite
+    pub created_by: UserId,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = invites)]
+pub struct NewInvite {
+    pub email: String,
+    #[serde(skip_deserializing)]
+    pub token_hash: String,
+    #[serde(skip_deserializing)]
+    pub created_by: UserId,
+    #[serde(skip_deserializing)]
+    pub created_at: i64,
+    #[serde(skip_deserializing)]
+    pub expires_at: i64,
+}
+
+impl Validate for NewInvite {
+    fn check(&self, errors: &mut ValidationErrors) {
+        errors.email("email", &self.email);
+    }
+}
+
+impl NewInvite {
+    /// Store the invite, replacing any earlier one for the same email, and
+    /// return it along with the token for the link, which can't be
+    /// recovered later
+    pub fn insert(mut self, conn: &mut DbConnection) -> QueryResult<(Invite, String)> {
+        use crate::schema::invites::dsl::*;
+        let token = random_token(TOKEN_LENGTH);
+        self.token_hash = hash_token(&token);
+        self.expires_at = self.created_at + INVITE_LIFETIME_SECONDS;
+        let invite = conn.transaction(|conn| {
+            diesel::delete(
+                invites.filter(email.eq(&self.email).or(expires_at.le(self.created_at))),
+            )
+            .execute(conn)?;
+            diesel::insert_into(invites).values(&self).get_result(conn)
+        })?;
+        Ok((invite, token))
+    }
+}
+
+impl Invite {
+    /// The invite the token is for, if it hasn't expired or been used
+    pub fn find(conn: &mut DbConnection, token: &str, now: i64) -> QueryResult<Option<Invite>> {
+        use crate::schema::invites::dsl::*;
+        invites
+            .filter(token_hash.eq(hash_token(token)))
+            .filter(expires_at.gt(now))
+            .first(conn)
+            .optional()
+    }
+
+    /// Use up the invites for an email once its account exists
+    pub fn delete_for_email(conn: &mut DbConnection, invited: &str) -> QueryResult<usize> {
+        use crate::schema::invites::dsl::*;
+        diesel::delete(invites.filter(email.eq(invited))).execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn invite(conn: &mut DbConnection, to: &str, now: i64) -> String {
+        let new_invite = NewInvite {
+            email: to.to_string(),
+            token_hash: String::new(),
+            created_by: UserId(1),
+            created_at: now,
+            expires_at: 0,
+        };
+        new_invite.insert(conn).unwrap().1
+    }
+
+    #[test]
+    fn test_invite_and_find() {
+        let mut conn = get_test_db_connection();
+        let token = invite(&mut conn, "new@example.com", 1000);
+        assert_eq!(token.len(), TOKEN_LENGTH);
+
+        assert_eq!(Invite::find(&mut conn, "wrong", 1100), Ok(None));
+        let found = Invite::find(&mut conn, &token, 1100).unwrap().unwrap();
+        assert_eq!(found.email, "new@example.com");
+        assert_eq!(found.expires_at, 1000 + INVITE_LIFETIME_SECONDS);
+        assert_eq!(
+            Invite::find(&mut conn, &token, 1000 + INVITE_LIFETIME_SECONDS),
+            Ok(None)
+        );
+
+        // single use
+        assert_eq!(
+            Invite::delete_for_email(&mut conn, "new@example.com"),
+            Ok(1)
+        );
+        assert_eq!(Invite::find(&mut conn, &token, 1100), Ok(None));
+    }
+
+    #[test]
+    fn test_new_invite_replaces_old() {
+        let mut conn = get_test_db_connection();
+        let first = invite(&mut conn, "new@example.com", 1000);
+        let other = invite(&mut conn, "other@example.com", 1000);
+        let second = invite(&mut conn, "new@example.com", 2000);
+        assert_eq!(Invite::find(&mut conn, &first, 2100), Ok(None));
+        assert!(Invite::find(&mut conn, &second, 2100).unwrap().is_some());
+        assert!(Invite::find(&mut conn, &other, 2100).unwrap().is_some());
+    }
+}
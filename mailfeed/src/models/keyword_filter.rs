@@ -0,0 +1,254 @@
+use std::fmt;
+
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, FromSqlRow},
+    sql_types::Text,
+    AsExpression,
+};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::search_query::words;
+use crate::db::to_sql_as_text;
+
+/// Most keywords in each of a subscription's lists
+pub const MAX_KEYWORDS: usize = 50;
+const MAX_KEYWORD_LENGTH: usize = 200;
+/// Compiled size limit for regex keywords, so one can't use up memory
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum KeywordError {
+    #[error("At most {} keywords are allowed", MAX_KEYWORDS)]
+    TooMany,
+    #[error("Keywords must not be empty")]
+    Empty,
+    #[error("Keywords must be at most {} characters", MAX_KEYWORD_LENGTH)]
+    TooLong,
+    #[error("Keywords must be on one line")]
+    Multiline,
+    #[error("Invalid regex {0}: {1}")]
+    InvalidRegex(String, String),
+}
+
+/// A subscription's include or exclude keywords, stored one per line and
+/// serialized as a list
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+#[serde(transparent)]
+pub struct Keywords(pub Vec<String>);
+
+impl Keywords {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The keywords with surrounding whitespace trimmed, as they're stored
+    pub fn trimmed(&self) -> Self {
+        Keywords(self.0.iter().map(|k| k.trim().to_string()).collect())
+    }
+
+    pub fn validate(&self) -> Result<(), KeywordError> {
+        parse_all(self).map(|_| ())
+    }
+}
+
+impl fmt::Display for Keywords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.join("\n"))
+    }
+}
+
+impl<DB> FromSql<Text, DB> for Keywords
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+        Ok(Keywords(
+            String::from_sql(bytes)?
+                .lines()
+                .filter(|keyword| !keyword.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ))
+    }
+}
+
+to_sql_as_text!(Keywords, |keywords| keywords.to_string());
+
+/// One keyword: words or a phrase matched case-insensitively by whole
+/// word, or a case-insensitive regex when written like `/pattern/`
+#[derive(Debug, Clone)]
+enum Keyword {
+    Words(Vec<String>),
+    Regex(Regex),
+}
+
+impl Keyword {
+    fn parse(keyword: &str) -> Result<Keyword, KeywordError> {
+        if keyword.trim().is_empty() {
+            return Err(KeywordError::Empty);
+        }
+        if keyword.chars().count() > MAX_KEYWORD_LENGTH {
+            return Err(KeywordError::TooLong);
+        }
+        if keyword.contains('\n') {
+            return Err(KeywordError::Multiline);
+        }
+        let keyword = keyword.trim();
+        if let Some(pattern) = keyword
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+            .filter(|pattern| !pattern.is_empty())
+        {
+            return RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .size_limit(REGEX_SIZE_LIMIT)
+                .build()
+                .map(Keyword::Regex)
+                .map_err(|e| KeywordError::InvalidRegex(keyword.to_string(), e.to_string()));
+        }
+        match words(keyword) {
+            words if words.is_empty() => Err(KeywordError::Empty),
+            words => Ok(Keyword::Words(words)),
+        }
+    }
+
+    fn matches(&self, text: &str, text_words: &[String]) -> bool {
+        match self {
+            Keyword::Words(words) => text_words
+                .windows(words.len())
+                .any(|window| window == words.as_slice()),
+            Keyword::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// Why an item didn't pass a subscription's keyword filters
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeywordMiss {
+    /// it matched this exclude keyword
+    Excluded(String),
+    /// it didn't match any include keyword
+    NotIncluded,
+}
+
+/// A subscription's keyword filters. Items must match at least one include
+/// keyword, if there are any, and none of the exclude keywords.
+#[derive(Debug, Clone, Default)]
+pub struct KeywordFilter {
+    include: Vec<Keyword>,
+    exclude: Vec<(String, Keyword)>,
+}
+
+impl KeywordFilter {
+    pub fn parse(include: &Keywords, exclude: &Keywords) -> Result<Self, KeywordError> {
+        Ok(KeywordFilter {
+            include: parse_all(include)?
+                .into_iter()
+                .map(|(_, keyword)| keyword)
+                .collect(),
+            exclude: parse_all(exclude)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Check an item's plain text (title and description), None if it
+    /// passes
+    pub fn check(&self, text: &str) -> Option<KeywordMiss> {
+        if self.is_empty() {
+            return None;
+        }
+        let text_words = words(text);
+        if let Some((source, _)) = self
+            .exclude
+            .iter()
+            .find(|(_, keyword)| keyword.matches(text, &text_words))
+        {
+            return Some(KeywordMiss::Excluded(source.clone()));
+        }
+        if !self.include.is_empty()
+            && !self
+                .include
+                .iter()
+                .any(|keyword| keyword.matches(text, &text_words))
+        {
+            return Some(KeywordMiss::NotIncluded);
+        }
+        None
+    }
+}
+
+fn parse_all(keywords: &Keywords) -> Result<Vec<(String, Keyword)>, KeywordError> {
+    if keywords.0.len() > MAX_KEYWORDS {
+        return Err(KeywordError::TooMany);
+    }
+    keywords
+        .0
+        .iter()
+        .map(|source| Keyword::parse(source).map(|keyword| (source.clone(), keyword)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keywords(list: &[&str]) -> Keywords {
+        Keywords(list.iter().map(|k| k.to_string()).collect())
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let filter = KeywordFilter::parse(
+            &keywords(&["rust", "web assembly"]),
+            &keywords(&["job posting", "/\\bhiring\\b/"]),
+        )
+        .unwrap();
+
+        assert_eq!(filter.check("Async Rust in 2026"), None);
+        assert_eq!(filter.check("Shipping WEB Assembly today"), None);
+        // whole words only
+        assert_eq!(filter.check("Trusty tools"), Some(KeywordMiss::NotIncluded));
+        assert_eq!(
+            filter.check("assembly of the web"),
+            Some(KeywordMiss::NotIncluded)
+        );
+        assert_eq!(
+            filter.check("Rust job posting"),
+            Some(KeywordMiss::Excluded("job posting".to_string()))
+        );
+        assert_eq!(
+            filter.check("Rust team is HIRING"),
+            Some(KeywordMiss::Excluded("/\\bhiring\\b/".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_exclude_only() {
+        let filter = KeywordFilter::parse(&Keywords::default(), &keywords(&["crypto"])).unwrap();
+        assert_eq!(filter.check("Anything else"), None);
+        assert!(filter.check("Crypto news").is_some());
+        assert!(KeywordFilter::default().check("anything").is_none());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let parse = |list: &[&str]| KeywordFilter::parse(&keywords(list), &Keywords::default());
+        assert_eq!(parse(&[" "]).unwrap_err(), KeywordError::Empty);
+        assert_eq!(parse(&["--"]).unwrap_err(), KeywordError::Empty);
+        assert_eq!(parse(&["a\nb"]).unwrap_err(), KeywordError::Multiline);
+        assert!(matches!(
+            parse(&["/(unclosed/"]).unwrap_err(),
+            KeywordError::InvalidRegex(..)
+        ));
+        let many: Vec<&str> = vec!["rust"; MAX_KEYWORDS + 1];
+        assert_eq!(parse(&many).unwrap_err(), KeywordError::TooMany);
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use super::settings::{self, NewSetting, Setting};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+const ENABLED: &str = "maintenance_mode.enabled";
+const MESSAGE: &str = "maintenance_mode.message";
+
+/// Shown to users when the admin didn't give a message
+pub const DEFAULT_MESSAGE: &str =
+    "Mailfeed is down for maintenance and will be back shortly. Your feeds are safe.";
+const MAX_MESSAGE_LENGTH: usize = 500;
+
+/// Whether the instance is in maintenance mode, stored as system settings.
+/// While it is, the API turns away everyone but admins and the background
+/// senders pause, e.g. during backups and migrations.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MaintenanceMode {
+    pub enabled: bool,
+    /// for users, empty for the default
+    #[serde(default)]
+    pub message: String,
+}
+
+impl MaintenanceMode {
+    /// The current mode, off if it was never set
+    pub fn load(conn: &mut DbConnection) -> MaintenanceMode {
+        let mut get = |key| {
+            Setting::get(conn, key, None)
+                .ok()
+                .map(|setting| setting.value)
+        };
+        MaintenanceMode {
+            enabled: get(ENABLED).is_some_and(|value| value == "true"),
+            message: get(MESSAGE).unwrap_or_default(),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        for (key, value) in [
+            (ENABLED, self.enabled.to_string()),
+            (MESSAGE, self.message.trim().to_string()),
+        ] {
+            let setting = NewSetting {
+                user_id: None,
+                key: key.to_string(),
+                value,
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// The message to show users
+    pub fn message(&self) -> &str {
+        match self.message.trim() {
+            "" => DEFAULT_MESSAGE,
+            message => message,
+        }
+    }
+}
+
+impl Validate for MaintenanceMode {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.message.trim().chars().count() > MAX_MESSAGE_LENGTH {
+            errors.add(
+                "message",
+                format!("At most {} characters", MAX_MESSAGE_LENGTH),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        let mode = MaintenanceMode::load(&mut conn);
+        assert!(!mode.enabled);
+        assert_eq!(mode.message(), DEFAULT_MESSAGE);
+
+        let mode = MaintenanceMode {
+            enabled: true,
+            message: " Back at 10:00 UTC ".to_string(),
+        };
+        assert!(mode.validate().is_ok());
+        mode.save(&mut conn).unwrap();
+        let loaded = MaintenanceMode::load(&mut conn);
+        assert!(loaded.enabled);
+        assert_eq!(loaded.message(), "Back at 10:00 UTC");
+
+        let long = MaintenanceMode {
+            enabled: true,
+            message: "x".repeat(MAX_MESSAGE_LENGTH + 1),
+        };
+        assert!(long.validate().is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ids::UserId,
+    settings::{self, NewSetting, Setting},
+};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+const HOMESERVER: &str = "matrix.homeserver";
+pub(super) const ACCESS_TOKEN: &str = "matrix.access_token";
+pub(super) const ROOM_ID: &str = "matrix.room_id";
+
+/// The Matrix room the user's subscriptions delivered by Matrix are posted
+/// to, and the account posting them, stored as the user's settings.
+/// Nothing is sent until all three are set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MatrixSettings {
+    /// e.g. `https://matrix.example.org`
+    pub homeserver: String,
+    /// of the account posting, which must have joined the room. Never sent
+    /// back by the API. When saving, `None` keeps the current one.
+    #[serde(skip_serializing, default)]
+    pub access_token: Option<String>,
+    /// e.g. `!abcdef:example.org`
+    pub room_id: String,
+}
+
+/// Where to post, from complete settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatrixRoom<'a> {
+    pub homeserver: &'a str,
+    pub access_token: &'a str,
+    pub room_id: &'a str,
+}
+
+impl MatrixSettings {
+    /// The user's settings, with defaults for anything not set
+    pub fn load(conn: &mut DbConnection, user_id: UserId) -> MatrixSettings {
+        let mut get = |key| {
+            Setting::get(conn, key, Some(user_id))
+                .ok()
+                .map(|setting| setting.value)
+        };
+        MatrixSettings {
+            homeserver: get(HOMESERVER).unwrap_or_default(),
+            access_token: get(ACCESS_TOKEN).filter(|token| !token.is_empty()),
+            room_id: get(ROOM_ID).unwrap_or_default(),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection, user_id: UserId) -> Result<(), settings::Error> {
+        let mut values = vec![
+            (HOMESERVER, self.homeserver.trim()),
+            (ROOM_ID, self.room_id.trim()),
+        ];
+        if let Some(token) = &self.access_token {
+            values.push((ACCESS_TOKEN, token.trim()));
+        }
+        for (key, value) in values {
+            let setting = NewSetting {
+                user_id: Some(user_id),
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// The room to post to, if everything is set
+    pub fn room(&self) -> Option<MatrixRoom<'_>> {
+        match (
+            self.homeserver.as_str(),
+            self.access_token.as_deref(),
+            self.room_id.as_str(),
+        ) {
+            ("", _, _) | (_, None, _) | (_, _, "") => None,
+            (homeserver, Some(access_token), room_id) => Some(MatrixRoom {
+                homeserver,
+                access_token,
+                room_id,
+            }),
+        }
+    }
+}
+
+impl Validate for MatrixSettings {
+    fn check(&self, errors: &mut ValidationErrors) {
+        let homeserver = self.homeserver.trim();
+        if !homeserver.is_empty() {
+            errors.url("homeserver", homeserver);
+        }
+        let room_id = self.room_id.trim();
+        let is_room_id = room_id.starts_with('!') && room_id.contains(':');
+        if !room_id.is_empty() && !is_room_id {
+            errors.add(
+                "room_id",
+                "Must be a room ID like !abcdef:example.org, not an alias",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(MatrixSettings::load(&mut conn, UserId(1)).room(), None);
+
+        let settings = MatrixSettings {
+            homeserver: "https://matrix.example.org".to_string(),
+            access_token: Some("syt_token".to_string()),
+            room_id: "!abcdef:example.org".to_string(),
+        };
+        assert!(settings.validate().is_ok());
+        settings.save(&mut conn, UserId(1)).unwrap();
+        assert_eq!(
+            MatrixSettings::load(&mut conn, UserId(1)).room(),
+            Some(MatrixRoom {
+                homeserver: "https://matrix.example.org",
+                access_token: "syt_token",
+                room_id: "!abcdef:example.org",
+            })
+        );
+
+        // leaving the token out keeps it, an empty one clears it
+        MatrixSettings {
+            access_token: None,
+            ..settings.clone()
+        }
+        .save(&mut conn, UserId(1))
+        .unwrap();
+        assert!(MatrixSettings::load(&mut conn, UserId(1)).room().is_some());
+        MatrixSettings {
+            access_token: Some(String::new()),
+            ..settings
+        }
+        .save(&mut conn, UserId(1))
+        .unwrap();
+        assert_eq!(MatrixSettings::load(&mut conn, UserId(1)).room(), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(MatrixSettings::default().validate().is_ok());
+        let settings = MatrixSettings {
+            homeserver: "matrix.example.org".to_string(),
+            access_token: None,
+            room_id: "#news:example.org".to_string(),
+        };
+        let errors = settings.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["homeserver", "room_id"]);
+    }
+}
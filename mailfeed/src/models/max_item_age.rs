@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    settings::{self, NewSetting, Setting},
+    subscription::Subscription,
+};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+const DEFAULT_DAYS: &str = "delivery.max_item_age_days";
+
+/// Longest limit an admin or user may set, about ten years
+pub const MAX_ITEM_AGE_DAYS_LIMIT: i32 = 3650;
+const DAY: i64 = 24 * 60 * 60;
+
+/// How old an item may be and still be delivered, for subscriptions without
+/// their own limit, stored as a system setting. Keeps months-old items from
+/// flooding out after a feed fixes its dates or backfills.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct MaxItemAge {
+    /// 0 for no limit
+    pub default_days: i32,
+}
+
+impl MaxItemAge {
+    pub fn load(conn: &mut DbConnection) -> MaxItemAge {
+        let default_days = Setting::get(conn, DEFAULT_DAYS, None)
+            .ok()
+            .and_then(|setting| setting.value.parse().ok())
+            .unwrap_or_default();
+        MaxItemAge { default_days }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        let setting = NewSetting {
+            user_id: None,
+            key: DEFAULT_DAYS.to_string(),
+            value: self.default_days.to_string(),
+        };
+        Setting::set(conn, &setting)?;
+        Ok(())
+    }
+
+    /// The subscription's limit in days, its own or else the default. None
+    /// if there isn't one.
+    pub fn days_for(&self, sub: &Subscription) -> Option<i32> {
+        Some(sub.max_item_age_days.unwrap_or(self.default_days)).filter(|days| *days > 0)
+    }
+
+    /// The oldest publish date of items the subscription sends at `now`,
+    /// None if it sends items of any age
+    pub fn oldest_for(&self, sub: &Subscription, now: i64) -> Option<i64> {
+        self.days_for(sub).map(|days| now - days as i64 * DAY)
+    }
+}
+
+impl Validate for MaxItemAge {
+    fn check(&self, errors: &mut ValidationErrors) {
+        errors.max_item_age("default_days", Some(self.default_days));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{ids::UserId, subscription::NewSubscription},
+        test_helpers::test_helpers::get_test_db_connection,
+    };
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(MaxItemAge::load(&mut conn), MaxItemAge::default());
+
+        let limit = MaxItemAge { default_days: 7 };
+        limit.save(&mut conn).unwrap();
+        assert_eq!(MaxItemAge::load(&mut conn), limit);
+        assert!(MaxItemAge { default_days: -1 }.validate().is_err());
+    }
+
+    #[test]
+    fn test_subscription_overrides_default() {
+        let mut conn = get_test_db_connection();
+        let mut sub = NewSubscription {
+            user_id: UserId(1),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let now = 100 * DAY;
+
+        assert_eq!(MaxItemAge::default().oldest_for(&sub, now), None);
+        let limit = MaxItemAge { default_days: 7 };
+        assert_eq!(limit.oldest_for(&sub, now), Some(93 * DAY));
+
+        sub.max_item_age_days = Some(30);
+        assert_eq!(limit.oldest_for(&sub, now), Some(70 * DAY));
+    }
+}
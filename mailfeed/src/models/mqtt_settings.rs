@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+
+use super::settings::{self, NewSetting, Setting};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+const ENABLED: &str = "mqtt.enabled";
+const HOST: &str = "mqtt.host";
+const PORT: &str = "mqtt.port";
+const USERNAME: &str = "mqtt.username";
+const PASSWORD: &str = "mqtt.password";
+const CLIENT_ID: &str = "mqtt.client_id";
+const ITEMS_TOPIC: &str = "mqtt.items_topic";
+const DELIVERIES_TOPIC: &str = "mqtt.deliveries_topic";
+
+/// The MQTT broker events are published to, stored as system settings.
+/// Publishing is off until an admin turns it on.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MqttSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    /// empty to connect without credentials
+    pub username: String,
+    /// never sent back by the API. When saving, `None` keeps the current
+    /// password.
+    #[serde(skip_serializing, default)]
+    pub password: Option<String>,
+    pub client_id: String,
+    /// where each new feed item is published
+    pub items_topic: String,
+    /// where each digest sent is published
+    pub deliveries_topic: String,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        MqttSettings {
+            enabled: false,
+            host: String::new(),
+            port: 1883,
+            username: String::new(),
+            password: None,
+            client_id: "mailfeed".to_string(),
+            items_topic: "mailfeed/items".to_string(),
+            deliveries_topic: "mailfeed/deliveries".to_string(),
+        }
+    }
+}
+
+impl MqttSettings {
+    /// The settings, with defaults for anything not set
+    pub fn load(conn: &mut DbConnection) -> MqttSettings {
+        let default = MqttSettings::default();
+        let mut get = |key| {
+            Setting::get(conn, key, None)
+                .ok()
+                .map(|setting| setting.value)
+        };
+        MqttSettings {
+            enabled: get(ENABLED).map_or(default.enabled, |value| value == "true"),
+            host: get(HOST).unwrap_or(default.host),
+            port: get(PORT)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default.port),
+            username: get(USERNAME).unwrap_or(default.username),
+            password: get(PASSWORD).filter(|password| !password.is_empty()),
+            client_id: get(CLIENT_ID).unwrap_or(default.client_id),
+            items_topic: get(ITEMS_TOPIC).unwrap_or(default.items_topic),
+            deliveries_topic: get(DELIVERIES_TOPIC).unwrap_or(default.deliveries_topic),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        let port = self.port.to_string();
+        let mut values = vec![
+            (ENABLED, if self.enabled { "true" } else { "false" }),
+            (HOST, self.host.as_str()),
+            (PORT, port.as_str()),
+            (USERNAME, self.username.as_str()),
+            (CLIENT_ID, self.client_id.as_str()),
+            (ITEMS_TOPIC, self.items_topic.as_str()),
+            (DELIVERIES_TOPIC, self.deliveries_topic.as_str()),
+        ];
+        if let Some(password) = &self.password {
+            values.push((PASSWORD, password.as_str()));
+        }
+        for (key, value) in values {
+            let setting = NewSetting {
+                user_id: None,
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for MqttSettings {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.enabled && self.host.trim().is_empty() {
+            errors.add("host", "Required when MQTT is enabled");
+        }
+        if self.port == 0 {
+            errors.add("port", "Must be between 1 and 65535");
+        }
+        if self.client_id.is_empty() {
+            errors.add("client_id", "Must not be empty");
+        }
+        for (field, topic) in [
+            ("items_topic", &self.items_topic),
+            ("deliveries_topic", &self.deliveries_topic),
+        ] {
+            // wildcards are only for subscribing
+            if topic.is_empty() || topic.contains(['#', '+']) {
+                errors.add(field, "Must be a topic name without wildcards");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(MqttSettings::load(&mut conn), MqttSettings::default());
+
+        let settings = MqttSettings {
+            enabled: true,
+            host: "broker.local".to_string(),
+            username: "mailfeed".to_string(),
+            password: Some("secret".to_string()),
+            ..Default::default()
+        };
+        settings.save(&mut conn).unwrap();
+        assert_eq!(MqttSettings::load(&mut conn), settings);
+
+        // leaving the password out keeps it, an empty one clears it
+        let without_password = MqttSettings {
+            password: None,
+            port: 8883,
+            ..settings.clone()
+        };
+        without_password.save(&mut conn).unwrap();
+        let loaded = MqttSettings::load(&mut conn);
+        assert_eq!(loaded.port, 8883);
+        assert_eq!(loaded.password.as_deref(), Some("secret"));
+        MqttSettings {
+            password: Some(String::new()),
+            ..settings
+        }
+        .save(&mut conn)
+        .unwrap();
+        assert_eq!(MqttSettings::load(&mut conn).password, None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(MqttSettings::default().validate().is_ok());
+        let settings = MqttSettings {
+            enabled: true,
+            items_topic: "mailfeed/#".to_string(),
+            ..Default::default()
+        };
+        let errors = settings.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["host", "items_topic"]);
+    }
+}
@@ -0,0 +1,151 @@
+use super::ids::UserId;
+use super::user::User;
+use crate::db::DbConnection;
+use crate::schema::*;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A new user's progress through the first-run checklist on the dashboard.
+/// Only users created by an admin have one, so existing users aren't shown
+/// a checklist for things they've already done.
+#[derive(
+    Debug, Serialize, Deserialize, Queryable, Insertable, Identifiable, Associations, PartialEq,
+)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = onboarding, primary_key(user_id))]
+pub struct Onboarding {
+    pub user_id: UserId,
+    pub added_feed: bool,
+    /// chose where and when their emails are sent
+    pub set_delivery: bool,
+    pub sent_test: bool,
+    /// the user hid the checklist before finishing it
+    pub dismissed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnboardingStep {
+    AddFeed,
+    SetDelivery,
+    SendTest,
+}
+
+impl Onboarding {
+    pub fn start(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<Onboarding, diesel::result::Error> {
+        diesel::insert_into(onboarding::table)
+            .values(Onboarding {
+                user_id,
+                added_feed: false,
+                set_delivery: false,
+                sent_test: false,
+                dismissed: false,
+            })
+            .get_result(conn)
+    }
+
+    pub fn get(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<Option<Onboarding>, diesel::result::Error> {
+        onboarding::table
+            .find(user_id)
+            .first::<Onboarding>(conn)
+            .optional()
+    }
+
+    /// Tick off a step. Does nothing for users without a checklist.
+    pub fn complete(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        step: OnboardingStep,
+    ) -> Result<usize, diesel::result::Error> {
+        use crate::schema::onboarding::dsl::{added_feed, onboarding, sent_test, set_delivery};
+        let row = diesel::update(onboarding.find(user_id));
+        match step {
+            OnboardingStep::AddFeed => row.set(added_feed.eq(true)).execute(conn),
+            OnboardingStep::SetDelivery => row.set(set_delivery.eq(true)).execute(conn),
+            OnboardingStep::SendTest => row.set(sent_test.eq(true)).execute(conn),
+        }
+    }
+
+    pub fn dismiss(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<usize, diesel::result::Error> {
+        use crate::schema::onboarding::dsl::{dismissed, onboarding};
+        diesel::update(onboarding.find(user_id))
+            .set(dismissed.eq(true))
+            .execute(conn)
+    }
+
+    pub fn delete(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<usize, diesel::result::Error> {
+        diesel::delete(onboarding::table.find(user_id)).execute(conn)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.added_feed && self.set_delivery && self.sent_test
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_complete() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(Onboarding::get(&mut conn, UserId(1)), Ok(None));
+        // no checklist to tick off
+        assert_eq!(
+            Onboarding::complete(&mut conn, UserId(1), OnboardingStep::AddFeed),
+            Ok(0)
+        );
+
+        Onboarding::start(&mut conn, UserId(1)).unwrap();
+        Onboarding::start(&mut conn, UserId(2)).unwrap();
+        for step in [
+            OnboardingStep::AddFeed,
+            OnboardingStep::SetDelivery,
+            OnboardingStep::AddFeed,
+        ] {
+            Onboarding::complete(&mut conn, UserId(1), step).unwrap();
+        }
+        let progress = Onboarding::get(&mut conn, UserId(1)).unwrap().unwrap();
+        assert!(progress.added_feed && progress.set_delivery);
+        assert!(!progress.is_complete());
+
+        Onboarding::complete(&mut conn, UserId(1), OnboardingStep::SendTest).unwrap();
+        let progress = Onboarding::get(&mut conn, UserId(1)).unwrap().unwrap();
+        assert!(progress.is_complete());
+        assert!(!progress.dismissed);
+        assert!(
+            !Onboarding::get(&mut conn, UserId(2))
+                .unwrap()
+                .unwrap()
+                .added_feed
+        );
+    }
+
+    #[test]
+    fn test_dismiss_and_delete() {
+        let mut conn = get_test_db_connection();
+        Onboarding::start(&mut conn, UserId(1)).unwrap();
+        Onboarding::dismiss(&mut conn, UserId(1)).unwrap();
+        assert!(
+            Onboarding::get(&mut conn, UserId(1))
+                .unwrap()
+                .unwrap()
+                .dismissed
+        );
+
+        assert_eq!(Onboarding::delete(&mut conn, UserId(1)), Ok(1));
+        assert_eq!(Onboarding::get(&mut conn, UserId(1)), Ok(None));
+    }
+}
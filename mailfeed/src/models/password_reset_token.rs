@@ -0,0 +1,148 @@
+use diesel::prelude::*;
+
+use super::ids::UserId;
+use crate::db::DbConnection;
+use crate::schema::*;
+use crate::security::tokens::{hash_token, random_token};
+
+/// How long an emailed reset link works
+pub const TOKEN_LIFETIME_SECONDS: i64 = 60 * 60;
+/// A user can't be sent another reset email until this long after the last
+const RESEND_AFTER_SECONDS: i64 = 60;
+const TOKEN_LENGTH: usize = 48;
+
+/// A single-use token emailed to a user who forgot their password. Only
+/// its hash is stored.
+#[derive(Debug, Queryable, Identifiable, PartialEq)]
+#[diesel(table_name = password_reset_tokens)]
+pub struct PasswordResetToken {
+    pub id: i32,
+    pub user_id: UserId,
+    pub token_hash: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = password_reset_tokens)]
+struct NewPasswordResetToken<'a> {
+    user_id: UserId,
+    token_hash: &'a str,
+    created_at: i64,
+    expires_at: i64,
+}
+
+impl PasswordResetToken {
+    /// A new token for the user, replacing any they already had. None if
+    /// one was issued too recently, so the endpoint can't be used to flood
+    /// the user's inbox.
+    pub fn issue(conn: &mut DbConnection, uid: UserId, now: i64) -> QueryResult<Option<String>> {
+        use crate::schema::password_reset_tokens::dsl::*;
+        let latest: Option<i64> = password_reset_tokens
+            .filter(user_id.eq(uid))
+            .select(diesel::dsl::max(created_at))
+            .first(conn)?;
+        if latest.is_some_and(|latest| now - latest < RESEND_AFTER_SECONDS) {
+            return Ok(None);
+        }
+
+        let token = random_token(TOKEN_LENGTH);
+        conn.transaction(|conn| {
+            diesel::delete(password_reset_tokens.filter(user_id.eq(uid).or(expires_at.le(now))))
+                .execute(conn)?;
+            diesel::insert_into(password_reset_tokens)
+                .values(NewPasswordResetToken {
+                    user_id: uid,
+                    token_hash: &hash_token(&token),
+                    created_at: now,
+                    expires_at: now + TOKEN_LIFETIME_SECONDS,
+                })
+                .execute(conn)
+        })?;
+        Ok(Some(token))
+    }
+
+    /// The user the token was issued to, if it's valid. Using a token
+    /// removes it, along with the user's other tokens.
+    pub fn redeem(conn: &mut DbConnection, token: &str, now: i64) -> QueryResult<Option<UserId>> {
+        use crate::schema::password_reset_tokens::dsl::*;
+        conn.transaction(|conn| {
+            let found = password_reset_tokens
+                .filter(token_hash.eq(hash_token(token)))
+                .first::<PasswordResetToken>(conn)
+                .optional()?;
+            let found = match found {
+                Some(found) => found,
+                None => return Ok(None),
+            };
+            diesel::delete(password_reset_tokens.filter(user_id.eq(found.user_id)))
+                .execute(conn)?;
+            Ok((found.expires_at > now).then_some(found.user_id))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_issue_and_redeem() {
+        let mut conn = get_test_db_connection();
+        let token = PasswordResetToken::issue(&mut conn, UserId(1), 1000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(token.len(), TOKEN_LENGTH);
+        // too soon for another
+        assert_eq!(
+            PasswordResetToken::issue(&mut conn, UserId(1), 1030),
+            Ok(None)
+        );
+
+        assert_eq!(
+            PasswordResetToken::redeem(&mut conn, "wrong", 1100),
+            Ok(None)
+        );
+        assert_eq!(
+            PasswordResetToken::redeem(&mut conn, &token, 1100),
+            Ok(Some(UserId(1)))
+        );
+        // single use
+        assert_eq!(
+            PasswordResetToken::redeem(&mut conn, &token, 1100),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_new_token_replaces_old() {
+        let mut conn = get_test_db_connection();
+        let first = PasswordResetToken::issue(&mut conn, UserId(1), 1000)
+            .unwrap()
+            .unwrap();
+        let second = PasswordResetToken::issue(&mut conn, UserId(1), 2000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            PasswordResetToken::redeem(&mut conn, &first, 2100),
+            Ok(None)
+        );
+        assert_eq!(
+            PasswordResetToken::redeem(&mut conn, &second, 2100),
+            Ok(Some(UserId(1)))
+        );
+    }
+
+    #[test]
+    fn test_expired_token() {
+        let mut conn = get_test_db_connection();
+        let token = PasswordResetToken::issue(&mut conn, UserId(1), 1000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            PasswordResetToken::redeem(&mut conn, &token, 1000 + TOKEN_LIFETIME_SECONDS),
+            Ok(None)
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::ids::{AccessTokenId, UserId};
+use crate::db::DbConnection;
+use crate::schema::*;
+use crate::security::{
+    tokens::{hash_token, random_token},
+    validation::{Validate, ValidationErrors},
+};
+
+/// Starts every personal access token, so they can be told apart from JWTs
+/// in the Authorization header
+pub const TOKEN_PREFIX: &str = "mfpat_";
+const TOKEN_LENGTH: usize = 40;
+/// How stale `last_used_at` may get, so not every request writes to the
+/// database
+const LAST_USED_PRECISION_SECONDS: i64 = 60;
+const MAX_NAME_LENGTH: usize = 100;
+
+/// A revocable bearer token for scripts and other clients that can't go
+/// through the login flow. It acts as its user, with their role. Only its
+/// hash is stored.
+#[derive(Debug, Clone, Serialize, Queryable, Identifiable, PartialEq)]
+#[diesel(table_name = personal_access_tokens)]
+pub struct PersonalAccessToken {
+    pub id: AccessTokenId,
+    pub user_id: UserId,
+    /// what the token is for, e.g. "backup script"
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub created_at: i64,
+    /// zero if never used
+    pub last_used_at: i64,
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = personal_access_tokens)]
+pub struct NewPersonalAccessToken {
+    #[serde(skip_deserializing)]
+    pub user_id: UserId,
+    pub name: String,
+    #[serde(skip_deserializing)]
+    pub token_hash: String,
+    #[serde(skip_deserializing)]
+    pub created_at: i64,
+    /// never expires if not given
+    pub expires_at: Option<i64>,
+}
+
+impl Validate for NewPersonalAccessToken {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.name.trim().is_empty() {
+            errors.add("name", "Must not be empty");
+        } else if self.name.chars().count() > MAX_NAME_LENGTH {
+            errors.add(
+                "name",
+                format!("Must be at most {} characters", MAX_NAME_LENGTH),
+            );
+        }
+        if self.expires_at.is_some_and(|at| at <= self.created_at) {
+            errors.add("expires_at", "Must be in the future");
+        }
+    }
+}
+
+impl NewPersonalAccessToken {
+    /// Store the token, returning it along with the secret to give the
+    /// user, which can't be recovered later
+    pub fn insert(mut self, conn: &mut DbConnection) -> QueryResult<(PersonalAccessToken, String)> {
+        let secret = format!("{}{}", TOKEN_PREFIX, random_token(TOKEN_LENGTH));
+        self.token_hash = hash_token(&secret);
+        let token = diesel::insert_into(personal_access_tokens::table)
+            .values(&self)
+            .get_result(conn)?;
+        Ok((token, secret))
+    }
+}
+
+impl PersonalAccessToken {
+    pub fn is_personal_access_token(token: &str) -> bool {
+        token.starts_with(TOKEN_PREFIX)
+    }
+
+    pub fn get_for_user(
+        conn: &mut DbConnection,
+        uid: UserId,
+    ) -> QueryResult<Vec<PersonalAccessToken>> {
+        use crate::schema::personal_access_tokens::dsl::*;
+        personal_access_tokens
+            .filter(user_id.eq(uid))
+            .order(id)
+            .load(conn)
+    }
+
+    pub fn count_for_user(conn: &mut DbConnection, uid: UserId) -> QueryResult<i64> {
+        use crate::schema::personal_access_tokens::dsl::*;
+        personal_access_tokens
+            .filter(user_id.eq(uid))
+            .count()
+            .get_result(conn)
+    }
+
+    /// Only the user's own tokens can be revoked
+    pub fn delete(
+        conn: &mut DbConnection,
+        uid: UserId,
+        token_id: AccessTokenId,
+    ) -> QueryResult<usize> {
+        use crate::schema::personal_access_tokens::dsl::*;
+        diesel::delete(
+            personal_access_tokens
+                .find(token_id)
+                .filter(user_id.eq(uid)),
+        )
+        .execute(conn)
+    }
+
+    /// The stored token matching the secret, if it hasn't expired, noting
+    /// that it was used
+    pub fn authenticate(
+        conn: &mut DbConnection,
+        secret: &str,
+        now: i64,
+    ) -> QueryResult<Option<PersonalAccessToken>> {
+        use crate::schema::personal_access_tokens::dsl::*;
+        let token = personal_access_tokens
+            .filter(token_hash.eq(hash_token(secret)))
+            .first::<PersonalAccessToken>(conn)
+            .optional()?;
+        let token = match token {
+            Some(token) if token.expires_at.is_none_or(|at| at > now) => token,
+            _ => return Ok(None),
+        };
+        if now - token.last_used_at >= LAST_USED_PRECISION_SECONDS {
+            diesel::update(personal_access_tokens.find(token.id))
+                .set(last_used_at.eq(now))
+                .execute(conn)?;
+        }
+        Ok(Some(token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn new_token(expires_at: Option<i64>) -> NewPersonalAccessToken {
+        NewPersonalAccessToken {
+            user_id: UserId(1),
+            name: "backup script".to_string(),
+            token_hash: String::new(),
+            created_at: 1000,
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let mut conn = get_test_db_connection();
+        let (token, secret) = new_token(Some(5000)).insert(&mut conn).unwrap();
+        assert!(PersonalAccessToken::is_personal_access_token(&secret));
+        assert_ne!(token.token_hash, secret);
+
+        let found = PersonalAccessToken::authenticate(&mut conn, &secret, 2000).unwrap();
+        assert_eq!(found.map(|found| found.id), Some(token.id));
+        let listed = PersonalAccessToken::get_for_user(&mut conn, UserId(1)).unwrap();
+        assert_eq!(listed[0].last_used_at, 2000);
+
+        assert_eq!(
+            PersonalAccessToken::authenticate(&mut conn, "mfpat_wrong", 2000),
+            Ok(None)
+        );
+        // expired
+        assert_eq!(
+            PersonalAccessToken::authenticate(&mut conn, &secret, 5000),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut conn = get_test_db_connection();
+        let (token, secret) = new_token(None).insert(&mut conn).unwrap();
+        assert_eq!(
+            PersonalAccessToken::delete(&mut conn, UserId(2), token.id),
+            Ok(0)
+        );
+        assert_eq!(
+            PersonalAccessToken::delete(&mut conn, UserId(1), token.id),
+            Ok(1)
+        );
+        assert_eq!(
+            PersonalAccessToken::authenticate(&mut conn, &secret, 2000),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(new_token(None).validate().is_ok());
+        let invalid = NewPersonalAccessToken {
+            name: " ".to_string(),
+            ..new_token(Some(500))
+        };
+        let errors = invalid.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["name", "expires_at"]);
+    }
+}
@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ids::UserId,
+    settings::{self, NewSetting, Setting},
+};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+pub(super) const SERVICE: &str = "push.service";
+const URL: &str = "push.url";
+pub(super) const TOPIC: &str = "push.topic";
+pub(super) const TOKEN: &str = "push.token";
+
+/// Used for ntfy when no server is set
+const NTFY_DEFAULT_URL: &str = "https://ntfy.sh";
+const MAX_TOPIC_LENGTH: usize = 64;
+
+/// A push notification service new items can be sent to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PushService {
+    Ntfy,
+    Gotify,
+}
+
+impl PushService {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PushService::Ntfy => "ntfy",
+            PushService::Gotify => "gotify",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "ntfy" => Some(PushService::Ntfy),
+            "gotify" => Some(PushService::Gotify),
+            _ => None,
+        }
+    }
+}
+
+/// Where the user's subscriptions delivered by push are sent, stored as the
+/// user's settings. Nothing is sent until a service is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PushSettings {
+    pub service: Option<PushService>,
+    /// the server's base URL. Optional for ntfy, which defaults to
+    /// https://ntfy.sh
+    #[serde(default)]
+    pub url: String,
+    /// the ntfy topic to publish to
+    #[serde(default)]
+    pub topic: String,
+    /// the Gotify application token, or an ntfy access token for protected
+    /// topics. Never sent back by the API. When saving, `None` keeps the
+    /// current one.
+    #[serde(skip_serializing, default)]
+    pub token: Option<String>,
+}
+
+impl PushSettings {
+    /// The user's settings, with defaults for anything not set
+    pub fn load(conn: &mut DbConnection, user_id: UserId) -> PushSettings {
+        let mut get = |key| {
+            Setting::get(conn, key, Some(user_id))
+                .ok()
+                .map(|setting| setting.value)
+        };
+        PushSettings {
+            service: get(SERVICE).and_then(|value| PushService::from_str(&value)),
+            url: get(URL).unwrap_or_default(),
+            topic: get(TOPIC).unwrap_or_default(),
+            token: get(TOKEN).filter(|token| !token.is_empty()),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection, user_id: UserId) -> Result<(), settings::Error> {
+        let mut values = vec![
+            (SERVICE, self.service.map_or("", |service| service.as_str())),
+            (URL, self.url.trim().trim_end_matches('/')),
+            (TOPIC, self.topic.trim()),
+        ];
+        if let Some(token) = &self.token {
+            values.push((TOKEN, token.trim()));
+        }
+        for (key, value) in values {
+            let setting = NewSetting {
+                user_id: Some(user_id),
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// The server to send to, falling back to ntfy.sh for ntfy
+    pub fn server_url(&self) -> &str {
+        match (self.service, self.url.as_str()) {
+            (Some(PushService::Ntfy), "") => NTFY_DEFAULT_URL,
+            (_, url) => url,
+        }
+    }
+
+    /// Whether enough is set to send notifications
+    pub fn is_configured(&self) -> bool {
+        match self.service {
+            Some(PushService::Ntfy) => !self.topic.is_empty(),
+            Some(PushService::Gotify) => !self.url.is_empty() && self.token.is_some(),
+            None => false,
+        }
+    }
+}
+
+impl Validate for PushSettings {
+    fn check(&self, errors: &mut ValidationErrors) {
+        let service = match self.service {
+            Some(service) => service,
+            None => return,
+        };
+        let url = self.url.trim();
+        if service == PushService::Gotify || !url.is_empty() {
+            errors.url("url", url);
+        }
+        if service == PushService::Ntfy {
+            let topic = self.topic.trim();
+            let valid = topic
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if topic.is_empty() || topic.len() > MAX_TOPIC_LENGTH || !valid {
+                errors.add(
+                    "topic",
+                    format!("Must be 1 to {} letters, digits, - or _", MAX_TOPIC_LENGTH),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        let loaded = PushSettings::load(&mut conn, UserId(1));
+        assert_eq!(loaded, PushSettings::default());
+        assert!(!loaded.is_configured());
+
+        let settings = PushSettings {
+            service: Some(PushService::Ntfy),
+            url: String::new(),
+            topic: "my-feeds".to_string(),
+            token: None,
+        };
+        settings.save(&mut conn, UserId(1)).unwrap();
+        let loaded = PushSettings::load(&mut conn, UserId(1));
+        assert!(loaded.is_configured());
+        assert_eq!(loaded.server_url(), NTFY_DEFAULT_URL);
+
+        let settings = PushSettings {
+            service: Some(PushService::Gotify),
+            url: "https://push.example.com/".to_string(),
+            topic: String::new(),
+            token: Some("AbCdEf".to_string()),
+        };
+        settings.save(&mut conn, UserId(1)).unwrap();
+        // leaving the token out keeps it
+        PushSettings {
+            token: None,
+            ..settings
+        }
+        .save(&mut conn, UserId(1))
+        .unwrap();
+        let loaded = PushSettings::load(&mut conn, UserId(1));
+        assert!(loaded.is_configured());
+        assert_eq!(loaded.server_url(), "https://push.example.com");
+        assert_eq!(loaded.token.as_deref(), Some("AbCdEf"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(PushSettings::default().validate().is_ok());
+        let ntfy = PushSettings {
+            service: Some(PushService::Ntfy),
+            topic: "my feeds".to_string(),
+            ..Default::default()
+        };
+        let errors = ntfy.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["topic"]);
+
+        let gotify = PushSettings {
+            service: Some(PushService::Gotify),
+            ..Default::default()
+        };
+        let errors = gotify.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["url"]);
+    }
+}
@@ -0,0 +1,96 @@
+use std::{cmp::Reverse, collections::VecDeque, sync::Mutex, time::Instant};
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+/// How many recent timings are kept to pick the slowest from
+const MAX_RECENT: usize = 500;
+/// How many of the slowest recent queries are reported
+const SLOWEST_REPORTED: usize = 20;
+/// Queries slower than this are logged
+const SLOW_QUERY_MS: u128 = 250;
+
+static RECENT: Lazy<Recorder> = Lazy::new(Recorder::default);
+
+/// How long a named query took
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct QueryTiming {
+    pub name: &'static str,
+    pub duration_us: u128,
+    pub at: i64,
+}
+
+#[derive(Default)]
+struct Recorder {
+    timings: Mutex<VecDeque<QueryTiming>>,
+}
+
+impl Recorder {
+    fn record(&self, timing: QueryTiming) {
+        let mut timings = self.timings.lock().unwrap();
+        timings.push_back(timing);
+        while timings.len() > MAX_RECENT {
+            timings.pop_front();
+        }
+    }
+
+    fn slowest(&self, count: usize) -> Vec<QueryTiming> {
+        let mut timings: Vec<QueryTiming> = self.timings.lock().unwrap().iter().cloned().collect();
+        timings.sort_by_key(|timing| Reverse(timing.duration_us));
+        timings.truncate(count);
+        timings
+    }
+}
+
+/// Run a query, remembering how long it took
+pub fn timed<T>(name: &'static str, query: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = query();
+    let elapsed = started.elapsed();
+    if elapsed.as_millis() > SLOW_QUERY_MS {
+        log::warn!("Slow query {} took {}ms", name, elapsed.as_millis());
+    }
+    RECENT.record(QueryTiming {
+        name,
+        duration_us: elapsed.as_micros(),
+        at: Utc::now().timestamp(),
+    });
+    result
+}
+
+/// The slowest of the recently timed queries, slowest first
+pub fn slowest() -> Vec<QueryTiming> {
+    RECENT.slowest(SLOWEST_REPORTED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(name: &'static str, duration_us: u128) -> QueryTiming {
+        QueryTiming {
+            name,
+            duration_us,
+            at: 1000,
+        }
+    }
+
+    #[test]
+    fn test_slowest() {
+        let recorder = Recorder::default();
+        recorder.record(timing("fast", 10));
+        recorder.record(timing("slow", 300));
+        recorder.record(timing("medium", 50));
+        assert_eq!(
+            recorder.slowest(2),
+            vec![timing("slow", 300), timing("medium", 50)]
+        );
+
+        for _ in 0..MAX_RECENT {
+            recorder.record(timing("fast", 10));
+        }
+        // older timings are forgotten
+        assert_eq!(recorder.slowest(1), vec![timing("fast", 10)]);
+    }
+}
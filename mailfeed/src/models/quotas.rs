@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    feed::Feed,
+    ids::UserId,
+    settings::{self, NewSetting, Setting},
+    subscription::Subscription,
+};
+use crate::db::DbConnection;
+
+const MAX_SUBSCRIPTIONS_PER_USER: &str = "quota.max_subscriptions_per_user";
+const MAX_REALTIME_SUBSCRIPTIONS_PER_USER: &str = "quota.max_realtime_subscriptions_per_user";
+const MAX_FEEDS: &str = "quota.max_feeds";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum QuotaError {
+    #[error("Subscription limit reached: each user may have at most {0} subscriptions")]
+    TooManySubscriptions(u32),
+    #[error("Realtime subscription limit reached: each user may have at most {0} realtime subscriptions")]
+    TooManyRealtimeSubscriptions(u32),
+    #[error("Feed limit reached: this instance may have at most {0} feeds")]
+    TooManyFeeds(u32),
+    #[error("Database error")]
+    Database,
+}
+
+/// Instance-wide usage limits, stored as system settings. A limit of None
+/// means unlimited.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Quotas {
+    pub max_subscriptions_per_user: Option<u32>,
+    pub max_realtime_subscriptions_per_user: Option<u32>,
+    pub max_feeds: Option<u32>,
+}
+
+impl Quotas {
+    pub fn load(conn: &mut DbConnection) -> Quotas {
+        let mut get = |key| {
+            Setting::get(conn, key, None)
+                .ok()
+                .and_then(|setting| setting.value.parse().ok())
+        };
+        Quotas {
+            max_subscriptions_per_user: get(MAX_SUBSCRIPTIONS_PER_USER),
+            max_realtime_subscriptions_per_user: get(MAX_REALTIME_SUBSCRIPTIONS_PER_USER),
+            max_feeds: get(MAX_FEEDS),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        for (key, limit) in [
+            (MAX_SUBSCRIPTIONS_PER_USER, self.max_subscriptions_per_user),
+            (
+                MAX_REALTIME_SUBSCRIPTIONS_PER_USER,
+                self.max_realtime_subscriptions_per_user,
+            ),
+            (MAX_FEEDS, self.max_feeds),
+        ] {
+            match limit {
+                Some(limit) => {
+                    let setting = NewSetting {
+                        user_id: None,
+                        key: key.to_string(),
+                        value: limit.to_string(),
+                    };
+                    Setting::set(conn, &setting)?;
+                }
+                None => Setting::remove(conn, key, None)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether the user may add another subscription, which may also
+    /// need a new feed
+    pub fn check_new_subscription(
+        &self,
+        conn: &mut DbConnection,
+        user_id: UserId,
+        realtime: bool,
+        new_feed: bool,
+    ) -> Result<(), QuotaError> {
+        if let Some(max) = self.max_subscriptions_per_user {
+            let count =
+                Subscription::count_for_user(conn, user_id).map_err(|_| QuotaError::Database)?;
+            if count >= max as i64 {
+                return Err(QuotaError::TooManySubscriptions(max));
+            }
+        }
+        if realtime {
+            self.check_new_realtime(conn, user_id)?;
+        }
+        if new_feed {
+            if let Some(max) = self.max_feeds {
+                let count = Feed::count(conn).map_err(|_| QuotaError::Database)?;
+                if count >= max as i64 {
+                    return Err(QuotaError::TooManyFeeds(max));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether the user may have another realtime subscription
+    pub fn check_new_realtime(
+        &self,
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<(), QuotaError> {
+        if let Some(max) = self.max_realtime_subscriptions_per_user {
+            let count = Subscription::count_realtime_for_user(conn, user_id)
+                .map_err(|_| QuotaError::Database)?;
+            if count >= max as i64 {
+                return Err(QuotaError::TooManyRealtimeSubscriptions(max));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        feed::NewFeed,
+        ids::FeedId,
+        subscription::{Frequency, NewSubscription},
+    };
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(Quotas::load(&mut conn), Quotas::default());
+
+        let quotas = Quotas {
+            max_subscriptions_per_user: Some(10),
+            max_realtime_subscriptions_per_user: Some(2),
+            max_feeds: None,
+        };
+        quotas.save(&mut conn).unwrap();
+        assert_eq!(Quotas::load(&mut conn), quotas);
+
+        Quotas::default().save(&mut conn).unwrap();
+        assert_eq!(Quotas::load(&mut conn), Quotas::default());
+    }
+
+    #[test]
+    fn test_check_new_subscription() {
+        let mut conn = get_test_db_connection();
+        let quotas = Quotas {
+            max_subscriptions_per_user: Some(2),
+            max_realtime_subscriptions_per_user: Some(1),
+            max_feeds: Some(1),
+        };
+        assert_eq!(
+            quotas.check_new_subscription(&mut conn, UserId(1), true, true),
+            Ok(())
+        );
+
+        NewFeed {
+            url: "https://example.com/feed.xml",
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        NewSubscription {
+            user_id: UserId(1),
+            feed_id: FeedId(1),
+            frequency: Frequency::Realtime,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+
+        assert_eq!(
+            quotas.check_new_subscription(&mut conn, UserId(1), true, false),
+            Err(QuotaError::TooManyRealtimeSubscriptions(1))
+        );
+        assert_eq!(
+            quotas.check_new_subscription(&mut conn, UserId(1), false, true),
+            Err(QuotaError::TooManyFeeds(1))
+        );
+        assert_eq!(
+            quotas.check_new_subscription(&mut conn, UserId(1), false, false),
+            Ok(())
+        );
+
+        NewSubscription {
+            user_id: UserId(1),
+            feed_id: FeedId(1),
+            frequency: Frequency::Daily,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        assert_eq!(
+            quotas.check_new_subscription(&mut conn, UserId(1), false, false),
+            Err(QuotaError::TooManySubscriptions(2))
+        );
+        // other users aren't affected
+        assert_eq!(
+            quotas.check_new_subscription(&mut conn, UserId(2), true, false),
+            Ok(())
+        );
+    }
+}
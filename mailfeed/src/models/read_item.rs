@@ -0,0 +1,564 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use base64::{engine::general_purpose, Engine};
+use diesel::{
+    prelude::*,
+    sql_types::{Nullable, Text},
+};
+use serde::Serialize;
+
+use super::{
+    feed_item::FeedItem,
+    ids::{FeedId, UserId},
+};
+use crate::db::{with_backend, DbConnection, MultiBackend};
+use crate::schema::*;
+
+/// A user's state for an item
+#[derive(Debug, Clone, Copy, Default, Serialize, Queryable, PartialEq)]
+pub struct ItemState {
+    /// None if unread
+    pub read_at: Option<i64>,
+    pub starred: bool,
+}
+
+/// Which items to list, by the user's state for them. Both may be set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StateFilter {
+    /// only items the user hasn't read
+    pub unread: bool,
+    /// only items the user starred
+    pub starred: bool,
+}
+
+/// Which of the items from a user's subscriptions to list
+#[derive(Debug, Clone, Default)]
+pub struct ItemQuery {
+    /// only this feed's
+    pub feed_id: Option<FeedId>,
+    /// first seen after this unix timestamp
+    pub after: Option<i64>,
+    /// first seen before this unix timestamp
+    pub before: Option<i64>,
+    /// text in the title or description, ignoring case
+    pub search: Option<String>,
+    pub state: StateFilter,
+}
+
+/// Where a page of items, newest first, left off. The next page starts
+/// after it, so items fetched in between don't shift the pages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ItemCursor {
+    pub first_seen: i64,
+    pub id: i32,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("Invalid cursor")]
+pub struct InvalidCursor;
+
+impl ItemCursor {
+    pub fn after(item: &FeedItem) -> ItemCursor {
+        ItemCursor {
+            first_seen: item.first_seen,
+            id: item.id,
+        }
+    }
+}
+
+/// Cursors are opaque to clients, so how they work can change
+impl fmt::Display for ItemCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!("{}:{}", self.first_seen, self.id);
+        f.write_str(&general_purpose::URL_SAFE_NO_PAD.encode(raw))
+    }
+}
+
+impl FromStr for ItemCursor {
+    type Err = InvalidCursor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw = general_purpose::URL_SAFE_NO_PAD
+            .decode(s)
+            .map_err(|_| InvalidCursor)?;
+        let raw = String::from_utf8(raw).map_err(|_| InvalidCursor)?;
+        let (first_seen, id) = raw.split_once(':').ok_or(InvalidCursor)?;
+        Ok(ItemCursor {
+            first_seen: first_seen.parse().map_err(|_| InvalidCursor)?,
+            id: id.parse().map_err(|_| InvalidCursor)?,
+        })
+    }
+}
+
+define_sql_function! {
+    /// LIKE ignores case on SQLite but not Postgres, so both sides are
+    /// lowercased first
+    fn lower(text: Nullable<Text>) -> Nullable<Text>;
+}
+
+/// `%` and `_` are wildcards to LIKE, so they're escaped to be matched as
+/// they are
+fn like_pattern(search: &str) -> String {
+    let escaped = search
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+impl ItemQuery {
+    /// The items from the user's subscriptions that match, in no
+    /// particular order
+    fn matching(&self, uid: UserId) -> feed_items::BoxedQuery<'static, MultiBackend> {
+        let subscribed = subscriptions::table
+            .filter(subscriptions::user_id.eq(uid))
+            .select(subscriptions::feed_id);
+        let mut query = feed_items::table
+            .filter(feed_items::feed_id.eq_any(subscribed))
+            .into_boxed();
+        if let Some(fid) = self.feed_id {
+            query = query.filter(feed_items::feed_id.eq(fid));
+        }
+        if let Some(after) = self.after {
+            query = query.filter(feed_items::first_seen.gt(after));
+        }
+        if let Some(before) = self.before {
+            query = query.filter(feed_items::first_seen.lt(before));
+        }
+        if let Some(search) = self.search.as_deref().map(str::trim) {
+            if !search.is_empty() {
+                let pattern = like_pattern(search);
+                query = query.filter(
+                    lower(feed_items::title.nullable())
+                        .like(lower(pattern.clone()))
+                        .escape('\\')
+                        .or(lower(feed_items::description)
+                            .like(lower(pattern))
+                            .escape('\\')),
+                );
+            }
+        }
+        if self.state.unread {
+            let read = read_items::table
+                .filter(read_items::user_id.eq(uid))
+                .select(read_items::feed_item_id);
+            query = query.filter(feed_items::id.ne_all(read));
+        }
+        if self.state.starred {
+            let starred = starred_items::table
+                .filter(starred_items::user_id.eq(uid))
+                .select(starred_items::feed_item_id);
+            query = query.filter(feed_items::id.eq_any(starred));
+        }
+        query
+    }
+
+    /// Up to `limit` matching items, newest first by when they were
+    /// fetched, starting after the cursor if there is one
+    pub fn page(
+        &self,
+        conn: &mut DbConnection,
+        uid: UserId,
+        cursor: Option<ItemCursor>,
+        limit: i64,
+    ) -> QueryResult<Vec<(FeedItem, ItemState)>> {
+        let mut query = self.matching(uid);
+        if let Some(cursor) = cursor {
+            query = query.filter(
+                feed_items::first_seen
+                    .lt(cursor.first_seen)
+                    .or(feed_items::first_seen
+                        .eq(cursor.first_seen)
+                        .and(feed_items::id.lt(cursor.id))),
+            );
+        }
+        let items: Vec<FeedItem> = query
+            .order((feed_items::first_seen.desc(), feed_items::id.desc()))
+            .limit(limit)
+            .load(conn)?;
+        let ids: Vec<i32> = items.iter().map(|item| item.id).collect();
+        let mut states = ReadItem::states(conn, uid, &ids)?;
+        Ok(items
+            .into_iter()
+            .map(|item| {
+                let state = states.remove(&item.id).unwrap_or_default();
+                (item, state)
+            })
+            .collect())
+    }
+
+    /// How many items match in all, ignoring pagination
+    pub fn count(&self, conn: &mut DbConnection, uid: UserId) -> QueryResult<i64> {
+        self.matching(uid).count().get_result(conn)
+    }
+}
+
+/// That a user has read an item in the reader view. Items without one are
+/// unread.
+#[derive(Debug, Insertable)]
+#[diesel(table_name = read_items)]
+struct NewReadItem {
+    user_id: UserId,
+    feed_item_id: i32,
+    read_at: i64,
+}
+
+pub struct ReadItem;
+
+impl ReadItem {
+    /// Marking an item that's already read keeps when it was first read
+    pub fn mark_read(
+        conn: &mut DbConnection,
+        uid: UserId,
+        item_id: i32,
+        now: i64,
+    ) -> QueryResult<usize> {
+        let insert = diesel::insert_into(read_items::table)
+            .values(NewReadItem {
+                user_id: uid,
+                feed_item_id: item_id,
+                read_at: now,
+            })
+            .on_conflict_do_nothing();
+        with_backend!(conn, insert.execute(conn))
+    }
+
+    /// Returns how many were removed, zero if it wasn't read
+    pub fn mark_unread(conn: &mut DbConnection, uid: UserId, item_id: i32) -> QueryResult<usize> {
+        use crate::schema::read_items::dsl::*;
+
+        diesel::delete(
+            read_items
+                .filter(user_id.eq(uid))
+                .filter(feed_item_id.eq(item_id)),
+        )
+        .execute(conn)
+    }
+
+    pub fn state(conn: &mut DbConnection, uid: UserId, item_id: i32) -> QueryResult<ItemState> {
+        let mut states = ReadItem::states(conn, uid, &[item_id])?;
+        Ok(states.remove(&item_id).unwrap_or_default())
+    }
+
+    /// A page of items from the feeds the user is subscribed to, newest
+    /// first by when they were fetched, with the user's state for each
+    pub fn reader_page(
+        conn: &mut DbConnection,
+        uid: UserId,
+        filter: StateFilter,
+        offset: i64,
+        limit: i64,
+    ) -> QueryResult<Vec<(FeedItem, ItemState)>> {
+        let subscribed = subscriptions::table
+            .filter(subscriptions::user_id.eq(uid))
+            .select(subscriptions::feed_id);
+        let mut query = feed_items::table
+            .left_join(
+                read_items::table.on(read_items::feed_item_id
+                    .eq(feed_items::id)
+                    .and(read_items::user_id.eq(uid))),
+            )
+            .left_join(
+                starred_items::table.on(starred_items::feed_item_id
+                    .eq(feed_items::id)
+                    .and(starred_items::user_id.eq(uid))),
+            )
+            .filter(feed_items::feed_id.eq_any(subscribed))
+            .select((
+                feed_items::all_columns,
+                (
+                    read_items::read_at.nullable(),
+                    starred_items::id.nullable().is_not_null(),
+                ),
+            ))
+            .order((feed_items::first_seen.desc(), feed_items::id.desc()))
+            .offset(offset)
+            .limit(limit)
+            .into_boxed();
+        if filter.unread {
+            query = query.filter(read_items::id.is_null());
+        }
+        if filter.starred {
+            query = query.filter(starred_items::id.is_not_null());
+        }
+        query.load(conn)
+    }
+
+    /// A feed's items first seen after `since`, oldest first like
+    /// FeedItem::items_after, leaving out those the filter rules out for
+    /// the user. Empty if they aren't subscribed to it.
+    pub fn feed_items_after(
+        conn: &mut DbConnection,
+        uid: UserId,
+        fid: FeedId,
+        since: i64,
+        filter: StateFilter,
+    ) -> QueryResult<Vec<FeedItem>> {
+        let query = ItemQuery {
+            feed_id: Some(fid),
+            after: Some(since),
+            state: filter,
+            ..Default::default()
+        };
+        query
+            .matching(uid)
+            .order(feed_items::first_seen.asc())
+            .load(conn)
+    }
+
+    /// The user's state for each of the items they've read or starred
+    pub fn states(
+        conn: &mut DbConnection,
+        uid: UserId,
+        item_ids: &[i32],
+    ) -> QueryResult<HashMap<i32, ItemState>> {
+        let read: Vec<(i32, i64)> = read_items::table
+            .filter(read_items::user_id.eq(uid))
+            .filter(read_items::feed_item_id.eq_any(item_ids))
+            .select((read_items::feed_item_id, read_items::read_at))
+            .load(conn)?;
+        let starred: Vec<i32> = starred_items::table
+            .filter(starred_items::user_id.eq(uid))
+            .filter(starred_items::feed_item_id.eq_any(item_ids))
+            .select(starred_items::feed_item_id)
+            .load(conn)?;
+        let mut states: HashMap<i32, ItemState> = HashMap::new();
+        for (item_id, read_at) in read {
+            states.entry(item_id).or_default().read_at = Some(read_at);
+        }
+        for item_id in starred {
+            states.entry(item_id).or_default().starred = true;
+        }
+        Ok(states)
+    }
+
+    /// How many items in the user's reader view they haven't read
+    pub fn unread_count(conn: &mut DbConnection, uid: UserId) -> QueryResult<i64> {
+        let subscribed = subscriptions::table
+            .filter(subscriptions::user_id.eq(uid))
+            .select(subscriptions::feed_id);
+        feed_items::table
+            .left_join(
+                read_items::table.on(read_items::feed_item_id
+                    .eq(feed_items::id)
+                    .and(read_items::user_id.eq(uid))),
+            )
+            .filter(feed_items::feed_id.eq_any(subscribed))
+            .filter(read_items::id.is_null())
+            .count()
+            .get_result(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{
+            feed_item::NewFeedItem, starred_item::StarredItem, subscription::NewSubscription,
+        },
+        test_helpers::test_helpers::get_test_db_connection,
+    };
+
+    fn titles(page: &[(FeedItem, ItemState)]) -> Vec<&str> {
+        page.iter().map(|(item, _)| item.title.as_str()).collect()
+    }
+
+    const ALL: StateFilter = StateFilter {
+        unread: false,
+        starred: false,
+    };
+
+    #[test]
+    fn test_reader_page() {
+        let mut conn = get_test_db_connection();
+        NewSubscription {
+            user_id: UserId(1),
+            feed_id: FeedId(1),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        for (feed_id, title, first_seen) in [(1, "old", 100), (1, "new", 200), (2, "other", 300)] {
+            NewFeedItem {
+                feed_id: FeedId(feed_id),
+                title,
+                link: title,
+                pub_date: first_seen,
+                first_seen,
+                ..Default::default()
+            }
+            .insert(&mut conn);
+        }
+
+        let page = ReadItem::reader_page(&mut conn, UserId(1), ALL, 0, 10).unwrap();
+        // items from feeds the user isn't subscribed to aren't shown
+        assert_eq!(titles(&page), vec!["new", "old"]);
+        assert_eq!(ReadItem::unread_count(&mut conn, UserId(1)), Ok(2));
+
+        let new_id = page[0].0.id;
+        ReadItem::mark_read(&mut conn, UserId(1), new_id, 1000).unwrap();
+        ReadItem::mark_read(&mut conn, UserId(1), new_id, 2000).unwrap();
+        assert_eq!(
+            ReadItem::state(&mut conn, UserId(1), new_id).map(|state| state.read_at),
+            Ok(Some(1000))
+        );
+        let page = ReadItem::reader_page(&mut conn, UserId(1), ALL, 0, 1).unwrap();
+        assert_eq!(page[0].1.read_at, Some(1000));
+        let unread = StateFilter {
+            unread: true,
+            ..ALL
+        };
+        let unread = ReadItem::reader_page(&mut conn, UserId(1), unread, 0, 10).unwrap();
+        assert_eq!(titles(&unread), vec!["old"]);
+        // another user's reads don't count
+        assert_eq!(
+            ReadItem::state(&mut conn, UserId(2), new_id).map(|state| state.read_at),
+            Ok(None)
+        );
+
+        assert_eq!(ReadItem::mark_unread(&mut conn, UserId(1), new_id), Ok(1));
+        assert_eq!(ReadItem::unread_count(&mut conn, UserId(1)), Ok(2));
+    }
+
+    #[test]
+    fn test_state_filters() {
+        let mut conn = get_test_db_connection();
+        NewSubscription {
+            user_id: UserId(1),
+            feed_id: FeedId(1),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        for (title, first_seen) in [("read", 100), ("starred", 200), ("both", 300)] {
+            NewFeedItem {
+                feed_id: FeedId(1),
+                title,
+                link: title,
+                pub_date: first_seen,
+                first_seen,
+                ..Default::default()
+            }
+            .insert(&mut conn);
+        }
+        let ids: Vec<i32> = FeedItem::items_after(&mut conn, FeedId(1), 0)
+            .iter()
+            .map(|item| item.id)
+            .collect();
+        ReadItem::mark_read(&mut conn, UserId(1), ids[0], 1000).unwrap();
+        ReadItem::mark_read(&mut conn, UserId(1), ids[2], 1000).unwrap();
+        StarredItem::star(&mut conn, UserId(1), ids[1], 1000).unwrap();
+        StarredItem::star(&mut conn, UserId(1), ids[2], 1000).unwrap();
+        // someone else's state doesn't count
+        StarredItem::star(&mut conn, UserId(2), ids[0], 1000).unwrap();
+
+        let page = ReadItem::reader_page(&mut conn, UserId(1), ALL, 0, 10).unwrap();
+        let states: Vec<ItemState> = page.iter().map(|(_, state)| *state).collect();
+        assert_eq!(
+            states,
+            vec![
+                ItemState {
+                    read_at: Some(1000),
+                    starred: true
+                },
+                ItemState {
+                    read_at: None,
+                    starred: true
+                },
+                ItemState {
+                    read_at: Some(1000),
+                    starred: false
+                },
+            ]
+        );
+
+        let filtered = |conn: &mut DbConnection, unread, starred| {
+            let filter = StateFilter { unread, starred };
+            let page = ReadItem::reader_page(conn, UserId(1), filter, 0, 10).unwrap();
+            let after = ReadItem::feed_items_after(conn, UserId(1), FeedId(1), 0, filter).unwrap();
+            // the feed's items come oldest first
+            let mut after: Vec<String> = after.into_iter().map(|item| item.title).collect();
+            after.reverse();
+            assert_eq!(titles(&page), after);
+            after
+        };
+        assert_eq!(filtered(&mut conn, true, false), vec!["starred"]);
+        assert_eq!(filtered(&mut conn, false, true), vec!["both", "starred"]);
+        assert_eq!(filtered(&mut conn, true, true), vec!["starred"]);
+        assert_eq!(filtered(&mut conn, false, false).len(), 3);
+    }
+
+    #[test]
+    fn test_item_query() {
+        let mut conn = get_test_db_connection();
+        for feed_id in [1, 2] {
+            NewSubscription {
+                user_id: UserId(1),
+                feed_id: FeedId(feed_id),
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap();
+        }
+        let items = [
+            (1, "Rust 1.70", 100),
+            (1, "100% faster", 200),
+            (2, "rusty_hook", 200),
+            (2, "Python", 300),
+            (3, "Rust elsewhere", 300),
+        ];
+        for (feed_id, title, first_seen) in items {
+            NewFeedItem {
+                feed_id: FeedId(feed_id),
+                title,
+                link: title,
+                pub_date: first_seen,
+                first_seen,
+                ..Default::default()
+            }
+            .insert(&mut conn);
+        }
+        let list = |conn: &mut DbConnection, query: &ItemQuery| {
+            let page = query.page(conn, UserId(1), None, 10).unwrap();
+            assert_eq!(query.count(conn, UserId(1)), Ok(page.len() as i64));
+            titles(&page)
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // unsubscribed feeds aren't listed, and ties go by id
+        let all = ItemQuery::default();
+        assert_eq!(
+            list(&mut conn, &all),
+            vec!["Python", "rusty_hook", "100% faster", "Rust 1.70"]
+        );
+        let search = |search: &str| ItemQuery {
+            search: Some(search.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            list(&mut conn, &search("RUST")),
+            vec!["rusty_hook", "Rust 1.70"]
+        );
+        // wildcards are matched as they are
+        assert_eq!(list(&mut conn, &search("0%")), vec!["100% faster"]);
+        assert_eq!(list(&mut conn, &search("y_h")), vec!["rusty_hook"]);
+        let ranged = ItemQuery {
+            feed_id: Some(FeedId(2)),
+            after: Some(100),
+            before: Some(300),
+            ..Default::default()
+        };
+        assert_eq!(list(&mut conn, &ranged), vec!["rusty_hook"]);
+
+        // a page starts after the cursor, even with ties on first_seen
+        let first = all.page(&mut conn, UserId(1), None, 2).unwrap();
+        let cursor = ItemCursor::after(&first[1].0);
+        assert_eq!(cursor.to_string().parse(), Ok(cursor));
+        let next = all.page(&mut conn, UserId(1), Some(cursor), 2).unwrap();
+        assert_eq!(titles(&next), vec!["100% faster", "Rust 1.70"]);
+        assert_eq!("bm9wZQ".parse::<ItemCursor>(), Err(InvalidCursor));
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use super::settings::{self, NewSetting, Setting};
+use crate::db::DbConnection;
+
+const MODE: &str = "registration.mode";
+
+/// Who can get an account without an admin creating it
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    /// only admins create accounts
+    Closed,
+    /// admins create accounts or send invites
+    #[default]
+    Invite,
+    /// anyone can sign up from the registration page too, and can log in
+    /// once an admin approves them
+    OpenWithApproval,
+}
+
+impl RegistrationMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RegistrationMode::Closed => "closed",
+            RegistrationMode::Invite => "invite",
+            RegistrationMode::OpenWithApproval => "open_with_approval",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "closed" => Some(RegistrationMode::Closed),
+            "invite" => Some(RegistrationMode::Invite),
+            "open_with_approval" => Some(RegistrationMode::OpenWithApproval),
+            _ => None,
+        }
+    }
+
+    pub fn allows_invites(&self) -> bool {
+        *self != RegistrationMode::Closed
+    }
+
+    pub fn allows_sign_up(&self) -> bool {
+        *self == RegistrationMode::OpenWithApproval
+    }
+}
+
+/// The instance's registration mode, stored as a system setting
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Registration {
+    pub registration_mode: RegistrationMode,
+}
+
+impl Registration {
+    /// The current mode, invite-only if it was never set
+    pub fn load(conn: &mut DbConnection) -> Registration {
+        let registration_mode = Setting::get(conn, MODE, None)
+            .ok()
+            .and_then(|setting| RegistrationMode::parse(&setting.value))
+            .unwrap_or_default();
+        Registration { registration_mode }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        let setting = NewSetting {
+            user_id: None,
+            key: MODE.to_string(),
+            value: self.registration_mode.as_str().to_string(),
+        };
+        Setting::set(conn, &setting).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        let registration = Registration::load(&mut conn);
+        assert_eq!(registration.registration_mode, RegistrationMode::Invite);
+        assert!(registration.registration_mode.allows_invites());
+        assert!(!registration.registration_mode.allows_sign_up());
+
+        for mode in [
+            RegistrationMode::Closed,
+            RegistrationMode::OpenWithApproval,
+            RegistrationMode::Invite,
+        ] {
+            let registration = Registration {
+                registration_mode: mode,
+            };
+            registration.save(&mut conn).unwrap();
+            assert_eq!(Registration::load(&mut conn), registration);
+        }
+    }
+}
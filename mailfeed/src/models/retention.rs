@@ -0,0 +1,453 @@
+use std::collections::HashMap;
+
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Integer},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    feed::Feed,
+    ids::FeedId,
+    max_item_age::MAX_ITEM_AGE_DAYS_LIMIT,
+    settings::{self, NewSetting, Setting},
+};
+use crate::db::DbConnection;
+use crate::schema::*;
+use crate::security::validation::{Validate, ValidationErrors};
+
+const RETENTION_DAYS: &str = "retention.days";
+const MAX_ITEMS_PER_FEED: &str = "retention.max_items_per_feed";
+const DELETE_ORPHANED_FEEDS: &str = "retention.delete_orphaned_feeds";
+const VACUUM_DAYS: &str = "retention.vacuum_days";
+const LAST_VACUUM_AT: &str = "retention.last_vacuum_at";
+
+/// Highest per-feed cap an admin may set
+pub const MAX_ITEMS_PER_FEED_LIMIT: i32 = 100_000;
+const DAY: i64 = 24 * 60 * 60;
+/// Items deleted per statement, to stay well under SQLite's limit on
+/// bound parameters
+const DELETE_BATCH_SIZE: usize = 500;
+
+/// How long items are kept, stored as system settings. Pruning runs with
+/// the rest of the database maintenance. Starred items, and items still
+/// waiting to be sent to an active subscription, are always kept.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct Retention {
+    /// items first seen longer ago than this are deleted, 0 to keep them
+    pub retention_days: i32,
+    /// only each feed's newest items are kept, 0 for no cap
+    pub max_items_per_feed: i32,
+    /// delete feeds nobody is subscribed to, with their items
+    #[serde(default)]
+    pub delete_orphaned_feeds: bool,
+    /// run a full VACUUM at most this often, 0 never
+    #[serde(default)]
+    pub vacuum_days: i32,
+}
+
+#[derive(QueryableByName)]
+struct PruneCandidate {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Integer)]
+    feed_id: i32,
+    #[diesel(sql_type = BigInt)]
+    pub_date: i64,
+}
+
+/// Items that may be pruned: not starred, and already sent to all of their
+/// feed's active subscriptions
+const PRUNABLE: &str = "
+    item.id NOT IN (SELECT feed_item_id FROM starred_items)
+    AND item.first_seen <= COALESCE((
+        SELECT MIN(last_sent_time) FROM subscriptions
+        WHERE subscriptions.feed_id = item.feed_id AND subscriptions.is_active
+    ), item.first_seen)";
+
+/// Items first seen before `$1`
+const EXPIRED: &str = "
+    SELECT item.id, item.feed_id, item.pub_date FROM feed_items AS item
+    WHERE item.first_seen < $1";
+
+/// Each feed's items past the newest `$1`
+const OVER_CAP: &str = "
+    SELECT item.id, item.feed_id, item.pub_date FROM (
+        SELECT id, feed_id, pub_date, first_seen, ROW_NUMBER() OVER (
+            PARTITION BY feed_id ORDER BY first_seen DESC, id DESC
+        ) AS position
+        FROM feed_items
+    ) AS item
+    WHERE item.position > $1";
+
+/// Delete the items and what users kept about them, and remember the
+/// newest publish date pruned from each feed
+fn delete_items(conn: &mut DbConnection, candidates: &[(i32, FeedId, i64)]) -> QueryResult<usize> {
+    let mut pruned_through: HashMap<FeedId, i64> = HashMap::new();
+    for (_, feed_id, pub_date) in candidates {
+        let newest = pruned_through.entry(*feed_id).or_default();
+        *newest = (*newest).max(*pub_date);
+    }
+    conn.transaction(|conn| {
+        let mut deleted = 0;
+        for batch in candidates.chunks(DELETE_BATCH_SIZE) {
+            let ids: Vec<i32> = batch.iter().map(|(id, _, _)| *id).collect();
+            diesel::delete(read_items::table.filter(read_items::feed_item_id.eq_any(&ids)))
+                .execute(conn)?;
+            diesel::delete(starred_items::table.filter(starred_items::feed_item_id.eq_any(&ids)))
+                .execute(conn)?;
+            deleted += diesel::delete(feed_items::table.filter(feed_items::id.eq_any(&ids)))
+                .execute(conn)?;
+        }
+        for (feed_id, newest) in pruned_through {
+            diesel::update(
+                feeds::table
+                    .find(feed_id)
+                    .filter(feeds::pruned_through.lt(newest)),
+            )
+            .set(feeds::pruned_through.eq(newest))
+            .execute(conn)?;
+        }
+        Ok(deleted)
+    })
+}
+
+impl Retention {
+    /// The current settings, keeping everything if they were never set
+    pub fn load(conn: &mut DbConnection) -> Retention {
+        let mut get = |key| {
+            Setting::get(conn, key, None)
+                .ok()
+                .map(|setting| setting.value)
+        };
+        Retention {
+            retention_days: get(RETENTION_DAYS)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_default(),
+            max_items_per_feed: get(MAX_ITEMS_PER_FEED)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_default(),
+            delete_orphaned_feeds: get(DELETE_ORPHANED_FEEDS).is_some_and(|value| value == "true"),
+            vacuum_days: get(VACUUM_DAYS)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        for (key, value) in [
+            (RETENTION_DAYS, self.retention_days.to_string()),
+            (MAX_ITEMS_PER_FEED, self.max_items_per_feed.to_string()),
+            (
+                DELETE_ORPHANED_FEEDS,
+                self.delete_orphaned_feeds.to_string(),
+            ),
+            (VACUUM_DAYS, self.vacuum_days.to_string()),
+        ] {
+            let setting = NewSetting {
+                user_id: None,
+                key: key.to_string(),
+                value,
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// Whether there are any items to prune by age or count
+    pub fn prunes_items(&self) -> bool {
+        self.retention_days > 0 || self.max_items_per_feed > 0
+    }
+
+    /// Delete items older than the retention period or past each feed's
+    /// cap, returning how many were deleted. Items an active subscription
+    /// hasn't been sent yet are kept either way.
+    pub fn prune_items(&self, conn: &mut DbConnection, now: i64) -> QueryResult<usize> {
+        let mut candidates: Vec<PruneCandidate> = Vec::new();
+        if self.retention_days > 0 {
+            candidates.extend(
+                diesel::sql_query(format!("{} AND {}", EXPIRED, PRUNABLE))
+                    .bind::<BigInt, _>(now - self.retention_days as i64 * DAY)
+                    .load::<PruneCandidate>(conn)?,
+            );
+        }
+        if self.max_items_per_feed > 0 {
+            candidates.extend(
+                diesel::sql_query(format!("{} AND {}", OVER_CAP, PRUNABLE))
+                    .bind::<Integer, _>(self.max_items_per_feed)
+                    .load::<PruneCandidate>(conn)?,
+            );
+        }
+        let mut candidates: Vec<(i32, FeedId, i64)> = candidates
+            .into_iter()
+            .map(|item| (item.id, FeedId(item.feed_id), item.pub_date))
+            .collect();
+        candidates.sort_unstable_by_key(|(id, _, _)| *id);
+        candidates.dedup_by_key(|(id, _, _)| *id);
+        delete_items(conn, &candidates)
+    }
+
+    /// Delete feeds without subscriptions, unless someone starred one of
+    /// their items. Returns how many were deleted.
+    pub fn prune_orphaned_feeds(conn: &mut DbConnection) -> QueryResult<usize> {
+        let subscribed = subscriptions::table.select(subscriptions::feed_id);
+        let starred = starred_items::table
+            .inner_join(feed_items::table)
+            .select(feed_items::feed_id);
+        let orphaned: Vec<FeedId> = feeds::table
+            .filter(feeds::id.ne_all(subscribed))
+            .filter(feeds::id.ne_all(starred))
+            .select(feeds::id)
+            .load(conn)?;
+        let mut deleted = 0;
+        for feed_id in orphaned {
+            let items: Vec<(i32, FeedId, i64)> = feed_items::table
+                .filter(feed_items::feed_id.eq(feed_id))
+                .select((feed_items::id, feed_items::feed_id, feed_items::pub_date))
+                .load(conn)?;
+            delete_items(conn, &items)?;
+            if Feed::delete(conn, feed_id) {
+                log::info!("Deleted feed {}, which had no subscriptions", feed_id.0);
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Whether a full VACUUM is due, and if so, records that it's starting
+    pub fn start_vacuum(&self, conn: &mut DbConnection, now: i64) -> bool {
+        if self.vacuum_days <= 0 {
+            return false;
+        }
+        let last = Setting::get(conn, LAST_VACUUM_AT, None)
+            .ok()
+            .and_then(|setting| setting.value.parse::<i64>().ok());
+        if last.is_some_and(|last| now - last < self.vacuum_days as i64 * DAY) {
+            return false;
+        }
+        let setting = NewSetting {
+            user_id: None,
+            key: LAST_VACUUM_AT.to_string(),
+            value: now.to_string(),
+        };
+        if let Err(e) = Setting::set(conn, &setting) {
+            log::warn!("Error recording vacuum time: {}", e);
+        }
+        true
+    }
+}
+
+impl Validate for Retention {
+    fn check(&self, errors: &mut ValidationErrors) {
+        for (field, days) in [
+            ("retention_days", self.retention_days),
+            ("vacuum_days", self.vacuum_days),
+        ] {
+            if !(0..=MAX_ITEM_AGE_DAYS_LIMIT).contains(&days) {
+                errors.add(
+                    field,
+                    format!("Must be between 0 and {}", MAX_ITEM_AGE_DAYS_LIMIT),
+                );
+            }
+        }
+        if !(0..=MAX_ITEMS_PER_FEED_LIMIT).contains(&self.max_items_per_feed) {
+            errors.add(
+                "max_items_per_feed",
+                format!("Must be between 0 and {}", MAX_ITEMS_PER_FEED_LIMIT),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{
+            feed::NewFeed,
+            feed_item::{FeedItem, NewFeedItem},
+            ids::UserId,
+            read_item::ReadItem,
+            starred_item::StarredItem,
+            subscription::{NewSubscription, PartialSubscription, Subscription},
+        },
+        test_helpers::test_helpers::get_test_db_connection,
+    };
+
+    fn add_items(conn: &mut DbConnection, feed_id: FeedId, first_seen: &[i64]) -> Vec<i32> {
+        first_seen
+            .iter()
+            .map(|first_seen| {
+                let link = format!("https://example.com/{}/{}", feed_id.0, first_seen);
+                NewFeedItem {
+                    feed_id,
+                    title: "item",
+                    link: &link,
+                    pub_date: *first_seen,
+                    first_seen: *first_seen,
+                    ..Default::default()
+                }
+                .insert(conn)
+                .unwrap()
+                .id
+            })
+            .collect()
+    }
+
+    fn remaining(conn: &mut DbConnection, feed_id: FeedId) -> Vec<i64> {
+        let mut first_seen: Vec<i64> = FeedItem::items_after(conn, feed_id, 0)
+            .iter()
+            .map(|item| item.first_seen)
+            .collect();
+        first_seen.sort();
+        first_seen
+    }
+
+    #[test]
+    fn test_prune_by_age() {
+        let mut conn = get_test_db_connection();
+        let feed = NewFeed {
+            url: "https://example.com/feed.xml",
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let ids = add_items(&mut conn, feed.id, &[DAY, 2 * DAY, 9 * DAY]);
+        StarredItem::star(&mut conn, UserId(1), ids[0], 0).unwrap();
+        ReadItem::mark_read(&mut conn, UserId(1), ids[1], 0).unwrap();
+
+        let retention = Retention {
+            retention_days: 5,
+            ..Default::default()
+        };
+        assert_eq!(retention.prune_items(&mut conn, 10 * DAY), Ok(1));
+        // starred items are kept
+        assert_eq!(remaining(&mut conn, feed.id), vec![DAY, 9 * DAY]);
+        assert_eq!(
+            ReadItem::state(&mut conn, UserId(1), ids[1]).map(|state| state.read_at),
+            Ok(None)
+        );
+        let feed = Feed::get_by_id(&mut conn, feed.id).unwrap();
+        assert_eq!(feed.pruned_through, 2 * DAY);
+    }
+
+    #[test]
+    fn test_prune_by_age_keeps_unsent_items() {
+        let mut conn = get_test_db_connection();
+        for (last_sent_time, is_active) in [(2 * DAY, true), (0, false)] {
+            NewSubscription {
+                user_id: UserId(1),
+                feed_id: FeedId(1),
+                last_sent_time,
+                is_active,
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap();
+        }
+        add_items(&mut conn, FeedId(1), &[DAY, 2 * DAY, 3 * DAY, 9 * DAY]);
+        add_items(&mut conn, FeedId(2), &[DAY, 9 * DAY]);
+
+        let retention = Retention {
+            retention_days: 5,
+            ..Default::default()
+        };
+        // the paused subscription doesn't hold items back
+        assert_eq!(retention.prune_items(&mut conn, 10 * DAY), Ok(3));
+        assert_eq!(remaining(&mut conn, FeedId(1)), vec![3 * DAY, 9 * DAY]);
+        assert_eq!(remaining(&mut conn, FeedId(2)), vec![9 * DAY]);
+    }
+
+    #[test]
+    fn test_prune_over_cap() {
+        let mut conn = get_test_db_connection();
+        let sub = NewSubscription {
+            user_id: UserId(1),
+            feed_id: FeedId(1),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        add_items(&mut conn, FeedId(1), &[100, 200, 300, 400]);
+        add_items(&mut conn, FeedId(2), &[100, 200]);
+        let retention = Retention {
+            max_items_per_feed: 1,
+            ..Default::default()
+        };
+
+        // nothing's been sent yet, so everything's kept for the subscription
+        assert_eq!(retention.prune_items(&mut conn, 1000), Ok(1));
+        assert_eq!(remaining(&mut conn, FeedId(1)), vec![100, 200, 300, 400]);
+        assert_eq!(remaining(&mut conn, FeedId(2)), vec![200]);
+
+        let sent = PartialSubscription {
+            last_sent_time: Some(200),
+            ..Default::default()
+        };
+        Subscription::update(&mut conn, sub.id, &sent).unwrap();
+        assert_eq!(retention.prune_items(&mut conn, 1000), Ok(2));
+        assert_eq!(remaining(&mut conn, FeedId(1)), vec![300, 400]);
+    }
+
+    #[test]
+    fn test_prune_orphaned_feeds() {
+        let mut conn = get_test_db_connection();
+        let mut feeds = Vec::new();
+        for url in [
+            "https://a.example/",
+            "https://b.example/",
+            "https://c.example/",
+        ] {
+            feeds.push(
+                NewFeed {
+                    url,
+                    ..Default::default()
+                }
+                .insert(&mut conn)
+                .unwrap(),
+            );
+        }
+        NewSubscription {
+            user_id: UserId(1),
+            feed_id: feeds[0].id,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let starred = add_items(&mut conn, feeds[1].id, &[100]);
+        StarredItem::star(&mut conn, UserId(1), starred[0], 0).unwrap();
+        add_items(&mut conn, feeds[2].id, &[100, 200]);
+
+        assert_eq!(Retention::prune_orphaned_feeds(&mut conn), Ok(1));
+        assert!(Feed::get_by_id(&mut conn, feeds[1].id).is_some());
+        assert!(Feed::get_by_id(&mut conn, feeds[2].id).is_none());
+        assert!(remaining(&mut conn, feeds[2].id).is_empty());
+    }
+
+    #[test]
+    fn test_save_load_and_vacuum() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(Retention::load(&mut conn), Retention::default());
+        let retention = Retention {
+            retention_days: 90,
+            max_items_per_feed: 500,
+            delete_orphaned_feeds: true,
+            vacuum_days: 7,
+        };
+        assert!(retention.validate().is_ok());
+        retention.save(&mut conn).unwrap();
+        assert_eq!(Retention::load(&mut conn), retention);
+
+        assert!(retention.start_vacuum(&mut conn, 0));
+        assert!(!retention.start_vacuum(&mut conn, 6 * DAY));
+        assert!(retention.start_vacuum(&mut conn, 7 * DAY));
+        assert!(!Retention::default().start_vacuum(&mut conn, 100 * DAY));
+
+        let invalid = Retention {
+            retention_days: -1,
+            max_items_per_feed: MAX_ITEMS_PER_FEED_LIMIT + 1,
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+
+use super::settings::{self, NewSetting, Setting};
+use crate::db::DbConnection;
+use crate::security::validation::{Validate, ValidationErrors};
+
+/// Most attempts an admin may configure, including the first
+const MAX_ATTEMPTS_LIMIT: u32 = 10;
+/// Longest delay an admin may configure between attempts
+const MAX_DELAY_LIMIT_SECONDS: u64 = 60 * 60;
+
+/// A way of sending things out, each with its own retry policy
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Email,
+    Webhook,
+    /// pushing starred items to users' bookmark managers
+    Bookmarks,
+    /// saved search matches sent by Telegram
+    Telegram,
+    /// subscriptions delivered to Discord
+    Discord,
+    /// subscriptions delivered to Matrix rooms
+    Matrix,
+    /// ntfy and Gotify notifications
+    Push,
+}
+
+impl Channel {
+    fn setting_key(&self, name: &str) -> String {
+        let channel = match self {
+            Channel::Email => "email",
+            Channel::Webhook => "webhook",
+            Channel::Bookmarks => "bookmarks",
+            Channel::Telegram => "telegram",
+            Channel::Discord => "discord",
+            Channel::Matrix => "matrix",
+            Channel::Push => "push",
+        };
+        format!("retry.{}.{}", channel, name)
+    }
+}
+
+/// How a sender retries a failed delivery, stored as system settings.
+/// Waits between attempts start at the base delay and double each time up
+/// to the max delay, with jitter; see `tasks::retry`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RetryPolicy {
+    /// including the first attempt, so 1 means never retry
+    pub max_attempts: u32,
+    pub base_delay_seconds: u64,
+    pub max_delay_seconds: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay_seconds: 5,
+            max_delay_seconds: 60,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The channel's policy, with defaults for anything not set
+    pub fn load(conn: &mut DbConnection, channel: Channel) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        let mut get = |name| {
+            Setting::get(conn, &channel.setting_key(name), None)
+                .ok()
+                .and_then(|setting| setting.value.parse::<u64>().ok())
+        };
+        RetryPolicy {
+            max_attempts: get("max_attempts")
+                .map(|n| n as u32)
+                .unwrap_or(default.max_attempts),
+            base_delay_seconds: get("base_delay_seconds").unwrap_or(default.base_delay_seconds),
+            max_delay_seconds: get("max_delay_seconds").unwrap_or(default.max_delay_seconds),
+        }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection, channel: Channel) -> Result<(), settings::Error> {
+        for (name, value) in [
+            ("max_attempts", self.max_attempts as u64),
+            ("base_delay_seconds", self.base_delay_seconds),
+            ("max_delay_seconds", self.max_delay_seconds),
+        ] {
+            let setting = NewSetting {
+                user_id: None,
+                key: channel.setting_key(name),
+                value: value.to_string(),
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// The longest wait before the given retry, counting from 1 for the
+    /// first retry
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u64.saturating_pow(retry.saturating_sub(1));
+        let seconds = self
+            .base_delay_seconds
+            .saturating_mul(factor)
+            .min(self.max_delay_seconds);
+        Duration::from_secs(seconds)
+    }
+}
+
+impl Validate for RetryPolicy {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if self.max_attempts == 0 || self.max_attempts > MAX_ATTEMPTS_LIMIT {
+            errors.add(
+                "max_attempts",
+                format!("Must be between 1 and {}", MAX_ATTEMPTS_LIMIT),
+            );
+        }
+        if self.max_delay_seconds > MAX_DELAY_LIMIT_SECONDS {
+            errors.add(
+                "max_delay_seconds",
+                format!("Must be at most {}", MAX_DELAY_LIMIT_SECONDS),
+            );
+        }
+        if self.base_delay_seconds > self.max_delay_seconds {
+            errors.add(
+                "base_delay_seconds",
+                "Must not be more than max_delay_seconds",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(
+            RetryPolicy::load(&mut conn, Channel::Email),
+            RetryPolicy::default()
+        );
+
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay_seconds: 1,
+            max_delay_seconds: 30,
+        };
+        policy.save(&mut conn, Channel::Email).unwrap();
+        assert_eq!(RetryPolicy::load(&mut conn, Channel::Email), policy);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(RetryPolicy::default().validate().is_ok());
+
+        let policy = RetryPolicy {
+            max_attempts: 0,
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+
+        let policy = RetryPolicy {
+            base_delay_seconds: 120,
+            max_delay_seconds: 60,
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+
+        let policy = RetryPolicy {
+            max_delay_seconds: 24 * 60 * 60,
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay_seconds: 5,
+            max_delay_seconds: 60,
+        };
+        let backoffs: Vec<u64> = (1..=6)
+            .map(|retry| policy.backoff(retry).as_secs())
+            .collect();
+        assert_eq!(backoffs, vec![5, 10, 20, 40, 60, 60]);
+        assert_eq!(policy.backoff(100).as_secs(), 60);
+    }
+}
@@ -0,0 +1,143 @@
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    sql_types::Text,
+    AsExpression, FromSqlRow,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::db::to_sql_as_text;
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Unknown role '{0}'")]
+pub struct UnknownRole(String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    Admin,
+    User,
+}
+
+impl Role {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::User => "user",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = UnknownRole;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "admin" => Ok(Role::Admin),
+            "user" => Ok(Role::User),
+            other => Err(UnknownRole(other.to_string())),
+        }
+    }
+}
+
+/// A user's roles, stored and serialized as a comma-separated list like
+/// `user,admin`
+#[derive(Debug, Clone, Default, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub struct Roles(BTreeSet<Role>);
+
+impl Roles {
+    pub fn has(&self, role: Role) -> bool {
+        self.0.contains(&role)
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.has(Role::Admin)
+    }
+}
+
+impl From<Role> for Roles {
+    fn from(role: Role) -> Self {
+        Roles(BTreeSet::from([role]))
+    }
+}
+
+impl FromStr for Roles {
+    type Err = UnknownRole;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|role| !role.trim().is_empty())
+            .map(Role::from_str)
+            .collect::<Result<_, _>>()
+            .map(Roles)
+    }
+}
+
+impl fmt::Display for Roles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let roles: Vec<&str> = self.0.iter().map(Role::as_str).collect();
+        f.write_str(&roles.join(","))
+    }
+}
+
+impl Serialize for Roles {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Roles {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for Roles
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+        Ok(String::from_sql(bytes)?.parse()?)
+    }
+}
+
+to_sql_as_text!(Roles, |roles| roles.to_string());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_roles() {
+        let roles: Roles = "user, admin".parse().unwrap();
+        assert!(roles.is_admin());
+        assert!(roles.has(Role::User));
+        assert_eq!(roles.to_string(), "admin,user");
+
+        let roles: Roles = "user".parse().unwrap();
+        assert!(!roles.is_admin());
+        assert_eq!("".parse::<Roles>(), Ok(Roles::default()));
+
+        // near misses aren't quietly treated as some other role
+        assert_eq!(
+            "Admin".parse::<Roles>(),
+            Err(UnknownRole("Admin".to_string()))
+        );
+        assert!("user,admn".parse::<Roles>().is_err());
+    }
+
+    #[test]
+    fn test_serialize_roles() {
+        let roles = Roles::from(Role::Admin);
+        assert_eq!(serde_json::to_string(&roles).unwrap(), "\"admin\"");
+        let roles: Roles = serde_json::from_str("\"admin,user\"").unwrap();
+        assert!(roles.is_admin());
+        assert!(serde_json::from_str::<Roles>("\"root\"").is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, FromSqlRow},
+    prelude::*,
+    serialize::{self, Output, ToSql},
+    sql_types::Integer,
+    AsExpression,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ids::{FeedId, SavedSearchId, UserId},
+    search_query::SearchQuery,
+};
+use crate::db::DbConnection;
+use crate::schema::*;
+use crate::security::validation::{Validate, ValidationErrors};
+
+/// How a user is told about new items matching a saved search
+#[repr(i32)]
+#[derive(Debug, Serialize, Deserialize, AsExpression, Clone, Copy, PartialEq, FromSqlRow)]
+#[diesel(sql_type=Integer)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyBy {
+    Email = 0,
+    Telegram = 1,
+}
+
+impl<DB> FromSql<Integer, DB> for NotifyBy
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(NotifyBy::Email),
+            1 => Ok(NotifyBy::Telegram),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl<DB> ToSql<Integer, DB> for NotifyBy
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            NotifyBy::Email => 0.to_sql(out),
+            NotifyBy::Telegram => 1.to_sql(out),
+        }
+    }
+}
+
+/// A search over newly fetched items from the user's feeds, like a
+/// subscription to everything matching it. Matches are sent as they're
+/// found rather than on a schedule.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Insertable, PartialEq)]
+#[diesel(table_name = saved_searches)]
+pub struct SavedSearch {
+    pub id: SavedSearchId,
+    pub user_id: UserId,
+    pub name: String,
+    pub query: String,
+    pub notify_by: NotifyBy,
+    /// where Telegram notifications go
+    pub telegram_chat_id: Option<String>,
+    pub is_active: bool,
+    pub created_at: i64,
+    /// when an item last matched, zero if never
+    pub last_matched_at: i64,
+    pub match_count: i32,
+    /// send matches with a lead image as Telegram photos rather than text
+    pub telegram_photos: bool,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = saved_searches)]
+pub struct NewSavedSearch {
+    #[serde(skip_deserializing)]
+    pub user_id: UserId,
+    pub name: String,
+    pub query: String,
+    pub notify_by: NotifyBy,
+    pub telegram_chat_id: Option<String>,
+    #[serde(default)]
+    pub telegram_photos: bool,
+    #[serde(skip_deserializing)]
+    pub created_at: i64,
+}
+
+#[derive(Debug, Default, Deserialize, AsChangeset)]
+#[diesel(table_name = saved_searches)]
+pub struct PartialSavedSearch {
+    pub name: Option<String>,
+    pub query: Option<String>,
+    pub notify_by: Option<NotifyBy>,
+    /// Some(None) clears it
+    pub telegram_chat_id: Option<Option<String>>,
+    pub telegram_photos: Option<bool>,
+    pub is_active: Option<bool>,
+}
+
+fn check_query(errors: &mut ValidationErrors, query: &str) {
+    if let Err(e) = SearchQuery::parse(query) {
+        errors.add("query", e.to_string());
+    }
+}
+
+fn check_name(errors: &mut ValidationErrors, name: &str) {
+    if name.trim().is_empty() {
+        errors.add("name", "Must not be empty");
+    }
+}
+
+impl Validate for NewSavedSearch {
+    fn check(&self, errors: &mut ValidationErrors) {
+        check_name(errors, &self.name);
+        check_query(errors, &self.query);
+        if self.notify_by == NotifyBy::Telegram && self.telegram_chat_id.is_none() {
+            errors.add("telegram_chat_id", "Required for Telegram notifications");
+        }
+    }
+}
+
+impl Validate for PartialSavedSearch {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(name) = &self.name {
+            check_name(errors, name);
+        }
+        if let Some(query) = &self.query {
+            check_query(errors, query);
+        }
+    }
+}
+
+impl NewSavedSearch {
+    pub fn insert(&self, conn: &mut DbConnection) -> QueryResult<SavedSearch> {
+        diesel::insert_into(saved_searches::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl SavedSearch {
+    pub fn get_for_user(conn: &mut DbConnection, uid: UserId) -> QueryResult<Vec<SavedSearch>> {
+        use crate::schema::saved_searches::dsl::*;
+        saved_searches.filter(user_id.eq(uid)).order(id).load(conn)
+    }
+
+    pub fn count_for_user(conn: &mut DbConnection, uid: UserId) -> QueryResult<i64> {
+        use crate::schema::saved_searches::dsl::*;
+        saved_searches
+            .filter(user_id.eq(uid))
+            .count()
+            .get_result(conn)
+    }
+
+    /// Active searches of users with an active subscription to the feed,
+    /// since a search only covers the user's own feeds
+    pub fn get_active_for_feed(
+        conn: &mut DbConnection,
+        fid: FeedId,
+    ) -> QueryResult<Vec<SavedSearch>> {
+        let subscribers = subscriptions::table
+            .filter(subscriptions::feed_id.eq(fid))
+            .filter(subscriptions::is_active.eq(true))
+            .select(subscriptions::user_id);
+        saved_searches::table
+            .filter(saved_searches::is_active.eq(true))
+            .filter(saved_searches::user_id.eq_any(subscribers))
+            .load(conn)
+    }
+
+    /// Only the user's own searches can be changed
+    pub fn update(
+        conn: &mut DbConnection,
+        uid: UserId,
+        search_id: SavedSearchId,
+        update: &PartialSavedSearch,
+    ) -> QueryResult<SavedSearch> {
+        use crate::schema::saved_searches::dsl::*;
+        diesel::update(saved_searches.find(search_id).filter(user_id.eq(uid)))
+            .set(update)
+            .get_result(conn)
+    }
+
+    pub fn delete(
+        conn: &mut DbConnection,
+        uid: UserId,
+        search_id: SavedSearchId,
+    ) -> QueryResult<usize> {
+        use crate::schema::saved_searches::dsl::*;
+        diesel::delete(saved_searches.find(search_id).filter(user_id.eq(uid))).execute(conn)
+    }
+
+    pub fn record_matches(
+        conn: &mut DbConnection,
+        search_id: SavedSearchId,
+        count: usize,
+        now: i64,
+    ) -> QueryResult<usize> {
+        use crate::schema::saved_searches::dsl::*;
+        diesel::update(saved_searches.find(search_id))
+            .set((
+                last_matched_at.eq(now),
+                match_count.eq(match_count + count as i32),
+            ))
+            .execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::subscription::NewSubscription, test_helpers::test_helpers::get_test_db_connection,
+    };
+
+    fn new_search(user_id: UserId, query: &str) -> NewSavedSearch {
+        NewSavedSearch {
+            user_id,
+            name: "Rust".to_string(),
+            query: query.to_string(),
+            notify_by: NotifyBy::Email,
+            telegram_chat_id: None,
+            telegram_photos: false,
+            created_at: 1000,
+        }
+    }
+
+    #[test]
+    fn test_active_for_feed() {
+        let mut conn = get_test_db_connection();
+        NewSubscription {
+            user_id: UserId(1),
+            feed_id: FeedId(1),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let search = new_search(UserId(1), "rust").insert(&mut conn).unwrap();
+        // not subscribed to the feed
+        new_search(UserId(2), "rust").insert(&mut conn).unwrap();
+
+        assert_eq!(
+            SavedSearch::get_active_for_feed(&mut conn, FeedId(1)),
+            Ok(vec![search.clone()])
+        );
+        assert_eq!(
+            SavedSearch::get_active_for_feed(&mut conn, FeedId(2)),
+            Ok(vec![])
+        );
+
+        SavedSearch::record_matches(&mut conn, search.id, 2, 2000).unwrap();
+        SavedSearch::record_matches(&mut conn, search.id, 1, 3000).unwrap();
+        let paused = PartialSavedSearch {
+            is_active: Some(false),
+            ..Default::default()
+        };
+        let updated = SavedSearch::update(&mut conn, UserId(1), search.id, &paused).unwrap();
+        assert_eq!((updated.match_count, updated.last_matched_at), (3, 3000));
+        assert_eq!(
+            SavedSearch::get_active_for_feed(&mut conn, FeedId(1)),
+            Ok(vec![])
+        );
+
+        // other users' searches can't be changed
+        assert_eq!(SavedSearch::delete(&mut conn, UserId(2), search.id), Ok(0));
+        assert_eq!(SavedSearch::delete(&mut conn, UserId(1), search.id), Ok(1));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(new_search(UserId(1), "rust AND async").validate().is_ok());
+        let invalid = NewSavedSearch {
+            name: " ".to_string(),
+            notify_by: NotifyBy::Telegram,
+            ..new_search(UserId(1), "(rust")
+        };
+        let errors = invalid.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["name", "query", "telegram_chat_id"]);
+    }
+}
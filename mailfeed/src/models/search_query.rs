@@ -0,0 +1,344 @@
+use std::{iter::Peekable, str::Chars};
+
+use thiserror::Error;
+
+/// Longest search that can be saved
+pub const MAX_QUERY_LENGTH: usize = 500;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ParseError {
+    #[error("Search is empty")]
+    Empty,
+    #[error("Search is longer than {} characters", MAX_QUERY_LENGTH)]
+    TooLong,
+    #[error("Missing closing quote")]
+    UnclosedQuote,
+    #[error("Unmatched parenthesis")]
+    UnmatchedParen,
+    #[error("Expected a search term, found {0}")]
+    ExpectedTerm(String),
+    #[error("Search must include a term that isn't negated")]
+    OnlyNegated,
+}
+
+/// A search over item text like `rust AND (async OR tokio) -"job posting"`.
+/// Terms next to each other must all match, `OR` matches either side, and
+/// `NOT` or a leading `-` excludes. Operators are only recognised in
+/// capitals. Quoted phrases match words in order, and a trailing `*`
+/// matches any word starting with the term. Matching ignores case and
+/// punctuation, and is by whole word.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchQuery {
+    Term { words: Vec<String>, prefix: bool },
+    And(Vec<SearchQuery>),
+    Or(Vec<SearchQuery>),
+    Not(Box<SearchQuery>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(word) => format!("'{}'", word),
+            Token::Phrase(phrase) => format!("\"{}\"", phrase),
+            Token::And => "AND".to_string(),
+            Token::Or => "OR".to_string(),
+            Token::Not => "NOT".to_string(),
+            Token::Open => "'('".to_string(),
+            Token::Close => "')'".to_string(),
+        }
+    }
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Result<SearchQuery, ParseError> {
+        if query.chars().count() > MAX_QUERY_LENGTH {
+            return Err(ParseError::TooLong);
+        }
+        let tokens = tokenize(query)?;
+        if tokens.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let parsed = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(ParseError::UnmatchedParen);
+        }
+        if !parsed.has_positive() {
+            return Err(ParseError::OnlyNegated);
+        }
+        Ok(parsed)
+    }
+
+    /// Whether the text, split with `words`, matches
+    pub fn matches(&self, text: &[String]) -> bool {
+        match self {
+            SearchQuery::Term { words, prefix } => {
+                let last = words.len() - 1;
+                text.windows(words.len()).any(|window| {
+                    window
+                        .iter()
+                        .zip(words)
+                        .enumerate()
+                        .all(|(i, (word, term))| {
+                            if *prefix && i == last {
+                                word.starts_with(term.as_str())
+                            } else {
+                                word == term
+                            }
+                        })
+                })
+            }
+            SearchQuery::And(parts) => parts.iter().all(|part| part.matches(text)),
+            SearchQuery::Or(parts) => parts.iter().any(|part| part.matches(text)),
+            SearchQuery::Not(part) => !part.matches(text),
+        }
+    }
+
+    /// Whether anything is needed to match, rather than only excluded
+    fn has_positive(&self) -> bool {
+        match self {
+            SearchQuery::Term { .. } => true,
+            SearchQuery::And(parts) => parts.iter().any(SearchQuery::has_positive),
+            SearchQuery::Or(parts) => parts.iter().all(SearchQuery::has_positive),
+            SearchQuery::Not(_) => false,
+        }
+    }
+}
+
+/// The lowercase words in the text, for matching against
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        closed = true;
+                        break;
+                    }
+                    phrase.push(c);
+                }
+                if !closed {
+                    return Err(ParseError::UnclosedQuote);
+                }
+                tokens.push(Token::Phrase(phrase));
+            }
+            '-' => {
+                chars.next();
+                tokens.push(Token::Not);
+            }
+            _ => {
+                let word = read_word(&mut chars);
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn read_word(chars: &mut Peekable<Chars>) -> String {
+    let mut word = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+            break;
+        }
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<SearchQuery, ParseError> {
+        let mut parts = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            parts.push(self.and()?);
+        }
+        Ok(collapse(parts, SearchQuery::Or))
+    }
+
+    fn and(&mut self) -> Result<SearchQuery, ParseError> {
+        let mut parts = vec![self.not()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Word(_) | Token::Phrase(_) | Token::Not | Token::Open) => {}
+                _ => break,
+            }
+            parts.push(self.not()?);
+        }
+        Ok(collapse(parts, SearchQuery::And))
+    }
+
+    fn not(&mut self) -> Result<SearchQuery, ParseError> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(SearchQuery::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<SearchQuery, ParseError> {
+        match self.next() {
+            Some(Token::Open) => {
+                let inner = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => Err(ParseError::UnmatchedParen),
+                }
+            }
+            Some(Token::Word(word)) => {
+                let (word, prefix) = match word.strip_suffix('*') {
+                    Some(stem) => (stem, true),
+                    None => (word.as_str(), false),
+                };
+                term(word, prefix, word)
+            }
+            Some(Token::Phrase(phrase)) => term(&phrase, false, &format!("\"{}\"", phrase)),
+            Some(token) => Err(ParseError::ExpectedTerm(token.describe())),
+            None => Err(ParseError::ExpectedTerm(
+                "the end of the search".to_string(),
+            )),
+        }
+    }
+}
+
+fn term(text: &str, prefix: bool, source: &str) -> Result<SearchQuery, ParseError> {
+    let words = words(text);
+    if words.is_empty() {
+        return Err(ParseError::ExpectedTerm(format!("'{}'", source)));
+    }
+    Ok(SearchQuery::Term { words, prefix })
+}
+
+fn collapse(
+    mut parts: Vec<SearchQuery>,
+    combine: fn(Vec<SearchQuery>) -> SearchQuery,
+) -> SearchQuery {
+    if parts.len() == 1 {
+        parts.remove(0)
+    } else {
+        combine(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(query: &str, text: &str) -> bool {
+        SearchQuery::parse(query).unwrap().matches(&words(text))
+    }
+
+    #[test]
+    fn test_matches() {
+        let title = "Announcing Tokio 2.0: async Rust, faster";
+        assert!(matches("rust AND async", title));
+        assert!(matches("rust async", title));
+        assert!(matches("RUST", title));
+        assert!(!matches("rust AND python", title));
+        assert!(matches("python OR tokio", title));
+        assert!(matches("rust -python", title));
+        assert!(!matches("rust NOT tokio", title));
+        assert!(matches("(python OR go) OR (rust AND faster)", title));
+        assert!(matches("\"async rust\"", title));
+        assert!(!matches("\"rust async\"", title));
+        assert!(matches("announc*", title));
+        // whole words only
+        assert!(!matches("rus", title));
+        assert!(!matches("trust", "rust"));
+        // lowercase operators are words
+        assert!(!matches("python or tokio", title));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(SearchQuery::parse("  "), Err(ParseError::Empty));
+        assert_eq!(
+            SearchQuery::parse("\"rust async"),
+            Err(ParseError::UnclosedQuote)
+        );
+        assert_eq!(
+            SearchQuery::parse("(rust OR go"),
+            Err(ParseError::UnmatchedParen)
+        );
+        assert_eq!(SearchQuery::parse("rust)"), Err(ParseError::UnmatchedParen));
+        assert_eq!(
+            SearchQuery::parse("rust AND"),
+            Err(ParseError::ExpectedTerm(
+                "the end of the search".to_string()
+            ))
+        );
+        assert_eq!(
+            SearchQuery::parse("OR rust"),
+            Err(ParseError::ExpectedTerm("OR".to_string()))
+        );
+        assert_eq!(
+            SearchQuery::parse("rust ---"),
+            Err(ParseError::ExpectedTerm(
+                "the end of the search".to_string()
+            ))
+        );
+        assert_eq!(SearchQuery::parse("-rust"), Err(ParseError::OnlyNegated));
+        assert_eq!(
+            SearchQuery::parse(&"a ".repeat(300)),
+            Err(ParseError::TooLong)
+        );
+    }
+}
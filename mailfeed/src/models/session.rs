@@ -0,0 +1,238 @@
+use diesel::prelude::*;
+use serde::Serialize;
+
+use super::ids::{SessionId, UserId};
+use crate::db::DbConnection;
+use crate::schema::*;
+
+/// How long a login lasts, which is how long its refresh token works
+pub const SESSION_LIFETIME_SECONDS: i64 = 60 * 60 * 24 * 7;
+/// How stale `last_used_at` may get, so not every request writes to the
+/// database
+const LAST_USED_PRECISION_SECONDS: i64 = 60;
+const MAX_USER_AGENT_LENGTH: usize = 256;
+
+/// A device the user is logged in on. The access and refresh tokens issued
+/// for it carry its id, and stop working once it's deleted.
+#[derive(Debug, Clone, Serialize, Queryable, Identifiable, PartialEq)]
+#[diesel(table_name = sessions)]
+pub struct Session {
+    pub id: SessionId,
+    pub user_id: UserId,
+    pub created_at: i64,
+    pub last_used_at: i64,
+    pub expires_at: i64,
+    /// as the browser or client sent it
+    pub user_agent: Option<String>,
+    /// where it last logged in or refreshed from
+    pub ip: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = sessions)]
+pub struct NewSession<'a> {
+    pub user_id: UserId,
+    pub created_at: i64,
+    pub last_used_at: i64,
+    pub expires_at: i64,
+    pub user_agent: Option<&'a str>,
+    pub ip: Option<&'a str>,
+}
+
+/// Long user agents are cut short rather than stored whole
+fn truncate(user_agent: &str) -> &str {
+    match user_agent.char_indices().nth(MAX_USER_AGENT_LENGTH) {
+        Some((end, _)) => &user_agent[..end],
+        None => user_agent,
+    }
+}
+
+impl<'a> NewSession<'a> {
+    pub fn new(
+        user_id: UserId,
+        now: i64,
+        user_agent: Option<&'a str>,
+        ip: Option<&'a str>,
+    ) -> NewSession<'a> {
+        NewSession {
+            user_id,
+            created_at: now,
+            last_used_at: now,
+            expires_at: now + SESSION_LIFETIME_SECONDS,
+            user_agent: user_agent.map(truncate),
+            ip,
+        }
+    }
+
+    pub fn insert(&self, conn: &mut DbConnection) -> QueryResult<Session> {
+        diesel::insert_into(sessions::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Session {
+    /// The user's unexpired sessions, most recently used first
+    pub fn get_for_user(
+        conn: &mut DbConnection,
+        uid: UserId,
+        now: i64,
+    ) -> QueryResult<Vec<Session>> {
+        use crate::schema::sessions::dsl::*;
+        sessions
+            .filter(user_id.eq(uid))
+            .filter(expires_at.gt(now))
+            .order((last_used_at.desc(), id.desc()))
+            .load(conn)
+    }
+
+    /// The user's session, if it hasn't expired or been logged out, noting
+    /// that it was used
+    pub fn authenticate(
+        conn: &mut DbConnection,
+        uid: UserId,
+        session_id: SessionId,
+        now: i64,
+    ) -> QueryResult<Option<Session>> {
+        use crate::schema::sessions::dsl::*;
+        let session = sessions
+            .find(session_id)
+            .filter(user_id.eq(uid))
+            .filter(expires_at.gt(now))
+            .first::<Session>(conn)
+            .optional()?;
+        let Some(mut session) = session else {
+            return Ok(None);
+        };
+        if now - session.last_used_at >= LAST_USED_PRECISION_SECONDS {
+            diesel::update(sessions.find(session.id))
+                .set(last_used_at.eq(now))
+                .execute(conn)?;
+            session.last_used_at = now;
+        }
+        Ok(Some(session))
+    }
+
+    /// Note where the session refreshed its access token from
+    pub fn touch(
+        conn: &mut DbConnection,
+        session_id: SessionId,
+        now: i64,
+        agent: Option<&str>,
+        address: Option<&str>,
+    ) -> QueryResult<usize> {
+        use crate::schema::sessions::dsl::*;
+        diesel::update(sessions.find(session_id))
+            .set((
+                last_used_at.eq(now),
+                user_agent.eq(agent.map(truncate)),
+                ip.eq(address),
+            ))
+            .execute(conn)
+    }
+
+    /// Log out one of the user's own sessions
+    pub fn delete(
+        conn: &mut DbConnection,
+        uid: UserId,
+        session_id: SessionId,
+    ) -> QueryResult<usize> {
+        use crate::schema::sessions::dsl::*;
+        diesel::delete(sessions.find(session_id).filter(user_id.eq(uid))).execute(conn)
+    }
+
+    /// Log the user out everywhere, except `keep` if given
+    pub fn delete_for_user(
+        conn: &mut DbConnection,
+        uid: UserId,
+        keep: Option<SessionId>,
+    ) -> QueryResult<usize> {
+        use crate::schema::sessions::dsl::*;
+        match keep {
+            Some(keep) => {
+                diesel::delete(sessions.filter(user_id.eq(uid)).filter(id.ne(keep))).execute(conn)
+            }
+            None => diesel::delete(sessions.filter(user_id.eq(uid))).execute(conn),
+        }
+    }
+
+    /// Remove sessions whose refresh tokens have expired
+    pub fn delete_expired(conn: &mut DbConnection, now: i64) -> QueryResult<usize> {
+        use crate::schema::sessions::dsl::*;
+        diesel::delete(sessions.filter(expires_at.le(now))).execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn log_in(conn: &mut DbConnection, uid: UserId, now: i64) -> Session {
+        NewSession::new(uid, now, Some("Firefox"), Some("10.0.0.1"))
+            .insert(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let mut conn = get_test_db_connection();
+        let session = log_in(&mut conn, UserId(1), 1000);
+        assert_eq!(session.expires_at, 1000 + SESSION_LIFETIME_SECONDS);
+
+        let found = Session::authenticate(&mut conn, UserId(1), session.id, 1030)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.last_used_at, 1000);
+        let found = Session::authenticate(&mut conn, UserId(1), session.id, 1100)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.last_used_at, 1100);
+
+        // someone else's, or expired
+        assert_eq!(
+            Session::authenticate(&mut conn, UserId(2), session.id, 1100),
+            Ok(None)
+        );
+        assert_eq!(
+            Session::authenticate(&mut conn, UserId(1), session.id, found.expires_at),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_log_out() {
+        let mut conn = get_test_db_connection();
+        let first = log_in(&mut conn, UserId(1), 1000);
+        let second = log_in(&mut conn, UserId(1), 2000);
+        let third = log_in(&mut conn, UserId(1), 3000);
+        let other = log_in(&mut conn, UserId(2), 1000);
+
+        let ids = |conn: &mut DbConnection, uid| {
+            Session::get_for_user(conn, uid, 3000)
+                .unwrap()
+                .into_iter()
+                .map(|session| session.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(&mut conn, UserId(1)),
+            vec![third.id, second.id, first.id]
+        );
+
+        assert_eq!(Session::delete(&mut conn, UserId(2), first.id), Ok(0));
+        assert_eq!(Session::delete(&mut conn, UserId(1), first.id), Ok(1));
+        assert_eq!(
+            Session::delete_for_user(&mut conn, UserId(1), Some(third.id)),
+            Ok(1)
+        );
+        assert_eq!(ids(&mut conn, UserId(1)), vec![third.id]);
+        assert_eq!(Session::delete_for_user(&mut conn, UserId(1), None), Ok(1));
+        assert_eq!(ids(&mut conn, UserId(2)), vec![other.id]);
+
+        assert_eq!(
+            Session::delete_expired(&mut conn, 1000 + SESSION_LIFETIME_SECONDS),
+            Ok(1)
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use super::ids::UserId;
+use crate::db::DbConnection;
 use crate::schema::*;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -7,17 +9,17 @@ use thiserror::Error;
 #[diesel(table_name = settings)]
 pub struct Setting {
     pub id: Option<i32>,
-    pub user_id: Option<i32>,
+    pub user_id: Option<UserId>,
     pub key: String,
     pub value: String,
-    pub created_at: i32,
-    pub updated_at: i32,
+    pub created_at: i64,
+    pub updated_at: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable)]
 #[diesel(table_name = settings)]
 pub struct NewSetting {
-    pub user_id: Option<i32>,
+    pub user_id: Option<UserId>,
     pub key: String,
     pub value: String,
 }
@@ -31,15 +33,21 @@ pub struct UpdateSetting {
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Setting '{key:?}' already exists for user with id={user_id:?}")]
-    SettingExists { key: String, user_id: Option<i32> },
+    SettingExists {
+        key: String,
+        user_id: Option<UserId>,
+    },
     #[error("Setting '{key:?}' not found for user with id={user_id:?}")]
-    SettingNotFound { key: String, user_id: Option<i32> },
+    SettingNotFound {
+        key: String,
+        user_id: Option<UserId>,
+    },
     #[error("Database error")]
     Database,
 }
 
 impl Setting {
-    pub fn add(conn: &mut SqliteConnection, setting: &NewSetting) -> Result<Setting, Error> {
+    pub fn add(conn: &mut DbConnection, setting: &NewSetting) -> Result<Setting, Error> {
         use crate::schema::settings::dsl::*;
 
         // can't add if this key name for this user_id already exists, or
@@ -72,8 +80,8 @@ impl Setting {
             user_id: setting.user_id,
             key: setting.key.clone(),
             value: setting.value.clone(),
-            created_at: chrono::Utc::now().timestamp() as i32,
-            updated_at: chrono::Utc::now().timestamp() as i32,
+            created_at: chrono::Utc::now().timestamp(),
+            updated_at: chrono::Utc::now().timestamp(),
         };
 
         match diesel::insert_into(settings)
@@ -86,9 +94,9 @@ impl Setting {
     }
 
     pub fn get(
-        conn: &mut SqliteConnection,
+        conn: &mut DbConnection,
         query_key: &str,
-        query_user_id: Option<i32>,
+        query_user_id: Option<UserId>,
     ) -> Result<Setting, Error> {
         use crate::schema::settings::dsl::*;
 
@@ -108,6 +116,42 @@ impl Setting {
             user_id: query_user_id,
         })
     }
+
+    /// Add the setting, or replace its value if it already exists
+    pub fn set(conn: &mut DbConnection, setting: &NewSetting) -> Result<Setting, Error> {
+        use crate::schema::settings::dsl::*;
+
+        let existing = match Setting::get(conn, &setting.key, setting.user_id) {
+            Ok(existing) => existing,
+            Err(Error::SettingNotFound { .. }) => return Setting::add(conn, setting),
+            Err(e) => return Err(e),
+        };
+
+        diesel::update(settings.filter(id.eq(existing.id)))
+            .set((
+                value.eq(&setting.value),
+                updated_at.eq(chrono::Utc::now().timestamp()),
+            ))
+            .get_result(conn)
+            .map_err(|_| Error::Database)
+    }
+
+    /// Remove the setting if it exists
+    pub fn remove(
+        conn: &mut DbConnection,
+        query_key: &str,
+        query_user_id: Option<UserId>,
+    ) -> Result<(), Error> {
+        use crate::schema::settings::dsl::*;
+
+        let result = match query_user_id {
+            Some(uid) => diesel::delete(settings.filter(user_id.eq(uid)).filter(key.eq(query_key)))
+                .execute(conn),
+            None => diesel::delete(settings.filter(user_id.is_null()).filter(key.eq(query_key)))
+                .execute(conn),
+        };
+        result.map(|_| ()).map_err(|_| Error::Database)
+    }
 }
 
 #[cfg(test)]
@@ -138,7 +182,7 @@ mod tests {
     fn test_add_user_setting() {
         let mut conn = get_test_db_connection();
         let setting = NewSetting {
-            user_id: Some(1),
+            user_id: Some(UserId(1)),
             key: "test_key".to_string(),
             value: "test_value".to_string(),
         };
@@ -149,7 +193,7 @@ mod tests {
         assert_ne!(Some(result.id), None);
         assert_ne!(Some(result.created_at), None);
         assert_ne!(Some(result.updated_at), None);
-        assert_eq!(result.user_id, Some(1));
+        assert_eq!(result.user_id, Some(UserId(1)));
     }
 
     #[test]
@@ -171,7 +215,7 @@ mod tests {
     fn test_no_dupe_user_setting() {
         let mut conn = get_test_db_connection();
         let setting = NewSetting {
-            user_id: Some(1),
+            user_id: Some(UserId(1)),
             key: "test_key".to_string(),
             value: "test_value".to_string(),
         };
@@ -206,20 +250,20 @@ mod tests {
     fn test_get_user_setting() {
         let mut conn = get_test_db_connection();
         let setting = NewSetting {
-            user_id: Some(1),
+            user_id: Some(UserId(1)),
             key: "test_key".to_string(),
             value: "test_value".to_string(),
         };
 
         Setting::add(&mut conn, &setting).unwrap();
 
-        let result = Setting::get(&mut conn, &setting.key, Some(1)).unwrap();
+        let result = Setting::get(&mut conn, &setting.key, Some(UserId(1))).unwrap();
         assert_eq!(result.key, setting.key);
         assert_eq!(result.value, setting.value);
         assert_ne!(Some(result.id), None);
         assert_ne!(Some(result.created_at), None);
         assert_ne!(Some(result.updated_at), None);
-        assert_eq!(result.user_id, Some(1));
+        assert_eq!(result.user_id, Some(UserId(1)));
     }
 
     #[test]
@@ -237,11 +281,54 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_set_replaces_value() {
+        let mut conn = get_test_db_connection();
+        let setting = NewSetting {
+            user_id: None,
+            key: "test_key".to_string(),
+            value: "test_value".to_string(),
+        };
+        let added = Setting::set(&mut conn, &setting).unwrap();
+
+        let setting = NewSetting {
+            value: "new_value".to_string(),
+            ..setting
+        };
+        let updated = Setting::set(&mut conn, &setting).unwrap();
+        assert_eq!(updated.id, added.id);
+        assert_eq!(updated.value, "new_value");
+        assert_eq!(
+            Setting::get(&mut conn, "test_key", None).unwrap().value,
+            "new_value"
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut conn = get_test_db_connection();
+        for user_id in [None, Some(UserId(1))] {
+            let setting = NewSetting {
+                user_id,
+                key: "test_key".to_string(),
+                value: "test_value".to_string(),
+            };
+            Setting::add(&mut conn, &setting).unwrap();
+        }
+
+        Setting::remove(&mut conn, "test_key", None).unwrap();
+        assert!(Setting::get(&mut conn, "test_key", None).is_err());
+        assert!(Setting::get(&mut conn, "test_key", Some(UserId(1))).is_ok());
+
+        // removing a missing setting is fine
+        Setting::remove(&mut conn, "test_key", None).unwrap();
+    }
+
     #[test]
     fn test_gets_for_correct_user() {
         let mut conn = get_test_db_connection();
         let setting = NewSetting {
-            user_id: Some(1),
+            user_id: Some(UserId(1)),
             key: "test_key".to_string(),
             value: "test_value".to_string(),
         };
@@ -250,14 +337,14 @@ mod tests {
 
         // add same key for different user
         let setting = NewSetting {
-            user_id: Some(2),
+            user_id: Some(UserId(2)),
             key: "test_key".to_string(),
             value: "other_value".to_string(),
         };
 
         Setting::add(&mut conn, &setting).unwrap();
 
-        let result = Setting::get(&mut conn, "test_key", Some(1)).unwrap();
+        let result = Setting::get(&mut conn, "test_key", Some(UserId(1))).unwrap();
         assert_eq!(result.value, "test_value");
     }
 }
@@ -0,0 +1,171 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::ids::{ShareLinkId, SubscriptionId};
+use crate::db::DbConnection;
+use crate::schema::*;
+use crate::security::{
+    tokens::{hash_token, random_token},
+    validation::{Validate, ValidationErrors},
+};
+
+const TOKEN_LENGTH: usize = 32;
+/// How stale `last_viewed_at` may get, so not every page view writes to the
+/// database
+const LAST_VIEWED_PRECISION_SECONDS: i64 = 60;
+pub const DEFAULT_ITEM_COUNT: i32 = 20;
+pub const MAX_ITEM_COUNT: i32 = 100;
+
+/// An unauthenticated, read-only page showing a subscription's newest
+/// items to anyone with the link. Revoking deletes it. Only the token's
+/// hash is stored.
+#[derive(Debug, Clone, Serialize, Queryable, Identifiable, PartialEq)]
+#[diesel(table_name = share_links)]
+pub struct ShareLink {
+    pub id: ShareLinkId,
+    pub subscription_id: SubscriptionId,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    /// how many of the newest items the page shows
+    pub item_count: i32,
+    pub created_at: i64,
+    /// zero if never viewed
+    pub last_viewed_at: i64,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = share_links)]
+pub struct NewShareLink {
+    #[serde(skip_deserializing)]
+    pub subscription_id: SubscriptionId,
+    #[serde(skip_deserializing)]
+    pub token_hash: String,
+    #[serde(default = "default_item_count")]
+    pub item_count: i32,
+    #[serde(skip_deserializing)]
+    pub created_at: i64,
+}
+
+fn default_item_count() -> i32 {
+    DEFAULT_ITEM_COUNT
+}
+
+impl Validate for NewShareLink {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if !(1..=MAX_ITEM_COUNT).contains(&self.item_count) {
+            errors.add(
+                "item_count",
+                format!("Must be between 1 and {}", MAX_ITEM_COUNT),
+            );
+        }
+    }
+}
+
+impl NewShareLink {
+    /// Store the link, returning it along with the token for its URL,
+    /// which can't be recovered later
+    pub fn insert(mut self, conn: &mut DbConnection) -> QueryResult<(ShareLink, String)> {
+        let token = random_token(TOKEN_LENGTH);
+        self.token_hash = hash_token(&token);
+        let link = diesel::insert_into(share_links::table)
+            .values(&self)
+            .get_result(conn)?;
+        Ok((link, token))
+    }
+}
+
+impl ShareLink {
+    pub fn get_for_subscription(
+        conn: &mut DbConnection,
+        sub_id: SubscriptionId,
+    ) -> QueryResult<Vec<ShareLink>> {
+        use crate::schema::share_links::dsl::*;
+        share_links
+            .filter(subscription_id.eq(sub_id))
+            .order(id)
+            .load(conn)
+    }
+
+    pub fn count_for_subscription(
+        conn: &mut DbConnection,
+        sub_id: SubscriptionId,
+    ) -> QueryResult<i64> {
+        use crate::schema::share_links::dsl::*;
+        share_links
+            .filter(subscription_id.eq(sub_id))
+            .count()
+            .get_result(conn)
+    }
+
+    /// Revoke a link, which only works through the subscription it shares
+    pub fn delete(
+        conn: &mut DbConnection,
+        sub_id: SubscriptionId,
+        link_id: ShareLinkId,
+    ) -> QueryResult<usize> {
+        use crate::schema::share_links::dsl::*;
+        diesel::delete(share_links.find(link_id).filter(subscription_id.eq(sub_id))).execute(conn)
+    }
+
+    /// The link matching the token from its URL, noting that it was viewed
+    pub fn view(conn: &mut DbConnection, token: &str, now: i64) -> QueryResult<Option<ShareLink>> {
+        use crate::schema::share_links::dsl::*;
+        let link = share_links
+            .filter(token_hash.eq(hash_token(token)))
+            .first::<ShareLink>(conn)
+            .optional()?;
+        if let Some(link) = &link {
+            if now - link.last_viewed_at >= LAST_VIEWED_PRECISION_SECONDS {
+                diesel::update(share_links.find(link.id))
+                    .set(last_viewed_at.eq(now))
+                    .execute(conn)?;
+            }
+        }
+        Ok(link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn new_link(item_count: i32) -> NewShareLink {
+        NewShareLink {
+            subscription_id: SubscriptionId(1),
+            token_hash: String::new(),
+            item_count,
+            created_at: 1000,
+        }
+    }
+
+    #[test]
+    fn test_view_and_revoke() {
+        let mut conn = get_test_db_connection();
+        let (link, token) = new_link(10).insert(&mut conn).unwrap();
+        assert_ne!(link.token_hash, token);
+
+        let viewed = ShareLink::view(&mut conn, &token, 2000).unwrap();
+        assert_eq!(viewed.map(|viewed| viewed.id), Some(link.id));
+        let listed = ShareLink::get_for_subscription(&mut conn, SubscriptionId(1)).unwrap();
+        assert_eq!(listed[0].last_viewed_at, 2000);
+        assert_eq!(ShareLink::view(&mut conn, "wrong", 2000), Ok(None));
+
+        assert_eq!(
+            ShareLink::delete(&mut conn, SubscriptionId(2), link.id),
+            Ok(0)
+        );
+        assert_eq!(
+            ShareLink::delete(&mut conn, SubscriptionId(1), link.id),
+            Ok(1)
+        );
+        assert_eq!(ShareLink::view(&mut conn, &token, 3000), Ok(None));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(new_link(DEFAULT_ITEM_COUNT).validate().is_ok());
+        assert!(new_link(0).validate().is_err());
+        assert!(new_link(MAX_ITEM_COUNT + 1).validate().is_err());
+    }
+}
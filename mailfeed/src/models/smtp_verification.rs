@@ -0,0 +1,155 @@
+use serde::Serialize;
+
+use super::settings::{self, NewSetting, Setting};
+use crate::db::DbConnection;
+use crate::security::tokens::{hash_token, random_token};
+
+const VERIFIED: &str = "smtp.verified";
+const PENDING: &str = "smtp.pending";
+const TOKEN_HASH: &str = "smtp.verification_token_hash";
+const SENT_AT: &str = "smtp.verification_sent_at";
+
+const TOKEN_LENGTH: usize = 32;
+
+/// Whether the SMTP settings in use are known to work. When they change,
+/// digests are held until someone clicks the link in an email sent through
+/// the new ones, so a typo'd host can't quietly swallow them. Settings are
+/// only stored as fingerprints, see `EmailServerCfg::fingerprint`.
+#[derive(Debug, Default, Clone, Serialize, PartialEq)]
+pub struct SmtpVerification {
+    /// the settings last verified, empty if the instance never has been
+    #[serde(skip)]
+    pub verified: String,
+    /// the settings a verification email went out for
+    #[serde(skip)]
+    pub pending: String,
+    #[serde(skip)]
+    token_hash: String,
+    /// when the verification email was sent, zero if none is waiting
+    pub sent_at: i64,
+}
+
+impl SmtpVerification {
+    pub fn load(conn: &mut DbConnection) -> SmtpVerification {
+        let mut get = |key| {
+            Setting::get(conn, key, None)
+                .ok()
+                .map(|setting| setting.value)
+                .unwrap_or_default()
+        };
+        SmtpVerification {
+            verified: get(VERIFIED),
+            pending: get(PENDING),
+            token_hash: get(TOKEN_HASH),
+            sent_at: get(SENT_AT).parse().unwrap_or_default(),
+        }
+    }
+
+    fn save(&self, conn: &mut DbConnection) -> Result<(), settings::Error> {
+        for (key, value) in [
+            (VERIFIED, self.verified.clone()),
+            (PENDING, self.pending.clone()),
+            (TOKEN_HASH, self.token_hash.clone()),
+            (SENT_AT, self.sent_at.to_string()),
+        ] {
+            let setting = NewSetting {
+                user_id: None,
+                key: key.to_string(),
+                value,
+            };
+            Setting::set(conn, &setting)?;
+        }
+        Ok(())
+    }
+
+    /// Whether digests sent with these settings have to wait. An instance
+    /// that's never checked takes the settings it has as working, so
+    /// upgrading doesn't hold anything.
+    pub fn holds(&self, fingerprint: &str) -> bool {
+        !self.verified.is_empty() && self.verified != fingerprint
+    }
+
+    /// Called when the sender starts. Returns a token for the verification
+    /// link if the settings have changed since they were last verified,
+    /// replacing any sent before.
+    pub fn start(
+        conn: &mut DbConnection,
+        fingerprint: &str,
+        now: i64,
+    ) -> Result<Option<String>, settings::Error> {
+        let mut verification = SmtpVerification::load(conn);
+        if verification.verified.is_empty() {
+            verification.verified = fingerprint.to_string();
+            verification.save(conn)?;
+            return Ok(None);
+        }
+        if !verification.holds(fingerprint) {
+            return Ok(None);
+        }
+        let token = random_token(TOKEN_LENGTH);
+        verification.pending = fingerprint.to_string();
+        verification.token_hash = hash_token(&token);
+        verification.sent_at = now;
+        verification.save(conn)?;
+        Ok(Some(token))
+    }
+
+    /// Check the token from a verification link, and if it's the one sent
+    /// last, take the settings it was sent with as working
+    pub fn verify(conn: &mut DbConnection, token: &str) -> Result<bool, settings::Error> {
+        let verification = SmtpVerification::load(conn);
+        if verification.pending.is_empty() || hash_token(token) != verification.token_hash {
+            return Ok(false);
+        }
+        let pending = verification.pending.clone();
+        SmtpVerification::confirm(conn, &pending)?;
+        Ok(true)
+    }
+
+    /// Take these settings as working without a link, e.g. when an admin
+    /// knows they are or the instance has no public URL to link to
+    pub fn confirm(conn: &mut DbConnection, fingerprint: &str) -> Result<(), settings::Error> {
+        SmtpVerification {
+            verified: fingerprint.to_string(),
+            ..Default::default()
+        }
+        .save(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_verification() {
+        let mut conn = get_test_db_connection();
+        // the first settings seen are taken as working
+        assert_eq!(
+            SmtpVerification::start(&mut conn, "old", 100).unwrap(),
+            None
+        );
+        assert!(!SmtpVerification::load(&mut conn).holds("old"));
+
+        let token = SmtpVerification::start(&mut conn, "new", 200)
+            .unwrap()
+            .unwrap();
+        let verification = SmtpVerification::load(&mut conn);
+        assert!(verification.holds("new"));
+        assert_eq!(verification.sent_at, 200);
+
+        // a restart sends a new link, and the old one stops working
+        let again = SmtpVerification::start(&mut conn, "new", 300)
+            .unwrap()
+            .unwrap();
+        assert!(!SmtpVerification::verify(&mut conn, &token).unwrap());
+        assert!(SmtpVerification::load(&mut conn).holds("new"));
+
+        assert!(SmtpVerification::verify(&mut conn, &again).unwrap());
+        let verification = SmtpVerification::load(&mut conn);
+        assert!(!verification.holds("new"));
+        assert_eq!(verification.sent_at, 0);
+        assert!(!SmtpVerification::verify(&mut conn, &again).unwrap());
+    }
+}
@@ -0,0 +1,242 @@
+use diesel::prelude::*;
+use serde::Serialize;
+
+use super::{feed_item::FeedItem, ids::UserId};
+use crate::db::{with_backend, DbConnection};
+use crate::schema::*;
+
+/// An item a user starred, and whether it's been pushed to their bookmark
+/// manager
+#[derive(Debug, Clone, Serialize, Queryable, Identifiable, PartialEq)]
+#[diesel(table_name = starred_items)]
+pub struct StarredItem {
+    pub id: i32,
+    pub user_id: UserId,
+    pub feed_item_id: i32,
+    pub starred_at: i64,
+    /// when it was pushed, or found already bookmarked, zero if not yet
+    pub synced_at: i64,
+    /// the bookmark's id in the bookmark manager
+    pub bookmark_id: Option<String>,
+    /// why pushing it failed, None if it hasn't
+    pub sync_error: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = starred_items)]
+struct NewStarredItem {
+    user_id: UserId,
+    feed_item_id: i32,
+    starred_at: i64,
+}
+
+impl StarredItem {
+    /// Star the item for the user. Starring an item that's already starred
+    /// keeps it as it is, except that a failed push is tried again.
+    pub fn star(
+        conn: &mut DbConnection,
+        uid: UserId,
+        item_id: i32,
+        now: i64,
+    ) -> QueryResult<StarredItem> {
+        use crate::schema::starred_items::dsl::*;
+
+        conn.transaction(|conn| {
+            let insert = diesel::insert_into(starred_items)
+                .values(NewStarredItem {
+                    user_id: uid,
+                    feed_item_id: item_id,
+                    starred_at: now,
+                })
+                .on_conflict_do_nothing();
+            with_backend!(conn, insert.execute(conn))?;
+            diesel::update(
+                starred_items
+                    .filter(user_id.eq(uid))
+                    .filter(feed_item_id.eq(item_id)),
+            )
+            .set(sync_error.eq(None::<String>))
+            .get_result(conn)
+        })
+    }
+
+    /// Returns how many were removed, zero if it wasn't starred. Bookmarks
+    /// already pushed are left in the bookmark manager.
+    pub fn unstar(conn: &mut DbConnection, uid: UserId, item_id: i32) -> QueryResult<usize> {
+        use crate::schema::starred_items::dsl::*;
+
+        diesel::delete(
+            starred_items
+                .filter(user_id.eq(uid))
+                .filter(feed_item_id.eq(item_id)),
+        )
+        .execute(conn)
+    }
+
+    /// The user's starred items, most recently starred first
+    pub fn get_for_user(
+        conn: &mut DbConnection,
+        uid: UserId,
+    ) -> QueryResult<Vec<(StarredItem, FeedItem)>> {
+        starred_items::table
+            .inner_join(feed_items::table)
+            .filter(starred_items::user_id.eq(uid))
+            .order(starred_items::starred_at.desc())
+            .load(conn)
+    }
+
+    /// The user's items waiting to be pushed, oldest first
+    pub fn pending(
+        conn: &mut DbConnection,
+        uid: UserId,
+        limit: i64,
+    ) -> QueryResult<Vec<(StarredItem, FeedItem)>> {
+        starred_items::table
+            .inner_join(feed_items::table)
+            .filter(starred_items::user_id.eq(uid))
+            .filter(starred_items::synced_at.eq(0))
+            .filter(starred_items::sync_error.is_null())
+            .order(starred_items::id)
+            .limit(limit)
+            .load(conn)
+    }
+
+    /// Try pushing the user's failed items again, e.g. after they fix their
+    /// bookmark settings
+    pub fn retry_failed(conn: &mut DbConnection, uid: UserId) -> QueryResult<usize> {
+        use crate::schema::starred_items::dsl::*;
+
+        diesel::update(
+            starred_items
+                .filter(user_id.eq(uid))
+                .filter(sync_error.is_not_null()),
+        )
+        .set(sync_error.eq(None::<String>))
+        .execute(conn)
+    }
+
+    /// Another of the user's starred items with the same link that's already
+    /// been pushed, e.g. the same article from two feeds
+    pub fn synced_with_link(
+        conn: &mut DbConnection,
+        uid: UserId,
+        link: &str,
+    ) -> QueryResult<Option<StarredItem>> {
+        starred_items::table
+            .inner_join(feed_items::table)
+            .filter(starred_items::user_id.eq(uid))
+            .filter(starred_items::synced_at.gt(0))
+            .filter(feed_items::link.eq(link))
+            .select(starred_items::all_columns)
+            .first(conn)
+            .optional()
+    }
+
+    /// Record that the item was pushed as the given bookmark, or why it
+    /// couldn't be
+    pub fn record_sync(
+        conn: &mut DbConnection,
+        star_id: i32,
+        outcome: Result<&str, &str>,
+        now: i64,
+    ) -> QueryResult<usize> {
+        use crate::schema::starred_items::dsl::*;
+
+        let target = starred_items.find(star_id);
+        match outcome {
+            Ok(bookmark) => diesel::update(target)
+                .set((
+                    synced_at.eq(now),
+                    bookmark_id.eq(Some(bookmark)),
+                    sync_error.eq(None::<String>),
+                ))
+                .execute(conn),
+            Err(error) => diesel::update(target)
+                .set(sync_error.eq(Some(error)))
+                .execute(conn),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{feed_item::NewFeedItem, ids::FeedId},
+        test_helpers::test_helpers::get_test_db_connection,
+    };
+
+    #[test]
+    fn test_star_and_sync() {
+        let mut conn = get_test_db_connection();
+        let item = NewFeedItem {
+            feed_id: FeedId(1),
+            title: "title",
+            link: "https://example.com/1",
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+
+        let star = StarredItem::star(&mut conn, UserId(1), item.id, 1000).unwrap();
+        assert_eq!(star.synced_at, 0);
+        // starring again doesn't reset when it was starred
+        assert_eq!(
+            StarredItem::star(&mut conn, UserId(1), item.id, 2000),
+            Ok(star.clone())
+        );
+        assert_eq!(
+            StarredItem::pending(&mut conn, UserId(1), 10).unwrap(),
+            vec![(star.clone(), item.clone())]
+        );
+
+        // failed pushes wait until the item is starred again
+        StarredItem::record_sync(&mut conn, star.id, Err("HTTP 401"), 1100).unwrap();
+        assert!(StarredItem::pending(&mut conn, UserId(1), 10)
+            .unwrap()
+            .is_empty());
+        StarredItem::star(&mut conn, UserId(1), item.id, 1200).unwrap();
+        assert_eq!(
+            StarredItem::pending(&mut conn, UserId(1), 10)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(StarredItem::pending(&mut conn, UserId(2), 10)
+            .unwrap()
+            .is_empty());
+        StarredItem::record_sync(&mut conn, star.id, Err("HTTP 401"), 1100).unwrap();
+        assert_eq!(StarredItem::retry_failed(&mut conn, UserId(1)), Ok(1));
+
+        assert_eq!(
+            StarredItem::synced_with_link(&mut conn, UserId(1), &item.link),
+            Ok(None)
+        );
+        StarredItem::record_sync(&mut conn, star.id, Ok("42"), 1300).unwrap();
+        let synced = StarredItem::synced_with_link(&mut conn, UserId(1), &item.link)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (synced.synced_at, synced.bookmark_id.as_deref()),
+            (1300, Some("42"))
+        );
+        assert!(StarredItem::pending(&mut conn, UserId(1), 10)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            StarredItem::synced_with_link(&mut conn, UserId(2), &item.link),
+            Ok(None)
+        );
+
+        assert_eq!(
+            StarredItem::get_for_user(&mut conn, UserId(1))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(StarredItem::unstar(&mut conn, UserId(1), item.id), Ok(1));
+        assert!(StarredItem::get_for_user(&mut conn, UserId(1))
+            .unwrap()
+            .is_empty());
+    }
+}
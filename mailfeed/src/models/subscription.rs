@@ -1,68 +1,277 @@
-use super::user::User;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use super::ids::{FeedId, SubscriptionId, UserId};
+use super::{
+    delivery::Delivery,
+    delivery_window::DeliveryWindow,
+    feed::Feed,
+    keyword_filter::{KeywordFilter, Keywords},
+    query_timing::timed,
+    tag::Tag,
+    user::User,
+};
+use crate::db::{to_sql_as_text, DbConnection};
+use crate::scheduler::cron::{CronError, CronSchedule};
 use crate::schema::*;
 use diesel::{
     backend::Backend,
     deserialize::{self, FromSql, FromSqlRow},
     prelude::*,
     serialize::{self, Output, ToSql},
-    sql_types::Integer,
+    sql_types::{BigInt, Integer, Nullable, Text},
     AsExpression,
 };
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Identifiable, Associations)]
 #[diesel(belongs_to(User))]
 #[diesel(table_name = subscriptions)]
 pub struct Subscription {
-    pub id: i32,
-    pub user_id: i32,
+    pub id: SubscriptionId,
+    pub user_id: UserId,
     pub friendly_name: String,
-    /// realtime, hourly, daily
+    /// realtime, hourly, daily, weekly, weekly on a given day, every N
+    /// hours or a cron expression
     pub frequency: Frequency,
     /// zero if never sent
-    pub last_sent_time: i32,
+    pub last_sent_time: i64,
     /// zero if no limit
     pub max_items: i32,
     pub is_active: bool,
-    pub feed_id: i32,
+    pub feed_id: FeedId,
+    /// overrides the feed's description if set
+    pub description: Option<String>,
+    /// overrides the feed's homepage if set
+    pub homepage: Option<String>,
+    /// overrides the user's send_email if set
+    pub send_email: Option<String>,
+    /// prepended to the subject of this subscription's emails
+    pub subject_prefix: Option<String>,
+    /// overrides the user's subject template if set
+    pub subject_template: Option<String>,
+    /// show score and comment counts for aggregator items
+    pub show_stats: bool,
+    /// only send aggregator items with at least this score
+    pub min_score: Option<i32>,
+    /// only send aggregator items with at least this many comments
+    pub min_comments: Option<i32>,
+    /// when the user was last told the feed is failing, zero if never
+    pub feed_failure_notified_at: i64,
+    /// HH:MM-HH:MM in the user's time zone, when emails may be sent
+    pub delivery_window: Option<String>,
+    /// if any, only items matching one of these are sent
+    pub include_keywords: Keywords,
+    /// items matching any of these aren't sent
+    pub exclude_keywords: Keywords,
+    #[serde(default)]
+    pub delivery_method: DeliveryMethod,
+    /// items published longer ago than this aren't sent, None for the
+    /// instance default
+    #[serde(default)]
+    pub max_item_age_days: Option<i32>,
+    /// where the user put it in their list, zero until they've ordered it
+    #[serde(default)]
+    pub position: i32,
     // TODO: add send_existing option
 }
 
+/// What the dashboard can sort a subscription by besides its name
+#[derive(Debug, Default, Clone, Copy, Serialize, PartialEq, QueryableByName)]
+pub struct ListStats {
+    /// publish date of the feed's newest item, None if it has none
+    #[diesel(sql_type = Nullable<BigInt>)]
+    pub latest_item_date: Option<i64>,
+    /// the feed's items the user hasn't marked read
+    #[diesel(sql_type = BigInt)]
+    pub unread_count: i64,
+}
+
+/// How often a subscription's new items are sent, in the user's local time
+#[derive(Debug, Serialize, Deserialize, AsExpression, Clone, PartialEq, FromSqlRow)]
+#[diesel(sql_type=Text)]
+#[serde(rename_all = "snake_case")]
+pub enum Frequency {
+    /// as soon as they're fetched
+    Realtime,
+    /// at the top of each hour
+    Hourly,
+    /// at the user's daily send time
+    Daily,
+    /// at the user's daily send time on Mondays
+    Weekly,
+    /// at the user's daily send time on the given day
+    WeeklyOn(Weekday),
+    /// every so many hours, counted from the user's daily send time
+    EveryHours(u32),
+    /// at each match of a cron expression like `0 */6 * * *`, see
+    /// `scheduler::cron`
+    Cron(String),
+}
+
+/// Longest interval an `every_hours` frequency may have, 30 days
+pub const MAX_INTERVAL_HOURS: u32 = 30 * 24;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+
+    pub fn days_from_monday(&self) -> i64 {
+        *self as i64
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Weekday::Monday => "mon",
+            Weekday::Tuesday => "tue",
+            Weekday::Wednesday => "wed",
+            Weekday::Thursday => "thu",
+            Weekday::Friday => "fri",
+            Weekday::Saturday => "sat",
+            Weekday::Sunday => "sun",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Weekday> {
+        Weekday::ALL.into_iter().find(|day| day.as_str() == value)
+    }
+}
+
+impl Frequency {
+    pub fn is_realtime(&self) -> bool {
+        matches!(self, Frequency::Realtime)
+    }
+
+    /// The parsed schedule of a cron frequency, None for the others
+    pub fn cron_schedule(&self) -> Option<Result<CronSchedule, CronError>> {
+        match self {
+            Frequency::Cron(expression) => Some(CronSchedule::parse(expression)),
+            _ => None,
+        }
+    }
+}
+
+/// How it's stored: the name, `weekly:tue`, `every:6h`, or the cron
+/// expression, which always has spaces so can't be mistaken for the others
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Frequency::Realtime => f.write_str("realtime"),
+            Frequency::Hourly => f.write_str("hourly"),
+            Frequency::Daily => f.write_str("daily"),
+            Frequency::Weekly => f.write_str("weekly"),
+            Frequency::WeeklyOn(day) => write!(f, "weekly:{}", day.as_str()),
+            Frequency::EveryHours(hours) => write!(f, "every:{}h", hours),
+            Frequency::Cron(expression) => f.write_str(expression),
+        }
+    }
+}
+
+impl Frequency {
+    fn parse(value: &str) -> Frequency {
+        let weekly_on = value.strip_prefix("weekly:").and_then(Weekday::parse);
+        let every_hours = value
+            .strip_prefix("every:")
+            .and_then(|every| every.strip_suffix('h'))
+            .and_then(|hours| hours.parse().ok());
+        match (value, weekly_on, every_hours) {
+            ("realtime", _, _) => Frequency::Realtime,
+            ("hourly", _, _) => Frequency::Hourly,
+            ("daily", _, _) => Frequency::Daily,
+            ("weekly", _, _) => Frequency::Weekly,
+            (_, Some(day), _) => Frequency::WeeklyOn(day),
+            (_, _, Some(hours)) => Frequency::EveryHours(hours),
+            (expression, _, _) => Frequency::Cron(expression.to_string()),
+        }
+    }
+}
+
+impl<DB> FromSql<Text, DB> for Frequency
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+        Ok(Frequency::parse(&String::from_sql(bytes)?))
+    }
+}
+
+to_sql_as_text!(Frequency, |frequency| frequency.to_string());
+
+/// Where a subscription's new items go
 #[repr(i32)]
-#[derive(Debug, Serialize, Deserialize, AsExpression, Clone, Copy, FromSqlRow)]
+#[derive(
+    Debug, Default, Serialize, Deserialize, AsExpression, Clone, Copy, PartialEq, FromSqlRow,
+)]
 #[diesel(sql_type=Integer)]
 #[serde(rename_all = "snake_case")]
-pub enum Frequency {
-    Realtime = 0,
-    Hourly = 1,
-    Daily = 2,
+pub enum DeliveryMethod {
+    /// a digest email, on the subscription's frequency
+    #[default]
+    Email = 0,
+    /// a signed POST to the user's delivery webhook, see
+    /// `tasks::webhook_sender`
+    Webhook = 1,
+    /// embeds posted to the user's Discord webhook, see `tasks::discord`
+    Discord = 2,
+    /// notices posted to the user's Matrix room, see `tasks::matrix_sender`
+    Matrix = 3,
+    /// a notification per item to the user's ntfy topic or Gotify server,
+    /// for realtime subscriptions, see `tasks::push_sender`
+    Push = 4,
 }
 
-impl<DB> FromSql<Integer, DB> for Frequency
+impl<DB> FromSql<Integer, DB> for DeliveryMethod
 where
     DB: Backend,
     i32: FromSql<Integer, DB>,
 {
     fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
         match i32::from_sql(bytes)? {
-            0 => Ok(Frequency::Realtime),
-            1 => Ok(Frequency::Hourly),
-            2 => Ok(Frequency::Daily),
+            0 => Ok(DeliveryMethod::Email),
+            1 => Ok(DeliveryMethod::Webhook),
+            2 => Ok(DeliveryMethod::Discord),
+            3 => Ok(DeliveryMethod::Matrix),
+            4 => Ok(DeliveryMethod::Push),
             _ => Err("Unrecognized enum variant".into()),
         }
     }
 }
 
-impl<DB> ToSql<Integer, DB> for Frequency
+impl<DB> ToSql<Integer, DB> for DeliveryMethod
 where
     DB: Backend,
     i32: ToSql<Integer, DB>,
 {
     fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
         match self {
-            Frequency::Realtime => 0.to_sql(out),
-            Frequency::Hourly => 1.to_sql(out),
-            Frequency::Daily => 2.to_sql(out),
+            DeliveryMethod::Email => 0.to_sql(out),
+            DeliveryMethod::Webhook => 1.to_sql(out),
+            DeliveryMethod::Discord => 2.to_sql(out),
+            DeliveryMethod::Matrix => 3.to_sql(out),
+            DeliveryMethod::Push => 4.to_sql(out),
         }
     }
 }
@@ -70,28 +279,56 @@ where
 #[derive(Debug, Serialize, Deserialize, Insertable)]
 #[diesel(table_name = subscriptions)]
 pub struct NewSubscription {
-    pub user_id: i32,
+    pub user_id: UserId,
     pub friendly_name: String,
-    /// realtime, hourly, daily
+    /// realtime, hourly, daily, weekly or a cron expression
     pub frequency: Frequency,
     /// zero if never sent
-    pub last_sent_time: i32,
+    pub last_sent_time: i64,
     /// zero if no limit
     pub max_items: i32,
     pub is_active: bool,
-    pub feed_id: i32,
+    pub feed_id: FeedId,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub send_email: Option<String>,
+    pub subject_prefix: Option<String>,
+    pub subject_template: Option<String>,
+    pub show_stats: bool,
+    pub min_score: Option<i32>,
+    pub min_comments: Option<i32>,
+    pub feed_failure_notified_at: i64,
+    pub delivery_window: Option<String>,
+    pub include_keywords: Keywords,
+    pub exclude_keywords: Keywords,
+    pub delivery_method: DeliveryMethod,
+    pub max_item_age_days: Option<i32>,
 }
 
 impl Default for NewSubscription {
     fn default() -> Self {
         Self {
-            user_id: 0,
+            user_id: UserId::default(),
             friendly_name: "".to_string(),
             frequency: Frequency::Realtime,
             last_sent_time: 0,
             max_items: 0,
             is_active: true,
-            feed_id: 0,
+            feed_id: FeedId::default(),
+            description: None,
+            homepage: None,
+            send_email: None,
+            subject_prefix: None,
+            subject_template: None,
+            show_stats: false,
+            min_score: None,
+            min_comments: None,
+            feed_failure_notified_at: 0,
+            delivery_window: None,
+            include_keywords: Keywords::default(),
+            exclude_keywords: Keywords::default(),
+            delivery_method: DeliveryMethod::Email,
+            max_item_age_days: None,
         }
     }
 }
@@ -100,17 +337,40 @@ impl Default for NewSubscription {
 #[diesel(table_name = subscriptions)]
 pub struct PartialSubscription {
     pub friendly_name: Option<String>,
-    /// realtime, hourly, daily
+    /// realtime, hourly, daily, weekly or a cron expression
     pub frequency: Option<Frequency>,
     /// zero if never sent
-    pub last_sent_time: Option<i32>,
+    pub last_sent_time: Option<i64>,
     /// zero if no limit
     pub max_items: Option<i32>,
     pub is_active: Option<bool>,
+    /// Some(None) clears the override
+    pub description: Option<Option<String>>,
+    /// Some(None) clears the override
+    pub homepage: Option<Option<String>>,
+    /// Some(None) clears the override
+    pub send_email: Option<Option<String>>,
+    /// Some(None) clears the prefix
+    pub subject_prefix: Option<Option<String>>,
+    /// Some(None) clears the override
+    pub subject_template: Option<Option<String>>,
+    pub show_stats: Option<bool>,
+    /// Some(None) clears the threshold
+    pub min_score: Option<Option<i32>>,
+    /// Some(None) clears the threshold
+    pub min_comments: Option<Option<i32>>,
+    pub feed_failure_notified_at: Option<i64>,
+    /// Some(None) clears the window
+    pub delivery_window: Option<Option<String>>,
+    pub include_keywords: Option<Keywords>,
+    pub exclude_keywords: Option<Keywords>,
+    pub delivery_method: Option<DeliveryMethod>,
+    /// Some(None) uses the instance default
+    pub max_item_age_days: Option<Option<i32>>,
 }
 
 impl NewSubscription {
-    pub fn insert(&self, conn: &mut SqliteConnection) -> Option<Subscription> {
+    pub fn insert(&self, conn: &mut DbConnection) -> Option<Subscription> {
         use crate::schema::subscriptions::dsl::*;
         match diesel::insert_into(subscriptions)
             .values(self)
@@ -126,7 +386,85 @@ impl NewSubscription {
 }
 
 impl Subscription {
-    pub fn get_by_id(conn: &mut SqliteConnection, id: i32) -> Option<Subscription> {
+    /// Name to show for this subscription, falling back to the feed's title
+    pub fn display_name<'a>(&'a self, feed: &'a Feed) -> &'a str {
+        if self.friendly_name.is_empty() {
+            &feed.title
+        } else {
+            &self.friendly_name
+        }
+    }
+
+    pub fn display_description<'a>(&'a self, feed: &'a Feed) -> Option<&'a str> {
+        self.description.as_deref().or(feed.description.as_deref())
+    }
+
+    /// Homepage to link to, falling back to the feed's homepage and
+    /// then the feed URL itself
+    pub fn display_homepage<'a>(&'a self, feed: &'a Feed) -> &'a str {
+        self.homepage
+            .as_deref()
+            .or(feed.homepage.as_deref())
+            .unwrap_or(&feed.url)
+    }
+
+    /// Address to send this subscription's emails to
+    pub fn destination<'a>(&'a self, user: &'a User) -> &'a str {
+        self.send_email.as_deref().unwrap_or(&user.send_email)
+    }
+
+    /// The subscription's delivery window, if it has a valid one
+    pub fn delivery_window(&self) -> Option<DeliveryWindow> {
+        DeliveryWindow::parse(self.delivery_window.as_deref()?)
+    }
+
+    /// The subscription's keyword filters. Invalid keywords are rejected when
+    /// they're set, so this only fails if the rules changed since.
+    pub fn keyword_filter(&self) -> KeywordFilter {
+        KeywordFilter::parse(&self.include_keywords, &self.exclude_keywords).unwrap_or_else(|e| {
+            log::warn!("Ignoring keyword filters of sub_id={}: {}", self.id, e);
+            KeywordFilter::default()
+        })
+    }
+
+    /// Whether the user should be told the feed has been failing for at
+    /// least `after` seconds. Each run of failures is only reported once.
+    pub fn needs_failure_notice(&self, feed: &Feed, now: i64, after: i64) -> bool {
+        match feed.failing_since() {
+            Some(since) => now - since >= after && self.feed_failure_notified_at < since,
+            None => false,
+        }
+    }
+
+    /// The subscription as it would be after the update, without saving it
+    pub fn with_changes(self, update: PartialSubscription) -> Subscription {
+        Subscription {
+            friendly_name: update.friendly_name.unwrap_or(self.friendly_name),
+            frequency: update.frequency.unwrap_or(self.frequency),
+            last_sent_time: update.last_sent_time.unwrap_or(self.last_sent_time),
+            max_items: update.max_items.unwrap_or(self.max_items),
+            is_active: update.is_active.unwrap_or(self.is_active),
+            description: update.description.unwrap_or(self.description),
+            homepage: update.homepage.unwrap_or(self.homepage),
+            send_email: update.send_email.unwrap_or(self.send_email),
+            subject_prefix: update.subject_prefix.unwrap_or(self.subject_prefix),
+            subject_template: update.subject_template.unwrap_or(self.subject_template),
+            show_stats: update.show_stats.unwrap_or(self.show_stats),
+            min_score: update.min_score.unwrap_or(self.min_score),
+            min_comments: update.min_comments.unwrap_or(self.min_comments),
+            feed_failure_notified_at: update
+                .feed_failure_notified_at
+                .unwrap_or(self.feed_failure_notified_at),
+            delivery_window: update.delivery_window.unwrap_or(self.delivery_window),
+            include_keywords: update.include_keywords.unwrap_or(self.include_keywords),
+            exclude_keywords: update.exclude_keywords.unwrap_or(self.exclude_keywords),
+            delivery_method: update.delivery_method.unwrap_or(self.delivery_method),
+            max_item_age_days: update.max_item_age_days.unwrap_or(self.max_item_age_days),
+            ..self
+        }
+    }
+
+    pub fn get_by_id(conn: &mut DbConnection, id: SubscriptionId) -> Option<Subscription> {
         use crate::schema::subscriptions::dsl::subscriptions;
         match subscriptions.find(id).first::<Subscription>(conn) {
             Ok(subscription) => Some(subscription),
@@ -137,7 +475,7 @@ impl Subscription {
         }
     }
 
-    pub fn get_all(conn: &mut SqliteConnection) -> Option<Vec<Subscription>> {
+    pub fn get_all(conn: &mut DbConnection) -> Option<Vec<Subscription>> {
         use crate::schema::subscriptions::dsl::subscriptions;
         match subscriptions.load::<Subscription>(conn) {
             Ok(found) => match found.len() {
@@ -151,13 +489,180 @@ impl Subscription {
         }
     }
 
+    /// A hash of what the dashboard shows of the user's subscriptions and
+    /// their feeds' health, from a single query, so clients polling for
+    /// changes don't have to load the whole list
+    pub fn state_hash(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<String, diesel::result::Error> {
+        #[derive(QueryableByName)]
+        struct State {
+            #[diesel(sql_type = Text)]
+            state: String,
+        }
+
+        // SQLite joins rows in the order the subquery gives them, Postgres
+        // has to be told
+        let (join_rows, join_tags, char) = match conn {
+            DbConnection::Sqlite(_) => (
+                "group_concat(row, char(30))",
+                "group_concat(tags.name, char(29))",
+                "char",
+            ),
+            DbConnection::Postgres(_) => (
+                "string_agg(row, chr(30) ORDER BY id)",
+                "string_agg(tags.name, chr(29) ORDER BY tags.id)",
+                "chr",
+            ),
+        };
+        let state = diesel::sql_query(format!(
+            "SELECT COALESCE({join_rows}, '') AS state FROM (
+                SELECT subscriptions.id, subscriptions.id || {char}(31) || subscriptions.friendly_name
+                    || {char}(31) || subscriptions.frequency || {char}(31) || subscriptions.is_active
+                    || {char}(31) || subscriptions.last_sent_time
+                    || {char}(31) || subscriptions.delivery_method
+                    || {char}(31) || subscriptions.position
+                    || {char}(31) || COALESCE(CAST(feeds.error_time AS TEXT), '')
+                    || {char}(31) || COALESCE(CAST(feeds.error_kind AS TEXT), '')
+                    || {char}(31) || COALESCE(feeds.parse_warnings, '')
+                    || {char}(31) || COALESCE((
+                        SELECT {join_tags} FROM subscription_tags
+                        JOIN tags ON tags.id = subscription_tags.tag_id
+                        WHERE subscription_tags.subscription_id = subscriptions.id
+                    ), '') AS row
+                FROM subscriptions
+                LEFT JOIN feeds ON feeds.id = subscriptions.feed_id
+                WHERE subscriptions.user_id = $1
+                ORDER BY subscriptions.id
+            ) AS rows",
+        ))
+        .bind::<Integer, _>(user_id)
+        .get_result::<State>(conn)?
+        .state;
+
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+        Ok(format!("{:016x}", hasher.finish()))
+    }
+
     pub fn get_all_for_user(
-        conn: &mut SqliteConnection,
-        user_id: i32,
+        conn: &mut DbConnection,
+        user_id: UserId,
     ) -> Result<Vec<Subscription>, diesel::result::Error> {
         use crate::schema::subscriptions::dsl::{subscriptions, user_id as user_id_col};
+        match timed("subscriptions_for_user", || {
+            subscriptions
+                .filter(user_id_col.eq(user_id))
+                .load::<Subscription>(conn)
+        }) {
+            Ok(found) => Ok(found),
+            Err(e) => {
+                log::warn!("Error getting subscriptions: {:?}", e);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn get_all_for_feed(
+        conn: &mut DbConnection,
+        feed_id: FeedId,
+    ) -> Result<Vec<Subscription>, diesel::result::Error> {
+        use crate::schema::subscriptions::dsl::{feed_id as feed_id_col, subscriptions};
+        timed("subscriptions_for_feed", || {
+            subscriptions
+                .filter(feed_id_col.eq(feed_id))
+                .load::<Subscription>(conn)
+        })
+    }
+
+    /// Each of the user's subscriptions' newest item and how many of its
+    /// items they haven't read, for sorting the dashboard
+    pub fn list_stats(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<HashMap<SubscriptionId, ListStats>, diesel::result::Error> {
+        #[derive(QueryableByName)]
+        struct Row {
+            #[diesel(sql_type = Integer)]
+            id: SubscriptionId,
+            #[diesel(embed)]
+            stats: ListStats,
+        }
+
+        let rows = timed("subscription_list_stats", || {
+            diesel::sql_query(
+                "SELECT subscriptions.id,
+                    (SELECT MAX(pub_date) FROM feed_items
+                        WHERE feed_items.feed_id = subscriptions.feed_id) AS latest_item_date,
+                    (SELECT COUNT(*) FROM feed_items
+                        WHERE feed_items.feed_id = subscriptions.feed_id
+                        AND feed_items.id NOT IN (
+                            SELECT feed_item_id FROM read_items WHERE read_items.user_id = $1
+                        )) AS unread_count
+                FROM subscriptions WHERE subscriptions.user_id = $1",
+            )
+            .bind::<Integer, _>(user_id)
+            .load::<Row>(conn)
+        })?;
+        Ok(rows.into_iter().map(|row| (row.id, row.stats)).collect())
+    }
+
+    /// Put the user's subscriptions in the given order. Any left out go
+    /// after them, oldest first.
+    pub fn set_positions(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        order: &[SubscriptionId],
+    ) -> Result<(), diesel::result::Error> {
+        use crate::schema::subscriptions::dsl::{id, position, subscriptions, user_id as uid};
+        conn.transaction(|conn| {
+            diesel::update(subscriptions.filter(uid.eq(user_id)))
+                .set(position.eq(0))
+                .execute(conn)?;
+            for (index, sub_id) in order.iter().enumerate() {
+                diesel::update(subscriptions.filter(uid.eq(user_id)).filter(id.eq(sub_id)))
+                    .set(position.eq(index as i32 + 1))
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn count_for_user(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<i64, diesel::result::Error> {
+        use crate::schema::subscriptions::dsl::{subscriptions, user_id as user_id_col};
+        subscriptions
+            .filter(user_id_col.eq(user_id))
+            .count()
+            .get_result(conn)
+    }
+
+    pub fn count_realtime_for_user(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<i64, diesel::result::Error> {
+        use crate::schema::subscriptions::dsl::{frequency, subscriptions, user_id as user_id_col};
+        subscriptions
+            .filter(user_id_col.eq(user_id))
+            .filter(frequency.eq(Frequency::Realtime))
+            .count()
+            .get_result(conn)
+    }
+
+    /// The user's subscriptions with the given ids. Ids that don't exist or
+    /// belong to another user are left out.
+    pub fn get_many_for_user(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        sub_ids: &[SubscriptionId],
+    ) -> Result<Vec<Subscription>, diesel::result::Error> {
+        use crate::schema::subscriptions::dsl::{id, subscriptions, user_id as user_id_col};
         match subscriptions
             .filter(user_id_col.eq(user_id))
+            .filter(id.eq_any(sub_ids))
             .load::<Subscription>(conn)
         {
             Ok(found) => Ok(found),
@@ -169,9 +674,9 @@ impl Subscription {
     }
 
     pub fn get_for_user_and_feed(
-        conn: &mut SqliteConnection,
-        user_id: i32,
-        feed_id: i32,
+        conn: &mut DbConnection,
+        user_id: UserId,
+        feed_id: FeedId,
     ) -> Result<Option<Subscription>, diesel::result::Error> {
         use crate::schema::subscriptions::dsl::{
             feed_id as feed_id_col, subscriptions, user_id as user_id_col,
@@ -193,8 +698,8 @@ impl Subscription {
     }
 
     pub fn update(
-        conn: &mut SqliteConnection,
-        sub_id: i32,
+        conn: &mut DbConnection,
+        sub_id: SubscriptionId,
         update: &PartialSubscription,
     ) -> Option<Subscription> {
         use crate::schema::subscriptions::dsl::{id, subscriptions};
@@ -210,8 +715,16 @@ impl Subscription {
         }
     }
 
-    pub fn delete(conn: &mut SqliteConnection, sub_id: i32) -> bool {
+    pub fn delete(conn: &mut DbConnection, sub_id: SubscriptionId) -> bool {
         use crate::schema::subscriptions::dsl::{id, subscriptions};
+        if let Err(e) = Delivery::delete_for_subscription(conn, sub_id) {
+            log::warn!("Error deleting subscription's deliveries: {:?}", e);
+            return false;
+        }
+        if let Err(e) = Tag::delete_for_subscription(conn, sub_id) {
+            log::warn!("Error deleting subscription's tags: {:?}", e);
+            return false;
+        }
         match diesel::delete(subscriptions.filter(id.eq(sub_id))).execute(conn) {
             Ok(_) => true,
             Err(e) => {
@@ -221,3 +734,299 @@ impl Subscription {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::feed_item::NewFeedItem;
+    use crate::models::read_item::ReadItem;
+    use crate::test_helpers::test_helpers::{get_test_db_connection, test_feed, test_user};
+
+    fn test_subscription() -> Subscription {
+        Subscription {
+            id: SubscriptionId(1),
+            user_id: UserId(1),
+            friendly_name: String::new(),
+            frequency: Frequency::Daily,
+            last_sent_time: 0,
+            max_items: 0,
+            is_active: true,
+            feed_id: FeedId(1),
+            description: None,
+            homepage: None,
+            send_email: None,
+            subject_prefix: None,
+            subject_template: None,
+            show_stats: false,
+            min_score: None,
+            min_comments: None,
+            feed_failure_notified_at: 0,
+            delivery_window: None,
+            include_keywords: Keywords::default(),
+            exclude_keywords: Keywords::default(),
+            delivery_method: DeliveryMethod::Email,
+            max_item_age_days: None,
+            position: 0,
+        }
+    }
+
+    #[test]
+    fn test_get_many_for_user() {
+        let mut conn = get_test_db_connection();
+        let mut insert = |user_id, feed_id| {
+            NewSubscription {
+                user_id,
+                feed_id,
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap()
+            .id
+        };
+        let first = insert(UserId(1), FeedId(1));
+        let second = insert(UserId(1), FeedId(2));
+        let other_user = insert(UserId(2), FeedId(1));
+
+        let found = Subscription::get_many_for_user(
+            &mut conn,
+            UserId(1),
+            &[first, second, other_user, SubscriptionId(99)],
+        )
+        .unwrap();
+        let mut ids: Vec<SubscriptionId> = found.iter().map(|sub| sub.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![first, second]);
+    }
+
+    #[test]
+    fn test_frequency_round_trip() {
+        let mut conn = get_test_db_connection();
+        for frequency in [
+            Frequency::Weekly,
+            Frequency::WeeklyOn(Weekday::Sunday),
+            Frequency::EveryHours(36),
+            Frequency::Cron("0 9 * * mon".to_string()),
+        ] {
+            let inserted = NewSubscription {
+                user_id: UserId(1),
+                frequency: frequency.clone(),
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap();
+            let found = Subscription::get_many_for_user(&mut conn, UserId(1), &[inserted.id])
+                .unwrap()
+                .remove(0);
+            assert_eq!(found.frequency, frequency);
+        }
+
+        let cron: Frequency = serde_json::from_str(r#"{"cron": "0 */6 * * *"}"#).unwrap();
+        assert_eq!(
+            cron.cron_schedule().map(|schedule| schedule.is_ok()),
+            Some(true)
+        );
+        assert_eq!(
+            serde_json::to_string(&Frequency::Weekly).unwrap(),
+            r#""weekly""#
+        );
+        let tuesdays: Frequency = serde_json::from_str(r#"{"weekly_on": "tuesday"}"#).unwrap();
+        assert_eq!(tuesdays, Frequency::WeeklyOn(Weekday::Tuesday));
+        let every: Frequency = serde_json::from_str(r#"{"every_hours": 6}"#).unwrap();
+        assert_eq!(every, Frequency::EveryHours(6));
+        let invalid = Frequency::Cron("every monday".to_string());
+        assert!(matches!(invalid.cron_schedule(), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let mut conn = get_test_db_connection();
+        let empty = Subscription::state_hash(&mut conn, UserId(1)).unwrap();
+        let sub = NewSubscription {
+            user_id: UserId(1),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let added = Subscription::state_hash(&mut conn, UserId(1)).unwrap();
+        assert_ne!(added, empty);
+        assert_eq!(
+            Subscription::state_hash(&mut conn, UserId(1)).unwrap(),
+            added
+        );
+
+        NewSubscription {
+            user_id: UserId(2),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        assert_eq!(
+            Subscription::state_hash(&mut conn, UserId(1)).unwrap(),
+            added
+        );
+
+        let sent = PartialSubscription {
+            last_sent_time: Some(1000),
+            ..Default::default()
+        };
+        Subscription::update(&mut conn, sub.id, &sent).unwrap();
+        assert_ne!(
+            Subscription::state_hash(&mut conn, UserId(1)).unwrap(),
+            added
+        );
+    }
+
+    #[test]
+    fn test_display_falls_back_to_feed() {
+        let feed = Feed {
+            description: Some("Feed description".to_string()),
+            ..test_feed()
+        };
+        let sub = test_subscription();
+        assert_eq!(sub.display_name(&feed), "Example");
+        assert_eq!(sub.display_description(&feed), Some("Feed description"));
+        assert_eq!(sub.display_homepage(&feed), "https://example.com/feed.xml");
+    }
+
+    #[test]
+    fn test_display_uses_overrides() {
+        let mut feed = test_feed();
+        feed.homepage = Some("https://example.com".to_string());
+        let mut sub = test_subscription();
+        assert_eq!(sub.display_homepage(&feed), "https://example.com");
+
+        sub.friendly_name = "My Name".to_string();
+        sub.description = Some("My description".to_string());
+        sub.homepage = Some("https://example.com/blog".to_string());
+        assert_eq!(sub.display_name(&feed), "My Name");
+        assert_eq!(sub.display_description(&feed), Some("My description"));
+        assert_eq!(sub.display_homepage(&feed), "https://example.com/blog");
+    }
+
+    #[test]
+    fn test_destination() {
+        let user = User {
+            send_email: "inbox@example.com".to_string(),
+            ..test_user()
+        };
+        let mut sub = test_subscription();
+        assert_eq!(sub.destination(&user), "inbox@example.com");
+
+        sub.send_email = Some("work@example.com".to_string());
+        assert_eq!(sub.destination(&user), "work@example.com");
+    }
+
+    #[test]
+    fn test_with_changes() {
+        let mut sub = test_subscription();
+        sub.subject_prefix = Some("[news]".to_string());
+        let update = PartialSubscription {
+            friendly_name: Some("Renamed".to_string()),
+            subject_prefix: Some(None),
+            delivery_method: Some(DeliveryMethod::Discord),
+            ..Default::default()
+        };
+        let changed = sub.with_changes(update);
+        assert_eq!(changed.friendly_name, "Renamed");
+        assert_eq!(changed.subject_prefix, None);
+        assert_eq!(changed.delivery_method, DeliveryMethod::Discord);
+        assert_eq!(changed.frequency, Frequency::Daily);
+        assert_eq!(changed.id, SubscriptionId(1));
+    }
+
+    #[test]
+    fn test_delivery_window() {
+        let mut sub = test_subscription();
+        assert_eq!(sub.delivery_window(), None);
+        sub.delivery_window = Some("07:00-09:00".to_string());
+        assert_eq!(
+            sub.delivery_window().map(|window| window.to_string()),
+            Some("07:00-09:00".to_string())
+        );
+        sub.delivery_window = Some("mornings".to_string());
+        assert_eq!(sub.delivery_window(), None);
+    }
+
+    #[test]
+    fn test_needs_failure_notice() {
+        const DAY: i64 = 24 * 60 * 60;
+        let now = 10 * DAY;
+        let mut feed = test_feed();
+        let mut sub = test_subscription();
+        assert!(!sub.needs_failure_notice(&feed, now, 3 * DAY));
+
+        feed.error_time = now - 2 * DAY;
+        assert!(!sub.needs_failure_notice(&feed, now, 3 * DAY));
+
+        feed.error_time = now - 4 * DAY;
+        assert!(sub.needs_failure_notice(&feed, now, 3 * DAY));
+
+        // already told about this run of failures
+        sub.feed_failure_notified_at = now - DAY;
+        assert!(!sub.needs_failure_notice(&feed, now, 3 * DAY));
+
+        // but a later one is reported again
+        feed.error_time = now - DAY / 2;
+        assert!(sub.needs_failure_notice(&feed, now + 3 * DAY, 3 * DAY));
+    }
+
+    #[test]
+    fn test_set_positions() {
+        let mut conn = get_test_db_connection();
+        let ids: Vec<SubscriptionId> = (1..=3)
+            .map(|feed| {
+                NewSubscription {
+                    user_id: UserId(1),
+                    feed_id: FeedId(feed),
+                    ..Default::default()
+                }
+                .insert(&mut conn)
+                .unwrap()
+                .id
+            })
+            .collect();
+        let other = NewSubscription {
+            user_id: UserId(2),
+            feed_id: FeedId(1),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+
+        Subscription::set_positions(&mut conn, UserId(1), &[ids[2], ids[0], other.id]).unwrap();
+        let position =
+            |conn: &mut DbConnection, id| Subscription::get_by_id(conn, id).unwrap().position;
+        assert_eq!(position(&mut conn, ids[2]), 1);
+        assert_eq!(position(&mut conn, ids[0]), 2);
+        assert_eq!(position(&mut conn, ids[1]), 0);
+        // only the user's own are moved
+        assert_eq!(position(&mut conn, other.id), 0);
+
+        let items: Vec<i32> = [("https://example.com/1", 50), ("https://example.com/2", 70)]
+            .into_iter()
+            .map(|(link, pub_date)| {
+                NewFeedItem {
+                    feed_id: FeedId(1),
+                    title: "item",
+                    link,
+                    pub_date,
+                    ..Default::default()
+                }
+                .insert(&mut conn)
+                .unwrap()
+                .id
+            })
+            .collect();
+        ReadItem::mark_read(&mut conn, UserId(1), items[0], 0).unwrap();
+        let stats = Subscription::list_stats(&mut conn, UserId(1)).unwrap();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(
+            stats[&ids[0]],
+            ListStats {
+                latest_item_date: Some(70),
+                unread_count: 1,
+            }
+        );
+        assert_eq!(stats[&ids[1]], ListStats::default());
+    }
+}
@@ -0,0 +1,174 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ids::{SubscriptionId, UserId},
+    settings::{self, NewSetting, Setting},
+    subscription::ListStats,
+};
+use crate::db::DbConnection;
+
+const SORT: &str = "subscriptions.sort";
+
+/// How the dashboard lists a user's subscriptions
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionSort {
+    /// the order the user dragged them into, then the rest oldest first
+    #[default]
+    Manual,
+    Name,
+    /// newest item first
+    LastItem,
+    /// most unread items first
+    Unread,
+}
+
+impl SubscriptionSort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SubscriptionSort::Manual => "manual",
+            SubscriptionSort::Name => "name",
+            SubscriptionSort::LastItem => "last_item",
+            SubscriptionSort::Unread => "unread",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "manual" => Some(SubscriptionSort::Manual),
+            "name" => Some(SubscriptionSort::Name),
+            "last_item" => Some(SubscriptionSort::LastItem),
+            "unread" => Some(SubscriptionSort::Unread),
+            _ => None,
+        }
+    }
+
+    pub fn compare(&self, a: &SortKey, b: &SortKey) -> Ordering {
+        let by_name = || {
+            a.name
+                .to_lowercase()
+                .cmp(&b.name.to_lowercase())
+                .then(a.id.cmp(&b.id))
+        };
+        match self {
+            // unordered ones have position zero, and go last
+            SubscriptionSort::Manual => {
+                (a.position == 0, a.position, a.id).cmp(&(b.position == 0, b.position, b.id))
+            }
+            SubscriptionSort::Name => by_name(),
+            SubscriptionSort::LastItem => b
+                .stats
+                .latest_item_date
+                .cmp(&a.stats.latest_item_date)
+                .then_with(by_name),
+            SubscriptionSort::Unread => b
+                .stats
+                .unread_count
+                .cmp(&a.stats.unread_count)
+                .then_with(by_name),
+        }
+    }
+}
+
+/// What a subscription is sorted on
+pub struct SortKey<'a> {
+    pub id: SubscriptionId,
+    pub name: &'a str,
+    pub position: i32,
+    pub stats: ListStats,
+}
+
+/// The user's choice of sort, stored as a user setting
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct SortSettings {
+    pub sort: SubscriptionSort,
+}
+
+impl SortSettings {
+    pub fn load(conn: &mut DbConnection, user_id: UserId) -> SortSettings {
+        let sort = Setting::get(conn, SORT, Some(user_id))
+            .ok()
+            .and_then(|setting| SubscriptionSort::parse(&setting.value))
+            .unwrap_or_default();
+        SortSettings { sort }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection, user_id: UserId) -> Result<(), settings::Error> {
+        let setting = NewSetting {
+            user_id: Some(user_id),
+            key: SORT.to_string(),
+            value: self.sort.as_str().to_string(),
+        };
+        Setting::set(conn, &setting).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn key(id: i32, name: &str, position: i32, latest: Option<i64>, unread: i64) -> SortKey<'_> {
+        SortKey {
+            id: SubscriptionId(id),
+            name,
+            position,
+            stats: ListStats {
+                latest_item_date: latest,
+                unread_count: unread,
+            },
+        }
+    }
+
+    fn sorted(sort: SubscriptionSort, keys: &mut [SortKey]) -> Vec<i32> {
+        keys.sort_by(|a, b| sort.compare(a, b));
+        keys.iter().map(|key| key.id.0).collect()
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut keys = [
+            key(1, "rust blog", 0, Some(300), 0),
+            key(2, "Alpha", 2, None, 5),
+            key(3, "beta", 1, Some(100), 5),
+            key(4, "zeta", 0, Some(200), 9),
+        ];
+        assert_eq!(
+            sorted(SubscriptionSort::Manual, &mut keys),
+            vec![3, 2, 1, 4]
+        );
+        assert_eq!(sorted(SubscriptionSort::Name, &mut keys), vec![2, 3, 1, 4]);
+        assert_eq!(
+            sorted(SubscriptionSort::LastItem, &mut keys),
+            vec![1, 4, 3, 2]
+        );
+        assert_eq!(
+            sorted(SubscriptionSort::Unread, &mut keys),
+            vec![4, 2, 3, 1]
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(
+            SortSettings::load(&mut conn, UserId(1)).sort,
+            SubscriptionSort::Manual
+        );
+        SortSettings {
+            sort: SubscriptionSort::Unread,
+        }
+        .save(&mut conn, UserId(1))
+        .unwrap();
+        assert_eq!(
+            SortSettings::load(&mut conn, UserId(1)).sort,
+            SubscriptionSort::Unread
+        );
+        assert_eq!(
+            SortSettings::load(&mut conn, UserId(2)).sort,
+            SubscriptionSort::Manual
+        );
+    }
+}
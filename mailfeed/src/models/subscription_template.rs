@@ -0,0 +1,317 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::{
+    ids::{TemplateId, UserId},
+    subscription::Frequency,
+};
+use crate::db::DbConnection;
+use crate::schema::*;
+use crate::security::validation::{Validate, ValidationErrors};
+
+/// Saved defaults for new subscriptions, so setting up many similar feeds
+/// doesn't mean repeating the same frequency, filters and delivery
+/// settings. Changing a template doesn't change subscriptions made from it.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Insertable, PartialEq)]
+#[diesel(table_name = subscription_templates)]
+pub struct SubscriptionTemplate {
+    pub id: TemplateId,
+    pub user_id: UserId,
+    pub name: String,
+    pub frequency: Frequency,
+    /// zero if no limit
+    pub max_items: i32,
+    pub send_email: Option<String>,
+    pub subject_prefix: Option<String>,
+    pub subject_template: Option<String>,
+    pub show_stats: bool,
+    pub min_score: Option<i32>,
+    pub min_comments: Option<i32>,
+    pub delivery_window: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = subscription_templates)]
+pub struct NewSubscriptionTemplate {
+    #[serde(skip_deserializing)]
+    pub user_id: UserId,
+    pub name: String,
+    pub frequency: Frequency,
+    #[serde(default)]
+    pub max_items: i32,
+    pub send_email: Option<String>,
+    pub subject_prefix: Option<String>,
+    pub subject_template: Option<String>,
+    #[serde(default)]
+    pub show_stats: bool,
+    pub min_score: Option<i32>,
+    pub min_comments: Option<i32>,
+    pub delivery_window: Option<String>,
+    #[serde(skip_deserializing)]
+    pub created_at: i64,
+}
+
+#[derive(Debug, Default, Deserialize, AsChangeset)]
+#[diesel(table_name = subscription_templates)]
+pub struct PartialSubscriptionTemplate {
+    pub name: Option<String>,
+    pub frequency: Option<Frequency>,
+    pub max_items: Option<i32>,
+    /// null clears it
+    #[serde(default, deserialize_with = "nullable")]
+    pub send_email: Option<Option<String>>,
+    /// null clears it
+    #[serde(default, deserialize_with = "nullable")]
+    pub subject_prefix: Option<Option<String>>,
+    /// null clears it
+    #[serde(default, deserialize_with = "nullable")]
+    pub subject_template: Option<Option<String>>,
+    pub show_stats: Option<bool>,
+    /// null clears it
+    #[serde(default, deserialize_with = "nullable")]
+    pub min_score: Option<Option<i32>>,
+    /// null clears it
+    #[serde(default, deserialize_with = "nullable")]
+    pub min_comments: Option<Option<i32>>,
+    /// null clears it
+    #[serde(default, deserialize_with = "nullable")]
+    pub delivery_window: Option<Option<String>>,
+}
+
+/// Keeps an explicit null as Some(None), rather than the None of a missing
+/// field, so updates can clear a setting
+pub(crate) fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+fn check_name(errors: &mut ValidationErrors, name: &str) {
+    if name.trim().is_empty() {
+        errors.add("name", "Must not be empty");
+    }
+}
+
+/// Checks shared by new and updated templates, with each field given if set
+fn check_settings(
+    errors: &mut ValidationErrors,
+    send_email: Option<&str>,
+    subject_template: Option<&str>,
+    delivery_window: Option<&str>,
+    numbers: [(&'static str, Option<i32>); 3],
+) {
+    if let Some(send_email) = send_email {
+        errors.email("send_email", send_email);
+    }
+    if let Some(template) = subject_template {
+        errors.subject_template("subject_template", template);
+    }
+    if let Some(window) = delivery_window {
+        errors.delivery_window("delivery_window", window);
+    }
+    for (field, value) in numbers {
+        errors.non_negative(field, value);
+    }
+}
+
+impl Validate for NewSubscriptionTemplate {
+    fn check(&self, errors: &mut ValidationErrors) {
+        check_name(errors, &self.name);
+        errors.frequency("frequency", &self.frequency);
+        check_settings(
+            errors,
+            self.send_email.as_deref(),
+            self.subject_template.as_deref(),
+            self.delivery_window.as_deref(),
+            [
+                ("max_items", Some(self.max_items)),
+                ("min_score", self.min_score),
+                ("min_comments", self.min_comments),
+            ],
+        );
+    }
+}
+
+impl Validate for PartialSubscriptionTemplate {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(name) = &self.name {
+            check_name(errors, name);
+        }
+        if let Some(frequency) = &self.frequency {
+            errors.frequency("frequency", frequency);
+        }
+        check_settings(
+            errors,
+            self.send_email.clone().flatten().as_deref(),
+            self.subject_template.clone().flatten().as_deref(),
+            self.delivery_window.clone().flatten().as_deref(),
+            [
+                ("max_items", self.max_items),
+                ("min_score", self.min_score.flatten()),
+                ("min_comments", self.min_comments.flatten()),
+            ],
+        );
+    }
+}
+
+impl NewSubscriptionTemplate {
+    pub fn insert(&self, conn: &mut DbConnection) -> QueryResult<SubscriptionTemplate> {
+        diesel::insert_into(subscription_templates::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl SubscriptionTemplate {
+    pub fn get_for_user(
+        conn: &mut DbConnection,
+        uid: UserId,
+    ) -> QueryResult<Vec<SubscriptionTemplate>> {
+        use crate::schema::subscription_templates::dsl::*;
+        subscription_templates
+            .filter(user_id.eq(uid))
+            .order(id)
+            .load(conn)
+    }
+
+    /// Only the user's own templates can be used
+    pub fn get(
+        conn: &mut DbConnection,
+        uid: UserId,
+        template_id: TemplateId,
+    ) -> QueryResult<Option<SubscriptionTemplate>> {
+        use crate::schema::subscription_templates::dsl::*;
+        subscription_templates
+            .find(template_id)
+            .filter(user_id.eq(uid))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn count_for_user(conn: &mut DbConnection, uid: UserId) -> QueryResult<i64> {
+        use crate::schema::subscription_templates::dsl::*;
+        subscription_templates
+            .filter(user_id.eq(uid))
+            .count()
+            .get_result(conn)
+    }
+
+    pub fn update(
+        conn: &mut DbConnection,
+        uid: UserId,
+        template_id: TemplateId,
+        update: &PartialSubscriptionTemplate,
+    ) -> QueryResult<SubscriptionTemplate> {
+        use crate::schema::subscription_templates::dsl::*;
+        diesel::update(
+            subscription_templates
+                .find(template_id)
+                .filter(user_id.eq(uid)),
+        )
+        .set(update)
+        .get_result(conn)
+    }
+
+    pub fn delete(
+        conn: &mut DbConnection,
+        uid: UserId,
+        template_id: TemplateId,
+    ) -> QueryResult<usize> {
+        use crate::schema::subscription_templates::dsl::*;
+        diesel::delete(
+            subscription_templates
+                .find(template_id)
+                .filter(user_id.eq(uid)),
+        )
+        .execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    fn new_template(name: &str) -> NewSubscriptionTemplate {
+        NewSubscriptionTemplate {
+            user_id: UserId(1),
+            name: name.to_string(),
+            frequency: Frequency::Daily,
+            max_items: 10,
+            send_email: None,
+            subject_prefix: Some("[news]".to_string()),
+            subject_template: None,
+            show_stats: true,
+            min_score: Some(100),
+            min_comments: None,
+            delivery_window: Some("07:00-09:00".to_string()),
+            created_at: 1000,
+        }
+    }
+
+    #[test]
+    fn test_crud() {
+        let mut conn = get_test_db_connection();
+        let template = new_template("News").insert(&mut conn).unwrap();
+        assert_eq!(
+            SubscriptionTemplate::get(&mut conn, UserId(1), template.id),
+            Ok(Some(template.clone()))
+        );
+        // other users' templates can't be used or changed
+        assert_eq!(
+            SubscriptionTemplate::get(&mut conn, UserId(2), template.id),
+            Ok(None)
+        );
+        assert_eq!(
+            SubscriptionTemplate::delete(&mut conn, UserId(2), template.id),
+            Ok(0)
+        );
+
+        let update = PartialSubscriptionTemplate {
+            min_score: Some(None),
+            ..Default::default()
+        };
+        let updated =
+            SubscriptionTemplate::update(&mut conn, UserId(1), template.id, &update).unwrap();
+        assert_eq!(updated.min_score, None);
+        assert_eq!(updated.subject_prefix.as_deref(), Some("[news]"));
+
+        assert_eq!(
+            SubscriptionTemplate::count_for_user(&mut conn, UserId(1)),
+            Ok(1)
+        );
+        assert_eq!(
+            SubscriptionTemplate::delete(&mut conn, UserId(1), template.id),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn test_update_clears_with_null() {
+        let update: PartialSubscriptionTemplate =
+            serde_json::from_str(r#"{"min_score": null, "subject_prefix": "[rss]"}"#).unwrap();
+        assert_eq!(update.min_score, Some(None));
+        assert_eq!(update.subject_prefix, Some(Some("[rss]".to_string())));
+        assert_eq!(update.send_email, None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(new_template("News").validate().is_ok());
+        let invalid = NewSubscriptionTemplate {
+            send_email: Some("nope".to_string()),
+            delivery_window: Some("mornings".to_string()),
+            min_score: Some(-1),
+            ..new_template(" ")
+        };
+        let errors = invalid.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|error| error.field).collect();
+        assert_eq!(
+            fields,
+            vec!["name", "send_email", "delivery_window", "min_score"]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::ids::{SubscriptionId, TagId, UserId};
+use crate::db::DbConnection;
+use crate::schema::*;
+use crate::security::validation::{Validate, ValidationErrors};
+
+/// Longest tag name, so they fit in the dashboard and email subjects
+pub const MAX_NAME_LENGTH: usize = 50;
+
+/// A user's label for grouping subscriptions. Subscriptions sharing a tag
+/// with `combined_digest` set are sent together as one email.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Insertable, PartialEq)]
+#[diesel(table_name = tags)]
+pub struct Tag {
+    pub id: TagId,
+    pub user_id: UserId,
+    pub name: String,
+    pub combined_digest: bool,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = tags)]
+struct NewTag<'a> {
+    user_id: UserId,
+    name: &'a str,
+    created_at: i64,
+}
+
+#[derive(Debug, Default, Deserialize, AsChangeset)]
+#[diesel(table_name = tags)]
+pub struct PartialTag {
+    pub name: Option<String>,
+    pub combined_digest: Option<bool>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = subscription_tags)]
+struct SubscriptionTag {
+    subscription_id: SubscriptionId,
+    tag_id: TagId,
+}
+
+pub fn check_name(errors: &mut ValidationErrors, field: &'static str, name: &str) {
+    if name.trim().is_empty() {
+        errors.add(field, "Must not be empty");
+    } else if name.trim().chars().count() > MAX_NAME_LENGTH {
+        errors.add(
+            field,
+            format!("Must be at most {} characters", MAX_NAME_LENGTH),
+        );
+    }
+}
+
+impl Validate for PartialTag {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(name) = &self.name {
+            check_name(errors, "name", name);
+        }
+    }
+}
+
+impl Tag {
+    pub fn get_for_user(conn: &mut DbConnection, uid: UserId) -> QueryResult<Vec<Tag>> {
+        use crate::schema::tags::dsl::*;
+        tags.filter(user_id.eq(uid)).order(name).load(conn)
+    }
+
+    pub fn get_for_subscription(
+        conn: &mut DbConnection,
+        sub_id: SubscriptionId,
+    ) -> QueryResult<Vec<Tag>> {
+        tags::table
+            .inner_join(subscription_tags::table)
+            .filter(subscription_tags::subscription_id.eq(sub_id))
+            .select(tags::all_columns)
+            .order(tags::name)
+            .load(conn)
+    }
+
+    /// Each of the user's tagged subscriptions' tags, by name
+    pub fn by_subscription(
+        conn: &mut DbConnection,
+        uid: UserId,
+    ) -> QueryResult<HashMap<SubscriptionId, Vec<Tag>>> {
+        let links: Vec<(SubscriptionId, Tag)> = subscription_tags::table
+            .inner_join(tags::table)
+            .filter(tags::user_id.eq(uid))
+            .select((subscription_tags::subscription_id, tags::all_columns))
+            .order(tags::name)
+            .load(conn)?;
+        let mut by_sub: HashMap<SubscriptionId, Vec<Tag>> = HashMap::new();
+        for (sub_id, tag) in links {
+            by_sub.entry(sub_id).or_default().push(tag);
+        }
+        Ok(by_sub)
+    }
+
+    /// Replace the subscription's tags with `names`, creating any the user
+    /// doesn't have yet. Tags left without subscriptions are kept, so their
+    /// settings aren't lost.
+    pub fn set_for_subscription(
+        conn: &mut DbConnection,
+        uid: UserId,
+        sub_id: SubscriptionId,
+        names: &[String],
+        now: i64,
+    ) -> QueryResult<Vec<Tag>> {
+        let mut wanted: Vec<&str> = Vec::new();
+        for name in names.iter().map(|name| name.trim()) {
+            if !wanted.contains(&name) {
+                wanted.push(name);
+            }
+        }
+        conn.transaction(|conn| {
+            diesel::delete(
+                subscription_tags::table.filter(subscription_tags::subscription_id.eq(sub_id)),
+            )
+            .execute(conn)?;
+            for name in wanted {
+                let existing = tags::table
+                    .filter(tags::user_id.eq(uid))
+                    .filter(tags::name.eq(name))
+                    .first::<Tag>(conn)
+                    .optional()?;
+                let tag = match existing {
+                    Some(tag) => tag,
+                    None => diesel::insert_into(tags::table)
+                        .values(NewTag {
+                            user_id: uid,
+                            name,
+                            created_at: now,
+                        })
+                        .get_result(conn)?,
+                };
+                diesel::insert_into(subscription_tags::table)
+                    .values(SubscriptionTag {
+                        subscription_id: sub_id,
+                        tag_id: tag.id,
+                    })
+                    .execute(conn)?;
+            }
+            Tag::get_for_subscription(conn, sub_id)
+        })
+    }
+
+    /// Only the user's own tags can be changed
+    pub fn update(
+        conn: &mut DbConnection,
+        uid: UserId,
+        tag_id: TagId,
+        update: &PartialTag,
+    ) -> QueryResult<Tag> {
+        use crate::schema::tags::dsl::*;
+        let update = PartialTag {
+            name: update
+                .name
+                .as_ref()
+                .map(|new_name| new_name.trim().to_string()),
+            combined_digest: update.combined_digest,
+        };
+        diesel::update(tags.find(tag_id).filter(user_id.eq(uid)))
+            .set(&update)
+            .get_result(conn)
+    }
+
+    /// Delete the tag and untag its subscriptions
+    pub fn delete(conn: &mut DbConnection, uid: UserId, tag_id: TagId) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            let owned = tags::table
+                .find(tag_id)
+                .filter(tags::user_id.eq(uid))
+                .select(tags::id);
+            diesel::delete(
+                subscription_tags::table.filter(subscription_tags::tag_id.eq_any(owned)),
+            )
+            .execute(conn)?;
+            diesel::delete(tags::table.find(tag_id).filter(tags::user_id.eq(uid))).execute(conn)
+        })
+    }
+
+    pub fn delete_for_subscription(
+        conn: &mut DbConnection,
+        sub_id: SubscriptionId,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            subscription_tags::table.filter(subscription_tags::subscription_id.eq(sub_id)),
+        )
+        .execute(conn)
+    }
+
+    /// The tag each of the user's subscriptions is combined under. A
+    /// subscription with several combined tags goes with the oldest, so
+    /// its items are only sent once.
+    pub fn combined_for_user(
+        conn: &mut DbConnection,
+        uid: UserId,
+    ) -> QueryResult<HashMap<SubscriptionId, Tag>> {
+        let links: Vec<(SubscriptionId, Tag)> = subscription_tags::table
+            .inner_join(tags::table)
+            .filter(tags::user_id.eq(uid))
+            .filter(tags::combined_digest.eq(true))
+            .select((subscription_tags::subscription_id, tags::all_columns))
+            .order(tags::id.desc())
+            .load(conn)?;
+        // later entries win, so the oldest tag is kept
+        Ok(links.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{ids::FeedId, subscription::NewSubscription},
+        test_helpers::test_helpers::get_test_db_connection,
+    };
+
+    fn names(tags: &[Tag]) -> Vec<&str> {
+        tags.iter().map(|tag| tag.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_set_for_subscription() {
+        let mut conn = get_test_db_connection();
+        let sub = NewSubscription {
+            user_id: UserId(1),
+            feed_id: FeedId(1),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+
+        let wanted = vec![" rust ".to_string(), "news".to_string(), "rust".to_string()];
+        let tags = Tag::set_for_subscription(&mut conn, UserId(1), sub.id, &wanted, 1000).unwrap();
+        assert_eq!(names(&tags), vec!["news", "rust"]);
+
+        // existing tags are reused, and dropped ones are kept for the user
+        let wanted = vec!["rust".to_string()];
+        let tags = Tag::set_for_subscription(&mut conn, UserId(1), sub.id, &wanted, 2000).unwrap();
+        assert_eq!(names(&tags), vec!["rust"]);
+        assert_eq!(tags[0].created_at, 1000);
+        let all = Tag::get_for_user(&mut conn, UserId(1)).unwrap();
+        assert_eq!(names(&all), vec!["news", "rust"]);
+        assert_eq!(
+            Tag::by_subscription(&mut conn, UserId(1)).unwrap(),
+            HashMap::from([(sub.id, tags.clone())])
+        );
+
+        // other users can't delete it
+        assert_eq!(Tag::delete(&mut conn, UserId(2), tags[0].id), Ok(0));
+        assert_eq!(Tag::delete(&mut conn, UserId(1), tags[0].id), Ok(1));
+        assert_eq!(Tag::get_for_subscription(&mut conn, sub.id), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_combined_for_user() {
+        let mut conn = get_test_db_connection();
+        let sub = NewSubscription {
+            user_id: UserId(1),
+            feed_id: FeedId(1),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let wanted = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let tags = Tag::set_for_subscription(&mut conn, UserId(1), sub.id, &wanted, 1000).unwrap();
+        assert!(Tag::combined_for_user(&mut conn, UserId(1))
+            .unwrap()
+            .is_empty());
+
+        let combined = PartialTag {
+            combined_digest: Some(true),
+            ..Default::default()
+        };
+        for tag in &tags[1..] {
+            Tag::update(&mut conn, UserId(1), tag.id, &combined).unwrap();
+        }
+        let by_sub = Tag::combined_for_user(&mut conn, UserId(1)).unwrap();
+        assert_eq!(by_sub[&sub.id].name, "b");
+        assert!(Tag::combined_for_user(&mut conn, UserId(2))
+            .unwrap()
+            .is_empty());
+    }
+}
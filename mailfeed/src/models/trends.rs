@@ -0,0 +1,283 @@
+use std::collections::{HashMap, HashSet};
+
+use diesel::prelude::*;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ids::UserId,
+    search_query::words,
+    settings::{self, NewSetting, Setting},
+};
+use crate::db::DbConnection;
+use crate::schema::*;
+
+const IN_DIGEST: &str = "trends.in_digest";
+const LAST_SENT: &str = "trends.last_sent";
+
+/// The report covers this past week
+pub const TRENDS_PERIOD_SECONDS: i64 = 7 * 24 * 60 * 60;
+/// Words are weighed against titles from this long, so ones that always
+/// come up don't crowd out what's new
+const BASELINE_SECONDS: i64 = 4 * TRENDS_PERIOD_SECONDS;
+/// Most keywords and domains listed
+const TOP_COUNT: usize = 10;
+/// A word must be in at least this many titles this week to be a trend
+const MIN_KEYWORD_COUNT: usize = 2;
+
+/// Too common to say anything about a title
+const STOPWORDS: &[&str] = &[
+    "about", "after", "all", "and", "are", "but", "can", "for", "from", "has", "have", "how",
+    "into", "its", "new", "not", "now", "one", "our", "out", "over", "than", "that", "the",
+    "their", "this", "top", "was", "what", "when", "who", "why", "will", "with", "you", "your",
+];
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Keyword {
+    pub word: String,
+    /// titles it was in this week
+    pub count: usize,
+    /// TF-IDF: how often it came up this week, weighed by how rare it was
+    /// over the last four weeks
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Domain {
+    pub domain: String,
+    pub count: usize,
+}
+
+/// The most frequent keywords and link domains across the past week's
+/// items from a user's active subscriptions
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Trends {
+    /// start of the week covered
+    pub since: i64,
+    pub item_count: usize,
+    pub keywords: Vec<Keyword>,
+    pub domains: Vec<Domain>,
+}
+
+/// Item fields the report is built from
+#[derive(Debug, Queryable)]
+pub struct TrendItem {
+    pub title: String,
+    pub link: String,
+    pub pub_date: i64,
+}
+
+impl Trends {
+    pub fn for_user(conn: &mut DbConnection, uid: UserId, now: i64) -> QueryResult<Trends> {
+        let feeds = subscriptions::table
+            .filter(subscriptions::user_id.eq(uid))
+            .filter(subscriptions::is_active.eq(true))
+            .select(subscriptions::feed_id);
+        let items = feed_items::table
+            .filter(feed_items::feed_id.eq_any(feeds))
+            .filter(feed_items::pub_date.ge(now - BASELINE_SECONDS))
+            .filter(feed_items::pub_date.le(now))
+            .select((feed_items::title, feed_items::link, feed_items::pub_date))
+            .load::<TrendItem>(conn)?;
+        Ok(Trends::from_items(&items, now - TRENDS_PERIOD_SECONDS))
+    }
+
+    /// Items before `since` only count towards how common words are
+    pub fn from_items(items: &[TrendItem], since: i64) -> Trends {
+        let titles: Vec<HashSet<String>> = items
+            .iter()
+            .map(|item| {
+                words(&item.title)
+                    .into_iter()
+                    .filter(|word| is_keyword(word))
+                    .collect()
+            })
+            .collect();
+        let mut in_titles: HashMap<&str, usize> = HashMap::new();
+        let mut this_week: HashMap<&str, usize> = HashMap::new();
+        for (item, words) in items.iter().zip(&titles) {
+            for word in words {
+                *in_titles.entry(word).or_default() += 1;
+                if item.pub_date >= since {
+                    *this_week.entry(word).or_default() += 1;
+                }
+            }
+        }
+
+        let total = items.len() as f64;
+        let mut keywords: Vec<Keyword> = this_week
+            .into_iter()
+            .filter(|(_, count)| *count >= MIN_KEYWORD_COUNT)
+            .map(|(word, count)| {
+                // smoothed, so a word in every title still counts for a little
+                let idf = ((total + 1.0) / (in_titles[word] as f64 + 1.0)).ln() + 1.0;
+                Keyword {
+                    word: word.to_string(),
+                    count,
+                    score: (count as f64 * idf * 100.0).round() / 100.0,
+                }
+            })
+            .collect();
+        keywords.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.word.cmp(&b.word)));
+        keywords.truncate(TOP_COUNT);
+
+        let week: Vec<&TrendItem> = items.iter().filter(|item| item.pub_date >= since).collect();
+        let mut domains: HashMap<String, usize> = HashMap::new();
+        for item in &week {
+            if let Some(domain) = domain(&item.link) {
+                *domains.entry(domain).or_default() += 1;
+            }
+        }
+        let mut domains: Vec<Domain> = domains
+            .into_iter()
+            .map(|(domain, count)| Domain { domain, count })
+            .collect();
+        domains.sort_by(|a, b| b.count.cmp(&a.count).then(a.domain.cmp(&b.domain)));
+        domains.truncate(TOP_COUNT);
+
+        Trends {
+            since,
+            item_count: week.len(),
+            keywords,
+            domains,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keywords.is_empty() && self.domains.is_empty()
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    word.chars().count() >= 3
+        && !word.chars().all(|c| c.is_ascii_digit())
+        && !STOPWORDS.contains(&word)
+}
+
+fn domain(link: &str) -> Option<String> {
+    let url = Url::parse(link).ok()?;
+    let host = url.host_str()?;
+    Some(host.strip_prefix("www.").unwrap_or(host).to_string())
+}
+
+/// Whether the report is added to the user's digests, stored as the user's
+/// settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TrendSettings {
+    pub in_digest: bool,
+}
+
+impl TrendSettings {
+    pub fn load(conn: &mut DbConnection, user_id: UserId) -> TrendSettings {
+        let in_digest = Setting::get(conn, IN_DIGEST, Some(user_id))
+            .map(|setting| setting.value == "true")
+            .unwrap_or(false);
+        TrendSettings { in_digest }
+    }
+
+    pub fn save(&self, conn: &mut DbConnection, user_id: UserId) -> Result<(), settings::Error> {
+        let setting = NewSetting {
+            user_id: Some(user_id),
+            key: IN_DIGEST.to_string(),
+            value: self.in_digest.to_string(),
+        };
+        Setting::set(conn, &setting).map(|_| ())
+    }
+
+    /// Whether a week has passed since the report was last in a digest
+    pub fn is_due(conn: &mut DbConnection, user_id: UserId, now: i64) -> bool {
+        let last_sent = Setting::get(conn, LAST_SENT, Some(user_id))
+            .ok()
+            .and_then(|setting| setting.value.parse::<i64>().ok())
+            .unwrap_or(0);
+        now - last_sent >= TRENDS_PERIOD_SECONDS
+    }
+
+    pub fn record_sent(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        now: i64,
+    ) -> Result<(), settings::Error> {
+        let setting = NewSetting {
+            user_id: Some(user_id),
+            key: LAST_SENT.to_string(),
+            value: now.to_string(),
+        };
+        Setting::set(conn, &setting).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    const NOW: i64 = 100 * 24 * 60 * 60;
+
+    fn item(title: &str, link: &str, days_ago: i64) -> TrendItem {
+        TrendItem {
+            title: title.to_string(),
+            link: link.to_string(),
+            pub_date: NOW - days_ago * 24 * 60 * 60,
+        }
+    }
+
+    #[test]
+    fn test_from_items() {
+        let items = vec![
+            item("Rust 2.0 released", "https://www.blog.rust-lang.org/a", 1),
+            item("Why Rust is fast", "https://example.com/b", 2),
+            item("Linux news roundup", "https://example.com/c", 3),
+            item("Linux kernel update", "https://lwn.net/d", 4),
+            // only in the baseline
+            item("Linux again", "https://lwn.net/e", 10),
+            item("Linux forever", "https://lwn.net/f", 20),
+        ];
+        let trends = Trends::from_items(&items, NOW - TRENDS_PERIOD_SECONDS);
+        assert_eq!(trends.item_count, 4);
+        let words: Vec<_> = trends.keywords.iter().map(|k| k.word.as_str()).collect();
+        // both were in two titles this week, but linux is always around
+        assert_eq!(words, vec!["rust", "linux"]);
+        assert!(trends.keywords[0].score > trends.keywords[1].score);
+        assert_eq!(
+            trends.domains,
+            vec![
+                Domain {
+                    domain: "example.com".to_string(),
+                    count: 2
+                },
+                Domain {
+                    domain: "blog.rust-lang.org".to_string(),
+                    count: 1
+                },
+                Domain {
+                    domain: "lwn.net".to_string(),
+                    count: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_settings() {
+        let mut conn = get_test_db_connection();
+        assert_eq!(
+            TrendSettings::load(&mut conn, UserId(1)),
+            TrendSettings::default()
+        );
+        TrendSettings { in_digest: true }
+            .save(&mut conn, UserId(1))
+            .unwrap();
+        assert!(TrendSettings::load(&mut conn, UserId(1)).in_digest);
+        assert!(!TrendSettings::load(&mut conn, UserId(2)).in_digest);
+
+        assert!(TrendSettings::is_due(&mut conn, UserId(1), NOW));
+        TrendSettings::record_sent(&mut conn, UserId(1), NOW).unwrap();
+        assert!(!TrendSettings::is_due(&mut conn, UserId(1), NOW + 60));
+        assert!(TrendSettings::is_due(
+            &mut conn,
+            UserId(1),
+            NOW + TRENDS_PERIOD_SECONDS
+        ));
+    }
+}
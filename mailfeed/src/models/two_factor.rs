@@ -0,0 +1,235 @@
+use diesel::prelude::*;
+
+use super::ids::UserId;
+use crate::db::{with_backend, DbConnection};
+use crate::schema::*;
+use crate::security::{
+    secret_box::SecretBox,
+    tokens::{hash_token, random_token},
+    totp,
+};
+
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Characters in each half of a recovery code, e.g. `k3j9x-2mq8d`
+const RECOVERY_CODE_HALF: usize = 5;
+
+/// A user's TOTP secret, which only guards their logins once a code from
+/// their authenticator app has confirmed it
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, PartialEq)]
+#[diesel(table_name = two_factor, primary_key(user_id))]
+pub struct TwoFactor {
+    pub user_id: UserId,
+    /// encrypted with the instance's SecretBox
+    pub secret: String,
+    pub enabled: bool,
+    pub last_used_step: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = recovery_codes)]
+struct NewRecoveryCode {
+    user_id: UserId,
+    code_hash: String,
+}
+
+impl TwoFactor {
+    pub fn get(conn: &mut DbConnection, uid: UserId) -> QueryResult<Option<TwoFactor>> {
+        use crate::schema::two_factor::dsl::*;
+        two_factor.find(uid).first(conn).optional()
+    }
+
+    /// Start enrolling with a new secret, replacing any earlier enrollment
+    /// that wasn't confirmed
+    pub fn enroll(
+        conn: &mut DbConnection,
+        uid: UserId,
+        encrypted_secret: String,
+        now: i64,
+    ) -> QueryResult<TwoFactor> {
+        conn.transaction(|conn| {
+            diesel::delete(two_factor::table.find(uid)).execute(conn)?;
+            diesel::insert_into(two_factor::table)
+                .values(&TwoFactor {
+                    user_id: uid,
+                    secret: encrypted_secret,
+                    enabled: false,
+                    last_used_step: 0,
+                    created_at: now,
+                })
+                .get_result(conn)
+        })
+    }
+
+    /// Turn on two-factor logins once a code has confirmed the secret,
+    /// returning the recovery codes to show the user
+    pub fn enable(&self, conn: &mut DbConnection, step: i64) -> QueryResult<Vec<String>> {
+        use crate::schema::two_factor::dsl::*;
+        conn.transaction(|conn| {
+            diesel::update(two_factor.find(self.user_id))
+                .set((enabled.eq(true), last_used_step.eq(step)))
+                .execute(conn)?;
+            Self::regenerate_recovery_codes(conn, self.user_id)
+        })
+    }
+
+    pub fn disable(conn: &mut DbConnection, uid: UserId) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(uid)))
+                .execute(conn)?;
+            diesel::delete(two_factor::table.find(uid)).execute(conn)
+        })
+    }
+
+    /// Replace the user's recovery codes, returning the new ones. Only
+    /// their hashes are stored.
+    pub fn regenerate_recovery_codes(
+        conn: &mut DbConnection,
+        uid: UserId,
+    ) -> QueryResult<Vec<String>> {
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| {
+                format!(
+                    "{}-{}",
+                    random_token(RECOVERY_CODE_HALF),
+                    random_token(RECOVERY_CODE_HALF)
+                )
+                .to_lowercase()
+            })
+            .collect();
+        let rows: Vec<_> = codes
+            .iter()
+            .map(|code| NewRecoveryCode {
+                user_id: uid,
+                code_hash: hash_token(&normalize_recovery_code(code)),
+            })
+            .collect();
+        conn.transaction(|conn| {
+            diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(uid)))
+                .execute(conn)?;
+            let insert = diesel::insert_into(recovery_codes::table).values(&rows);
+            with_backend!(conn, insert.execute(conn))
+        })?;
+        Ok(codes)
+    }
+
+    pub fn recovery_codes_left(conn: &mut DbConnection, uid: UserId) -> QueryResult<i64> {
+        recovery_codes::table
+            .filter(recovery_codes::user_id.eq(uid))
+            .count()
+            .get_result(conn)
+    }
+
+    /// The time step of `code`, if it's a current code for this secret
+    /// that hasn't been used yet. None if the secret can't be decrypted.
+    pub fn verify_code(&self, secret_box: &SecretBox, code: &str, now: i64) -> Option<i64> {
+        let secret = match secret_box.decrypt(&self.secret) {
+            Some(secret) => secret,
+            None => {
+                log::error!(
+                    "Can't decrypt the two-factor secret of user {}, was MF_SECRET_KEY changed?",
+                    self.user_id
+                );
+                return None;
+            }
+        };
+        totp::verify(&secret, code, now, self.last_used_step)
+    }
+
+    /// Whether `code` is a current TOTP code or one of the user's recovery
+    /// codes, using it up either way
+    pub fn redeem(
+        &self,
+        conn: &mut DbConnection,
+        secret_box: &SecretBox,
+        code: &str,
+        now: i64,
+    ) -> QueryResult<bool> {
+        if totp::is_code(code) {
+            let step = match self.verify_code(secret_box, code, now) {
+                Some(step) => step,
+                None => return Ok(false),
+            };
+            diesel::update(two_factor::table.find(self.user_id))
+                .set(two_factor::last_used_step.eq(step))
+                .execute(conn)?;
+            return Ok(true);
+        }
+
+        let deleted = diesel::delete(
+            recovery_codes::table
+                .filter(recovery_codes::user_id.eq(self.user_id))
+                .filter(recovery_codes::code_hash.eq(hash_token(&normalize_recovery_code(code)))),
+        )
+        .execute(conn)?;
+        if deleted > 0 {
+            log::info!("User {} used a recovery code", self.user_id);
+        }
+        Ok(deleted > 0)
+    }
+}
+
+/// Recovery codes are accepted in any case, with or without the dash
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_recovery_codes() {
+        let mut conn = get_test_db_connection();
+        let secret_box = SecretBox::new("test key");
+        let two_factor = TwoFactor::enroll(
+            &mut conn,
+            UserId(1),
+            secret_box.encrypt(&totp::generate_secret()),
+            1000,
+        )
+        .unwrap();
+        assert!(!two_factor.enabled);
+
+        let codes = two_factor.enable(&mut conn, 1).unwrap();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(
+            TwoFactor::get(&mut conn, UserId(1))
+                .unwrap()
+                .unwrap()
+                .enabled
+        );
+
+        let code = codes[0].to_uppercase().replace('-', "");
+        assert_eq!(
+            two_factor.redeem(&mut conn, &secret_box, &code, 1000),
+            Ok(true)
+        );
+        // used up
+        assert_eq!(
+            two_factor.redeem(&mut conn, &secret_box, &code, 1000),
+            Ok(false)
+        );
+        assert_eq!(
+            TwoFactor::recovery_codes_left(&mut conn, UserId(1)),
+            Ok(RECOVERY_CODE_COUNT as i64 - 1)
+        );
+
+        assert_eq!(TwoFactor::disable(&mut conn, UserId(1)), Ok(1));
+        assert_eq!(TwoFactor::get(&mut conn, UserId(1)), Ok(None));
+        assert_eq!(TwoFactor::recovery_codes_left(&mut conn, UserId(1)), Ok(0));
+    }
+
+    #[test]
+    fn test_enroll_replaces_pending() {
+        let mut conn = get_test_db_connection();
+        TwoFactor::enroll(&mut conn, UserId(1), "first".to_string(), 1000).unwrap();
+        TwoFactor::enroll(&mut conn, UserId(1), "second".to_string(), 2000).unwrap();
+        let two_factor = TwoFactor::get(&mut conn, UserId(1)).unwrap().unwrap();
+        assert_eq!(two_factor.secret, "second");
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::{Datelike, NaiveDate, TimeZone, Utc};
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Integer, Text},
+};
+use serde::Serialize;
+
+use super::ids::{FeedId, UserId};
+use crate::db::DbConnection;
+
+/// Months covered unless asked otherwise
+pub const DEFAULT_USAGE_MONTHS: u32 = 12;
+/// Most months that can be asked for
+pub const MAX_USAGE_MONTHS: u32 = 36;
+/// Feeds listed in the instance breakdown
+const HEAVIEST_FEEDS: i64 = 20;
+
+/// What an item takes up, roughly: the text stored for it, in bytes
+fn item_bytes(conn: &DbConnection) -> String {
+    let bytes = |column: &str| match conn {
+        DbConnection::Sqlite(_) => format!("LENGTH(CAST(feed_items.{} AS BLOB))", column),
+        DbConnection::Postgres(_) => format!("OCTET_LENGTH(feed_items.{})", column),
+    };
+    format!(
+        "{} + {} + COALESCE({}, 0) + COALESCE({}, 0) + COALESCE({}, 0)",
+        bytes("title"),
+        bytes("link"),
+        bytes("description"),
+        bytes("author"),
+        bytes("comments_link")
+    )
+}
+
+/// The calendar month (UTC) of a timestamp column, like `2026-10`
+fn month(conn: &DbConnection, column: &str) -> String {
+    match conn {
+        DbConnection::Sqlite(_) => format!("strftime('%Y-%m', {}, 'unixepoch')", column),
+        DbConnection::Postgres(_) => format!(
+            "to_char(to_timestamp({}) AT TIME ZONE 'UTC', 'YYYY-MM')",
+            column
+        ),
+    }
+}
+
+#[derive(QueryableByName)]
+struct MonthCount {
+    #[diesel(sql_type = Text)]
+    month: String,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+    #[diesel(sql_type = BigInt)]
+    bytes: i64,
+}
+
+/// One calendar month (UTC), like `2026-10`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MonthUsage {
+    pub month: String,
+    /// items fetched that month
+    pub items: i64,
+    /// emails the relay accepted that month
+    pub digests: i64,
+    /// text stored for those items
+    pub bytes: i64,
+}
+
+/// A user's usage, month by month, oldest first
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Usage {
+    pub months: Vec<MonthUsage>,
+    pub total_items: i64,
+    pub total_digests: i64,
+    pub total_bytes: i64,
+}
+
+/// A feed's share of the instance's items over the months covered
+#[derive(Debug, Serialize, PartialEq, QueryableByName)]
+pub struct FeedUsage {
+    #[diesel(sql_type = Integer)]
+    pub feed_id: FeedId,
+    #[diesel(sql_type = Text)]
+    pub url: String,
+    #[diesel(sql_type = Text)]
+    pub title: String,
+    #[diesel(sql_type = BigInt)]
+    pub subscribers: i64,
+    #[diesel(sql_type = BigInt)]
+    pub items: i64,
+    #[diesel(sql_type = BigInt)]
+    pub bytes: i64,
+}
+
+/// Usage across every user, with the feeds that take up the most space
+#[derive(Debug, Serialize, PartialEq)]
+pub struct InstanceUsage {
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub heaviest_feeds: Vec<FeedUsage>,
+}
+
+/// The start of each of the last `months` months, oldest first, counting
+/// the one `now` is in
+fn month_starts(now: i64, months: u32) -> Vec<(String, i64)> {
+    let today = Utc
+        .timestamp_opt(now, 0)
+        .single()
+        .unwrap_or_default()
+        .date_naive();
+    let current = today.year() * 12 + today.month0() as i32;
+    (0..months as i32)
+        .rev()
+        .filter_map(|back| {
+            let month = current - back;
+            let start = NaiveDate::from_ymd_opt(month / 12, month as u32 % 12 + 1, 1)?;
+            Some((
+                start.format("%Y-%m").to_string(),
+                start.and_hms_opt(0, 0, 0)?.and_utc().timestamp(),
+            ))
+        })
+        .collect()
+}
+
+impl Usage {
+    /// Fill in every month, including ones with nothing in them
+    fn from_counts(
+        starts: &[(String, i64)],
+        items: &[MonthCount],
+        digests: &[MonthCount],
+    ) -> Usage {
+        let months: Vec<MonthUsage> = starts
+            .iter()
+            .map(|(month, _)| {
+                let items = items.iter().find(|count| &count.month == month);
+                let digests = digests.iter().find(|count| &count.month == month);
+                MonthUsage {
+                    month: month.clone(),
+                    items: items.map_or(0, |count| count.count),
+                    digests: digests.map_or(0, |count| count.count),
+                    bytes: items.map_or(0, |count| count.bytes),
+                }
+            })
+            .collect();
+        Usage {
+            total_items: months.iter().map(|month| month.items).sum(),
+            total_digests: months.iter().map(|month| month.digests).sum(),
+            total_bytes: months.iter().map(|month| month.bytes).sum(),
+            months,
+        }
+    }
+
+    /// Items from the feeds the user subscribes to, and emails sent for
+    /// their subscriptions, over the last `months` months
+    pub fn for_user(
+        conn: &mut DbConnection,
+        uid: UserId,
+        now: i64,
+        months: u32,
+    ) -> QueryResult<Usage> {
+        let starts = month_starts(now, months);
+        let since = starts.first().map_or(now, |(_, start)| *start);
+        let items = diesel::sql_query(format!(
+            "SELECT {} AS month, COUNT(*) AS count, \
+             COALESCE(SUM({}), 0) AS bytes FROM feed_items \
+             WHERE feed_id IN (SELECT feed_id FROM subscriptions WHERE user_id = $1) \
+             AND first_seen >= $2 GROUP BY month",
+            month(conn, "first_seen"),
+            item_bytes(conn)
+        ))
+        .bind::<Integer, _>(uid)
+        .bind::<BigInt, _>(since)
+        .load::<MonthCount>(conn)?;
+        let digests = diesel::sql_query(format!(
+            "SELECT {} AS month, COUNT(*) AS count, \
+             CAST(0 AS BIGINT) AS bytes FROM deliveries \
+             WHERE subscription_id IN (SELECT id FROM subscriptions WHERE user_id = $1) \
+             AND accepted AND sent_at >= $2 GROUP BY month",
+            month(conn, "sent_at")
+        ))
+        .bind::<Integer, _>(uid)
+        .bind::<BigInt, _>(since)
+        .load::<MonthCount>(conn)?;
+        Ok(Usage::from_counts(&starts, &items, &digests))
+    }
+}
+
+impl InstanceUsage {
+    pub fn load(conn: &mut DbConnection, now: i64, months: u32) -> QueryResult<InstanceUsage> {
+        let starts = month_starts(now, months);
+        let since = starts.first().map_or(now, |(_, start)| *start);
+        let items = diesel::sql_query(format!(
+            "SELECT {} AS month, COUNT(*) AS count, \
+             COALESCE(SUM({}), 0) AS bytes FROM feed_items \
+             WHERE first_seen >= $1 GROUP BY month",
+            month(conn, "first_seen"),
+            item_bytes(conn)
+        ))
+        .bind::<BigInt, _>(since)
+        .load::<MonthCount>(conn)?;
+        let digests = diesel::sql_query(format!(
+            "SELECT {} AS month, COUNT(*) AS count, \
+             CAST(0 AS BIGINT) AS bytes FROM deliveries WHERE accepted AND sent_at >= $1 GROUP BY month",
+            month(conn, "sent_at")
+        ))
+        .bind::<BigInt, _>(since)
+        .load::<MonthCount>(conn)?;
+        let heaviest_feeds = diesel::sql_query(format!(
+            "SELECT feeds.id AS feed_id, feeds.url, feeds.title, \
+             (SELECT COUNT(*) FROM subscriptions WHERE subscriptions.feed_id = feeds.id) \
+             AS subscribers, COUNT(*) AS items, COALESCE(SUM({}), 0) AS bytes \
+             FROM feed_items INNER JOIN feeds ON feeds.id = feed_items.feed_id \
+             WHERE first_seen >= $1 GROUP BY feeds.id \
+             ORDER BY bytes DESC, items DESC, feeds.id LIMIT $2",
+            item_bytes(conn)
+        ))
+        .bind::<BigInt, _>(since)
+        .bind::<BigInt, _>(HEAVIEST_FEEDS)
+        .load::<FeedUsage>(conn)?;
+        Ok(InstanceUsage {
+            usage: Usage::from_counts(&starts, &items, &digests),
+            heaviest_feeds,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{
+            delivery::NewDelivery, feed::NewFeed, feed_item::NewFeedItem,
+            subscription::NewSubscription,
+        },
+        test_helpers::test_helpers::get_test_db_connection,
+    };
+
+    // 2026-10-16 and 2026-09-30, UTC
+    const OCT_16: i64 = 1_792_108_800;
+    const SEP_30: i64 = 1_790_726_400;
+
+    #[test]
+    fn test_month_starts() {
+        let starts = month_starts(OCT_16, 3);
+        let months: Vec<&str> = starts.iter().map(|(month, _)| month.as_str()).collect();
+        assert_eq!(months, vec!["2026-08", "2026-09", "2026-10"]);
+        assert_eq!(starts[2].1, OCT_16 - 15 * 24 * 60 * 60);
+        let starts = month_starts(OCT_16, 11);
+        assert_eq!(starts[0].0, "2025-12");
+    }
+
+    #[test]
+    fn test_usage() {
+        let mut conn = get_test_db_connection();
+        let mut feeds = Vec::new();
+        for url in ["https://a.example/", "https://b.example/"] {
+            feeds.push(
+                NewFeed {
+                    url,
+                    ..Default::default()
+                }
+                .insert(&mut conn)
+                .unwrap(),
+            );
+        }
+        let sub = NewSubscription {
+            user_id: UserId(1),
+            feed_id: feeds[0].id,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        for (feed, link, first_seen) in [
+            (feeds[0].id, "https://a.example/1", SEP_30),
+            (feeds[0].id, "https://a.example/2", OCT_16),
+            (feeds[1].id, "https://b.example/1", OCT_16),
+            (feeds[1].id, "https://b.example/2", OCT_16),
+        ] {
+            NewFeedItem {
+                feed_id: feed,
+                title: "abc",
+                link,
+                first_seen,
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap();
+        }
+        for accepted in [true, false] {
+            NewDelivery {
+                subscription_id: sub.id,
+                sent_at: OCT_16,
+                recipient: "test@example.com",
+                item_count: 1,
+                accepted,
+                relay_response: "",
+            }
+            .insert(&mut conn)
+            .unwrap();
+        }
+
+        let usage = Usage::for_user(&mut conn, UserId(1), OCT_16, 2).unwrap();
+        let item_bytes = 3 + "https://a.example/1".len() as i64;
+        assert_eq!(
+            usage.months,
+            vec![
+                MonthUsage {
+                    month: "2026-09".to_string(),
+                    items: 1,
+                    digests: 0,
+                    bytes: item_bytes,
+                },
+                MonthUsage {
+                    month: "2026-10".to_string(),
+                    items: 1,
+                    digests: 1,
+                    bytes: item_bytes,
+                },
+            ]
+        );
+        assert_eq!(usage.total_bytes, 2 * item_bytes);
+        // nothing from before the months asked for
+        let usage = Usage::for_user(&mut conn, UserId(1), OCT_16, 1).unwrap();
+        assert_eq!(usage.total_items, 1);
+        assert_eq!(
+            Usage::for_user(&mut conn, UserId(2), OCT_16, 1)
+                .unwrap()
+                .total_items,
+            0
+        );
+
+        let instance = InstanceUsage::load(&mut conn, OCT_16, 2).unwrap();
+        assert_eq!(instance.usage.total_items, 4);
+        let heaviest: Vec<(FeedId, i64, i64)> = instance
+            .heaviest_feeds
+            .iter()
+            .map(|feed| (feed.feed_id, feed.subscribers, feed.items))
+            .collect();
+        assert_eq!(heaviest, vec![(feeds[0].id, 1, 2), (feeds[1].id, 0, 2)]);
+    }
+}
@@ -1,26 +1,64 @@
-use crate::{claims::Claims, schema::*};
+use super::ids::UserId;
+use super::onboarding::Onboarding;
+use super::retry_policy::Channel;
+use super::role::{Role, Roles};
+use super::session::Session;
+use super::{delivery_webhook, discord_webhook, matrix_settings, push_settings};
+use crate::{
+    claims::Claims,
+    db::DbConnection,
+    scheduler::time_zone,
+    schema::*,
+    security::{
+        password_policy::PasswordPolicy,
+        redact,
+        validation::{Validate, ValidationErrors},
+    },
+};
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
     Argon2, PasswordHash, PasswordVerifier,
 };
-use diesel::prelude::*;
+use chrono::{FixedOffset, NaiveDate, NaiveTime, TimeZone};
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Bool, Nullable},
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, AsChangeset)]
+pub const DEFAULT_ITEM_TRUNCATE_LENGTH: i32 = 200;
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, Identifiable, AsChangeset,
+)]
 #[diesel(table_name = users)]
 pub struct User {
-    pub id: i32,
+    pub id: UserId,
     pub login_email: String,
     // TODO: optional name (for email sending)
     pub send_email: String,
     #[serde(skip_serializing)]
     pub password: String,
-    pub created_at: i32,
+    pub created_at: i64,
     pub is_active: bool,
     pub daily_send_time: String, // HH:MM+HH:MM
-    pub role: String,            // CSV
-    #[serde(skip_serializing)]
-    pub refresh_token: Option<String>,
+    pub role: Roles,
+    /// max characters of each item's description in digests, zero if no limit
+    pub item_truncate_length: i32,
+    /// set when an admin forces a password reset
+    pub must_change_password: bool,
+    /// display name for the From header of this user's emails
+    pub from_name: Option<String>,
+    /// subject template for this user's emails, see email_sender::subject
+    pub subject_template: Option<String>,
+    /// IANA time zone like `Europe/Berlin`, which overrides the offset in
+    /// `daily_send_time` and follows daylight saving changes
+    pub timezone: Option<String>,
+    /// when the user last logged in, None if never
+    pub last_login_at: Option<i64>,
+    /// signed up on their own and waiting for an admin to approve them,
+    /// see models::registration. They're inactive until then.
+    pub pending_approval: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable, AsChangeset)]
@@ -30,12 +68,22 @@ pub struct InsertableUser {
     pub send_email: String,
     #[serde(skip_serializing)]
     pub password: String,
-    pub created_at: i32,
+    pub created_at: i64,
     pub is_active: bool,
     pub daily_send_time: String, // HH:MM+HH:MM
-    pub role: String,            // CSV
-    #[serde(skip_serializing)]
-    pub refresh_token: Option<String>,
+    pub role: Roles,
+    /// max characters of each item's description in digests, zero if no limit
+    pub item_truncate_length: i32,
+    /// set when an admin forces a password reset
+    pub must_change_password: bool,
+    /// display name for the From header of this user's emails
+    pub from_name: Option<String>,
+    /// subject template for this user's emails, see email_sender::subject
+    pub subject_template: Option<String>,
+    /// IANA time zone like `Europe/Berlin`, which overrides the offset in
+    /// `daily_send_time` and follows daylight saving changes
+    pub timezone: Option<String>,
+    pub pending_approval: bool,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, AsChangeset)]
@@ -45,9 +93,12 @@ pub struct PartialUser {
     pub send_email: Option<String>,
     pub is_active: Option<bool>,
     pub daily_send_time: Option<String>, // HH:MM+HH:MM
-    pub role: Option<String>,
-    #[serde(skip_deserializing)]
-    pub refresh_token: Option<String>,
+    pub role: Option<Roles>,
+    pub item_truncate_length: Option<i32>,
+    pub from_name: Option<String>,
+    pub subject_template: Option<String>,
+    /// empty clears it
+    pub timezone: Option<String>,
 }
 
 impl PartialUser {
@@ -57,6 +108,34 @@ impl PartialUser {
             && self.is_active.is_none()
             && self.daily_send_time.is_none()
             && self.role.is_none()
+            && self.item_truncate_length.is_none()
+            && self.from_name.is_none()
+            && self.subject_template.is_none()
+            && self.timezone.is_none()
+    }
+}
+
+impl Validate for PartialUser {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(login_email) = &self.login_email {
+            errors.email("login_email", login_email);
+        }
+        if let Some(send_email) = &self.send_email {
+            errors.email("send_email", send_email);
+        }
+        errors.non_negative("item_truncate_length", self.item_truncate_length);
+        // an empty template clears the user's own
+        if let Some(template) = self.subject_template.as_deref().filter(|t| !t.is_empty()) {
+            errors.subject_template("subject_template", template);
+        }
+        if let Some(send_time) = &self.daily_send_time {
+            errors.daily_send_time("daily_send_time", send_time);
+        }
+        if let Some(timezone) = self.timezone.as_deref().filter(|tz| !tz.is_empty()) {
+            if let Err(e) = time_zone::get(timezone) {
+                errors.add("timezone", e.to_string());
+            }
+        }
     }
 }
 
@@ -66,33 +145,156 @@ pub struct NewUser {
     pub password: String,
 }
 
+impl Validate for NewUser {
+    fn check(&self, errors: &mut ValidationErrors) {
+        errors.email("email", &self.email);
+        if let Err(e) = PasswordPolicy::global().validate(&self.password) {
+            errors.add("password", e.to_string());
+        }
+    }
+}
+
+/// A user with what the admin user list shows about their activity
+#[derive(Debug, Serialize, QueryableByName)]
+pub struct UserSummary {
+    #[diesel(embed)]
+    #[serde(flatten)]
+    pub user: User,
+    #[diesel(sql_type = BigInt)]
+    pub active_subscriptions: i64,
+    /// when the relay last accepted a delivery to the user
+    #[diesel(sql_type = Nullable<BigInt>)]
+    pub last_delivery_at: Option<i64>,
+    #[serde(skip)]
+    #[diesel(sql_type = Bool)]
+    has_webhook: bool,
+    #[serde(skip)]
+    #[diesel(sql_type = Bool)]
+    has_discord: bool,
+    #[serde(skip)]
+    #[diesel(sql_type = Bool)]
+    has_matrix: bool,
+    #[serde(skip)]
+    #[diesel(sql_type = Bool)]
+    has_push: bool,
+}
+
+impl UserSummary {
+    /// Channels other than email the user has set up to deliver to
+    pub fn channels(&self) -> Vec<Channel> {
+        [
+            (self.has_webhook, Channel::Webhook),
+            (self.has_discord, Channel::Discord),
+            (self.has_matrix, Channel::Matrix),
+            (self.has_push, Channel::Push),
+        ]
+        .into_iter()
+        .filter_map(|(configured, channel)| configured.then_some(channel))
+        .collect()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub enum UserTableError {
     UserNotFound,
     EmailExists,
     PasswordHashError,
     PasswordTooShort,
+    /// Password doesn't meet the password policy; holds the reason
+    WeakPassword(String),
     DatabaseError,
     Unauthorized,
 }
 
 #[derive(Debug)]
 pub enum UserQuery<'a> {
-    Id(i32),
+    Id(UserId),
     Email(&'a str),
 }
 
 impl User {
+    /// The user's offset from UTC at the given time, from their time zone
+    /// if they've set one, else the offset in `daily_send_time`
+    pub fn utc_offset(&self, at: i64) -> FixedOffset {
+        if let Some(name) = self.timezone.as_deref().filter(|tz| !tz.is_empty()) {
+            match time_zone::get(name) {
+                Ok(zone) => return time_zone::offset_at(zone, at),
+                Err(e) => log::warn!("Ignoring time zone of user {}: {}", self.id, e),
+            }
+        }
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let offset = match self.daily_send_time.get(5..) {
+            Some(offset) if !offset.is_empty() => offset,
+            _ => return utc,
+        };
+        let (sign, hours_minutes) = match offset.split_at(1) {
+            ("+", rest) => (1, rest),
+            ("-", rest) => (-1, rest),
+            _ => return utc,
+        };
+        let seconds = hours_minutes.split_once(':').and_then(|(hours, minutes)| {
+            Some(hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60)
+        });
+        seconds
+            .and_then(|seconds| FixedOffset::east_opt(sign * seconds))
+            .unwrap_or(utc)
+    }
+
+    /// The user's local date at the given time
+    pub fn local_date(&self, now: i64) -> NaiveDate {
+        self.utc_offset(now)
+            .timestamp_opt(now, 0)
+            .single()
+            .map_or(NaiveDate::MIN, |time| time.date_naive())
+    }
+
+    /// The user's local time of day at the given time
+    pub fn local_time(&self, now: i64) -> NaiveTime {
+        self.utc_offset(now)
+            .timestamp_opt(now, 0)
+            .single()
+            .map_or(NaiveTime::MIN, |time| time.time())
+    }
+
+    /// The local time of day daily emails are sent, from `daily_send_time`,
+    /// or midnight if it isn't set
+    pub fn send_time(&self) -> NaiveTime {
+        self.daily_send_time
+            .get(..5)
+            .and_then(|time| NaiveTime::parse_from_str(time, "%H:%M").ok())
+            .unwrap_or(NaiveTime::MIN)
+    }
+
     // TODO: refactor the way the models for feed_items and feeds are
     pub fn create(
-        conn: &mut SqliteConnection,
+        conn: &mut DbConnection,
         new_user: &NewUser,
         claims: Claims,
     ) -> Result<User, UserTableError> {
-        if &claims.role != "admin" {
+        if !claims.role.is_admin() {
             log::warn!("User {} is not an admin", claims.sub);
             return Err(UserTableError::UserNotFound);
         }
+        Self::register(conn, new_user)
+    }
+
+    /// Create a user without an admin asking for it, for an invite the
+    /// caller has already checked
+    pub fn register(conn: &mut DbConnection, new_user: &NewUser) -> Result<User, UserTableError> {
+        Self::insert(conn, new_user, false)
+    }
+
+    /// Create an inactive user who signed up on their own, for an admin to
+    /// approve or reject
+    pub fn sign_up(conn: &mut DbConnection, new_user: &NewUser) -> Result<User, UserTableError> {
+        Self::insert(conn, new_user, true)
+    }
+
+    fn insert(
+        conn: &mut DbConnection,
+        new_user: &NewUser,
+        pending: bool,
+    ) -> Result<User, UserTableError> {
         use crate::schema::users::dsl::*;
         let user_exists = users
             .filter(login_email.eq(&new_user.email))
@@ -100,10 +302,15 @@ impl User {
             .is_ok();
 
         if user_exists {
-            log::warn!("User with email {} already exists", new_user.email);
+            log::warn!(
+                "User with email {} already exists",
+                redact::email(&new_user.email)
+            );
             return Err(UserTableError::EmailExists);
         }
 
+        Self::check_password_policy(&new_user.password)?;
+
         let password_hash = match Self::hash_password(&new_user.password) {
             Ok(hash) => hash,
             Err(UserTableError::PasswordTooShort) => {
@@ -120,11 +327,16 @@ impl User {
             login_email: new_user.email.clone(),
             send_email: new_user.email.clone(),
             password: password_hash,
-            created_at: chrono::Utc::now().timestamp() as i32,
-            is_active: true,
+            created_at: chrono::Utc::now().timestamp(),
+            is_active: !pending,
             daily_send_time: "00:00+00:00".into(),
-            role: "user".into(),
-            refresh_token: None,
+            role: Role::User.into(),
+            item_truncate_length: DEFAULT_ITEM_TRUNCATE_LENGTH,
+            must_change_password: false,
+            from_name: None,
+            subject_template: None,
+            timezone: None,
+            pending_approval: pending,
         };
 
         match diesel::insert_into(users).values(&user).get_result(conn) {
@@ -136,7 +348,7 @@ impl User {
         }
     }
 
-    pub fn exists(conn: &mut SqliteConnection, email: &str) -> bool {
+    pub fn exists(conn: &mut DbConnection, email: &str) -> bool {
         use crate::schema::users::dsl::*;
         users
             .filter(login_email.eq(email))
@@ -144,7 +356,7 @@ impl User {
             .is_ok()
     }
 
-    pub fn get(conn: &mut SqliteConnection, query: UserQuery) -> Option<User> {
+    pub fn get(conn: &mut DbConnection, query: UserQuery) -> Option<User> {
         use crate::schema::users::dsl::*;
         log::info!("Getting user: {:?}", query);
         match query {
@@ -153,7 +365,7 @@ impl User {
         }
     }
 
-    pub fn get_all(conn: &mut SqliteConnection) -> Result<Vec<User>, UserTableError> {
+    pub fn get_all(conn: &mut DbConnection) -> Result<Vec<User>, UserTableError> {
         use crate::schema::users::dsl::*;
         log::info!("Getting all users");
         users.load::<User>(conn).map_err(|err| {
@@ -162,12 +374,84 @@ impl User {
         })
     }
 
-    pub fn get_all_admin(conn: &mut SqliteConnection) -> Result<Vec<User>, UserTableError> {
+    /// Every user with their subscription, login and delivery activity,
+    /// gathered in one query
+    pub fn summaries(conn: &mut DbConnection) -> Result<Vec<UserSummary>, UserTableError> {
+        diesel::sql_query(format!(
+            "SELECT users.*,
+                COALESCE(subs.count, 0) AS active_subscriptions,
+                delivered.sent_at AS last_delivery_at,
+                COALESCE(channels.webhook, FALSE) AS has_webhook,
+                COALESCE(channels.discord, FALSE) AS has_discord,
+                COALESCE(channels.matrix, FALSE) AS has_matrix,
+                COALESCE(channels.push, FALSE) AS has_push
+            FROM users
+            LEFT JOIN (
+                SELECT user_id, COUNT(*) AS count
+                FROM subscriptions
+                WHERE is_active
+                GROUP BY user_id
+            ) AS subs ON subs.user_id = users.id
+            LEFT JOIN (
+                SELECT subscriptions.user_id, MAX(deliveries.sent_at) AS sent_at
+                FROM deliveries
+                JOIN subscriptions ON subscriptions.id = deliveries.subscription_id
+                WHERE deliveries.accepted
+                GROUP BY subscriptions.user_id
+            ) AS delivered ON delivered.user_id = users.id
+            LEFT JOIN (
+                SELECT user_id,
+                    COUNT(CASE WHEN key = '{webhook_url}' THEN 1 END) > 0 AS webhook,
+                    COUNT(CASE WHEN key = '{discord_url}' THEN 1 END) > 0 AS discord,
+                    COUNT(CASE WHEN key IN ('{matrix_token}', '{matrix_room}') THEN 1 END) = 2
+                        AS matrix,
+                    COUNT(CASE WHEN key = '{push_service}' THEN 1 END) > 0
+                        AND COUNT(CASE WHEN key IN ('{push_topic}', '{push_token}') THEN 1 END) > 0
+                        AS push
+                FROM settings
+                WHERE user_id IS NOT NULL AND value != ''
+                GROUP BY user_id
+            ) AS channels ON channels.user_id = users.id
+            ORDER BY users.id",
+            webhook_url = delivery_webhook::URL,
+            discord_url = discord_webhook::URL,
+            matrix_token = matrix_settings::ACCESS_TOKEN,
+            matrix_room = matrix_settings::ROOM_ID,
+            push_service = push_settings::SERVICE,
+            push_topic = push_settings::TOPIC,
+            push_token = push_settings::TOKEN,
+        ))
+        .load::<UserSummary>(conn)
+        .map_err(|err| {
+            log::error!("Failed to get user summaries: {:?}", err);
+            UserTableError::DatabaseError
+        })
+    }
+
+    /// Note that the user just logged in
+    pub fn record_login(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        at: i64,
+    ) -> Result<(), UserTableError> {
+        use crate::schema::users::dsl::*;
+        diesel::update(users.filter(id.eq(user_id)))
+            .set(last_login_at.eq(at))
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|err| {
+                log::error!("Failed to record login: {:?}", err);
+                UserTableError::DatabaseError
+            })
+    }
+
+    pub fn get_all_admin(conn: &mut DbConnection) -> Result<Vec<User>, UserTableError> {
         use crate::schema::users::dsl::*;
         log::info!("Getting all admins");
+        // roles are a CSV column, so admins are picked out after loading
         users
-            .filter(role.eq("admin"))
             .load::<User>(conn)
+            .map(|all| all.into_iter().filter(|u| u.role.is_admin()).collect())
             .map_err(|err| {
                 log::error!("Failed to get admins: {:?}", err);
                 UserTableError::DatabaseError
@@ -175,8 +459,8 @@ impl User {
     }
 
     pub fn update(
-        conn: &mut SqliteConnection,
-        user_id: i32,
+        conn: &mut DbConnection,
+        user_id: UserId,
         updates: &PartialUser,
     ) -> Result<User, UserTableError> {
         use crate::schema::users::dsl::*;
@@ -184,7 +468,10 @@ impl User {
         if let Some(update_email) = &updates.login_email {
             let user_exists = User::exists(conn, update_email);
             if user_exists {
-                log::warn!("User with email {} already exists", update_email);
+                log::warn!(
+                    "User with email {} already exists",
+                    redact::email(update_email)
+                );
                 return Err(UserTableError::EmailExists);
             }
         }
@@ -202,41 +489,15 @@ impl User {
         }
     }
 
-    pub fn clear_refresh_token(
-        conn: &mut SqliteConnection,
-        user_id: UserQuery,
-    ) -> Result<(), UserTableError> {
-        use crate::schema::users::dsl::*;
-
-        log::info!("Clearing refresh token for user: {:?}", user_id);
-
-        let res = match user_id {
-            UserQuery::Id(user_id) => diesel::update(users.filter(id.eq(user_id)))
-                .set(refresh_token.eq(None::<String>))
-                .execute(conn),
-            UserQuery::Email(email) => diesel::update(users.filter(login_email.eq(email)))
-                .set(refresh_token.eq(None::<String>))
-                .execute(conn),
-        };
-
-        match res {
-            Ok(_) => Ok(()),
-            Err(err) => {
-                log::error!("Failed to clear refresh token: {:?}", err);
-                Err(UserTableError::DatabaseError)
-            }
-        }
-    }
-
     pub fn delete(
-        conn: &mut SqliteConnection,
-        user_id: i32,
+        conn: &mut DbConnection,
+        user_id: UserId,
         claims: Claims,
     ) -> Result<(), UserTableError> {
         use crate::schema::users::dsl::*;
         log::info!("Deleting user (id={})", user_id);
 
-        if claims.role != "admin" && claims.sub != user_id {
+        if !claims.role.is_admin() && claims.sub != user_id {
             log::warn!(
                 "User {} is not authorized to delete user {}",
                 claims.sub,
@@ -245,15 +506,17 @@ impl User {
             return Err(UserTableError::Unauthorized);
         }
 
-        let deleted_rows = diesel::delete(users.filter(id.eq(user_id)))
-            .execute(conn)
+        let deleted_rows = conn
+            .transaction(|conn| {
+                User::delete_owned(conn, user_id)?;
+                diesel::delete(users.filter(id.eq(user_id))).execute(conn)
+            })
             .map_err(|err| {
                 log::error!("Failed to delete user: {:?}", err);
                 UserTableError::DatabaseError
-            })
-            .ok();
+            })?;
 
-        if deleted_rows == Some(0) {
+        if deleted_rows == 0 {
             log::warn!("User with id {} does not exist", user_id);
             Err(UserTableError::UserNotFound)
         } else {
@@ -261,6 +524,156 @@ impl User {
         }
     }
 
+    /// Delete everything stored for the user, with their subscriptions'
+    /// deliveries, share links and tags. Audit entries about the account go
+    /// too; ones about what they did to other accounts are kept.
+    fn delete_owned(conn: &mut DbConnection, uid: UserId) -> QueryResult<()> {
+        Onboarding::delete(conn, uid)?;
+        let subs = subscriptions::table
+            .filter(subscriptions::user_id.eq(uid))
+            .select(subscriptions::id);
+        diesel::delete(deliveries::table.filter(deliveries::subscription_id.eq_any(subs)))
+            .execute(conn)?;
+        diesel::delete(share_links::table.filter(share_links::subscription_id.eq_any(subs)))
+            .execute(conn)?;
+        diesel::delete(
+            subscription_tags::table.filter(subscription_tags::subscription_id.eq_any(subs)),
+        )
+        .execute(conn)?;
+        let tag_ids = tags::table.filter(tags::user_id.eq(uid)).select(tags::id);
+        diesel::delete(subscription_tags::table.filter(subscription_tags::tag_id.eq_any(tag_ids)))
+            .execute(conn)?;
+        diesel::delete(subscriptions::table.filter(subscriptions::user_id.eq(uid)))
+            .execute(conn)?;
+        diesel::delete(tags::table.filter(tags::user_id.eq(uid))).execute(conn)?;
+        diesel::delete(sessions::table.filter(sessions::user_id.eq(uid))).execute(conn)?;
+        diesel::delete(
+            personal_access_tokens::table.filter(personal_access_tokens::user_id.eq(uid)),
+        )
+        .execute(conn)?;
+        diesel::delete(two_factor::table.filter(two_factor::user_id.eq(uid))).execute(conn)?;
+        diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(uid)))
+            .execute(conn)?;
+        diesel::delete(password_reset_tokens::table.filter(password_reset_tokens::user_id.eq(uid)))
+            .execute(conn)?;
+        diesel::delete(starred_items::table.filter(starred_items::user_id.eq(uid)))
+            .execute(conn)?;
+        diesel::delete(read_items::table.filter(read_items::user_id.eq(uid))).execute(conn)?;
+        diesel::delete(saved_searches::table.filter(saved_searches::user_id.eq(uid)))
+            .execute(conn)?;
+        diesel::delete(
+            subscription_templates::table.filter(subscription_templates::user_id.eq(uid)),
+        )
+        .execute(conn)?;
+        diesel::delete(settings::table.filter(settings::user_id.eq(uid))).execute(conn)?;
+        diesel::delete(invites::table.filter(invites::created_by.eq(uid))).execute(conn)?;
+        diesel::delete(audit_log::table.filter(audit_log::user_id.eq(uid))).execute(conn)?;
+        Ok(())
+    }
+
+    /// Let a user who signed up log in
+    pub fn approve(conn: &mut DbConnection, user_id: UserId) -> Result<User, UserTableError> {
+        use crate::schema::users::dsl::*;
+        log::info!("Approving user (id={})", user_id);
+        diesel::update(
+            users
+                .filter(id.eq(user_id))
+                .filter(pending_approval.eq(true)),
+        )
+        .set((pending_approval.eq(false), is_active.eq(true)))
+        .get_result::<User>(conn)
+        .optional()
+        .map_err(|err| {
+            log::error!("Failed to approve user: {:?}", err);
+            UserTableError::DatabaseError
+        })?
+        .ok_or(UserTableError::UserNotFound)
+    }
+
+    /// Delete a user who signed up and hasn't been approved. Nothing else
+    /// is stored for them until they are.
+    pub fn reject(conn: &mut DbConnection, user_id: UserId) -> Result<(), UserTableError> {
+        use crate::schema::users::dsl::*;
+        log::info!("Rejecting user (id={})", user_id);
+        let deleted = diesel::delete(
+            users
+                .filter(id.eq(user_id))
+                .filter(pending_approval.eq(true)),
+        )
+        .execute(conn)
+        .map_err(|err| {
+            log::error!("Failed to reject user: {:?}", err);
+            UserTableError::DatabaseError
+        })?;
+        match deleted {
+            0 => Err(UserTableError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// Replace the user's password with `temp_password`, log out all of
+    /// their sessions, and require a password change on next login.
+    /// The temporary password isn't checked against the password policy
+    /// since the user has to replace it anyway.
+    pub fn force_password_reset(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        temp_password: &str,
+    ) -> Result<User, UserTableError> {
+        log::info!("Forcing password reset for user (id={})", user_id);
+        Self::set_password(conn, user_id, temp_password, true)
+    }
+
+    /// Replace the user's password and log out all of their sessions
+    pub fn change_password(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        new_password: &str,
+    ) -> Result<User, UserTableError> {
+        log::info!("Changing password for user (id={})", user_id);
+        Self::check_password_policy(new_password)?;
+        Self::set_password(conn, user_id, new_password, false)
+    }
+
+    fn check_password_policy(password: &str) -> Result<(), UserTableError> {
+        if password.is_empty() {
+            return Err(UserTableError::PasswordTooShort);
+        }
+        PasswordPolicy::global().validate(password).map_err(|e| {
+            log::warn!("Password rejected by policy: {}", e);
+            UserTableError::WeakPassword(e.to_string())
+        })
+    }
+
+    fn set_password(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        new_password: &str,
+        require_change: bool,
+    ) -> Result<User, UserTableError> {
+        use crate::schema::users::dsl::*;
+
+        let password_hash = Self::hash_password(new_password)?;
+
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let user = diesel::update(users.filter(id.eq(user_id)))
+                .set((
+                    password.eq(password_hash),
+                    must_change_password.eq(require_change),
+                ))
+                .get_result::<User>(conn)?;
+            Session::delete_for_user(conn, user_id, None)?;
+            Ok(user)
+        })
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => UserTableError::UserNotFound,
+            err => {
+                log::error!("Failed to set password: {:?}", err);
+                UserTableError::DatabaseError
+            }
+        })
+    }
+
     fn hash_password(password: &str) -> Result<String, UserTableError> {
         if password.is_empty() {
             return Err(UserTableError::PasswordTooShort);
@@ -295,6 +708,7 @@ mod tests {
     use chrono::Utc;
 
     use super::*;
+    use crate::models::session::NewSession;
     use crate::test_helpers::test_helpers::get_test_db_connection;
 
     #[test]
@@ -302,14 +716,16 @@ mod tests {
         let mut conn = get_test_db_connection();
         let new_user = NewUser {
             email: "test@me.com".into(),
-            password: "password".into(),
+            password: "correct horse".into(),
         };
 
         let claims = Claims {
-            sub: 0,
+            sub: UserId(0),
             email: new_user.email.clone(),
-            role: "admin".into(),
+            role: Role::Admin.into(),
             exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
         };
 
         let result = User::create(&mut conn, &new_user, claims.clone());
@@ -327,8 +743,122 @@ mod tests {
         assert_eq!(user.login_email, new_user.email);
         assert_eq!(user.send_email, new_user.email);
         assert_ne!(user.password, new_user.password);
-        assert_eq!(user.is_active, true);
-        assert_eq!(user.role, "user");
+        assert!(user.is_active);
+        assert_eq!(user.role, Role::User.into());
+        assert!(!user.must_change_password);
+    }
+
+    #[test]
+    fn test_local_date() {
+        let mut conn = get_test_db_connection();
+        let new_user = NewUser {
+            email: "test@me.com".into(),
+            password: "correct horse".into(),
+        };
+        let claims = Claims {
+            sub: UserId(0),
+            email: new_user.email.clone(),
+            role: Role::Admin.into(),
+            exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
+        };
+        let mut user = User::create(&mut conn, &new_user, claims).unwrap();
+        // 2026-10-16 23:30 UTC
+        let now = 1_792_193_400;
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(user.local_date(now), date(2026, 10, 16));
+
+        user.daily_send_time = "08:00+05:30".to_string();
+        assert_eq!(user.utc_offset(now).local_minus_utc(), 5 * 3600 + 30 * 60);
+        assert_eq!(user.local_date(now), date(2026, 10, 17));
+        assert_eq!(
+            user.local_time(now),
+            NaiveTime::from_hms_opt(5, 0, 0).unwrap()
+        );
+
+        assert_eq!(user.send_time(), NaiveTime::from_hms_opt(8, 0, 0).unwrap());
+
+        user.daily_send_time = "08:00-07:00".to_string();
+        assert_eq!(user.local_date(now), date(2026, 10, 16));
+
+        // no offset is UTC
+        user.daily_send_time = String::new();
+        assert_eq!(user.utc_offset(now).local_minus_utc(), 0);
+        assert_eq!(user.send_time(), NaiveTime::MIN);
+
+        // a time zone overrides the offset
+        user.daily_send_time = "08:00+05:30".to_string();
+        user.timezone = Some("America/New_York".to_string());
+        assert_eq!(user.utc_offset(now).local_minus_utc(), -4 * 3600);
+        // 2026-12-16 23:30 UTC, after daylight saving ends
+        let winter = now + 61 * 24 * 3600;
+        assert_eq!(user.utc_offset(winter).local_minus_utc(), -5 * 3600);
+    }
+
+    #[test]
+    fn test_force_password_reset() {
+        let mut conn = get_test_db_connection();
+        let new_user = NewUser {
+            email: "test@me.com".into(),
+            password: "correct horse".into(),
+        };
+
+        let claims = Claims {
+            sub: UserId(0),
+            email: new_user.email.clone(),
+            role: Role::Admin.into(),
+            exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
+        };
+
+        let user = User::create(&mut conn, &new_user, claims).unwrap();
+        NewSession::new(user.id, 1000, None, None)
+            .insert(&mut conn)
+            .unwrap();
+
+        let user = User::force_password_reset(&mut conn, user.id, "temporary").unwrap();
+        assert!(user.must_change_password);
+        assert_eq!(Session::get_for_user(&mut conn, user.id, 1000), Ok(vec![]));
+        assert!(!User::check_password(&user, "correct horse").unwrap());
+        assert!(User::check_password(&user, "temporary").unwrap());
+
+        let result = User::force_password_reset(&mut conn, UserId(user.id.0 + 1), "temporary");
+        assert!(matches!(result, Err(UserTableError::UserNotFound)));
+    }
+
+    #[test]
+    fn test_change_password() {
+        let mut conn = get_test_db_connection();
+        let new_user = NewUser {
+            email: "test@me.com".into(),
+            password: "correct horse".into(),
+        };
+
+        let claims = Claims {
+            sub: UserId(0),
+            email: new_user.email.clone(),
+            role: Role::Admin.into(),
+            exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
+        };
+
+        let user = User::create(&mut conn, &new_user, claims).unwrap();
+        let user = User::force_password_reset(&mut conn, user.id, "temporary").unwrap();
+        assert!(user.must_change_password);
+
+        let user = User::change_password(&mut conn, user.id, "new password").unwrap();
+        assert!(!user.must_change_password);
+        assert!(User::check_password(&user, "new password").unwrap());
+        assert!(!User::check_password(&user, "temporary").unwrap());
+
+        let result = User::change_password(&mut conn, user.id, "");
+        assert!(matches!(result, Err(UserTableError::PasswordTooShort)));
+
+        let result = User::change_password(&mut conn, user.id, "qwerty123");
+        assert!(matches!(result, Err(UserTableError::WeakPassword(_))));
     }
 
     #[test]
@@ -336,14 +866,16 @@ mod tests {
         let mut conn = get_test_db_connection();
         let new_user = NewUser {
             email: "test@me.com".into(),
-            password: "password".into(),
+            password: "correct horse".into(),
         };
 
         let claims = Claims {
-            sub: 0,
+            sub: UserId(0),
             email: new_user.email.clone(),
-            role: "user".into(),
+            role: Role::User.into(),
             exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
         };
 
         let result = User::create(&mut conn, &new_user, claims);
@@ -359,10 +891,12 @@ mod tests {
         };
 
         let claims = Claims {
-            sub: 0,
+            sub: UserId(0),
             email: new_user.email.clone(),
-            role: "admin".into(),
+            role: Role::Admin.into(),
             exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
         };
 
         let result = User::create(&mut conn, &new_user, claims);
@@ -377,14 +911,16 @@ mod tests {
         let mut conn = get_test_db_connection();
         let new_user = NewUser {
             email: "test@me.com".into(),
-            password: "password".into(),
+            password: "correct horse".into(),
         };
 
         let claims = Claims {
-            sub: 0,
+            sub: UserId(0),
             email: new_user.email.clone(),
-            role: "admin".into(),
+            role: Role::Admin.into(),
             exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
         };
 
         let result = User::create(&mut conn, &new_user, claims);
@@ -394,8 +930,8 @@ mod tests {
         assert_eq!(existing_user.login_email, new_user.email);
         assert_eq!(existing_user.send_email, new_user.email);
         assert_ne!(existing_user.password, new_user.password);
-        assert_eq!(existing_user.is_active, true);
-        assert_eq!(existing_user.role, "user");
+        assert!(existing_user.is_active);
+        assert_eq!(existing_user.role, Role::User.into());
 
         let user = PartialUser {
             login_email: Some("myNewEmail@ok.yup".into()),
@@ -403,7 +939,10 @@ mod tests {
             is_active: Some(true),
             role: None,
             daily_send_time: None,
-            refresh_token: Some("some refresh token".into()),
+            item_truncate_length: None,
+            from_name: None,
+            subject_template: None,
+            timezone: None,
         };
 
         let result = User::update(&mut conn, existing_user.id, &user);
@@ -412,9 +951,41 @@ mod tests {
         let user = User::get(&mut conn, UserQuery::Email(&user.login_email.unwrap())).unwrap();
         assert_eq!(user.login_email, "myNewEmail@ok.yup");
         assert_eq!(user.send_email, "test@me.com");
-        assert_ne!(user.password, "password");
-        assert_eq!(user.is_active, true);
-        assert_eq!(user.role, "user");
+        assert_ne!(user.password, "correct horse");
+        assert!(user.is_active);
+        assert_eq!(user.role, Role::User.into());
+    }
+
+    #[test]
+    fn test_sign_up_approve_and_reject() {
+        let mut conn = get_test_db_connection();
+        let sign_up = |conn: &mut DbConnection, email: &str| {
+            let new_user = NewUser {
+                email: email.into(),
+                password: "correct horse".into(),
+            };
+            User::sign_up(conn, &new_user).unwrap()
+        };
+
+        let user = sign_up(&mut conn, "pending@me.com");
+        assert!(user.pending_approval);
+        assert!(!user.is_active);
+        let approved = User::approve(&mut conn, user.id).unwrap();
+        assert!(!approved.pending_approval);
+        assert!(approved.is_active);
+        // only pending users can be approved or rejected
+        assert!(matches!(
+            User::approve(&mut conn, user.id),
+            Err(UserTableError::UserNotFound)
+        ));
+        assert!(matches!(
+            User::reject(&mut conn, user.id),
+            Err(UserTableError::UserNotFound)
+        ));
+
+        let user = sign_up(&mut conn, "spam@me.com");
+        assert!(User::reject(&mut conn, user.id).is_ok());
+        assert!(User::get(&mut conn, UserQuery::Id(user.id)).is_none());
     }
 
     #[test]
@@ -422,14 +993,16 @@ mod tests {
         let mut conn = get_test_db_connection();
         let new_user = NewUser {
             email: "me@test.com".into(),
-            password: "password".into(),
+            password: "correct horse".into(),
         };
 
         let claims = Claims {
-            sub: 0,
+            sub: UserId(0),
             email: new_user.email.clone(),
-            role: "admin".into(),
+            role: Role::Admin.into(),
             exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
         };
 
         let result = User::create(&mut conn, &new_user, claims.clone());
@@ -447,14 +1020,16 @@ mod tests {
         let mut conn = get_test_db_connection();
         let new_user = NewUser {
             email: "me@test.com".into(),
-            password: "password".into(),
+            password: "correct horse".into(),
         };
 
         let claims = Claims {
-            sub: 0,
+            sub: UserId(0),
             email: "admin".into(),
-            role: "admin".into(),
+            role: Role::Admin.into(),
             exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
         };
 
         let result = User::create(&mut conn, &new_user, claims);
@@ -464,10 +1039,12 @@ mod tests {
         assert_eq!(user.login_email, new_user.email);
 
         let claims = Claims {
-            sub: 0,
+            sub: UserId(0),
             email: new_user.email.clone(),
-            role: "user".into(),
+            role: Role::User.into(),
             exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
         };
 
         let result = User::delete(&mut conn, user.id, claims);
@@ -477,11 +1054,215 @@ mod tests {
         let claims = Claims {
             sub: user.id,
             email: new_user.email.clone(),
-            role: "user".into(),
+            role: Role::User.into(),
             exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
         };
 
         let result = User::delete(&mut conn, user.id, claims);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_delete_removes_what_the_user_owns() {
+        use crate::models::{
+            audit_log::{AuditAction, NewAuditEntry},
+            delivery::NewDelivery,
+            ids::FeedId,
+            personal_access_token::NewPersonalAccessToken,
+            read_item::ReadItem,
+            settings::{NewSetting, Setting},
+            share_link::NewShareLink,
+            starred_item::StarredItem,
+            subscription::NewSubscription,
+            tag::Tag,
+        };
+
+        let mut conn = get_test_db_connection();
+        let admin = Claims {
+            sub: UserId(0),
+            email: "admin".into(),
+            role: Role::Admin.into(),
+            exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
+        };
+        let mut add_user = |email: &str| {
+            let new_user = NewUser {
+                email: email.into(),
+                password: "correct horse".into(),
+            };
+            User::create(&mut conn, &new_user, admin.clone()).unwrap();
+            User::get(&mut conn, UserQuery::Email(email)).unwrap().id
+        };
+        let (uid, other) = (add_user("me@test.com"), add_user("other@test.com"));
+
+        let sub = NewSubscription {
+            user_id: uid,
+            feed_id: FeedId(1),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        NewDelivery {
+            subscription_id: sub.id,
+            sent_at: 100,
+            recipient: "me@test.com",
+            item_count: 1,
+            accepted: true,
+            relay_response: "250 OK",
+        }
+        .insert(&mut conn);
+        NewShareLink {
+            subscription_id: sub.id,
+            token_hash: String::new(),
+            item_count: 10,
+            created_at: 100,
+        }
+        .insert(&mut conn)
+        .unwrap();
+        Tag::set_for_subscription(&mut conn, uid, sub.id, &["news".to_string()], 100).unwrap();
+        NewSession::new(uid, 100, None, None)
+            .insert(&mut conn)
+            .unwrap();
+        NewPersonalAccessToken {
+            user_id: uid,
+            name: "cli".into(),
+            token_hash: String::new(),
+            created_at: 100,
+            expires_at: None,
+        }
+        .insert(&mut conn)
+        .unwrap();
+        StarredItem::star(&mut conn, uid, 1, 100).unwrap();
+        ReadItem::mark_read(&mut conn, uid, 1, 100).unwrap();
+        let setting = NewSetting {
+            user_id: Some(uid),
+            key: "digest.skip_days".into(),
+            value: "[]".into(),
+        };
+        Setting::set(&mut conn, &setting).unwrap();
+        NewAuditEntry::new(AuditAction::Login, Some(uid), Some(uid)).record(&mut conn);
+        NewAuditEntry::new(AuditAction::UserDeleted, Some(uid), Some(other)).record(&mut conn);
+
+        User::delete(&mut conn, uid, admin).unwrap();
+        let counts: Vec<i64> = vec![
+            subscriptions::table
+                .filter(subscriptions::user_id.eq(uid))
+                .count()
+                .get_result(&mut conn)
+                .unwrap(),
+            deliveries::table.count().get_result(&mut conn).unwrap(),
+            share_links::table.count().get_result(&mut conn).unwrap(),
+            subscription_tags::table
+                .count()
+                .get_result(&mut conn)
+                .unwrap(),
+            tags::table.count().get_result(&mut conn).unwrap(),
+            sessions::table.count().get_result(&mut conn).unwrap(),
+            personal_access_tokens::table
+                .count()
+                .get_result(&mut conn)
+                .unwrap(),
+            starred_items::table.count().get_result(&mut conn).unwrap(),
+            read_items::table.count().get_result(&mut conn).unwrap(),
+            settings::table
+                .filter(settings::user_id.eq(uid))
+                .count()
+                .get_result(&mut conn)
+                .unwrap(),
+        ];
+        assert_eq!(counts, vec![0; 10]);
+        // what they did to other accounts stays in the audit log
+        let audited: Vec<Option<UserId>> = audit_log::table
+            .select(audit_log::user_id)
+            .load(&mut conn)
+            .unwrap();
+        assert_eq!(audited, vec![Some(other)]);
+        assert!(User::get(&mut conn, UserQuery::Id(other)).is_some());
+    }
+
+    #[test]
+    fn test_summaries() {
+        use crate::models::{
+            delivery::NewDelivery,
+            settings::{NewSetting, Setting},
+            subscription::NewSubscription,
+        };
+
+        let mut conn = get_test_db_connection();
+        let claims = Claims {
+            sub: UserId(0),
+            email: "admin".into(),
+            role: Role::Admin.into(),
+            exp: (Utc::now().timestamp() + 1000) as usize,
+            sid: None,
+            must_change_password: false,
+        };
+        let mut create = |email: &str| {
+            let new_user = NewUser {
+                email: email.into(),
+                password: "correct horse".into(),
+            };
+            User::create(&mut conn, &new_user, claims.clone()).unwrap()
+        };
+        let active = create("active@test.com");
+        let idle = create("idle@test.com");
+
+        for is_active in [true, true, false] {
+            let sub = NewSubscription {
+                user_id: active.id,
+                is_active,
+                ..Default::default()
+            }
+            .insert(&mut conn)
+            .unwrap();
+            for (sent_at, accepted) in [(100, true), (200, false)] {
+                NewDelivery {
+                    subscription_id: sub.id,
+                    sent_at,
+                    recipient: "active@test.com",
+                    item_count: 1,
+                    accepted,
+                    relay_response: "",
+                }
+                .insert(&mut conn);
+            }
+        }
+        for (key, value) in [
+            (
+                "discord.webhook_url",
+                "https://discord.com/api/webhooks/1/x",
+            ),
+            ("matrix.room_id", "!room:example.com"),
+            ("matrix.access_token", ""),
+            ("push.service", "ntfy"),
+            ("push.topic", "news"),
+        ] {
+            let setting = NewSetting {
+                user_id: Some(active.id),
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+            Setting::set(&mut conn, &setting).unwrap();
+        }
+        User::record_login(&mut conn, active.id, 300).unwrap();
+
+        let summaries = User::summaries(&mut conn).unwrap();
+        assert_eq!(summaries.len(), 2);
+        let summary = &summaries[0];
+        assert_eq!(summary.user.id, active.id);
+        assert_eq!(summary.active_subscriptions, 2);
+        assert_eq!(summary.last_delivery_at, Some(100));
+        assert_eq!(summary.user.last_login_at, Some(300));
+        assert_eq!(summary.channels(), vec![Channel::Discord, Channel::Push]);
+
+        let summary = &summaries[1];
+        assert_eq!(summary.user.id, idle.id);
+        assert_eq!(summary.active_subscriptions, 0);
+        assert_eq!(summary.last_delivery_at, None);
+        assert_eq!(summary.user.last_login_at, None);
+        assert!(summary.channels().is_empty());
+    }
 }
@@ -0,0 +1,262 @@
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    prelude::*,
+    sql_types::Text,
+    AsExpression, FromSqlRow,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use super::ids::WebhookId;
+use crate::db::{to_sql_as_text, DbConnection};
+use crate::schema::*;
+use crate::security::validation::{Validate, ValidationErrors};
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Unknown event type '{0}'")]
+pub struct UnknownEventType(String);
+
+/// Something that happened on the instance that webhooks can be sent for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventType {
+    UserCreated,
+    /// a feed that was working started failing
+    FeedBroken,
+    DigestSent,
+    /// a background task, like sending a digest or database maintenance,
+    /// failed
+    TaskFailed,
+}
+
+impl EventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::UserCreated => "user_created",
+            EventType::FeedBroken => "feed_broken",
+            EventType::DigestSent => "digest_sent",
+            EventType::TaskFailed => "task_failed",
+        }
+    }
+}
+
+impl FromStr for EventType {
+    type Err = UnknownEventType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "user_created" => Ok(EventType::UserCreated),
+            "feed_broken" => Ok(EventType::FeedBroken),
+            "digest_sent" => Ok(EventType::DigestSent),
+            "task_failed" => Ok(EventType::TaskFailed),
+            other => Err(UnknownEventType(other.to_string())),
+        }
+    }
+}
+
+/// The event types a webhook receives, stored and serialized as a
+/// comma-separated list like `feed_broken,task_failed`
+#[derive(Debug, Clone, Default, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub struct EventTypes(BTreeSet<EventType>);
+
+impl EventTypes {
+    pub fn has(&self, event_type: EventType) -> bool {
+        self.0.contains(&event_type)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromStr for EventTypes {
+    type Err = UnknownEventType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|event_type| !event_type.trim().is_empty())
+            .map(EventType::from_str)
+            .collect::<Result<_, _>>()
+            .map(EventTypes)
+    }
+}
+
+impl fmt::Display for EventTypes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let event_types: Vec<&str> = self.0.iter().map(EventType::as_str).collect();
+        f.write_str(&event_types.join(","))
+    }
+}
+
+impl Serialize for EventTypes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for EventTypes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for EventTypes
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+        Ok(String::from_sql(bytes)?.parse()?)
+    }
+}
+
+to_sql_as_text!(EventTypes, |events| events.to_string());
+
+/// An admin-configured endpoint that instance events are POSTed to
+#[derive(Debug, Clone, Serialize, Queryable, Identifiable, PartialEq)]
+#[diesel(table_name = webhooks)]
+pub struct Webhook {
+    pub id: WebhookId,
+    pub url: String,
+    /// signs each request, see `tasks::webhooks`
+    pub secret: String,
+    pub events: EventTypes,
+    pub is_active: bool,
+    pub created_at: i64,
+    /// when an event was last sent to it, zero if never
+    pub last_delivery_at: i64,
+    /// why the last send failed, None if it succeeded
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = webhooks)]
+pub struct NewWebhook<'a> {
+    pub url: &'a str,
+    pub secret: &'a str,
+    pub events: &'a EventTypes,
+    pub is_active: bool,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Default, Deserialize, AsChangeset)]
+#[diesel(table_name = webhooks)]
+pub struct PartialWebhook {
+    pub url: Option<String>,
+    pub events: Option<EventTypes>,
+    pub is_active: Option<bool>,
+    #[serde(skip)]
+    pub last_delivery_at: Option<i64>,
+    #[serde(skip)]
+    pub last_error: Option<Option<String>>,
+}
+
+impl Validate for PartialWebhook {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(url) = &self.url {
+            errors.url("url", url);
+        }
+        if matches!(&self.events, Some(events) if events.is_empty()) {
+            errors.add("events", "Must include at least one event type");
+        }
+    }
+}
+
+impl<'a> NewWebhook<'a> {
+    pub fn insert(&self, conn: &mut DbConnection) -> QueryResult<Webhook> {
+        diesel::insert_into(webhooks::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Webhook {
+    pub fn get_all(conn: &mut DbConnection) -> QueryResult<Vec<Webhook>> {
+        webhooks::table.order(webhooks::id).load(conn)
+    }
+
+    /// Active webhooks that receive this type of event
+    pub fn get_for_event(
+        conn: &mut DbConnection,
+        event_type: EventType,
+    ) -> QueryResult<Vec<Webhook>> {
+        let active = webhooks::table
+            .filter(webhooks::is_active.eq(true))
+            .load::<Webhook>(conn)?;
+        Ok(active
+            .into_iter()
+            .filter(|webhook| webhook.events.has(event_type))
+            .collect())
+    }
+
+    pub fn update(
+        conn: &mut DbConnection,
+        webhook_id: WebhookId,
+        update: &PartialWebhook,
+    ) -> QueryResult<Webhook> {
+        diesel::update(webhooks::table.find(webhook_id))
+            .set(update)
+            .get_result(conn)
+    }
+
+    pub fn delete(conn: &mut DbConnection, webhook_id: WebhookId) -> QueryResult<usize> {
+        diesel::delete(webhooks::table.find(webhook_id)).execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_parse_event_types() {
+        let events: EventTypes = "task_failed, feed_broken".parse().unwrap();
+        assert!(events.has(EventType::FeedBroken));
+        assert!(!events.has(EventType::UserCreated));
+        assert_eq!(events.to_string(), "feed_broken,task_failed");
+        assert_eq!(
+            "feed_borken".parse::<EventTypes>(),
+            Err(UnknownEventType("feed_borken".to_string()))
+        );
+        assert!("".parse::<EventTypes>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_for_event() {
+        let mut conn = get_test_db_connection();
+        let events: EventTypes = "feed_broken".parse().unwrap();
+        let webhook = NewWebhook {
+            url: "https://example.com/hook",
+            secret: "secret",
+            events: &events,
+            is_active: true,
+            created_at: 1000,
+        }
+        .insert(&mut conn)
+        .unwrap();
+        assert_eq!(webhook.events, events);
+
+        let found = Webhook::get_for_event(&mut conn, EventType::FeedBroken).unwrap();
+        assert_eq!(found, vec![webhook.clone()]);
+        assert!(Webhook::get_for_event(&mut conn, EventType::DigestSent)
+            .unwrap()
+            .is_empty());
+
+        let update = PartialWebhook {
+            is_active: Some(false),
+            ..Default::default()
+        };
+        Webhook::update(&mut conn, webhook.id, &update).unwrap();
+        assert!(Webhook::get_for_event(&mut conn, EventType::FeedBroken)
+            .unwrap()
+            .is_empty());
+        assert_eq!(Webhook::delete(&mut conn, webhook.id), Ok(1));
+    }
+}
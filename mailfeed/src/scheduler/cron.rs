@@ -0,0 +1,301 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use thiserror::Error;
+
+/// Most steps taken looking for the next match before giving up. Every
+/// step moves to the next month, day, hour or minute, so this covers
+/// several years of any schedule that can match at all.
+const MAX_STEPS: usize = 100_000;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CronError {
+    #[error("Expected 5 fields (minute hour day-of-month month day-of-week), found {0}")]
+    FieldCount(usize),
+    #[error("Invalid {field} '{value}'")]
+    InvalidField { field: &'static str, value: String },
+    #[error("Unknown shortcut '{0}'")]
+    UnknownShortcut(String),
+    #[error("Schedule never matches")]
+    NeverMatches,
+}
+
+/// A standard five-field cron expression, like `0 9 * * mon-fri` for 9:00
+/// on weekdays, evaluated in UTC. Fields accept `*`, numbers, ranges
+/// (`1-5`), lists (`1,15`) and steps (`*/15`, `0-30/10`); months and
+/// weekdays also accept names, and Sunday is 0 or 7. As in cron, when both
+/// the day of the month and the day of the week are restricted, a day
+/// matching either is enough. `@hourly`, `@daily`, `@weekly`, `@monthly`
+/// and `@yearly` are shorthands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// whether the day fields were given, rather than `*`
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+struct Field {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    names: &'static [&'static str],
+    /// value of the first name
+    names_start: u32,
+}
+
+const MINUTE: Field = Field {
+    name: "minute",
+    min: 0,
+    max: 59,
+    names: &[],
+    names_start: 0,
+};
+const HOUR: Field = Field {
+    name: "hour",
+    min: 0,
+    max: 23,
+    names: &[],
+    names_start: 0,
+};
+const DAY_OF_MONTH: Field = Field {
+    name: "day of month",
+    min: 1,
+    max: 31,
+    names: &[],
+    names_start: 0,
+};
+const MONTH: Field = Field {
+    name: "month",
+    min: 1,
+    max: 12,
+    names: &MONTHS,
+    names_start: 1,
+};
+const DAY_OF_WEEK: Field = Field {
+    name: "day of week",
+    min: 0,
+    max: 7,
+    names: &WEEKDAYS,
+    names_start: 0,
+};
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<CronSchedule, CronError> {
+        let expression = expression.trim();
+        let expanded = match expression {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            shortcut if shortcut.starts_with('@') => {
+                return Err(CronError::UnknownShortcut(shortcut.to_string()))
+            }
+            expression => expression,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(CronError::FieldCount(fields.len()));
+        }
+
+        let mut days_of_week = parse_field(fields[4], &DAY_OF_WEEK)?;
+        // 7 is also Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        let schedule = CronSchedule {
+            minutes: parse_field(fields[0], &MINUTE)?,
+            hours: parse_field(fields[1], &HOUR)?,
+            days_of_month: parse_field(fields[2], &DAY_OF_MONTH)?,
+            months: parse_field(fields[3], &MONTH)?,
+            days_of_week,
+            dom_restricted: !fields[2].starts_with('*'),
+            dow_restricted: !fields[4].starts_with('*'),
+        };
+        if schedule.next_after(0).is_none() {
+            return Err(CronError::NeverMatches);
+        }
+        Ok(schedule)
+    }
+
+    /// The first matching minute after the given time, as a unix timestamp
+    pub fn next_after(&self, after: i64) -> Option<i64> {
+        let after = Utc.timestamp_opt(after, 0).single()?.naive_utc();
+        // the start of the next whole minute
+        let mut time =
+            after.date().and_hms_opt(after.hour(), after.minute(), 0)? + Duration::minutes(1);
+        for _ in 0..MAX_STEPS {
+            if !has(self.months, time.month()) {
+                time = next_month(time)?;
+            } else if !self.day_matches(time.date()) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !has(self.hours, time.hour()) {
+                time = time.date().and_hms_opt(time.hour(), 0, 0)? + Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(timestamp(time));
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let dom = has(self.days_of_month, date.day());
+        let dow = has(self.days_of_week, date.weekday().num_days_from_sunday());
+        if self.dom_restricted && self.dow_restricted {
+            dom || dow
+        } else {
+            dom && dow
+        }
+    }
+}
+
+fn timestamp(time: NaiveDateTime) -> i64 {
+    Utc.from_utc_datetime(&time).timestamp()
+}
+
+fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn next_month(time: NaiveDateTime) -> Option<NaiveDateTime> {
+    let (year, month) = match time.month() {
+        12 => (time.year() + 1, 1),
+        month => (time.year(), month + 1),
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)
+}
+
+/// The field's allowed values as bits
+fn parse_field(text: &str, field: &Field) -> Result<u64, CronError> {
+    let invalid = || CronError::InvalidField {
+        field: field.name,
+        value: text.to_string(),
+    };
+    let mut bits = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().map_err(|_| invalid())?;
+                if step == 0 {
+                    return Err(invalid());
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (Some(field.min), Some(field.max)),
+            range => match range.split_once('-') {
+                Some((start, end)) => (value(start, field), value(end, field)),
+                // `5/15` runs from 5 to the end
+                None if step > 1 => (value(range, field), Some(field.max)),
+                None => (value(range, field), value(range, field)),
+            },
+        };
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) if start <= end => (start, end),
+            _ => return Err(invalid()),
+        };
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn value(text: &str, field: &Field) -> Option<u32> {
+    let lower = text.to_ascii_lowercase();
+    let value = match field.names.iter().position(|name| *name == lower) {
+        Some(index) => index as u32 + field.names_start,
+        None => text.parse::<u32>().ok()?,
+    };
+    (field.min..=field.max).contains(&value).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str) -> i64 {
+        timestamp(NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap())
+    }
+
+    fn next(expression: &str, after: &str) -> i64 {
+        CronSchedule::parse(expression)
+            .unwrap()
+            .next_after(at(after))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_next_after() {
+        // 2026-10-16 is a Friday
+        assert_eq!(
+            next("0 9 * * mon-fri", "2026-10-16 08:30"),
+            at("2026-10-16 09:00")
+        );
+        assert_eq!(
+            next("0 9 * * mon-fri", "2026-10-16 09:00"),
+            at("2026-10-19 09:00")
+        );
+        assert_eq!(
+            next("*/15 * * * *", "2026-10-16 10:07"),
+            at("2026-10-16 10:15")
+        );
+        assert_eq!(
+            next("30 6 1 */3 *", "2026-10-16 00:00"),
+            at("2027-01-01 06:30")
+        );
+        // either day field matches when both are given
+        assert_eq!(
+            next("0 0 20 * 7", "2026-10-16 00:00"),
+            at("2026-10-18 00:00")
+        );
+        assert_eq!(next("@monthly", "2026-12-05 12:00"), at("2027-01-01 00:00"));
+        assert_eq!(
+            next("0 12 29 feb *", "2026-10-16 00:00"),
+            at("2028-02-29 12:00")
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            CronSchedule::parse("0 9 * *"),
+            Err(CronError::FieldCount(4))
+        );
+        assert_eq!(
+            CronSchedule::parse("60 * * * *"),
+            Err(CronError::InvalidField {
+                field: "minute",
+                value: "60".to_string()
+            })
+        );
+        assert_eq!(
+            CronSchedule::parse("0 9 * * fri-mon"),
+            Err(CronError::InvalidField {
+                field: "day of week",
+                value: "fri-mon".to_string()
+            })
+        );
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert_eq!(
+            CronSchedule::parse("@often"),
+            Err(CronError::UnknownShortcut("@often".to_string()))
+        );
+        assert_eq!(
+            CronSchedule::parse("0 0 31 feb *"),
+            Err(CronError::NeverMatches)
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use chrono::{FixedOffset, Offset, TimeZone as _, Utc};
+use chrono_tz::Tz;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Unknown time zone '{0}'")]
+pub struct UnknownTimeZone(String);
+
+/// A named time zone like `Europe/Berlin`, from the time zone database built
+/// into chrono-tz, so local times follow daylight saving changes
+pub fn get(name: &str) -> Result<Tz, UnknownTimeZone> {
+    name.parse().map_err(|_| UnknownTimeZone(name.to_string()))
+}
+
+/// The zone's offset from UTC at the given time
+pub fn offset_at(zone: Tz, at: i64) -> FixedOffset {
+    match Utc.timestamp_opt(at, 0).single() {
+        Some(time) => time.with_timezone(&zone).offset().fix(),
+        None => FixedOffset::east_opt(0).unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i32 = 60 * 60;
+
+    fn at(rfc3339: &str) -> i64 {
+        chrono::DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .timestamp()
+    }
+
+    fn offset(zone: Tz, rfc3339: &str) -> i32 {
+        offset_at(zone, at(rfc3339)).local_minus_utc()
+    }
+
+    #[test]
+    fn test_offset_at() {
+        let berlin = get("Europe/Berlin").unwrap();
+        assert_eq!(offset(berlin, "2026-01-15T12:00:00Z"), HOUR);
+        assert_eq!(offset(berlin, "2026-07-15T12:00:00Z"), 2 * HOUR);
+        // DST starts at 01:00 UTC on the last Sunday of March
+        assert_eq!(offset(berlin, "2026-03-29T00:59:59Z"), HOUR);
+        assert_eq!(offset(berlin, "2026-03-29T01:00:00Z"), 2 * HOUR);
+        assert_eq!(offset(berlin, "2026-10-25T00:59:59Z"), 2 * HOUR);
+        assert_eq!(offset(berlin, "2026-10-25T01:00:00Z"), HOUR);
+
+        let new_york = get("America/New_York").unwrap();
+        assert_eq!(offset(new_york, "2026-01-15T12:00:00Z"), -5 * HOUR);
+        assert_eq!(offset(new_york, "1990-07-15T12:00:00Z"), -4 * HOUR);
+
+        let kolkata = get("Asia/Kolkata").unwrap();
+        assert_eq!(offset(kolkata, "2026-01-15T12:00:00Z"), 5 * HOUR + 30 * 60);
+    }
+
+    #[test]
+    fn test_unknown_zone() {
+        assert_eq!(
+            get("Mars/Olympus_Mons"),
+            Err(UnknownTimeZone("Mars/Olympus_Mons".to_string()))
+        );
+        assert!(get("../../etc/passwd").is_err());
+        assert!(get("").is_err());
+    }
+}
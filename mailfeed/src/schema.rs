@@ -1,14 +1,53 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        created_at -> BigInt,
+        action -> Text,
+        actor_id -> Nullable<Integer>,
+        user_id -> Nullable<Integer>,
+        details -> Nullable<Text>,
+        ip -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    deliveries (id) {
+        id -> Integer,
+        subscription_id -> Integer,
+        sent_at -> BigInt,
+        recipient -> Text,
+        item_count -> Integer,
+        accepted -> Bool,
+        relay_response -> Text,
+    }
+}
+
+diesel::table! {
+    feed_changes (id) {
+        id -> Integer,
+        feed_id -> Integer,
+        changed_at -> BigInt,
+        kind -> Integer,
+        old_value -> Nullable<Text>,
+        new_value -> Nullable<Text>,
+        significant -> Bool,
+    }
+}
+
 diesel::table! {
     feed_items (id) {
         id -> Integer,
         feed_id -> Integer,
         title -> Text,
         link -> Text,
-        pub_date -> Integer,
+        pub_date -> BigInt,
         description -> Nullable<Text>,
         author -> Nullable<Text>,
+        comments_link -> Nullable<Text>,
+        first_seen -> BigInt,
+        image_url -> Nullable<Text>,
     }
 }
 
@@ -18,10 +57,115 @@ diesel::table! {
         url -> Text,
         feed_type -> Integer,
         title -> Text,
-        last_checked -> Integer,
-        last_updated -> Integer,
-        error_time -> Integer,
+        last_checked -> BigInt,
+        last_updated -> BigInt,
+        error_time -> BigInt,
         error_message -> Nullable<Text>,
+        description -> Nullable<Text>,
+        homepage -> Nullable<Text>,
+        link_mode -> Integer,
+        poll_interval -> Integer,
+        body_hash -> Nullable<Text>,
+        error_kind -> Integer,
+        new_items -> Integer,
+        skipped_items -> Integer,
+        etag -> Nullable<Text>,
+        last_modified -> Nullable<Text>,
+        fetch_schedule -> Nullable<Text>,
+        parse_warnings -> Text,
+        credentials -> Nullable<Text>,
+        pruned_through -> BigInt,
+        consecutive_failures -> Integer,
+        broken_at -> BigInt,
+        paused_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    invites (id) {
+        id -> Integer,
+        email -> Text,
+        token_hash -> Text,
+        created_by -> Integer,
+        created_at -> BigInt,
+        expires_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    onboarding (user_id) {
+        user_id -> Integer,
+        added_feed -> Bool,
+        set_delivery -> Bool,
+        sent_test -> Bool,
+        dismissed -> Bool,
+    }
+}
+
+diesel::table! {
+    password_reset_tokens (id) {
+        id -> Integer,
+        user_id -> Integer,
+        token_hash -> Text,
+        created_at -> BigInt,
+        expires_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    personal_access_tokens (id) {
+        id -> Integer,
+        user_id -> Integer,
+        name -> Text,
+        token_hash -> Text,
+        created_at -> BigInt,
+        last_used_at -> BigInt,
+        expires_at -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    read_items (id) {
+        id -> Integer,
+        user_id -> Integer,
+        feed_item_id -> Integer,
+        read_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    recovery_codes (id) {
+        id -> Integer,
+        user_id -> Integer,
+        code_hash -> Text,
+    }
+}
+
+diesel::table! {
+    saved_searches (id) {
+        id -> Integer,
+        user_id -> Integer,
+        name -> Text,
+        query -> Text,
+        notify_by -> Integer,
+        telegram_chat_id -> Nullable<Text>,
+        is_active -> Bool,
+        created_at -> BigInt,
+        last_matched_at -> BigInt,
+        match_count -> Integer,
+        telegram_photos -> Bool,
+    }
+}
+
+diesel::table! {
+    sessions (id) {
+        id -> Integer,
+        user_id -> Integer,
+        created_at -> BigInt,
+        last_used_at -> BigInt,
+        expires_at -> BigInt,
+        user_agent -> Nullable<Text>,
+        ip -> Nullable<Text>,
     }
 }
 
@@ -31,8 +175,31 @@ diesel::table! {
         user_id -> Nullable<Integer>,
         key -> Text,
         value -> Text,
-        created_at -> Integer,
-        updated_at -> Integer,
+        created_at -> BigInt,
+        updated_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    share_links (id) {
+        id -> Integer,
+        subscription_id -> Integer,
+        token_hash -> Text,
+        item_count -> Integer,
+        created_at -> BigInt,
+        last_viewed_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    starred_items (id) {
+        id -> Integer,
+        user_id -> Integer,
+        feed_item_id -> Integer,
+        starred_at -> BigInt,
+        synced_at -> BigInt,
+        bookmark_id -> Nullable<Text>,
+        sync_error -> Nullable<Text>,
     }
 }
 
@@ -41,11 +208,71 @@ diesel::table! {
         id -> Integer,
         user_id -> Integer,
         friendly_name -> Text,
-        frequency -> Integer,
-        last_sent_time -> Integer,
+        frequency -> Text,
+        last_sent_time -> BigInt,
         max_items -> Integer,
         is_active -> Bool,
         feed_id -> Integer,
+        description -> Nullable<Text>,
+        homepage -> Nullable<Text>,
+        send_email -> Nullable<Text>,
+        subject_prefix -> Nullable<Text>,
+        subject_template -> Nullable<Text>,
+        show_stats -> Bool,
+        min_score -> Nullable<Integer>,
+        min_comments -> Nullable<Integer>,
+        feed_failure_notified_at -> BigInt,
+        delivery_window -> Nullable<Text>,
+        include_keywords -> Text,
+        exclude_keywords -> Text,
+        delivery_method -> Integer,
+        max_item_age_days -> Nullable<Integer>,
+        position -> Integer,
+    }
+}
+
+diesel::table! {
+    subscription_tags (subscription_id, tag_id) {
+        subscription_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
+diesel::table! {
+    subscription_templates (id) {
+        id -> Integer,
+        user_id -> Integer,
+        name -> Text,
+        frequency -> Text,
+        max_items -> Integer,
+        send_email -> Nullable<Text>,
+        subject_prefix -> Nullable<Text>,
+        subject_template -> Nullable<Text>,
+        show_stats -> Bool,
+        min_score -> Nullable<Integer>,
+        min_comments -> Nullable<Integer>,
+        delivery_window -> Nullable<Text>,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    tags (id) {
+        id -> Integer,
+        user_id -> Integer,
+        name -> Text,
+        combined_digest -> Bool,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    two_factor (user_id) {
+        user_id -> Integer,
+        secret -> Text,
+        enabled -> Bool,
+        last_used_step -> BigInt,
+        created_at -> BigInt,
     }
 }
 
@@ -55,22 +282,78 @@ diesel::table! {
         login_email -> Text,
         send_email -> Text,
         password -> Text,
-        created_at -> Integer,
+        created_at -> BigInt,
         is_active -> Bool,
         daily_send_time -> Text,
         role -> Text,
-        refresh_token -> Nullable<Text>,
+        item_truncate_length -> Integer,
+        must_change_password -> Bool,
+        from_name -> Nullable<Text>,
+        subject_template -> Nullable<Text>,
+        timezone -> Nullable<Text>,
+        last_login_at -> Nullable<BigInt>,
+        pending_approval -> Bool,
+    }
+}
+
+diesel::table! {
+    webhooks (id) {
+        id -> Integer,
+        url -> Text,
+        secret -> Text,
+        events -> Text,
+        is_active -> Bool,
+        created_at -> BigInt,
+        last_delivery_at -> BigInt,
+        last_error -> Nullable<Text>,
     }
 }
 
+diesel::joinable!(deliveries -> subscriptions (subscription_id));
+diesel::joinable!(feed_changes -> feeds (feed_id));
 diesel::joinable!(feed_items -> feeds (feed_id));
+diesel::joinable!(invites -> users (created_by));
+diesel::joinable!(onboarding -> users (user_id));
+diesel::joinable!(password_reset_tokens -> users (user_id));
+diesel::joinable!(personal_access_tokens -> users (user_id));
+diesel::joinable!(read_items -> feed_items (feed_item_id));
+diesel::joinable!(read_items -> users (user_id));
+diesel::joinable!(recovery_codes -> users (user_id));
+diesel::joinable!(saved_searches -> users (user_id));
+diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(share_links -> subscriptions (subscription_id));
+diesel::joinable!(starred_items -> feed_items (feed_item_id));
+diesel::joinable!(starred_items -> users (user_id));
+diesel::joinable!(subscription_tags -> subscriptions (subscription_id));
+diesel::joinable!(subscription_tags -> tags (tag_id));
+diesel::joinable!(subscription_templates -> users (user_id));
 diesel::joinable!(subscriptions -> feeds (feed_id));
 diesel::joinable!(subscriptions -> users (user_id));
+diesel::joinable!(tags -> users (user_id));
+diesel::joinable!(two_factor -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    audit_log,
+    deliveries,
+    feed_changes,
     feed_items,
     feeds,
+    invites,
+    onboarding,
+    password_reset_tokens,
+    personal_access_tokens,
+    read_items,
+    recovery_codes,
+    saved_searches,
+    sessions,
     settings,
+    share_links,
+    starred_items,
+    subscription_tags,
+    subscription_templates,
     subscriptions,
+    tags,
+    two_factor,
     users,
+    webhooks,
 );
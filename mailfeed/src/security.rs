@@ -0,0 +1,9 @@
+pub mod client_ip;
+pub mod ip_network;
+pub mod item_links;
+pub mod password_policy;
+pub mod redact;
+pub mod secret_box;
+pub mod tokens;
+pub mod totp;
+pub mod validation;
@@ -0,0 +1,141 @@
+use std::{env, net::IpAddr};
+
+use actix_web::{
+    http::header::{HeaderMap, HeaderName},
+    HttpRequest,
+};
+use once_cell::sync::OnceCell;
+
+use super::ip_network::IpNetwork;
+
+static TRUSTED_PROXIES: OnceCell<Vec<IpNetwork>> = OnceCell::new();
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// Comma-separated ranges, skipping any that aren't valid
+pub fn parse_proxies(ranges: &str) -> Vec<IpNetwork> {
+    ranges
+        .split(',')
+        .map(str::trim)
+        .filter(|range| !range.is_empty())
+        .filter_map(|range| match range.parse() {
+            Ok(network) => Some(network),
+            Err(e) => {
+                log::warn!("Ignoring trusted proxy: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// The reverse proxies in MF_TRUSTED_PROXIES, loaded from the environment
+/// on first use. Without any, X-Forwarded-For is ignored.
+pub fn trusted_proxies() -> &'static [IpNetwork] {
+    TRUSTED_PROXIES.get_or_init(|| match env::var("MF_TRUSTED_PROXIES") {
+        Ok(ranges) => {
+            let proxies = parse_proxies(&ranges);
+            log::info!("Trusting X-Forwarded-For from {} proxies", proxies.len());
+            proxies
+        }
+        Err(_) => Vec::new(),
+    })
+}
+
+/// Every X-Forwarded-For line, in order, as one list
+pub fn forwarded_for(headers: &HeaderMap) -> Option<String> {
+    let lines: Vec<&str> = headers
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    Some(lines.join(",")).filter(|value| !value.is_empty())
+}
+
+/// The address a request came from. Behind trusted proxies, that's the
+/// last address in X-Forwarded-For that isn't one of them, since anything
+/// before it could have been made up by the client.
+pub fn resolve(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    proxies: &[IpNetwork],
+) -> Option<IpAddr> {
+    let is_proxy = |addr: IpAddr| proxies.iter().any(|proxy| proxy.contains(addr));
+    let mut client = peer?;
+    let forwarded_for = match forwarded_for {
+        Some(forwarded_for) if is_proxy(client) => forwarded_for,
+        _ => return Some(client),
+    };
+    for hop in forwarded_for.rsplit(',') {
+        client = hop.trim().parse().ok()?;
+        if !is_proxy(client) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+/// The address the request came from, per MF_TRUSTED_PROXIES
+pub fn real_ip(req: &HttpRequest) -> Option<IpAddr> {
+    resolve(
+        req.peer_addr().map(|addr| addr.ip()),
+        forwarded_for(req.headers()).as_deref(),
+        trusted_proxies(),
+    )
+}
+
+/// For logging, where an unknown address is still worth a line
+pub fn describe(ip: Option<IpAddr>) -> String {
+    ip.map_or_else(|| "unknown address".to_string(), |ip| ip.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_forwarded_for() {
+        // proxies may each add their own header line
+        let req = actix_web::test::TestRequest::default()
+            .append_header(("X-Forwarded-For", "10.0.0.1"))
+            .append_header(("X-Forwarded-For", "198.51.100.7"))
+            .to_http_request();
+        assert_eq!(
+            forwarded_for(req.headers()).as_deref(),
+            Some("10.0.0.1,198.51.100.7")
+        );
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        assert_eq!(forwarded_for(req.headers()), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        // the header is ignored without trusted proxies
+        assert_eq!(
+            resolve(ip("203.0.113.9"), Some("10.0.0.1"), &[]),
+            ip("203.0.113.9")
+        );
+
+        let proxies = parse_proxies("127.0.0.1, 172.16.0.0/12,, not-an-ip");
+        assert_eq!(proxies.len(), 2);
+        // the first hop is the client's claim, which is skipped
+        assert_eq!(
+            resolve(
+                ip("127.0.0.1"),
+                Some("10.0.0.1, 198.51.100.7, 172.16.0.3"),
+                &proxies
+            ),
+            ip("198.51.100.7")
+        );
+        // untrusted peers' headers aren't believed
+        assert_eq!(
+            resolve(ip("198.51.100.7"), Some("10.0.0.1"), &proxies),
+            ip("198.51.100.7")
+        );
+        assert_eq!(resolve(ip("127.0.0.1"), Some("junk"), &proxies), None);
+        // a request straight from the proxy's host
+        assert_eq!(resolve(ip("127.0.0.1"), None, &proxies), ip("127.0.0.1"));
+    }
+}
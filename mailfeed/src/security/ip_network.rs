@@ -0,0 +1,116 @@
+use std::{fmt, net::IpAddr, str::FromStr};
+
+/// An address range in CIDR notation, like `10.0.0.0/8` or `2001:db8::/32`.
+/// A bare address is a range of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum IpNetworkError {
+    #[error("'{0}' isn't an IP address")]
+    Address(String),
+    #[error("'{0}' isn't a valid prefix length")]
+    Prefix(String),
+}
+
+/// IPv4 clients of a dual-stack listener show up as IPv4-mapped IPv6
+/// addresses, which should match IPv4 ranges
+fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+        IpAddr::V4(_) => addr,
+    }
+}
+
+impl IpNetwork {
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, canonical(addr)) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = IpNetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .map(canonical)
+            .map_err(|_| IpNetworkError::Address(addr.to_string()))?;
+        let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| IpNetworkError::Prefix(prefix.to_string()))?,
+            None => max_prefix,
+        };
+        Ok(IpNetwork { addr, prefix })
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_contains() {
+        let net: IpNetwork = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains(ip("10.1.255.3")));
+        assert!(!net.contains(ip("10.2.0.1")));
+        assert!(net.contains(ip("::ffff:10.1.0.9")));
+
+        let one: IpNetwork = "192.168.1.5".parse().unwrap();
+        assert_eq!(one.to_string(), "192.168.1.5/32");
+        assert!(one.contains(ip("192.168.1.5")));
+        assert!(!one.contains(ip("192.168.1.6")));
+
+        let any: IpNetwork = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(ip("203.0.113.7")));
+        assert!(!any.contains(ip("2001:db8::1")));
+
+        let v6: IpNetwork = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains(ip("2001:db8:1::1")));
+        assert!(!v6.contains(ip("2001:db9::1")));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "10.0.0.0/33".parse::<IpNetwork>(),
+            Err(IpNetworkError::Prefix("33".to_string()))
+        );
+        assert_eq!(
+            "example.com".parse::<IpNetwork>(),
+            Err(IpNetworkError::Address("example.com".to_string()))
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use base64::{engine::general_purpose, Engine};
+use ring::hmac;
+use serde::Deserialize;
+
+use crate::{global::JWT_SECRET, models::ids::UserId};
+
+/// What a link under an item in a digest does to it
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemAction {
+    Read,
+    Star,
+}
+
+impl ItemAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemAction::Read => "read",
+            ItemAction::Star => "star",
+        }
+    }
+}
+
+/// Signs and checks the links in digests that mark an item read or star it
+/// without logging in. A link only works for the user, item and action it
+/// was made for, and stops working if the instance's secret changes.
+pub struct ItemLinks {
+    key: hmac::Key,
+}
+
+impl ItemLinks {
+    pub fn new(secret: &str) -> Self {
+        ItemLinks {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+        }
+    }
+
+    /// Signed with the instance's JWT secret, None before it's loaded
+    pub fn global() -> Option<ItemLinks> {
+        JWT_SECRET.get().map(|secret| ItemLinks::new(secret))
+    }
+
+    // the colons keep these apart from JWTs, which are signed with the same
+    // secret but never contain one
+    fn message(user_id: UserId, item_id: i32, action: ItemAction) -> String {
+        format!("item-link:{}:{}:{}", user_id, item_id, action.as_str())
+    }
+
+    pub fn sign(&self, user_id: UserId, item_id: i32, action: ItemAction) -> String {
+        let tag = hmac::sign(
+            &self.key,
+            Self::message(user_id, item_id, action).as_bytes(),
+        );
+        general_purpose::URL_SAFE_NO_PAD.encode(tag.as_ref())
+    }
+
+    pub fn verify(
+        &self,
+        user_id: UserId,
+        item_id: i32,
+        action: ItemAction,
+        signature: &str,
+    ) -> bool {
+        let Ok(tag) = general_purpose::URL_SAFE_NO_PAD.decode(signature) else {
+            return false;
+        };
+        let message = Self::message(user_id, item_id, action);
+        hmac::verify(&self.key, message.as_bytes(), &tag).is_ok()
+    }
+
+    /// The link to put in an email, with `base` the instance's public URL
+    pub fn url(&self, base: &str, user_id: UserId, item_id: i32, action: ItemAction) -> String {
+        format!(
+            "{}/email/items/{}/{}?user={}&sig={}",
+            base.trim_end_matches('/'),
+            item_id,
+            action.as_str(),
+            user_id,
+            self.sign(user_id, item_id, action)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let links = ItemLinks::new("test secret");
+        let sig = links.sign(UserId(1), 7, ItemAction::Read);
+        assert!(links.verify(UserId(1), 7, ItemAction::Read, &sig));
+        assert!(!links.verify(UserId(2), 7, ItemAction::Read, &sig));
+        assert!(!links.verify(UserId(1), 8, ItemAction::Read, &sig));
+        assert!(!links.verify(UserId(1), 7, ItemAction::Star, &sig));
+        assert!(!links.verify(UserId(1), 7, ItemAction::Read, "not base64!"));
+        assert!(!ItemLinks::new("other secret").verify(UserId(1), 7, ItemAction::Read, &sig));
+
+        assert_eq!(
+            links.url("https://mail.example/", UserId(1), 7, ItemAction::Read),
+            format!("https://mail.example/email/items/7/read?user=1&sig={}", sig)
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use std::{collections::HashSet, env, fs};
+
+use once_cell::sync::OnceCell;
+use thiserror::Error;
+
+static POLICY: OnceCell<PasswordPolicy> = OnceCell::new();
+
+const DEFAULT_MIN_LENGTH: usize = 8;
+
+/// A small sample of the most common passwords from public breach lists.
+/// Instances that want more can point MF_PASSWORD_DENYLIST_FILE at a
+/// larger list.
+const COMMON_PASSWORDS: &[&str] = &[
+    "000000",
+    "111111",
+    "123123",
+    "123456",
+    "1234567",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "1q2w3e4r",
+    "654321",
+    "666666",
+    "987654321",
+    "abc123",
+    "admin",
+    "admin123",
+    "baseball",
+    "dragon",
+    "football",
+    "iloveyou",
+    "letmein",
+    "login",
+    "master",
+    "monkey",
+    "passw0rd",
+    "password",
+    "password1",
+    "password123",
+    "princess",
+    "qwerty",
+    "qwerty123",
+    "qwertyuiop",
+    "shadow",
+    "starwars",
+    "sunshine",
+    "superman",
+    "trustno1",
+    "welcome",
+    "welcome1",
+];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("Password must be at least {0} characters long")]
+    TooShort(usize),
+    #[error("Password must contain at least one {0}")]
+    MissingCharClass(&'static str),
+    #[error("Password is too common, please choose another")]
+    TooCommon,
+}
+
+#[derive(Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// lowercased passwords that are always rejected
+    pub denylist: HashSet<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: DEFAULT_MIN_LENGTH,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            denylist: COMMON_PASSWORDS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// The instance-wide policy, loaded from the environment on first use
+    pub fn global() -> &'static PasswordPolicy {
+        POLICY.get_or_init(PasswordPolicy::from_env)
+    }
+
+    pub fn from_env() -> Self {
+        let mut policy = PasswordPolicy::default();
+
+        if let Ok(min_length) = env::var("MF_PASSWORD_MIN_LENGTH") {
+            match min_length.parse::<usize>() {
+                Ok(min_length) => policy.min_length = min_length.max(1),
+                Err(_) => log::warn!(
+                    "Invalid MF_PASSWORD_MIN_LENGTH '{}', using default of {}",
+                    min_length,
+                    DEFAULT_MIN_LENGTH
+                ),
+            }
+        }
+        policy.require_lowercase = env_flag("MF_PASSWORD_REQUIRE_LOWERCASE", false);
+        policy.require_uppercase = env_flag("MF_PASSWORD_REQUIRE_UPPERCASE", false);
+        policy.require_digit = env_flag("MF_PASSWORD_REQUIRE_DIGIT", false);
+        policy.require_symbol = env_flag("MF_PASSWORD_REQUIRE_SYMBOL", false);
+
+        if !env_flag("MF_PASSWORD_DENY_COMMON", true) {
+            policy.denylist.clear();
+        }
+        if let Ok(path) = env::var("MF_PASSWORD_DENYLIST_FILE") {
+            match fs::read_to_string(&path) {
+                Ok(contents) => {
+                    let before = policy.denylist.len();
+                    policy.denylist.extend(
+                        contents
+                            .lines()
+                            .map(|line| line.trim().to_lowercase())
+                            .filter(|line| !line.is_empty()),
+                    );
+                    log::info!(
+                        "Loaded {} denied passwords from {}",
+                        policy.denylist.len() - before,
+                        path
+                    );
+                }
+                Err(e) => log::warn!("Failed to read password denylist {}: {:?}", path, e),
+            }
+        }
+
+        log::info!(
+            "Password policy: min_length={}, lowercase={}, uppercase={}, digit={}, symbol={}, denylist={}",
+            policy.min_length,
+            policy.require_lowercase,
+            policy.require_uppercase,
+            policy.require_digit,
+            policy.require_symbol,
+            policy.denylist.len()
+        );
+        policy
+    }
+
+    pub fn validate(&self, password: &str) -> Result<(), Error> {
+        if password.chars().count() < self.min_length {
+            return Err(Error::TooShort(self.min_length));
+        }
+
+        let has = |matches: fn(&char) -> bool| password.chars().any(|c| matches(&c));
+        let char_classes = [
+            (
+                self.require_lowercase,
+                "lowercase letter",
+                has(char::is_ascii_lowercase),
+            ),
+            (
+                self.require_uppercase,
+                "uppercase letter",
+                has(char::is_ascii_uppercase),
+            ),
+            (self.require_digit, "digit", has(char::is_ascii_digit)),
+            (self.require_symbol, "symbol", has(is_symbol)),
+        ];
+        for (required, name, present) in char_classes {
+            if required && !present {
+                return Err(Error::MissingCharClass(name));
+            }
+        }
+
+        if self.denylist.contains(&password.to_lowercase()) {
+            return Err(Error::TooCommon);
+        }
+
+        Ok(())
+    }
+}
+
+fn is_symbol(c: &char) -> bool {
+    !c.is_alphanumeric() && !c.is_whitespace()
+}
+
+fn env_flag(name: &str, default: bool) -> bool {
+    match env::var(name) {
+        Ok(value) => matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on"),
+        Err(_) => default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = PasswordPolicy::default();
+        assert_eq!(policy.validate(""), Err(Error::TooShort(8)));
+        assert_eq!(policy.validate("short"), Err(Error::TooShort(8)));
+        assert_eq!(policy.validate("Password"), Err(Error::TooCommon));
+        assert_eq!(policy.validate("correct horse"), Ok(()));
+    }
+
+    #[test]
+    fn test_char_classes() {
+        let policy = PasswordPolicy {
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.validate("CORRECT HORSE"),
+            Err(Error::MissingCharClass("lowercase letter"))
+        );
+        assert_eq!(
+            policy.validate("correct horse"),
+            Err(Error::MissingCharClass("uppercase letter"))
+        );
+        assert_eq!(
+            policy.validate("Correct horse"),
+            Err(Error::MissingCharClass("digit"))
+        );
+        assert_eq!(
+            policy.validate("Correct horse 1"),
+            Err(Error::MissingCharClass("symbol"))
+        );
+        assert_eq!(policy.validate("Correct-horse-1"), Ok(()));
+    }
+
+    #[test]
+    fn test_error_messages() {
+        assert_eq!(
+            Error::TooShort(12).to_string(),
+            "Password must be at least 12 characters long"
+        );
+        assert_eq!(
+            Error::MissingCharClass("digit").to_string(),
+            "Password must contain at least one digit"
+        );
+    }
+}
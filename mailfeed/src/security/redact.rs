@@ -0,0 +1,129 @@
+use std::io::Write;
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use super::tokens::hash_token;
+
+static EMAIL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b([a-z0-9._%+-]+)@([a-z0-9.-]+\.[a-z]{2,})\b").expect("Valid email regex")
+});
+static BEARER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)\bbearer\s+[a-z0-9._~+/=-]+").expect("Valid bearer regex"));
+/// JSON web tokens, like access and refresh tokens
+static JWT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\beyJ[a-zA-Z0-9_-]*\.[a-zA-Z0-9_-]+\.[a-zA-Z0-9_-]*").expect("Valid JWT regex")
+});
+/// `password=...`, `"token": "..."`, `?access_token=...` and the like
+static SECRET_FIELD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?i)\b([a-z_]*(?:password|secret|token|session_id|api_key)"?\s*[:=]\s*"?)([^\s"&,}]+)"#,
+    )
+    .expect("Valid secret field regex")
+});
+
+/// An email address with all but the first letter of its local part hidden,
+/// e.g. `j***@example.com`, so logs can still tell users apart
+pub fn email(address: &str) -> String {
+    match address.split_once('@') {
+        Some((local, domain)) => {
+            let first: String = local.chars().take(1).collect();
+            format!("{}***@{}", first, domain)
+        }
+        None => "***".to_string(),
+    }
+}
+
+/// A short hash of a secret such as a session ID, the same for the same
+/// value so log lines can be matched up without revealing it
+pub fn secret(value: &str) -> String {
+    format!("#{}", &hash_token(value)[..8])
+}
+
+/// The message with email addresses masked and anything that looks like a
+/// token, password or other secret removed. Every log line goes through
+/// this, so secrets passed to a log macro by mistake don't end up on disk.
+pub fn scrub(message: &str) -> String {
+    let message = BEARER.replace_all(message, "Bearer [redacted]");
+    let message = JWT.replace_all(&message, "[redacted]");
+    let message = SECRET_FIELD.replace_all(&message, "${1}[redacted]");
+    EMAIL
+        .replace_all(&message, |caps: &Captures| {
+            email(&format!("{}@{}", &caps[1], &caps[2]))
+        })
+        .into_owned()
+}
+
+/// Set up logging like `env_logger`'s defaults, with each message scrubbed
+pub fn init_logger() {
+    env_logger::Builder::from_env(env_logger::Env::new().default_filter_or("info"))
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {} {}] {}",
+                buf.timestamp(),
+                buf.default_styled_level(record.level()),
+                record.target(),
+                scrub(&record.args().to_string())
+            )
+        })
+        .init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email() {
+        assert_eq!(email("jane.doe@example.com"), "j***@example.com");
+        assert_eq!(email("not an address"), "***");
+    }
+
+    #[test]
+    fn test_secret() {
+        let hashed = secret("session-abc123");
+        assert_eq!(hashed, secret("session-abc123"));
+        assert_ne!(hashed, secret("session-abc124"));
+        assert!(!hashed.contains("abc123"));
+    }
+
+    #[test]
+    fn test_scrub_masks_emails() {
+        let scrubbed = scrub("Email sent to jane.doe@example.com for sub_id=3");
+        assert_eq!(scrubbed, "Email sent to j***@example.com for sub_id=3");
+    }
+
+    #[test]
+    fn test_scrub_removes_secrets() {
+        let jwt = "eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOjF9.c2lnbmF0dXJl";
+        let lines = [
+            format!("Authorization: Bearer {}", jwt),
+            format!("Error checking access token {}", jwt),
+            "EmailServerCfg { username: \"mailer\", password: \"hunter2\" }".to_string(),
+            "POST https://example.com/hook?token=s3cr3t&x=1".to_string(),
+            "refresh_token=abc.def session_id: 0f9e8d".to_string(),
+        ];
+        for line in &lines {
+            let scrubbed = scrub(line);
+            for secret in [jwt, "hunter2", "s3cr3t", "abc.def", "0f9e8d"] {
+                assert!(
+                    !scrubbed.contains(secret),
+                    "{} leaked in {}",
+                    secret,
+                    scrubbed
+                );
+            }
+        }
+        assert_eq!(
+            scrub("POST https://example.com/hook?token=s3cr3t&x=1"),
+            "POST https://example.com/hook?token=[redacted]&x=1"
+        );
+    }
+
+    #[test]
+    fn test_scrub_leaves_ordinary_messages() {
+        let message = "Sent 3 items of sub_id=5 to Discord";
+        assert_eq!(scrub(message), message);
+    }
+}
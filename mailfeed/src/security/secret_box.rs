@@ -0,0 +1,90 @@
+use std::env;
+
+use base64::{engine::general_purpose, Engine};
+use once_cell::sync::OnceCell;
+use rand::{rngs::OsRng, Rng};
+use ring::{aead, digest};
+
+static SECRET_BOX: OnceCell<Option<SecretBox>> = OnceCell::new();
+
+/// Encrypts secrets the server has to read back later, like TOTP keys,
+/// with a key from `MF_SECRET_KEY`. The key isn't stored, so a copy of the
+/// database alone isn't enough to recover them.
+pub struct SecretBox {
+    key: aead::LessSafeKey,
+}
+
+impl SecretBox {
+    pub fn new(secret_key: &str) -> Self {
+        let key_bytes = digest::digest(&digest::SHA256, secret_key.as_bytes());
+        let key = aead::UnboundKey::new(&aead::CHACHA20_POLY1305, key_bytes.as_ref())
+            .expect("SHA-256 output is a valid ChaCha20 key");
+        SecretBox {
+            key: aead::LessSafeKey::new(key),
+        }
+    }
+
+    /// The instance's box, None if `MF_SECRET_KEY` isn't set
+    pub fn global() -> Option<&'static SecretBox> {
+        SECRET_BOX
+            .get_or_init(|| {
+                env::var("MF_SECRET_KEY")
+                    .ok()
+                    .filter(|key| !key.is_empty())
+                    .map(|key| SecretBox::new(&key))
+            })
+            .as_ref()
+    }
+
+    /// Base64 of a random nonce followed by the sealed plaintext
+    pub fn encrypt(&self, plaintext: &[u8]) -> String {
+        let mut nonce = [0u8; aead::NONCE_LEN];
+        OsRng.fill(&mut nonce);
+        let mut sealed = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                aead::Nonce::assume_unique_for_key(nonce),
+                aead::Aad::empty(),
+                &mut sealed,
+            )
+            .expect("plaintext fits in one message");
+        let mut out = nonce.to_vec();
+        out.extend(sealed);
+        general_purpose::STANDARD.encode(out)
+    }
+
+    /// None if it was sealed with another key or has been tampered with
+    pub fn decrypt(&self, encoded: &str) -> Option<Vec<u8>> {
+        let mut nonce = general_purpose::STANDARD.decode(encoded).ok()?;
+        if nonce.len() < aead::NONCE_LEN {
+            return None;
+        }
+        let mut sealed = nonce.split_off(aead::NONCE_LEN);
+        let nonce = aead::Nonce::try_assume_unique_for_key(&nonce).ok()?;
+        let plaintext = self
+            .key
+            .open_in_place(nonce, aead::Aad::empty(), &mut sealed)
+            .ok()?;
+        Some(plaintext.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let secret_box = SecretBox::new("test key");
+        let encrypted = secret_box.encrypt(b"totp secret");
+        assert_ne!(encrypted, secret_box.encrypt(b"totp secret"));
+        assert_eq!(
+            secret_box.decrypt(&encrypted),
+            Some(b"totp secret".to_vec())
+        );
+
+        assert_eq!(SecretBox::new("other key").decrypt(&encrypted), None);
+        assert_eq!(secret_box.decrypt("not base64!"), None);
+        assert_eq!(secret_box.decrypt("AAAA"), None);
+    }
+}
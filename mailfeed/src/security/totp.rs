@@ -0,0 +1,129 @@
+//! Time-based one-time passwords (RFC 6238) with the defaults authenticator
+//! apps assume: HMAC-SHA1, six digits, thirty second steps.
+
+use rand::{rngs::OsRng, Rng};
+use ring::hmac;
+
+const STEP_SECONDS: i64 = 30;
+const DIGITS: usize = 6;
+/// Steps either side of the current one that are accepted, for clock drift
+const WINDOW: i64 = 1;
+const SECRET_LENGTH: usize = 20;
+const ISSUER: &str = "MailFeed";
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LENGTH];
+    OsRng.fill(&mut secret[..]);
+    secret
+}
+
+/// Unpadded base32, which is how authenticator apps take secrets
+pub fn base32(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    for chunk in bytes.chunks(5) {
+        let mut buf = [0u8; 5];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = buf.iter().fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+        let chars = (chunk.len() * 8).div_ceil(5);
+        for i in 0..chars {
+            let index = (bits >> (35 - i * 5)) & 0x1f;
+            out.push(BASE32_ALPHABET[index as usize] as char);
+        }
+    }
+    out
+}
+
+/// The `otpauth://` URI authenticator apps scan as a QR code
+pub fn provisioning_uri(secret: &[u8], account: &str) -> String {
+    let label: String =
+        url::form_urlencoded::byte_serialize(format!("{}:{}", ISSUER, account).as_bytes())
+            .collect();
+    format!(
+        "otpauth://totp/{}?secret={}&issuer={}",
+        label,
+        base32(secret),
+        ISSUER
+    )
+}
+
+fn code_at(secret: &[u8], step: i64) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let tag = hmac::sign(&key, &step.to_be_bytes());
+    let hash = tag.as_ref();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        value % 10u32.pow(DIGITS as u32),
+        width = DIGITS
+    )
+}
+
+/// The time step `code` belongs to, if it's valid at `now` and from a later
+/// step than `last_step`, so each code only works once
+pub fn verify(secret: &[u8], code: &str, now: i64, last_step: i64) -> Option<i64> {
+    if !is_code(code) {
+        return None;
+    }
+    let code = code.trim();
+    let current = now / STEP_SECONDS;
+    (current - WINDOW..=current + WINDOW)
+        .filter(|step| *step > last_step)
+        .find(|step| code_at(secret, *step) == code)
+}
+
+/// Whether this looks like a TOTP code rather than a recovery code
+pub fn is_code(code: &str) -> bool {
+    let code = code.trim();
+    code.len() == DIGITS && code.bytes().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, truncated to six digits
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_codes() {
+        assert_eq!(code_at(SECRET, 59 / STEP_SECONDS), "287082");
+        assert_eq!(code_at(SECRET, 1111111109 / STEP_SECONDS), "081804");
+        assert_eq!(code_at(SECRET, 1234567890 / STEP_SECONDS), "005924");
+    }
+
+    #[test]
+    fn test_verify() {
+        let now = 1234567890;
+        let step = now / STEP_SECONDS;
+        assert_eq!(verify(SECRET, "005924", now, 0), Some(step));
+        // a step late, for clock drift
+        assert_eq!(verify(SECRET, "005924", now + STEP_SECONDS, 0), Some(step));
+        assert_eq!(verify(SECRET, "005924", now + 3 * STEP_SECONDS, 0), None);
+        // already used
+        assert_eq!(verify(SECRET, "005924", now, step), None);
+        assert_eq!(verify(SECRET, "123456", now, 0), None);
+        assert_eq!(verify(SECRET, "abcdef", now, 0), None);
+    }
+
+    #[test]
+    fn test_base32() {
+        assert_eq!(base32(SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32(b"f"), "MY");
+        assert_eq!(base32(b"foobar"), "MZXW6YTBOI");
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        assert_eq!(
+            provisioning_uri(SECRET, "a@b.com"),
+            "otpauth://totp/MailFeed%3Aa%40b.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=MailFeed"
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
+use chrono::NaiveTime;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::models::{
+    delivery_window::DeliveryWindow,
+    keyword_filter::Keywords,
+    max_item_age::MAX_ITEM_AGE_DAYS_LIMIT,
+    subscription::{Frequency, MAX_INTERVAL_HOURS},
+};
+use crate::tasks::email_sender::subject;
+
+/// A problem with one field of a request
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+/// Everything wrong with a request, returned as a 400 with one entry per
+/// problem so clients can show each next to its field
+#[derive(Error, Debug, Default, Serialize)]
+#[error("Invalid request")]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Serialize)]
+struct ValidationResponse<'a> {
+    message: String,
+    errors: &'a [FieldError],
+}
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field,
+            message: message.into(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn email(&mut self, field: &'static str, value: &str) {
+        if value.parse::<lettre::Address>().is_err() {
+            self.add(field, "Invalid email address");
+        }
+    }
+
+    pub fn url(&mut self, field: &'static str, value: &str) {
+        if url::Url::parse(value).is_err() {
+            self.add(field, "Invalid URL");
+        }
+    }
+
+    pub fn non_negative(&mut self, field: &'static str, value: Option<i32>) {
+        if matches!(value, Some(n) if n < 0) {
+            self.add(field, "Must be zero or positive");
+        }
+    }
+
+    /// A number of days, up to about ten years
+    pub fn max_item_age(&mut self, field: &'static str, days: Option<i32>) {
+        if matches!(days, Some(days) if !(0..=MAX_ITEM_AGE_DAYS_LIMIT).contains(&days)) {
+            self.add(
+                field,
+                format!("Must be between 0 and {}", MAX_ITEM_AGE_DAYS_LIMIT),
+            );
+        }
+    }
+
+    /// `HH:MM`, optionally followed by a UTC offset like `+05:30`
+    pub fn daily_send_time(&mut self, field: &'static str, send_time: &str) {
+        let is_time = |time: Option<&str>| {
+            time.is_some_and(|time| {
+                time.len() == 5 && NaiveTime::parse_from_str(time, "%H:%M").is_ok()
+            })
+        };
+        let valid_offset = match send_time.get(5..) {
+            Some("") => true,
+            Some(offset) => matches!(offset.get(..1), Some("+" | "-")) && is_time(offset.get(1..)),
+            None => false,
+        };
+        let valid = send_time.is_empty() || (is_time(send_time.get(..5)) && valid_offset);
+        if !valid {
+            self.add(
+                field,
+                "Must be a time like 07:00, optionally with a UTC offset like 07:00+05:30",
+            );
+        }
+    }
+
+    pub fn delivery_window(&mut self, field: &'static str, window: &str) {
+        if DeliveryWindow::parse(window).is_none() {
+            self.add(field, "Must be two different times like 07:00-09:00");
+        }
+    }
+
+    pub fn frequency(&mut self, field: &'static str, frequency: &Frequency) {
+        if let Frequency::EveryHours(hours) = frequency {
+            if !(1..=MAX_INTERVAL_HOURS).contains(hours) {
+                self.add(
+                    field,
+                    format!("Must be every 1 to {} hours", MAX_INTERVAL_HOURS),
+                );
+            }
+        }
+        if let Some(Err(e)) = frequency.cron_schedule() {
+            self.add(field, e.to_string());
+        }
+    }
+
+    pub fn keywords(&mut self, field: &'static str, keywords: &Keywords) {
+        if let Err(e) = keywords.validate() {
+            self.add(field, e.to_string());
+        }
+    }
+
+    pub fn subject_template(&mut self, field: &'static str, template: &str) {
+        if let Err(e) = subject::validate(template) {
+            self.add(field, e.to_string());
+        }
+    }
+}
+
+impl ResponseError for ValidationErrors {
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::BadRequest().json(ValidationResponse {
+            message: self.to_string(),
+            errors: &self.errors,
+        })
+    }
+
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+/// Implemented by request bodies to check their fields before a handler
+/// acts on them
+pub trait Validate {
+    /// Add any problems with the request to `errors`
+    fn check(&self, errors: &mut ValidationErrors);
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        self.check(&mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::MessageBody;
+
+    struct Form {
+        email: &'static str,
+        count: Option<i32>,
+    }
+
+    impl Validate for Form {
+        fn check(&self, errors: &mut ValidationErrors) {
+            errors.email("email", self.email);
+            errors.non_negative("count", self.count);
+        }
+    }
+
+    #[test]
+    fn test_collects_every_problem() {
+        let form = Form {
+            email: "test@example.com",
+            count: Some(0),
+        };
+        assert!(form.validate().is_ok());
+
+        let form = Form {
+            email: "not an email",
+            count: Some(-1),
+        };
+        let errors = form.validate().unwrap_err();
+        let fields: Vec<_> = errors.errors.iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["email", "count"]);
+
+        let response = errors.error_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.into_body().try_into_bytes().unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["message"], "Invalid request");
+        assert_eq!(body["errors"][1]["field"], "count");
+        assert_eq!(body["errors"][1]["message"], "Must be zero or positive");
+    }
+
+    #[test]
+    fn test_daily_send_time() {
+        for valid in ["", "07:00", "07:00+05:30", "23:59-07:00"] {
+            let mut errors = ValidationErrors::default();
+            errors.daily_send_time("daily_send_time", valid);
+            assert!(errors.is_empty(), "{}", valid);
+        }
+        for invalid in [
+            "7:00",
+            "25:00",
+            "07:00+5",
+            "07:00 UTC",
+            "07:00+Europe/Berlin",
+        ] {
+            let mut errors = ValidationErrors::default();
+            errors.daily_send_time("daily_send_time", invalid);
+            assert!(!errors.is_empty(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_frequency() {
+        for valid in [
+            Frequency::EveryHours(1),
+            Frequency::EveryHours(MAX_INTERVAL_HOURS),
+            Frequency::Cron("0 9 * * mon".to_string()),
+        ] {
+            let mut errors = ValidationErrors::default();
+            errors.frequency("frequency", &valid);
+            assert!(errors.is_empty(), "{:?}", valid);
+        }
+        for invalid in [
+            Frequency::EveryHours(0),
+            Frequency::EveryHours(MAX_INTERVAL_HOURS + 1),
+            Frequency::Cron("every monday".to_string()),
+        ] {
+            let mut errors = ValidationErrors::default();
+            errors.frequency("frequency", &invalid);
+            assert!(!errors.is_empty(), "{:?}", invalid);
+        }
+    }
+}
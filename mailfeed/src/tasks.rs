@@ -1,4 +1,19 @@
-mod types;
+pub(crate) mod html_to_text;
+pub(crate) mod item_media;
+mod retry;
+pub(crate) mod types;
 
+pub mod bookmark_sync;
+pub mod db_maintenance;
+pub mod discord;
+pub mod dispatch;
 pub mod email_sender;
 pub mod feed_monitor;
+pub mod jobs;
+pub mod matrix_sender;
+pub mod mqtt;
+pub mod push_sender;
+pub mod session_cleanup;
+pub mod telegram;
+pub mod webhook_sender;
+pub mod webhooks;
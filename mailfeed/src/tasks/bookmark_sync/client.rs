@@ -0,0 +1,242 @@
+use reqwest::{Client, RequestBuilder, Response};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::models::{
+    bookmark_settings::{BookmarkService, BookmarkSettings},
+    feed_item::FeedItem,
+};
+
+/// Tag added to every bookmark pushed, so they're easy to find
+const TAG: &str = "mailfeed";
+
+#[derive(thiserror::Error, Debug)]
+pub enum SyncError {
+    #[error("No API token or password set")]
+    NoToken,
+    #[error("HTTP {0}")]
+    Status(u16),
+    #[error("{0}")]
+    Request(String),
+    #[error("Unexpected response: {0}")]
+    Response(String),
+}
+
+impl SyncError {
+    /// Whether trying again later might work
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SyncError::Status(status) => *status == 429 || *status >= 500,
+            SyncError::Request(_) => true,
+            SyncError::NoToken | SyncError::Response(_) => false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Bookmark {
+    id: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct LinkdingCheck {
+    bookmark: Option<Bookmark>,
+}
+
+#[derive(Deserialize)]
+struct ShioriLogin {
+    message: ShioriSession,
+}
+
+#[derive(Deserialize)]
+struct ShioriSession {
+    token: String,
+    session: Option<String>,
+}
+
+enum Auth {
+    /// Linkding's API token
+    Token(String),
+    /// a Shiori login
+    Session {
+        token: String,
+        session: Option<String>,
+    },
+}
+
+/// Pushes items to one user's bookmark manager
+pub struct BookmarkClient {
+    http: Client,
+    service: BookmarkService,
+    url: String,
+    auth: Auth,
+}
+
+impl BookmarkClient {
+    /// Ready to push with the user's settings, logging in first for Shiori
+    pub async fn connect(
+        http: &Client,
+        service: BookmarkService,
+        settings: &BookmarkSettings,
+    ) -> Result<Self, SyncError> {
+        let token = settings.token.clone().ok_or(SyncError::NoToken)?;
+        let auth = match service {
+            BookmarkService::Linkding => Auth::Token(token),
+            BookmarkService::Shiori => {
+                let request = with_json(
+                    http.post(format!("{}/api/v1/auth/login", settings.url)),
+                    json!({
+                        "username": settings.username,
+                        "password": token,
+                        "remember_me": false,
+                    }),
+                );
+                let login: ShioriLogin = parse(send(request).await?).await?;
+                Auth::Session {
+                    token: login.message.token,
+                    session: login.message.session,
+                }
+            }
+        };
+        Ok(BookmarkClient {
+            http: http.clone(),
+            service,
+            url: settings.url.clone(),
+            auth,
+        })
+    }
+
+    /// Bookmark the item, returning the bookmark's id. For Linkding, an
+    /// existing bookmark of the link is returned instead of adding another.
+    pub async fn push(&self, item: &FeedItem) -> Result<String, SyncError> {
+        match self.service {
+            BookmarkService::Linkding => {
+                if let Some(existing) = self.linkding_existing(&item.link).await? {
+                    log::debug!("{} is already bookmarked as {}", item.link, existing);
+                    return Ok(existing);
+                }
+                let request = self.request(self.http.post(format!("{}/api/bookmarks/", self.url)));
+                let created: Bookmark = parse(
+                    send(with_json(
+                        request,
+                        json!({
+                            "url": item.link,
+                            "title": item.title,
+                            "description": item.description.as_deref().unwrap_or_default(),
+                            "tag_names": [TAG],
+                        }),
+                    ))
+                    .await?,
+                )
+                .await?;
+                Ok(bookmark_id(&created))
+            }
+            BookmarkService::Shiori => {
+                let request = self.request(self.http.post(format!("{}/api/bookmarks", self.url)));
+                let created: Bookmark = parse(
+                    send(with_json(
+                        request,
+                        json!({
+                            "url": item.link,
+                            "title": item.title,
+                            "excerpt": item.description.as_deref().unwrap_or_default(),
+                            "tags": [{"name": TAG}],
+                            "createArchive": false,
+                        }),
+                    ))
+                    .await?,
+                )
+                .await?;
+                Ok(bookmark_id(&created))
+            }
+        }
+    }
+
+    async fn linkding_existing(&self, link: &str) -> Result<Option<String>, SyncError> {
+        let request = self
+            .request(self.http.get(format!("{}/api/bookmarks/check/", self.url)))
+            .query(&[("url", link)]);
+        let check: LinkdingCheck = parse(send(request).await?).await?;
+        Ok(check.bookmark.as_ref().map(bookmark_id))
+    }
+
+    fn request(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.auth {
+            Auth::Token(token) => request.header("Authorization", format!("Token {}", token)),
+            Auth::Session { token, session } => {
+                let request = request.bearer_auth(token);
+                match session {
+                    Some(session) => request.header("X-Session-Id", session),
+                    None => request,
+                }
+            }
+        }
+    }
+}
+
+async fn send(request: RequestBuilder) -> Result<Response, SyncError> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| SyncError::Request(e.to_string()))?;
+    match response.status() {
+        status if status.is_success() => Ok(response),
+        status => Err(SyncError::Status(status.as_u16())),
+    }
+}
+
+fn with_json(request: RequestBuilder, body: serde_json::Value) -> RequestBuilder {
+    request
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+}
+
+async fn parse<T: serde::de::DeserializeOwned>(response: Response) -> Result<T, SyncError> {
+    let body = response
+        .text()
+        .await
+        .map_err(|e| SyncError::Request(e.to_string()))?;
+    serde_json::from_str(&body).map_err(|e| SyncError::Response(e.to_string()))
+}
+
+/// Ids are numbers in both services, but kept as text in case that changes
+fn bookmark_id(bookmark: &Bookmark) -> String {
+    match &bookmark.id {
+        serde_json::Value::String(id) => id.clone(),
+        id => id.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_responses() {
+        let check: LinkdingCheck = serde_json::from_str(
+            r#"{"bookmark": {"id": 7, "url": "https://example.com"}, "metadata": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            check.bookmark.as_ref().map(bookmark_id),
+            Some("7".to_string())
+        );
+        let check: LinkdingCheck =
+            serde_json::from_str(r#"{"bookmark": null, "metadata": {}}"#).unwrap();
+        assert!(check.bookmark.is_none());
+
+        let login: ShioriLogin = serde_json::from_str(
+            r#"{"ok": true, "message": {"token": "abc", "session": "def", "expires": 0}}"#,
+        )
+        .unwrap();
+        assert_eq!(login.message.token, "abc");
+        assert_eq!(login.message.session.as_deref(), Some("def"));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(SyncError::Status(502).is_retryable());
+        assert!(!SyncError::Status(401).is_retryable());
+        assert!(!SyncError::NoToken.is_retryable());
+    }
+}
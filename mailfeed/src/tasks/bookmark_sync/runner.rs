@@ -0,0 +1,153 @@
+use chrono::Utc;
+use reqwest::Client;
+
+use super::client::{BookmarkClient, SyncError};
+use crate::{
+    db::DbConnection,
+    models::{
+        bookmark_settings::{BookmarkService, BookmarkSettings},
+        maintenance_mode::MaintenanceMode,
+        retry_policy::{Channel, RetryPolicy},
+        starred_item::StarredItem,
+        user::User,
+    },
+    tasks::{
+        retry::with_retries_async,
+        types::{BOOKMARK_SYNC_INTERVAL, BOOKMARK_TIMEOUT},
+    },
+    DbPool,
+};
+
+/// Most of a user's starred items pushed in one pass
+const BATCH_SIZE: i64 = 50;
+
+/// Periodically push each user's new starred items to their bookmark
+/// manager, if they've set one up. Items stay pending until they have.
+pub async fn start(pool: DbPool) {
+    let http = Client::builder()
+        .timeout(BOOKMARK_TIMEOUT)
+        .user_agent("Mailfeed (https://github.com/anson-vandoren/mailfeed)")
+        .build()
+        .expect("Error building HTTP client");
+    let mut interval = tokio::time::interval(BOOKMARK_SYNC_INTERVAL);
+    loop {
+        interval.tick().await;
+        let mut conn = match pool.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("Error getting DB connection: {:?}", e);
+                continue;
+            }
+        };
+        if MaintenanceMode::load(&mut conn).enabled {
+            log::debug!("In maintenance mode, not sending bookmarks");
+            continue;
+        }
+
+        let users = match User::get_all(&mut conn) {
+            Ok(users) => users,
+            Err(e) => {
+                log::error!("Error getting users: {:?}", e);
+                continue;
+            }
+        };
+        let retry_policy = RetryPolicy::load(&mut conn, Channel::Bookmarks);
+        for user in users.into_iter().filter(|user| user.is_active) {
+            let settings = BookmarkSettings::load(&mut conn, user.id);
+            if let Some(service) = settings.service {
+                sync_user(&mut conn, &http, &retry_policy, &user, service, &settings).await;
+            }
+        }
+    }
+}
+
+async fn sync_user(
+    conn: &mut DbConnection,
+    http: &Client,
+    retry_policy: &RetryPolicy,
+    user: &User,
+    service: BookmarkService,
+    settings: &BookmarkSettings,
+) {
+    let pending = match StarredItem::pending(conn, user.id, BATCH_SIZE) {
+        Ok(pending) if pending.is_empty() => return,
+        Ok(pending) => pending,
+        Err(e) => {
+            log::error!("Error getting starred items for user {}: {:?}", user.id, e);
+            return;
+        }
+    };
+
+    let client = with_retries_async(
+        retry_policy,
+        &format!("connect to {} for user {}", settings.url, user.id),
+        || BookmarkClient::connect(http, service, settings),
+        SyncError::is_retryable,
+    )
+    .await;
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            // the items are tried again once the user fixes their settings
+            log::warn!(
+                "Error connecting to {} for user {}: {}",
+                settings.url,
+                user.id,
+                e
+            );
+            let error = e.to_string();
+            for (star, _) in &pending {
+                record(conn, star, Err(&error));
+            }
+            return;
+        }
+    };
+
+    for (star, item) in &pending {
+        // the same link starred from another feed only needs one bookmark
+        match StarredItem::synced_with_link(conn, user.id, &item.link) {
+            Ok(Some(synced)) => {
+                record(
+                    conn,
+                    star,
+                    Ok(synced.bookmark_id.as_deref().unwrap_or_default()),
+                );
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                log::error!("Error checking for duplicate bookmarks: {:?}", e);
+                continue;
+            }
+        }
+
+        let pushed = with_retries_async(
+            retry_policy,
+            &format!("bookmark {} for user {}", item.link, user.id),
+            || client.push(item),
+            SyncError::is_retryable,
+        )
+        .await;
+        match &pushed {
+            Ok(bookmark_id) => {
+                log::info!("Bookmarked {} for user {}", item.link, user.id);
+                record(conn, star, Ok(bookmark_id));
+            }
+            Err(e) => {
+                log::warn!(
+                    "Error bookmarking {} for user {}: {}",
+                    item.link,
+                    user.id,
+                    e
+                );
+                record(conn, star, Err(&e.to_string()));
+            }
+        }
+    }
+}
+
+fn record(conn: &mut DbConnection, star: &StarredItem, outcome: Result<&str, &str>) {
+    if let Err(e) = StarredItem::record_sync(conn, star.id, outcome, Utc::now().timestamp()) {
+        log::error!("Error recording bookmark sync for {}: {:?}", star.id, e);
+    }
+}
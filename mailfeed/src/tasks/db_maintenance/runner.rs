@@ -0,0 +1,247 @@
+use std::time::Instant;
+
+use chrono::{Timelike, Utc};
+use diesel::{connection::SimpleConnection, prelude::*, sql_types::BigInt};
+
+use super::types::{MaintenanceRun, MaintenanceStatus, StepResult};
+use crate::{
+    db::DbConnection,
+    models::retention::Retention,
+    tasks::{
+        types::MAINTENANCE_CHECK_INTERVAL,
+        webhooks::{Event, Webhooks},
+    },
+    DbPool,
+};
+
+/// Run in order. `optimize` and `ANALYZE` refresh the statistics the query
+/// planner uses, `incremental_vacuum` returns free pages to the filesystem,
+/// since a migration set the database to `auto_vacuum = INCREMENTAL`.
+const SQLITE_STEPS: [(&str, &str); 3] = [
+    ("optimize", "PRAGMA optimize;"),
+    ("incremental_vacuum", "PRAGMA incremental_vacuum;"),
+    ("analyze", "ANALYZE;"),
+];
+/// Postgres's autovacuum frees space by itself, so only the statistics
+/// are refreshed
+const POSTGRES_STEPS: [(&str, &str); 1] = [("analyze", "ANALYZE;")];
+
+fn steps_for(conn: &DbConnection) -> &'static [(&'static str, &'static str)] {
+    match conn {
+        DbConnection::Sqlite(_) => &SQLITE_STEPS,
+        DbConnection::Postgres(_) => &POSTGRES_STEPS,
+    }
+}
+
+#[derive(QueryableByName)]
+struct FreelistCount {
+    #[diesel(sql_type = BigInt)]
+    freelist_count: i64,
+}
+
+/// Once per maintenance window, tidy up the database
+pub async fn start(pool: DbPool, status: MaintenanceStatus, webhooks: Webhooks) {
+    let window = status.report().window;
+    log::info!(
+        "Database maintenance runs between {:02}:00 and {:02}:00 UTC",
+        window.start_hour,
+        window.end_hour
+    );
+    let mut interval = tokio::time::interval(MAINTENANCE_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        let last_started_at = status.report().last_run.map(|run| run.started_at);
+        if !window.is_due(now.hour(), now.timestamp(), last_started_at) {
+            continue;
+        }
+
+        let mut conn = match pool.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("Error getting DB connection: {:?}", e);
+                continue;
+            }
+        };
+        status.set_running();
+        let run = run(&mut conn);
+        let failures: Vec<String> = run
+            .steps
+            .iter()
+            .filter_map(|step| {
+                let error = step.error.as_ref()?;
+                Some(format!("{}: {}", step.name, error))
+            })
+            .collect();
+        if !failures.is_empty() {
+            webhooks.emit(Event::TaskFailed {
+                task: "database_maintenance".to_string(),
+                message: failures.join("; "),
+            });
+        }
+        status.finish(run);
+    }
+}
+
+/// Time one step, logging how it went
+fn step(
+    conn: &mut DbConnection,
+    name: &str,
+    f: impl FnOnce(&mut DbConnection) -> QueryResult<()>,
+) -> StepResult {
+    let started = Instant::now();
+    let result = f(conn);
+    let step = StepResult {
+        name: name.to_string(),
+        duration_ms: started.elapsed().as_millis(),
+        error: result.err().map(|e| e.to_string()),
+    };
+    match &step.error {
+        Some(e) => log::error!("Database maintenance: {} failed: {}", name, e),
+        None => log::info!("Database maintenance: {} took {}ms", name, step.duration_ms),
+    }
+    step
+}
+
+pub fn run(conn: &mut DbConnection) -> MaintenanceRun {
+    let started_at = Utc::now().timestamp();
+    let freelist_pages_before = freelist_count(conn);
+    let retention = Retention::load(conn);
+    let mut steps = Vec::new();
+
+    // pruned first, so the statistics and free pages reflect what's left
+    let mut items_pruned = None;
+    if retention.prunes_items() {
+        steps.push(step(conn, "prune_items", |conn| {
+            let pruned = retention.prune_items(conn, started_at)?;
+            log::info!("Database maintenance: pruned {} items", pruned);
+            items_pruned = Some(pruned);
+            Ok(())
+        }));
+    }
+    let mut feeds_pruned = None;
+    if retention.delete_orphaned_feeds {
+        steps.push(step(conn, "prune_feeds", |conn| {
+            let pruned = Retention::prune_orphaned_feeds(conn)?;
+            log::info!("Database maintenance: pruned {} feeds", pruned);
+            feeds_pruned = Some(pruned);
+            Ok(())
+        }));
+    }
+    for (name, sql) in steps_for(conn) {
+        steps.push(step(conn, name, |conn| conn.batch_execute(sql)));
+    }
+    // rewrites the whole file, so only as often as the settings allow
+    if retention.start_vacuum(conn, started_at) {
+        steps.push(step(conn, "vacuum", |conn| conn.batch_execute("VACUUM;")));
+    }
+
+    let freelist_pages_after = freelist_count(conn);
+    if let (Some(before), Some(after)) = (freelist_pages_before, freelist_pages_after) {
+        log::info!(
+            "Database maintenance: {} free pages before, {} after",
+            before,
+            after
+        );
+    }
+
+    MaintenanceRun {
+        started_at,
+        finished_at: Utc::now().timestamp(),
+        success: steps.iter().all(|step| step.error.is_none()),
+        steps,
+        freelist_pages_before,
+        freelist_pages_after,
+        items_pruned,
+        feeds_pruned,
+    }
+}
+
+/// Pages SQLite has free in the file, None on Postgres, which has no such
+/// count
+fn freelist_count(conn: &mut DbConnection) -> Option<i64> {
+    if let DbConnection::Postgres(_) = conn {
+        return None;
+    }
+    match diesel::sql_query("PRAGMA freelist_count").get_result::<FreelistCount>(conn) {
+        Ok(count) => Some(count.freelist_count),
+        Err(e) => {
+            log::warn!("Error getting database free pages: {:?}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_run() {
+        let mut conn = get_test_db_connection();
+        let run = run(&mut conn);
+        assert!(run.success);
+        let names: Vec<&str> = run.steps.iter().map(|step| step.name.as_str()).collect();
+        let sqlite = matches!(conn, DbConnection::Sqlite(_));
+        if sqlite {
+            assert_eq!(names, vec!["optimize", "incremental_vacuum", "analyze"]);
+        } else {
+            assert_eq!(names, vec!["analyze"]);
+        }
+        assert_eq!(run.freelist_pages_before.is_some(), sqlite);
+        assert_eq!(run.freelist_pages_after.is_some(), sqlite);
+        assert_eq!(run.items_pruned, None);
+    }
+
+    #[test]
+    fn test_auto_vacuum_is_incremental() {
+        #[derive(QueryableByName)]
+        struct AutoVacuum {
+            #[diesel(sql_type = BigInt)]
+            auto_vacuum: i64,
+        }
+        let mut conn = get_test_db_connection();
+        if let DbConnection::Postgres(_) = conn {
+            return;
+        }
+        let mode = diesel::sql_query("PRAGMA auto_vacuum")
+            .get_result::<AutoVacuum>(&mut conn)
+            .unwrap();
+        // 2 is INCREMENTAL
+        assert_eq!(mode.auto_vacuum, 2);
+    }
+
+    #[test]
+    fn test_run_with_retention() {
+        let mut conn = get_test_db_connection();
+        // Postgres can't VACUUM in the transaction each test runs in
+        if let DbConnection::Postgres(_) = conn {
+            return;
+        }
+        Retention {
+            retention_days: 30,
+            delete_orphaned_feeds: true,
+            vacuum_days: 7,
+            ..Default::default()
+        }
+        .save(&mut conn)
+        .unwrap();
+        let run = run(&mut conn);
+        assert!(run.success);
+        let names: Vec<&str> = run.steps.iter().map(|step| step.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "prune_items",
+                "prune_feeds",
+                "optimize",
+                "incremental_vacuum",
+                "analyze",
+                "vacuum"
+            ]
+        );
+        assert_eq!(run.items_pruned, Some(0));
+        assert_eq!(run.feeds_pruned, Some(0));
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    env,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+
+const DEFAULT_WINDOW: MaintenanceWindow = MaintenanceWindow {
+    start_hour: 3,
+    end_hour: 5,
+};
+
+/// Hours of the day (UTC) when maintenance may run, ending before
+/// `end_hour`. The window wraps past midnight if it ends before it starts,
+/// and covers the whole day if both are the same.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct MaintenanceWindow {
+    pub start_hour: u32,
+    pub end_hour: u32,
+}
+
+impl MaintenanceWindow {
+    /// From `MF_MAINTENANCE_WINDOW`, e.g. `3-5`
+    pub fn from_env() -> Self {
+        match env::var("MF_MAINTENANCE_WINDOW") {
+            Ok(value) => Self::parse(&value).unwrap_or_else(|| {
+                log::warn!(
+                    "Invalid MF_MAINTENANCE_WINDOW '{}', using default of {}-{}",
+                    value,
+                    DEFAULT_WINDOW.start_hour,
+                    DEFAULT_WINDOW.end_hour
+                );
+                DEFAULT_WINDOW
+            }),
+            Err(_) => DEFAULT_WINDOW,
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let (start, end) = value.split_once('-')?;
+        let start_hour = start.trim().parse::<u32>().ok().filter(|h| *h < 24)?;
+        let end_hour = end.trim().parse::<u32>().ok().filter(|h| *h < 24)?;
+        Some(MaintenanceWindow {
+            start_hour,
+            end_hour,
+        })
+    }
+
+    pub fn contains(&self, hour: u32) -> bool {
+        match self.start_hour.cmp(&self.end_hour) {
+            std::cmp::Ordering::Less => hour >= self.start_hour && hour < self.end_hour,
+            std::cmp::Ordering::Greater => hour >= self.start_hour || hour < self.end_hour,
+            std::cmp::Ordering::Equal => true,
+        }
+    }
+
+    /// Length of the window in seconds
+    fn length(&self) -> i64 {
+        let hours = (self.end_hour + 24 - self.start_hour) % 24;
+        let hours = if hours == 0 { 24 } else { hours };
+        hours as i64 * 60 * 60
+    }
+
+    /// Whether maintenance should run now, at most once per window
+    pub fn is_due(&self, hour: u32, now: i64, last_started_at: Option<i64>) -> bool {
+        if !self.contains(hour) {
+            return false;
+        }
+        match last_started_at {
+            Some(started_at) => now - started_at >= self.length(),
+            None => true,
+        }
+    }
+}
+
+/// How one maintenance statement went
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct StepResult {
+    pub name: String,
+    pub duration_ms: u128,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MaintenanceRun {
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub success: bool,
+    pub steps: Vec<StepResult>,
+    /// unused pages in the database file, which incremental_vacuum frees
+    pub freelist_pages_before: Option<i64>,
+    pub freelist_pages_after: Option<i64>,
+    /// deleted under the retention settings, if they say to prune
+    pub items_pruned: Option<usize>,
+    pub feeds_pruned: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceReport {
+    pub window: MaintenanceWindow,
+    pub running: bool,
+    pub last_run: Option<MaintenanceRun>,
+}
+
+/// The maintenance window and the last run, shared with the admin API.
+/// Kept in memory, so nothing is reported until the first run after a
+/// restart.
+#[derive(Clone)]
+pub struct MaintenanceStatus {
+    report: Arc<Mutex<MaintenanceReport>>,
+}
+
+impl MaintenanceStatus {
+    pub fn new(window: MaintenanceWindow) -> Self {
+        MaintenanceStatus {
+            report: Arc::new(Mutex::new(MaintenanceReport {
+                window,
+                running: false,
+                last_run: None,
+            })),
+        }
+    }
+
+    pub fn report(&self) -> MaintenanceReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    pub(super) fn set_running(&self) {
+        self.report.lock().unwrap().running = true;
+    }
+
+    pub(super) fn finish(&self, run: MaintenanceRun) {
+        let mut report = self.report.lock().unwrap();
+        report.running = false;
+        report.last_run = Some(run);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_window() {
+        assert_eq!(
+            MaintenanceWindow::parse("1-4"),
+            Some(MaintenanceWindow {
+                start_hour: 1,
+                end_hour: 4
+            })
+        );
+        assert_eq!(
+            MaintenanceWindow::parse(" 23 - 2 "),
+            Some(MaintenanceWindow {
+                start_hour: 23,
+                end_hour: 2
+            })
+        );
+        assert_eq!(MaintenanceWindow::parse("3"), None);
+        assert_eq!(MaintenanceWindow::parse("3-24"), None);
+        assert_eq!(MaintenanceWindow::parse("a-b"), None);
+    }
+
+    #[test]
+    fn test_window_contains() {
+        let window = MaintenanceWindow::parse("3-5").unwrap();
+        assert!(!window.contains(2));
+        assert!(window.contains(3));
+        assert!(window.contains(4));
+        assert!(!window.contains(5));
+
+        let wrapping = MaintenanceWindow::parse("23-2").unwrap();
+        assert!(wrapping.contains(23));
+        assert!(wrapping.contains(0));
+        assert!(wrapping.contains(1));
+        assert!(!wrapping.contains(2));
+        assert!(!wrapping.contains(12));
+
+        let all_day = MaintenanceWindow::parse("0-0").unwrap();
+        assert!(all_day.contains(12));
+    }
+
+    #[test]
+    fn test_is_due_once_per_window() {
+        let hour = 60 * 60;
+        let wrapping = MaintenanceWindow::parse("23-2").unwrap();
+        assert!(wrapping.is_due(23, 0, None));
+        assert!(!wrapping.is_due(12, 0, None));
+        // still in the same window after midnight
+        assert!(!wrapping.is_due(1, 2 * hour, Some(0)));
+        // the next night
+        assert!(wrapping.is_due(23, 24 * hour, Some(0)));
+    }
+}
@@ -0,0 +1,319 @@
+use chrono::{TimeZone, Utc};
+use futures_util::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Serialize;
+
+use crate::{
+    db::DbConnection,
+    models::{
+        discord_webhook::DiscordWebhook,
+        feed::Feed,
+        feed_item::FeedItem,
+        retry_policy::{Channel, RetryPolicy},
+        subscription::DeliveryMethod,
+        user::User,
+    },
+    tasks::{
+        dispatch::{is_web_link, truncate, Batch, ChannelError, DeliveryChannel, Destination},
+        html_to_text::html_to_text_truncated,
+        retry::with_retries_async,
+        types::DISCORD_TIMEOUT,
+    },
+};
+
+/// Name the messages are posted as, instead of the webhook's own
+const USERNAME: &str = "Mailfeed";
+/// Discord's limits on each message
+const MAX_EMBEDS: usize = 10;
+const MAX_MESSAGE_CHARS: usize = 6000;
+/// Discord's limits on each embed
+const MAX_TITLE_CHARS: usize = 256;
+const MAX_FOOTER_CHARS: usize = 2048;
+/// Characters of each item's description shown in its embed
+const SUMMARY_CHARS: usize = 300;
+/// The summary's link footnotes come on top, so cap the whole thing too
+const MAX_DESCRIPTION_CHARS: usize = 1000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("HTTP {0}")]
+    Status(u16),
+    #[error("{0}")]
+    Request(String),
+}
+
+impl Error {
+    /// Whether trying again later might work
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Status(status) => *status == 429 || *status >= 500,
+            Error::Request(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Message<'a> {
+    username: &'static str,
+    embeds: &'a [Embed],
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Embed {
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<Author>,
+    footer: Footer,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Author {
+    name: String,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Footer {
+    text: String,
+}
+
+impl Embed {
+    /// The characters Discord counts against a message's limit
+    fn chars(&self) -> usize {
+        self.title.chars().count()
+            + self.description.chars().count()
+            + self.footer.text.chars().count()
+            + self
+                .author
+                .as_ref()
+                .map_or(0, |author| author.name.chars().count())
+    }
+}
+
+/// An item as an embed, with the subscription's name for the feed in the
+/// footer
+fn embed(item: &FeedItem, feed: &Feed, feed_title: &str) -> Embed {
+    let (link, comments_link) = item.display_links(feed.link_mode());
+    let mut description = item
+        .description
+        .as_deref()
+        .map(|description| html_to_text_truncated(description, SUMMARY_CHARS).0)
+        .unwrap_or_default();
+    if let Some(comments_link) = comments_link.filter(|link| is_web_link(link)) {
+        if !description.is_empty() {
+            description.push_str("\n\n");
+        }
+        description.push_str(&format!("[Comments]({})", comments_link));
+    }
+    Embed {
+        title: truncate(&item.title, MAX_TITLE_CHARS),
+        url: Some(link.to_string()).filter(|link| is_web_link(link)),
+        description: truncate(&description, MAX_DESCRIPTION_CHARS),
+        timestamp: Utc
+            .timestamp_opt(item.pub_date, 0)
+            .single()
+            .map(|date| date.to_rfc3339()),
+        author: item.author.as_ref().map(|author| Author {
+            name: truncate(author, MAX_TITLE_CHARS),
+        }),
+        footer: Footer {
+            text: truncate(feed_title, MAX_FOOTER_CHARS),
+        },
+    }
+}
+
+/// The request bodies for a subscription's new items, one embed per item,
+/// split into as many messages as Discord's limits need
+fn messages(feed_title: &str, feed: &Feed, items: &[FeedItem]) -> Vec<String> {
+    let mut batches: Vec<Vec<Embed>> = vec![];
+    let mut batch_chars = 0;
+    for embed in items.iter().map(|item| embed(item, feed, feed_title)) {
+        let chars = embed.chars();
+        match batches.last_mut() {
+            Some(batch) if batch.len() < MAX_EMBEDS && batch_chars + chars <= MAX_MESSAGE_CHARS => {
+                batch_chars += chars;
+                batch.push(embed);
+            }
+            _ => {
+                batch_chars = chars;
+                batches.push(vec![embed]);
+            }
+        }
+    }
+    batches
+        .iter()
+        .map(|embeds| {
+            let message = Message {
+                username: USERNAME,
+                embeds,
+            };
+            serde_json::to_string(&message).expect("Embeds serialize to JSON")
+        })
+        .collect()
+}
+
+/// Post a message to the webhook
+pub async fn send(
+    client: &Client,
+    url: &str,
+    body: &str,
+    retry_policy: &RetryPolicy,
+) -> Result<(), Error> {
+    with_retries_async(
+        retry_policy,
+        "send Discord message",
+        || send_once(client, url, body),
+        Error::is_retryable,
+    )
+    .await
+}
+
+async fn send_once(client: &Client, url: &str, body: &str) -> Result<(), Error> {
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        // the URL holds the webhook's token, so leave it out of the error
+        .map_err(|e| Error::Request(e.without_url().to_string()))?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(Error::Status(status.as_u16())),
+    }
+}
+
+/// New items of subscriptions delivered by Discord, posted to the user's
+/// Discord webhook on the subscription's frequency like emails
+pub struct DiscordChannel {
+    client: Client,
+}
+
+impl Default for DiscordChannel {
+    fn default() -> Self {
+        let client = Client::builder()
+            .timeout(DISCORD_TIMEOUT)
+            .build()
+            .expect("Error building HTTP client");
+        DiscordChannel { client }
+    }
+}
+
+impl DeliveryChannel for DiscordChannel {
+    fn method(&self) -> DeliveryMethod {
+        DeliveryMethod::Discord
+    }
+
+    fn retry_channel(&self) -> Channel {
+        Channel::Discord
+    }
+
+    fn destination(&self, conn: &mut DbConnection, user: &User) -> Option<Box<dyn Destination>> {
+        let url = DiscordWebhook::load(conn, user.id).url()?.to_string();
+        Some(Box::new(DiscordDestination {
+            client: self.client.clone(),
+            url,
+        }))
+    }
+
+    fn preview(&self, _user: &User, batch: &Batch) -> Vec<String> {
+        messages(batch.title(), batch.feed, batch.items)
+    }
+}
+
+struct DiscordDestination {
+    client: Client,
+    url: String,
+}
+
+impl Destination for DiscordDestination {
+    /// Not the webhook URL, which holds its token
+    fn recipient(&self) -> &str {
+        "Discord"
+    }
+
+    fn send<'a>(
+        &'a self,
+        batch: &'a Batch<'a>,
+        retry_policy: &'a RetryPolicy,
+    ) -> BoxFuture<'a, Result<(), ChannelError>> {
+        async move {
+            for body in messages(batch.title(), batch.feed, batch.items) {
+                send(&self.client, &self.url, &body, retry_policy).await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::feed::LinkMode,
+        test_helpers::test_helpers::{test_feed, test_item},
+    };
+
+    fn hello_item(id: i32, description: &str) -> FeedItem {
+        FeedItem {
+            title: "Hello".to_string(),
+            link: "https://example.com/hello".to_string(),
+            description: Some(description.to_string()),
+            author: Some("Ann".to_string()),
+            comments_link: Some("https://example.com/hello#comments".to_string()),
+            ..test_item(id)
+        }
+    }
+
+    #[test]
+    fn test_embed() {
+        let feed = Feed {
+            link_mode: LinkMode::Both,
+            ..test_feed()
+        };
+        let embed = embed(&hello_item(1, "<p>Hi <b>there</b></p>"), &feed, "My feed");
+        assert_eq!(
+            embed,
+            Embed {
+                title: "Hello".to_string(),
+                url: Some("https://example.com/hello".to_string()),
+                description: "Hi there\n\n[Comments](https://example.com/hello#comments)"
+                    .to_string(),
+                timestamp: Some("1970-01-01T00:00:00+00:00".to_string()),
+                author: Some(Author {
+                    name: "Ann".to_string()
+                }),
+                footer: Footer {
+                    text: "My feed".to_string()
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_messages_split_at_limits() {
+        let feed = test_feed();
+        let items: Vec<FeedItem> = (0..12).map(|id| hello_item(id, "short")).collect();
+        let bodies = messages("My feed", &feed, &items);
+        let counts: Vec<usize> = bodies
+            .iter()
+            .map(|body| {
+                let message: serde_json::Value = serde_json::from_str(body).unwrap();
+                assert_eq!(message["username"], USERNAME);
+                message["embeds"].as_array().unwrap().len()
+            })
+            .collect();
+        assert_eq!(counts, vec![10, 2]);
+
+        // long embeds fill a message's characters before its embed count
+        let items: Vec<FeedItem> = (0..10).map(|id| hello_item(id, "short")).collect();
+        let bodies = messages(&"x".repeat(2000), &feed, &items);
+        assert_eq!(bodies.len(), 5);
+    }
+}
@@ -0,0 +1,619 @@
+pub mod decisions;
+pub mod enrichment;
+pub mod runner;
+
+use std::{collections::HashMap, env, sync::Arc};
+
+use chrono::Timelike;
+use futures_util::{future::BoxFuture, FutureExt};
+
+use self::{
+    decisions::{Gate, ItemDecision, ItemReason, SendDecision},
+    enrichment::ItemStats,
+};
+use crate::{
+    db::DbConnection,
+    models::{
+        feed::Feed,
+        feed_item::FeedItem,
+        max_item_age::MaxItemAge,
+        retry_policy::{Channel, RetryPolicy},
+        subscription::{DeliveryMethod, Frequency, Subscription},
+        user::User,
+    },
+    scheduler::cron::CronSchedule,
+    tasks::html_to_text::item_text,
+};
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+/// How far into a week Monday starts, since slots count from 1970-01-01,
+/// a Thursday
+const MONDAY: i64 = 4 * DAY;
+/// The next send time of a subscription whose schedule never matches
+const NEVER: i64 = i64::MAX;
+const DEFAULT_JITTER_MINUTES: i64 = 10;
+
+/// When subscriptions on a frequency are sent, for every delivery method.
+///
+/// Each user's subscriptions are sent in fixed slots: hourly ones at the
+/// top of the user's local hour, daily ones at their daily send time,
+/// weekly ones at that time on Mondays, and cron ones at each local time
+/// their schedule matches. A subscription is due once a slot passes after
+/// it was last sent, so late sends don't push the next one later. Each
+/// user's slots are shifted by up to `MF_SEND_JITTER_MINUTES`, the same
+/// amount every time, so users don't all send at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SendSlots {
+    max_jitter: i64,
+}
+
+impl Default for SendSlots {
+    fn default() -> Self {
+        SendSlots {
+            max_jitter: DEFAULT_JITTER_MINUTES * 60,
+        }
+    }
+}
+
+impl SendSlots {
+    pub fn from_env() -> Self {
+        let minutes = match env::var("MF_SEND_JITTER_MINUTES") {
+            Ok(value) => match value.parse::<i64>() {
+                Ok(minutes) if (0..60).contains(&minutes) => minutes,
+                _ => {
+                    log::warn!(
+                        "Invalid MF_SEND_JITTER_MINUTES '{}', using default of {}",
+                        value,
+                        DEFAULT_JITTER_MINUTES
+                    );
+                    DEFAULT_JITTER_MINUTES
+                }
+            },
+            Err(_) => DEFAULT_JITTER_MINUTES,
+        };
+        SendSlots {
+            max_jitter: minutes * 60,
+        }
+    }
+
+    /// The earliest time the subscription's next delivery can be sent, or
+    /// when it was last sent for realtime subscriptions
+    pub fn next_send_time(&self, sub: &Subscription, user: &User) -> i64 {
+        if sub.frequency.is_realtime() {
+            return sub.last_sent_time;
+        }
+        let offset_at = |at: i64| user.utc_offset(at).local_minus_utc() as i64;
+        let next_after = |local: i64| self.next_local_slot(&sub.frequency, user, local);
+        // slots are found in the user's local time, then converted back with
+        // the offset at that time, which differs if daylight saving starts or
+        // ends in between
+        let last_offset = offset_at(sub.last_sent_time);
+        let local_next = match next_after(sub.last_sent_time + last_offset) {
+            Some(local_next) => local_next,
+            None => return NEVER,
+        };
+        let shift = offset_at(local_next - last_offset);
+        match local_next - shift {
+            next if next > sub.last_sent_time => next,
+            _ => next_after(local_next).map_or(NEVER, |local| local - shift),
+        }
+    }
+
+    /// The first slot after the given local time, None for realtime or a
+    /// cron schedule that never matches
+    fn next_local_slot(&self, frequency: &Frequency, user: &User, local: i64) -> Option<i64> {
+        let jitter = self.jitter(user);
+        let send_time = user.send_time().num_seconds_from_midnight() as i64;
+        match frequency {
+            Frequency::Realtime => None,
+            Frequency::Hourly => Some(next_slot(local, HOUR, jitter)),
+            Frequency::Daily => Some(next_slot(local, DAY, send_time + jitter)),
+            Frequency::Weekly => Some(next_slot(local, WEEK, MONDAY + send_time + jitter)),
+            Frequency::WeeklyOn(day) => Some(next_slot(
+                local,
+                WEEK,
+                MONDAY + day.days_from_monday() * DAY + send_time + jitter,
+            )),
+            Frequency::EveryHours(hours) => {
+                Some(next_slot(local, *hours as i64 * HOUR, send_time + jitter))
+            }
+            Frequency::Cron(expression) => match CronSchedule::parse(expression) {
+                Ok(schedule) => schedule
+                    .next_after(local - jitter)
+                    .map(|next| next + jitter),
+                Err(e) => {
+                    log::warn!("Invalid cron frequency '{}': {}", expression, e);
+                    None
+                }
+            },
+        }
+    }
+
+    /// Whether the subscription's new items should be sent now
+    pub fn is_due(&self, sub: &Subscription, user: &User, now: i64) -> bool {
+        sub.is_active
+            && match sub.frequency {
+                Frequency::Realtime => true,
+                _ => now >= self.next_send_time(sub, user),
+            }
+    }
+
+    /// How far the user's slots are shifted, spread over the allowed range
+    /// by user ID so it stays the same between runs
+    fn jitter(&self, user: &User) -> i64 {
+        (user.id.0 as i64 * 2_654_435_761).rem_euclid(self.max_jitter + 1)
+    }
+}
+
+/// The first time after `after` that's `phase` seconds into a period
+fn next_slot(after: i64, period: i64, phase: i64) -> i64 {
+    after - (after - phase).rem_euclid(period) + period
+}
+
+pub type ChannelError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A way of delivering subscriptions' new items, by email or otherwise.
+/// Register it with the dispatcher in `main`, which works out which
+/// subscriptions are due, gathers and filters their new items, and records
+/// each delivery; the channel only sends.
+pub trait DeliveryChannel: Send + Sync {
+    /// The subscriptions it delivers
+    fn method(&self) -> DeliveryMethod;
+
+    /// Whose retry policy its sends follow
+    fn retry_channel(&self) -> Channel;
+
+    /// Where the user's deliveries go, or None until they've set it up
+    fn destination(&self, conn: &mut DbConnection, user: &User) -> Option<Box<dyn Destination>>;
+
+    /// The body of each message it would send the user for the batch, for
+    /// showing before a subscription's changes are saved
+    fn preview(&self, user: &User, batch: &Batch) -> Vec<String>;
+}
+
+/// The registered channels, shared by the dispatcher and the API
+#[derive(Clone)]
+pub struct Channels(Arc<Vec<Box<dyn DeliveryChannel>>>);
+
+impl Channels {
+    pub fn new(channels: Vec<Box<dyn DeliveryChannel>>) -> Self {
+        Channels(Arc::new(channels))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn DeliveryChannel> {
+        self.0.iter().map(|channel| channel.as_ref())
+    }
+
+    /// The channel delivering subscriptions by `method`, if there is one
+    pub fn get(&self, method: DeliveryMethod) -> Option<&dyn DeliveryChannel> {
+        self.iter().find(|channel| channel.method() == method)
+    }
+}
+
+/// A channel set up for one user, ready to send
+pub trait Destination: Send + Sync {
+    /// Recorded in the delivery ledger, so nothing secret
+    fn recipient(&self) -> &str;
+
+    /// Send the batch, retrying per the policy. Only Ok if all of it was
+    /// accepted.
+    fn send<'a>(
+        &'a self,
+        batch: &'a Batch<'a>,
+        retry_policy: &'a RetryPolicy,
+    ) -> BoxFuture<'a, Result<(), ChannelError>>;
+
+    /// Send all of the user's due batches, saying how each went, in the same
+    /// order. Each is sent on its own, unless the channel can combine them
+    /// the way email digests do.
+    fn send_all<'a>(
+        &'a self,
+        _conn: &'a mut DbConnection,
+        batches: &'a [Batch<'a>],
+        retry_policy: &'a RetryPolicy,
+    ) -> BoxFuture<'a, Vec<Sent>> {
+        async move {
+            let mut sent = Vec::with_capacity(batches.len());
+            for batch in batches {
+                let response = self.send(batch, retry_policy).await;
+                sent.push(Sent {
+                    recipient: self.recipient().to_string(),
+                    response: response.map(|()| "Accepted".to_string()),
+                });
+            }
+            sent
+        }
+        .boxed()
+    }
+}
+
+/// How sending a batch went, for the delivery ledger
+pub struct Sent {
+    pub recipient: String,
+    /// what the other end replied, or why it wasn't accepted
+    pub response: Result<String, ChannelError>,
+}
+
+/// A subscription's new items that pass its keyword filters
+pub struct Batch<'a> {
+    pub sub: &'a Subscription,
+    pub feed: &'a Feed,
+    pub items: &'a [FeedItem],
+    /// score and comment count of the items that have them, by item id,
+    /// if the subscription shows or filters on them
+    pub stats: &'a HashMap<i32, ItemStats>,
+    /// when it's being sent
+    pub now: i64,
+}
+
+impl Batch<'_> {
+    /// The subscription's name for the feed
+    pub fn title(&self) -> &str {
+        self.sub.display_name(self.feed)
+    }
+
+    pub fn homepage(&self) -> &str {
+        self.sub.display_homepage(self.feed)
+    }
+}
+
+/// Why the subscription shouldn't be sent now, if it shouldn't. On the
+/// user's skip days, items wait for the next day that isn't skipped.
+fn hold(
+    slots: &SendSlots,
+    sub: &Subscription,
+    user: &User,
+    skip_today: bool,
+    now: i64,
+) -> Option<SendDecision> {
+    let gate = if !slots.is_due(sub, user, now) {
+        if sub.is_active {
+            Gate::NotDue
+        } else {
+            Gate::Inactive
+        }
+    } else if skip_today {
+        Gate::Skipped
+    } else if sub
+        .delivery_window()
+        .is_some_and(|window| !window.contains(user.local_time(now)))
+    {
+        Gate::OutsideWindow
+    } else {
+        return None;
+    };
+    Some(SendDecision {
+        checked_at: now,
+        gate,
+        sent_after: sub.last_sent_time,
+        due_at: (gate == Gate::NotDue).then(|| slots.next_send_time(sub, user)),
+        items: Vec::new(),
+        sent: false,
+        error: None,
+    })
+}
+
+/// The items that pass the subscription's keyword filters and are new
+/// enough to send at `now`
+pub fn passing_filters(
+    sub: &Subscription,
+    max_age: &MaxItemAge,
+    items: Vec<FeedItem>,
+    now: i64,
+) -> Vec<FeedItem> {
+    filter_items(sub, max_age, items, now, &mut Vec::new())
+}
+
+/// As `passing_filters`, adding why each item was dropped to `dropped`.
+/// Age goes first, so a stale item is reported as that even if it also
+/// misses the keywords.
+fn filter_items(
+    sub: &Subscription,
+    max_age: &MaxItemAge,
+    items: Vec<FeedItem>,
+    now: i64,
+    dropped: &mut Vec<ItemDecision>,
+) -> Vec<FeedItem> {
+    let keyword_filter = sub.keyword_filter();
+    let max_age_days = max_age.days_for(sub);
+    let oldest = max_age.oldest_for(sub, now).unwrap_or(i64::MIN);
+    let mut passing = Vec::new();
+    for item in items {
+        if item.pub_date < oldest {
+            let reason = ItemReason::TooOld {
+                pub_date: item.pub_date,
+                max_age_days: max_age_days.unwrap_or_default(),
+            };
+            dropped.push(ItemDecision::excluded(&item, reason));
+            continue;
+        }
+        match keyword_filter.check(&item_text(&item)) {
+            Some(miss) => dropped.push(ItemDecision::excluded(&item, miss.into())),
+            None => passing.push(item),
+        }
+    }
+    passing
+}
+
+/// Cut the text to at most `max_chars`, ending in an ellipsis if it was
+/// longer
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Whether the link is one chat and push clients will open
+pub fn is_web_link(link: &str) -> bool {
+    link.starts_with("https://") || link.starts_with("http://")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{
+            ids::{FeedId, SubscriptionId, UserId},
+            keyword_filter::Keywords,
+            subscription::Weekday,
+        },
+        test_helpers::test_helpers::{test_item, test_user},
+    };
+
+    fn sending_at(daily_send_time: &str) -> User {
+        User {
+            daily_send_time: daily_send_time.to_string(),
+            ..test_user()
+        }
+    }
+
+    fn test_subscription(frequency: Frequency, last_sent_time: i64) -> Subscription {
+        Subscription {
+            id: SubscriptionId(1),
+            user_id: UserId(1),
+            friendly_name: String::new(),
+            frequency,
+            last_sent_time,
+            max_items: 0,
+            is_active: true,
+            feed_id: FeedId(1),
+            description: None,
+            homepage: None,
+            send_email: None,
+            subject_prefix: None,
+            subject_template: None,
+            show_stats: false,
+            min_score: None,
+            min_comments: None,
+            feed_failure_notified_at: 0,
+            delivery_window: None,
+            include_keywords: Keywords::default(),
+            exclude_keywords: Keywords::default(),
+            delivery_method: DeliveryMethod::Email,
+            max_item_age_days: None,
+            position: 0,
+        }
+    }
+
+    const NO_JITTER: SendSlots = SendSlots { max_jitter: 0 };
+    // 2026-10-16 00:00 UTC
+    const MIDNIGHT: i64 = 1_792_108_800;
+
+    #[test]
+    fn test_next_slot() {
+        assert_eq!(next_slot(0, HOUR, 0), HOUR);
+        assert_eq!(next_slot(10, HOUR, 0), HOUR);
+        assert_eq!(next_slot(HOUR - 1, HOUR, 0), HOUR);
+        assert_eq!(next_slot(HOUR, HOUR, 0), 2 * HOUR);
+        assert_eq!(next_slot(10, HOUR, 600), 600);
+        assert_eq!(next_slot(700, HOUR, 600), HOUR + 600);
+    }
+
+    #[test]
+    fn test_hourly_slots_dont_drift() {
+        let user = sending_at("00:00+00:00");
+        // sent a few minutes late, the next is still at the top of the hour
+        let sub = test_subscription(Frequency::Hourly, MIDNIGHT + 7 * 60);
+        assert_eq!(NO_JITTER.next_send_time(&sub, &user), MIDNIGHT + HOUR);
+        assert!(!NO_JITTER.is_due(&sub, &user, MIDNIGHT + HOUR - 1));
+        assert!(NO_JITTER.is_due(&sub, &user, MIDNIGHT + HOUR));
+
+        // local hours half an hour off UTC
+        let user = sending_at("08:00+05:30");
+        assert_eq!(NO_JITTER.next_send_time(&sub, &user), MIDNIGHT + HOUR / 2);
+    }
+
+    #[test]
+    fn test_daily_slot_is_the_users_send_time() {
+        // 08:00 at UTC+05:30 is 02:30 UTC
+        let user = sending_at("08:00+05:30");
+        let sub = test_subscription(Frequency::Daily, MIDNIGHT + 3 * HOUR);
+        assert_eq!(
+            NO_JITTER.next_send_time(&sub, &user),
+            MIDNIGHT + DAY + 2 * HOUR + HOUR / 2
+        );
+        // 20:00 at UTC-07:00 is 03:00 UTC the next day
+        let user = sending_at("20:00-07:00");
+        assert_eq!(
+            NO_JITTER.next_send_time(&sub, &user),
+            MIDNIGHT + DAY + 3 * HOUR
+        );
+        // no send time is midnight UTC
+        let user = sending_at("");
+        assert_eq!(NO_JITTER.next_send_time(&sub, &user), MIDNIGHT + DAY);
+    }
+
+    #[test]
+    fn test_daily_slot_follows_daylight_saving() {
+        let mut user = sending_at("07:00");
+        user.timezone = Some("Europe/Berlin".to_string());
+        // 07:00 CEST is 05:00 UTC, until DST ends on 2026-10-25
+        let sub = test_subscription(Frequency::Daily, MIDNIGHT + 5 * HOUR);
+        assert_eq!(
+            NO_JITTER.next_send_time(&sub, &user),
+            MIDNIGHT + DAY + 5 * HOUR
+        );
+        // then 07:00 CET is 06:00 UTC
+        let sub = test_subscription(Frequency::Daily, MIDNIGHT + 8 * DAY + 5 * HOUR);
+        assert_eq!(
+            NO_JITTER.next_send_time(&sub, &user),
+            MIDNIGHT + 9 * DAY + 6 * HOUR
+        );
+    }
+
+    #[test]
+    fn test_weekly_slot_is_monday_at_the_send_time() {
+        // 2026-10-16 is a Friday, so the next Monday is the 19th
+        let user = sending_at("09:00+02:00");
+        let sub = test_subscription(Frequency::Weekly, MIDNIGHT + 3 * HOUR);
+        let monday = MIDNIGHT + 3 * DAY;
+        assert_eq!(NO_JITTER.next_send_time(&sub, &user), monday + 7 * HOUR);
+        let sub = test_subscription(Frequency::Weekly, monday + 7 * HOUR);
+        assert_eq!(
+            NO_JITTER.next_send_time(&sub, &user),
+            monday + 7 * DAY + 7 * HOUR
+        );
+    }
+
+    #[test]
+    fn test_weekly_on_and_every_hours_slots() {
+        let user = sending_at("09:00+00:00");
+        // the Friday it is, after the send time, so next week's
+        let sub = test_subscription(Frequency::WeeklyOn(Weekday::Friday), MIDNIGHT + 10 * HOUR);
+        assert_eq!(
+            NO_JITTER.next_send_time(&sub, &user),
+            MIDNIGHT + 7 * DAY + 9 * HOUR
+        );
+        let sub = test_subscription(Frequency::WeeklyOn(Weekday::Sunday), MIDNIGHT);
+        assert_eq!(
+            NO_JITTER.next_send_time(&sub, &user),
+            MIDNIGHT + 2 * DAY + 9 * HOUR
+        );
+
+        // 09:00, 15:00, 21:00, 03:00, ...
+        let sub = test_subscription(Frequency::EveryHours(6), MIDNIGHT + 10 * HOUR);
+        assert_eq!(NO_JITTER.next_send_time(&sub, &user), MIDNIGHT + 15 * HOUR);
+        let sub = test_subscription(Frequency::EveryHours(6), MIDNIGHT + 22 * HOUR);
+        assert_eq!(
+            NO_JITTER.next_send_time(&sub, &user),
+            MIDNIGHT + DAY + 3 * HOUR
+        );
+    }
+
+    #[test]
+    fn test_cron_slots_are_in_local_time() {
+        // every 6 hours, at UTC-05:00
+        let user = sending_at("00:00-05:00");
+        let every_six_hours = Frequency::Cron("0 */6 * * *".to_string());
+        let sub = test_subscription(every_six_hours.clone(), MIDNIGHT + 6 * HOUR);
+        // 01:00 local, so 06:00 local is 11:00 UTC
+        assert_eq!(NO_JITTER.next_send_time(&sub, &user), MIDNIGHT + 11 * HOUR);
+        let sub = test_subscription(every_six_hours, MIDNIGHT + 11 * HOUR);
+        assert_eq!(NO_JITTER.next_send_time(&sub, &user), MIDNIGHT + 17 * HOUR);
+
+        let never = test_subscription(Frequency::Cron("0 0 30 2 *".to_string()), MIDNIGHT);
+        assert_eq!(NO_JITTER.next_send_time(&never, &user), NEVER);
+        assert!(!NO_JITTER.is_due(&never, &user, MIDNIGHT + 400 * DAY));
+    }
+
+    #[test]
+    fn test_jitter_is_fixed_per_user() {
+        let slots = SendSlots::default();
+        let mut user = sending_at("00:00+00:00");
+        // sent on the hour, the next is the same few minutes past every hour
+        let sub = test_subscription(Frequency::Hourly, MIDNIGHT);
+        let next = slots.next_send_time(&sub, &user);
+        assert!((MIDNIGHT + 1..=MIDNIGHT + slots.max_jitter).contains(&next));
+        let sub = test_subscription(Frequency::Hourly, next);
+        assert_eq!(slots.next_send_time(&sub, &user), next + HOUR);
+
+        let jitters: Vec<i64> = (1..=5)
+            .map(|id| {
+                user.id = UserId(id);
+                slots.jitter(&user)
+            })
+            .collect();
+        assert!(jitters.iter().all(|jitter| *jitter <= slots.max_jitter));
+        assert!(jitters.iter().any(|jitter| *jitter != jitters[0]));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        let truncated = truncate(&"a".repeat(300), 256);
+        assert_eq!(truncated.chars().count(), 256);
+        assert!(truncated.ends_with('…'));
+    }
+
+    #[test]
+    fn test_hold() {
+        let user = sending_at("00:00+00:00");
+        let mut sub = test_subscription(Frequency::Daily, MIDNIGHT);
+        let held = hold(&NO_JITTER, &sub, &user, false, MIDNIGHT + HOUR).unwrap();
+        assert_eq!(held.gate, Gate::NotDue);
+        assert_eq!(held.due_at, Some(MIDNIGHT + DAY));
+
+        let due = MIDNIGHT + DAY;
+        assert!(hold(&NO_JITTER, &sub, &user, false, due).is_none());
+        let held = hold(&NO_JITTER, &sub, &user, true, due).unwrap();
+        assert_eq!((held.gate, held.due_at), (Gate::Skipped, None));
+
+        sub.is_active = false;
+        let held = hold(&NO_JITTER, &sub, &user, false, due).unwrap();
+        assert_eq!((held.gate, held.due_at), (Gate::Inactive, None));
+    }
+
+    #[test]
+    fn test_filter_items_says_why_items_were_dropped() {
+        let item = |id: i32, title: &str, pub_date: i64| FeedItem {
+            title: title.to_string(),
+            pub_date,
+            ..test_item(id)
+        };
+        let mut sub = test_subscription(Frequency::Daily, 0);
+        sub.include_keywords = Keywords(vec!["rust".to_string()]);
+        let max_age = MaxItemAge { default_days: 2 };
+        let items = vec![
+            item(1, "Rust news", MIDNIGHT),
+            item(2, "Other news", MIDNIGHT),
+            // stale and missing the keyword, reported as stale
+            item(3, "Old news", MIDNIGHT - 3 * DAY),
+        ];
+
+        let mut dropped = Vec::new();
+        let passing = filter_items(&sub, &max_age, items, MIDNIGHT, &mut dropped);
+        assert_eq!(passing.len(), 1);
+        assert_eq!(passing[0].id, 1);
+        let reasons: Vec<(i32, &ItemReason)> = dropped
+            .iter()
+            .map(|decision| (decision.item_id, &decision.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (2, &ItemReason::NoIncludedKeyword),
+                (
+                    3,
+                    &ItemReason::TooOld {
+                        pub_date: MIDNIGHT - 3 * DAY,
+                        max_age_days: 2
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_realtime_and_inactive() {
+        let user = sending_at("00:00+00:00");
+        let mut sub = test_subscription(Frequency::Realtime, 1000);
+        assert!(NO_JITTER.is_due(&sub, &user, 1000));
+        sub.is_active = false;
+        assert!(!NO_JITTER.is_due(&sub, &user, 1000));
+    }
+}
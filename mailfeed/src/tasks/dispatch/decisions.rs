@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+
+use crate::models::{feed_item::FeedItem, ids::SubscriptionId, keyword_filter::KeywordMiss};
+
+/// Whether the dispatcher looked at the subscription's items
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Gate {
+    Due,
+    Inactive,
+    /// its frequency hasn't passed since it was last sent
+    NotDue,
+    /// the user skips deliveries today, a weekend or one of their skip dates
+    Skipped,
+    /// due, but outside the subscription's delivery window
+    OutsideWindow,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum ItemReason {
+    /// published since it was last sent
+    New,
+    BelowMinScore {
+        score: i64,
+        min: i32,
+    },
+    BelowMinComments {
+        comments: i64,
+        min: i32,
+    },
+    /// matched one of the subscription's exclude keywords
+    ExcludedKeyword {
+        keyword: String,
+    },
+    /// didn't match any of the subscription's include keywords
+    NoIncludedKeyword,
+    /// published longer ago than the subscription's max item age
+    TooOld {
+        pub_date: i64,
+        max_age_days: i32,
+    },
+}
+
+impl From<KeywordMiss> for ItemReason {
+    fn from(miss: KeywordMiss) -> Self {
+        match miss {
+            KeywordMiss::Excluded(keyword) => ItemReason::ExcludedKeyword { keyword },
+            KeywordMiss::NotIncluded => ItemReason::NoIncludedKeyword,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ItemDecision {
+    pub item_id: i32,
+    pub title: String,
+    pub included: bool,
+    #[serde(flatten)]
+    pub reason: ItemReason,
+}
+
+impl ItemDecision {
+    pub fn included(item: &FeedItem) -> Self {
+        ItemDecision {
+            item_id: item.id,
+            title: item.title.clone(),
+            included: true,
+            reason: ItemReason::New,
+        }
+    }
+
+    pub fn excluded(item: &FeedItem, reason: ItemReason) -> Self {
+        ItemDecision {
+            item_id: item.id,
+            title: item.title.clone(),
+            included: false,
+            reason,
+        }
+    }
+}
+
+/// What the dispatcher decided for a subscription on one check
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SendDecision {
+    pub checked_at: i64,
+    pub gate: Gate,
+    /// items published at or before this were already sent, so weren't
+    /// looked at
+    pub sent_after: i64,
+    /// when it becomes due, if it wasn't yet
+    pub due_at: Option<i64>,
+    pub items: Vec<ItemDecision>,
+    pub sent: bool,
+    pub error: Option<String>,
+}
+
+/// The last decision for each subscription, shared with the API. Kept in
+/// memory, so they're gone after a restart.
+#[derive(Clone, Default)]
+pub struct SendDecisions {
+    last: Arc<Mutex<HashMap<SubscriptionId, SendDecision>>>,
+}
+
+impl SendDecisions {
+    pub fn record(&self, sub_id: SubscriptionId, decision: SendDecision) {
+        self.last.lock().unwrap().insert(sub_id, decision);
+    }
+
+    pub fn get(&self, sub_id: SubscriptionId) -> Option<SendDecision> {
+        self.last.lock().unwrap().get(&sub_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::test_item;
+
+    #[test]
+    fn test_serialize_item_decision() {
+        let item = FeedItem {
+            title: "Title".to_string(),
+            pub_date: 1000,
+            ..test_item(7)
+        };
+        let decision =
+            ItemDecision::excluded(&item, ItemReason::BelowMinScore { score: 3, min: 10 });
+        assert_eq!(
+            serde_json::to_value(&decision).unwrap(),
+            serde_json::json!({
+                "item_id": 7,
+                "title": "Title",
+                "included": false,
+                "reason": "below_min_score",
+                "score": 3,
+                "min": 10,
+            })
+        );
+
+        let decisions = SendDecisions::default();
+        assert_eq!(decisions.get(SubscriptionId(1)), None);
+    }
+}
@@ -0,0 +1,288 @@
+use std::{collections::HashMap, fmt, time::Instant};
+
+use reqwest::Client;
+use serde_json::Value;
+use url::Url;
+
+use super::decisions::{ItemDecision, ItemReason};
+use crate::{
+    models::{feed_item::FeedItem, subscription::Subscription},
+    tasks::types::ITEM_STATS_CACHE_TTL,
+};
+
+/// Score and comment count of an aggregator item
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ItemStats {
+    pub score: i64,
+    pub comments: i64,
+}
+
+impl fmt::Display for ItemStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} points, {} comments", self.score, self.comments)
+    }
+}
+
+/// Where to look up an item's stats
+#[derive(Debug, PartialEq)]
+enum Source {
+    HackerNews(String),
+    Reddit(String),
+    Lobsters(String),
+}
+
+impl Source {
+    /// Recognize a Hacker News, Reddit or Lobsters discussion URL
+    fn from_link(link: &str) -> Option<Source> {
+        let url = Url::parse(link).ok()?;
+        let host = url.host_str()?;
+        let path = url.path().trim_end_matches('/');
+
+        if host == "news.ycombinator.com" && path == "/item" {
+            let id = url.query_pairs().find(|(k, _)| k == "id")?.1;
+            return Some(Source::HackerNews(format!(
+                "https://hacker-news.firebaseio.com/v0/item/{}.json",
+                id
+            )));
+        }
+        if (host == "reddit.com" || host.ends_with(".reddit.com")) && path.contains("/comments/") {
+            return Some(Source::Reddit(format!(
+                "https://www.reddit.com{}.json",
+                path
+            )));
+        }
+        if host == "lobste.rs" && path.starts_with("/s/") {
+            let short_id = path.split('/').nth(2)?;
+            return Some(Source::Lobsters(format!(
+                "https://lobste.rs/s/{}.json",
+                short_id
+            )));
+        }
+        None
+    }
+
+    fn api_url(&self) -> &str {
+        match self {
+            Source::HackerNews(url) | Source::Reddit(url) | Source::Lobsters(url) => url,
+        }
+    }
+
+    fn parse(&self, body: &str) -> Option<ItemStats> {
+        let json: Value = serde_json::from_str(body).ok()?;
+        let (score, comments) = match self {
+            Source::HackerNews(_) => (&json["score"], &json["descendants"]),
+            Source::Reddit(_) => {
+                let post = &json[0]["data"]["children"][0]["data"];
+                (&post["score"], &post["num_comments"])
+            }
+            Source::Lobsters(_) => (&json["score"], &json["comment_count"]),
+        };
+        Some(ItemStats {
+            score: score.as_i64()?,
+            // new HN stories may not have a descendants count yet
+            comments: comments.as_i64().unwrap_or(0),
+        })
+    }
+}
+
+/// Looks up aggregator item stats from the sites' public APIs, caching
+/// results (including failures) so each item is fetched at most once per
+/// cache period
+#[derive(Default)]
+pub struct Enricher {
+    client: Client,
+    cache: HashMap<String, (Instant, Option<ItemStats>)>,
+}
+
+impl Enricher {
+    /// Fetch stats for the subscription's items if it shows them or filters
+    /// on them, and drop items below its thresholds, adding why each was
+    /// dropped to `dropped`. Items whose stats can't be found are always
+    /// kept. Returns the stats found, by item id.
+    pub async fn enrich(
+        &mut self,
+        sub: &Subscription,
+        items: &mut Vec<FeedItem>,
+        dropped: &mut Vec<ItemDecision>,
+    ) -> HashMap<i32, ItemStats> {
+        let mut stats = HashMap::new();
+        if !sub.show_stats && sub.min_score.is_none() && sub.min_comments.is_none() {
+            return stats;
+        }
+        self.cache
+            .retain(|_, (fetched, _)| fetched.elapsed() < ITEM_STATS_CACHE_TTL);
+
+        for item in items.iter() {
+            if let Some(item_stats) = self.stats(item).await {
+                stats.insert(item.id, item_stats);
+            }
+        }
+
+        let before = dropped.len();
+        for item in std::mem::take(items) {
+            let miss = stats
+                .get(&item.id)
+                .and_then(|stats| threshold_miss(stats, sub.min_score, sub.min_comments));
+            match miss {
+                Some(reason) => dropped.push(ItemDecision::excluded(&item, reason)),
+                None => items.push(item),
+            }
+        }
+        if dropped.len() > before {
+            log::debug!(
+                "Filtered {} items below thresholds for sub_id={}",
+                dropped.len() - before,
+                sub.id
+            );
+        }
+        stats
+    }
+
+    async fn stats(&mut self, item: &FeedItem) -> Option<ItemStats> {
+        let source = item
+            .comments_link
+            .as_deref()
+            .and_then(Source::from_link)
+            .or_else(|| Source::from_link(&item.link))?;
+        let api_url = source.api_url().to_string();
+        if let Some((_, stats)) = self.cache.get(&api_url) {
+            return *stats;
+        }
+
+        let stats = match self.fetch(&api_url).await {
+            Ok(body) => source.parse(&body),
+            Err(e) => {
+                log::warn!("Error fetching item stats from {}: {:?}", api_url, e);
+                None
+            }
+        };
+        self.cache.insert(api_url, (Instant::now(), stats));
+        stats
+    }
+
+    async fn fetch(&self, api_url: &str) -> Result<String, reqwest::Error> {
+        self.client
+            .get(api_url)
+            .header(
+                "User-Agent",
+                "Mailfeed (https://github.com/anson-vandoren/mailfeed)",
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+    }
+}
+
+/// The first threshold the item is below, if any
+fn threshold_miss(
+    stats: &ItemStats,
+    min_score: Option<i32>,
+    min_comments: Option<i32>,
+) -> Option<ItemReason> {
+    match (min_score, min_comments) {
+        (Some(min), _) if stats.score < min as i64 => Some(ItemReason::BelowMinScore {
+            score: stats.score,
+            min,
+        }),
+        (_, Some(min)) if stats.comments < min as i64 => Some(ItemReason::BelowMinComments {
+            comments: stats.comments,
+            min,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_from_link() {
+        assert_eq!(
+            Source::from_link("https://news.ycombinator.com/item?id=123"),
+            Some(Source::HackerNews(
+                "https://hacker-news.firebaseio.com/v0/item/123.json".to_string()
+            ))
+        );
+        assert_eq!(
+            Source::from_link("https://old.reddit.com/r/rust/comments/abc/some_post/"),
+            Some(Source::Reddit(
+                "https://www.reddit.com/r/rust/comments/abc/some_post.json".to_string()
+            ))
+        );
+        assert_eq!(
+            Source::from_link("https://lobste.rs/s/xyz123/some_story"),
+            Some(Source::Lobsters(
+                "https://lobste.rs/s/xyz123.json".to_string()
+            ))
+        );
+        assert_eq!(Source::from_link("https://example.com/item?id=1"), None);
+        assert_eq!(Source::from_link("https://news.ycombinator.com/news"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let hn = Source::HackerNews(String::new());
+        assert_eq!(
+            hn.parse(r#"{"id": 1, "score": 120, "descendants": 45}"#),
+            Some(ItemStats {
+                score: 120,
+                comments: 45
+            })
+        );
+        assert_eq!(
+            hn.parse(r#"{"id": 1, "score": 3}"#),
+            Some(ItemStats {
+                score: 3,
+                comments: 0
+            })
+        );
+        assert_eq!(hn.parse("not json"), None);
+
+        let reddit = Source::Reddit(String::new());
+        assert_eq!(
+            reddit.parse(
+                r#"[{"data": {"children": [{"data": {"score": 10, "num_comments": 2}}]}}, {}]"#
+            ),
+            Some(ItemStats {
+                score: 10,
+                comments: 2
+            })
+        );
+
+        let lobsters = Source::Lobsters(String::new());
+        assert_eq!(
+            lobsters.parse(r#"{"score": 7, "comment_count": 1}"#),
+            Some(ItemStats {
+                score: 7,
+                comments: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_threshold_miss() {
+        let stats = ItemStats {
+            score: 100,
+            comments: 5,
+        };
+        assert_eq!(threshold_miss(&stats, None, None), None);
+        assert_eq!(threshold_miss(&stats, Some(100), Some(5)), None);
+        assert_eq!(
+            threshold_miss(&stats, Some(101), None),
+            Some(ItemReason::BelowMinScore {
+                score: 100,
+                min: 101
+            })
+        );
+        assert_eq!(
+            threshold_miss(&stats, None, Some(6)),
+            Some(ItemReason::BelowMinComments {
+                comments: 5,
+                min: 6
+            })
+        );
+    }
+}
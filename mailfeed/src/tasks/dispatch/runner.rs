@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use super::{
+    decisions::{Gate, ItemDecision, SendDecision, SendDecisions},
+    enrichment::{Enricher, ItemStats},
+    filter_items, hold, Batch, ChannelError, Channels, DeliveryChannel, Destination, SendSlots,
+    Sent,
+};
+use crate::{
+    db::DbConnection,
+    models::{
+        delivery::NewDelivery,
+        digest_skips::DigestSkips,
+        feed::Feed,
+        feed_item::FeedItem,
+        ids::SubscriptionId,
+        maintenance_mode::MaintenanceMode,
+        max_item_age::MaxItemAge,
+        retry_policy::RetryPolicy,
+        subscription::{DeliveryMethod, PartialSubscription, Subscription},
+        user::User,
+    },
+    tasks::{
+        mqtt::{Mqtt, MqttEvent},
+        types::CHECK_INTERVAL,
+        webhooks::{Event, Webhooks},
+    },
+    DbPool,
+};
+
+/// Periodically send the new items of every subscription through the
+/// channel for its delivery method. Each user's subscriptions are checked
+/// once per check, with what was decided about each kept for the schedule
+/// debug view; subscriptions wait until the user has set the channel up.
+pub async fn start(
+    pool: DbPool,
+    channels: Channels,
+    decisions: SendDecisions,
+    webhooks: Webhooks,
+    mqtt: Mqtt,
+) {
+    let channels: Vec<&dyn DeliveryChannel> = channels.iter().collect();
+    let slots = SendSlots::from_env();
+    let mut enricher = Enricher::default();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let mut conn = match pool.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("Error getting DB connection: {:?}", e);
+                continue;
+            }
+        };
+        if MaintenanceMode::load(&mut conn).enabled {
+            log::debug!("In maintenance mode, not dispatching deliveries");
+            continue;
+        }
+
+        let users = match User::get_all(&mut conn) {
+            Ok(users) => users,
+            Err(e) => {
+                log::error!("Error getting users: {:?}", e);
+                continue;
+            }
+        };
+        let retry_policies: Vec<RetryPolicy> = channels
+            .iter()
+            .map(|channel| RetryPolicy::load(&mut conn, channel.retry_channel()))
+            .collect();
+        let max_age = MaxItemAge::load(&mut conn);
+        let mut send = Send {
+            max_age: &max_age,
+            enricher: &mut enricher,
+            webhooks: &webhooks,
+            mqtt: &mqtt,
+        };
+        for user in users.into_iter().filter(|user| user.is_active) {
+            send_for_user(
+                &mut conn,
+                &channels,
+                &retry_policies,
+                &slots,
+                &decisions,
+                &mut send,
+                &user,
+            )
+            .await;
+        }
+    }
+}
+
+/// What sending needs besides the subscriptions, the same for every user
+struct Send<'a> {
+    max_age: &'a MaxItemAge,
+    enricher: &'a mut Enricher,
+    webhooks: &'a Webhooks,
+    mqtt: &'a Mqtt,
+}
+
+async fn send_for_user(
+    conn: &mut DbConnection,
+    channels: &[&dyn DeliveryChannel],
+    retry_policies: &[RetryPolicy],
+    slots: &SendSlots,
+    decisions: &SendDecisions,
+    send: &mut Send<'_>,
+    user: &User,
+) {
+    let subscriptions = match Subscription::get_all_for_user(conn, user.id) {
+        Ok(subs) => subs,
+        Err(e) => {
+            log::error!("Error getting subscriptions of user {}: {:?}", user.id, e);
+            return;
+        }
+    };
+    let now = Utc::now().timestamp();
+    let skip_today = DigestSkips::load(conn, user.id)
+        .skip_reason(user.local_date(now))
+        .is_some();
+    let mut due = Vec::new();
+    for sub in subscriptions {
+        match hold(slots, &sub, user, skip_today, now) {
+            Some(decision) => {
+                log::debug!("Not sending sub_id={}: {:?}", sub.id, decision.gate);
+                decisions.record(sub.id, decision);
+            }
+            None => due.push(sub),
+        }
+    }
+
+    for (channel, retry_policy) in channels.iter().zip(retry_policies) {
+        let subs: Vec<&Subscription> = due
+            .iter()
+            .filter(|sub| sub.delivery_method == channel.method())
+            .collect();
+        if subs.is_empty() {
+            continue;
+        }
+        let destination = match channel.destination(conn, user) {
+            Some(destination) => destination,
+            None => {
+                log::debug!(
+                    "User {} has {:?} subscriptions but they can't be sent yet",
+                    user.id,
+                    channel.method()
+                );
+                continue;
+            }
+        };
+        // failures are logged and kept in the delivery ledger
+        for outcome in
+            send_subscriptions(conn, destination.as_ref(), retry_policy, send, &subs).await
+        {
+            decisions.record(outcome.sub_id, outcome.decision);
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SendError {
+    #[error("No channel delivers {0:?} subscriptions")]
+    NoChannel(DeliveryMethod),
+    #[error("Set up {0:?} delivery first")]
+    NotSetUp(DeliveryMethod),
+    #[error("Subscription's feed not found")]
+    FeedNotFound,
+    #[error("{0}")]
+    Channel(ChannelError),
+}
+
+/// Send a subscription's new items through its channel right away, without
+/// waiting for its frequency. Returns how many items were sent.
+pub async fn send_now(
+    conn: &mut DbConnection,
+    channels: &Channels,
+    webhooks: &Webhooks,
+    mqtt: &Mqtt,
+    user: &User,
+    sub: &Subscription,
+) -> Result<usize, SendError> {
+    let method = sub.delivery_method;
+    let channel = channels.get(method).ok_or(SendError::NoChannel(method))?;
+    let destination = channel
+        .destination(conn, user)
+        .ok_or(SendError::NotSetUp(method))?;
+    let retry_policy = RetryPolicy::load(conn, channel.retry_channel());
+    let max_age = MaxItemAge::load(conn);
+    let mut send = Send {
+        max_age: &max_age,
+        enricher: &mut Enricher::default(),
+        webhooks,
+        mqtt,
+    };
+    let outcomes =
+        send_subscriptions(conn, destination.as_ref(), &retry_policy, &mut send, &[sub]).await;
+    outcomes
+        .into_iter()
+        .next()
+        .map_or(Ok(0), |outcome| outcome.result)
+}
+
+/// What came of sending one of the subscriptions
+struct Outcome {
+    sub_id: SubscriptionId,
+    decision: SendDecision,
+    /// how many items were sent
+    result: Result<usize, SendError>,
+}
+
+/// A due subscription's items that are going out
+struct Pending<'a> {
+    sub: &'a Subscription,
+    feed: Feed,
+    items: Vec<FeedItem>,
+    stats: HashMap<i32, ItemStats>,
+    decision: SendDecision,
+}
+
+/// Send the subscriptions' new items that pass their filters to the
+/// destination together, record each in the delivery ledger, and mark
+/// those that were accepted as sent
+async fn send_subscriptions(
+    conn: &mut DbConnection,
+    destination: &dyn Destination,
+    retry_policy: &RetryPolicy,
+    send: &mut Send<'_>,
+    subs: &[&Subscription],
+) -> Vec<Outcome> {
+    let now = Utc::now().timestamp();
+    let mut outcomes = Vec::new();
+    let mut pending = Vec::new();
+    for &sub in subs {
+        let mut decision = SendDecision {
+            checked_at: now,
+            gate: Gate::Due,
+            sent_after: sub.last_sent_time,
+            due_at: None,
+            items: Vec::new(),
+            sent: false,
+            error: None,
+        };
+        let feed = match Feed::get_by_id(conn, sub.feed_id) {
+            Some(feed) => feed,
+            None => {
+                log::error!("Feed {} of sub_id={} not found", sub.feed_id, sub.id);
+                decision.error = Some(SendError::FeedNotFound.to_string());
+                outcomes.push(Outcome {
+                    sub_id: sub.id,
+                    decision,
+                    result: Err(SendError::FeedNotFound),
+                });
+                continue;
+            }
+        };
+        let mut dropped = Vec::new();
+        let items = FeedItem::items_after(conn, feed.id, sub.last_sent_time);
+        let mut items = filter_items(sub, send.max_age, items, now, &mut dropped);
+        let stats = send.enricher.enrich(sub, &mut items, &mut dropped).await;
+        decision.items = items
+            .iter()
+            .map(ItemDecision::included)
+            .chain(dropped)
+            .collect();
+        if items.is_empty() {
+            log::debug!("No new items for sub_id={}", sub.id);
+            outcomes.push(Outcome {
+                sub_id: sub.id,
+                decision,
+                result: Ok(0),
+            });
+            continue;
+        }
+        pending.push(Pending {
+            sub,
+            feed,
+            items,
+            stats,
+            decision,
+        });
+    }
+    if pending.is_empty() {
+        return outcomes;
+    }
+
+    let batches: Vec<Batch> = pending
+        .iter()
+        .map(|pending| Batch {
+            sub: pending.sub,
+            feed: &pending.feed,
+            items: &pending.items,
+            stats: &pending.stats,
+            now,
+        })
+        .collect();
+    let sent = destination.send_all(conn, &batches, retry_policy).await;
+    for ((batch, sent), pending) in batches.iter().zip(sent).zip(&pending) {
+        let result = record(conn, send, batch, sent);
+        let mut decision = pending.decision.clone();
+        decision.sent = result.is_ok();
+        decision.error = result.as_ref().err().map(ToString::to_string);
+        outcomes.push(Outcome {
+            sub_id: batch.sub.id,
+            decision,
+            result,
+        });
+    }
+    outcomes
+}
+
+/// Record how sending the batch went in the delivery ledger, and let
+/// admins' webhooks and MQTT know. A batch that was accepted marks its
+/// subscription as sent. Returns how many items were sent.
+fn record(
+    conn: &mut DbConnection,
+    send: &Send<'_>,
+    batch: &Batch,
+    sent: Sent,
+) -> Result<usize, SendError> {
+    let sub = batch.sub;
+    let response = match &sent.response {
+        Ok(response) => response.clone(),
+        Err(e) => e.to_string(),
+    };
+    NewDelivery {
+        subscription_id: sub.id,
+        sent_at: batch.now,
+        recipient: &sent.recipient,
+        item_count: batch.items.len() as i32,
+        accepted: sent.response.is_ok(),
+        relay_response: &response,
+    }
+    .insert(conn);
+    if let Err(e) = sent.response {
+        // recipients may be email addresses, which are kept out of logs
+        log::error!("Error sending sub_id={}: {}", sub.id, e);
+        send.webhooks.emit(Event::TaskFailed {
+            task: "send_digest".to_string(),
+            message: format!("sub_id={}: {}", sub.id, e),
+        });
+        return Err(SendError::Channel(e));
+    }
+    log::info!(
+        "Sent {} items of sub_id={}: {}",
+        batch.items.len(),
+        sub.id,
+        response
+    );
+
+    let update = PartialSubscription {
+        last_sent_time: Some(batch.now),
+        ..Default::default()
+    };
+    Subscription::update(conn, sub.id, &update);
+    send.webhooks.emit(Event::DigestSent {
+        subscription_id: sub.id,
+        recipient: sent.recipient.clone(),
+        item_count: batch.items.len(),
+    });
+    send.mqtt.publish(MqttEvent::Delivery {
+        subscription_id: sub.id,
+        recipient: sent.recipient,
+        item_count: batch.items.len(),
+    });
+    Ok(batch.items.len())
+}
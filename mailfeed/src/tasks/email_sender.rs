@@ -1,2 +1,12 @@
+pub mod account_changes;
+pub mod channel;
+pub mod diagnostics;
+pub mod digest_templates;
+mod feed_failures;
+pub mod notification;
+pub mod onboarding;
+pub mod password_reset;
 pub mod runner;
+pub mod smtp_verification;
+pub mod subject;
 mod types;
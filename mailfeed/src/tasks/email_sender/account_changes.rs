@@ -0,0 +1,150 @@
+use thiserror::Error;
+
+use super::{
+    notification::{self, send_notification},
+    onboarding::login_url,
+};
+use crate::{
+    models::{
+        push_settings::PushSettings,
+        retry_policy::{Channel, RetryPolicy},
+        user::User,
+    },
+    tasks::push_sender::{self, send_notice},
+};
+
+const TITLE: &str = "Your MailFeed account was changed";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Email(#[from] notification::Error),
+    #[error("{0}")]
+    Push(#[from] push_sender::Error),
+}
+
+/// What changed between two versions of a user's account that they should
+/// hear about: their role, and whether they can log in at all
+pub fn account_changes(before: &User, after: &User) -> Vec<String> {
+    let mut changes = Vec::new();
+    if before.role != after.role {
+        changes.push(format!(
+            "Your role is now {} (it was {})",
+            after.role, before.role
+        ));
+    }
+    match (before.is_active, after.is_active) {
+        (true, false) => changes.push(
+            "Your account has been deactivated, so you can't log in until an administrator \
+             reactivates it"
+                .to_string(),
+        ),
+        (false, true) => changes.push("Your account has been reactivated".to_string()),
+        _ => {}
+    }
+    changes
+}
+
+/// The channel a user hears about account changes on: their push
+/// notifications if they've set them up, else email
+pub fn notice_channel(push: &PushSettings) -> Channel {
+    if push.is_configured() {
+        Channel::Push
+    } else {
+        Channel::Email
+    }
+}
+
+/// Tell a user an admin changed their account, so being locked out doesn't
+/// look like something broke. `retry_policy` is the one for their
+/// [`notice_channel`].
+pub async fn send_account_changed(
+    user: &User,
+    push: &PushSettings,
+    changes: &[String],
+    retry_policy: &RetryPolicy,
+) -> Result<(), Error> {
+    // a deactivated user isn't sent to a login page that won't let them in
+    let login_url = if user.is_active { login_url() } else { None };
+    match notice_channel(push) {
+        Channel::Push => {
+            send_notice(
+                push,
+                TITLE,
+                &changes.join("\n"),
+                login_url.as_deref(),
+                retry_policy,
+            )
+            .await?
+        }
+        _ => {
+            send_notification(
+                &user.send_email,
+                TITLE,
+                &account_changed_body(changes, login_url.as_deref()),
+                retry_policy,
+            )
+            .await?
+        }
+    }
+    Ok(())
+}
+
+fn account_changed_body(changes: &[String], login_url: Option<&str>) -> String {
+    let mut body = "An administrator changed your MailFeed account:\n\n".to_string();
+    for change in changes {
+        body.push_str(&format!("- {}\n", change));
+    }
+    if let Some(url) = login_url {
+        body.push_str(&format!("\nLog in at {}\n", url));
+    }
+    body.push_str("\nIf you weren't expecting this, contact your administrator.\n");
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{push_settings::PushService, role::Role},
+        test_helpers::test_helpers::test_user,
+    };
+
+    fn user(role: Role, is_active: bool) -> User {
+        User {
+            role: role.into(),
+            is_active,
+            ..test_user()
+        }
+    }
+
+    #[test]
+    fn test_account_changes() {
+        let before = user(Role::User, true);
+        assert!(account_changes(&before, &before).is_empty());
+
+        let after = user(Role::Admin, false);
+        let changes = account_changes(&before, &after);
+        assert_eq!(changes.len(), 2);
+        assert!(changes[0].contains("now admin (it was user)"));
+        assert!(changes[1].contains("deactivated"));
+        assert!(account_changes(&after, &before)[1].contains("reactivated"));
+
+        let body = account_changed_body(&changes, None);
+        assert!(body.contains("- Your role is now admin"));
+        assert!(!body.contains("Log in"));
+        let body = account_changed_body(&changes, Some("https://feeds.example.com/"));
+        assert!(body.contains("Log in at https://feeds.example.com/"));
+    }
+
+    #[test]
+    fn test_notice_channel() {
+        assert_eq!(notice_channel(&PushSettings::default()), Channel::Email);
+        let push = PushSettings {
+            service: Some(PushService::Ntfy),
+            topic: "mailfeed-testy".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(notice_channel(&push), Channel::Push);
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use futures_util::{future::BoxFuture, FutureExt};
+
+use super::{
+    runner::{self, Mailer},
+    types::EmailServerCfg,
+};
+use crate::{
+    db::DbConnection,
+    models::{
+        retry_policy::{Channel, RetryPolicy},
+        smtp_verification::SmtpVerification,
+        subscription::DeliveryMethod,
+        user::User,
+    },
+    tasks::dispatch::{Batch, ChannelError, DeliveryChannel, Destination, Sent},
+};
+
+/// Subscriptions' new items as email digests, sent through the SMTP relay
+/// in the environment. Subscriptions sharing a combined digest tag go out
+/// in one email, and the user's weekly trends report rides along with one
+/// digest a week.
+pub struct EmailChannel {
+    /// None if the SMTP settings are missing or invalid
+    mailer: Option<Arc<Mailer>>,
+}
+
+impl EmailChannel {
+    pub fn from_env() -> Self {
+        let cfg = match EmailServerCfg::from_env() {
+            Some(cfg) => cfg,
+            None => {
+                log::error!("Missing or invalid SMTP settings, not sending emails");
+                return EmailChannel { mailer: None };
+            }
+        };
+        let mailer = match cfg.to_transport() {
+            Ok(sender) => Some(Arc::new(Mailer { cfg, sender })),
+            Err(e) => {
+                log::error!("Error creating email sender: {:?}", e);
+                None
+            }
+        };
+        EmailChannel { mailer }
+    }
+}
+
+/// Why no emails can be sent for now, if they can't
+pub fn unavailable(conn: &mut DbConnection) -> Option<&'static str> {
+    let cfg = match EmailServerCfg::from_env() {
+        Some(cfg) => cfg,
+        None => return Some("Email sending is not configured"),
+    };
+    if SmtpVerification::load(conn).holds(&cfg.fingerprint()) {
+        return Some("Emails are held until the new SMTP settings are verified");
+    }
+    None
+}
+
+impl DeliveryChannel for EmailChannel {
+    fn method(&self) -> DeliveryMethod {
+        DeliveryMethod::Email
+    }
+
+    fn retry_channel(&self) -> Channel {
+        Channel::Email
+    }
+
+    /// None while SMTP isn't configured, or new settings wait to be
+    /// verified
+    fn destination(&self, conn: &mut DbConnection, user: &User) -> Option<Box<dyn Destination>> {
+        let mailer = self.mailer.as_ref()?;
+        if SmtpVerification::load(conn).holds(&mailer.cfg.fingerprint()) {
+            return None;
+        }
+        Some(Box::new(EmailDestination {
+            mailer: mailer.clone(),
+            user: user.clone(),
+        }))
+    }
+
+    fn preview(&self, user: &User, batch: &Batch) -> Vec<String> {
+        vec![runner::preview(user, batch).text]
+    }
+}
+
+struct EmailDestination {
+    mailer: Arc<Mailer>,
+    user: User,
+}
+
+impl Destination for EmailDestination {
+    fn recipient(&self) -> &str {
+        &self.user.send_email
+    }
+
+    fn send<'a>(
+        &'a self,
+        batch: &'a Batch<'a>,
+        retry_policy: &'a RetryPolicy,
+    ) -> BoxFuture<'a, Result<(), ChannelError>> {
+        async move {
+            runner::send_single(&self.mailer, retry_policy, &self.user, batch).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn send_all<'a>(
+        &'a self,
+        conn: &'a mut DbConnection,
+        batches: &'a [Batch<'a>],
+        retry_policy: &'a RetryPolicy,
+    ) -> BoxFuture<'a, Vec<Sent>> {
+        runner::send_digests(conn, &self.mailer, retry_policy, &self.user, batches).boxed()
+    }
+}
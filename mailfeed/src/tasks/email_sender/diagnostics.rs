@@ -0,0 +1,385 @@
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+
+use super::types::EmailServerCfg;
+use crate::{
+    db::DbConnection,
+    models::{
+        delivery::Delivery,
+        digest_skips::{DigestSkips, SkipReason},
+        feed::Feed,
+        feed_item::FeedItem,
+        ids::SubscriptionId,
+        quotas::Quotas,
+        subscription::{Frequency, Subscription},
+        user::User,
+    },
+    tasks::dispatch::SendSlots,
+};
+
+#[derive(Debug, Serialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Warning,
+    Problem,
+}
+
+/// One thing that affects whether the user gets their email, and what it
+/// looks like for them right now
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionCheck {
+    pub subscription_id: SubscriptionId,
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+    pub frequency: Frequency,
+    pub last_sent_time: i64,
+    /// None for realtime subscriptions, which are sent whenever there's
+    /// something new
+    pub next_send_time: Option<i64>,
+    pub pending_items: usize,
+}
+
+/// Answers "why didn't I get my email?" by walking through everything the
+/// email sender looks at for the user
+#[derive(Debug, Serialize)]
+pub struct Diagnostics {
+    /// the worst status of any check
+    pub status: Status,
+    pub checks: Vec<Check>,
+    pub subscriptions: Vec<SubscriptionCheck>,
+}
+
+pub fn diagnose(conn: &mut DbConnection, user: &User) -> Diagnostics {
+    let now = Utc::now().timestamp();
+    let subscriptions = Subscription::get_all_for_user(conn, user.id).unwrap_or_default();
+    let sub_ids: Vec<SubscriptionId> = subscriptions.iter().map(|sub| sub.id).collect();
+
+    let mut checks = vec![email_config_check(), account_check(user)];
+    checks.push(match Delivery::last_accepted(conn, &sub_ids) {
+        Ok(Some(delivery)) => Check {
+            name: "last_successful_send",
+            status: Status::Ok,
+            detail: format!(
+                "Last email accepted by the mail server at {}, sent to {}",
+                format_time(delivery.sent_at),
+                delivery.recipient
+            ),
+        },
+        _ => Check {
+            name: "last_successful_send",
+            status: Status::Warning,
+            detail: "No emails have been sent yet".to_string(),
+        },
+    });
+    checks.push(quota_check(conn, user));
+    checks.push(skip_check(&DigestSkips::load(conn, user.id), user, now));
+
+    let slots = SendSlots::from_env();
+    let subscriptions: Vec<SubscriptionCheck> = subscriptions
+        .iter()
+        .filter_map(|sub| {
+            let feed = Feed::get_by_id(conn, sub.feed_id)?;
+            let pending = FeedItem::items_after(conn, feed.id, sub.last_sent_time).len();
+            let last_delivery = Delivery::latest_for_subscription(conn, sub.id)
+                .ok()
+                .flatten();
+            let next_send_time = match sub.frequency {
+                Frequency::Realtime => None,
+                _ => Some(slots.next_send_time(sub, user)),
+            };
+            Some(subscription_check(
+                sub,
+                &feed,
+                pending,
+                last_delivery.as_ref(),
+                next_send_time,
+                now,
+            ))
+        })
+        .collect();
+
+    let status = checks
+        .iter()
+        .map(|check| check.status)
+        .chain(subscriptions.iter().map(|sub| sub.status))
+        .max()
+        .unwrap_or(Status::Ok);
+    Diagnostics {
+        status,
+        checks,
+        subscriptions,
+    }
+}
+
+fn skip_check(skips: &DigestSkips, user: &User, now: i64) -> Check {
+    let (status, detail) = match skips.skip_reason(user.local_date(now)) {
+        Some(reason) => {
+            let why = match reason {
+                SkipReason::Weekend => "it's the weekend",
+                SkipReason::SkipDate => "it's one of your skip dates",
+            };
+            (
+                Status::Ok,
+                format!(
+                    "No digests are sent today since {}. New items will be in the next one",
+                    why
+                ),
+            )
+        }
+        None if skips.skip_weekends || !skips.skip_dates.is_empty() => (
+            Status::Ok,
+            format!(
+                "Digests are sent today. They're skipped on {}",
+                match (skips.skip_weekends, skips.skip_dates.len()) {
+                    (true, 0) => "weekends".to_string(),
+                    (true, dates) => format!("weekends and {} other dates", dates),
+                    (false, dates) => format!("{} dates", dates),
+                }
+            ),
+        ),
+        None => (Status::Ok, "Digests are sent every day".to_string()),
+    };
+    Check {
+        name: "skip_days",
+        status,
+        detail,
+    }
+}
+
+fn email_config_check() -> Check {
+    match EmailServerCfg::from_env() {
+        Some(cfg) => Check {
+            name: "email_config",
+            status: Status::Ok,
+            detail: format!("Emails are sent from {} via {}", cfg.from_email, cfg.host),
+        },
+        None => Check {
+            name: "email_config",
+            status: Status::Problem,
+            detail: "The server's SMTP settings are missing or invalid, so no emails can be sent. \
+                     Ask your administrator to check them."
+                .to_string(),
+        },
+    }
+}
+
+fn account_check(user: &User) -> Check {
+    if user.is_active {
+        Check {
+            name: "account",
+            status: Status::Ok,
+            detail: format!("Your account is active, emails go to {}", user.send_email),
+        }
+    } else {
+        Check {
+            name: "account",
+            status: Status::Problem,
+            detail: "Your account is inactive, so no emails are sent to you".to_string(),
+        }
+    }
+}
+
+fn quota_check(conn: &mut DbConnection, user: &User) -> Check {
+    let quotas = Quotas::load(conn);
+    let count = Subscription::count_for_user(conn, user.id).unwrap_or(0);
+    let realtime = Subscription::count_realtime_for_user(conn, user.id).unwrap_or(0);
+    let full =
+        |limit: Option<u32>, count: i64| matches!(limit, Some(limit) if count >= limit as i64);
+
+    let mut reached = Vec::new();
+    if full(quotas.max_subscriptions_per_user, count) {
+        reached.push(format!("{} subscriptions", count));
+    }
+    if full(quotas.max_realtime_subscriptions_per_user, realtime) {
+        reached.push(format!("{} realtime subscriptions", realtime));
+    }
+    if reached.is_empty() {
+        Check {
+            name: "quotas",
+            status: Status::Ok,
+            detail: "You're within this server's subscription limits".to_string(),
+        }
+    } else {
+        Check {
+            name: "quotas",
+            status: Status::Warning,
+            detail: format!(
+                "You've reached this server's limit of {}, so you can't add more",
+                reached.join(" and ")
+            ),
+        }
+    }
+}
+
+fn subscription_check(
+    sub: &Subscription,
+    feed: &Feed,
+    pending_items: usize,
+    last_delivery: Option<&Delivery>,
+    next_send_time: Option<i64>,
+    now: i64,
+) -> SubscriptionCheck {
+    // realtime subscriptions have no next send time and are always due
+    let due = sub.is_active && next_send_time.is_none_or(|time| now >= time);
+    let (status, detail) = if !sub.is_active {
+        (
+            Status::Warning,
+            "Subscription is inactive, so nothing is sent for it".to_string(),
+        )
+    } else if let Some(since) = feed.failing_since() {
+        (
+            Status::Problem,
+            format!(
+                "The feed hasn't been fetched successfully since {}: {}",
+                format_time(since),
+                feed.error_message.as_deref().unwrap_or("unknown error")
+            ),
+        )
+    } else if let Some(delivery) = last_delivery.filter(|delivery| !delivery.accepted) {
+        (
+            Status::Problem,
+            format!(
+                "The last email, at {}, wasn't accepted by the mail server: {}",
+                format_time(delivery.sent_at),
+                delivery.relay_response
+            ),
+        )
+    } else if feed.skipped_items > 0 {
+        (
+            Status::Warning,
+            format!(
+                "The feed's last fetch had {} more items than can be taken at once, so the oldest were skipped",
+                feed.skipped_items
+            ),
+        )
+    } else if pending_items == 0 {
+        (Status::Ok, "No new items since the last email".to_string())
+    } else if let Some(window) = sub.delivery_window().filter(|_| due) {
+        (
+            Status::Ok,
+            format!(
+                "{} new items will be sent during the delivery window, {}",
+                pending_items, window
+            ),
+        )
+    } else if due {
+        (
+            Status::Ok,
+            format!(
+                "{} new items will be sent within a few minutes",
+                pending_items
+            ),
+        )
+    } else {
+        (
+            Status::Ok,
+            format!(
+                "{} new items will be sent after {} ({})",
+                pending_items,
+                format_time(next_send_time.unwrap_or(now)),
+                sub.frequency
+            ),
+        )
+    };
+
+    SubscriptionCheck {
+        subscription_id: sub.id,
+        name: sub.display_name(feed).to_string(),
+        status,
+        detail,
+        frequency: sub.frequency.clone(),
+        last_sent_time: sub.last_sent_time,
+        next_send_time,
+        pending_items,
+    }
+}
+
+fn format_time(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::feed::NewFeed;
+    use crate::models::ids::UserId;
+    use crate::models::subscription::NewSubscription;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_subscription_check() {
+        let mut conn = get_test_db_connection();
+        let mut feed = NewFeed {
+            url: "https://example.com/feed.xml",
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let mut sub = NewSubscription {
+            user_id: UserId(1),
+            feed_id: feed.id,
+            frequency: Frequency::Hourly,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        sub.last_sent_time = 1000;
+        // the next hourly slot
+        let next = Some(3600);
+
+        let check = subscription_check(&sub, &feed, 0, None, next, 2000);
+        assert_eq!(check.status, Status::Ok);
+        assert_eq!(check.next_send_time, next);
+
+        let check = subscription_check(&sub, &feed, 2, None, next, 2000);
+        assert!(check.detail.starts_with("2 new items will be sent after"));
+        let check = subscription_check(&sub, &feed, 2, None, next, 5000);
+        assert!(check.detail.contains("within a few minutes"));
+        sub.delivery_window = Some("07:00-09:00".to_string());
+        let check = subscription_check(&sub, &feed, 2, None, next, 5000);
+        assert!(check
+            .detail
+            .ends_with("during the delivery window, 07:00-09:00"));
+        sub.delivery_window = None;
+
+        let rejected = Delivery {
+            id: 1,
+            subscription_id: sub.id,
+            sent_at: 1000,
+            recipient: "test@example.com".to_string(),
+            item_count: 2,
+            accepted: false,
+            relay_response: "550 5.1.1 No such user".to_string(),
+        };
+        let check = subscription_check(&sub, &feed, 2, Some(&rejected), next, 5000);
+        assert_eq!(check.status, Status::Problem);
+        assert!(check.detail.ends_with("550 5.1.1 No such user"));
+
+        feed.skipped_items = 30;
+        let check = subscription_check(&sub, &feed, 2, None, next, 5000);
+        assert_eq!(check.status, Status::Warning);
+        assert!(check.detail.contains("30 more items"));
+
+        feed.error_time = 1500;
+        feed.error_message = Some("HTTP 404".to_string());
+        let check = subscription_check(&sub, &feed, 2, None, next, 5000);
+        assert_eq!(check.status, Status::Problem);
+        assert!(check.detail.ends_with("HTTP 404"));
+
+        sub.is_active = false;
+        let check = subscription_check(&sub, &feed, 2, None, next, 5000);
+        assert_eq!(check.status, Status::Warning);
+    }
+}
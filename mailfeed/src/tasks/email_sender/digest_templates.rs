@@ -0,0 +1,110 @@
+use askama::Template;
+use serde::{Deserialize, Serialize};
+
+use crate::models::trends::Trends;
+
+/// A digest ready for the HTML part, worked out once so every version of
+/// the template renders exactly the same thing
+pub struct DigestView<'a> {
+    pub sections: Vec<SectionView>,
+    pub trends: Option<&'a Trends>,
+}
+
+/// One subscription's items
+pub struct SectionView {
+    pub title: String,
+    pub link: String,
+    /// HTML, from the feed or the subscription
+    pub description: Option<String>,
+    pub items: Vec<ItemView>,
+}
+
+pub struct ItemView {
+    pub title: String,
+    pub link: String,
+    pub comments: Option<String>,
+    pub stats: Option<String>,
+    /// HTML, cut down to text if it was over the user's limit
+    pub description: String,
+    pub date: String,
+    pub author: String,
+    /// None without `MF_PUBLIC_URL`, since the links need to be absolute
+    pub actions: Option<ItemActions>,
+}
+
+/// The signed links under an item that mark it read or star it
+pub struct ItemActions {
+    pub read: String,
+    pub star: String,
+}
+
+#[derive(Template)]
+#[template(path = "digest.html")]
+struct Current<'a> {
+    digest: &'a DigestView<'a>,
+}
+
+#[derive(Template)]
+#[template(path = "digest_next.html")]
+struct Next<'a> {
+    digest: &'a DigestView<'a>,
+}
+
+/// The versions of the digest's HTML, by their file name under `templates/`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum DigestTemplate {
+    /// what digests are sent with
+    #[serde(rename = "digest.html")]
+    Current,
+    /// changes being worked on, to compare with the current version
+    #[serde(rename = "digest_next.html")]
+    Next,
+}
+
+impl DigestTemplate {
+    pub const ALL: [DigestTemplate; 2] = [DigestTemplate::Current, DigestTemplate::Next];
+
+    pub fn render(&self, digest: &DigestView) -> askama::Result<String> {
+        match self {
+            DigestTemplate::Current => Current { digest }.render(),
+            DigestTemplate::Next => Next { digest }.render(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let digest = DigestView {
+            sections: vec![SectionView {
+                title: "Feed <1>".to_string(),
+                link: "https://example.com/".to_string(),
+                description: None,
+                items: vec![ItemView {
+                    title: "Tom & Jerry".to_string(),
+                    link: "https://example.com/1?a=1&b=2".to_string(),
+                    comments: None,
+                    stats: Some("10 points, 2 comments".to_string()),
+                    description: "<b>Bold</b>".to_string(),
+                    date: "2026-10-16 12:00:00".to_string(),
+                    author: "Ann".to_string(),
+                    actions: None,
+                }],
+            }],
+            trends: None,
+        };
+        for template in DigestTemplate::ALL {
+            let html = template.render(&digest).unwrap();
+            assert!(html.contains("<h2>Feed &lt;1&gt;</h2>"));
+            assert!(
+                html.contains("<a href='https://example.com/1?a=1&amp;b=2'>Tom &amp; Jerry</a>")
+            );
+            assert!(html.contains("<p><b>Bold</b></p>"));
+            assert!(html.contains("<time>2026-10-16 12:00:00</time>"));
+            assert!(!html.contains("Mark as read"));
+        }
+    }
+}
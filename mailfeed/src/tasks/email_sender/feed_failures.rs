@@ -0,0 +1,102 @@
+use std::env;
+
+use chrono::{TimeZone, Utc};
+
+use super::notification::send_notification;
+use crate::db::DbConnection;
+use crate::models::{
+    feed::Feed,
+    retry_policy::RetryPolicy,
+    subscription::{PartialSubscription, Subscription},
+    user::User,
+};
+
+const DEFAULT_NOTICE_AFTER_DAYS: i64 = 3;
+const DAY: i64 = 24 * 60 * 60;
+
+/// How many seconds a feed must have been failing before its subscribers
+/// are told, from `MF_FEED_FAILURE_NOTICE_DAYS`. None if notices are off.
+pub(super) fn notice_after_from_env() -> Option<i64> {
+    let days = match env::var("MF_FEED_FAILURE_NOTICE_DAYS") {
+        Ok(value) => match value.parse::<i64>() {
+            Ok(days) if days >= 0 => days,
+            _ => {
+                log::warn!(
+                    "Invalid MF_FEED_FAILURE_NOTICE_DAYS '{}', using default of {}",
+                    value,
+                    DEFAULT_NOTICE_AFTER_DAYS
+                );
+                DEFAULT_NOTICE_AFTER_DAYS
+            }
+        },
+        Err(_) => DEFAULT_NOTICE_AFTER_DAYS,
+    };
+    match days {
+        0 => None,
+        days => Some(days * DAY),
+    }
+}
+
+/// Email the user about each of their active subscriptions whose feed has
+/// been failing for at least `after` seconds, rather than letting it go
+/// quiet with no explanation. Each run of failures is only reported once.
+pub(super) async fn notify_failing_feeds(
+    conn: &mut DbConnection,
+    user: &User,
+    retry_policy: &RetryPolicy,
+    after: i64,
+) {
+    let subscriptions = match Subscription::get_all_for_user(conn, user.id) {
+        Ok(subscriptions) => subscriptions,
+        Err(_) => return,
+    };
+    let now = Utc::now().timestamp();
+    for sub in subscriptions.iter().filter(|sub| sub.is_active) {
+        let feed = match Feed::get_by_id(conn, sub.feed_id) {
+            Some(feed) => feed,
+            None => continue,
+        };
+        if !sub.needs_failure_notice(&feed, now, after) {
+            continue;
+        }
+
+        let name = sub.display_name(&feed);
+        let subject = format!("MailFeed: {} is failing", name);
+        let sent = send_notification(
+            sub.destination(user),
+            &subject,
+            &notice_body(name, &feed),
+            retry_policy,
+        )
+        .await;
+        if let Err(e) = sent {
+            log::error!("Error sending failure notice for sub_id={}: {}", sub.id, e);
+            continue;
+        }
+        log::info!("Sent failure notice for sub_id={}", sub.id);
+
+        let update = PartialSubscription {
+            feed_failure_notified_at: Some(now),
+            ..Default::default()
+        };
+        Subscription::update(conn, sub.id, &update);
+    }
+}
+
+fn notice_body(name: &str, feed: &Feed) -> String {
+    let since = feed
+        .failing_since()
+        .and_then(|since| Utc.timestamp_opt(since, 0).single())
+        .map(|since| since.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    format!(
+        "MailFeed hasn't been able to fetch {} ({}) since {}.\n\n\
+         The last error was: {}\n\n\
+         You won't get new items from it until it recovers. If the feed has moved or \
+         shut down, you may want to update or remove the subscription.\n",
+        name,
+        feed.url,
+        since,
+        feed.error_message.as_deref().unwrap_or("unknown")
+    )
+}
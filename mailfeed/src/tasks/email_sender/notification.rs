@@ -0,0 +1,51 @@
+use super::types::{EmailServerCfg, ToEmail};
+use crate::{models::retry_policy::RetryPolicy, tasks::retry::with_retries};
+use lettre::{message::header::ContentType, Message, Transport};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("SMTP settings are missing or invalid")]
+    NotConfigured,
+    #[error("failed to build email: {0}")]
+    Build(String),
+    #[error("failed to send email: {0}")]
+    Send(String),
+}
+
+/// Send a one-off plain text email (account notices and the like),
+/// outside of the regular digest schedule
+pub async fn send_notification(
+    to_email: ToEmail<'_>,
+    subject: &str,
+    body: &str,
+    retry_policy: &RetryPolicy,
+) -> Result<(), Error> {
+    let cfg = EmailServerCfg::from_env().ok_or(Error::NotConfigured)?;
+    let sender = cfg.to_transport().map_err(|e| Error::Send(e.to_string()))?;
+
+    let from = cfg
+        .from_email
+        .parse()
+        .map_err(|e: lettre::address::AddressError| Error::Build(e.to_string()))?;
+    let to = to_email
+        .parse()
+        .map_err(|e: lettre::address::AddressError| Error::Build(e.to_string()))?;
+    let message = Message::builder()
+        .from(from)
+        .to(to)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body.to_string())
+        .map_err(|e| Error::Build(e.to_string()))?;
+
+    with_retries(
+        retry_policy,
+        &format!("send notification to {}", to_email),
+        || sender.send(&message),
+        |e| !e.is_permanent(),
+    )
+    .await
+    .map(|_| ())
+    .map_err(|e| Error::Send(e.to_string()))
+}
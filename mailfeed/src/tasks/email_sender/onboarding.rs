@@ -0,0 +1,103 @@
+use std::env;
+
+use super::notification::{send_notification, Error};
+use crate::models::{retry_policy::RetryPolicy, user::User};
+
+/// The instance's address, from `MF_PUBLIC_URL`, without a trailing slash.
+/// None if it isn't set, since the server can't tell what address it's
+/// reached at from behind a proxy.
+pub(super) fn public_url() -> Option<String> {
+    env::var("MF_PUBLIC_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+        .map(|url| url.trim_end_matches('/').to_string())
+}
+
+/// Where users log in
+pub(super) fn login_url() -> Option<String> {
+    public_url().map(|url| format!("{}/", url))
+}
+
+/// Greet a user an admin has just created
+pub async fn send_welcome(user: &User, retry_policy: &RetryPolicy) -> Result<(), Error> {
+    send_notification(
+        &user.send_email,
+        "Welcome to MailFeed",
+        &welcome_body(&user.login_email, login_url().as_deref()),
+        retry_policy,
+    )
+    .await
+}
+
+/// Tell a user who signed up that an admin approved their account
+pub async fn send_approved(user: &User, retry_policy: &RetryPolicy) -> Result<(), Error> {
+    send_notification(
+        &user.send_email,
+        "Your MailFeed account is ready",
+        &approved_body(&user.login_email, login_url().as_deref()),
+        retry_policy,
+    )
+    .await
+}
+
+/// Check the user's delivery settings work, without waiting for a digest
+pub async fn send_test(user: &User, retry_policy: &RetryPolicy) -> Result<(), Error> {
+    let body = format!(
+        "This is a test email from MailFeed.\n\n\
+         Your digests will be sent to {} from this address. If this landed in your spam \
+         folder, mark it as not spam or add the sender to your contacts so they don't.\n",
+        user.send_email
+    );
+    send_notification(&user.send_email, "MailFeed test email", &body, retry_policy).await
+}
+
+fn log_in(login_url: Option<&str>) -> String {
+    match login_url {
+        Some(url) => format!("Log in at {}", url),
+        None => "Log in".to_string(),
+    }
+}
+
+fn welcome_body(login_email: &str, login_url: Option<&str>) -> String {
+    let log_in = log_in(login_url);
+    format!(
+        "An account has been created for you on MailFeed, which sends your RSS and Atom \
+         feeds to your inbox.\n\n\
+         {} with {} and the password your administrator gave you. \
+         The dashboard will walk you through getting started:\n\n\
+         - Add a feed\n\
+         - Choose where and when your emails are sent\n\
+         - Send yourself a test email\n",
+        log_in, login_email
+    )
+}
+
+fn approved_body(login_email: &str, login_url: Option<&str>) -> String {
+    format!(
+        "An administrator has approved your MailFeed account.\n\n\
+         {} with {} and the password you signed up with. \
+         The dashboard will walk you through getting started.\n",
+        log_in(login_url),
+        login_email
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_welcome_body() {
+        let body = welcome_body("new@example.com", Some("https://feeds.example.com/"));
+        assert!(body.contains("Log in at https://feeds.example.com/ with new@example.com"));
+        let body = welcome_body("new@example.com", None);
+        assert!(body.contains("Log in with new@example.com"));
+    }
+
+    #[test]
+    fn test_approved_body() {
+        let body = approved_body("new@example.com", Some("https://feeds.example.com/"));
+        assert!(body.contains("Log in at https://feeds.example.com/ with new@example.com"));
+        assert!(body.contains("the password you signed up with"));
+    }
+}
@@ -0,0 +1,92 @@
+use super::{
+    notification::{send_notification, Error},
+    onboarding::login_url,
+};
+use crate::models::{
+    password_reset_token::TOKEN_LIFETIME_SECONDS, retry_policy::RetryPolicy, user::User,
+};
+
+/// Email the user a link to set a new password. Goes to the login email,
+/// since that's the address the account belongs to.
+pub async fn send_reset(user: &User, token: &str, retry_policy: &RetryPolicy) -> Result<(), Error> {
+    send_notification(
+        &user.login_email,
+        "Reset your MailFeed password",
+        &reset_body(token, login_url().as_deref()),
+        retry_policy,
+    )
+    .await
+}
+
+/// Email the user a link to set a new password after an admin reset it.
+/// Their old password already doesn't work, so unlike [`send_reset`] this
+/// isn't something they can ignore.
+pub async fn send_forced_reset(
+    user: &User,
+    token: &str,
+    retry_policy: &RetryPolicy,
+) -> Result<(), Error> {
+    send_notification(
+        &user.login_email,
+        "Your MailFeed password was reset",
+        &forced_reset_body(token, login_url().as_deref()),
+        retry_policy,
+    )
+    .await
+}
+
+fn reset_body(token: &str, login_url: Option<&str>) -> String {
+    format!(
+        "Someone asked to reset the password of your MailFeed account. {}\n\n\
+         This works once, for the next {} minutes. If you didn't ask for this, you can \
+         ignore this email and your password won't change.\n",
+        how_to_reset(token, login_url),
+        TOKEN_LIFETIME_SECONDS / 60
+    )
+}
+
+fn forced_reset_body(token: &str, login_url: Option<&str>) -> String {
+    format!(
+        "An administrator has reset the password of your MailFeed account, so your old \
+         password no longer works. {}\n\n\
+         This works once, for the next {} minutes. After that, use \"Forgot password\" on \
+         the login page to be sent another.\n",
+        how_to_reset(token, login_url),
+        TOKEN_LIFETIME_SECONDS / 60
+    )
+}
+
+fn how_to_reset(token: &str, login_url: Option<&str>) -> String {
+    match login_url {
+        Some(url) => format!(
+            "Set a new password at:\n\n{}reset-password?token={}",
+            url, token
+        ),
+        None => format!(
+            "Set a new password on the MailFeed reset page with this code:\n\n{}",
+            token
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reset_body() {
+        let body = reset_body("abc123", Some("https://feeds.example.com/"));
+        assert!(body.contains("https://feeds.example.com/reset-password?token=abc123"));
+        assert!(body.contains("next 60 minutes"));
+        let body = reset_body("abc123", None);
+        assert!(body.contains("with this code:\n\nabc123"));
+    }
+
+    #[test]
+    fn test_forced_reset_body() {
+        let body = forced_reset_body("abc123", Some("https://feeds.example.com/"));
+        assert!(body.contains("An administrator has reset"));
+        assert!(body.contains("https://feeds.example.com/reset-password?token=abc123"));
+        assert!(!body.contains("ignore this email"));
+    }
+}
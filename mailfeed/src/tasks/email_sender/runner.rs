@@ -1,35 +1,92 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use super::digest_templates::{DigestTemplate, DigestView, ItemActions, ItemView, SectionView};
+use super::feed_failures::{notice_after_from_env, notify_failing_feeds};
+use super::onboarding::public_url;
+use super::smtp_verification;
+use super::subject::{self, SubjectVars};
 use super::types::{
-    EmailData, EmailServerCfg, FeedData, FromEmail, MultiPartEmailContent, ToEmail,
+    Digest, EmailServerCfg, FeedData, FromEmail, MultiPartEmailContent, ToEmail, DEFAULT_SUBJECT,
 };
 use crate::{
+    db::DbConnection,
     models::{
         feed::Feed,
         feed_item::FeedItem,
-        subscription::{Frequency, PartialSubscription, Subscription},
+        ids::{SubscriptionId, TagId, UserId},
+        maintenance_mode::MaintenanceMode,
+        retry_policy::{Channel, RetryPolicy},
+        smtp_verification::SmtpVerification,
+        subscription::Subscription,
+        tag::Tag,
+        trends::{TrendSettings, Trends},
         user::User,
     },
-    tasks::types::CHECK_INTERVAL,
+    security::{
+        item_links::{ItemAction, ItemLinks},
+        redact,
+    },
+    tasks::{
+        dispatch::{enrichment::ItemStats, Batch, ChannelError, Sent},
+        html_to_text::html_to_text_truncated,
+        retry::with_retries,
+        types::CHECK_INTERVAL,
+    },
     DbPool,
 };
 use chrono::{TimeZone, Utc};
-use diesel::SqliteConnection;
 use lettre::{
     error::Error,
-    message::{header::ContentType, MultiPart, SinglePart},
-    Message, Transport,
+    message::{header::ContentType, Mailbox, MultiPart, SinglePart},
+    transport::smtp::response::Response,
+    Message, SmtpTransport, Transport,
 };
+use serde::Serialize;
+
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum DeliveryError {
+    #[error("Error constructing email: {0}")]
+    Build(String),
+    #[error("Error sending email: {0}")]
+    Send(String),
+}
+
+/// A digest rendered like it would be sent, without sending it
+#[derive(Debug, Serialize)]
+pub struct EmailPreview {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// The SMTP settings from the environment, and a transport through them
+pub struct Mailer {
+    pub cfg: EmailServerCfg,
+    pub sender: SmtpTransport,
+}
 
+/// Have new SMTP settings verified, then periodically tell users about
+/// their failing feeds. Digests are sent by the dispatcher, through the
+/// email channel.
 pub async fn start(pool: DbPool) {
-    let cfg = EmailServerCfg::from_env();
-    // return early if we can't create the sender
-    let sender = match cfg.to_transport() {
-        Ok(sender) => sender,
-        Err(e) => {
-            log::error!("Error creating email sender: {:?}", e);
-            return;
-        }
+    // the email channel has already said why
+    let cfg = match EmailServerCfg::from_env() {
+        Some(cfg) => cfg,
+        None => return,
     };
+    if let Err(e) = subject::validate(&cfg.email_subject) {
+        log::warn!("Invalid MF_EMAIL_SUBJECT '{}': {}", cfg.email_subject, e);
+    }
+    match pool.get() {
+        Ok(mut conn) => smtp_verification::check(&mut conn, &cfg).await,
+        Err(e) => log::error!("Error getting DB connection: {:?}", e),
+    }
+    let fingerprint = cfg.fingerprint();
 
+    let failure_notice_after = match notice_after_from_env() {
+        Some(after) => after,
+        None => return,
+    };
     let mut interval = tokio::time::interval(CHECK_INTERVAL);
     loop {
         interval.tick().await;
@@ -40,110 +97,335 @@ pub async fn start(pool: DbPool) {
                 continue;
             }
         };
+        if MaintenanceMode::load(&mut conn).enabled {
+            log::debug!("In maintenance mode, not sending feed failure notices");
+            continue;
+        }
+        if SmtpVerification::load(&mut conn).holds(&fingerprint) {
+            log::debug!("New SMTP settings haven't been verified, not sending emails");
+            continue;
+        }
 
         let users = User::get_all(&mut conn);
         // unwrap and get active users
         let users = users.into_iter().flatten().filter(|user| user.is_active);
-
+        let retry_policy = RetryPolicy::load(&mut conn, Channel::Email);
         for user in users {
-            let email_data = items_to_send_by_user(&mut conn, user.id);
-            for feed_data in &email_data.feed_data {
-                if feed_data.new_items.is_empty() {
-                    log::debug!("No new items for sub_id={}", feed_data.sub_id);
-                    continue;
-                }
-                let as_plain = to_plain_email(feed_data);
-                let as_html = to_html_email(feed_data);
-                let content = MultiPartEmailContent {
-                    as_plain: &as_plain,
-                    as_html: &as_html,
-                };
-
-                let subject = &cfg.email_subject
-                    .replace("{feed_title}", &feed_data.feed_title)
-                    .replace("{feed_link}", &feed_data.feed_link)
-                    .replace("{sub_id}", &feed_data.sub_id.to_string())
-                    .replace("{new_items_count}", &feed_data.new_items.len().to_string());
-                let message = construct_email(subject, &user.send_email, &cfg.from_email, content);
-                let message = match message {
-                    Ok(message) => message,
-                    Err(e) => {
-                        log::error!("Error constructing email: {:?}", e);
-                        continue;
-                    }
-                };
-                let email_result = sender.send(&message);
-                match email_result {
-                    Ok(_) => {
-                        log::info!(
-                            "Email sent to {} for sub_id={}",
-                            user.send_email,
-                            feed_data.sub_id
-                        );
-                    }
-                    Err(e) => {
-                        log::error!("Error sending email: {:?}", e);
-                        continue;
+            notify_failing_feeds(&mut conn, &user, &retry_policy, failure_notice_after).await;
+        }
+    }
+}
+
+/// Send the user's batches as digests, one per subscription except those
+/// sharing a combined digest tag, and say how each batch went, in the same
+/// order. The user's weekly trends report goes with the first digest sent.
+pub async fn send_digests(
+    conn: &mut DbConnection,
+    mailer: &Mailer,
+    retry_policy: &RetryPolicy,
+    user: &User,
+    batches: &[Batch<'_>],
+) -> Vec<Sent> {
+    let combined = Tag::combined_for_user(conn, user.id).unwrap_or_else(|e| {
+        log::error!(
+            "Error getting combined digests for user {}: {:?}",
+            user.id,
+            e
+        );
+        HashMap::new()
+    });
+    let due = batches
+        .iter()
+        .map(|batch| feed_data_for(user, batch))
+        .collect();
+    let mut trends = weekly_trends(conn, user);
+    let date = today();
+    let mut sent = HashMap::new();
+    for mut digest in group_digests(due, &combined) {
+        digest.trends = trends.take();
+        let delivered = deliver(mailer, retry_policy, user, &digest, &date).await;
+        match &delivered {
+            Ok(_) => {
+                if digest.trends.is_some() {
+                    let now = Utc::now().timestamp();
+                    if let Err(e) = TrendSettings::record_sent(conn, user.id, now) {
+                        log::error!("Error recording trends sent to {}: {}", user.id, e);
                     }
                 }
-
-                let update = PartialSubscription {
-                    last_sent_time: Some(Utc::now().timestamp() as i32),
-                    ..Default::default()
-                };
-                Subscription::update(&mut conn, feed_data.sub_id, &update);
             }
+            // try the report with the user's next digest
+            Err(_) => trends = digest.trends.take(),
+        }
+        for feed_data in &digest.feeds {
+            let response = delivered.clone().map_err(ChannelError::from);
+            sent.insert(
+                feed_data.sub_id,
+                Sent {
+                    recipient: feed_data.send_email.clone(),
+                    response,
+                },
+            );
         }
     }
+    batches
+        .iter()
+        .filter_map(|batch| sent.remove(&batch.sub.id))
+        .collect()
 }
 
-fn items_to_send_by_user(conn: &mut SqliteConnection, user_id: i32) -> EmailData {
-    let subscriptions = Subscription::get_all_for_user(conn, user_id).unwrap();
-    let mut feed_data = Vec::new();
-    for sub in subscriptions {
-        let feed_id = sub.feed_id;
-        let last_sent = sub.last_sent_time;
+/// Send the batch as a digest of its own, returning the relay's response
+pub async fn send_single(
+    mailer: &Mailer,
+    retry_policy: &RetryPolicy,
+    user: &User,
+    batch: &Batch<'_>,
+) -> Result<String, DeliveryError> {
+    let digest = Digest::single(feed_data_for(user, batch));
+    deliver(mailer, retry_policy, user, &digest, &today()).await
+}
 
-        // if last_sent + frequency is > now, skip
-        let now = chrono::Utc::now().timestamp() as i32;
-        let should_send = match sub.frequency {
-            Frequency::Realtime => true,
-            Frequency::Hourly => now - last_sent > 3600,
-            Frequency::Daily => now - last_sent > 86400,
+/// The user's trends report, if they want it in their digests and it's
+/// been a week since the last one
+fn weekly_trends(conn: &mut DbConnection, user: &User) -> Option<Trends> {
+    let now = Utc::now().timestamp();
+    if !TrendSettings::load(conn, user.id).in_digest || !TrendSettings::is_due(conn, user.id, now) {
+        return None;
+    }
+    match Trends::for_user(conn, user.id, now) {
+        Ok(trends) if trends.is_empty() => None,
+        Ok(trends) => Some(trends),
+        Err(e) => {
+            log::error!("Error getting trends for user {}: {:?}", user.id, e);
+            None
+        }
+    }
+}
+
+/// Put each subscription in its own digest, except those with a combined
+/// digest tag, which share one per tag and address. Digests keep the order
+/// of their first subscription.
+fn group_digests(due: Vec<FeedData>, combined: &HashMap<SubscriptionId, Tag>) -> Vec<Digest> {
+    let mut digests: Vec<Digest> = Vec::new();
+    let mut by_tag: HashMap<(TagId, String), usize> = HashMap::new();
+    for feed_data in due {
+        let tag = match combined.get(&feed_data.sub_id) {
+            Some(tag) => tag,
+            None => {
+                digests.push(Digest::single(feed_data));
+                continue;
+            }
         };
+        match by_tag.entry((tag.id, feed_data.send_email.clone())) {
+            Entry::Occupied(entry) => digests[*entry.get()].feeds.push(feed_data),
+            Entry::Vacant(entry) => {
+                entry.insert(digests.len());
+                digests.push(Digest {
+                    tag: Some(tag.name.clone()),
+                    ..Digest::single(feed_data)
+                });
+            }
+        }
+    }
+    digests
+}
 
-        let feed = Feed::get_by_id(conn, feed_id).unwrap();
+fn today() -> String {
+    Utc::now().format("%Y-%m-%d").to_string()
+}
 
-        if !should_send {
-            log::info!(
-                "Not enough time elapsed to send again for {:?} with frequency={:?}",
-                sub.friendly_name,
-                sub.frequency,
-            );
-            continue;
-        }
+/// Render a digest into an email and send it, returning the relay's
+/// response. Sending is retried per the retry policy unless the relay
+/// rejects the message outright.
+async fn deliver(
+    mailer: &Mailer,
+    retry_policy: &RetryPolicy,
+    user: &User,
+    digest: &Digest,
+    date: &str,
+) -> Result<String, DeliveryError> {
+    let cfg = &mailer.cfg;
+    let truncate_length = user.item_truncate_length.max(0) as usize;
+    let from_name = user
+        .from_name
+        .as_deref()
+        .filter(|name| !name.is_empty())
+        .or(cfg.from_name.as_deref());
+
+    let links = ActionLinks::for_user(user);
+    let as_plain = to_plain_email(digest, truncate_length, links.as_ref());
+    let as_html = to_html_email(digest, truncate_length, links.as_ref());
+    let content = MultiPartEmailContent {
+        as_plain: &as_plain,
+        as_html: &as_html,
+    };
+
+    let subject = digest_subject(
+        digest,
+        user.subject_template.as_deref(),
+        &cfg.email_subject,
+        date,
+    );
+    let message = construct_email(
+        &subject,
+        digest.send_email(),
+        &cfg.from_email,
+        from_name,
+        content,
+    )
+    .map_err(|e| DeliveryError::Build(e.to_string()))?;
+
+    let response = with_retries(
+        retry_policy,
+        &format!("send email for {}", digest.label()),
+        || mailer.sender.send(&message),
+        |e| !e.is_permanent(),
+    )
+    .await
+    .map_err(|e| DeliveryError::Send(e.to_string()))?;
+    let relay_response = relay_response(&response);
+    log::info!(
+        "Email sent to {} for {}: {}",
+        redact::email(digest.send_email()),
+        digest.label(),
+        relay_response
+    );
+    Ok(relay_response)
+}
+
+/// A combined digest's subject, from the user's template or else
+/// `default_template`, titled with the tag. Single subscriptions' digests
+/// use theirs.
+fn digest_subject(
+    digest: &Digest,
+    user_template: Option<&str>,
+    default_template: &str,
+    date: &str,
+) -> String {
+    let tag = match &digest.tag {
+        Some(tag) => tag,
+        None => return email_subject(&digest.feeds[0], default_template, date),
+    };
+    let template = user_template
+        .filter(|template| !template.is_empty())
+        .unwrap_or(default_template);
+    subject::render(
+        template,
+        &SubjectVars {
+            feed_title: tag,
+            feed_link: "",
+            sub_id: digest.feeds[0].sub_id,
+            count: digest.item_count(),
+            date,
+        },
+    )
+}
+
+/// The digest's subject, from the subscription's or user's template or
+/// else `default_template`, after the subscription's prefix
+fn email_subject(feed_data: &FeedData, default_template: &str, date: &str) -> String {
+    let template = feed_data
+        .subject_template
+        .as_deref()
+        .unwrap_or(default_template);
+    let subject = subject::render(
+        template,
+        &SubjectVars {
+            feed_title: &feed_data.feed_title,
+            feed_link: &feed_data.feed_link,
+            sub_id: feed_data.sub_id,
+            count: feed_data.new_items.len(),
+            date,
+        },
+    );
+    match &feed_data.subject_prefix {
+        Some(prefix) => format!("{} {}", prefix, subject),
+        None => subject.to_string(),
+    }
+}
+
+/// Render the batch's digest, whose items have passed the subscription's
+/// filters, without sending it
+pub fn preview(user: &User, batch: &Batch) -> EmailPreview {
+    let digest = Digest::single(feed_data_for(user, batch));
+    let links = ActionLinks::for_user(user);
+    let truncate_length = user.item_truncate_length.max(0) as usize;
+    let default_template =
+        EmailServerCfg::from_env().map_or(DEFAULT_SUBJECT.to_string(), |cfg| cfg.email_subject);
+    EmailPreview {
+        subject: email_subject(&digest.feeds[0], &default_template, &today()),
+        html: to_html_email(&digest, truncate_length, links.as_ref()),
+        text: to_plain_email(&digest, truncate_length, links.as_ref()),
+    }
+}
+
+/// The HTML part of the subscription's digest of `items` in each of the
+/// template versions, all from the same data, to compare them. Stats
+/// aren't fetched, so they're left out.
+pub fn preview_templates(
+    user: &User,
+    sub: &Subscription,
+    feed: &Feed,
+    items: Vec<FeedItem>,
+    templates: &[DigestTemplate],
+) -> askama::Result<Vec<String>> {
+    let batch = Batch {
+        sub,
+        feed,
+        items: &items,
+        stats: &HashMap::new(),
+        now: Utc::now().timestamp(),
+    };
+    let digest = Digest::single(feed_data_for(user, &batch));
+    let links = ActionLinks::for_user(user);
+    let truncate_length = user.item_truncate_length.max(0) as usize;
+    let view = digest_view(&digest, truncate_length, links.as_ref());
+    templates
+        .iter()
+        .map(|template| template.render(&view))
+        .collect()
+}
+
+/// The relay's reply on one line, e.g. "250 2.0.0 Ok: queued as 4F1A2B3C"
+fn relay_response(response: &Response) -> String {
+    let message: Vec<&str> = response.message().collect();
+    format!("{} {}", response.code(), message.join(" "))
+}
 
-        let new_items = FeedItem::items_after(conn, feed_id, last_sent);
-        feed_data.push(FeedData {
-            sub_id: sub.id,
-            new_items,
-            feed_title: feed.title,
-            feed_link: feed.url,
-        });
+/// Everything needed to render the batch's items
+fn feed_data_for(user: &User, batch: &Batch) -> FeedData {
+    let (sub, feed) = (batch.sub, batch.feed);
+    FeedData {
+        sub_id: sub.id,
+        new_items: batch.items.to_vec(),
+        feed_title: batch.title().to_string(),
+        feed_link: batch.homepage().to_string(),
+        feed_description: sub.display_description(feed).map(str::to_string),
+        send_email: sub.destination(user).to_string(),
+        subject_prefix: sub.subject_prefix.clone(),
+        link_mode: feed.link_mode(),
+        show_stats: sub.show_stats,
+        item_stats: batch.stats.clone(),
+        subject_template: [&sub.subject_template, &user.subject_template]
+            .into_iter()
+            .flatten()
+            .find(|template| !template.is_empty())
+            .cloned(),
     }
-    EmailData { feed_data }
 }
 
 fn construct_email(
     subject: &str,
     to_email: ToEmail,
     from_email: FromEmail,
+    from_name: Option<&str>,
     content: MultiPartEmailContent,
 ) -> Result<Message, Error> {
     // TODO: settings entries for SMTP server
-    // TODO: settings entry for updating From Name and From Email
     Message::builder()
-        .from(from_email.parse().unwrap())
+        .from(Mailbox::new(
+            from_name.map(str::to_string),
+            from_email.parse().unwrap(),
+        ))
         .to(to_email.parse().unwrap())
         .subject(subject)
         .multipart(
@@ -161,87 +443,310 @@ fn construct_email(
         )
 }
 
-fn to_html_email(feed_data: &FeedData) -> String {
-    let mut result = EMAIL_TEMPLATE_HEAD.to_string();
-    result.push_str(&format!(
-        "<h2>{}</h2>
-            <a href='{}'>View Feed</a>",
-        feed_data.feed_title, feed_data.feed_link
-    ));
-    for item in &feed_data.new_items {
-        let date_time = Utc.timestamp_opt(item.pub_date as i64, 0).unwrap();
-        result.push_str(&format!(
-            "<div class='feed-item'>
-                    <h2><a href='{}'>{}</a></h2>
-                    <time>{}</time>
-                    <p>{}</p>
-                    <p class='author'>{}</p>
-                </div>",
-            item.link,
-            item.title,
-            item.description
-                .as_deref()
-                .unwrap_or("No description provided"),
-            date_time.format("%Y-%m-%d %H:%M:%S"),
-            item.author.as_deref().unwrap_or("No author provided")
-        ));
+/// The links under each item that mark it read or star it without logging
+/// in
+struct ActionLinks {
+    base: String,
+    user_id: UserId,
+    links: ItemLinks,
+}
+
+impl ActionLinks {
+    /// None if `MF_PUBLIC_URL` isn't set, since the links need to be absolute
+    fn for_user(user: &User) -> Option<ActionLinks> {
+        Some(ActionLinks {
+            base: public_url()?,
+            user_id: user.id,
+            links: ItemLinks::global()?,
+        })
     }
-    result.push_str("<hr />");
-    result.push_str(EMAIL_TEMPLATE_FOOT);
-    result
+
+    fn url(&self, item: &FeedItem, action: ItemAction) -> String {
+        self.links.url(&self.base, self.user_id, item.id, action)
+    }
+}
+
+fn to_html_email(digest: &Digest, truncate_length: usize, links: Option<&ActionLinks>) -> String {
+    // rendering only fails if a field's Display does, and these are strings
+    DigestTemplate::Current
+        .render(&digest_view(digest, truncate_length, links))
+        .expect("Digest template renders")
+}
+
+/// What the HTML part shows, for any version of the template
+fn digest_view<'a>(
+    digest: &'a Digest,
+    truncate_length: usize,
+    links: Option<&ActionLinks>,
+) -> DigestView<'a> {
+    DigestView {
+        sections: digest
+            .feeds
+            .iter()
+            .map(|feed_data| section_view(feed_data, truncate_length, links))
+            .collect(),
+        trends: digest.trends.as_ref(),
+    }
+}
+
+/// One subscription's items in the HTML part
+fn section_view(
+    feed_data: &FeedData,
+    truncate_length: usize,
+    links: Option<&ActionLinks>,
+) -> SectionView {
+    let items = feed_data
+        .new_items
+        .iter()
+        .map(|item| {
+            let date_time = Utc.timestamp_opt(item.pub_date, 0).unwrap();
+            let (link, comments) = item.display_links(feed_data.link_mode);
+            ItemView {
+                title: item.title.clone(),
+                link: link.to_string(),
+                comments: comments.map(str::to_string),
+                stats: item_stats(feed_data, item).map(|stats| stats.to_string()),
+                description: html_description(item, link, truncate_length),
+                date: date_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                author: item
+                    .author
+                    .clone()
+                    .unwrap_or("No author provided".to_string()),
+                actions: links.map(|links| ItemActions {
+                    read: links.url(item, ItemAction::Read),
+                    star: links.url(item, ItemAction::Star),
+                }),
+            }
+        })
+        .collect();
+    SectionView {
+        title: feed_data.feed_title.clone(),
+        link: feed_data.feed_link.clone(),
+        description: feed_data.feed_description.clone(),
+        items,
+    }
+}
+
+/// Item description for the HTML part. Descriptions over the user's
+/// limit are cut down to plain text with a link to the full item.
+fn html_description(item: &FeedItem, link: &str, truncate_length: usize) -> String {
+    let description = match item.description.as_deref() {
+        Some(description) => description,
+        None => return "No description provided".to_string(),
+    };
+
+    let (text, truncated) = html_to_text_truncated(description, truncate_length);
+    if !truncated {
+        return description.to_string();
+    }
+    format!(
+        "{}<br /><a href='{}'>Continue reading</a>",
+        html_escape::encode_text(&text).replace('\n', "<br />"),
+        link
+    )
+}
+
+/// The item's score and comment count, if the subscription shows them
+fn item_stats<'a>(feed_data: &'a FeedData, item: &FeedItem) -> Option<&'a ItemStats> {
+    if !feed_data.show_stats {
+        return None;
+    }
+    feed_data.item_stats.get(&item.id)
 }
 
-fn to_plain_email(feed_data: &FeedData) -> String {
+fn to_plain_email(digest: &Digest, truncate_length: usize, links: Option<&ActionLinks>) -> String {
     let mut result = "MailFeed Digest\n\n".to_string();
+    for feed_data in &digest.feeds {
+        result.push_str(&plain_section(feed_data, truncate_length, links));
+    }
+    if let Some(trends) = &digest.trends {
+        result.push_str(&plain_trends(trends));
+    }
+    result.push('\n');
+    result
+}
+
+/// One subscription's items in the plain text part
+fn plain_section(
+    feed_data: &FeedData,
+    truncate_length: usize,
+    links: Option<&ActionLinks>,
+) -> String {
+    let mut result = String::new();
     result.push_str(&format!(
         "{}\nView Feed: {}\n",
         feed_data.feed_title, feed_data.feed_link
     ));
+    if let Some(description) = &feed_data.feed_description {
+        result.push_str(&format!("{}\n", html_to_text_truncated(description, 0).0));
+    }
     for item in &feed_data.new_items {
-        let date_time = Utc.timestamp_opt(item.pub_date as i64, 0).unwrap();
-        let description = item
-            .description
-            .clone()
-            .unwrap_or("No description provided".to_string());
+        let date_time = Utc.timestamp_opt(item.pub_date, 0).unwrap();
+        let (link, comments) = item.display_links(feed_data.link_mode);
+        let item_links = match comments {
+            Some(comments) => format!("{}\nComments: {}", link, comments),
+            None => link.to_string(),
+        };
+        let description = match item.description.as_deref() {
+            Some(description) => match html_to_text_truncated(description, truncate_length) {
+                (text, true) => format!("{}\nContinue reading: {}", text, link),
+                (text, false) => text,
+            },
+            None => "No description provided".to_string(),
+        };
+        let title = match item_stats(feed_data, item) {
+            Some(stats) => format!("{} ({})", item.title, stats),
+            None => item.title.clone(),
+        };
+        let actions = links
+            .map(|links| {
+                format!(
+                    "Mark as read: {}\nStar: {}\n",
+                    links.url(item, ItemAction::Read),
+                    links.url(item, ItemAction::Star)
+                )
+            })
+            .unwrap_or_default();
 
         result.push_str(&format!(
-            "{}\n{}\n{}\n{}\n{}\n----------\n\n",
-            item.link,
-            item.title,
-            html_escape::decode_html_entities(&description),
+            "{}\n{}\n{}\n{}\n{}\n{}----------\n\n",
+            item_links,
+            title,
+            description,
             date_time.format("%Y-%m-%d %H:%M:%S"),
             item.author
                 .clone()
-                .unwrap_or("No author provided".to_string())
+                .unwrap_or("No author provided".to_string()),
+            actions
         ));
     }
-    result.push('\n');
     result
 }
 
-const EMAIL_TEMPLATE_HEAD: &str = r#"<html>
-<head>
-  <meta charset='UTF-8' />
-  <title>MailFeed Digest</title>
-  <style>
-    body { font-family: Arial, sans-serif; margin: 0; padding: 0; background-color: #f6f6f6; } .container { width:
-    80%; margin: 0 auto; background-color: #ffffff; padding: 20px; } h1 { color: #333333; } .feed { margin-bottom:
-    20px; } .feed-item { border-bottom: 1px solid #dddddd; padding: 10px 0; } .feed-item:last-child { border-bottom:
-    0; } .feed-item h2 { margin: 0; font-size: 18px; } .feed-item a { color: #007bff; text-decoration: none; }
-    .feed-item p { color: #666666; margin: 10px 0; } .feed-item time { color: #999999; font-size: 12px; } .author {
-    color: #999999; font-size: 14px; }
-  </style>
-</head>
-<body>
-  <div class='container'>
-    <h1>MailFeed Digest</h1>
-    <div class='feed'>
-"#;
-
-const EMAIL_TEMPLATE_FOOT: &str = r#"
-        </div>
-      </div>
-    </div>
-  </body>
-</html>
-"#;
+fn plain_trends(trends: &Trends) -> String {
+    let mut result = format!(
+        "This week's trends, across {} items from your feeds\n",
+        trends.item_count
+    );
+    if !trends.keywords.is_empty() {
+        result.push_str("\nKeywords:\n");
+        for keyword in &trends.keywords {
+            result.push_str(&format!("- {} ({})\n", keyword.word, keyword.count));
+        }
+    }
+    if !trends.domains.is_empty() {
+        result.push_str("\nSites:\n");
+        for domain in &trends.domains {
+            result.push_str(&format!("- {} ({})\n", domain.domain, domain.count));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{feed::LinkMode, ids::FeedId},
+        test_helpers::test_helpers::test_item,
+    };
+
+    fn feed_data(sub_id: i32, send_email: &str, titles: &[&str]) -> FeedData {
+        FeedData {
+            sub_id: SubscriptionId(sub_id),
+            new_items: titles
+                .iter()
+                .enumerate()
+                .map(|(i, title)| FeedItem {
+                    feed_id: FeedId(sub_id),
+                    title: title.to_string(),
+                    ..test_item(sub_id * 100 + i as i32)
+                })
+                .collect(),
+            feed_title: format!("Feed {}", sub_id),
+            feed_link: "https://example.com".to_string(),
+            feed_description: None,
+            send_email: send_email.to_string(),
+            subject_prefix: None,
+            link_mode: LinkMode::Link,
+            show_stats: false,
+            item_stats: HashMap::new(),
+            subject_template: None,
+        }
+    }
+
+    fn tag(id: i32, name: &str) -> Tag {
+        Tag {
+            id: TagId(id),
+            user_id: UserId(1),
+            name: name.to_string(),
+            combined_digest: true,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_group_digests() {
+        let due = vec![
+            feed_data(1, "me@example.com", &["a"]),
+            feed_data(2, "me@example.com", &["b", "c"]),
+            feed_data(3, "me@example.com", &["d"]),
+            feed_data(4, "other@example.com", &["e"]),
+            feed_data(5, "me@example.com", &["f"]),
+        ];
+        let combined = HashMap::from([
+            (SubscriptionId(2), tag(1, "news")),
+            (SubscriptionId(4), tag(1, "news")),
+            (SubscriptionId(5), tag(1, "news")),
+        ]);
+
+        let digests = group_digests(due, &combined);
+        let grouped: Vec<(Option<&str>, String)> = digests
+            .iter()
+            .map(|digest| (digest.tag.as_deref(), digest.label()))
+            .collect();
+        assert_eq!(
+            grouped,
+            vec![
+                (None, "sub_id=1".to_string()),
+                (Some("news"), "sub_id=2,5".to_string()),
+                (None, "sub_id=3".to_string()),
+                // a different address gets its own email
+                (Some("news"), "sub_id=4".to_string()),
+            ]
+        );
+        assert_eq!(digests[1].item_count(), 3);
+    }
+
+    #[test]
+    fn test_combined_digest_rendering() {
+        let mut digest = Digest {
+            tag: Some("news".to_string()),
+            feeds: vec![
+                feed_data(1, "me@example.com", &["First item"]),
+                feed_data(2, "me@example.com", &["Second item"]),
+            ],
+            trends: None,
+        };
+        let html = to_html_email(&digest, 0, None);
+        let text = to_plain_email(&digest, 0, None);
+        for part in [&html, &text] {
+            assert!(part.contains("Feed 1") && part.contains("Feed 2"));
+            assert!(part.find("First item") < part.find("Second item"));
+        }
+        assert_eq!(html.matches("<html>").count(), 1);
+
+        let user_template = Some("{feed_title}: {count} new");
+        assert_eq!(
+            digest_subject(&digest, user_template, DEFAULT_SUBJECT, "2026-10-16"),
+            "news: 2 new"
+        );
+        // a single subscription's template already includes the user's
+        digest.tag = None;
+        digest.feeds.truncate(1);
+        assert_eq!(
+            digest_subject(&digest, user_template, "{feed_title}", "2026-10-16"),
+            "Feed 1"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use chrono::Utc;
+
+use super::{notification::send_notification, onboarding::public_url, types::EmailServerCfg};
+use crate::{
+    db::DbConnection,
+    models::{
+        retry_policy::{Channel, RetryPolicy},
+        smtp_verification::SmtpVerification,
+        user::User,
+    },
+    security::redact,
+};
+
+/// The fingerprint of the SMTP settings in the environment, None if
+/// they're missing or invalid
+pub fn current_fingerprint() -> Option<String> {
+    EmailServerCfg::from_env().map(|cfg| cfg.fingerprint())
+}
+
+/// If the settings have changed since they were last verified, email each
+/// admin a link that verifies them, sent through the new settings so it
+/// only arrives if they work. Digests wait until it's clicked.
+pub(super) async fn check(conn: &mut DbConnection, cfg: &EmailServerCfg) {
+    let now = Utc::now().timestamp();
+    let token = match SmtpVerification::start(conn, &cfg.fingerprint(), now) {
+        Ok(Some(token)) => token,
+        Ok(None) => return,
+        Err(e) => {
+            log::error!("Error checking SMTP settings: {:?}", e);
+            return;
+        }
+    };
+    log::warn!("SMTP settings have changed, holding digests until they're verified");
+
+    let admins = User::get_all_admin(conn).unwrap_or_default();
+    let retry_policy = RetryPolicy::load(conn, Channel::Email);
+    let body = verification_body(&cfg.host, &token, public_url().as_deref());
+    for admin in admins.iter().filter(|admin| admin.is_active) {
+        let to = redact::email(&admin.send_email);
+        match send_notification(
+            &admin.send_email,
+            "Verify MailFeed's new email settings",
+            &body,
+            &retry_policy,
+        )
+        .await
+        {
+            Ok(()) => log::info!("Sent SMTP verification email to {}", to),
+            Err(e) => log::error!("Error sending SMTP verification email to {}: {}", to, e),
+        }
+    }
+}
+
+fn verification_body(host: &str, token: &str, public_url: Option<&str>) -> String {
+    let how = match public_url {
+        Some(url) => format!(
+            "To start sending them again, open:\n\n{}/email/smtp/verify?token={}",
+            url, token
+        ),
+        None => "MF_PUBLIC_URL isn't set, so there's no link to open. Since this email \
+                 arrived, an admin can confirm the settings through the admin API instead."
+            .to_string(),
+    };
+    format!(
+        "MailFeed's email settings have changed, and it now sends through {}. Digests are \
+         held until the new settings are shown to work. {}\n\n\
+         If you didn't expect this, check MailFeed's SMTP settings.\n",
+        host, how
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verification_body() {
+        let body = verification_body("smtp.example.com", "abc", Some("https://mail.example"));
+        assert!(body.contains("smtp.example.com"));
+        assert!(body.contains("https://mail.example/email/smtp/verify?token=abc"));
+        let body = verification_body("smtp.example.com", "abc", None);
+        assert!(!body.contains("abc"));
+    }
+}
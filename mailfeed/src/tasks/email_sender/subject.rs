@@ -0,0 +1,154 @@
+use crate::models::ids::SubscriptionId;
+use thiserror::Error;
+
+/// Variables that may appear in a subject template as `{name}`
+pub const VARIABLES: &[&str] = &[
+    "feed_title",
+    "feed_link",
+    "sub_id",
+    "count",
+    "new_items_count",
+    "date",
+];
+
+const MAX_TEMPLATE_LENGTH: usize = 200;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("Subject template must not be empty")]
+    Empty,
+    #[error("Subject template must be at most {0} characters")]
+    TooLong(usize),
+    #[error("Subject template must be a single line")]
+    MultiLine,
+    #[error("Unknown subject variable {{{0}}}")]
+    UnknownVariable(String),
+    #[error("Unmatched brace in subject template")]
+    UnmatchedBrace,
+}
+
+/// Values substituted into a subject template
+pub struct SubjectVars<'a> {
+    pub feed_title: &'a str,
+    pub feed_link: &'a str,
+    pub sub_id: SubscriptionId,
+    pub count: usize,
+    /// already formatted for display
+    pub date: &'a str,
+}
+
+impl SubjectVars<'_> {
+    fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "feed_title" => Some(self.feed_title.to_string()),
+            "feed_link" => Some(self.feed_link.to_string()),
+            "sub_id" => Some(self.sub_id.to_string()),
+            "count" | "new_items_count" => Some(self.count.to_string()),
+            "date" => Some(self.date.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Check that a template is a single reasonable line and only uses
+/// whitelisted variables
+pub fn validate(template: &str) -> Result<(), Error> {
+    if template.trim().is_empty() {
+        return Err(Error::Empty);
+    }
+    if template.chars().count() > MAX_TEMPLATE_LENGTH {
+        return Err(Error::TooLong(MAX_TEMPLATE_LENGTH));
+    }
+    if template.contains(['\r', '\n']) {
+        return Err(Error::MultiLine);
+    }
+
+    let mut rest = template;
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            return Err(Error::UnmatchedBrace);
+        }
+        let after = &rest[open + 1..];
+        let close = after.find('}').ok_or(Error::UnmatchedBrace)?;
+        let name = &after[..close];
+        if name.contains('{') {
+            return Err(Error::UnmatchedBrace);
+        }
+        if !VARIABLES.contains(&name) {
+            return Err(Error::UnknownVariable(name.to_string()));
+        }
+        rest = &after[close + 1..];
+    }
+    Ok(())
+}
+
+/// Fill in a template in a single pass, so braces in the substituted values
+/// are never expanded. Anything that isn't a known variable is left as is.
+pub fn render(template: &str, vars: &SubjectVars) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after
+            .find('}')
+            .and_then(|close| vars.get(&after[..close]).map(|value| (close, value)))
+        {
+            Some((close, value)) => {
+                out.push_str(&value);
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> SubjectVars<'static> {
+        SubjectVars {
+            feed_title: "Example {count}",
+            feed_link: "https://example.com",
+            sub_id: SubscriptionId(7),
+            count: 3,
+            date: "2023-10-01",
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(validate("MailFeed Digest"), Ok(()));
+        assert_eq!(validate("{feed_title}: {count} new ({date})"), Ok(()));
+        assert_eq!(validate("{new_items_count} from {feed_link}"), Ok(()));
+        assert_eq!(validate("  "), Err(Error::Empty));
+        assert_eq!(validate("a\nBcc: x@example.com"), Err(Error::MultiLine));
+        assert_eq!(validate(&"a".repeat(201)), Err(Error::TooLong(200)));
+        assert_eq!(
+            validate("{password}"),
+            Err(Error::UnknownVariable("password".to_string()))
+        );
+        assert_eq!(validate("{feed_title"), Err(Error::UnmatchedBrace));
+        assert_eq!(validate("feed_title}"), Err(Error::UnmatchedBrace));
+        assert_eq!(validate("{{feed_title}"), Err(Error::UnmatchedBrace));
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("{feed_title}: {count} new on {date}", &vars()),
+            "Example {count}: 3 new on 2023-10-01"
+        );
+        assert_eq!(
+            render("#{sub_id} {new_items_count} {feed_link}", &vars()),
+            "#7 3 https://example.com"
+        );
+        assert_eq!(render("{unknown} {count", &vars()), "{unknown} {count");
+    }
+}
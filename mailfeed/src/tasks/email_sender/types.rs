@@ -1,34 +1,71 @@
-use std::env;
+use std::{collections::HashMap, env};
 
-use crate::models::feed_item::FeedItem;
+use crate::models::{feed::LinkMode, feed_item::FeedItem, ids::SubscriptionId, trends::Trends};
+use crate::security::tokens::hash_token;
+use crate::tasks::dispatch::enrichment::ItemStats;
 use lettre::{transport::smtp::authentication::Credentials, SmtpTransport};
 
-#[derive(Debug)]
+/// Subject template used when MF_EMAIL_SUBJECT isn't set
+pub const DEFAULT_SUBJECT: &str = "MailFeed Digest";
+
 pub struct EmailServerCfg {
     pub host: String,
     pub port: u16,
     pub username: String,
     pub password: String,
     pub from_email: String,
+    /// default display name for the From header, users may override it
+    pub from_name: Option<String>,
     pub email_subject: String,
 }
 
+/// Leaves the password out, so the config can't leak it into logs
+impl std::fmt::Debug for EmailServerCfg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailServerCfg")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &"[redacted]")
+            .field("from_email", &self.from_email)
+            .field("from_name", &self.from_name)
+            .field("email_subject", &self.email_subject)
+            .finish()
+    }
+}
+
 impl EmailServerCfg {
-    pub fn from_env() -> Self {
-        let host = env::var("MF_SMTP_HOST").unwrap();
-        let port = env::var("MF_SMTP_PORT").unwrap().parse::<u16>().unwrap();
-        let username = env::var("MF_SMTP_USERNAME").unwrap();
-        let password = env::var("MF_SMTP_PASSWORD").unwrap();
-        let from_email = env::var("MF_FROM_EMAIL").unwrap();
-        let email_subject = env::var("MF_EMAIL_SUBJECT").unwrap_or("MailFeed Digest".to_string());
-        EmailServerCfg {
+    /// Read SMTP settings from the environment, or None if any required
+    /// setting is missing or invalid
+    pub fn from_env() -> Option<Self> {
+        let host = env::var("MF_SMTP_HOST").ok()?;
+        let port = env::var("MF_SMTP_PORT").ok()?.parse::<u16>().ok()?;
+        let username = env::var("MF_SMTP_USERNAME").ok()?;
+        let password = env::var("MF_SMTP_PASSWORD").ok()?;
+        let from_email = env::var("MF_FROM_EMAIL").ok()?;
+        let from_name = env::var("MF_FROM_NAME")
+            .ok()
+            .filter(|name| !name.is_empty());
+        let email_subject = env::var("MF_EMAIL_SUBJECT").unwrap_or(DEFAULT_SUBJECT.to_string());
+        Some(EmailServerCfg {
             host,
             port,
             username,
             password,
             from_email,
+            from_name,
             email_subject,
-        }
+        })
+    }
+
+    /// What digests depend on reaching the right place, hashed: a change
+    /// means the settings have to be verified again. A new password fails
+    /// loudly if it's wrong, so it's left out.
+    pub fn fingerprint(&self) -> String {
+        hash_token(&format!(
+            "{}\n{}\n{}\n{}",
+            self.host, self.port, self.username, self.from_email
+        ))
     }
 
     pub fn to_transport(&self) -> Result<SmtpTransport, lettre::transport::smtp::Error> {
@@ -45,15 +82,59 @@ impl EmailServerCfg {
 
 #[derive(Debug)]
 pub struct FeedData {
-    pub sub_id: i32,
+    pub sub_id: SubscriptionId,
     pub new_items: Vec<FeedItem>,
     pub feed_title: String,
     pub feed_link: String,
+    pub feed_description: Option<String>,
+    pub send_email: String,
+    pub subject_prefix: Option<String>,
+    pub link_mode: LinkMode,
+    pub show_stats: bool,
+    /// found by the dispatcher's Enricher, by item id
+    pub item_stats: HashMap<i32, ItemStats>,
+    /// the subscription's or user's template, if either is set
+    pub subject_template: Option<String>,
 }
 
+/// What goes in one email: a subscription's new items, or those of each
+/// due subscription sharing a tag that's sent as a combined digest
 #[derive(Debug)]
-pub struct EmailData {
-    pub feed_data: Vec<FeedData>,
+pub struct Digest {
+    /// the tag's name, for a combined digest
+    pub tag: Option<String>,
+    /// never empty, and all for the same address
+    pub feeds: Vec<FeedData>,
+    /// the user's weekly trends report, added to one digest a week
+    pub trends: Option<Trends>,
+}
+
+impl Digest {
+    pub fn single(feed_data: FeedData) -> Self {
+        Digest {
+            tag: None,
+            feeds: vec![feed_data],
+            trends: None,
+        }
+    }
+
+    pub fn send_email(&self) -> &str {
+        &self.feeds[0].send_email
+    }
+
+    pub fn item_count(&self) -> usize {
+        self.feeds.iter().map(|feed| feed.new_items.len()).sum()
+    }
+
+    /// Which subscriptions it's for, for logs
+    pub fn label(&self) -> String {
+        let sub_ids: Vec<String> = self
+            .feeds
+            .iter()
+            .map(|feed| feed.sub_id.to_string())
+            .collect();
+        format!("sub_id={}", sub_ids.join(","))
+    }
 }
 
 pub type ToEmail<'a> = &'a str;
@@ -63,3 +144,24 @@ pub struct MultiPartEmailContent<'a> {
     pub as_html: &'a str,
     pub as_plain: &'a str,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_leaves_out_password() {
+        let cfg = EmailServerCfg {
+            host: "smtp.example.com".to_string(),
+            port: 587,
+            username: "mailer".to_string(),
+            password: "hunter2".to_string(),
+            from_email: "feeds@example.com".to_string(),
+            from_name: None,
+            email_subject: DEFAULT_SUBJECT.to_string(),
+        };
+        let debug = format!("{:?}", cfg);
+        assert!(debug.contains("smtp.example.com"));
+        assert!(!debug.contains("hunter2"));
+    }
+}
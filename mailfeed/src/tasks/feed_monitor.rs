@@ -1,2 +1,16 @@
+mod body_hash;
+mod broken_feeds;
+mod change_alerts;
+mod changes;
+mod dns_cache;
+mod fetch_error;
+pub mod import;
+mod item_links;
+mod link_cleaner;
+pub mod opml;
+mod parse_warnings;
+mod poll_interval;
+pub mod refresh;
 pub mod runner;
+mod saved_searches;
 mod types;
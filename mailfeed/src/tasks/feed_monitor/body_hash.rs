@@ -0,0 +1,27 @@
+/// A stable fingerprint of a feed body, so a byte-identical response can be
+/// recognized even when the server doesn't send ETag or Last-Modified
+/// headers. Uses 64-bit FNV-1a, which is plenty for telling one version of a
+/// single feed from the next and, unlike `DefaultHasher`, won't change
+/// between Rust releases.
+pub(super) fn body_hash(body: &str) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let hash = body.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_hash() {
+        assert_eq!(body_hash(""), "cbf29ce484222325");
+        assert_eq!(body_hash("a"), "af63dc4c8601ec8c");
+        assert_eq!(body_hash("<rss/>"), body_hash("<rss/>"));
+        assert_ne!(body_hash("<rss/>"), body_hash("<rss />"));
+    }
+}
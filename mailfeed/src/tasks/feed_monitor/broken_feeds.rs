@@ -0,0 +1,267 @@
+use std::{collections::HashMap, env};
+
+use super::fetch_error::FetchError;
+use crate::{
+    db::DbConnection,
+    models::{
+        feed::{Feed, PartialFeed},
+        feed_item::FeedItem,
+        retry_policy::{Channel, RetryPolicy},
+        subscription::{DeliveryMethod, PartialSubscription, Subscription},
+        user::{User, UserQuery},
+    },
+    security::redact,
+    tasks::{
+        dispatch::{Batch, Channels},
+        email_sender::notification::send_notification,
+    },
+};
+
+const DEFAULT_BROKEN_AFTER: i32 = 10;
+
+/// Gives up on feeds that keep failing. After `MF_FEED_BROKEN_AFTER_FAILURES`
+/// failed fetches in a row the feed is marked broken, its active
+/// subscriptions are paused, and their users are told through each
+/// subscription's delivery method, so a feed doesn't just go quiet.
+pub(super) struct BrokenFeeds {
+    /// None if feeds are never given up on
+    after: Option<i32>,
+    channels: Channels,
+}
+
+impl BrokenFeeds {
+    pub(super) fn from_env(channels: Channels) -> Self {
+        let after = match env::var("MF_FEED_BROKEN_AFTER_FAILURES") {
+            Ok(value) => match value.parse::<i32>() {
+                Ok(after) if after >= 0 => after,
+                _ => {
+                    log::warn!(
+                        "Invalid MF_FEED_BROKEN_AFTER_FAILURES '{}', using default of {}",
+                        value,
+                        DEFAULT_BROKEN_AFTER
+                    );
+                    DEFAULT_BROKEN_AFTER
+                }
+            },
+            Err(_) => DEFAULT_BROKEN_AFTER,
+        };
+        BrokenFeeds {
+            after: Some(after).filter(|after| *after > 0),
+            channels,
+        }
+    }
+
+    /// Whether the failure just recorded for the feed, as it was before,
+    /// is the one that breaks it
+    pub(super) fn breaks(&self, feed: &Feed) -> bool {
+        match self.after {
+            Some(after) => feed.broken_at == 0 && feed.consecutive_failures + 1 >= after,
+            None => false,
+        }
+    }
+
+    /// Mark the feed broken, pause its active subscriptions and tell their
+    /// users
+    pub(super) async fn give_up(&self, conn: &mut DbConnection, feed: &Feed, error: &FetchError) {
+        let now = chrono::Utc::now().timestamp();
+        let failures = feed.consecutive_failures + 1;
+        log::warn!(
+            "Feed {} failed {} times in a row, marking it broken",
+            feed.url,
+            failures
+        );
+        let broken = PartialFeed {
+            broken_at: Some(now),
+            ..Default::default()
+        };
+        Feed::update(conn, feed.id, &broken);
+
+        let subscriptions = Subscription::get_all_for_feed(conn, feed.id).unwrap_or_default();
+        for sub in subscriptions.iter().filter(|sub| sub.is_active) {
+            let paused = PartialSubscription {
+                is_active: Some(false),
+                ..Default::default()
+            };
+            Subscription::update(conn, sub.id, &paused);
+            log::info!("Paused sub_id={} of broken feed {}", sub.id, feed.id);
+
+            let user = match User::get(conn, UserQuery::Id(sub.user_id)) {
+                Some(user) if user.is_active => user,
+                _ => continue,
+            };
+            self.notify(
+                conn,
+                &user,
+                sub,
+                feed,
+                &notice(sub, feed, failures, error),
+                now,
+            )
+            .await;
+        }
+    }
+
+    /// Send the notice the way the subscription's items go, or by email if
+    /// that's how they go or the user hasn't set the channel up
+    async fn notify(
+        &self,
+        conn: &mut DbConnection,
+        user: &User,
+        sub: &Subscription,
+        feed: &Feed,
+        notice: &Notice,
+        now: i64,
+    ) {
+        let channel = match sub.delivery_method {
+            DeliveryMethod::Email => None,
+            method => self.channels.get(method),
+        };
+        if let Some(channel) = channel {
+            if let Some(destination) = channel.destination(conn, user) {
+                let item = notice.item(feed, now);
+                let batch = Batch {
+                    sub,
+                    feed,
+                    items: std::slice::from_ref(&item),
+                    stats: &HashMap::new(),
+                    now,
+                };
+                let retry_policy = RetryPolicy::load(conn, channel.retry_channel());
+                match destination.send(&batch, &retry_policy).await {
+                    Ok(()) => log::info!("Sent broken feed notice for sub_id={}", sub.id),
+                    Err(e) => log::error!(
+                        "Error sending broken feed notice for sub_id={} to {}: {}",
+                        sub.id,
+                        destination.recipient(),
+                        e
+                    ),
+                }
+                return;
+            }
+        }
+
+        let retry_policy = RetryPolicy::load(conn, Channel::Email);
+        let to = sub.destination(user);
+        match send_notification(to, &notice.subject, &notice.body, &retry_policy).await {
+            Ok(()) => log::info!("Sent broken feed notice for sub_id={}", sub.id),
+            Err(e) => log::error!(
+                "Error sending broken feed notice to {}: {}",
+                redact::email(to),
+                e
+            ),
+        }
+    }
+}
+
+struct Notice {
+    subject: String,
+    body: String,
+}
+
+impl Notice {
+    /// For channels that only send items, the notice as one
+    fn item(&self, feed: &Feed, now: i64) -> FeedItem {
+        FeedItem {
+            id: 0,
+            feed_id: feed.id,
+            title: self.subject.clone(),
+            link: feed.url.clone(),
+            pub_date: now,
+            description: Some(self.body.clone()),
+            author: None,
+            comments_link: None,
+            first_seen: now,
+            image_url: None,
+        }
+    }
+}
+
+fn notice(sub: &Subscription, feed: &Feed, failures: i32, error: &FetchError) -> Notice {
+    let name = sub.display_name(feed);
+    Notice {
+        subject: format!("MailFeed: {} stopped working", name),
+        body: format!(
+            "MailFeed couldn't fetch {} ({}) the last {} times it tried. \
+             The last error was: {}\n\n\
+             The subscription has been paused. Once the feed works again, resume the \
+             subscription to get its items again. If it has moved or shut down, you may \
+             want to remove the subscription instead.\n",
+            name, feed.url, failures, error.message
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        feed::{FeedErrorKind, NewFeed},
+        ids::UserId,
+        subscription::NewSubscription,
+    };
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[test]
+    fn test_breaks() {
+        let mut conn = get_test_db_connection();
+        let mut feed = NewFeed {
+            url: "https://example.com/feed.xml",
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let broken_feeds = BrokenFeeds {
+            after: Some(3),
+            channels: Channels::new(Vec::new()),
+        };
+        feed.consecutive_failures = 1;
+        assert!(!broken_feeds.breaks(&feed));
+        feed.consecutive_failures = 2;
+        assert!(broken_feeds.breaks(&feed));
+        // only once
+        feed.broken_at = 100;
+        assert!(!broken_feeds.breaks(&feed));
+
+        let never = BrokenFeeds {
+            after: None,
+            channels: Channels::new(Vec::new()),
+        };
+        feed.broken_at = 0;
+        assert!(!never.breaks(&feed));
+    }
+
+    #[actix_web::test]
+    async fn test_give_up() {
+        let mut conn = get_test_db_connection();
+        let feed = NewFeed {
+            url: "https://example.com/feed.xml",
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let sub = NewSubscription {
+            user_id: UserId(1),
+            feed_id: feed.id,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let broken_feeds = BrokenFeeds {
+            after: Some(1),
+            channels: Channels::new(Vec::new()),
+        };
+        let error = FetchError {
+            kind: FeedErrorKind::Http,
+            message: "404 Not Found".to_string(),
+        };
+        assert!(broken_feeds.breaks(&feed));
+        broken_feeds.give_up(&mut conn, &feed, &error).await;
+
+        assert!(Feed::get_by_id(&mut conn, feed.id).unwrap().broken_at > 0);
+        assert!(
+            !Subscription::get_by_id(&mut conn, sub.id)
+                .unwrap()
+                .is_active
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::{collections::BTreeSet, env};
+
+use crate::{
+    db::DbConnection,
+    models::{
+        feed::Feed,
+        feed_change::{FeedChange, FeedChangeKind},
+        retry_policy::{Channel, RetryPolicy},
+        subscription::Subscription,
+        user::{User, UserQuery},
+    },
+    security::redact,
+    tasks::email_sender::notification::send_notification,
+};
+
+/// Emails admins, and subscribers if `MF_FEED_CHANGE_NOTIFY_SUBSCRIBERS` is
+/// set, when a feed changes in a way that may mean it moved or was taken
+/// over
+pub(super) struct ChangeAlerts {
+    notify_subscribers: bool,
+}
+
+impl ChangeAlerts {
+    pub(super) fn from_env() -> Self {
+        let notify_subscribers = env::var("MF_FEED_CHANGE_NOTIFY_SUBSCRIBERS")
+            .map(|value| value == "true")
+            .unwrap_or(false);
+        ChangeAlerts { notify_subscribers }
+    }
+
+    pub(super) async fn send(&self, conn: &mut DbConnection, feed: &Feed, changes: &[FeedChange]) {
+        let recipients = self.recipients(conn, feed);
+        if recipients.is_empty() {
+            return;
+        }
+
+        let subject = format!("MailFeed: feed changed: {}", feed.url);
+        let mut body = format!("The feed at {} has changed:\n\n", feed.url);
+        for change in changes {
+            body.push_str(&format!("- {}\n", describe(change)));
+        }
+        body.push_str(
+            "\nThis can mean the site moved to a new domain, or that the feed has been \
+             taken over by someone else. Check it's still the feed you expect.\n",
+        );
+
+        let retry_policy = RetryPolicy::load(conn, Channel::Email);
+        for recipient in &recipients {
+            if let Err(e) = send_notification(recipient, &subject, &body, &retry_policy).await {
+                log::error!(
+                    "Error sending feed change alert to {}: {}",
+                    redact::email(recipient),
+                    e
+                );
+            }
+        }
+    }
+
+    fn recipients(&self, conn: &mut DbConnection, feed: &Feed) -> BTreeSet<String> {
+        let mut recipients: BTreeSet<String> = User::get_all_admin(conn)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|user| user.is_active)
+            .map(|user| user.send_email)
+            .collect();
+        if !self.notify_subscribers {
+            return recipients;
+        }
+
+        let subscriptions = Subscription::get_all_for_feed(conn, feed.id).unwrap_or_default();
+        for sub in subscriptions.iter().filter(|sub| sub.is_active) {
+            if let Some(user) = User::get(conn, UserQuery::Id(sub.user_id)) {
+                if user.is_active {
+                    recipients.insert(sub.destination(&user).to_string());
+                }
+            }
+        }
+        recipients
+    }
+}
+
+fn describe(change: &FeedChange) -> String {
+    let value = |value: &Option<String>| value.as_deref().unwrap_or("(none)").to_string();
+    match change.kind {
+        FeedChangeKind::Title => format!(
+            "Title changed from \"{}\" to \"{}\"",
+            value(&change.old_value),
+            value(&change.new_value)
+        ),
+        FeedChangeKind::SelfLink => format!(
+            "Self link changed from {} to {}",
+            value(&change.old_value),
+            value(&change.new_value)
+        ),
+        FeedChangeKind::Redirect => match &change.new_value {
+            Some(target) => format!("Now redirects to {}", target),
+            None => format!("No longer redirects to {}", value(&change.old_value)),
+        },
+    }
+}
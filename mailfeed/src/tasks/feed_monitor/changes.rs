@@ -0,0 +1,208 @@
+use url::Url;
+
+use crate::db::DbConnection;
+use crate::models::{
+    feed::Feed,
+    feed_change::{FeedChange, FeedChangeKind, NewFeedChange},
+};
+
+/// Record `current` in the feed's change history if it differs from the
+/// last value seen for `kind`. Returns the change if it's significant
+/// enough to alert about, like the feed moving to another host. The first
+/// title and self link seen are recorded without an alert, but a feed
+/// that's redirected from the start is still reported.
+pub(super) fn observe(
+    conn: &mut DbConnection,
+    feed: &Feed,
+    kind: FeedChangeKind,
+    current: Option<&str>,
+    now: i64,
+) -> Option<FeedChange> {
+    let previous = match FeedChange::latest(conn, feed.id, kind) {
+        Ok(previous) => previous,
+        Err(e) => {
+            log::warn!("Error getting change history for {}: {:?}", feed.url, e);
+            return None;
+        }
+    };
+    let old_value = previous
+        .as_ref()
+        .and_then(|change| change.new_value.as_deref());
+    if same(kind, old_value, current) {
+        return None;
+    }
+
+    let first_seen = previous.is_none() && kind != FeedChangeKind::Redirect;
+    let significant = !first_seen && is_significant(kind, old_value, current, &feed.url);
+    let change = NewFeedChange {
+        feed_id: feed.id,
+        changed_at: now,
+        kind,
+        old_value,
+        new_value: current,
+        significant,
+    }
+    .insert(conn)?;
+    log::info!(
+        "Feed {} {:?} changed from {:?} to {:?}",
+        feed.url,
+        kind,
+        change.old_value,
+        change.new_value
+    );
+    significant.then_some(change)
+}
+
+fn same(kind: FeedChangeKind, old: Option<&str>, new: Option<&str>) -> bool {
+    match kind {
+        // ignore changes in case and spacing
+        FeedChangeKind::Title => old.map(normalize_title) == new.map(normalize_title),
+        FeedChangeKind::SelfLink | FeedChangeKind::Redirect => old == new,
+    }
+}
+
+fn is_significant(
+    kind: FeedChangeKind,
+    old: Option<&str>,
+    new: Option<&str>,
+    feed_url: &str,
+) -> bool {
+    match kind {
+        FeedChangeKind::Title => new.is_some(),
+        // a new path on the same site is routine, a new site isn't
+        FeedChangeKind::SelfLink => match (old, new) {
+            (Some(old), Some(new)) => host(old) != host(new),
+            _ => false,
+        },
+        // where the feed is actually served from, with no redirect
+        // meaning its own URL
+        FeedChangeKind::Redirect => host(old.unwrap_or(feed_url)) != host(new.unwrap_or(feed_url)),
+    }
+}
+
+fn normalize_title(title: &str) -> String {
+    title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn host(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    Some(host.trim_start_matches("www.").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::feed::NewFeed;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    const FEED_URL: &str = "https://example.com/feed.xml";
+
+    #[test]
+    fn test_same() {
+        assert!(same(
+            FeedChangeKind::Title,
+            Some("My  Blog "),
+            Some("my blog")
+        ));
+        assert!(!same(
+            FeedChangeKind::Title,
+            Some("My Blog"),
+            Some("Casino")
+        ));
+        assert!(!same(
+            FeedChangeKind::SelfLink,
+            Some("https://example.com/feed"),
+            Some("https://example.com/feed/")
+        ));
+    }
+
+    #[test]
+    fn test_is_significant() {
+        use FeedChangeKind::*;
+        assert!(is_significant(
+            Title,
+            Some("My Blog"),
+            Some("Casino"),
+            FEED_URL
+        ));
+        assert!(!is_significant(Title, Some("My Blog"), None, FEED_URL));
+
+        assert!(!is_significant(
+            SelfLink,
+            Some("https://example.com/feed"),
+            Some("https://www.example.com/rss"),
+            FEED_URL
+        ));
+        assert!(is_significant(
+            SelfLink,
+            Some("https://example.com/feed"),
+            Some("https://elsewhere.net/feed"),
+            FEED_URL
+        ));
+
+        // http -> https on the same host is fine, a new domain isn't
+        assert!(!is_significant(
+            Redirect,
+            None,
+            Some("https://example.com/feed.xml"),
+            "http://example.com/feed.xml"
+        ));
+        assert!(is_significant(
+            Redirect,
+            None,
+            Some("https://example.org/feed.xml"),
+            FEED_URL
+        ));
+        assert!(is_significant(
+            Redirect,
+            Some("https://example.org/feed.xml"),
+            None,
+            FEED_URL
+        ));
+    }
+
+    #[test]
+    fn test_observe() {
+        let mut conn = get_test_db_connection();
+        let feed = NewFeed {
+            url: FEED_URL,
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let mut observe = |kind, value| observe(&mut conn, &feed, kind, value, 0);
+
+        // first title is only recorded
+        assert_eq!(observe(FeedChangeKind::Title, Some("My Blog")), None);
+        assert_eq!(observe(FeedChangeKind::Title, Some("my blog")), None);
+        let change = observe(FeedChangeKind::Title, Some("Casino")).unwrap();
+        assert_eq!(change.old_value.as_deref(), Some("My Blog"));
+        assert_eq!(change.new_value.as_deref(), Some("Casino"));
+
+        assert_eq!(observe(FeedChangeKind::Redirect, None), None);
+        assert!(observe(
+            FeedChangeKind::Redirect,
+            Some("https://example.org/feed.xml")
+        )
+        .is_some());
+        assert_eq!(
+            observe(
+                FeedChangeKind::Redirect,
+                Some("https://example.org/feed.xml")
+            ),
+            None
+        );
+
+        assert_eq!(
+            FeedChange::get_for_feed(&mut conn, feed.id, 10)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+
+use crate::tasks::types::DNS_CACHE_TTL;
+
+/// A failed host name lookup. Kept as its own type so fetch errors caused by
+/// DNS can be told apart from other connection errors.
+#[derive(thiserror::Error, Debug)]
+#[error("could not resolve {host}: {source}")]
+pub(super) struct ResolveError {
+    host: String,
+    source: io::Error,
+}
+
+/// Resolved addresses and when they were looked up, by host
+type Entries = HashMap<String, (Instant, Vec<SocketAddr>)>;
+
+/// Resolves feed hosts through the system resolver, reusing addresses for
+/// `DNS_CACHE_TTL` since many feeds share a host and are fetched together.
+/// Failed lookups aren't cached.
+#[derive(Clone, Default)]
+pub(super) struct DnsCache {
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl DnsCache {
+    fn get(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(host) {
+            Some((resolved, addrs)) if resolved.elapsed() < DNS_CACHE_TTL => Some(addrs.clone()),
+            _ => None,
+        }
+    }
+
+    fn insert(&self, host: String, addrs: Vec<SocketAddr>) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (resolved, _)| resolved.elapsed() < DNS_CACHE_TTL);
+        entries.insert(host, (Instant::now(), addrs));
+    }
+}
+
+impl Resolve for DnsCache {
+    fn resolve(&self, name: Name) -> Resolving {
+        let cache = self.clone();
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            if let Some(addrs) = cache.get(&host) {
+                return Ok(Box::new(addrs.into_iter()) as Addrs);
+            }
+
+            // the port is replaced with the URL's when connecting
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .map_err(|source| ResolveError {
+                    host: host.clone(),
+                    source,
+                })?
+                .collect();
+            log::debug!("Resolved {} to {:?}", host, addrs);
+            cache.insert(host, addrs.clone());
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let cache = DnsCache::default();
+        assert_eq!(cache.get("example.com"), None);
+
+        let addrs: Vec<SocketAddr> = vec!["93.184.216.34:0".parse().unwrap()];
+        cache.insert("example.com".to_string(), addrs.clone());
+        assert_eq!(cache.get("example.com"), Some(addrs));
+        assert_eq!(cache.get("example.org"), None);
+
+        // clones share entries, since reqwest holds its own copy
+        assert!(cache.clone().get("example.com").is_some());
+    }
+}
@@ -0,0 +1,141 @@
+use std::error::Error as StdError;
+
+use reqwest::StatusCode;
+use tokio::time::Duration;
+
+use super::{dns_cache::ResolveError, poll_interval::PollBounds};
+use crate::models::feed::{Feed, FeedErrorKind};
+
+/// Why fetching or parsing a feed failed
+#[derive(Debug, PartialEq)]
+pub(super) struct FetchError {
+    pub kind: FeedErrorKind,
+    pub message: String,
+}
+
+impl FetchError {
+    pub(super) fn from_reqwest(e: &reqwest::Error) -> Self {
+        let kind = if e.is_timeout() {
+            FeedErrorKind::Timeout
+        } else if caused_by_dns(e) {
+            FeedErrorKind::Dns
+        } else {
+            FeedErrorKind::Connection
+        };
+        FetchError {
+            kind,
+            message: e.to_string(),
+        }
+    }
+
+    pub(super) fn from_status(status: StatusCode) -> Self {
+        FetchError {
+            kind: FeedErrorKind::Http,
+            message: status.to_string(),
+        }
+    }
+
+    pub(super) fn from_parse(e: &feed_rs::parser::ParseFeedError) -> Self {
+        FetchError {
+            kind: FeedErrorKind::Parse,
+            message: e.to_string(),
+        }
+    }
+
+    /// How long to wait before trying the feed again. A transient error is
+    /// retried as soon as allowed the first time it happens; a repeat of it,
+    /// or any other error, doubles the feed's interval up to the max.
+    pub(super) fn retry_after(&self, feed: &Feed, bounds: &PollBounds) -> Duration {
+        if self.kind.is_transient() && feed.error_kind != self.kind {
+            return bounds.min;
+        }
+        let current = Duration::from_secs(feed.poll_interval.max(0) as u64);
+        (current * 2).clamp(bounds.min, bounds.max)
+    }
+}
+
+fn caused_by_dns(e: &reqwest::Error) -> bool {
+    let mut source = e.source();
+    while let Some(err) = source {
+        if err.is::<ResolveError>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::test_feed;
+
+    const HOUR: u64 = 60 * 60;
+
+    fn bounds() -> PollBounds {
+        PollBounds {
+            min: Duration::from_secs(5 * 60),
+            max: Duration::from_secs(24 * HOUR),
+        }
+    }
+
+    fn feed(poll_interval: u64, error_kind: FeedErrorKind) -> Feed {
+        Feed {
+            poll_interval: poll_interval as i32,
+            error_kind,
+            ..test_feed()
+        }
+    }
+
+    fn error(kind: FeedErrorKind) -> FetchError {
+        FetchError {
+            kind,
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_from_status() {
+        assert_eq!(
+            FetchError::from_status(StatusCode::NOT_FOUND),
+            FetchError {
+                kind: FeedErrorKind::Http,
+                message: "404 Not Found".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_transient_errors_retry_soon_once() {
+        let dns = error(FeedErrorKind::Dns);
+        assert_eq!(
+            dns.retry_after(&feed(HOUR, FeedErrorKind::None), &bounds()),
+            bounds().min
+        );
+        assert_eq!(
+            dns.retry_after(&feed(HOUR, FeedErrorKind::Timeout), &bounds()),
+            bounds().min
+        );
+        assert_eq!(
+            dns.retry_after(&feed(HOUR, FeedErrorKind::Dns), &bounds()),
+            Duration::from_secs(2 * HOUR)
+        );
+    }
+
+    #[test]
+    fn test_other_errors_back_off() {
+        let http = error(FeedErrorKind::Http);
+        assert_eq!(
+            http.retry_after(&feed(HOUR, FeedErrorKind::None), &bounds()),
+            Duration::from_secs(2 * HOUR)
+        );
+        assert_eq!(
+            http.retry_after(&feed(0, FeedErrorKind::None), &bounds()),
+            bounds().min
+        );
+        assert_eq!(
+            http.retry_after(&feed(20 * HOUR, FeedErrorKind::Http), &bounds()),
+            bounds().max
+        );
+    }
+}
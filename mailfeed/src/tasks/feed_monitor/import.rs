@@ -0,0 +1,221 @@
+use reqwest::Client;
+
+use super::{
+    fetch_error::FetchError,
+    opml::OpmlFeed,
+    runner::{fetch, http_client},
+};
+use crate::{
+    db::DbConnection,
+    models::{
+        feed::{Feed, NewFeed},
+        ids::UserId,
+        onboarding::{Onboarding, OnboardingStep},
+        quotas::Quotas,
+        subscription::{Frequency, NewSubscription, Subscription},
+    },
+    tasks::{
+        jobs::{JobResult, Jobs},
+        types::IMPORT_FETCH_DELAY,
+    },
+    DbPool,
+};
+
+/// Subscribe the user to each feed in turn, recording how each went on the
+/// job. Feeds that aren't in the database yet are fetched first to check
+/// they work, waiting between fetches so a large import doesn't flood
+/// servers or the network.
+pub async fn run(
+    pool: DbPool,
+    jobs: Jobs,
+    job_id: u64,
+    user_id: UserId,
+    feeds: Vec<OpmlFeed>,
+    frequency: Frequency,
+) {
+    let http_client = http_client();
+    let mut added = 0;
+    for feed in feeds {
+        let result = match pool.get() {
+            Ok(mut conn) => {
+                let (result, fetched) =
+                    import_feed(&mut conn, &http_client, user_id, &feed, &frequency).await;
+                if fetched {
+                    tokio::time::sleep(IMPORT_FETCH_DELAY).await;
+                }
+                result
+            }
+            Err(e) => {
+                log::error!("Error getting DB connection: {:?}", e);
+                JobResult::failed(&feed.url, "Error connecting to database")
+            }
+        };
+        // feeds the user was already subscribed to succeed with a message
+        if result.success && result.message.is_none() {
+            added += 1;
+        }
+        jobs.record(job_id, result, chrono::Utc::now().timestamp());
+    }
+    log::info!("Imported {} feeds for user {}", added, user_id);
+
+    if added > 0 {
+        let mut conn = match pool.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("Error getting DB connection: {:?}", e);
+                return;
+            }
+        };
+        if let Err(e) = Onboarding::complete(&mut conn, user_id, OnboardingStep::AddFeed) {
+            log::warn!("Error updating onboarding for user {}: {:?}", user_id, e);
+        }
+    }
+}
+
+/// Also returns whether the feed had to be fetched
+async fn import_feed(
+    conn: &mut DbConnection,
+    http_client: &Client,
+    user_id: UserId,
+    opml_feed: &OpmlFeed,
+    frequency: &Frequency,
+) -> (JobResult, bool) {
+    let url = opml_feed.url.as_str();
+    if !matches!(reqwest::Url::parse(url), Ok(parsed) if matches!(parsed.scheme(), "http" | "https"))
+    {
+        return (JobResult::failed(url, "Invalid feed URL"), false);
+    }
+
+    let existing_feed = Feed::get_by_url(conn, url);
+    if let Some(feed) = &existing_feed {
+        if feed.credentials.is_some() {
+            let message = "Private feed, subscribe to it with its credentials";
+            return (JobResult::failed(url, message), false);
+        }
+        match Subscription::get_for_user_and_feed(conn, user_id, feed.id) {
+            Ok(None) => {}
+            Ok(Some(_)) => {
+                let result = JobResult {
+                    message: Some("Already subscribed".to_string()),
+                    ..JobResult::ok(url)
+                };
+                return (result, false);
+            }
+            Err(_) => return (JobResult::failed(url, "Error getting subscriptions"), false),
+        }
+    }
+
+    let realtime = frequency.is_realtime();
+    if let Err(e) =
+        Quotas::load(conn).check_new_subscription(conn, user_id, realtime, existing_feed.is_none())
+    {
+        return (JobResult::failed(url, e.to_string()), false);
+    }
+
+    let (feed, fetched) = match existing_feed {
+        Some(feed) => (feed, false),
+        None => {
+            let title = match fetch_title(http_client, url).await {
+                Ok(title) => title,
+                Err(e) => return (JobResult::failed(url, e.message), true),
+            };
+            let new_feed = NewFeed {
+                url,
+                title: title.unwrap_or_default(),
+                ..Default::default()
+            };
+            match new_feed.insert(conn) {
+                Some(feed) => (feed, true),
+                None => return (JobResult::failed(url, "Error creating feed"), true),
+            }
+        }
+    };
+
+    let new_sub = NewSubscription {
+        user_id,
+        feed_id: feed.id,
+        frequency: frequency.clone(),
+        friendly_name: opml_feed.title.clone().unwrap_or(feed.title),
+        ..Default::default()
+    };
+    match new_sub.insert(conn) {
+        Some(_) => (JobResult::ok(url), fetched),
+        None => (
+            JobResult::failed(url, "Error creating subscription"),
+            fetched,
+        ),
+    }
+}
+
+/// Fetch and parse the feed to check it works, returning its title
+async fn fetch_title(http_client: &Client, url: &str) -> Result<Option<String>, FetchError> {
+    let fetched = fetch(http_client, url).await?;
+    let parsed =
+        feed_rs::parser::parse(fetched.body.as_bytes()).map_err(|e| FetchError::from_parse(&e))?;
+    Ok(parsed.title.map(|title| title.content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::get_test_db_connection;
+
+    #[actix_web::test]
+    async fn test_import_feed_skips_without_fetching() {
+        let mut conn = get_test_db_connection();
+        let http_client = http_client();
+        let feed = NewFeed {
+            url: "https://example.com/feed.xml",
+            title: "Example".to_string(),
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let opml_feed = OpmlFeed {
+            url: feed.url.clone(),
+            title: None,
+        };
+
+        // known feeds aren't fetched again
+        let (result, fetched) = import_feed(
+            &mut conn,
+            &http_client,
+            UserId(1),
+            &opml_feed,
+            &Frequency::Daily,
+        )
+        .await;
+        assert_eq!(result, JobResult::ok(&feed.url));
+        assert!(!fetched);
+        let sub = Subscription::get_for_user_and_feed(&mut conn, UserId(1), feed.id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(sub.friendly_name, "Example");
+
+        let (result, _) = import_feed(
+            &mut conn,
+            &http_client,
+            UserId(1),
+            &opml_feed,
+            &Frequency::Daily,
+        )
+        .await;
+        assert!(result.success);
+        assert_eq!(result.message.as_deref(), Some("Already subscribed"));
+
+        let bad_url = OpmlFeed {
+            url: "ftp://example.com/feed.xml".to_string(),
+            title: None,
+        };
+        let (result, fetched) = import_feed(
+            &mut conn,
+            &http_client,
+            UserId(1),
+            &bad_url,
+            &Frequency::Daily,
+        )
+        .await;
+        assert_eq!(result, JobResult::failed(&bad_url.url, "Invalid feed URL"));
+        assert!(!fetched);
+    }
+}
@@ -0,0 +1,139 @@
+use feed_rs::model::Entry;
+
+use crate::tasks::html_to_text::html_links;
+
+/// An item's article link, and its discussion page if it has one
+#[derive(Debug, PartialEq)]
+pub(super) struct ItemLinks {
+    pub link: String,
+    pub comments: Option<String>,
+}
+
+/// Find the article and comments links for a feed entry. Aggregators don't
+/// agree on where these go:
+/// - Atom feeds may have a `rel="replies"` link to the comments
+/// - Hacker News and Lobsters link to the article, with a "Comments" link
+///   in the description
+/// - Reddit links to the comments, with a "[link]" to the article in the
+///   description
+pub(super) fn item_links(entry: &Entry) -> Option<ItemLinks> {
+    let link = entry.links.first()?.href.clone();
+
+    let html = entry
+        .summary
+        .as_ref()
+        .map(|summary| summary.content.as_str())
+        .or_else(|| entry.content.as_ref().and_then(|c| c.body.as_deref()))
+        .unwrap_or_default();
+    let anchors = html_links(html);
+    let labelled = |label: &str| {
+        anchors
+            .iter()
+            .find(|(_, text)| {
+                text.trim_matches(|c: char| c == '[' || c == ']' || c.is_whitespace())
+                    .to_lowercase()
+                    .starts_with(label)
+            })
+            .map(|(href, _)| href.clone())
+    };
+
+    let comments = entry
+        .links
+        .iter()
+        .find(|l| l.rel.as_deref() == Some("replies"))
+        .map(|l| l.href.clone())
+        .or_else(|| labelled("comments"));
+
+    match labelled("link") {
+        Some(article) if article != link => Some(ItemLinks {
+            link: article,
+            comments: comments.or(Some(link)),
+        }),
+        _ => Some(ItemLinks { link, comments }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_entry(xml: &str) -> Entry {
+        feed_rs::parser::parse(xml.as_bytes())
+            .unwrap()
+            .entries
+            .remove(0)
+    }
+
+    #[test]
+    fn test_plain_item() {
+        let entry = first_entry(
+            r#"<rss version="2.0"><channel><title>t</title><item>
+                <link>https://example.com/post</link>
+                <description>Just a post</description>
+            </item></channel></rss>"#,
+        );
+        assert_eq!(
+            item_links(&entry),
+            Some(ItemLinks {
+                link: "https://example.com/post".to_string(),
+                comments: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_hacker_news_item() {
+        let entry = first_entry(
+            r#"<rss version="2.0"><channel><title>Hacker News</title><item>
+                <link>https://example.com/article</link>
+                <comments>https://news.ycombinator.com/item?id=1</comments>
+                <description><![CDATA[<a href="https://news.ycombinator.com/item?id=1">Comments</a>]]></description>
+            </item></channel></rss>"#,
+        );
+        assert_eq!(
+            item_links(&entry),
+            Some(ItemLinks {
+                link: "https://example.com/article".to_string(),
+                comments: Some("https://news.ycombinator.com/item?id=1".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_reddit_item() {
+        let entry = first_entry(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>r/rust</title><entry>
+                <id>t3_1</id><title>post</title>
+                <link href="https://www.reddit.com/r/rust/comments/1/post/" />
+                <content type="html">submitted by u/someone &lt;br/&gt;
+                    &lt;span&gt;&lt;a href="https://example.com/article"&gt;[link]&lt;/a&gt;&lt;/span&gt;
+                    &lt;span&gt;&lt;a href="https://www.reddit.com/r/rust/comments/1/post/"&gt;[comments]&lt;/a&gt;&lt;/span&gt;</content>
+            </entry></feed>"#,
+        );
+        assert_eq!(
+            item_links(&entry),
+            Some(ItemLinks {
+                link: "https://example.com/article".to_string(),
+                comments: Some("https://www.reddit.com/r/rust/comments/1/post/".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_atom_replies_link() {
+        let entry = first_entry(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>blog</title><entry>
+                <id>1</id><title>post</title>
+                <link href="https://example.com/post" />
+                <link rel="replies" href="https://example.com/post#comments" />
+            </entry></feed>"#,
+        );
+        assert_eq!(
+            item_links(&entry),
+            Some(ItemLinks {
+                link: "https://example.com/post".to_string(),
+                comments: Some("https://example.com/post#comments".to_string()),
+            })
+        );
+    }
+}
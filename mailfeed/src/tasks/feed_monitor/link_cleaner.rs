@@ -0,0 +1,156 @@
+use std::env;
+
+use url::Url;
+
+/// Query parameters that only exist to track where a click came from.
+/// A trailing `*` matches any parameter with that prefix.
+const DEFAULT_TRACKING_PARAMS: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "twclid",
+    "igshid", "mc_cid", "mc_eid", "_hsenc", "_hsmi", "mkt_tok",
+];
+
+/// Strips tracking parameters from item links before they are stored
+#[derive(Debug)]
+pub(super) struct LinkCleaner {
+    enabled: bool,
+    params: Vec<String>,
+}
+
+impl Default for LinkCleaner {
+    fn default() -> Self {
+        LinkCleaner {
+            enabled: false,
+            params: DEFAULT_TRACKING_PARAMS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl LinkCleaner {
+    pub(super) fn from_env() -> Self {
+        let enabled = matches!(
+            env::var("MF_STRIP_TRACKING_PARAMS")
+                .unwrap_or_default()
+                .to_lowercase()
+                .as_str(),
+            "1" | "true" | "yes" | "on"
+        );
+        let cleaner = match env::var("MF_TRACKING_PARAMS") {
+            Ok(params) => LinkCleaner {
+                enabled,
+                params: params
+                    .split(',')
+                    .map(|p| p.trim().to_lowercase())
+                    .filter(|p| !p.is_empty())
+                    .collect(),
+            },
+            Err(_) => LinkCleaner {
+                enabled,
+                ..Default::default()
+            },
+        };
+        if cleaner.enabled {
+            log::info!(
+                "Stripping tracking parameters from item links: {:?}",
+                cleaner.params
+            );
+        }
+        cleaner
+    }
+
+    fn is_tracking_param(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.params
+            .iter()
+            .any(|param| match param.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == *param,
+            })
+    }
+
+    /// Remove tracking parameters from a link. Links that can't be parsed,
+    /// or that have nothing to remove, are returned unchanged.
+    pub(super) fn clean(&self, link: &str) -> String {
+        if !self.enabled {
+            return link.to_string();
+        }
+        let mut url = match Url::parse(link) {
+            Ok(url) => url,
+            Err(_) => return link.to_string(),
+        };
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        let kept: Vec<&(String, String)> = pairs
+            .iter()
+            .filter(|(name, _)| !self.is_tracking_param(name))
+            .collect();
+        if kept.len() == pairs.len() {
+            return link.to_string();
+        }
+
+        if kept.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut()
+                .clear()
+                .extend_pairs(kept.iter().map(|(name, value)| (name, value)));
+        }
+        url.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> LinkCleaner {
+        LinkCleaner {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        let link = "https://example.com/post?utm_source=rss";
+        assert_eq!(LinkCleaner::default().clean(link), link);
+    }
+
+    #[test]
+    fn test_strips_tracking_params() {
+        let cleaner = enabled();
+        assert_eq!(
+            cleaner.clean("https://example.com/post?utm_source=rss&utm_medium=feed"),
+            "https://example.com/post"
+        );
+        assert_eq!(
+            cleaner.clean("https://example.com/post?id=3&FBCLID=abc&page=2#comments"),
+            "https://example.com/post?id=3&page=2#comments"
+        );
+    }
+
+    #[test]
+    fn test_leaves_clean_links_alone() {
+        let cleaner = enabled();
+        for link in [
+            "https://example.com/post?q=a%20b",
+            "https://example.com/post",
+            "not a url",
+        ] {
+            assert_eq!(cleaner.clean(link), link);
+        }
+    }
+
+    #[test]
+    fn test_custom_params() {
+        let cleaner = LinkCleaner {
+            enabled: true,
+            params: vec!["ref".to_string(), "src_*".to_string()],
+        };
+        assert_eq!(
+            cleaner.clean("https://example.com/?ref=rss&src_a=1&utm_source=x"),
+            "https://example.com/?utm_source=x"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use quick_xml::{events::Event, Reader};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("Not a valid OPML file: {0}")]
+    Invalid(String),
+    #[error("No feeds found in the OPML file")]
+    NoFeeds,
+}
+
+/// A feed listed in an OPML file
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpmlFeed {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+/// The feeds in an OPML subscription list, in order and without
+/// duplicates. Categories are flattened, since subscriptions don't have
+/// them.
+pub fn parse(opml: &str) -> Result<Vec<OpmlFeed>, Error> {
+    let mut reader = Reader::from_str(opml);
+    let mut is_opml = false;
+    let mut feeds: Vec<OpmlFeed> = Vec::new();
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| Error::Invalid(e.to_string()))?;
+        let element = match event {
+            Event::Start(element) | Event::Empty(element) => element,
+            Event::Eof => break,
+            _ => continue,
+        };
+        match element.name().as_ref() {
+            b"opml" => is_opml = true,
+            b"outline" => {
+                let mut url = None;
+                let mut title = None;
+                let mut text = None;
+                for attr in element.attributes() {
+                    let attr = attr.map_err(|e| Error::Invalid(e.to_string()))?;
+                    let value = attr
+                        .decode_and_unescape_value(&reader)
+                        .map_err(|e| Error::Invalid(e.to_string()))?
+                        .trim()
+                        .to_string();
+                    match attr.key.as_ref().to_ascii_lowercase().as_slice() {
+                        b"xmlurl" => url = Some(value),
+                        b"title" => title = Some(value),
+                        b"text" => text = Some(value),
+                        _ => {}
+                    }
+                }
+                // outlines without a feed URL are categories
+                let url = match url.filter(|url| !url.is_empty()) {
+                    Some(url) => url,
+                    None => continue,
+                };
+                if feeds.iter().any(|feed| feed.url == url) {
+                    continue;
+                }
+                feeds.push(OpmlFeed {
+                    url,
+                    title: title.or(text).filter(|title| !title.is_empty()),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    if !is_opml {
+        return Err(Error::Invalid("missing <opml> element".to_string()));
+    }
+    if feeds.is_empty() {
+        return Err(Error::NoFeeds);
+    }
+    Ok(feeds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let opml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head><title>Subscriptions</title></head>
+  <body>
+    <outline text="Tech" title="Tech">
+      <outline type="rss" text="Hacker News" xmlUrl="https://news.ycombinator.com/rss"/>
+      <outline type="rss" text="Lobsters" title="Lobste.rs" xmlUrl="https://lobste.rs/rss"/>
+    </outline>
+    <outline type="rss" xmlUrl="https://example.com/feed?a=1&amp;b=2"/>
+    <outline type="rss" text="Hacker News again" xmlUrl="https://news.ycombinator.com/rss"/>
+  </body>
+</opml>"#;
+        assert_eq!(
+            parse(opml),
+            Ok(vec![
+                OpmlFeed {
+                    url: "https://news.ycombinator.com/rss".to_string(),
+                    title: Some("Hacker News".to_string()),
+                },
+                OpmlFeed {
+                    url: "https://lobste.rs/rss".to_string(),
+                    title: Some("Lobste.rs".to_string()),
+                },
+                OpmlFeed {
+                    url: "https://example.com/feed?a=1&b=2".to_string(),
+                    title: None,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse(r#"<opml version="2.0"><body><outline text="Empty"/></body></opml>"#),
+            Err(Error::NoFeeds)
+        );
+        assert!(matches!(
+            parse(r#"<rss><channel><title>Not OPML</title></channel></rss>"#),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            parse(r#"<opml><body><outline xmlUrl="https://example.com/feed"></body>"#),
+            Err(Error::Invalid(_))
+        ));
+    }
+}
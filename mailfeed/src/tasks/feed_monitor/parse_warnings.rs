@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+
+use feed_rs::model::Feed;
+
+use crate::models::feed::{ParseWarning, ParseWarningKind, ParseWarnings};
+
+/// What's odd about a feed that parsed, so users can tell why its items
+/// look wrong. `body` is the text it was parsed from.
+pub(super) fn parse_warnings(body: &str, parsed: &Feed) -> ParseWarnings {
+    let mut ids = HashSet::new();
+    let duplicate_ids = parsed
+        .entries
+        .iter()
+        .filter(|entry| !entry.id.is_empty() && !ids.insert(entry.id.as_str()))
+        .count();
+    let missing_dates = parsed
+        .entries
+        .iter()
+        .filter(|entry| entry.published.is_none())
+        .count();
+    let missing_links = parsed
+        .entries
+        .iter()
+        .filter(|entry| entry.links.is_empty())
+        .count();
+    // undecodable bytes were replaced when the body was read as text
+    let invalid_encoding = body.contains(char::REPLACEMENT_CHARACTER);
+
+    let warnings = [
+        (ParseWarningKind::MissingDates, missing_dates),
+        (ParseWarningKind::DuplicateIds, duplicate_ids),
+        (ParseWarningKind::MissingLinks, missing_links),
+    ]
+    .into_iter()
+    .filter(|(_, items)| *items > 0)
+    .map(|(kind, items)| ParseWarning::new(kind, items))
+    .chain(invalid_encoding.then(|| ParseWarning::new(ParseWarningKind::InvalidEncoding, 0)))
+    .collect();
+    ParseWarnings(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(body: &str) -> Vec<(ParseWarningKind, usize)> {
+        let parsed = feed_rs::parser::parse(body.as_bytes()).unwrap();
+        parse_warnings(body, &parsed)
+            .0
+            .into_iter()
+            .map(|warning| (warning.kind, warning.items))
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_feed_has_no_warnings() {
+        let rss = r#"<rss version="2.0"><channel><title>Example</title>
+            <item><guid>1</guid><link>https://example.com/1</link><pubDate>Fri, 16 Oct 2026 10:00:00 GMT</pubDate></item>
+            <item><guid>2</guid><link>https://example.com/2</link><pubDate>Fri, 16 Oct 2026 11:00:00 GMT</pubDate></item>
+            </channel></rss>"#;
+        assert_eq!(kinds(rss), vec![]);
+    }
+
+    #[test]
+    fn test_warnings() {
+        let rss = "<rss version=\"2.0\"><channel><title>Caf\u{FFFD}</title>
+            <item><guid>1</guid><link>https://example.com/1</link></item>
+            <item><guid>1</guid><link>https://example.com/2</link><pubDate>Fri, 16 Oct 2026 11:00:00 GMT</pubDate></item>
+            <item><guid>3</guid><title>No link</title></item>
+            </channel></rss>";
+        assert_eq!(
+            kinds(rss),
+            vec![
+                (ParseWarningKind::MissingDates, 2),
+                (ParseWarningKind::DuplicateIds, 1),
+                (ParseWarningKind::MissingLinks, 1),
+                (ParseWarningKind::InvalidEncoding, 0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use std::env;
+
+use chrono::{DateTime, Utc};
+use tokio::time::Duration;
+
+use crate::models::feed::Feed;
+
+const DEFAULT_MIN_MINUTES: u64 = 5;
+const DEFAULT_MAX_MINUTES: u64 = 24 * 60;
+/// How many of the newest items are used to estimate posting frequency
+const OBSERVED_ITEMS: usize = 10;
+
+/// Admin limits on how often a feed may be polled
+#[derive(Debug, PartialEq)]
+pub(super) struct PollBounds {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl Default for PollBounds {
+    fn default() -> Self {
+        PollBounds {
+            min: Duration::from_secs(DEFAULT_MIN_MINUTES * 60),
+            max: Duration::from_secs(DEFAULT_MAX_MINUTES * 60),
+        }
+    }
+}
+
+impl PollBounds {
+    pub(super) fn from_env() -> Self {
+        let minutes = |name: &str, default: u64| match env::var(name) {
+            Ok(value) => match value.parse::<u64>() {
+                Ok(minutes) if minutes > 0 => minutes,
+                _ => {
+                    log::warn!("Invalid {} '{}', using default of {}", name, value, default);
+                    default
+                }
+            },
+            Err(_) => default,
+        };
+        let min = minutes("MF_FEED_POLL_MIN_MINUTES", DEFAULT_MIN_MINUTES);
+        let max = minutes("MF_FEED_POLL_MAX_MINUTES", DEFAULT_MAX_MINUTES).max(min);
+        log::info!("Polling feeds every {} to {} minutes", min, max);
+        PollBounds {
+            min: Duration::from_secs(min * 60),
+            max: Duration::from_secs(max * 60),
+        }
+    }
+}
+
+/// How long to wait before fetching a feed again. Polls at the faster of
+/// the feed's `sy:updatePeriod` and half its typical gap between posts,
+/// but never more often than its `<ttl>` allows, all within the admin's
+/// bounds. Feeds with no hints are polled as often as allowed.
+pub(super) fn poll_interval(
+    parsed: &feed_rs::model::Feed,
+    body: &str,
+    bounds: &PollBounds,
+) -> Duration {
+    let observed = observed_gap(&parsed.entries).map(|gap| gap / 2);
+    let expected = match (syndication_period(body), observed) {
+        (Some(a), Some(b)) => a.min(b),
+        (a, b) => a.or(b).unwrap_or(bounds.min),
+    };
+    let ttl = parsed
+        .ttl
+        .map(|minutes| Duration::from_secs(minutes as u64 * 60))
+        .unwrap_or_default();
+    expected.max(ttl).clamp(bounds.min, bounds.max)
+}
+
+/// How long to wait after a check that found the feed unchanged. Each
+/// unchanged check in a row stretches the interval by half, so feeds that
+/// rarely update are polled less and less often, up to the max. A feed that
+/// was failing starts over from the min, since its interval was backed off
+/// for errors rather than set from its posts.
+pub(super) fn unchanged_interval(feed: &Feed, bounds: &PollBounds) -> Duration {
+    if feed.failing_since().is_some() {
+        return bounds.min;
+    }
+    let current = Duration::from_secs(feed.poll_interval.max(0) as u64);
+    (current * 3 / 2).clamp(bounds.min, bounds.max)
+}
+
+/// Median time between the newest items' publication dates
+fn observed_gap(entries: &[feed_rs::model::Entry]) -> Option<Duration> {
+    let mut dates: Vec<DateTime<Utc>> = entries
+        .iter()
+        .filter_map(|entry| entry.published.or(entry.updated))
+        .collect();
+    dates.sort_unstable_by(|a, b| b.cmp(a));
+    dates.truncate(OBSERVED_ITEMS);
+
+    let mut gaps: Vec<i64> = dates
+        .windows(2)
+        .map(|pair| (pair[0] - pair[1]).num_seconds())
+        .collect();
+    if gaps.is_empty() {
+        return None;
+    }
+    gaps.sort_unstable();
+    Some(Duration::from_secs(gaps[gaps.len() / 2].max(0) as u64))
+}
+
+/// The RSS syndication module's `sy:updatePeriod` divided by
+/// `sy:updateFrequency`. feed-rs doesn't parse these, so they're read from
+/// the raw body.
+fn syndication_period(body: &str) -> Option<Duration> {
+    let period = match element_text(body, "sy:updatePeriod")?.as_str() {
+        "hourly" => 60 * 60,
+        "daily" => 24 * 60 * 60,
+        "weekly" => 7 * 24 * 60 * 60,
+        "monthly" => 30 * 24 * 60 * 60,
+        "yearly" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let frequency = element_text(body, "sy:updateFrequency")
+        .and_then(|f| f.parse::<u64>().ok())
+        .filter(|f| *f > 0)
+        .unwrap_or(1);
+    Some(Duration::from_secs(period / frequency))
+}
+
+fn element_text(body: &str, name: &str) -> Option<String> {
+    let start = body.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + body[start..].find(&format!("</{}>", name))?;
+    Some(body[start..end].trim().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::test_feed;
+
+    const HOUR: u64 = 60 * 60;
+
+    fn bounds() -> PollBounds {
+        PollBounds {
+            min: Duration::from_secs(5 * 60),
+            max: Duration::from_secs(48 * HOUR),
+        }
+    }
+
+    fn rss(channel: &str, items: &[&str]) -> String {
+        let items: String = items
+            .iter()
+            .map(|date| format!("<item><title>t</title><pubDate>{}</pubDate></item>", date))
+            .collect();
+        format!(
+            r#"<rss version="2.0" xmlns:sy="http://purl.org/rss/1.0/modules/syndication/"><channel><title>t</title>{}{}</channel></rss>"#,
+            channel, items
+        )
+    }
+
+    fn interval(body: &str) -> Duration {
+        let parsed = feed_rs::parser::parse(body.as_bytes()).unwrap();
+        poll_interval(&parsed, body, &bounds())
+    }
+
+    #[test]
+    fn test_no_hints_uses_min() {
+        assert_eq!(interval(&rss("", &[])), bounds().min);
+    }
+
+    #[test]
+    fn test_observed_frequency() {
+        // daily posts are checked every 12 hours
+        let body = rss(
+            "",
+            &[
+                "Wed, 04 Oct 2023 08:00:00 GMT",
+                "Tue, 03 Oct 2023 08:00:00 GMT",
+                "Mon, 02 Oct 2023 08:00:00 GMT",
+            ],
+        );
+        assert_eq!(interval(&body), Duration::from_secs(12 * HOUR));
+
+        // monthly posts hit the max
+        let body = rss(
+            "",
+            &[
+                "Sun, 01 Oct 2023 08:00:00 GMT",
+                "Fri, 01 Sep 2023 08:00:00 GMT",
+            ],
+        );
+        assert_eq!(interval(&body), bounds().max);
+    }
+
+    #[test]
+    fn test_syndication_hint() {
+        let body = rss(
+            "<sy:updatePeriod>hourly</sy:updatePeriod><sy:updateFrequency>2</sy:updateFrequency>",
+            &[],
+        );
+        assert_eq!(interval(&body), Duration::from_secs(HOUR / 2));
+
+        // the faster of the hint and observed frequency wins
+        let body = rss(
+            "<sy:updatePeriod>daily</sy:updatePeriod>",
+            &[
+                "Mon, 02 Oct 2023 10:00:00 GMT",
+                "Mon, 02 Oct 2023 08:00:00 GMT",
+            ],
+        );
+        assert_eq!(interval(&body), Duration::from_secs(HOUR));
+    }
+
+    #[test]
+    fn test_unchanged_interval() {
+        let mut feed = Feed {
+            title: "Feed".to_string(),
+            poll_interval: (2 * HOUR) as i32,
+            ..test_feed()
+        };
+        assert_eq!(
+            unchanged_interval(&feed, &bounds()),
+            Duration::from_secs(3 * HOUR)
+        );
+        feed.poll_interval = (40 * HOUR) as i32;
+        assert_eq!(unchanged_interval(&feed, &bounds()), bounds().max);
+        feed.poll_interval = 0;
+        assert_eq!(unchanged_interval(&feed, &bounds()), bounds().min);
+
+        // back from backing off after errors
+        feed.poll_interval = (40 * HOUR) as i32;
+        feed.error_time = 1000;
+        assert_eq!(unchanged_interval(&feed, &bounds()), bounds().min);
+    }
+
+    #[test]
+    fn test_ttl_is_a_floor() {
+        let body = rss(
+            "<ttl>180</ttl><sy:updatePeriod>hourly</sy:updatePeriod>",
+            &[],
+        );
+        assert_eq!(interval(&body), Duration::from_secs(3 * HOUR));
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::Notify;
+
+use crate::{
+    models::ids::{FeedId, UserId},
+    tasks::jobs::{Job, JobKind, JobResult, Jobs},
+};
+
+/// Feeds queued for fetching right away rather than when they're next due,
+/// shared between the API and the feed monitor. Anything still queued is
+/// lost on restart.
+#[derive(Clone)]
+pub struct RefreshJobs {
+    jobs: Jobs,
+    queue: Arc<Mutex<VecDeque<(u64, FeedId)>>>,
+    notify: Arc<Notify>,
+}
+
+impl RefreshJobs {
+    pub fn new(jobs: Jobs) -> Self {
+        RefreshJobs {
+            jobs,
+            queue: Arc::default(),
+            notify: Arc::default(),
+        }
+    }
+
+    /// Queue the feeds and wake the feed monitor. Only one refresh runs at a
+    /// time, so while one is running it's returned instead.
+    pub fn enqueue(&self, feed_ids: &[FeedId], user_id: UserId, now: i64) -> Job {
+        if let Some(running) = self.jobs.running(JobKind::RefreshFeeds, None) {
+            return running;
+        }
+        let job = self
+            .jobs
+            .start(JobKind::RefreshFeeds, user_id, feed_ids.len(), now);
+        self.queue
+            .lock()
+            .unwrap()
+            .extend(feed_ids.iter().map(|feed_id| (job.id, *feed_id)));
+        self.notify.notify_one();
+        job
+    }
+
+    /// The next queued feed, and the job it's for
+    pub(super) fn next(&self) -> Option<(u64, FeedId)> {
+        self.queue.lock().unwrap().pop_front()
+    }
+
+    pub(super) fn record(&self, id: u64, result: JobResult, now: i64) {
+        self.jobs.record(id, result, now);
+    }
+
+    /// Wait until feeds are queued, or the timeout passes
+    pub(super) async fn wait(&self, timeout: tokio::time::Duration) {
+        let _ = tokio::time::timeout(timeout, self.notify.notified()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queues_feeds() {
+        let jobs = Jobs::default();
+        let refresh_jobs = RefreshJobs::new(jobs.clone());
+        let job = refresh_jobs.enqueue(&[FeedId(1), FeedId(2)], UserId(1), 1000);
+
+        // asking again while it runs doesn't queue the feeds twice
+        assert_eq!(refresh_jobs.enqueue(&[FeedId(1)], UserId(2), 1001), job);
+
+        assert_eq!(refresh_jobs.next(), Some((job.id, FeedId(1))));
+        refresh_jobs.record(job.id, JobResult::ok("https://example.com/a.xml"), 1010);
+        assert_eq!(refresh_jobs.next(), Some((job.id, FeedId(2))));
+        refresh_jobs.record(job.id, JobResult::ok("https://example.com/b.xml"), 1020);
+        assert_eq!(refresh_jobs.next(), None);
+        assert_eq!(jobs.get(job.id).unwrap().finished_at, Some(1020));
+
+        // a finished job isn't reused
+        let next = refresh_jobs.enqueue(&[FeedId(1)], UserId(1), 2000);
+        assert_ne!(next.id, job.id);
+    }
+}
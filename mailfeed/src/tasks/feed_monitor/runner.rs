@@ -1,18 +1,64 @@
-use diesel::SqliteConnection;
-use reqwest::Client;
+use std::sync::Arc;
 
-use super::types::FeedUpdates;
+use feed_rs::model::Entry;
+use reqwest::{
+    header::{HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Client, RequestBuilder, Response, StatusCode, Url,
+};
+
+use super::{
+    body_hash::body_hash,
+    broken_feeds::BrokenFeeds,
+    change_alerts::ChangeAlerts,
+    changes::observe,
+    dns_cache::DnsCache,
+    fetch_error::FetchError,
+    item_links::item_links,
+    link_cleaner::LinkCleaner,
+    parse_warnings::parse_warnings,
+    poll_interval::{poll_interval, unchanged_interval, PollBounds},
+    refresh::RefreshJobs,
+    saved_searches::SavedSearches,
+    types::FeedUpdates,
+};
 use crate::{
+    db::DbConnection,
     models::{
-        feed::{Feed, PartialFeed},
-        feed_item::NewFeedItem,
+        feed::{Feed, FeedErrorKind, PartialFeed},
+        feed_change::{FeedChange, FeedChangeKind},
+        feed_credentials::FeedCredentials,
+        feed_item::{FeedItem, NewFeedItem},
+        ids::FeedId,
+        ingest_limits::IngestLimits,
+    },
+    tasks::{
+        dispatch::Channels,
+        item_media::lead_image,
+        jobs::JobResult,
+        mqtt::{Mqtt, MqttEvent},
+        types::{CHECK_INTERVAL, FETCH_TIMEOUT},
+        webhooks::{Event, Webhooks},
     },
-    tasks::types::CHECK_INTERVAL,
     DbPool,
 };
 
-pub async fn start(pool: DbPool) {
-    let http_client = Client::new();
+pub async fn start(
+    pool: DbPool,
+    refresh_jobs: RefreshJobs,
+    webhooks: Webhooks,
+    mqtt: Mqtt,
+    channels: Channels,
+) {
+    let monitor = Monitor {
+        http_client: http_client(),
+        link_cleaner: LinkCleaner::from_env(),
+        poll_bounds: PollBounds::from_env(),
+        change_alerts: ChangeAlerts::from_env(),
+        saved_searches: SavedSearches::from_env(),
+        broken_feeds: BrokenFeeds::from_env(channels),
+        webhooks,
+        mqtt,
+    };
     loop {
         let mut conn = match pool.get() {
             Ok(conn) => conn,
@@ -21,72 +67,334 @@ pub async fn start(pool: DbPool) {
                 continue;
             }
         };
+
+        // feeds an admin asked to refresh go ahead of the schedule
+        while let Some((job_id, feed_id)) = refresh_jobs.next() {
+            let result = match Feed::get_by_id(&mut conn, feed_id) {
+                Some(feed) => match monitor.check(&mut conn, &feed).await {
+                    Ok(()) => JobResult::ok(&feed.url),
+                    Err(e) => JobResult::failed(&feed.url, e.message),
+                },
+                None => JobResult::failed("", "Feed was deleted"),
+            };
+            refresh_jobs.record(job_id, result, chrono::Utc::now().timestamp());
+        }
+
         let feeds: Vec<Feed> = match Feed::get_all(&mut conn) {
             Some(feeds) => feeds,
             None => {
                 log::info!("No feeds found");
-                tokio::time::sleep(CHECK_INTERVAL).await;
+                refresh_jobs.wait(CHECK_INTERVAL).await;
                 continue;
             }
         };
 
-        for feed in &feeds {
-            let response = http_client.get(&feed.url)
-                // See: https://stackoverflow.com/a/7001617/5155484
-                .header(
-                    "Accept",
-                    "application/rss+xml, application/rdf+xml, application/atom+xml, application/feed+json, application/xml;q=0.9, text/xml;q=0.8"
-                )
-                .header(
-                    "User-Agent",
-                    "Mailfeed (https://github.com/anson-vandoren/mailfeed)"
-                )
-                .send().await;
-            match response {
-                Ok(response) => {
-                    if response.status().is_success() {
-                        log::info!("Got response for feed {}", feed.url);
-                        let body = response.text().await.unwrap();
-                        parse_and_insert(&mut conn, &body, feed);
-                    } else {
-                        let error_update = PartialFeed {
-                            error_time: Some(chrono::Utc::now().timestamp() as i32),
-                            error_message: Some(response.status().to_string()),
-                            ..Default::default()
-                        };
-                        Feed::update(&mut conn, feed.id, &error_update);
-                        log::warn!(
-                            "Got non-success response for feed {}: {}",
-                            feed.url,
-                            response.status()
-                        );
-                    }
-                }
-                Err(e) => {
-                    let error_update = PartialFeed {
-                        error_time: Some(chrono::Utc::now().timestamp() as i32),
-                        error_message: Some(e.to_string()),
-                        ..Default::default()
-                    };
-                    Feed::update(&mut conn, feed.id, &error_update);
-                    log::warn!("Error getting feed {}: {:?}", feed.url, e);
-                }
-            }
+        let now = chrono::Utc::now().timestamp();
+        for feed in feeds.iter().filter(|feed| feed.is_due(now)) {
+            // errors are recorded on the feed
+            let _ = monitor.check(&mut conn, feed).await;
         }
         let num_feeds = feeds.len();
         log::info!("Found {} feeds", num_feeds);
-        tokio::time::sleep(CHECK_INTERVAL).await;
+        refresh_jobs.wait(CHECK_INTERVAL).await;
     }
 }
 
-fn parse_and_insert(conn: &mut SqliteConnection, body: &str, feed: &Feed) {
-    let parsed = match feed_rs::parser::parse(body.as_bytes()) {
-        Ok(parsed) => parsed,
-        Err(e) => {
-            log::warn!("Error parsing feed: {:?}", e);
-            return;
+struct Monitor {
+    http_client: Client,
+    link_cleaner: LinkCleaner,
+    poll_bounds: PollBounds,
+    change_alerts: ChangeAlerts,
+    saved_searches: SavedSearches,
+    broken_feeds: BrokenFeeds,
+    webhooks: Webhooks,
+    mqtt: Mqtt,
+}
+
+impl Monitor {
+    /// Fetch the feed and store its new items. Errors are also recorded on
+    /// the feed.
+    async fn check(&self, conn: &mut DbConnection, feed: &Feed) -> Result<(), FetchError> {
+        let fetched = match fetch_if_changed(&self.http_client, feed).await {
+            Ok(Some(fetched)) => fetched,
+            Ok(None) => {
+                log::info!("Feed {} is unchanged since last check", feed.url);
+                let unchanged = PartialFeed {
+                    poll_interval: Some(
+                        unchanged_interval(feed, &self.poll_bounds).as_secs() as i32
+                    ),
+                    ..checked(chrono::Utc::now().timestamp())
+                };
+                Feed::update(conn, feed.id, &unchanged);
+                return Ok(());
+            }
+            Err(e) => {
+                self.failed(conn, feed, &e).await;
+                return Err(e);
+            }
+        };
+        let now = chrono::Utc::now().timestamp();
+        let mut changes: Vec<FeedChange> = observe(
+            conn,
+            feed,
+            FeedChangeKind::Redirect,
+            fetched.redirected_to.as_deref(),
+            now,
+        )
+        .into_iter()
+        .collect();
+        let limits = IngestLimits::load(conn);
+        let parsed = parse_and_insert(
+            conn,
+            &fetched,
+            feed,
+            &self.link_cleaner,
+            &self.poll_bounds,
+            &limits,
+        );
+        let outcome = match parsed {
+            Ok((found, added)) => {
+                changes.extend(found);
+                for item in &added {
+                    self.mqtt.publish(MqttEvent::new_item(item));
+                }
+                self.saved_searches.check(conn, feed, &added).await;
+                Ok(())
+            }
+            Err(e) => {
+                self.failed(conn, feed, &e).await;
+                Err(e)
+            }
+        };
+        if !changes.is_empty() {
+            self.change_alerts.send(conn, feed, &changes).await;
+        }
+        outcome
+    }
+
+    /// Record the error, tell webhooks if the feed was working until now,
+    /// and give up on it if it's failed too many times in a row
+    async fn failed(&self, conn: &mut DbConnection, feed: &Feed, error: &FetchError) {
+        record_error(conn, feed, error, &self.poll_bounds);
+        if feed.failing_since().is_none() {
+            self.webhooks.emit(Event::FeedBroken {
+                feed_id: feed.id,
+                url: feed.url.clone(),
+                kind: error.kind,
+                message: error.message.clone(),
+            });
         }
+        if self.broken_feeds.breaks(feed) {
+            self.broken_feeds.give_up(conn, feed, error).await;
+        }
+    }
+}
+
+/// Client for fetching feeds, which caches DNS lookups
+pub(super) fn http_client() -> Client {
+    Client::builder()
+        .dns_resolver(Arc::new(DnsCache::default()))
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .expect("Error building HTTP client")
+}
+
+pub(super) struct Fetched {
+    pub body: String,
+    /// Where the feed was finally served from, if it was redirected
+    pub redirected_to: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+pub(super) async fn fetch(http_client: &Client, url: &str) -> Result<Fetched, FetchError> {
+    let response = send(http_client.get(url)).await?;
+    if !response.status().is_success() {
+        return Err(FetchError::from_status(response.status()));
+    }
+    read(url, response).await
+}
+
+/// Like `fetch`, but logs in to private feeds and sends the validators
+/// saved from the feed's last fetch. None if the server says the feed
+/// hasn't changed since.
+pub(super) async fn fetch_if_changed(
+    http_client: &Client,
+    feed: &Feed,
+) -> Result<Option<Fetched>, FetchError> {
+    let mut request = http_client.get(&feed.url);
+    if let Some(sealed) = &feed.credentials {
+        request = match FeedCredentials::unseal(sealed) {
+            Some(FeedCredentials::Basic { username, password }) => {
+                request.basic_auth(username, Some(password))
+            }
+            Some(FeedCredentials::Bearer { token }) => request.bearer_auth(token),
+            None => {
+                log::warn!(
+                    "Can't unseal the credentials of feed {}, was MF_SECRET_KEY changed?",
+                    feed.id
+                );
+                request
+            }
+        };
+    }
+    if let Some(etag) = &feed.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &feed.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    let response = send(request).await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(FetchError::from_status(response.status()));
+    }
+    read(&feed.url, response).await.map(Some)
+}
+
+async fn send(request: RequestBuilder) -> Result<Response, FetchError> {
+    request
+        // See: https://stackoverflow.com/a/7001617/5155484
+        .header(
+            "Accept",
+            "application/rss+xml, application/rdf+xml, application/atom+xml, application/feed+json, application/xml;q=0.9, text/xml;q=0.8"
+        )
+        .header(
+            "User-Agent",
+            "Mailfeed (https://github.com/anson-vandoren/mailfeed)"
+        )
+        .send().await
+        .map_err(|e| FetchError::from_reqwest(&e))
+}
+
+async fn read(url: &str, response: Response) -> Result<Fetched, FetchError> {
+    log::info!("Got response for feed {}", url);
+    let redirected_to = match Url::parse(url) {
+        Ok(requested) if requested == *response.url() => None,
+        _ => Some(response.url().to_string()),
+    };
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    let body = response
+        .text()
+        .await
+        .map_err(|e| FetchError::from_reqwest(&e))?;
+    Ok(Fetched {
+        body,
+        redirected_to,
+        etag,
+        last_modified,
+    })
+}
+
+/// A successful check, clearing any error
+fn checked<'a>(now: i64) -> PartialFeed<'a> {
+    PartialFeed {
+        last_checked: Some(now),
+        error_time: Some(0),
+        error_message: Some(None),
+        error_kind: Some(FeedErrorKind::None),
+        consecutive_failures: Some(0),
+        broken_at: Some(0),
+        ..Default::default()
+    }
+}
+
+/// Store the error on the feed and push its next check back
+fn record_error(
+    conn: &mut DbConnection,
+    feed: &Feed,
+    error: &FetchError,
+    poll_bounds: &PollBounds,
+) {
+    let retry_after = error.retry_after(feed, poll_bounds);
+    log::warn!(
+        "Error getting feed {} ({:?}): {}, retrying in {:?}",
+        feed.url,
+        error.kind,
+        error.message,
+        retry_after
+    );
+    let now = chrono::Utc::now().timestamp();
+    let error_update = PartialFeed {
+        last_checked: Some(now),
+        poll_interval: Some(retry_after.as_secs() as i32),
+        // keep when the feed started failing, not just the latest error
+        error_time: Some(feed.failing_since().unwrap_or(now)),
+        error_message: Some(Some(error.message.clone())),
+        error_kind: Some(error.kind),
+        consecutive_failures: Some(feed.consecutive_failures + 1),
+        ..Default::default()
+    };
+    Feed::update(conn, feed.id, &error_update);
+}
+
+/// Returns any significant changes to the feed's title or self link
+fn parse_and_insert(
+    conn: &mut DbConnection,
+    fetched: &Fetched,
+    feed: &Feed,
+    link_cleaner: &LinkCleaner,
+    poll_bounds: &PollBounds,
+    limits: &IngestLimits,
+) -> Result<(Vec<FeedChange>, Vec<FeedItem>), FetchError> {
+    let now = chrono::Utc::now().timestamp();
+    let body = fetched.body.as_str();
+    // the validators are only kept once the body is known to be good
+    let checked = PartialFeed {
+        etag: Some(fetched.etag.as_deref()),
+        last_modified: Some(fetched.last_modified.as_deref()),
+        ..checked(now)
     };
+    let hash = body_hash(body);
+    if feed.body_hash.as_deref() == Some(hash.as_str()) {
+        log::info!("Feed {} is unchanged since last check", feed.url);
+        let unchanged = PartialFeed {
+            poll_interval: Some(unchanged_interval(feed, poll_bounds).as_secs() as i32),
+            ..checked
+        };
+        Feed::update(conn, feed.id, &unchanged);
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let parsed = feed_rs::parser::parse(body.as_bytes()).map_err(|e| FetchError::from_parse(&e))?;
+
+    let interval = poll_interval(&parsed, body, poll_bounds);
+    log::debug!("Next check of feed {} in {:?}", feed.url, interval);
+    let warnings = parse_warnings(body, &parsed);
+    if !warnings.0.is_empty() {
+        log::info!("Feed {} parsed with warnings: {:?}", feed.url, warnings.0);
+    }
+    let checked = PartialFeed {
+        poll_interval: Some(interval.as_secs() as i32),
+        body_hash: Some(&hash),
+        parse_warnings: Some(warnings),
+        ..checked
+    };
+    Feed::update(conn, feed.id, &checked);
+
+    let title = parsed.title.as_ref().map(|title| title.content.as_str());
+    let self_link = parsed
+        .links
+        .iter()
+        .find(|link| link.rel.as_deref() == Some("self"))
+        .map(|link| link.href.as_str());
+    let changes = [
+        observe(conn, feed, FeedChangeKind::Title, title, now),
+        observe(conn, feed, FeedChangeKind::SelfLink, self_link, now),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
 
     // Update feed if necessary
     let feed_updates = FeedUpdates::from_feed_rs(&parsed, feed);
@@ -96,41 +404,282 @@ fn parse_and_insert(conn: &mut SqliteConnection, body: &str, feed: &Feed) {
     }
 
     log::info!("Found {} items", parsed.entries.len());
-    let mut num_added = 0;
 
-    // insert new feed items
-    for entry in parsed.entries {
+    let mut entries: Vec<EntryFields> = parsed
+        .entries
+        .into_iter()
+        .filter_map(|entry| EntryFields::from_entry(entry, feed, link_cleaner, now))
+        .collect();
+    keep_stored_dates(conn, feed.id, &mut entries);
+    let mut entries: Vec<EntryFields> = entries
+        .into_iter()
+        // pruned items would otherwise be added, and sent, again
+        .filter(|entry| entry.pub_date > feed.pruned_through)
+        .collect();
+    let skipped = cap_entries(&mut entries, limits.max_items_per_fetch as usize);
+    if skipped > 0 {
+        log::warn!(
+            "Feed {} had {} items, skipped the oldest {} over the limit of {}",
+            feed.url,
+            entries.len() + skipped,
+            skipped,
+            limits.max_items_per_fetch
+        );
+    }
+    let items: Vec<NewFeedItem> = entries
+        .iter()
+        .map(|entry| NewFeedItem {
+            feed_id: feed.id,
+            title: &entry.title,
+            link: &entry.link,
+            pub_date: entry.pub_date,
+            description: entry.description.as_deref(),
+            author: entry.author.as_deref(),
+            comments_link: entry.comments_link.as_deref(),
+            first_seen: now,
+            image_url: entry.image_url.as_deref(),
+        })
+        .collect();
+    let added = match NewFeedItem::insert_all(conn, &items) {
+        Ok(added) => added,
+        Err(e) => {
+            log::warn!("Error inserting items: {:?}", e);
+            Vec::new()
+        }
+    };
+    let num_added = added.len();
+
+    log::info!("Added {} items", num_added);
+    if limits.is_alarming(num_added) {
+        log::warn!(
+            "Feed {} added {} new items in one fetch, more than the alarm of {}",
+            feed.url,
+            num_added,
+            limits.new_items_alarm
+        );
+    }
+    let counts = PartialFeed {
+        new_items: Some(num_added as i32),
+        skipped_items: Some(skipped as i32),
+        ..Default::default()
+    };
+    Feed::update(conn, feed.id, &counts);
+    Ok((changes, added))
+}
+
+/// Give entries without a publish date the one they were stored with, so
+/// they're recognized as the same items rather than added again with a new
+/// date on every fetch. One pruned while still in the feed can't be told
+/// from a new one, so retention should outlast how long feeds keep items.
+fn keep_stored_dates(conn: &mut DbConnection, feed_id: FeedId, entries: &mut [EntryFields]) {
+    let undated: Vec<&str> = entries
+        .iter()
+        .filter(|entry| !entry.dated)
+        .map(|entry| entry.link.as_str())
+        .collect();
+    if undated.is_empty() {
+        return;
+    }
+    let stored = match FeedItem::pub_dates_by_link(conn, feed_id, &undated) {
+        Ok(stored) => stored,
+        Err(e) => {
+            log::warn!("Error getting stored items of feed {}: {:?}", feed_id, e);
+            return;
+        }
+    };
+    for entry in entries.iter_mut().filter(|entry| !entry.dated) {
+        if let Some(pub_date) = stored.get(&entry.link) {
+            entry.pub_date = *pub_date;
+        }
+    }
+}
+
+/// Keep the newest entries up to the cap, returning how many were dropped
+fn cap_entries(entries: &mut Vec<EntryFields>, max: usize) -> usize {
+    if entries.len() <= max {
+        return 0;
+    }
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.pub_date));
+    let skipped = entries.len() - max;
+    entries.truncate(max);
+    skipped
+}
+
+/// The parts of a feed entry that are stored as an item
+struct EntryFields {
+    title: String,
+    link: String,
+    pub_date: i64,
+    /// whether `pub_date` came from the feed
+    dated: bool,
+    description: Option<String>,
+    author: Option<String>,
+    comments_link: Option<String>,
+    image_url: Option<String>,
+}
+
+impl EntryFields {
+    /// `now` stands in for the publish date when the entry has none
+    fn from_entry(entry: Entry, feed: &Feed, link_cleaner: &LinkCleaner, now: i64) -> Option<Self> {
+        let links = match item_links(&entry) {
+            Some(links) => links,
+            None => {
+                log::debug!("Skipping item without a link: {:?}", entry.id);
+                return None;
+            }
+        };
+        let link = link_cleaner.clean(&links.link);
+        let comments_link = links.comments.map(|comments| link_cleaner.clean(&comments));
+        let image_url = lead_image(&entry);
+
         let title = entry.title.or_else(|| entry.summary.clone());
         let title = title
             .map(|t| t.content)
             .unwrap_or_else(|| feed.title.clone());
-        let pub_date: i32 = entry.published.map(|p| p.timestamp() as i32).unwrap_or(0);
+        let pub_date: i64 = entry.published.map_or(now, |p| p.timestamp());
 
         // entry.authors may be an empty Vec
-        let author = entry.authors.get(0).map(|a| a.name.as_str());
+        let author = entry.authors.first().map(|a| a.name.clone());
         let description = entry.summary.map(|s| s.content);
 
-        let item = NewFeedItem {
-            feed_id: feed.id,
-            title: &title,
-            link: &entry.links[0].href,
+        Some(EntryFields {
+            title,
+            link,
             pub_date,
-            description: description.as_deref(),
+            dated: entry.published.is_some(),
+            description,
             author,
-        };
-        let result = item.insert_if_not_present(conn);
-        match result {
-            Ok(Some(_)) => {
-                num_added += 1;
-            }
-            Ok(None) => {
-                log::debug!("Item already exists: {:?}", item.link);
-            }
-            Err(e) => {
-                log::warn!("Error inserting item: {:?}", e);
-            }
+            comments_link,
+            image_url,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::feed::NewFeed, test_helpers::test_helpers::get_test_db_connection};
+
+    fn entry(pub_date: i64) -> EntryFields {
+        EntryFields {
+            title: String::new(),
+            link: format!("https://example.com/{}", pub_date),
+            pub_date,
+            dated: true,
+            description: None,
+            author: None,
+            comments_link: None,
+            image_url: None,
         }
     }
 
-    log::info!("Added {} items", num_added);
+    #[test]
+    fn test_cap_entries_keeps_newest() {
+        let mut entries = vec![entry(1), entry(3), entry(2)];
+        assert_eq!(cap_entries(&mut entries, 5), 0);
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(cap_entries(&mut entries, 2), 1);
+        let dates: Vec<i64> = entries.iter().map(|entry| entry.pub_date).collect();
+        assert_eq!(dates, vec![3, 2]);
+    }
+
+    #[test]
+    fn test_validators_kept_after_parsing() {
+        let mut conn = get_test_db_connection();
+        let feed = NewFeed {
+            url: "https://example.com/feed.xml",
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap();
+        let fetched = |body: &str| Fetched {
+            body: body.to_string(),
+            redirected_to: None,
+            etag: Some("\"v1\"".to_string()),
+            last_modified: Some("Wed, 14 Oct 2026 10:00:00 GMT".to_string()),
+        };
+        let parse = |conn: &mut DbConnection, fetched: &Fetched| {
+            parse_and_insert(
+                conn,
+                fetched,
+                &feed,
+                &LinkCleaner::default(),
+                &PollBounds::default(),
+                &IngestLimits::default(),
+            )
+        };
+
+        // a body that can't be parsed may be fixed without the validators changing
+        assert!(parse(&mut conn, &fetched("not a feed")).is_err());
+        let stored = Feed::get_by_id(&mut conn, feed.id).unwrap();
+        assert_eq!(stored.etag, None);
+
+        let rss = r#"<rss version="2.0"><channel><title>Example</title></channel></rss>"#;
+        assert!(parse(&mut conn, &fetched(rss)).is_ok());
+        let stored = Feed::get_by_id(&mut conn, feed.id).unwrap();
+        assert_eq!(stored.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(
+            stored.last_modified.as_deref(),
+            Some("Wed, 14 Oct 2026 10:00:00 GMT")
+        );
+    }
+
+    #[test]
+    fn test_undated_items_stored_once() {
+        use crate::schema::feed_items;
+        use diesel::prelude::*;
+
+        let mut conn = get_test_db_connection();
+        let feed_id = NewFeed {
+            url: "https://example.com/feed.xml",
+            ..Default::default()
+        }
+        .insert(&mut conn)
+        .unwrap()
+        .id;
+        let parse = |conn: &mut DbConnection, description: &str| {
+            let fetched = Fetched {
+                body: format!(
+                    r#"<rss version="2.0"><channel><title>Example</title>
+                    <description>{}</description>
+                    <item><title>Undated</title><link>https://example.com/undated</link></item>
+                    </channel></rss>"#,
+                    description
+                ),
+                redirected_to: None,
+                etag: None,
+                last_modified: None,
+            };
+            let feed = Feed::get_by_id(conn, feed_id).unwrap();
+            parse_and_insert(
+                conn,
+                &fetched,
+                &feed,
+                &LinkCleaner::default(),
+                &PollBounds::default(),
+                &IngestLimits::default(),
+            )
+            .unwrap()
+            .1
+        };
+
+        assert_eq!(parse(&mut conn, "first").len(), 1);
+        // as if it was first fetched an hour ago
+        let hour_ago = chrono::Utc::now().timestamp() - 60 * 60;
+        diesel::update(feed_items::table)
+            .set((
+                feed_items::pub_date.eq(hour_ago),
+                feed_items::first_seen.eq(hour_ago),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+
+        // a changed body is parsed again, but the item is already stored
+        assert!(parse(&mut conn, "second").is_empty());
+        let items = FeedItem::get_by_feed(&mut conn, feed_id).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].pub_date, hour_ago);
+        assert_eq!(items[0].first_seen, hour_ago);
+    }
 }
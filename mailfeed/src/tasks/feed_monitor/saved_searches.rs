@@ -0,0 +1,228 @@
+use chrono::Utc;
+use reqwest::Client;
+
+use crate::{
+    db::DbConnection,
+    models::{
+        feed::Feed,
+        feed_item::FeedItem,
+        retry_policy::{Channel, RetryPolicy},
+        saved_search::{NotifyBy, SavedSearch},
+        search_query::{words, SearchQuery},
+        user::{User, UserQuery},
+    },
+    tasks::{
+        dispatch::is_web_link, email_sender::notification::send_notification,
+        html_to_text::item_text, item_media::fetch_og_image, telegram::TelegramBot,
+        types::FETCH_TIMEOUT,
+    },
+};
+
+/// Checks newly fetched items against users' saved searches, sending each
+/// search's matches as soon as they're found
+pub(super) struct SavedSearches {
+    telegram: Option<TelegramBot>,
+    /// for finding the lead image of matches sent as Telegram photos
+    http: Client,
+}
+
+impl SavedSearches {
+    pub(super) fn from_env() -> Self {
+        SavedSearches {
+            telegram: TelegramBot::from_env(),
+            http: Client::builder()
+                .timeout(FETCH_TIMEOUT)
+                .build()
+                .expect("Error building HTTP client"),
+        }
+    }
+
+    pub(super) async fn check(&self, conn: &mut DbConnection, feed: &Feed, added: &[FeedItem]) {
+        if added.is_empty() {
+            return;
+        }
+        let searches = match SavedSearch::get_active_for_feed(conn, feed.id) {
+            Ok(searches) if searches.is_empty() => return,
+            Ok(searches) => searches,
+            Err(e) => {
+                log::error!("Error getting saved searches for feed {}: {:?}", feed.id, e);
+                return;
+            }
+        };
+
+        let texts: Vec<Vec<String>> = added.iter().map(item_words).collect();
+        for search in &searches {
+            // the query was checked when it was saved
+            let query = match SearchQuery::parse(&search.query) {
+                Ok(query) => query,
+                Err(e) => {
+                    log::warn!("Skipping saved search {}: {}", search.id, e);
+                    continue;
+                }
+            };
+            let matched: Vec<&FeedItem> = added
+                .iter()
+                .zip(&texts)
+                .filter(|(_, text)| query.matches(text))
+                .map(|(item, _)| item)
+                .collect();
+            if matched.is_empty() {
+                continue;
+            }
+            log::info!(
+                "{} new items from feed {} match saved search {}",
+                matched.len(),
+                feed.id,
+                search.id
+            );
+            self.notify(conn, feed, search, &matched).await;
+            if let Err(e) =
+                SavedSearch::record_matches(conn, search.id, matched.len(), Utc::now().timestamp())
+            {
+                log::error!(
+                    "Error recording matches for saved search {}: {:?}",
+                    search.id,
+                    e
+                );
+            }
+        }
+    }
+
+    async fn notify(
+        &self,
+        conn: &mut DbConnection,
+        feed: &Feed,
+        search: &SavedSearch,
+        matched: &[&FeedItem],
+    ) {
+        let body = message(feed, search, matched);
+        match (search.notify_by, &search.telegram_chat_id) {
+            (NotifyBy::Telegram, Some(chat_id)) => {
+                let Some(telegram) = &self.telegram else {
+                    log::warn!(
+                        "Saved search {} notifies by Telegram, but MF_TELEGRAM_BOT_TOKEN isn't set",
+                        search.id
+                    );
+                    return;
+                };
+                let retry_policy = RetryPolicy::load(conn, Channel::Telegram);
+                if search.telegram_photos {
+                    for item in matched {
+                        self.send_photo(telegram, chat_id, feed, search, item, &retry_policy)
+                            .await;
+                    }
+                    return;
+                }
+                if let Err(e) = telegram.send(chat_id, &body, &retry_policy).await {
+                    log::error!(
+                        "Error sending saved search {} to Telegram: {}",
+                        search.id,
+                        e
+                    );
+                }
+            }
+            _ => {
+                let user = match User::get(conn, UserQuery::Id(search.user_id)) {
+                    Some(user) if user.is_active => user,
+                    _ => return,
+                };
+                let subject = format!("MailFeed: new matches for \"{}\"", search.name);
+                let retry_policy = RetryPolicy::load(conn, Channel::Email);
+                if let Err(e) =
+                    send_notification(&user.send_email, &subject, &body, &retry_policy).await
+                {
+                    log::error!(
+                        "Error emailing saved search {} to {}: {}",
+                        search.id,
+                        user.send_email,
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    /// Send a match on its own, as a photo if it has a lead image, falling
+    /// back to text if it doesn't or Telegram won't take the photo
+    async fn send_photo(
+        &self,
+        telegram: &TelegramBot,
+        chat_id: &str,
+        feed: &Feed,
+        search: &SavedSearch,
+        item: &FeedItem,
+        retry_policy: &RetryPolicy,
+    ) {
+        let caption = caption(feed, search, item);
+        let image = match &item.image_url {
+            Some(image) => Some(image.clone()),
+            None if is_web_link(&item.link) => fetch_og_image(&self.http, &item.link).await,
+            None => None,
+        };
+        if let Some(image) = image {
+            match telegram
+                .send_photo(chat_id, &image, &caption, retry_policy)
+                .await
+            {
+                Ok(()) => return,
+                Err(e) => log::warn!(
+                    "Sending item {} of saved search {} as text, since Telegram didn't take its photo: {}",
+                    item.id,
+                    search.id,
+                    e
+                ),
+            }
+        }
+        if let Err(e) = telegram.send(chat_id, &caption, retry_policy).await {
+            log::error!(
+                "Error sending saved search {} to Telegram: {}",
+                search.id,
+                e
+            );
+        }
+    }
+}
+
+/// The item's title and description as words to match against
+fn item_words(item: &FeedItem) -> Vec<String> {
+    words(&item_text(item))
+}
+
+/// A single match, for sending as a photo
+fn caption(feed: &Feed, search: &SavedSearch, item: &FeedItem) -> String {
+    format!(
+        "New in {}, matching \"{}\":\n\n{}\n{}",
+        feed.title, search.name, item.title, item.link
+    )
+}
+
+fn message(feed: &Feed, search: &SavedSearch, matched: &[&FeedItem]) -> String {
+    let mut body = format!(
+        "New items in {} match your saved search \"{}\" ({}):\n",
+        feed.title, search.name, search.query
+    );
+    for item in matched {
+        body.push_str(&format!("\n- {}\n  {}\n", item.title, item.link));
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::test_item;
+
+    #[test]
+    fn test_item_words() {
+        let item = FeedItem {
+            title: "Async Rust".to_string(),
+            description: Some("<p>Now with <b>Tokio</b></p>".to_string()),
+            ..test_item(1)
+        };
+        let query = SearchQuery::parse("rust AND tokio").unwrap();
+        assert!(query.matches(&item_words(&item)));
+        // tags aren't words
+        let query = SearchQuery::parse("b").unwrap();
+        assert!(!query.matches(&item_words(&item)));
+    }
+}
@@ -4,7 +4,9 @@ use crate::models::feed::{FeedType, PartialFeed};
 pub(super) struct FeedUpdates<'a> {
     feed_type: Option<FeedType>,
     title: Option<&'a str>,
-    last_updated: Option<i32>,
+    last_updated: Option<i64>,
+    description: Option<&'a str>,
+    homepage: Option<&'a str>,
 }
 
 impl<'a> From<FeedUpdates<'a>> for PartialFeed<'a> {
@@ -13,6 +15,8 @@ impl<'a> From<FeedUpdates<'a>> for PartialFeed<'a> {
             feed_type: updates.feed_type,
             title: updates.title,
             last_updated: updates.last_updated,
+            description: updates.description,
+            homepage: updates.homepage,
             ..Default::default()
         }
     }
@@ -65,32 +69,64 @@ impl<'a> FeedUpdates<'a> {
         parsed: &feed_rs::model::Feed,
         existing: &crate::models::feed::Feed,
     ) -> &mut Self {
-        self.last_updated = parsed
-            .updated
-            .map(|updated| updated.timestamp() as i32)
-            .and_then(|last_updated| {
-                // Especially w/ RSS, feed.updated may be when the feed definition
-                // was updated, but not when a newer item was added. So we also
-                // check the newest item's published time.
-                let newest_item_ts = parsed
-                    .entries
-                    .first()
-                    .and_then(|i| i.published.map(|p| p.timestamp() as i32));
+        self.last_updated =
+            parsed
+                .updated
+                .map(|updated| updated.timestamp())
+                .and_then(|last_updated| {
+                    // Especially w/ RSS, feed.updated may be when the feed definition
+                    // was updated, but not when a newer item was added. So we also
+                    // check the newest item's published time.
+                    let newest_item_ts = parsed
+                        .entries
+                        .first()
+                        .and_then(|i| i.published.map(|p| p.timestamp()));
 
-                let last_updated = newest_item_ts.map_or(last_updated, |newest_item_ts| {
-                    if newest_item_ts > last_updated {
-                        newest_item_ts
+                    let last_updated = newest_item_ts.map_or(last_updated, |newest_item_ts| {
+                        if newest_item_ts > last_updated {
+                            newest_item_ts
+                        } else {
+                            last_updated
+                        }
+                    });
+
+                    if existing.last_updated != last_updated {
+                        Some(last_updated)
                     } else {
-                        last_updated
+                        None
                     }
                 });
+        self
+    }
 
-                if existing.last_updated != last_updated {
-                    Some(last_updated)
-                } else {
-                    None
-                }
-            });
+    /// If existing feed has no description, set it from parsed feed
+    fn set_description(
+        &mut self,
+        parsed: &'a feed_rs::model::Feed,
+        existing: &crate::models::feed::Feed,
+    ) -> &mut Self {
+        if existing.description.is_none() {
+            if let Some(description) = &parsed.description {
+                self.description = Some(&description.content);
+            }
+        }
+        self
+    }
+
+    /// If existing feed has no homepage, set it from the parsed feed's
+    /// first link that isn't a link back to the feed itself
+    fn set_homepage(
+        &mut self,
+        parsed: &'a feed_rs::model::Feed,
+        existing: &crate::models::feed::Feed,
+    ) -> &mut Self {
+        if existing.homepage.is_none() {
+            self.homepage = parsed
+                .links
+                .iter()
+                .find(|link| matches!(link.rel.as_deref(), None | Some("alternate")))
+                .map(|link| link.href.as_str());
+        }
         self
     }
 
@@ -99,6 +135,8 @@ impl<'a> FeedUpdates<'a> {
             feed_type: self.feed_type,
             title: self.title,
             last_updated: self.last_updated,
+            description: self.description,
+            homepage: self.homepage,
         }
     }
 
@@ -110,10 +148,16 @@ impl<'a> FeedUpdates<'a> {
             .set_feed_type(parsed_feed, existing_feed)
             .set_title(parsed_feed, existing_feed)
             .set_last_updated(parsed_feed, existing_feed)
+            .set_description(parsed_feed, existing_feed)
+            .set_homepage(parsed_feed, existing_feed)
             .build()
     }
 
     pub(super) fn is_some(&self) -> bool {
-        self.feed_type.is_some() || self.title.is_some() || self.last_updated.is_some()
+        self.feed_type.is_some()
+            || self.title.is_some()
+            || self.last_updated.is_some()
+            || self.description.is_some()
+            || self.homepage.is_some()
     }
 }
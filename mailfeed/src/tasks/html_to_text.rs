@@ -0,0 +1,487 @@
+use crate::models::feed_item::FeedItem;
+
+/// Convert an HTML fragment (typically a feed item's description) into
+/// readable plain text, cut to at most `max_chars` characters on a word
+/// boundary (zero for no limit). Also returns whether anything was cut.
+///
+/// Block elements become paragraphs, headings are prefixed with `#`,
+/// list items keep their bullets/numbers, and links are rendered as
+/// numbered footnotes (`text[1]`) listed after the body. Footnotes for
+/// links that were cut off are dropped.
+pub fn html_to_text_truncated(html: &str, max_chars: usize) -> (String, bool) {
+    let (body, links) = convert(html);
+    let (mut body, truncated) = truncate_words(&body, max_chars);
+
+    // Links are numbered by first appearance, so the ones still
+    // referenced are always a prefix of the list
+    let kept = (0..links.len())
+        .take_while(|i| body.contains(&format!("[{}]", i + 1)))
+        .count();
+    if kept > 0 {
+        body.push('\n');
+        for (i, link) in links[..kept].iter().enumerate() {
+            body.push_str(&format!("\n[{}] {}", i + 1, link));
+        }
+    }
+    (body, truncated)
+}
+
+/// A feed item's title and description as plain text, for matching
+/// searches and keywords against
+pub fn item_text(item: &FeedItem) -> String {
+    let description = item
+        .description
+        .as_deref()
+        .map(|html| html_to_text_truncated(html, 0).0)
+        .unwrap_or_default();
+    format!("{}\n{}", item.title, description)
+}
+
+/// Every link in an HTML fragment, as (href, link text)
+pub fn html_links(html: &str) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let end = match tag_end(rest) {
+            Some(end) if is_tag_start(rest) => end,
+            _ => {
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag_name(tag) != "a" {
+            continue;
+        }
+        if let Some(href) = attr(tag, "href") {
+            let close = rest.to_ascii_lowercase().find("</a").unwrap_or(rest.len());
+            found.push((href, convert(&rest[..close]).0));
+            rest = &rest[close..];
+        }
+    }
+    found
+}
+
+/// Cut `text` to at most `max_chars` characters (zero for no limit),
+/// backing up to the last whitespace so words aren't split, and mark
+/// the cut with an ellipsis.
+fn truncate_words(text: &str, max_chars: usize) -> (String, bool) {
+    if max_chars == 0 || text.chars().count() <= max_chars {
+        return (text.to_string(), false);
+    }
+
+    let cut = text
+        .char_indices()
+        .nth(max_chars)
+        .map_or(text.len(), |(i, _)| i);
+    let head = &text[..cut];
+    // A single word longer than the limit gets cut mid-word
+    let head = match head.rfind(char::is_whitespace) {
+        Some(i) if !text[cut..].starts_with(char::is_whitespace) => &head[..i],
+        _ => head,
+    };
+
+    (format!("{}…", head.trim_end()), true)
+}
+
+fn convert(html: &str) -> (String, Vec<String>) {
+    let mut writer = TextWriter::default();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        writer.push_text(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            rest = match rest.find("-->") {
+                Some(end) => &rest[end + 3..],
+                None => "",
+            };
+            continue;
+        }
+
+        match tag_end(rest) {
+            Some(end) if is_tag_start(rest) => {
+                writer.push_tag(&rest[1..end]);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                // Not a tag, e.g. "a < b"
+                writer.push_text("<");
+                rest = &rest[1..];
+            }
+        }
+    }
+    writer.push_text(rest);
+
+    writer.finish()
+}
+
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "header",
+    "hr",
+    "p",
+    "section",
+    "table",
+    "tr",
+];
+
+const SKIP_TAGS: &[&str] = &["head", "script", "style", "template"];
+
+#[derive(Default)]
+struct TextWriter {
+    out: String,
+    /// Footnote URLs, in order of first appearance
+    links: Vec<String>,
+    /// One entry per open <a>; None if the link had no usable href
+    open_links: Vec<Option<String>>,
+    /// One entry per open list; Some(next number) for <ol>, None for <ul>
+    lists: Vec<Option<usize>>,
+    pending_space: bool,
+    skip_depth: usize,
+    pre_depth: usize,
+}
+
+impl TextWriter {
+    fn push_text(&mut self, text: &str) {
+        if self.skip_depth > 0 || text.is_empty() {
+            return;
+        }
+        let text = html_escape::decode_html_entities(text);
+
+        if self.pre_depth > 0 {
+            self.out.push_str(&text);
+            return;
+        }
+
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.pending_space = true;
+                continue;
+            }
+            if self.pending_space && !self.out.is_empty() && !self.out.ends_with([' ', '\n']) {
+                self.out.push(' ');
+            }
+            self.pending_space = false;
+            self.out.push(c);
+        }
+    }
+
+    fn push_tag(&mut self, tag: &str) {
+        let (name, is_closing) = match tag.strip_prefix('/') {
+            Some(name) => (tag_name(name), true),
+            None => (tag_name(tag), false),
+        };
+
+        if SKIP_TAGS.contains(&name.as_str()) {
+            if is_closing {
+                self.skip_depth = self.skip_depth.saturating_sub(1);
+            } else if !tag.ends_with('/') {
+                self.skip_depth += 1;
+            }
+            return;
+        }
+        if self.skip_depth > 0 {
+            return;
+        }
+
+        match (name.as_str(), is_closing) {
+            ("br", _) => self.line_break(),
+            ("a", false) => {
+                let href = attr(tag, "href").filter(|href| {
+                    !href.is_empty() && !href.starts_with('#') && !href.starts_with("javascript:")
+                });
+                self.open_links.push(href);
+            }
+            ("a", true) => {
+                if let Some(Some(href)) = self.open_links.pop() {
+                    let index = match self.links.iter().position(|l| l == &href) {
+                        Some(index) => index,
+                        None => {
+                            self.links.push(href);
+                            self.links.len() - 1
+                        }
+                    };
+                    self.out.push_str(&format!("[{}]", index + 1));
+                }
+            }
+            ("img", _) => {
+                if let Some(alt) = attr(tag, "alt").filter(|alt| !alt.trim().is_empty()) {
+                    self.push_text(&format!(" [{}] ", alt.trim()));
+                }
+            }
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) => {
+                self.block_break();
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                self.out.push_str(&"#".repeat(level));
+                self.out.push(' ');
+            }
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", true) => self.block_break(),
+            ("ul" | "ol", false) => {
+                if self.lists.is_empty() {
+                    self.block_break();
+                } else {
+                    self.line_break();
+                }
+                self.lists.push(if name == "ol" { Some(1) } else { None });
+            }
+            ("ul" | "ol", true) => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block_break();
+                } else {
+                    self.line_break();
+                }
+            }
+            ("li", false) => {
+                self.line_break();
+                let depth = self.lists.len().max(1);
+                self.out.push_str(&"  ".repeat(depth - 1));
+                match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        self.out.push_str(&format!("{}. ", number));
+                        *number += 1;
+                    }
+                    _ => self.out.push_str("* "),
+                }
+            }
+            ("li", true) => self.line_break(),
+            ("pre", false) => {
+                self.block_break();
+                self.pre_depth += 1;
+            }
+            ("pre", true) => {
+                self.pre_depth = self.pre_depth.saturating_sub(1);
+                self.block_break();
+            }
+            ("td" | "th", false) => self.pending_space = true,
+            (name, _) if BLOCK_TAGS.contains(&name) => self.block_break(),
+            _ => {}
+        }
+    }
+
+    /// Start a new line, unless already at the start of one
+    fn line_break(&mut self) {
+        self.trim_trailing_spaces();
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.pending_space = false;
+    }
+
+    /// Start a new paragraph, unless already at the start of one
+    fn block_break(&mut self) {
+        self.trim_trailing_spaces();
+        if !self.out.is_empty() {
+            while !self.out.ends_with("\n\n") {
+                self.out.push('\n');
+            }
+        }
+        self.pending_space = false;
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        let trimmed_len = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed_len);
+    }
+
+    fn finish(self) -> (String, Vec<String>) {
+        let body = self
+            .out
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string();
+        (body, self.links)
+    }
+}
+
+/// A tag starts with a letter, '/' or '!' right after the '<'
+fn is_tag_start(s: &str) -> bool {
+    matches!(s[1..].chars().next(), Some(c) if c.is_ascii_alphabetic() || c == '/' || c == '!')
+}
+
+/// Index of the '>' that closes the tag at the start of `s`, skipping
+/// over any quoted attribute values
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Value of the attribute `name` in the tag body (everything between '<' and '>')
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag.trim_start_matches(|c: char| c.is_ascii_alphanumeric());
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let attr_name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+
+        let mut value = None;
+        if let Some(after_eq) = rest.strip_prefix('=') {
+            let after_eq = after_eq.trim_start();
+            let (raw, remaining) = match after_eq.chars().next() {
+                Some(q @ ('"' | '\'')) => match after_eq[1..].find(q) {
+                    Some(end) => (&after_eq[1..end + 1], &after_eq[end + 2..]),
+                    None => (&after_eq[1..], ""),
+                },
+                _ => {
+                    let end = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+                    (&after_eq[..end], &after_eq[end..])
+                }
+            };
+            value = Some(raw);
+            rest = remaining;
+        }
+
+        if attr_name.eq_ignore_ascii_case(name) {
+            return value.map(|v| html_escape::decode_html_entities(v.trim()).into_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html_to_text(html: &str) -> String {
+        html_to_text_truncated(html, 0).0
+    }
+
+    #[test]
+    fn test_plain_text_passes_through() {
+        assert_eq!(html_to_text("Just some text"), "Just some text");
+        assert_eq!(html_to_text("  spaced \n  out  "), "spaced out");
+        assert_eq!(html_to_text("1 < 2 &amp; 3 > 2"), "1 < 2 & 3 > 2");
+    }
+
+    #[test]
+    fn test_strips_tags_and_keeps_paragraphs() {
+        let html = "<p>First <b>bold</b> paragraph.</p><p>Second<br>line</p>";
+        assert_eq!(html_to_text(html), "First bold paragraph.\n\nSecond\nline");
+    }
+
+    #[test]
+    fn test_links_become_footnotes() {
+        let html = r##"Read <a href="https://a.com/?x=1&amp;y=2">this</a> and
+            <a href='https://b.com'>that</a>, or <a href="https://a.com/?x=1&amp;y=2">this again</a>.
+            <a href="#top">Top</a>"##;
+        assert_eq!(
+            html_to_text(html),
+            "Read this[1] and that[2], or this again[1]. Top\n\n\
+             [1] https://a.com/?x=1&y=2\n\
+             [2] https://b.com"
+        );
+    }
+
+    #[test]
+    fn test_headings_and_lists() {
+        let html = "<h2>Title</h2><ul><li>one</li><li>two<ol><li>a</li><li>b</li></ol></li></ul><p>after</p>";
+        assert_eq!(
+            html_to_text(html),
+            "## Title\n\n* one\n* two\n  1. a\n  2. b\n\nafter"
+        );
+    }
+
+    #[test]
+    fn test_skips_scripts_styles_and_comments() {
+        let html = "<style>p { color: red; }</style><!-- hidden --><p>shown</p><script>alert('x')</script>";
+        assert_eq!(html_to_text(html), "shown");
+    }
+
+    #[test]
+    fn test_preformatted_text_is_kept() {
+        let html = "<p>code:</p><pre>fn main() {\n    42\n}</pre>";
+        assert_eq!(html_to_text(html), "code:\n\nfn main() {\n    42\n}");
+    }
+
+    #[test]
+    fn test_truncate_on_word_boundary() {
+        assert_eq!(truncate_words("short", 10), ("short".to_string(), false));
+        assert!(!truncate_words("no limit at all", 0).1);
+        assert_eq!(
+            truncate_words("the quick brown fox", 12),
+            ("the quick…".to_string(), true)
+        );
+        assert_eq!(
+            truncate_words("the quick brown fox", 9),
+            ("the quick…".to_string(), true)
+        );
+        assert_eq!(
+            truncate_words("supercalifragilistic", 5),
+            ("super…".to_string(), true)
+        );
+        assert_eq!(
+            truncate_words("héllo wörld", 8),
+            ("héllo…".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn test_truncated_drops_cut_footnotes() {
+        let html = r#"<a href="https://a.com">one</a> two three <a href="https://b.com">four</a>"#;
+        assert_eq!(
+            html_to_text_truncated(html, 14),
+            ("one[1] two…\n\n[1] https://a.com".to_string(), true)
+        );
+        assert!(!html_to_text_truncated(html, 0).1);
+    }
+
+    #[test]
+    fn test_html_links() {
+        let html = r#"<p><a href="https://a.com/?x=1&amp;y=2">Article <b>one</b></a>
+            1 < 2 <A HREF='https://b.com'>[comments]</A> <a name="x">no href</a></p>"#;
+        assert_eq!(
+            html_links(html),
+            vec![
+                (
+                    "https://a.com/?x=1&y=2".to_string(),
+                    "Article one".to_string()
+                ),
+                ("https://b.com".to_string(), "[comments]".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_image_alt_text() {
+        let html = r#"<p>Look: <img src="x.png" alt="a cat" /></p>"#;
+        assert_eq!(html_to_text(html), "Look: [a cat]");
+    }
+}
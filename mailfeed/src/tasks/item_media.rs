@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use feed_rs::model::Entry;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Client;
+use url::Url;
+
+/// Images bigger than this aren't kept, since Telegram won't send a photo
+/// over 5 MB by URL
+pub const MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+/// How much of an item's page is read looking for og:image, which is in the
+/// <head> of any page that has one
+const MAX_PAGE_BYTES: usize = 256 * 1024;
+const PAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+static META_TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<meta\s[^>]*>").expect("Valid meta tag regex"));
+static ATTRIBUTE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?is)([a-z:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).expect("Valid attribute regex")
+});
+
+/// The entry's first image: an image enclosure or Media RSS content, else a
+/// Media RSS thumbnail. Images said to be over `MAX_IMAGE_BYTES` are
+/// skipped.
+pub fn lead_image(entry: &Entry) -> Option<String> {
+    let content = entry
+        .media
+        .iter()
+        .flat_map(|media| &media.content)
+        .filter(|content| {
+            content
+                .content_type
+                .as_ref()
+                .is_some_and(|mime| mime.type_().as_str() == "image")
+        })
+        .filter(|content| content.size.is_none_or(|size| size <= MAX_IMAGE_BYTES))
+        .find_map(|content| content.url.as_ref().map(|url| url.to_string()));
+    content.or_else(|| {
+        entry
+            .media
+            .iter()
+            .flat_map(|media| &media.thumbnails)
+            .map(|thumbnail| thumbnail.image.uri.clone())
+            .find(|uri| uri.starts_with("http"))
+    })
+}
+
+/// The `og:image` an item's page declares for previews, made absolute
+pub fn og_image(html: &str, page: &str) -> Option<String> {
+    let image = META_TAG.find_iter(html).find_map(|tag| {
+        let mut property = None;
+        let mut content = None;
+        for attribute in ATTRIBUTE.captures_iter(tag.as_str()) {
+            let Some(value) = attribute.get(2).or_else(|| attribute.get(3)) else {
+                continue;
+            };
+            let value = value.as_str();
+            match attribute[1].to_ascii_lowercase().as_str() {
+                "property" | "name" => property = Some(value.to_ascii_lowercase()),
+                "content" => content = Some(value),
+                _ => {}
+            }
+        }
+        match property.as_deref() {
+            Some("og:image" | "og:image:url" | "og:image:secure_url") => content,
+            _ => None,
+        }
+    })?;
+    let image = html_escape::decode_html_entities(image.trim());
+    let url = Url::parse(page).ok()?.join(&image).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+/// Look for an `og:image` on the item's page, for items whose feed didn't
+/// give an image. None if the page can't be fetched or doesn't have one.
+pub async fn fetch_og_image(http: &Client, link: &str) -> Option<String> {
+    let mut response = http
+        .get(link)
+        .timeout(PAGE_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| log::debug!("Error fetching {} for its image: {}", link, e))
+        .ok()?;
+    let mut page = Vec::new();
+    while let Some(chunk) = response.chunk().await.ok()? {
+        page.extend_from_slice(&chunk);
+        if page.len() >= MAX_PAGE_BYTES {
+            break;
+        }
+    }
+    og_image(&String::from_utf8_lossy(&page), link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lead_image() {
+        let rss = r#"<rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/"><channel>
+            <item><title>Audio</title><link>https://example.com/1</link>
+                <enclosure url="https://example.com/1.mp3" type="audio/mpeg" length="1000"/>
+                <media:thumbnail url="https://example.com/1-thumb.jpg"/></item>
+            <item><title>Huge</title><link>https://example.com/2</link>
+                <enclosure url="https://example.com/2.png" type="image/png" length="99999999"/></item>
+            <item><title>Photo</title><link>https://example.com/3</link>
+                <enclosure url="https://example.com/3.jpg" type="image/jpeg" length="2048"/></item>
+            </channel></rss>"#;
+        let feed = feed_rs::parser::parse(rss.as_bytes()).unwrap();
+        let images: Vec<Option<String>> = feed.entries.iter().map(lead_image).collect();
+        assert_eq!(
+            images,
+            vec![
+                Some("https://example.com/1-thumb.jpg".to_string()),
+                None,
+                Some("https://example.com/3.jpg".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_og_image() {
+        let html = r#"<html><head>
+            <meta name="description" content="A post">
+            <meta content="/img/lead.jpg?w=800&amp;h=600" property="og:image" />
+            </head></html>"#;
+        assert_eq!(
+            og_image(html, "https://example.com/posts/1"),
+            Some("https://example.com/img/lead.jpg?w=800&h=600".to_string())
+        );
+        let html = r#"<meta property='og:image' content='https://cdn.example.com/a.png'>"#;
+        assert_eq!(
+            og_image(html, "https://example.com/"),
+            Some("https://cdn.example.com/a.png".to_string())
+        );
+        assert_eq!(
+            og_image(
+                "<meta property=\"og:title\" content=\"x\">",
+                "https://example.com/"
+            ),
+            None
+        );
+        assert_eq!(
+            og_image(
+                r#"<meta property="og:image" content="javascript:alert(1)">"#,
+                "https://example.com/"
+            ),
+            None
+        );
+    }
+}
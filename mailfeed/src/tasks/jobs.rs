@@ -0,0 +1,193 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+
+use crate::models::ids::UserId;
+
+/// How many jobs are remembered for polling, oldest are forgotten first
+const MAX_JOBS: usize = 50;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// an admin refetching every active feed
+    RefreshFeeds,
+    /// a user subscribing to the feeds in an OPML file
+    ImportFeeds,
+}
+
+/// What happened to one of a job's feeds
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct JobResult {
+    pub url: String,
+    pub success: bool,
+    /// why it failed, or anything else worth knowing about it
+    pub message: Option<String>,
+}
+
+impl JobResult {
+    pub fn ok(url: &str) -> Self {
+        JobResult {
+            url: url.to_string(),
+            success: true,
+            message: None,
+        }
+    }
+
+    pub fn failed(url: &str, message: impl Into<String>) -> Self {
+        JobResult {
+            url: url.to_string(),
+            success: false,
+            message: Some(message.into()),
+        }
+    }
+}
+
+/// Progress of work done in the background for a request
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Job {
+    pub id: u64,
+    pub kind: JobKind,
+    /// who started the job
+    pub user_id: UserId,
+    pub created_at: i64,
+    pub total: usize,
+    pub done: usize,
+    pub errors: usize,
+    pub remaining: usize,
+    pub finished_at: Option<i64>,
+    /// one per feed handled so far, in order
+    pub results: Vec<JobResult>,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    jobs: VecDeque<Job>,
+}
+
+/// Background jobs, shared between the API and the tasks doing the work.
+/// They're kept in memory, so they're lost on restart.
+#[derive(Clone, Default)]
+pub struct Jobs {
+    state: Arc<Mutex<State>>,
+}
+
+impl Jobs {
+    pub fn start(&self, kind: JobKind, user_id: UserId, total: usize, now: i64) -> Job {
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+        let job = Job {
+            id: state.next_id,
+            kind,
+            user_id,
+            created_at: now,
+            total,
+            done: 0,
+            errors: 0,
+            remaining: total,
+            finished_at: if total == 0 { Some(now) } else { None },
+            results: Vec::new(),
+        };
+        state.jobs.push_back(job.clone());
+        while state.jobs.len() > MAX_JOBS {
+            state.jobs.pop_front();
+        }
+        job
+    }
+
+    pub fn get(&self, id: u64) -> Option<Job> {
+        let state = self.state.lock().unwrap();
+        state.jobs.iter().find(|job| job.id == id).cloned()
+    }
+
+    /// An unfinished job of this kind, optionally only one started by the
+    /// given user
+    pub fn running(&self, kind: JobKind, user_id: Option<UserId>) -> Option<Job> {
+        let state = self.state.lock().unwrap();
+        state
+            .jobs
+            .iter()
+            .find(|job| {
+                job.kind == kind
+                    && job.finished_at.is_none()
+                    && (user_id.is_none() || user_id == Some(job.user_id))
+            })
+            .cloned()
+    }
+
+    pub fn record(&self, id: u64, result: JobResult, now: i64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(job) = state.jobs.iter_mut().find(|job| job.id == id) {
+            if result.success {
+                job.done += 1;
+            } else {
+                job.errors += 1;
+            }
+            job.results.push(result);
+            job.remaining = job.remaining.saturating_sub(1);
+            if job.remaining == 0 {
+                job.finished_at = Some(now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_progress() {
+        let jobs = Jobs::default();
+        let job = jobs.start(JobKind::ImportFeeds, UserId(1), 2, 1000);
+        assert_eq!(job.id, 1);
+        assert_eq!(job.remaining, 2);
+        assert_eq!(
+            jobs.running(JobKind::ImportFeeds, Some(UserId(1))),
+            Some(job)
+        );
+        assert_eq!(jobs.running(JobKind::ImportFeeds, Some(UserId(2))), None);
+        assert_eq!(jobs.running(JobKind::RefreshFeeds, None), None);
+
+        jobs.record(1, JobResult::ok("https://example.com/a.xml"), 1010);
+        let progress = jobs.get(1).unwrap();
+        assert_eq!(
+            (progress.done, progress.errors, progress.remaining),
+            (1, 0, 1)
+        );
+        assert_eq!(progress.finished_at, None);
+
+        jobs.record(
+            1,
+            JobResult::failed("https://example.com/b.xml", "HTTP 404"),
+            1020,
+        );
+        let progress = jobs.get(1).unwrap();
+        assert_eq!(
+            (progress.done, progress.errors, progress.remaining),
+            (1, 1, 0)
+        );
+        assert_eq!(progress.finished_at, Some(1020));
+        assert_eq!(
+            progress.results[1],
+            JobResult {
+                url: "https://example.com/b.xml".to_string(),
+                success: false,
+                message: Some("HTTP 404".to_string()),
+            }
+        );
+        assert_eq!(jobs.running(JobKind::ImportFeeds, None), None);
+        assert_eq!(jobs.get(2), None);
+    }
+
+    #[test]
+    fn test_empty_job_is_finished() {
+        let jobs = Jobs::default();
+        let job = jobs.start(JobKind::RefreshFeeds, UserId(1), 0, 1000);
+        assert_eq!(job.finished_at, Some(1000));
+    }
+}
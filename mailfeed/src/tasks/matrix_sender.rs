@@ -0,0 +1,307 @@
+use futures_util::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Serialize;
+use url::Url;
+
+use crate::{
+    db::DbConnection,
+    models::{
+        feed::Feed,
+        feed_item::FeedItem,
+        matrix_settings::{MatrixRoom, MatrixSettings},
+        retry_policy::{Channel, RetryPolicy},
+        subscription::DeliveryMethod,
+        user::User,
+    },
+    tasks::{
+        dispatch::{Batch, ChannelError, DeliveryChannel, Destination},
+        html_to_text::html_to_text_truncated,
+        retry::with_retries_async,
+        types::MATRIX_TIMEOUT,
+    },
+};
+
+/// Items in each message, keeping them well under Matrix's 64 KiB event limit
+const MAX_ITEMS_PER_MESSAGE: usize = 20;
+/// Characters of each item's description shown in its message
+const SUMMARY_CHARS: usize = 300;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid homeserver URL")]
+    Homeserver,
+    #[error("HTTP {0}")]
+    Status(u16),
+    #[error("{0}")]
+    Request(String),
+}
+
+impl Error {
+    /// Whether trying again later might work
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Homeserver => false,
+            Error::Status(status) => *status == 429 || *status >= 500,
+            Error::Request(_) => true,
+        }
+    }
+}
+
+/// An `m.room.message` event's content. Notices are what bots send, so
+/// clients don't treat them like messages from people.
+#[derive(Debug, Serialize)]
+struct Message {
+    msgtype: &'static str,
+    body: String,
+    format: &'static str,
+    formatted_body: String,
+}
+
+/// The event contents for a subscription's new items, split into as many
+/// messages as needed, each with a plain text and an HTML body
+fn messages(feed_title: &str, feed: &Feed, items: &[FeedItem]) -> Vec<String> {
+    let link_mode = feed.link_mode();
+    items
+        .chunks(MAX_ITEMS_PER_MESSAGE)
+        .map(|chunk| {
+            let mut body = format!("{}\n", feed_title);
+            let mut formatted_body = format!(
+                "<p><strong>{}</strong></p><ul>",
+                html_escape::encode_text(feed_title)
+            );
+            for item in chunk {
+                let (link, comments_link) = item.display_links(link_mode);
+                let summary = item
+                    .description
+                    .as_deref()
+                    .map(|description| html_to_text_truncated(description, SUMMARY_CHARS).0)
+                    .unwrap_or_default();
+
+                body.push_str(&format!("\n- {}\n  {}\n", item.title, link));
+                formatted_body.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a>",
+                    html_escape::encode_double_quoted_attribute(link),
+                    html_escape::encode_text(&item.title)
+                ));
+                if let Some(comments_link) = comments_link {
+                    body.push_str(&format!("  Comments: {}\n", comments_link));
+                    formatted_body.push_str(&format!(
+                        " (<a href=\"{}\">comments</a>)",
+                        html_escape::encode_double_quoted_attribute(comments_link)
+                    ));
+                }
+                if !summary.is_empty() {
+                    body.push_str(&format!("  {}\n", summary));
+                    formatted_body.push_str(&format!(
+                        "<br />{}",
+                        html_escape::encode_text(&summary).replace('\n', "<br />")
+                    ));
+                }
+                formatted_body.push_str("</li>");
+            }
+            formatted_body.push_str("</ul>");
+
+            let message = Message {
+                msgtype: "m.notice",
+                body: body.trim_end().to_string(),
+                format: "org.matrix.custom.html",
+                formatted_body,
+            };
+            serde_json::to_string(&message).expect("Message serializes to JSON")
+        })
+        .collect()
+}
+
+/// The client-server API endpoint for sending a message to the room. The
+/// homeserver treats repeats of a transaction ID as the same message, so
+/// retries can't post it twice.
+fn send_url(room: &MatrixRoom, txn_id: &str) -> Result<Url, Error> {
+    let mut url = Url::parse(room.homeserver).map_err(|_| Error::Homeserver)?;
+    url.path_segments_mut()
+        .map_err(|_| Error::Homeserver)?
+        .pop_if_empty()
+        .extend([
+            "_matrix",
+            "client",
+            "v3",
+            "rooms",
+            room.room_id,
+            "send",
+            "m.room.message",
+            txn_id,
+        ]);
+    Ok(url)
+}
+
+/// Post a message to the room
+pub async fn send(
+    client: &Client,
+    room: &MatrixRoom<'_>,
+    txn_id: &str,
+    body: &str,
+    retry_policy: &RetryPolicy,
+) -> Result<(), Error> {
+    let url = send_url(room, txn_id)?;
+    with_retries_async(
+        retry_policy,
+        &format!("send Matrix message to {}", room.room_id),
+        || send_once(client, &url, room.access_token, body),
+        Error::is_retryable,
+    )
+    .await
+}
+
+async fn send_once(
+    client: &Client,
+    url: &Url,
+    access_token: &str,
+    body: &str,
+) -> Result<(), Error> {
+    let response = client
+        .put(url.clone())
+        .bearer_auth(access_token)
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| Error::Request(e.to_string()))?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(Error::Status(status.as_u16())),
+    }
+}
+
+/// New items of subscriptions delivered by Matrix, posted to the user's
+/// room on the subscription's frequency like emails
+pub struct MatrixChannel {
+    client: Client,
+}
+
+impl Default for MatrixChannel {
+    fn default() -> Self {
+        let client = Client::builder()
+            .timeout(MATRIX_TIMEOUT)
+            .build()
+            .expect("Error building HTTP client");
+        MatrixChannel { client }
+    }
+}
+
+impl DeliveryChannel for MatrixChannel {
+    fn method(&self) -> DeliveryMethod {
+        DeliveryMethod::Matrix
+    }
+
+    fn retry_channel(&self) -> Channel {
+        Channel::Matrix
+    }
+
+    fn destination(&self, conn: &mut DbConnection, user: &User) -> Option<Box<dyn Destination>> {
+        let settings = MatrixSettings::load(conn, user.id);
+        let room = settings.room()?;
+        Some(Box::new(MatrixDestination {
+            client: self.client.clone(),
+            homeserver: room.homeserver.to_string(),
+            access_token: room.access_token.to_string(),
+            room_id: room.room_id.to_string(),
+        }))
+    }
+
+    fn preview(&self, _user: &User, batch: &Batch) -> Vec<String> {
+        messages(batch.title(), batch.feed, batch.items)
+    }
+}
+
+struct MatrixDestination {
+    client: Client,
+    homeserver: String,
+    access_token: String,
+    room_id: String,
+}
+
+impl Destination for MatrixDestination {
+    fn recipient(&self) -> &str {
+        &self.room_id
+    }
+
+    fn send<'a>(
+        &'a self,
+        batch: &'a Batch<'a>,
+        retry_policy: &'a RetryPolicy,
+    ) -> BoxFuture<'a, Result<(), ChannelError>> {
+        async move {
+            let room = MatrixRoom {
+                homeserver: &self.homeserver,
+                access_token: &self.access_token,
+                room_id: &self.room_id,
+            };
+            let bodies = messages(batch.title(), batch.feed, batch.items);
+            for (i, body) in bodies.iter().enumerate() {
+                let txn_id = format!("mailfeed.{}.{}.{}", batch.sub.id, batch.now, i);
+                send(&self.client, &room, &txn_id, body, retry_policy).await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::feed::LinkMode,
+        test_helpers::test_helpers::{test_feed, test_item},
+    };
+
+    fn fish_item(id: i32) -> FeedItem {
+        FeedItem {
+            title: "Fish & chips".to_string(),
+            link: "https://example.com/hello?a=1&b=2".to_string(),
+            description: Some("<p>Hi <b>there</b></p>".to_string()),
+            comments_link: Some("https://example.com/hello#comments".to_string()),
+            ..test_item(id)
+        }
+    }
+
+    #[test]
+    fn test_messages() {
+        let feed = Feed {
+            link_mode: LinkMode::Both,
+            ..test_feed()
+        };
+        let bodies = messages("My feed", &feed, &[fish_item(1)]);
+        assert_eq!(bodies.len(), 1);
+        let message: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+        assert_eq!(message["msgtype"], "m.notice");
+        assert_eq!(
+            message["body"],
+            "My feed\n\n- Fish & chips\n  https://example.com/hello?a=1&b=2\n  Comments: https://example.com/hello#comments\n  Hi there"
+        );
+        assert_eq!(
+            message["formatted_body"],
+            "<p><strong>My feed</strong></p><ul><li><a href=\"https://example.com/hello?a=1&amp;b=2\">Fish &amp; chips</a> (<a href=\"https://example.com/hello#comments\">comments</a>)<br />Hi there</li></ul>"
+        );
+
+        let items: Vec<FeedItem> = (0..25).map(test_item).collect();
+        assert_eq!(messages("My feed", &feed, &items).len(), 2);
+    }
+
+    #[test]
+    fn test_send_url() {
+        let room = MatrixRoom {
+            homeserver: "https://matrix.example.org/",
+            access_token: "syt_token",
+            room_id: "!abc/def:example.org",
+        };
+        assert_eq!(
+            send_url(&room, "mailfeed.1.2.0").unwrap().as_str(),
+            "https://matrix.example.org/_matrix/client/v3/rooms/!abc%2Fdef:example.org/send/m.room.message/mailfeed.1.2.0"
+        );
+        let bad = MatrixRoom {
+            homeserver: "mailto:someone@example.org",
+            ..room
+        };
+        assert!(matches!(send_url(&bad, "1"), Err(Error::Homeserver)));
+    }
+}
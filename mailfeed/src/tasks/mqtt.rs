@@ -0,0 +1,116 @@
+pub mod runner;
+
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::models::{
+    feed_item::FeedItem,
+    ids::{FeedId, SubscriptionId},
+    mqtt_settings::MqttSettings,
+};
+
+/// Something to publish, sent as `{"event", "data", "created_at"}`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum MqttEvent {
+    NewItem {
+        feed_id: FeedId,
+        title: String,
+        link: String,
+        pub_date: i64,
+    },
+    Delivery {
+        subscription_id: SubscriptionId,
+        recipient: String,
+        item_count: usize,
+    },
+}
+
+impl MqttEvent {
+    pub fn new_item(item: &FeedItem) -> Self {
+        MqttEvent::NewItem {
+            feed_id: item.feed_id,
+            title: item.title.clone(),
+            link: item.link.clone(),
+            pub_date: item.pub_date,
+        }
+    }
+
+    /// The configured topic for this kind of event
+    fn topic<'a>(&self, settings: &'a MqttSettings) -> &'a str {
+        match self {
+            MqttEvent::NewItem { .. } => &settings.items_topic,
+            MqttEvent::Delivery { .. } => &settings.deliveries_topic,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    event: &'a MqttEvent,
+    created_at: i64,
+}
+
+/// Hands events to the MQTT runner, shared between the API and tasks.
+/// Publishing never waits for the broker.
+#[derive(Clone)]
+pub struct Mqtt {
+    sender: UnboundedSender<(MqttEvent, i64)>,
+}
+
+impl Mqtt {
+    /// The publisher, and the events for `runner::start` to send
+    pub fn new() -> (Self, UnboundedReceiver<(MqttEvent, i64)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Mqtt { sender }, receiver)
+    }
+
+    pub fn publish(&self, event: MqttEvent) {
+        if let Err(e) = self.sender.send((event, Utc::now().timestamp())) {
+            log::warn!("MQTT runner stopped, dropping {:?}", e.0 .0);
+        }
+    }
+}
+
+/// The message body for an event
+fn payload(event: &MqttEvent, created_at: i64) -> String {
+    serde_json::to_string(&Payload { event, created_at }).expect("Events serialize to JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::test_item;
+
+    #[test]
+    fn test_topic_and_payload() {
+        let settings = MqttSettings::default();
+        let event = MqttEvent::Delivery {
+            subscription_id: SubscriptionId(3),
+            recipient: "test@example.com".to_string(),
+            item_count: 2,
+        };
+        assert_eq!(event.topic(&settings), "mailfeed/deliveries");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&payload(&event, 1000)).unwrap(),
+            serde_json::json!({
+                "event": "delivery",
+                "data": {"subscription_id": 3, "recipient": "test@example.com", "item_count": 2},
+                "created_at": 1000,
+            })
+        );
+
+        let item = FeedItem {
+            feed_id: FeedId(2),
+            title: "Title".to_string(),
+            pub_date: 900,
+            ..test_item(1)
+        };
+        assert_eq!(
+            MqttEvent::new_item(&item).topic(&settings),
+            "mailfeed/items"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use tokio::{sync::mpsc::UnboundedReceiver, task::JoinHandle};
+
+use super::{payload, MqttEvent};
+use crate::{
+    models::mqtt_settings::MqttSettings,
+    tasks::types::{MQTT_KEEP_ALIVE, MQTT_QUEUE_SIZE, MQTT_RECONNECT_DELAY},
+    DbPool,
+};
+
+/// A client for the broker in the settings it was made from
+struct Connection {
+    settings: MqttSettings,
+    client: AsyncClient,
+    event_loop: JoinHandle<()>,
+}
+
+impl Connection {
+    fn open(settings: MqttSettings) -> Self {
+        let mut options = MqttOptions::new(&settings.client_id, &settings.host, settings.port);
+        options.set_keep_alive(MQTT_KEEP_ALIVE);
+        if !settings.username.is_empty() {
+            options.set_credentials(
+                &settings.username,
+                settings.password.clone().unwrap_or_default(),
+            );
+        }
+        let (client, mut event_loop) = AsyncClient::new(options, MQTT_QUEUE_SIZE);
+        let host = format!("{}:{}", settings.host, settings.port);
+        // messages are only sent while the event loop is polled, and polling
+        // again after an error reconnects
+        let event_loop = tokio::spawn(async move {
+            loop {
+                if let Err(e) = event_loop.poll().await {
+                    log::warn!("MQTT connection to {} failed: {}", host, e);
+                    tokio::time::sleep(MQTT_RECONNECT_DELAY).await;
+                }
+            }
+        });
+        log::info!(
+            "Publishing events to MQTT broker {}:{}",
+            settings.host,
+            settings.port
+        );
+        Connection {
+            settings,
+            client,
+            event_loop,
+        }
+    }
+
+    fn close(self) {
+        self.event_loop.abort();
+    }
+}
+
+/// Publish each event to the broker in the settings, if MQTT is enabled.
+/// Settings are read for every event, so changes apply without a restart.
+pub async fn start(pool: DbPool, mut events: UnboundedReceiver<(MqttEvent, i64)>) {
+    let mut connection: Option<Connection> = None;
+    while let Some((event, created_at)) = events.recv().await {
+        let settings = match pool.get() {
+            Ok(mut conn) => MqttSettings::load(&mut conn),
+            Err(e) => {
+                log::error!("Error getting DB connection: {:?}", e);
+                continue;
+            }
+        };
+        if !settings.enabled {
+            if let Some(open) = connection.take() {
+                open.close();
+            }
+            continue;
+        }
+        let current = match connection.take() {
+            Some(open) if open.settings == settings => open,
+            open => {
+                if let Some(open) = open {
+                    open.close();
+                }
+                Connection::open(settings)
+            }
+        };
+
+        let topic = event.topic(&current.settings);
+        // don't wait on a broker that's down, messages are dropped once the queue is full
+        if let Err(e) =
+            current
+                .client
+                .try_publish(topic, QoS::AtLeastOnce, false, payload(&event, created_at))
+        {
+            log::warn!("Error publishing to MQTT topic {}: {}", topic, e);
+        }
+        connection = Some(current);
+    }
+}
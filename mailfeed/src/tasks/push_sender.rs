@@ -0,0 +1,380 @@
+use futures_util::{future::BoxFuture, FutureExt};
+use reqwest::{Client, RequestBuilder};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    db::DbConnection,
+    models::{
+        feed::Feed,
+        feed_item::FeedItem,
+        push_settings::{PushService, PushSettings},
+        retry_policy::{Channel, RetryPolicy},
+        subscription::DeliveryMethod,
+        user::User,
+    },
+    tasks::{
+        dispatch::{is_web_link, truncate, Batch, ChannelError, DeliveryChannel, Destination},
+        html_to_text::html_to_text_truncated,
+        retry::with_retries_async,
+        types::PUSH_TIMEOUT,
+    },
+};
+
+/// Longest title and message sent, well within what both services accept
+/// and what fits on a phone's lock screen
+const MAX_TITLE_CHARS: usize = 120;
+const MAX_MESSAGE_CHARS: usize = 500;
+/// Most notifications sent for a subscription at once. Past this, the rest
+/// are counted in one last notification instead.
+const MAX_NOTIFICATIONS: usize = 5;
+/// Gotify's default priority, which shows a notification without sound
+/// on most clients
+const GOTIFY_PRIORITY: u8 = 5;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Push notifications aren't set up")]
+    NotConfigured,
+    #[error("HTTP {0}")]
+    Status(u16),
+    #[error("{0}")]
+    Request(String),
+}
+
+impl Error {
+    /// Whether trying again later might work
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::NotConfigured => false,
+            Error::Status(status) => *status == 429 || *status >= 500,
+            Error::Request(_) => true,
+        }
+    }
+}
+
+/// One push notification, opening `click` when tapped
+#[derive(Debug, Serialize, PartialEq)]
+struct Notification {
+    title: String,
+    message: String,
+    click: Option<String>,
+}
+
+/// The notifications for a subscription's new items, one per item up to
+/// the limit
+fn notifications(
+    feed_title: &str,
+    feed: &Feed,
+    homepage: &str,
+    items: &[FeedItem],
+) -> Vec<Notification> {
+    let link_mode = feed.link_mode();
+    let shown = if items.len() > MAX_NOTIFICATIONS {
+        MAX_NOTIFICATIONS - 1
+    } else {
+        items.len()
+    };
+    let mut notifications: Vec<Notification> = items[..shown]
+        .iter()
+        .map(|item| {
+            let (link, _) = item.display_links(link_mode);
+            let summary = item
+                .description
+                .as_deref()
+                .map(|description| html_to_text_truncated(description, MAX_MESSAGE_CHARS).0)
+                .filter(|summary| !summary.is_empty());
+            Notification {
+                title: truncate(&item.title, MAX_TITLE_CHARS),
+                message: truncate(
+                    &summary.unwrap_or_else(|| feed_title.to_string()),
+                    MAX_MESSAGE_CHARS,
+                ),
+                click: Some(link.to_string()).filter(|link| is_web_link(link)),
+            }
+        })
+        .collect();
+    if shown < items.len() {
+        notifications.push(Notification {
+            title: truncate(feed_title, MAX_TITLE_CHARS),
+            message: format!("{} more new items", items.len() - shown),
+            click: Some(homepage.to_string()).filter(|link| is_web_link(link)),
+        });
+    }
+    notifications
+}
+
+/// The request publishing the notification to the user's service
+fn request(
+    client: &Client,
+    settings: &PushSettings,
+    notification: &Notification,
+) -> Result<RequestBuilder, Error> {
+    let url = settings.server_url();
+    let request = match settings.service {
+        Some(PushService::Ntfy) if !settings.topic.is_empty() => {
+            let body = json!({
+                "topic": settings.topic,
+                "title": notification.title,
+                "message": notification.message,
+                "click": notification.click,
+            });
+            let request = client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+            match &settings.token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        }
+        Some(PushService::Gotify) if !url.is_empty() => {
+            let token = settings.token.as_deref().ok_or(Error::NotConfigured)?;
+            let mut body = json!({
+                "title": notification.title,
+                "message": notification.message,
+                "priority": GOTIFY_PRIORITY,
+            });
+            if let Some(click) = &notification.click {
+                body["extras"] = json!({
+                    "client::notification": { "click": { "url": click } }
+                });
+            }
+            client
+                .post(format!("{}/message", url))
+                .header("X-Gotify-Key", token)
+                .header("Content-Type", "application/json")
+                .body(body.to_string())
+        }
+        _ => return Err(Error::NotConfigured),
+    };
+    Ok(request)
+}
+
+/// Send a notification to the user's service
+async fn send(
+    client: &Client,
+    settings: &PushSettings,
+    notification: &Notification,
+    retry_policy: &RetryPolicy,
+) -> Result<(), Error> {
+    with_retries_async(
+        retry_policy,
+        "send push notification",
+        || send_once(client, settings, notification),
+        Error::is_retryable,
+    )
+    .await
+}
+
+async fn send_once(
+    client: &Client,
+    settings: &PushSettings,
+    notification: &Notification,
+) -> Result<(), Error> {
+    let response = request(client, settings, notification)?
+        .send()
+        .await
+        // an ntfy topic is as good as a password, so leave the URL out
+        .map_err(|e| Error::Request(e.without_url().to_string()))?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(Error::Status(status.as_u16())),
+    }
+}
+
+/// Send a one-off notice, like telling the user about a change to their
+/// account, rather than feed items
+pub async fn send_notice(
+    settings: &PushSettings,
+    title: &str,
+    message: &str,
+    click: Option<&str>,
+    retry_policy: &RetryPolicy,
+) -> Result<(), Error> {
+    let client = Client::builder()
+        .timeout(PUSH_TIMEOUT)
+        .build()
+        .map_err(|e| Error::Request(e.to_string()))?;
+    let notification = Notification {
+        title: truncate(title, MAX_TITLE_CHARS),
+        message: truncate(message, MAX_MESSAGE_CHARS),
+        click: click.filter(|link| is_web_link(link)).map(str::to_string),
+    };
+    send(&client, settings, &notification, retry_policy).await
+}
+
+/// Push notifications for the new items of realtime subscriptions, sent to
+/// the user's ntfy topic or Gotify server
+pub struct PushChannel {
+    client: Client,
+}
+
+impl Default for PushChannel {
+    fn default() -> Self {
+        let client = Client::builder()
+            .timeout(PUSH_TIMEOUT)
+            .build()
+            .expect("Error building HTTP client");
+        PushChannel { client }
+    }
+}
+
+impl DeliveryChannel for PushChannel {
+    fn method(&self) -> DeliveryMethod {
+        DeliveryMethod::Push
+    }
+
+    fn retry_channel(&self) -> Channel {
+        Channel::Push
+    }
+
+    fn destination(&self, conn: &mut DbConnection, user: &User) -> Option<Box<dyn Destination>> {
+        let settings = PushSettings::load(conn, user.id);
+        if !settings.is_configured() {
+            return None;
+        }
+        Some(Box::new(PushDestination {
+            client: self.client.clone(),
+            settings,
+        }))
+    }
+
+    /// Each notification's title, message and link, as the services differ
+    fn preview(&self, _user: &User, batch: &Batch) -> Vec<String> {
+        notifications(batch.title(), batch.feed, batch.homepage(), batch.items)
+            .iter()
+            .map(|notification| {
+                serde_json::to_string(notification).expect("Notification serializes to JSON")
+            })
+            .collect()
+    }
+}
+
+struct PushDestination {
+    client: Client,
+    settings: PushSettings,
+}
+
+impl Destination for PushDestination {
+    /// The ntfy topic works like a password, so only the service is
+    /// recorded
+    fn recipient(&self) -> &str {
+        self.settings
+            .service
+            .map_or("push", |service| service.as_str())
+    }
+
+    fn send<'a>(
+        &'a self,
+        batch: &'a Batch<'a>,
+        retry_policy: &'a RetryPolicy,
+    ) -> BoxFuture<'a, Result<(), ChannelError>> {
+        async move {
+            let sent = notifications(batch.title(), batch.feed, batch.homepage(), batch.items);
+            for notification in sent {
+                send(&self.client, &self.settings, &notification, retry_policy).await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test_helpers::{test_feed, test_item};
+
+    fn long_item(id: i32, description: Option<&str>) -> FeedItem {
+        FeedItem {
+            title: "x".repeat(200),
+            description: description.map(str::to_string),
+            ..test_item(id)
+        }
+    }
+
+    #[test]
+    fn test_notifications() {
+        let items = vec![
+            long_item(1, Some("<p>Hi <b>there</b></p>")),
+            long_item(2, None),
+        ];
+        let sent = notifications("My feed", &test_feed(), "https://example.com", &items);
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].title.chars().count(), MAX_TITLE_CHARS);
+        assert!(sent[0].title.ends_with('…'));
+        assert_eq!(sent[0].message, "Hi there");
+        assert_eq!(sent[0].click.as_deref(), Some("https://example.com/1"));
+        // without a description, the feed's name is the message
+        assert_eq!(sent[1].message, "My feed");
+    }
+
+    #[test]
+    fn test_notifications_past_the_limit_are_counted() {
+        let items: Vec<FeedItem> = (0..8).map(|id| long_item(id, None)).collect();
+        let sent = notifications("My feed", &test_feed(), "https://example.com", &items);
+        assert_eq!(sent.len(), MAX_NOTIFICATIONS);
+        assert_eq!(
+            sent.last(),
+            Some(&Notification {
+                title: "My feed".to_string(),
+                message: "4 more new items".to_string(),
+                click: Some("https://example.com".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_request() {
+        let client = Client::new();
+        let notification = Notification {
+            title: "Title".to_string(),
+            message: "Message".to_string(),
+            click: Some("https://example.com/1".to_string()),
+        };
+
+        let ntfy = PushSettings {
+            service: Some(PushService::Ntfy),
+            topic: "my-feeds".to_string(),
+            ..Default::default()
+        };
+        let built = request(&client, &ntfy, &notification)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(built.url().as_str(), "https://ntfy.sh/");
+        let body: serde_json::Value =
+            serde_json::from_slice(built.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(body["topic"], "my-feeds");
+        assert_eq!(body["click"], "https://example.com/1");
+
+        let gotify = PushSettings {
+            service: Some(PushService::Gotify),
+            url: "https://push.example.com".to_string(),
+            token: Some("AbCdEf".to_string()),
+            ..Default::default()
+        };
+        let built = request(&client, &gotify, &notification)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(built.url().as_str(), "https://push.example.com/message");
+        assert_eq!(built.headers()["X-Gotify-Key"], "AbCdEf");
+        let body: serde_json::Value =
+            serde_json::from_slice(built.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(
+            body["extras"]["client::notification"]["click"]["url"],
+            "https://example.com/1"
+        );
+
+        let unset = PushSettings {
+            token: None,
+            ..gotify
+        };
+        assert!(matches!(
+            request(&client, &unset, &notification),
+            Err(Error::NotConfigured)
+        ));
+    }
+}
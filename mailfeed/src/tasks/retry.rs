@@ -0,0 +1,136 @@
+use std::{fmt::Display, future::Future};
+
+use rand::Rng;
+use tokio::time::Duration;
+
+use crate::models::retry_policy::RetryPolicy;
+
+/// Run `op` until it succeeds, fails with an error `is_retryable` rejects,
+/// or the policy runs out of attempts, waiting a jittered exponential
+/// backoff between attempts. Returns the last attempt's result.
+pub async fn with_retries<T, E, F, R>(
+    policy: &RetryPolicy,
+    what: &str,
+    mut op: F,
+    is_retryable: R,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Result<T, E>,
+    R: Fn(&E) -> bool,
+{
+    with_retries_async(policy, what, || std::future::ready(op()), is_retryable).await
+}
+
+/// Like `with_retries`, for operations that are themselves async
+pub async fn with_retries_async<T, E, F, Fut, R>(
+    policy: &RetryPolicy,
+    what: &str,
+    mut op: F,
+    is_retryable: R,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    R: Fn(&E) -> bool,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Err(e) if attempt < policy.max_attempts && is_retryable(&e) => {
+                let delay = jitter(policy.backoff(attempt));
+                log::warn!(
+                    "Attempt {} of {} to {} failed: {}, retrying in {:?}",
+                    attempt,
+                    policy.max_attempts,
+                    what,
+                    e,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Somewhere between half and all of the backoff, so senders retrying at
+/// the same time spread out without ever retrying immediately
+fn jitter(backoff: Duration) -> Duration {
+    let max = backoff.as_millis() as u64;
+    Duration::from_millis(rand::thread_rng().gen_range(max / 2..=max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay_seconds: 0,
+            max_delay_seconds: 0,
+        }
+    }
+
+    #[test]
+    fn test_jitter() {
+        for _ in 0..100 {
+            let delay = jitter(Duration::from_secs(10));
+            assert!(delay >= Duration::from_secs(5) && delay <= Duration::from_secs(10));
+        }
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+    }
+
+    #[actix_web::test]
+    async fn test_retries_until_success() {
+        let mut attempts = 0;
+        let result: Result<u32, String> = with_retries(
+            &policy(3),
+            "test",
+            || {
+                attempts += 1;
+                match attempts {
+                    3 => Ok(attempts),
+                    _ => Err("transient".to_string()),
+                }
+            },
+            |_| true,
+        )
+        .await;
+        assert_eq!(result, Ok(3));
+    }
+
+    #[actix_web::test]
+    async fn test_gives_up() {
+        let mut attempts = 0;
+        let result: Result<(), String> = with_retries(
+            &policy(3),
+            "test",
+            || {
+                attempts += 1;
+                Err(format!("failure {}", attempts))
+            },
+            |_| true,
+        )
+        .await;
+        assert_eq!(result, Err("failure 3".to_string()));
+
+        // errors that won't go away aren't retried
+        let mut attempts = 0;
+        let result: Result<(), String> = with_retries(
+            &policy(3),
+            "test",
+            || {
+                attempts += 1;
+                Err("permanent".to_string())
+            },
+            |e| e != "permanent",
+        )
+        .await;
+        assert_eq!(result, Err("permanent".to_string()));
+        assert_eq!(attempts, 1);
+    }
+}
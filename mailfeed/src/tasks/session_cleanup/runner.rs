@@ -0,0 +1,23 @@
+use crate::{models::session::Session, tasks::types::SESSION_CLEANUP_INTERVAL, DbPool};
+
+/// Periodically delete sessions whose refresh tokens have expired, so
+/// stale sessions don't linger in the database
+pub async fn start(pool: DbPool) {
+    let mut interval = tokio::time::interval(SESSION_CLEANUP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let mut conn = match pool.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("Error getting DB connection: {:?}", e);
+                continue;
+            }
+        };
+
+        match Session::delete_expired(&mut conn, chrono::Utc::now().timestamp()) {
+            Ok(0) => log::debug!("Session cleanup: no expired sessions"),
+            Ok(cleared) => log::info!("Session cleanup: removed {} expired sessions", cleared),
+            Err(e) => log::error!("Error cleaning up sessions: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::env;
+
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    models::retry_policy::RetryPolicy,
+    tasks::{dispatch::truncate, retry::with_retries_async, types::TELEGRAM_TIMEOUT},
+};
+
+/// Telegram only accepts messages up to this many characters
+const MAX_MESSAGE_CHARS: usize = 4096;
+/// ...and photo captions up to this many
+const MAX_CAPTION_CHARS: usize = 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("HTTP {0}")]
+    Status(u16),
+    #[error("{0}")]
+    Request(String),
+    /// the Bot API turned the call down, with its description of why
+    #[error("{0}")]
+    Api(String),
+}
+
+impl Error {
+    /// Whether trying again later might work
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Status(status) => *status == 429 || *status >= 500,
+            Error::Request(_) => true,
+            Error::Api(_) => false,
+        }
+    }
+}
+
+/// How the bot receives updates. MailFeed only sends, but a webhook set by
+/// something else sharing the token is worth knowing about.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateMode {
+    Webhook,
+    /// no webhook, so updates wait for getUpdates
+    Polling,
+}
+
+/// What the Bot API says about the configured token, for diagnosing it
+/// without the logs
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct BotStatus {
+    /// whether `MF_TELEGRAM_BOT_TOKEN` is set
+    pub configured: bool,
+    /// whether the Bot API accepted the token
+    pub connected: bool,
+    pub username: Option<String>,
+    pub update_mode: Option<UpdateMode>,
+    /// updates Telegram is holding for the bot
+    pub pending_updates: Option<i64>,
+    /// why the API couldn't be reached or turned the token down, or else
+    /// the last error Telegram had delivering to the webhook
+    pub last_error: Option<String>,
+    /// when Telegram had that webhook error
+    pub last_error_at: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BotUser {
+    username: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookInfo {
+    url: String,
+    #[serde(default)]
+    pending_update_count: i64,
+    last_error_date: Option<i64>,
+    last_error_message: Option<String>,
+}
+
+/// Telegram's answer to a call, which has a description of what went wrong
+/// even when the status isn't a success
+fn parse_response<T: DeserializeOwned>(status: u16, body: &str) -> Result<T, Error> {
+    match serde_json::from_str::<ApiResponse<T>>(body) {
+        Ok(ApiResponse {
+            ok: true,
+            result: Some(result),
+            ..
+        }) => Ok(result),
+        Ok(ApiResponse {
+            description: Some(description),
+            ..
+        }) => Err(Error::Api(description)),
+        _ => Err(Error::Status(status)),
+    }
+}
+
+fn bot_status(
+    me: Result<BotUser, Error>,
+    webhook: Option<Result<WebhookInfo, Error>>,
+) -> BotStatus {
+    let me = match me {
+        Ok(me) => me,
+        Err(e) => {
+            return BotStatus {
+                configured: true,
+                last_error: Some(e.to_string()),
+                ..Default::default()
+            }
+        }
+    };
+    let mut status = BotStatus {
+        configured: true,
+        connected: true,
+        username: me.username,
+        ..Default::default()
+    };
+    match webhook {
+        Some(Ok(info)) => {
+            status.update_mode = Some(if info.url.is_empty() {
+                UpdateMode::Polling
+            } else {
+                UpdateMode::Webhook
+            });
+            status.pending_updates = Some(info.pending_update_count);
+            status.last_error = info.last_error_message;
+            status.last_error_at = info.last_error_date;
+        }
+        Some(Err(e)) => status.last_error = Some(e.to_string()),
+        None => {}
+    }
+    status
+}
+
+/// Sends messages as the bot set with `MF_TELEGRAM_BOT_TOKEN`
+pub struct TelegramBot {
+    http: Client,
+    token: String,
+}
+
+impl TelegramBot {
+    /// None if no bot token is set
+    pub fn from_env() -> Option<Self> {
+        let token = env::var("MF_TELEGRAM_BOT_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())?;
+        let http = Client::builder()
+            .timeout(TELEGRAM_TIMEOUT)
+            .build()
+            .expect("Error building HTTP client");
+        Some(TelegramBot { http, token })
+    }
+
+    /// Send a plain text message to the chat, cut short if it's too long
+    pub async fn send(
+        &self,
+        chat_id: &str,
+        text: &str,
+        retry_policy: &RetryPolicy,
+    ) -> Result<(), Error> {
+        let body = json!({
+            "chat_id": chat_id,
+            "text": truncate(text, MAX_MESSAGE_CHARS),
+            "disable_web_page_preview": true,
+        })
+        .to_string();
+        with_retries_async(
+            retry_policy,
+            &format!("send Telegram message to {}", chat_id),
+            || self.send_once("sendMessage", &body),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    /// Send a photo by its URL, with a caption cut short if it's too long.
+    /// Telegram fetches the photo itself, and turns it down with a 400 if
+    /// it can't or the photo is too big, so callers can fall back to text.
+    pub async fn send_photo(
+        &self,
+        chat_id: &str,
+        photo_url: &str,
+        caption: &str,
+        retry_policy: &RetryPolicy,
+    ) -> Result<(), Error> {
+        let body = json!({
+            "chat_id": chat_id,
+            "photo": photo_url,
+            "caption": truncate(caption, MAX_CAPTION_CHARS),
+        })
+        .to_string();
+        with_retries_async(
+            retry_policy,
+            &format!("send Telegram photo to {}", chat_id),
+            || self.send_once("sendPhoto", &body),
+            Error::is_retryable,
+        )
+        .await
+    }
+
+    /// Ask the Bot API who the bot is and how it gets updates
+    pub async fn status(&self) -> BotStatus {
+        let me = self.call::<BotUser>("getMe").await;
+        let webhook = match me {
+            Ok(_) => Some(self.call::<WebhookInfo>("getWebhookInfo").await),
+            Err(_) => None,
+        };
+        bot_status(me, webhook)
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str) -> Result<T, Error> {
+        let response = self
+            .http
+            .get(format!(
+                "https://api.telegram.org/bot{}/{}",
+                self.token, method
+            ))
+            .send()
+            .await
+            .map_err(|e| Error::Request(e.without_url().to_string()))?;
+        let status = response.status().as_u16();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| Error::Request(e.without_url().to_string()))?;
+        parse_response(status, &body)
+    }
+
+    async fn send_once(&self, method: &str, body: &str) -> Result<(), Error> {
+        let response = self
+            .http
+            .post(format!(
+                "https://api.telegram.org/bot{}/{}",
+                self.token, method
+            ))
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            // the URL holds the token, so leave it out of the error
+            .map_err(|e| Error::Request(e.without_url().to_string()))?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(Error::Status(status.as_u16())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", MAX_MESSAGE_CHARS), "short");
+        let long = "a".repeat(MAX_MESSAGE_CHARS + 10);
+        let truncated = truncate(&long, MAX_MESSAGE_CHARS);
+        assert_eq!(truncated.chars().count(), MAX_MESSAGE_CHARS);
+        assert!(truncated.ends_with('…'));
+    }
+
+    #[test]
+    fn test_bot_status() {
+        let me = parse_response::<BotUser>(
+            200,
+            r#"{"ok":true,"result":{"id":1,"is_bot":true,"username":"mailfeed_bot"}}"#,
+        );
+        let webhook = parse_response::<WebhookInfo>(
+            200,
+            r#"{"ok":true,"result":{"url":"https://hooks.example/tg","pending_update_count":3,
+                "last_error_date":1700000000,"last_error_message":"Connection refused"}}"#,
+        );
+        assert_eq!(
+            bot_status(me, Some(webhook)),
+            BotStatus {
+                configured: true,
+                connected: true,
+                username: Some("mailfeed_bot".to_string()),
+                update_mode: Some(UpdateMode::Webhook),
+                pending_updates: Some(3),
+                last_error: Some("Connection refused".to_string()),
+                last_error_at: Some(1_700_000_000),
+            }
+        );
+
+        let webhook = parse_response::<WebhookInfo>(
+            200,
+            r#"{"ok":true,"result":{"url":"","pending_update_count":0}}"#,
+        );
+        let me = parse_response::<BotUser>(200, r#"{"ok":true,"result":{"username":"b"}}"#);
+        let status = bot_status(me, Some(webhook));
+        assert_eq!(status.update_mode, Some(UpdateMode::Polling));
+        assert_eq!(status.last_error, None);
+
+        let bad_token = parse_response::<BotUser>(
+            401,
+            r#"{"ok":false,"error_code":401,"description":"Unauthorized"}"#,
+        );
+        let status = bot_status(bad_token, None);
+        assert!(!status.connected);
+        assert_eq!(status.last_error.as_deref(), Some("Unauthorized"));
+
+        let not_json = parse_response::<BotUser>(502, "Bad Gateway");
+        assert_eq!(
+            bot_status(not_json, None).last_error.as_deref(),
+            Some("HTTP 502")
+        );
+    }
+}
@@ -1,3 +1,51 @@
 use tokio::time::Duration;
 
 pub const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 5);
+
+/// How long resolved feed host addresses are reused
+pub const DNS_CACHE_TTL: Duration = Duration::from_secs(60 * 10);
+
+/// How long to wait for a feed to respond before giving up on it
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait after fetching a feed being imported before the next,
+/// so large imports don't hit many servers at once
+pub const IMPORT_FETCH_DELAY: Duration = Duration::from_secs(1);
+
+/// How long aggregator score and comment counts are reused before refetching
+pub const ITEM_STATS_CACHE_TTL: Duration = Duration::from_secs(60 * 15);
+
+/// How often to check whether it's time for database maintenance
+pub const MAINTENANCE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+pub const SESSION_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long to wait for a webhook endpoint to respond
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often to ping the MQTT broker to keep the connection open
+pub const MQTT_KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// How long to wait before reconnecting to the MQTT broker
+pub const MQTT_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Messages waiting for the MQTT broker before more are dropped
+pub const MQTT_QUEUE_SIZE: usize = 100;
+
+/// How often starred items are pushed to users' bookmark managers
+pub const BOOKMARK_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long to wait for a bookmark manager to respond
+pub const BOOKMARK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long to wait for the Telegram Bot API to respond
+pub const TELEGRAM_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long to wait for Discord to accept a webhook message
+pub const DISCORD_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long to wait for a Matrix homeserver to accept a message
+pub const MATRIX_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long to wait for an ntfy or Gotify server to accept a notification
+pub const PUSH_TIMEOUT: Duration = Duration::from_secs(15);
@@ -0,0 +1,248 @@
+use futures_util::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Serialize;
+
+use crate::{
+    db::DbConnection,
+    models::{
+        delivery_webhook::DeliveryWebhook,
+        feed::Feed,
+        feed_item::FeedItem,
+        ids::SubscriptionId,
+        retry_policy::{Channel, RetryPolicy},
+        subscription::DeliveryMethod,
+        user::User,
+    },
+    tasks::{
+        dispatch::{Batch, ChannelError, DeliveryChannel, Destination},
+        retry::with_retries_async,
+        types::WEBHOOK_TIMEOUT,
+        webhooks::{
+            runner::{post, SendError},
+            sign,
+        },
+    },
+};
+
+/// The `X-Mailfeed-Event` header and `event` field of deliveries, so
+/// endpoints that also receive instance events can tell them apart
+const EVENT: &str = "new_items";
+
+/// A subscription's new items, sent as `{"event": "new_items", "data",
+/// "created_at"}` like instance events
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    event: &'static str,
+    data: NewItems<'a>,
+    created_at: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct NewItems<'a> {
+    subscription_id: SubscriptionId,
+    feed: FeedInfo<'a>,
+    items: Vec<Item<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct FeedInfo<'a> {
+    /// the subscription's name for it
+    title: &'a str,
+    url: &'a str,
+    homepage: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct Item<'a> {
+    title: &'a str,
+    /// per the feed's link mode, like in emails
+    link: &'a str,
+    comments_link: Option<&'a str>,
+    pub_date: i64,
+    author: Option<&'a str>,
+    /// as the feed sent it, usually HTML
+    description: Option<&'a str>,
+}
+
+/// The request body for a subscription's new items
+fn payload(
+    subscription_id: SubscriptionId,
+    title: &str,
+    feed: &Feed,
+    homepage: &str,
+    items: &[FeedItem],
+    created_at: i64,
+) -> String {
+    let link_mode = feed.link_mode();
+    let items = items
+        .iter()
+        .map(|item| {
+            let (link, comments_link) = item.display_links(link_mode);
+            Item {
+                title: &item.title,
+                link,
+                comments_link,
+                pub_date: item.pub_date,
+                author: item.author.as_deref(),
+                description: item.description.as_deref(),
+            }
+        })
+        .collect();
+    let payload = Payload {
+        event: EVENT,
+        data: NewItems {
+            subscription_id,
+            feed: FeedInfo {
+                title,
+                url: &feed.url,
+                homepage,
+            },
+            items,
+        },
+        created_at,
+    };
+    serde_json::to_string(&payload).expect("Items serialize to JSON")
+}
+
+/// New items of subscriptions delivered by webhook, POSTed to the user's
+/// delivery webhook on the subscription's frequency like emails
+pub struct WebhookChannel {
+    client: Client,
+}
+
+impl Default for WebhookChannel {
+    fn default() -> Self {
+        let client = Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .expect("Error building HTTP client");
+        WebhookChannel { client }
+    }
+}
+
+impl DeliveryChannel for WebhookChannel {
+    fn method(&self) -> DeliveryMethod {
+        DeliveryMethod::Webhook
+    }
+
+    fn retry_channel(&self) -> Channel {
+        Channel::Webhook
+    }
+
+    fn destination(&self, conn: &mut DbConnection, user: &User) -> Option<Box<dyn Destination>> {
+        let webhook = DeliveryWebhook::load(conn, user.id);
+        let (url, secret) = webhook.target()?;
+        Some(Box::new(WebhookDestination {
+            client: self.client.clone(),
+            url: url.to_string(),
+            secret: secret.to_string(),
+        }))
+    }
+
+    fn preview(&self, _user: &User, batch: &Batch) -> Vec<String> {
+        vec![payload(
+            batch.sub.id,
+            batch.title(),
+            batch.feed,
+            batch.homepage(),
+            batch.items,
+            batch.now,
+        )]
+    }
+}
+
+struct WebhookDestination {
+    client: Client,
+    url: String,
+    secret: String,
+}
+
+impl Destination for WebhookDestination {
+    fn recipient(&self) -> &str {
+        &self.url
+    }
+
+    fn send<'a>(
+        &'a self,
+        batch: &'a Batch<'a>,
+        retry_policy: &'a RetryPolicy,
+    ) -> BoxFuture<'a, Result<(), ChannelError>> {
+        async move {
+            let body = payload(
+                batch.sub.id,
+                batch.title(),
+                batch.feed,
+                batch.homepage(),
+                batch.items,
+                batch.now,
+            );
+            let signature = sign(&self.secret, &body);
+            with_retries_async(
+                retry_policy,
+                &format!("send items for sub_id={} to {}", batch.sub.id, self.url),
+                || post(&self.client, &self.url, EVENT, &signature, &body),
+                SendError::is_retryable,
+            )
+            .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::feed::LinkMode,
+        test_helpers::test_helpers::{test_feed, test_item},
+    };
+
+    #[test]
+    fn test_payload() {
+        let feed = Feed {
+            url: "https://example.com/feed".to_string(),
+            link_mode: LinkMode::Both,
+            ..test_feed()
+        };
+        let item = FeedItem {
+            title: "Hello".to_string(),
+            link: "https://example.com/hello".to_string(),
+            pub_date: 900,
+            description: Some("<p>Hi</p>".to_string()),
+            comments_link: Some("https://example.com/hello#comments".to_string()),
+            ..test_item(1)
+        };
+        let body = payload(
+            SubscriptionId(2),
+            "My feed",
+            &feed,
+            "https://example.com",
+            &[item],
+            1000,
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!({
+                "event": "new_items",
+                "data": {
+                    "subscription_id": 2,
+                    "feed": {
+                        "title": "My feed",
+                        "url": "https://example.com/feed",
+                        "homepage": "https://example.com"
+                    },
+                    "items": [{
+                        "title": "Hello",
+                        "link": "https://example.com/hello",
+                        "comments_link": "https://example.com/hello#comments",
+                        "pub_date": 900,
+                        "author": null,
+                        "description": "<p>Hi</p>"
+                    }]
+                },
+                "created_at": 1000
+            })
+        );
+    }
+}
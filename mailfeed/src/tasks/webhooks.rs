@@ -0,0 +1,145 @@
+pub mod runner;
+
+use chrono::Utc;
+use ring::hmac;
+use serde::Serialize;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::models::{
+    feed::FeedErrorKind,
+    ids::{FeedId, SubscriptionId, UserId},
+    webhook::EventType,
+};
+
+/// Something that happened, sent to webhooks as `{"event", "data",
+/// "created_at"}`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum Event {
+    UserCreated {
+        user_id: UserId,
+        email: String,
+    },
+    FeedBroken {
+        feed_id: FeedId,
+        url: String,
+        kind: FeedErrorKind,
+        message: String,
+    },
+    DigestSent {
+        subscription_id: SubscriptionId,
+        recipient: String,
+        item_count: usize,
+    },
+    TaskFailed {
+        /// e.g. `send_digest` or `database_maintenance`
+        task: String,
+        message: String,
+    },
+}
+
+impl Event {
+    pub fn event_type(&self) -> EventType {
+        match self {
+            Event::UserCreated { .. } => EventType::UserCreated,
+            Event::FeedBroken { .. } => EventType::FeedBroken,
+            Event::DigestSent { .. } => EventType::DigestSent,
+            Event::TaskFailed { .. } => EventType::TaskFailed,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    event: &'a Event,
+    created_at: i64,
+}
+
+/// Hands events to the webhook runner, shared between the API and tasks.
+/// Emitting never waits for the webhooks to be sent.
+#[derive(Clone)]
+pub struct Webhooks {
+    sender: UnboundedSender<(Event, i64)>,
+}
+
+impl Webhooks {
+    /// The emitter, and the events for `runner::start` to send
+    pub fn new() -> (Self, UnboundedReceiver<(Event, i64)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Webhooks { sender }, receiver)
+    }
+
+    pub fn emit(&self, event: Event) {
+        if let Err(e) = self.sender.send((event, Utc::now().timestamp())) {
+            log::warn!("Webhook runner stopped, dropping {:?}", e.0 .0);
+        }
+    }
+}
+
+/// The request body for an event
+fn payload(event: &Event, created_at: i64) -> String {
+    serde_json::to_string(&Payload { event, created_at }).expect("Events serialize to JSON")
+}
+
+/// HMAC-SHA256 of the body keyed with the webhook's secret, as sent in the
+/// `X-Mailfeed-Signature` header so endpoints can check requests came from
+/// this instance
+pub(crate) fn sign(secret: &str, body: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, body.as_bytes());
+    let hex: String = tag
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(
+            sign("key", "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_payload() {
+        let event = Event::TaskFailed {
+            task: "database_maintenance".to_string(),
+            message: "analyze failed".to_string(),
+        };
+        assert_eq!(event.event_type(), EventType::TaskFailed);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&payload(&event, 1000)).unwrap(),
+            serde_json::json!({
+                "event": "task_failed",
+                "data": {"task": "database_maintenance", "message": "analyze failed"},
+                "created_at": 1000,
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_emit() {
+        let (webhooks, mut events) = Webhooks::new();
+        let event = Event::UserCreated {
+            user_id: UserId(1),
+            email: "test@example.com".to_string(),
+        };
+        webhooks.emit(event.clone());
+        assert_eq!(events.recv().await.map(|(event, _)| event), Some(event));
+
+        // nothing fails once the runner is gone
+        drop(events);
+        webhooks.emit(Event::TaskFailed {
+            task: "test".to_string(),
+            message: "test".to_string(),
+        });
+    }
+}
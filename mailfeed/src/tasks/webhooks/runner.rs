@@ -0,0 +1,157 @@
+use chrono::Utc;
+use reqwest::Client;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use super::{payload, sign, Event};
+use crate::{
+    models::{
+        retry_policy::{Channel, RetryPolicy},
+        webhook::{PartialWebhook, Webhook},
+    },
+    tasks::{retry::with_retries_async, types::WEBHOOK_TIMEOUT},
+    DbPool,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum SendError {
+    #[error("HTTP {0}")]
+    Status(u16),
+    #[error("{0}")]
+    Request(String),
+}
+
+impl SendError {
+    /// Whether trying again later might work
+    pub(crate) fn is_retryable(&self) -> bool {
+        match self {
+            SendError::Status(status) => *status == 429 || *status >= 500,
+            SendError::Request(_) => true,
+        }
+    }
+}
+
+/// Send each event to the active webhooks that receive its type. Each
+/// webhook is sent to in the background, so a slow endpoint doesn't hold up
+/// the others.
+pub async fn start(pool: DbPool, mut events: UnboundedReceiver<(Event, i64)>) {
+    let client = Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .expect("Error building HTTP client");
+    while let Some((event, created_at)) = events.recv().await {
+        let mut conn = match pool.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("Error getting DB connection: {:?}", e);
+                continue;
+            }
+        };
+        let webhooks = match Webhook::get_for_event(&mut conn, event.event_type()) {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                log::error!("Error getting webhooks: {:?}", e);
+                continue;
+            }
+        };
+        if webhooks.is_empty() {
+            continue;
+        }
+
+        let body = payload(&event, created_at);
+        let retry_policy = RetryPolicy::load(&mut conn, Channel::Webhook);
+        for webhook in webhooks {
+            tokio::spawn(deliver(
+                pool.clone(),
+                client.clone(),
+                retry_policy.clone(),
+                webhook,
+                event.event_type().as_str(),
+                body.clone(),
+            ));
+        }
+    }
+}
+
+/// POST the body, retrying per the policy, and record how it went on the
+/// webhook
+async fn deliver(
+    pool: DbPool,
+    client: Client,
+    retry_policy: RetryPolicy,
+    webhook: Webhook,
+    event_type: &'static str,
+    body: String,
+) {
+    let signature = sign(&webhook.secret, &body);
+    let sent = with_retries_async(
+        &retry_policy,
+        &format!("send {} webhook to {}", event_type, webhook.url),
+        || post(&client, &webhook.url, event_type, &signature, &body),
+        SendError::is_retryable,
+    )
+    .await;
+    match &sent {
+        Ok(()) => log::info!("Sent {} webhook to {}", event_type, webhook.url),
+        Err(e) => log::error!(
+            "Error sending {} webhook to {}: {}",
+            event_type,
+            webhook.url,
+            e
+        ),
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(e) => {
+            log::error!("Error getting DB connection: {:?}", e);
+            return;
+        }
+    };
+    let update = PartialWebhook {
+        last_delivery_at: Some(Utc::now().timestamp()),
+        last_error: Some(sent.err().map(|e| e.to_string())),
+        ..Default::default()
+    };
+    if let Err(e) = Webhook::update(&mut conn, webhook.id, &update) {
+        log::warn!("Error recording webhook delivery: {:?}", e);
+    }
+}
+
+pub(crate) async fn post(
+    client: &Client,
+    url: &str,
+    event_type: &str,
+    signature: &str,
+    body: &str,
+) -> Result<(), SendError> {
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header(
+            "User-Agent",
+            "Mailfeed (https://github.com/anson-vandoren/mailfeed)",
+        )
+        .header("X-Mailfeed-Event", event_type)
+        .header("X-Mailfeed-Signature", signature)
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| SendError::Request(e.to_string()))?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(SendError::Status(status.as_u16())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(SendError::Status(503).is_retryable());
+        assert!(SendError::Status(429).is_retryable());
+        assert!(!SendError::Status(404).is_retryable());
+        assert!(SendError::Request("connection refused".to_string()).is_retryable());
+    }
+}
@@ -1,15 +1,140 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 pub mod test_helpers {
-    use crate::MIGRATIONS;
-    use diesel::{Connection, SqliteConnection};
-    use diesel_migrations::MigrationHarness;
+    use crate::models::{
+        feed::{Feed, FeedErrorKind, FeedType, LinkMode},
+        feed_item::FeedItem,
+        ids::{FeedId, UserId},
+        role::Role,
+        user::User,
+    };
+    use crate::{
+        db::{self, ConnectionManager, DatabaseUrl, DbConnection},
+        DbPool,
+    };
+    use diesel::{connection::SimpleConnection, r2d2};
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
-    pub fn get_test_db_connection() -> SqliteConnection {
-        let mut conn = SqliteConnection::establish(":memory:")
-            .unwrap_or_else(|_| panic!("Error connecting to in-memory SQLite database"));
+    static NEXT_SCHEMA: AtomicUsize = AtomicUsize::new(0);
 
-        conn.run_pending_migrations(MIGRATIONS)
-            .expect("Failed to run migrations");
+    /// Tests run against an in-memory SQLite database, or against the
+    /// Postgres database in MF_TEST_DATABASE_URL if it's set, where each
+    /// test's changes are rolled back
+    fn test_database() -> ConnectionManager {
+        match std::env::var("MF_TEST_DATABASE_URL") {
+            Ok(url) => ConnectionManager::for_tests(
+                DatabaseUrl::parse(&url).expect("Invalid MF_TEST_DATABASE_URL"),
+            ),
+            Err(_) => ConnectionManager::new(DatabaseUrl::Sqlite(":memory:".to_string())),
+        }
+    }
+
+    /// On Postgres, each test gets a schema of its own, so ids start from 1
+    /// and tests running at once don't wait on each other's rows. It's
+    /// dropped with the rest of the test's changes.
+    fn migrate(conn: &mut DbConnection) {
+        if let DbConnection::Postgres(_) = conn {
+            let schema = format!(
+                "test_{}_{}",
+                std::process::id(),
+                NEXT_SCHEMA.fetch_add(1, Ordering::Relaxed)
+            );
+            conn.batch_execute(&format!(
+                "CREATE SCHEMA {0}; SET LOCAL search_path TO {0}",
+                schema
+            ))
+            .expect("Failed to create the test schema");
+        }
+        db::run_migrations(conn).expect("Failed to run migrations");
+    }
+
+    pub fn get_test_db_connection() -> DbConnection {
+        let mut conn = test_database()
+            .establish()
+            .unwrap_or_else(|_| panic!("Error connecting to the test database"));
+        migrate(&mut conn);
         conn
     }
+
+    /// A pool of one connection, so an in-memory database is the same
+    /// throughout
+    pub fn get_test_db_pool() -> DbPool {
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .build(test_database())
+            .unwrap();
+        migrate(&mut pool.get().unwrap());
+        pool
+    }
+
+    /// A feed that was never fetched, for tests that don't need one in the
+    /// database. Change what matters with `Feed { .., ..test_feed() }`.
+    pub fn test_feed() -> Feed {
+        Feed {
+            id: FeedId(1),
+            url: "https://example.com/feed.xml".to_string(),
+            feed_type: FeedType::Rss,
+            title: "Example".to_string(),
+            last_checked: 0,
+            last_updated: 0,
+            error_time: 0,
+            error_message: None,
+            description: None,
+            homepage: None,
+            link_mode: LinkMode::Auto,
+            poll_interval: 0,
+            body_hash: None,
+            error_kind: FeedErrorKind::None,
+            new_items: 0,
+            skipped_items: 0,
+            etag: None,
+            last_modified: None,
+            fetch_schedule: None,
+            parse_warnings: Default::default(),
+            credentials: None,
+            pruned_through: 0,
+            consecutive_failures: 0,
+            broken_at: 0,
+            paused_at: 0,
+        }
+    }
+
+    /// An item of `test_feed()` with no description, published and first
+    /// seen at 0. Change what matters with `FeedItem { .., ..test_item(1) }`.
+    pub fn test_item(id: i32) -> FeedItem {
+        FeedItem {
+            id,
+            feed_id: FeedId(1),
+            title: format!("Item {}", id),
+            link: format!("https://example.com/{}", id),
+            pub_date: 0,
+            description: None,
+            author: None,
+            comments_link: None,
+            first_seen: 0,
+            image_url: None,
+        }
+    }
+
+    /// An active user with id 1, sending at midnight UTC. Change what matters
+    /// with `User { .., ..test_user() }`.
+    pub fn test_user() -> User {
+        User {
+            id: UserId(1),
+            login_email: "me@example.com".to_string(),
+            send_email: "me@example.com".to_string(),
+            password: String::new(),
+            created_at: 0,
+            is_active: true,
+            daily_send_time: "00:00+00:00".to_string(),
+            role: Role::User.into(),
+            item_truncate_length: 200,
+            must_change_password: false,
+            from_name: None,
+            subject_template: None,
+            timezone: None,
+            last_login_at: None,
+            pending_approval: false,
+        }
+    }
 }